@@ -0,0 +1,181 @@
+//! Trade aggregation
+//!
+//! This module folds consecutive trades at the same price and side into a
+//! single synthetic `AggTrade`, the way Binance's aggTrade stream does, for
+//! exchanges that publish every fill. It keeps trade volume comparable
+//! across exchanges.
+
+use crate::exchange::{AggTrade, ExchangeType};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Check if an exchange's trade stream is already aggregated
+pub fn aggregates_trades(exchange: ExchangeType) -> bool {
+    matches!(exchange, ExchangeType::Binance)
+}
+
+/// Trades merged since the first one of the current window
+#[derive(Debug)]
+struct Pending {
+    trade: AggTrade,
+    since: Instant,
+}
+
+/// Per-symbol buffer that merges runs of same-price, same-side trades
+#[derive(Debug)]
+pub struct TradeAggregator {
+    window: Duration,
+    /// Windows replacing the default for individual symbols
+    symbol_windows: HashMap<String, Duration>,
+    pending: HashMap<(ExchangeType, String), Pending>,
+}
+
+impl TradeAggregator {
+    /// Create an aggregator merging trades up to `window` after the first of a run
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            symbol_windows: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Use a different window for one symbol; a zero window passes its trades through
+    pub fn with_symbol_window(mut self, symbol: &str, window: Duration) -> Self {
+        self.symbol_windows.insert(symbol.to_uppercase(), window);
+        self
+    }
+
+    /// Window applied to a symbol's trades
+    pub fn window_for(&self, symbol: &str) -> Duration {
+        self.symbol_windows.get(symbol).copied().unwrap_or(self.window)
+    }
+
+    /// Shortest non-zero window of any symbol
+    pub fn min_window(&self) -> Duration {
+        std::iter::once(self.window)
+            .chain(self.symbol_windows.values().copied())
+            .filter(|window| !window.is_zero())
+            .min()
+            .unwrap_or(self.window)
+    }
+
+    /// Number of symbols with a trade waiting
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Check if no trades are waiting
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Merge a trade into its symbol's pending run, returning a trade that is ready to publish.
+    ///
+    /// A trade at another price or side, or past the window, ends the run
+    /// and the finished aggregate is returned. Symbols with a zero window
+    /// get their trade back unchanged.
+    pub fn push(&mut self, trade: AggTrade, now: Instant) -> Option<AggTrade> {
+        let window = self.window_for(&trade.symbol);
+        if window.is_zero() {
+            return Some(trade);
+        }
+
+        let key = (trade.exchange, trade.symbol.clone());
+        if let Some(pending) = self.pending.get_mut(&key) {
+            let run = &mut pending.trade;
+            let within = trade.timestamp - run.timestamp < window.as_millis() as i64;
+            if within && trade.price == run.price && trade.is_buyer_maker == run.is_buyer_maker {
+                run.quantity += trade.quantity;
+                return None;
+            }
+        }
+        self.pending
+            .insert(key, Pending { trade, since: now })
+            .map(|finished| finished.trade)
+    }
+
+    /// Take the aggregates whose window has closed by `now`
+    pub fn take_due(&mut self, now: Instant) -> Vec<AggTrade> {
+        let due: Vec<_> = self.pending
+            .iter()
+            .filter(|(key, pending)| now.duration_since(pending.since) >= self.window_for(&key.1))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        due.into_iter()
+            .filter_map(|key| self.pending.remove(&key))
+            .map(|pending| pending.trade)
+            .collect()
+    }
+
+    /// Take every pending aggregate regardless of its window, e.g. on shutdown
+    pub fn take_all(&mut self) -> Vec<AggTrade> {
+        self.pending.drain().map(|(_, pending)| pending.trade).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn trade(price: f64, quantity: f64, is_buyer_maker: bool, timestamp: i64, trade_id: u64) -> AggTrade {
+        AggTrade {
+            price,
+            quantity,
+            timestamp,
+            is_buyer_maker,
+            received_at: timestamp + 5,
+            ..testing::trade(ExchangeType::Okx, trade_id)
+        }
+    }
+
+    #[test]
+    fn test_same_price_buys_collapse_into_one_trade() {
+        let window = Duration::from_millis(100);
+        let mut aggregator = TradeAggregator::new(window);
+        let start = Instant::now();
+
+        assert!(aggregator.push(trade(50000.0, 0.1, false, 1_000, 1), start).is_none());
+        assert!(aggregator.push(trade(50000.0, 0.2, false, 1_010, 2), start).is_none());
+        assert!(aggregator.push(trade(50000.0, 0.3, false, 1_020, 3), start).is_none());
+        assert!(aggregator.take_due(start + Duration::from_millis(50)).is_empty());
+
+        let emitted = aggregator.take_due(start + window);
+        assert_eq!(emitted.len(), 1);
+        let merged = &emitted[0];
+        assert!((merged.quantity - 0.6).abs() < 1e-9);
+        assert!(!merged.is_buyer_maker);
+        assert_eq!((merged.price, merged.timestamp, merged.trade_id), (50000.0, 1_000, 1));
+        assert!(aggregator.is_empty());
+    }
+
+    #[test]
+    fn test_new_price_or_side_ends_the_run() {
+        let mut aggregator = TradeAggregator::new(Duration::from_millis(100));
+        let now = Instant::now();
+
+        aggregator.push(trade(50000.0, 0.1, false, 1_000, 1), now);
+        let finished = aggregator.push(trade(50000.0, 0.2, true, 1_010, 2), now).unwrap();
+        assert_eq!(finished.trade_id, 1);
+        let finished = aggregator.push(trade(50001.0, 0.3, true, 1_020, 3), now).unwrap();
+        assert_eq!(finished.trade_id, 2);
+        // Past the window from the start of the run
+        let finished = aggregator.push(trade(50001.0, 0.4, true, 1_200, 4), now).unwrap();
+        assert_eq!(finished.trade_id, 3);
+        assert_eq!(aggregator.take_all().len(), 1);
+    }
+
+    #[test]
+    fn test_symbol_windows() {
+        let mut aggregator = TradeAggregator::new(Duration::from_millis(100))
+            .with_symbol_window("btcusdt", Duration::ZERO);
+        let now = Instant::now();
+
+        assert!(aggregator.push(trade(50000.0, 0.1, false, 1_000, 1), now).is_some());
+        assert!(aggregator.is_empty());
+        assert_eq!(aggregator.window_for("ETHUSDT"), Duration::from_millis(100));
+        assert_eq!(aggregator.min_window(), Duration::from_millis(100));
+    }
+}
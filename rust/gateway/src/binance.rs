@@ -1,111 +1,443 @@
-//! Binance Futures WebSocket implementation
+//! Binance WebSocket implementation
 //!
-//! This module handles WebSocket connections to Binance Futures
-//! and parses incoming market data.
+//! This module handles WebSocket connections to Binance spot, USDⓈ-M
+//! futures and COIN-M futures, and parses incoming market data. With an API
+//! key it also streams the account's order and balance updates on futures.
 
 use crate::exchange::{
     Exchange, ExchangeType, MarketEvent, AggTrade, Kline, DepthUpdate, BookTicker,
-    Subscription, DataType, KlineInterval,
+    FundingRate, Liquidation, Side, Ticker24h, Subscription, DataType, KlineInterval, ContractType,
+    DepthUpdateSpeed, RateLimiter, StaleWatchdog, ParseErrorMonitor, check_depth_levels, now_ms,
 };
+use crate::metrics;
+use crate::orderbook::{OrderBook, BINANCE_FUTURES_REST, BINANCE_FUTURES_TESTNET_REST, SNAPSHOT_LIMIT};
+use crate::proxy::{self, ProxyConfig};
+use crate::reconnect::ReconnectPolicy;
 use crate::redis_publisher::RedisPublisher;
-use anyhow::{Result, anyhow};
+use crate::symbol;
+use crate::user_data::{AccountUpdate, BalanceUpdate, OrderUpdate, PositionUpdate, UserDataEvent};
+use crate::clock::{self, SharedClock};
+use crate::error::{GatewayError, Result};
+pub use crate::exchange::MarketKind;
 use async_trait::async_trait;
 use futures_util::{SinkExt, StreamExt};
-use serde_json::Value;
-use tokio_tungstenite::{connect_async, tungstenite::Message, WebSocketStream};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time;
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
 use tracing::{debug, error, info, warn};
 use url::Url;
 
 /// Binance WebSocket endpoints
 pub const BINANCE_FUTURES_WS: &str = "wss://fstream.binance.com/ws";
 pub const BINANCE_FUTURES_TESTNET_WS: &str = "wss://stream.binancefuture.com/ws";
+pub const BINANCE_COIN_FUTURES_WS: &str = "wss://dstream.binance.com/ws";
+pub const BINANCE_COIN_FUTURES_TESTNET_WS: &str = "wss://dstream.binancefuture.com/ws";
+pub const BINANCE_SPOT_WS: &str = "wss://stream.binance.com:9443/ws";
+pub const BINANCE_SPOT_TESTNET_WS: &str = "wss://testnet.binance.vision/ws";
+
+/// Default number of streams per subscription batch
+pub const DEFAULT_SUBSCRIBE_BATCH_SIZE: usize = 50;
+
+/// Levels offered by the partial book depth streams
+pub const SUPPORTED_DEPTH_LEVELS: [u16; 3] = [5, 10, 20];
+
+/// Control frames sent per second by default (Binance drops connections sending over 10/s)
+pub const DEFAULT_MESSAGES_PER_SECOND: u32 = 5;
+
+/// Streams a single futures connection may listen to
+pub const MAX_STREAMS_PER_CONNECTION: usize = 200;
+
+/// Streams a single spot connection may listen to
+pub const MAX_SPOT_STREAMS_PER_CONNECTION: usize = 1024;
+
+/// A listen key expires 60 minutes after its last keepalive
+pub const LISTEN_KEY_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+impl MarketKind {
+    /// Raw-stream WebSocket endpoint
+    pub fn ws_url(self, testnet: bool) -> &'static str {
+        match (self, testnet) {
+            (MarketKind::Spot, false) => BINANCE_SPOT_WS,
+            (MarketKind::Spot, true) => BINANCE_SPOT_TESTNET_WS,
+            (MarketKind::UsdFutures, false) => BINANCE_FUTURES_WS,
+            (MarketKind::UsdFutures, true) => BINANCE_FUTURES_TESTNET_WS,
+            (MarketKind::CoinFutures, false) => BINANCE_COIN_FUTURES_WS,
+            (MarketKind::CoinFutures, true) => BINANCE_COIN_FUTURES_TESTNET_WS,
+        }
+    }
+
+    /// Streams one connection may listen to
+    pub fn max_streams(self) -> usize {
+        match self {
+            MarketKind::Spot => MAX_SPOT_STREAMS_PER_CONNECTION,
+            MarketKind::UsdFutures | MarketKind::CoinFutures => MAX_STREAMS_PER_CONNECTION,
+        }
+    }
+}
 
 /// Binance-specific WebSocket client
 pub struct BinanceClient {
     exchange_type: ExchangeType,
+    market: MarketKind,
+    testnet: bool,
     ws_url: String,
     ws: Option<WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>>,
-    symbols: Vec<String>,
+    /// Active subscriptions, re-sent after a reconnect
+    subscriptions: Vec<Subscription>,
     redis_publisher: Option<RedisPublisher>,
     connected: bool,
+    subscribe_batch_size: usize,
+    /// Paces subscribe and unsubscribe frames
+    rate_limiter: RateLimiter,
+    /// Drops the connection once it goes silent
+    watchdog: StaleWatchdog,
+    /// Flags a connection whose frames mostly fail to parse
+    parse_errors: ParseErrorMonitor,
+    /// Publish partial book snapshots as best bid/ask tickers
+    derive_book_ticker: bool,
+    /// Stamps `received_at` on parsed events
+    clock: SharedClock,
+    next_request_id: u64,
+    /// Control requests awaiting their `{"result":null,"id":N}` ack, by id
+    pending_requests: HashMap<u64, String>,
+    rest_url: String,
+    /// Levels emitted from reconstructed books; `None` publishes raw diffs
+    order_book_depth: Option<usize>,
+    order_books: HashMap<String, OrderBook>,
+    /// Last aggregate trade id seen per symbol
+    last_trade_ids: HashMap<String, u64>,
+    /// Sequence gaps detected per symbol
+    trade_gaps: HashMap<String, u64>,
+    /// Retries and timeout for each connect call
+    connect_policy: ReconnectPolicy,
+    /// Proxy the WebSocket is tunneled through, if any
+    proxy: Option<ProxyConfig>,
+    /// API key, needed for the user data stream
+    api_key: Option<String>,
 }
 
 impl BinanceClient {
     /// Create a new Binance client
     pub fn new(testnet: bool) -> Self {
-        let exchange_type = if testnet {
-            ExchangeType::Binance // Still Binance, just testnet
-        } else {
-            ExchangeType::Binance
-        };
-
         let ws_url = if testnet {
             BINANCE_FUTURES_TESTNET_WS.to_string()
         } else {
             BINANCE_FUTURES_WS.to_string()
         };
 
+        let rest_url = if testnet {
+            BINANCE_FUTURES_TESTNET_REST.to_string()
+        } else {
+            BINANCE_FUTURES_REST.to_string()
+        };
+
         Self {
-            exchange_type,
+            // Still Binance on the testnet
+            exchange_type: ExchangeType::Binance,
+            market: MarketKind::UsdFutures,
+            testnet,
             ws_url,
             ws: None,
-            symbols: Vec::new(),
+            subscriptions: Vec::new(),
             redis_publisher: None,
             connected: false,
+            subscribe_batch_size: DEFAULT_SUBSCRIBE_BATCH_SIZE,
+            rate_limiter: RateLimiter::new(DEFAULT_MESSAGES_PER_SECOND, 1),
+            watchdog: StaleWatchdog::default(),
+            parse_errors: ParseErrorMonitor::default(),
+            derive_book_ticker: false,
+            clock: clock::system(),
+            next_request_id: 1,
+            pending_requests: HashMap::new(),
+            rest_url,
+            order_book_depth: None,
+            order_books: HashMap::new(),
+            last_trade_ids: HashMap::new(),
+            trade_gaps: HashMap::new(),
+            connect_policy: ReconnectPolicy::default(),
+            proxy: None,
+            api_key: None,
         }
     }
 
+    /// Stream from another Binance market, switching to its endpoints
+    pub fn with_market(mut self, market: MarketKind) -> Self {
+        self.market = market;
+        self.ws_url = market.ws_url(self.testnet).to_string();
+        self.rest_url = market.rest_url(self.testnet).to_string();
+        self
+    }
+
+    /// Connect to `url` instead of the market's WebSocket endpoint, e.g. a regional host or a proxy.
+    ///
+    /// `with_market` resets the endpoint, so call this after it.
+    pub fn with_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.ws_url = url.into();
+        self
+    }
+
+    /// Tunnel the WebSocket through an HTTP CONNECT or SOCKS5 proxy
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Authenticate with an API key, enabling the user data stream.
+    ///
+    /// Listen key requests only carry the key, so no secret is needed.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Stream of the account's order and balance updates, sharing this client's endpoints and proxy
+    pub fn user_data_stream(&self) -> Result<UserDataStream> {
+        let api_key = self.api_key.as_ref()
+            .ok_or_else(|| GatewayError::Config("The Binance user data stream needs an API key".to_string()))?;
+        let listen_key_path = match self.market {
+            MarketKind::UsdFutures => "/fapi/v1/listenKey",
+            MarketKind::CoinFutures => "/dapi/v1/listenKey",
+            MarketKind::Spot => {
+                return Err(GatewayError::Config("The Binance user data stream is only supported on futures".to_string()))
+            }
+        };
+
+        Ok(UserDataStream {
+            exchange_type: self.exchange_type,
+            listen_key_url: format!("{}{}", self.rest_url, listen_key_path),
+            ws_url: self.ws_url.clone(),
+            api_key: api_key.clone(),
+            http: reqwest::Client::new(),
+            proxy: self.proxy.clone(),
+            connect_policy: self.connect_policy.clone(),
+            reconnect_policy: ReconnectPolicy::default(),
+            redis_publisher: None,
+            clock: self.clock.clone(),
+        })
+    }
+
+    /// Market the client streams from
+    pub fn market(&self) -> MarketKind {
+        self.market
+    }
+
+    /// Maintain local order books and emit the top `depth` levels instead of raw diffs
+    pub fn with_order_book(mut self, depth: usize) -> Self {
+        self.order_book_depth = Some(depth.max(1));
+        self
+    }
+
     /// Set the Redis publisher for forwarding events
     pub fn with_redis_publisher(mut self, publisher: RedisPublisher) -> Self {
         self.redis_publisher = Some(publisher);
         self
     }
 
-    /// Build combined stream URL for multiple subscriptions
-    fn build_stream_url(&self, subscriptions: &[Subscription]) -> Result<String> {
-        if subscriptions.is_empty() {
-            return Ok(format!("{}/{}", self.ws_url, ""));
+    /// Set the retries and timeout applied to each connect call
+    pub fn with_connect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.connect_policy = policy;
+        self
+    }
+
+    /// Set how many streams are sent per subscription batch
+    pub fn with_subscribe_batch_size(mut self, batch_size: usize) -> Self {
+        self.subscribe_batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set how many control frames may be sent per second
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limiter = RateLimiter::new(per_second, 1);
+        self
+    }
+
+    /// Treat the connection as dead after this long without a frame (zero disables)
+    pub fn with_stale_timeout(mut self, timeout: Duration) -> Self {
+        self.watchdog = StaleWatchdog::new(timeout);
+        self
+    }
+
+    /// Alarm on, and optionally reconnect after, a storm of unparseable frames
+    pub fn with_parse_error_monitor(mut self, monitor: ParseErrorMonitor) -> Self {
+        self.parse_errors = monitor;
+        self
+    }
+
+    /// Turn partial book snapshots into best bid/ask tickers before anything sees them
+    pub fn with_derived_book_ticker(mut self, enabled: bool) -> Self {
+        self.derive_book_ticker = enabled;
+        self
+    }
+
+    /// Stamp events with this clock instead of the host's, e.g. a mock in tests
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Get the stream name for a subscription, or `None` if the market has no WebSocket stream for it
+    fn stream_name(&self, sub: &Subscription) -> Option<String> {
+        let symbol_lower = symbol::to_exchange_symbol(ExchangeType::Binance, &sub.symbol).to_lowercase();
+        let stream = match sub.data_type {
+            DataType::AggTrade => {
+                format!("{}@aggTrade", symbol_lower)
+            }
+            DataType::Kline => {
+                let interval = sub.interval.unwrap_or(KlineInterval::OneMinute).as_str();
+                format!("{}@kline_{}", symbol_lower, interval)
+            }
+            DataType::Depth => {
+                let speed = match (self.market, sub.update_speed.unwrap_or_default()) {
+                    // Spot pushes every 100ms or, unsuffixed, every 1000ms; there is no 500ms
+                    (MarketKind::Spot, DepthUpdateSpeed::Ms500) => String::new(),
+                    (_, speed) => format!("@{}", speed.as_str()),
+                };
+                match sub.depth_levels {
+                    Some(levels) => format!("{}@depth{}{}", symbol_lower, levels, speed),
+                    None => format!("{}@depth{}", symbol_lower, speed),
+                }
+            }
+            DataType::BookTicker => {
+                format!("{}@bookTicker", symbol_lower)
+            }
+            // Spot has no contracts, funding or liquidation streams
+            DataType::ContinuousKline | DataType::FundingRate | DataType::Liquidation if self.market == MarketKind::Spot => {
+                return None;
+            }
+            DataType::ContinuousKline => {
+                let contract_type = sub.contract_type.unwrap_or(ContractType::Perpetual).as_str();
+                let interval = sub.interval.unwrap_or(KlineInterval::OneMinute).as_str();
+                format!("{}_{}@continuousKline_{}", symbol_lower, contract_type.to_lowercase(), interval)
+            }
+            DataType::FundingRate => {
+                format!("{}@markPrice", symbol_lower)
+            }
+            DataType::Liquidation => {
+                format!("{}@forceOrder", symbol_lower)
+            }
+            DataType::Ticker24h => {
+                format!("{}@ticker", symbol_lower)
+            }
+            // Polled over REST
+            DataType::OpenInterest => return None,
+            // Derived by the gateway from book tickers and the NBBO
+            DataType::Nbbo | DataType::Opportunity => return None,
+        };
+        Some(stream)
+    }
+
+    /// Fail if adding `subscriptions` would take the connection past its stream cap
+    fn check_stream_limit(&self, subscriptions: &[Subscription]) -> Result<()> {
+        let mut streams: Vec<String> = Vec::new();
+        for stream in self.subscriptions.iter().chain(subscriptions).filter_map(|sub| self.stream_name(sub)) {
+            if !streams.contains(&stream) {
+                streams.push(stream);
+            }
         }
 
-        let mut streams = Vec::new();
+        if streams.len() > self.market.max_streams() {
+            return Err(GatewayError::Subscription(format!(
+                "Subscribing would need {} Binance streams, more than the {} allowed per connection; track fewer symbols",
+                streams.len(),
+                self.market.max_streams()
+            )));
+        }
+        Ok(())
+    }
 
+    /// Record subscriptions as active, returning the ones not already tracked
+    fn track_subscriptions(&mut self, subscriptions: Vec<Subscription>) -> Vec<Subscription> {
+        let mut added = Vec::new();
         for sub in subscriptions {
-            let symbol_lower = sub.symbol.to_lowercase();
-            let stream = match sub.data_type {
-                DataType::AggTrade => {
-                    format!("{}@aggTrade", symbol_lower)
-                }
-                DataType::Kline => {
-                    let interval = sub.interval.unwrap_or(KlineInterval::OneMinute).as_str();
-                    format!("{}@kline_{}", symbol_lower, interval)
-                }
-                DataType::Depth => {
-                    format!("{}@depth@100ms", symbol_lower)
-                }
-                DataType::BookTicker => {
-                    format!("{}@bookTicker", symbol_lower)
-                }
-            };
-            streams.push(stream);
+            if !self.subscriptions.contains(&sub) && !added.contains(&sub) {
+                added.push(sub);
+            }
+        }
+        self.subscriptions.extend(added.iter().cloned());
+        added
+    }
+
+    /// Stop tracking subscriptions, returning the ones that were active
+    fn untrack_subscriptions(&mut self, subscriptions: &[Subscription]) -> Vec<Subscription> {
+        let removed: Vec<Subscription> = self.subscriptions.iter()
+            .filter(|sub| subscriptions.contains(sub))
+            .cloned()
+            .collect();
+        self.subscriptions.retain(|sub| !subscriptions.contains(sub));
+        removed
+    }
+
+    /// Build SUBSCRIBE or UNSUBSCRIBE control messages, one per batch of streams,
+    /// registering each request id so its ack can be matched up
+    fn build_control_msgs(&mut self, method: &str, subscriptions: &[Subscription]) -> Vec<Value> {
+        let mut streams: Vec<String> = Vec::new();
+        for stream in subscriptions.iter().filter_map(|sub| self.stream_name(sub)) {
+            if !streams.contains(&stream) {
+                streams.push(stream);
+            }
         }
 
-        // Combine streams: /stream1/stream2/stream3
-        let combined = streams.join("/");
-        Ok(format!("{}/{}", self.ws_url, combined))
+        streams.chunks(self.subscribe_batch_size)
+            .map(|params| {
+                let id = self.next_request_id;
+                self.next_request_id += 1;
+                self.pending_requests.insert(id, method.to_string());
+
+                json!({ "method": method, "params": params, "id": id })
+            })
+            .collect()
+    }
+
+    /// Send control messages over the open connection, paced to the message rate limit
+    async fn send_control_msgs(&mut self, msgs: Vec<Value>) -> Result<()> {
+        let ws = self.ws.as_mut().ok_or_else(|| GatewayError::Disconnected("Not connected".to_string()))?;
+
+        for msg in msgs {
+            self.rate_limiter.acquire().await;
+            ws.send(Message::Text(msg.to_string())).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Match a `{"result":null,"id":N}` (or error) response to its control request.
+    /// Returns false if the frame is not a control response.
+    fn handle_control_response(&mut self, msg: &str) -> bool {
+        let Ok(data) = serde_json::from_str::<Value>(msg) else {
+            return false;
+        };
+        let Some(id) = data.get("id").and_then(|id| id.as_u64()) else {
+            return false;
+        };
+        let method = self.pending_requests.remove(&id);
+        let method = method.as_deref().unwrap_or("unknown");
+
+        match data.get("error") {
+            Some(error) => error!("Binance {} request {} failed: {}", method, id, error),
+            None => debug!("Binance {} request {} acknowledged", method, id),
+        }
+        true
     }
 
     /// Parse aggregated trade event from Binance WebSocket message
     fn parse_agg_trade(&self, data: &Value) -> Result<MarketEvent> {
-        let price = data["p"].as_str().ok_or_else(|| anyhow!("Missing price"))?
+        let price = data["p"].as_str().ok_or_else(|| GatewayError::Parse("Missing price".to_string()))?
             .parse::<f64>()?;
-        let quantity = data["q"].as_str().ok_or_else(|| anyhow!("Missing quantity"))?
+        let quantity = data["q"].as_str().ok_or_else(|| GatewayError::Parse("Missing quantity".to_string()))?
             .parse::<f64>()?;
-        let symbol = data["s"].as_str().ok_or_else(|| anyhow!("Missing symbol"))?
+        let symbol = data["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
             .to_string();
-        let is_buyer_maker = data["m"].as_bool().ok_or_else(|| anyhow!("Missing is_buyer_maker"))?;
-        let trade_id = data["a"].as_u64().ok_or_else(|| anyhow!("Missing trade ID"))?;
-        let timestamp = data["T"].as_i64().ok_or_else(|| anyhow!("Missing trade time"))?
-            .unwrap_or_else(|| data["E"].as_i64().unwrap_or(0));
+        let is_buyer_maker = data["m"].as_bool().ok_or_else(|| GatewayError::Parse("Missing is_buyer_maker".to_string()))?;
+        let trade_id = data["a"].as_u64().ok_or_else(|| GatewayError::Parse("Missing trade ID".to_string()))?;
+        // Prefer trade time, fall back to event time
+        let timestamp = data["T"].as_i64()
+            .or_else(|| data["E"].as_i64())
+            .unwrap_or(0);
 
         Ok(MarketEvent::AggTrade(AggTrade {
             exchange: self.exchange_type,
@@ -115,30 +447,52 @@ impl BinanceClient {
             timestamp,
             is_buyer_maker,
             trade_id,
+            received_at: self.clock.now_ms(),
         }))
     }
 
     /// Parse kline event from Binance WebSocket message
     fn parse_kline(&self, data: &Value) -> Result<MarketEvent> {
-        let k = data.get("k").ok_or_else(|| anyhow!("Missing kline data"))?;
+        let symbol = data["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
+            .to_string();
 
-        let symbol = data["s"].as_str().ok_or_else(|| anyhow!("Missing symbol"))?
+        self.build_kline(symbol, data, None)
+    }
+
+    /// Parse continuous-contract kline event from Binance WebSocket message
+    fn parse_continuous_kline(&self, data: &Value) -> Result<MarketEvent> {
+        let symbol = data["ps"].as_str().ok_or_else(|| GatewayError::Parse("Missing pair".to_string()))?
             .to_string();
-        let interval = k["i"].as_str().ok_or_else(|| anyhow!("Missing interval"))?
+        let contract_type = data["ct"].as_str().ok_or_else(|| GatewayError::Parse("Missing contract type".to_string()))?
+            .parse::<ContractType>()?;
+
+        self.build_kline(symbol, data, Some(contract_type))
+    }
+
+    /// Build a Kline from the `k` object shared by kline and continuous kline events
+    fn build_kline(
+        &self,
+        symbol: String,
+        data: &Value,
+        contract_type: Option<ContractType>,
+    ) -> Result<MarketEvent> {
+        let k = data.get("k").ok_or_else(|| GatewayError::Parse("Missing kline data".to_string()))?;
+
+        let interval = k["i"].as_str().ok_or_else(|| GatewayError::Parse("Missing interval".to_string()))?
             .to_string();
-        let open_time = k["t"].as_i64().ok_or_else(|| anyhow!("Missing open time"))?;
-        let close_time = k["T"].as_i64().ok_or_else(|| anyhow!("Missing close time"))?;
-        let open = k["o"].as_str().ok_or_else(|| anyhow!("Missing open"))?
+        let open_time = k["t"].as_i64().ok_or_else(|| GatewayError::Parse("Missing open time".to_string()))?;
+        let close_time = k["T"].as_i64().ok_or_else(|| GatewayError::Parse("Missing close time".to_string()))?;
+        let open = k["o"].as_str().ok_or_else(|| GatewayError::Parse("Missing open".to_string()))?
             .parse::<f64>()?;
-        let high = k["h"].as_str().ok_or_else(|| anyhow!("Missing high"))?
+        let high = k["h"].as_str().ok_or_else(|| GatewayError::Parse("Missing high".to_string()))?
             .parse::<f64>()?;
-        let low = k["l"].as_str().ok_or_else(|| anyhow!("Missing low"))?
+        let low = k["l"].as_str().ok_or_else(|| GatewayError::Parse("Missing low".to_string()))?
             .parse::<f64>()?;
-        let close = k["c"].as_str().ok_or_else(|| anyhow!("Missing close"))?
+        let close = k["c"].as_str().ok_or_else(|| GatewayError::Parse("Missing close".to_string()))?
             .parse::<f64>()?;
-        let volume = k["v"].as_str().ok_or_else(|| anyhow!("Missing volume"))?
+        let volume = k["v"].as_str().ok_or_else(|| GatewayError::Parse("Missing volume".to_string()))?
             .parse::<f64>()?;
-        let is_closed = k["x"].as_bool().ok_or_else(|| anyhow!("Missing is_closed"))?;
+        let is_closed = k["x"].as_bool().ok_or_else(|| GatewayError::Parse("Missing is_closed".to_string()))?;
 
         Ok(MarketEvent::Kline(Kline {
             exchange: self.exchange_type,
@@ -152,14 +506,23 @@ impl BinanceClient {
             close,
             volume,
             is_closed,
+            contract_type,
+            received_at: self.clock.now_ms(),
         }))
     }
 
     /// Parse depth update event from Binance WebSocket message
     fn parse_depth_update(&self, data: &Value) -> Result<MarketEvent> {
-        let symbol = data["s"].as_str().ok_or_else(|| anyhow!("Missing symbol"))?
+        let symbol = data["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
             .to_string();
-        let timestamp = data["E"].as_i64().ok_or_else(|| anyhow!("Missing event time"))?;
+        let timestamp = data["E"].as_i64().ok_or_else(|| GatewayError::Parse("Missing event time".to_string()))?;
+        let first_update_id = data["U"].as_u64();
+        let final_update_id = data["u"].as_u64();
+        let prev_final_update_id = data["pu"].as_u64();
+        // Partial book streams share the diff payload but always carry the whole top of book
+        let is_snapshot = self.subscriptions.iter().any(|sub| {
+            sub.data_type == DataType::Depth && sub.depth_levels.is_some() && sub.symbol == symbol
+        });
 
         let mut bids = Vec::new();
         if let Some(b) = data.get("b") {
@@ -201,24 +564,29 @@ impl BinanceClient {
             bids,
             asks,
             timestamp,
+            is_snapshot,
+            first_update_id,
+            final_update_id,
+            prev_final_update_id,
+            received_at: self.clock.now_ms(),
         }))
     }
 
     /// Parse book ticker event from Binance WebSocket message
     fn parse_book_ticker(&self, data: &Value) -> Result<MarketEvent> {
-        let symbol = data["s"].as_str().ok_or_else(|| anyhow!("Missing symbol"))?
+        let symbol = data["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
             .to_string();
-        let bid_price = data["b"].as_str().ok_or_else(|| anyhow!("Missing bid price"))?
+        let bid_price = data["b"].as_str().ok_or_else(|| GatewayError::Parse("Missing bid price".to_string()))?
             .parse::<f64>()?;
-        let bid_qty = data["B"].as_str().ok_or_else(|| anyhow!("Missing bid qty"))?
+        let bid_qty = data["B"].as_str().ok_or_else(|| GatewayError::Parse("Missing bid qty".to_string()))?
             .parse::<f64>()?;
-        let ask_price = data["a"].as_str().ok_or_else(|| anyhow!("Missing ask price"))?
+        let ask_price = data["a"].as_str().ok_or_else(|| GatewayError::Parse("Missing ask price".to_string()))?
             .parse::<f64>()?;
-        let ask_qty = data["A"].as_str().ok_or_else(|| anyhow!("Missing ask qty"))?
+        let ask_qty = data["A"].as_str().ok_or_else(|| GatewayError::Parse("Missing ask qty".to_string()))?
             .parse::<f64>()?;
         let timestamp = data.get("E")
             .and_then(|e| e.as_i64())
-            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+            .unwrap_or_else(now_ms);
 
         Ok(MarketEvent::BookTicker(BookTicker {
             exchange: self.exchange_type,
@@ -228,25 +596,245 @@ impl BinanceClient {
             ask_price,
             ask_qty,
             timestamp,
+            received_at: self.clock.now_ms(),
         }))
     }
 
+    /// Parse rolling 24h statistics from a 24hrTicker event
+    fn parse_ticker_24h(&self, data: &Value) -> Result<MarketEvent> {
+        let field = |key: &str, name: &str| -> Result<f64> {
+            Ok(data[key].as_str().ok_or_else(|| GatewayError::Parse(format!("Missing {}", name)))?
+                .parse::<f64>()?)
+        };
+        let symbol = data["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
+            .to_string();
+        let timestamp = data.get("E")
+            .and_then(|e| e.as_i64())
+            .unwrap_or_else(now_ms);
+
+        Ok(MarketEvent::Ticker24h(Ticker24h {
+            exchange: self.exchange_type,
+            symbol,
+            last_price: field("c", "last price")?,
+            open_price: field("o", "open price")?,
+            high_price: field("h", "high price")?,
+            low_price: field("l", "low price")?,
+            volume: field("v", "volume")?,
+            quote_volume: field("q", "quote volume")?,
+            price_change: field("p", "price change")?,
+            price_change_percent: field("P", "price change percent")?,
+            timestamp,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse funding rate from a markPriceUpdate event
+    fn parse_mark_price(&self, data: &Value) -> Result<MarketEvent> {
+        let symbol = data["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
+            .to_string();
+        let funding_rate = data["r"].as_str().ok_or_else(|| GatewayError::Parse("Missing funding rate".to_string()))?
+            .parse::<f64>()?;
+        let next_funding_time = data["T"].as_i64().ok_or_else(|| GatewayError::Parse("Missing next funding time".to_string()))?;
+        let timestamp = data["E"].as_i64().ok_or_else(|| GatewayError::Parse("Missing event time".to_string()))?;
+
+        Ok(MarketEvent::FundingRate(FundingRate {
+            exchange: self.exchange_type,
+            symbol,
+            funding_rate,
+            next_funding_time,
+            timestamp,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse liquidation from a forceOrder event (order fields are nested under `o`)
+    fn parse_force_order(&self, data: &Value) -> Result<MarketEvent> {
+        let order = data.get("o").ok_or_else(|| GatewayError::Parse("Missing order".to_string()))?;
+
+        let symbol = order["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
+            .to_string();
+        let side = order["S"].as_str().ok_or_else(|| GatewayError::Parse("Missing side".to_string()))?
+            .parse::<Side>()?;
+        // Average fill price and filled quantity, falling back to the order's own
+        let price = order["ap"].as_str()
+            .or_else(|| order["p"].as_str())
+            .ok_or_else(|| GatewayError::Parse("Missing price".to_string()))?
+            .parse::<f64>()?;
+        let quantity = order["z"].as_str()
+            .or_else(|| order["q"].as_str())
+            .ok_or_else(|| GatewayError::Parse("Missing quantity".to_string()))?
+            .parse::<f64>()?;
+        let timestamp = order["T"].as_i64()
+            .or_else(|| data["E"].as_i64())
+            .unwrap_or(0);
+
+        Ok(MarketEvent::Liquidation(Liquidation {
+            exchange: self.exchange_type,
+            symbol,
+            side,
+            price,
+            quantity,
+            timestamp,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Apply a depth diff to the symbol's local book and return its top levels
+    async fn update_order_book(&mut self, update: DepthUpdate, depth: usize) -> Result<Option<MarketEvent>> {
+        if !self.order_books.contains_key(&update.symbol) {
+            let book = OrderBook::fetch_snapshot(&self.depth_url(), &update.symbol, SNAPSHOT_LIMIT).await?;
+            self.order_books.insert(update.symbol.clone(), book);
+        }
+
+        let book = self.order_books.get_mut(&update.symbol).unwrap();
+        match book.apply_update(&update) {
+            Ok(true) => Ok(Some(MarketEvent::DepthUpdate(
+                book.to_depth_update(self.exchange_type, depth, update.timestamp, self.clock.now_ms()),
+            ))),
+            Ok(false) => Ok(None),
+            Err(e) => {
+                // Rebuild from a fresh snapshot on the next diff
+                warn!("{} order book out of sync, resnapshotting: {}", update.symbol, e);
+                self.order_books.remove(&update.symbol);
+                Ok(None)
+            }
+        }
+    }
+
+    /// REST depth snapshot endpoint for the client's market
+    fn depth_url(&self) -> String {
+        format!("{}{}", self.rest_url, self.market.depth_path())
+    }
+
+    /// Parse a REST depth response into a snapshot of its top `limit` levels
+    fn parse_depth_snapshot(&self, symbol: &str, data: &Value, limit: u16) -> Result<DepthUpdate> {
+        let book = OrderBook::from_snapshot_json(symbol, data)?;
+        let received_at = self.clock.now_ms();
+        // Spot snapshots carry no event time, so they are dated when they arrived
+        let timestamp = data["E"].as_i64().unwrap_or(received_at);
+        Ok(book.to_depth_update(self.exchange_type, limit as usize, timestamp, received_at))
+    }
+
+    /// Number of agg trade sequence gaps detected for a symbol
+    pub fn gap_count(&self, symbol: &str) -> u64 {
+        self.trade_gaps.get(symbol).copied().unwrap_or(0)
+    }
+
+    /// Sequence gaps detected so far, by symbol
+    pub fn gap_counts(&self) -> &HashMap<String, u64> {
+        &self.trade_gaps
+    }
+
+    /// Check an agg trade id against the last one for its symbol, returning
+    /// how many trades were skipped if it isn't the next in sequence
+    fn track_trade_id(&mut self, symbol: &str, trade_id: u64) -> Option<u64> {
+        let last = match self.last_trade_ids.get(symbol) {
+            // Replays and out-of-order ids don't move the sequence back
+            Some(&last) if trade_id <= last => return None,
+            last => last.copied(),
+        };
+        self.last_trade_ids.insert(symbol.to_string(), trade_id);
+
+        // The first trade for a symbol has nothing to compare against
+        let missed = trade_id - last? - 1;
+        if missed == 0 {
+            return None;
+        }
+
+        *self.trade_gaps.entry(symbol.to_string()).or_insert(0) += 1;
+        metrics::global().record_trade_gap(self.exchange_type);
+        warn!(
+            "{} agg trade gap: {} trades missing between ids {} and {}",
+            symbol, missed, trade_id - missed - 1, trade_id
+        );
+        Some(missed)
+    }
+
+    /// Parse a text frame and forward the resulting event
+    async fn handle_text(&mut self, text: &str) -> Result<Option<MarketEvent>> {
+        // Raw frames are a debugging aid, only queued so they never hold up parsing
+        if let Some(ref publisher) = self.redis_publisher {
+            publisher.publish_raw(self.exchange_type, text);
+        }
+
+        let event = match self.parse_message(text) {
+            Ok(event) => {
+                self.parse_errors.record_success();
+                event
+            }
+            Err(e) => {
+                // Control responses carry no event type, so they land here
+                if !self.handle_control_response(text) {
+                    debug!("Failed to parse message: {}", e);
+                    metrics::global().record_parse_error(self.exchange_type);
+                    if self.parse_errors.record_failure(self.exchange_type) {
+                        self.connected = false;
+                    }
+                }
+                return Ok(None);
+            }
+        };
+
+        let event = match (event, self.order_book_depth) {
+            (MarketEvent::DepthUpdate(update), Some(depth)) if !update.is_snapshot => {
+                match self.update_order_book(update, depth).await {
+                    Ok(Some(event)) => event,
+                    Ok(None) => return Ok(None),
+                    Err(e) => {
+                        error!("Failed to fetch order book snapshot: {}", e);
+                        return Ok(None);
+                    }
+                }
+            }
+            (event, _) => event,
+        };
+        let event = if self.derive_book_ticker { event.derive_book_ticker() } else { event };
+
+        if let MarketEvent::AggTrade(ref trade) = event {
+            self.track_trade_id(&trade.symbol, trade.trade_id);
+        }
+
+        // Forward to Redis if configured
+        if let Some(ref mut publisher) = self.redis_publisher {
+            if let Err(e) = publisher.publish_event(&event).await {
+                error!("Failed to publish event to Redis: {}", e);
+            }
+        }
+        Ok(Some(event))
+    }
+
     /// Parse incoming message into a MarketEvent
     fn parse_message(&self, msg: &str) -> Result<MarketEvent> {
-        let data: Value = serde_json::from_str(msg)?;
+        let mut data: Value = serde_json::from_str(msg)?;
 
-        let event_type = data.get("e")
-            .and_then(|e| e.as_str())
-            .ok_or_else(|| anyhow!("Missing event type"))?;
+        // The combined endpoint (`/stream?streams=...`) wraps each payload as {"stream":..,"data":{..}}
+        if data.get("stream").is_some() {
+            data = data.get_mut("data")
+                .map(Value::take)
+                .ok_or_else(|| GatewayError::Parse("Combined stream message without data".to_string()))?;
+        }
+
+        let event_type = Self::event_type(&data).ok_or_else(|| GatewayError::Parse("Missing event type".to_string()))?;
 
         match event_type {
             "aggTrade" => self.parse_agg_trade(&data),
             "kline" => self.parse_kline(&data),
+            "continuous_kline" => self.parse_continuous_kline(&data),
             "depthUpdate" => self.parse_depth_update(&data),
             "bookTicker" => self.parse_book_ticker(&data),
-            _ => Err(anyhow!("Unknown event type: {}", event_type)),
+            "24hrTicker" => self.parse_ticker_24h(&data),
+            "markPriceUpdate" => self.parse_mark_price(&data),
+            "forceOrder" => self.parse_force_order(&data),
+            _ => Err(GatewayError::Parse(format!("Unknown event type: {}", event_type))),
         }
     }
+
+    /// Event type of a payload; book ticker frames without an `e` field are recognised by shape
+    fn event_type(data: &Value) -> Option<&str> {
+        data.get("e").and_then(|e| e.as_str()).or_else(|| {
+            ["u", "b", "a"].iter().all(|key| data.get(key).is_some()).then_some("bookTicker")
+        })
+    }
 }
 
 #[async_trait]
@@ -256,15 +844,38 @@ impl Exchange for BinanceClient {
     }
 
     async fn connect(&mut self) -> Result<()> {
-        info!("Connecting to Binance Futures WebSocket at {}", self.ws_url);
+        info!("Connecting to Binance {} WebSocket at {}", self.market, self.ws_url);
 
         let url = Url::parse(&self.ws_url)?;
-        let (ws_stream, _) = connect_async(url).await?;
+        let proxy = self.proxy.clone();
+        let (ws_stream, _) = self.connect_policy
+            .retry_connect("Binance", || proxy::connect_async(url.clone(), proxy.clone()))
+            .await?;
 
         self.ws = Some(ws_stream);
         self.connected = true;
+        self.watchdog.touch();
+        // Requests sent on the old connection will never be acknowledged
+        self.pending_requests.clear();
+
+        info!("Connected to Binance {} WebSocket", self.market);
+
+        Ok(())
+    }
+
+    fn active_subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.clone()
+    }
+
+    async fn resubscribe(&mut self) -> Result<()> {
+        // Restore streams that were active before a reconnect
+        if !self.subscriptions.is_empty() {
+            info!("Restoring {} Binance subscriptions", self.subscriptions.len());
+            let subscriptions = self.subscriptions.clone();
+            let msgs = self.build_control_msgs("SUBSCRIBE", &subscriptions);
+            self.send_control_msgs(msgs).await?;
+        }
 
-        info!("Connected to Binance Futures WebSocket");
         Ok(())
     }
 
@@ -278,38 +889,49 @@ impl Exchange for BinanceClient {
     }
 
     async fn subscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
-        // For Binance, we need to reconnect with new stream URL
-        // This is because Binance uses combined streams
         info!("Subscribing to {} data streams", subscriptions.len());
+        check_depth_levels(self.exchange_type, &subscriptions, &SUPPORTED_DEPTH_LEVELS)?;
+        self.check_stream_limit(&subscriptions)?;
 
-        if self.connected {
-            self.disconnect().await?;
+        if !self.connected {
+            self.connect().await?;
+            self.resubscribe().await?;
         }
 
-        let stream_url = self.build_stream_url(&subscriptions)?;
-        info!("Connecting to stream: {}", stream_url);
+        // Streams restored by `resubscribe` are already active
+        let added = self.track_subscriptions(subscriptions);
+        if added.is_empty() {
+            debug!("All requested Binance streams are already subscribed");
+            return Ok(());
+        }
 
-        let url = Url::parse(&stream_url)?;
-        let (ws_stream, _) = connect_async(url).await?;
+        // Streams are added to the live connection, leaving the others untouched
+        let msgs = self.build_control_msgs("SUBSCRIBE", &added);
+        self.send_control_msgs(msgs).await?;
 
-        self.ws = Some(ws_stream);
-        self.connected = true;
+        info!("Successfully subscribed to {} streams", added.len());
+        Ok(())
+    }
 
-        // Track symbols
-        for sub in &subscriptions {
-            if !self.symbols.contains(&sub.symbol) {
-                self.symbols.push(sub.symbol.clone());
-            }
+    async fn unsubscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        let removed = self.untrack_subscriptions(&subscriptions);
+        if removed.is_empty() {
+            debug!("No Binance streams to unsubscribe");
+            return Ok(());
         }
 
-        info!("Successfully subscribed to {} streams", subscriptions.len());
-        Ok(())
-    }
+        info!("Unsubscribing from {} Binance data streams", removed.len());
+
+        // A later resubscribe must start again from a snapshot
+        for sub in removed.iter().filter(|sub| sub.data_type == DataType::Depth) {
+            self.order_books.remove(&sub.symbol);
+        }
+
+        if self.connected {
+            let msgs = self.build_control_msgs("UNSUBSCRIBE", &removed);
+            self.send_control_msgs(msgs).await?;
+        }
 
-    async fn unsubscribe(&mut self, _subscriptions: Vec<Subscription>) -> Result<()> {
-        // For Binance, we'd need to reconnect with updated stream list
-        // This is a simplified implementation
-        warn!("Unsubscribe not fully implemented for Binance, requires reconnect");
         Ok(())
     }
 
@@ -320,24 +942,19 @@ impl Exchange for BinanceClient {
 
         let ws = self.ws.as_mut().unwrap();
 
-        match ws.next().await {
-            Some(Ok(Message::Text(text))) => {
-                match self.parse_message(&text) {
-                    Ok(event) => {
-                        // Forward to Redis if configured
-                        if let Some(ref mut publisher) = self.redis_publisher {
-                            if let Err(e) = publisher.publish_event(&event).await {
-                                error!("Failed to publish event to Redis: {}", e);
-                            }
-                        }
-                        Ok(Some(event))
-                    }
-                    Err(e) => {
-                        debug!("Failed to parse message: {}", e);
-                        Ok(None)
-                    }
-                }
+        let msg = tokio::select! {
+            msg = ws.next() => msg,
+            _ = self.watchdog.expired() => {
+                warn!("No Binance frame for {:?}, dropping the stale connection", self.watchdog.silence());
+                self.connected = false;
+                return Ok(None);
             }
+        };
+        // Any frame, pings included, shows the connection is alive
+        self.watchdog.touch();
+
+        match msg {
+            Some(Ok(Message::Text(text))) => self.handle_text(&text).await,
             Some(Ok(Message::Ping(payload))) => {
                 ws.send(Message::Pong(payload)).await?;
                 self.recv_event().await // Recursive call to get next message
@@ -363,17 +980,251 @@ impl Exchange for BinanceClient {
     }
 
     fn is_connected(&self) -> bool {
-        self.connected
+        self.connected && !self.watchdog.is_stale()
     }
 
     fn ws_endpoint(&self) -> &str {
         &self.ws_url
     }
+
+    async fn fetch_depth_snapshot(&self, symbol: &str, limit: u16) -> Result<DepthUpdate> {
+        let symbol = symbol.to_uppercase();
+        let data = OrderBook::fetch_snapshot_json(&self.depth_url(), &symbol, limit).await?;
+        self.parse_depth_snapshot(&symbol, &data, limit)
+    }
+}
+
+/// Futures user data stream: a listen key from REST, then a WebSocket at `/ws/<listenKey>`
+pub struct UserDataStream {
+    exchange_type: ExchangeType,
+    listen_key_url: String,
+    ws_url: String,
+    api_key: String,
+    http: reqwest::Client,
+    proxy: Option<ProxyConfig>,
+    /// Retries and timeout for each connect call
+    connect_policy: ReconnectPolicy,
+    /// Backoff between dropped connections
+    reconnect_policy: ReconnectPolicy,
+    redis_publisher: Option<RedisPublisher>,
+    /// Stamps `received_at`, shared with the client the stream came from
+    clock: SharedClock,
+}
+
+impl UserDataStream {
+    /// Set the backoff between dropped connections
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    /// Also publish each event to the Redis user data channel
+    pub fn with_redis_publisher(mut self, publisher: RedisPublisher) -> Self {
+        self.redis_publisher = Some(publisher);
+        self
+    }
+
+    /// Create a listen key, or get the active one
+    pub async fn create_listen_key(&self) -> Result<String> {
+        let data: Value = self.http.post(&self.listen_key_url)
+            .header("X-MBX-APIKEY", &self.api_key)
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+        data["listenKey"].as_str()
+            .map(str::to_string)
+            .ok_or_else(|| GatewayError::Parse("Missing listenKey".to_string()))
+    }
+
+    /// Extend the active listen key's validity by 60 minutes
+    pub async fn keepalive_listen_key(&self) -> Result<()> {
+        self.http.put(&self.listen_key_url)
+            .header("X-MBX-APIKEY", &self.api_key)
+            .send().await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Stream until shutdown or the receiving side is dropped, reconnecting with a fresh listen key
+    pub fn spawn(mut self, tx: mpsc::Sender<UserDataEvent>, mut shutdown: watch::Receiver<bool>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while !*shutdown.borrow() && !tx.is_closed() {
+                if let Err(e) = self.stream(&tx, &mut shutdown).await {
+                    warn!("Binance user data stream dropped: {}", e);
+                }
+                if *shutdown.borrow() || tx.is_closed() {
+                    break;
+                }
+
+                let delay = self.reconnect_policy.next_delay();
+                info!("Reconnecting Binance user data stream in {:?}", delay);
+                tokio::select! {
+                    _ = time::sleep(delay) => {}
+                    changed = shutdown.changed() => {
+                        // A dropped sender can never signal, so stop rather than spin
+                        if changed.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Run one connection until it drops, keeping its listen key alive
+    async fn stream(&mut self, tx: &mpsc::Sender<UserDataEvent>, shutdown: &mut watch::Receiver<bool>) -> Result<()> {
+        let listen_key = self.create_listen_key().await?;
+        let url = Url::parse(&format!("{}/{}", self.ws_url.trim_end_matches('/'), listen_key))?;
+        let proxy = self.proxy.clone();
+        let (mut ws, _) = self.connect_policy
+            .retry_connect("Binance user data", || proxy::connect_async(url.clone(), proxy.clone()))
+            .await?;
+        info!("Connected to Binance user data stream");
+        self.reconnect_policy.reset();
+
+        let mut keepalive = time::interval_at(time::Instant::now() + LISTEN_KEY_KEEPALIVE_INTERVAL, LISTEN_KEY_KEEPALIVE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => {
+                    ws.close(None).await?;
+                    return Ok(());
+                }
+                _ = keepalive.tick() => {
+                    // A missed keepalive is retried on the next tick, well before the key expires
+                    if let Err(e) = self.keepalive_listen_key().await {
+                        warn!("Failed to keep the Binance listen key alive: {}", e);
+                    }
+                }
+                msg = ws.next() => match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        let event = match parse_user_data(self.exchange_type, &text, self.clock.now_ms()) {
+                            Ok(event) => event,
+                            Err(GatewayError::Parse(e)) => {
+                                debug!("Failed to parse user data message: {}", e);
+                                continue;
+                            }
+                            Err(e) => return Err(e),
+                        };
+                        if let Some(ref publisher) = self.redis_publisher {
+                            if let Err(e) = publisher.publish_user_data(&event).await {
+                                error!("Failed to publish user data to Redis: {}", e);
+                            }
+                        }
+                        if tx.send(event).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                    Some(Ok(Message::Ping(payload))) => ws.send(Message::Pong(payload)).await?,
+                    Some(Ok(Message::Close(_))) | None => {
+                        return Err(GatewayError::Disconnected("Binance closed the user data stream".to_string()));
+                    }
+                    Some(Err(e)) => return Err(e.into()),
+                    _ => {}
+                },
+            }
+        }
+    }
+}
+
+/// Parse a user data frame received at `received_at`; an expired listen key is reported as a disconnect
+fn parse_user_data(exchange: ExchangeType, msg: &str, received_at: i64) -> Result<UserDataEvent> {
+    let data: Value = serde_json::from_str(msg)?;
+    let event_type = data["e"].as_str().ok_or_else(|| GatewayError::Parse("Missing event type".to_string()))?;
+
+    match event_type {
+        "ORDER_TRADE_UPDATE" => parse_order_update(exchange, &data, received_at),
+        "ACCOUNT_UPDATE" => parse_account_update(exchange, &data, received_at),
+        "listenKeyExpired" => Err(GatewayError::Disconnected("Binance listen key expired".to_string())),
+        _ => Err(GatewayError::Parse(format!("Unknown user data event type: {}", event_type))),
+    }
+}
+
+/// String field holding a number
+fn decimal(data: &Value, key: &str) -> Result<f64> {
+    data[key].as_str()
+        .ok_or_else(|| GatewayError::Parse(format!("Missing {}", key)))?
+        .parse::<f64>()
+        .map_err(|e| GatewayError::Parse(format!("Invalid {}: {}", key, e)))
+}
+
+/// String field, or an empty string if absent
+fn text(data: &Value, key: &str) -> String {
+    data[key].as_str().unwrap_or_default().to_string()
+}
+
+/// Parse an ORDER_TRADE_UPDATE event (order fields are nested under `o`)
+fn parse_order_update(exchange: ExchangeType, data: &Value, received_at: i64) -> Result<UserDataEvent> {
+    let order = data.get("o").ok_or_else(|| GatewayError::Parse("Missing order".to_string()))?;
+
+    Ok(UserDataEvent::OrderUpdate(OrderUpdate {
+        exchange,
+        symbol: order["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?.to_string(),
+        order_id: order["i"].as_u64().ok_or_else(|| GatewayError::Parse("Missing order id".to_string()))?,
+        client_order_id: text(order, "c"),
+        side: order["S"].as_str().ok_or_else(|| GatewayError::Parse("Missing side".to_string()))?.parse::<Side>()?,
+        order_type: text(order, "o"),
+        time_in_force: text(order, "f"),
+        price: decimal(order, "p")?,
+        quantity: decimal(order, "q")?,
+        execution_type: text(order, "x"),
+        status: text(order, "X"),
+        last_filled_price: decimal(order, "L")?,
+        last_filled_quantity: decimal(order, "l")?,
+        filled_quantity: decimal(order, "z")?,
+        average_price: decimal(order, "ap")?,
+        // Commission fields are left out until the order trades
+        commission: decimal(order, "n").unwrap_or(0.0),
+        commission_asset: order["N"].as_str().map(str::to_string),
+        realized_pnl: decimal(order, "rp").unwrap_or(0.0),
+        trade_id: order["t"].as_u64().unwrap_or(0),
+        reduce_only: order["R"].as_bool().unwrap_or(false),
+        timestamp: data["T"].as_i64()
+            .or_else(|| data["E"].as_i64())
+            .ok_or_else(|| GatewayError::Parse("Missing transaction time".to_string()))?,
+        received_at,
+    }))
+}
+
+/// Parse an ACCOUNT_UPDATE event (balances under `a.B`, positions under `a.P`)
+fn parse_account_update(exchange: ExchangeType, data: &Value, received_at: i64) -> Result<UserDataEvent> {
+    let account = data.get("a").ok_or_else(|| GatewayError::Parse("Missing account".to_string()))?;
+    let entries = |key: &str| account[key].as_array().cloned().unwrap_or_default();
+
+    let balances = entries("B").iter()
+        .map(|balance| Ok(BalanceUpdate {
+            asset: text(balance, "a"),
+            wallet_balance: decimal(balance, "wb")?,
+            cross_wallet_balance: decimal(balance, "cw")?,
+            balance_change: decimal(balance, "bc").unwrap_or(0.0),
+        }))
+        .collect::<Result<Vec<_>>>()?;
+    let positions = entries("P").iter()
+        .map(|position| Ok(PositionUpdate {
+            symbol: text(position, "s"),
+            amount: decimal(position, "pa")?,
+            entry_price: decimal(position, "ep")?,
+            unrealized_pnl: decimal(position, "up")?,
+            position_side: text(position, "ps"),
+        }))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(UserDataEvent::AccountUpdate(AccountUpdate {
+        exchange,
+        reason: text(account, "m"),
+        balances,
+        positions,
+        timestamp: data["T"].as_i64()
+            .or_else(|| data["E"].as_i64())
+            .ok_or_else(|| GatewayError::Parse("Missing transaction time".to_string()))?,
+        received_at,
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::MockClock;
+    use std::sync::Arc;
 
     #[test]
     fn test_parse_agg_trade() {
@@ -393,6 +1244,150 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_agg_trade_gap_detection() {
+        let mut client = BinanceClient::new(false);
+
+        assert_eq!(client.track_trade_id("BTCUSDT", 100), None);
+        assert_eq!(client.track_trade_id("BTCUSDT", 101), None);
+        assert_eq!(client.track_trade_id("BTCUSDT", 105), Some(3));
+        // Symbols are tracked independently, and a repeat isn't a gap
+        assert_eq!(client.track_trade_id("ETHUSDT", 7), None);
+        assert_eq!(client.track_trade_id("BTCUSDT", 105), None);
+
+        assert_eq!(client.gap_count("BTCUSDT"), 1);
+        assert_eq!(client.gap_count("ETHUSDT"), 0);
+        assert_eq!(client.gap_counts().len(), 1);
+    }
+
+    #[test]
+    fn test_parsed_event_records_received_at() {
+        let client = BinanceClient::new(false);
+        let sent = now_ms();
+        let json = format!(
+            r#"{{"e":"aggTrade","E":{0},"s":"BTCUSDT","a":1,"p":"50000","q":"1","f":1,"l":1,"T":{0},"m":false}}"#,
+            sent
+        );
+
+        let event = client.parse_message(&json).unwrap();
+        assert_eq!(event.timestamp(), sent);
+        let MarketEvent::AggTrade(trade) = event else {
+            panic!("Expected AggTrade event");
+        };
+        assert!(trade.received_at >= trade.timestamp);
+        assert!(trade.received_at <= now_ms());
+    }
+
+    #[test]
+    fn test_parse_mark_price_funding_rate() {
+        let client = BinanceClient::new(false);
+        let json = r#"{"e":"markPriceUpdate","E":1562305380000,"s":"BTCUSDT","p":"11794.15000000","i":"11784.62659091","P":"11784.25641265","r":"0.00038167","T":1562306400000}"#;
+
+        if let Ok(MarketEvent::FundingRate(funding)) = client.parse_message(json) {
+            assert_eq!(funding.symbol, "BTCUSDT");
+            assert_eq!(funding.funding_rate, 0.00038167);
+            assert_eq!(funding.next_funding_time, 1562306400000);
+            assert_eq!(funding.timestamp, 1562305380000);
+        } else {
+            panic!("Expected FundingRate event");
+        }
+    }
+
+    #[test]
+    fn test_parse_force_order() {
+        let client = BinanceClient::new(false);
+        let json = r#"{"e":"forceOrder","E":1568014460893,"o":{"s":"BTCUSDT","S":"SELL","o":"LIMIT","f":"IOC","q":"0.014","p":"9910","ap":"9910","X":"FILLED","l":"0.014","z":"0.014","T":1568014460893}}"#;
+
+        if let Ok(MarketEvent::Liquidation(liquidation)) = client.parse_message(json) {
+            assert_eq!(liquidation.symbol, "BTCUSDT");
+            assert_eq!(liquidation.side, Side::Sell);
+            assert_eq!(liquidation.price, 9910.0);
+            assert_eq!(liquidation.quantity, 0.014);
+            assert_eq!(liquidation.timestamp, 1568014460893);
+        } else {
+            panic!("Expected Liquidation event");
+        }
+    }
+
+    #[test]
+    fn test_agg_trade_timestamp_prefers_trade_time() {
+        let client = BinanceClient::new(false);
+        let json = r#"{"e":"aggTrade","E":123456789,"s":"BTCUSDT","a":12345,"p":"50000.5","q":"0.001","f":100,"l":200,"T":123456788,"m":true}"#;
+
+        if let Ok(MarketEvent::AggTrade(trade)) = client.parse_message(json) {
+            assert_eq!(trade.timestamp, 123456788);
+        } else {
+            panic!("Expected AggTrade event");
+        }
+    }
+
+    #[test]
+    fn test_parse_combined_stream_message() {
+        let client = BinanceClient::new(false);
+        let raw = r#"{"e":"aggTrade","E":123456789,"s":"BTCUSDT","a":12345,"p":"50000.5","q":"0.001","f":100,"l":200,"T":123456788,"m":true}"#;
+        let wrapped = format!(r#"{{"stream":"btcusdt@aggTrade","data":{}}}"#, raw);
+
+        let mut from_raw = client.parse_message(raw).unwrap();
+        let mut from_wrapped = client.parse_message(&wrapped).unwrap();
+        // Receive times differ between the two parses
+        for event in [&mut from_raw, &mut from_wrapped] {
+            if let MarketEvent::AggTrade(trade) = event {
+                trade.received_at = 0;
+            }
+        }
+        assert!(matches!(from_raw, MarketEvent::AggTrade(_)));
+        assert_eq!(from_wrapped, from_raw);
+
+        assert!(client.parse_message(r#"{"stream":"btcusdt@aggTrade"}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_failures_are_parse_errors() {
+        let client = BinanceClient::new(false);
+
+        assert!(matches!(client.parse_message("not json"), Err(GatewayError::Parse(_))));
+        // An aggTrade without its price
+        let truncated = r#"{"e":"aggTrade","E":123456789,"s":"BTCUSDT","a":12345,"q":"0.001","m":true}"#;
+        match client.parse_message(truncated) {
+            Err(GatewayError::Parse(message)) => assert_eq!(message, "Missing price"),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_agg_trade_timestamp_falls_back_to_event_time() {
+        let client = BinanceClient::new(false);
+        let json = r#"{"e":"aggTrade","E":123456789,"s":"BTCUSDT","a":12345,"p":"50000.5","q":"0.001","f":100,"l":200,"m":true}"#;
+
+        if let Ok(MarketEvent::AggTrade(trade)) = client.parse_message(json) {
+            assert_eq!(trade.timestamp, 123456789);
+        } else {
+            panic!("Expected AggTrade event");
+        }
+    }
+
+    #[test]
+    fn test_parse_depth_snapshot() {
+        let client = BinanceClient::new(false).with_clock(Arc::new(MockClock::new(1589436923000)));
+        let json = r#"{"lastUpdateId":1027024,"E":1589436922972,"T":1589436922959,"bids":[["4.00000000","431.00000000"],["4.10000000","12.00000000"],["3.90000000","5.00000000"]],"asks":[["4.00000200","12.00000000"],["4.00000100","3.00000000"]]}"#;
+
+        let snapshot = client.parse_depth_snapshot("BTCUSDT", &serde_json::from_str(json).unwrap(), 2).unwrap();
+        assert_eq!(snapshot.exchange, ExchangeType::Binance);
+        assert_eq!(snapshot.symbol, "BTCUSDT");
+        assert!(snapshot.is_snapshot);
+        assert_eq!(snapshot.final_update_id, Some(1027024));
+        assert_eq!(snapshot.timestamp, 1589436922972);
+        // Best levels first, cut to the limit
+        assert_eq!(snapshot.bids, vec![(4.1, 12.0), (4.0, 431.0)]);
+        assert_eq!(snapshot.asks, vec![(4.000001, 3.0), (4.000002, 12.0)]);
+        assert_eq!(snapshot.received_at, 1589436923000);
+
+        // Spot snapshots have no event time, so they are dated by the client's clock too
+        let spot = r#"{"lastUpdateId":1027024,"bids":[["4.00000000","431.00000000"]],"asks":[["4.00000200","12.00000000"]]}"#;
+        let snapshot = client.parse_depth_snapshot("BTCUSDT", &serde_json::from_str(spot).unwrap(), 2).unwrap();
+        assert_eq!((snapshot.timestamp, snapshot.received_at), (1589436923000, 1589436923000));
+    }
+
     #[test]
     fn test_parse_book_ticker() {
         let client = BinanceClient::new(false);
@@ -409,4 +1404,355 @@ mod tests {
             panic!("Expected BookTicker event");
         }
     }
+
+    #[test]
+    fn test_parse_ticker_24h() {
+        let client = BinanceClient::new(false);
+        let json = r#"{"e":"24hrTicker","E":1672515782136,"s":"BTCUSDT","p":"-150.50","P":"-0.895","w":"16750.12","c":"16650.00","Q":"0.010","o":"16800.50","h":"16900.00","l":"16600.25","v":"182345.123","q":"3054321987.45","O":1672429382136,"C":1672515782136,"F":2400000000,"L":2401000000,"n":1000001}"#;
+
+        if let Ok(MarketEvent::Ticker24h(ticker)) = client.parse_message(json) {
+            assert_eq!(ticker.symbol, "BTCUSDT");
+            assert_eq!(ticker.last_price, 16650.0);
+            assert_eq!(ticker.open_price, 16800.5);
+            assert_eq!(ticker.high_price, 16900.0);
+            assert_eq!(ticker.low_price, 16600.25);
+            assert_eq!(ticker.volume, 182345.123);
+            assert_eq!(ticker.quote_volume, 3054321987.45);
+            assert_eq!(ticker.price_change, -150.5);
+            assert_eq!(ticker.price_change_percent, -0.895);
+            assert_eq!(ticker.timestamp, 1672515782136);
+        } else {
+            panic!("Expected Ticker24h event");
+        }
+    }
+
+    #[test]
+    fn test_parse_continuous_kline() {
+        let client = BinanceClient::new(false);
+        let json = r#"{"e":"continuous_kline","E":1607443058651,"ps":"BTCUSDT","ct":"PERPETUAL","k":{"t":1607443020000,"T":1607443079999,"i":"1m","f":116467658886,"L":116468012423,"o":"18787.00","c":"18804.04","h":"18804.04","l":"18786.54","v":"197.664","n":543,"x":false,"q":"3715253.19494","V":"184.769","Q":"3472925.84746","B":"0"}}"#;
+
+        let result = client.parse_message(json);
+
+        if let Ok(MarketEvent::Kline(kline)) = result {
+            assert_eq!(kline.symbol, "BTCUSDT");
+            assert_eq!(kline.interval, "1m");
+            assert_eq!(kline.open, 18787.0);
+            assert_eq!(kline.close, 18804.04);
+            assert!(!kline.is_closed);
+            assert_eq!(kline.contract_type, Some(ContractType::Perpetual));
+        } else {
+            panic!("Expected Kline event");
+        }
+
+        let sub = Subscription {
+            symbol: "BTCUSDT".to_string(),
+            data_type: DataType::ContinuousKline,
+            interval: Some(KlineInterval::FiveMinutes),
+            contract_type: Some(ContractType::CurrentQuarter),
+            depth_levels: None,
+            update_speed: None,
+            okx_inst_type: None,
+        };
+        assert_eq!(BinanceClient::new(false).stream_name(&sub).unwrap(), "btcusdt_current_quarter@continuousKline_5m");
+    }
+
+    #[test]
+    fn test_depth_stream_names() {
+        let partial = Subscription::partial_depth("BTCUSDT", 20);
+        assert_eq!(BinanceClient::new(false).stream_name(&partial).unwrap(), "btcusdt@depth20@100ms");
+
+        let slow = Subscription { update_speed: Some(DepthUpdateSpeed::Ms500), ..Subscription::depth("ETHUSDT") };
+        assert_eq!(BinanceClient::new(false).stream_name(&slow).unwrap(), "ethusdt@depth@500ms");
+        assert_eq!(BinanceClient::new(false).stream_name(&Subscription::depth("ETHUSDT")).unwrap(), "ethusdt@depth@100ms");
+
+        let unsupported = [Subscription::partial_depth("BTCUSDT", 50)];
+        assert!(check_depth_levels(ExchangeType::Binance, &unsupported, &SUPPORTED_DEPTH_LEVELS).is_err());
+    }
+
+    #[test]
+    fn test_market_endpoints() {
+        let futures = BinanceClient::new(false);
+        assert_eq!(futures.market(), MarketKind::UsdFutures);
+        assert_eq!(futures.ws_endpoint(), "wss://fstream.binance.com/ws");
+        assert_eq!(futures.depth_url(), "https://fapi.binance.com/fapi/v1/depth");
+
+        let spot = BinanceClient::new(false).with_market(MarketKind::Spot);
+        assert_eq!(spot.ws_endpoint(), "wss://stream.binance.com:9443/ws");
+        assert_eq!(spot.depth_url(), "https://api.binance.com/api/v3/depth");
+
+        let spot_testnet = BinanceClient::new(true).with_market(MarketKind::Spot);
+        assert_eq!(spot_testnet.ws_endpoint(), "wss://testnet.binance.vision/ws");
+        assert_eq!(spot_testnet.depth_url(), "https://testnet.binance.vision/api/v3/depth");
+
+        assert_eq!(BinanceClient::new(true).ws_endpoint(), "wss://stream.binancefuture.com/ws");
+        let coin = BinanceClient::new(false).with_market(MarketKind::CoinFutures);
+        assert_eq!(coin.ws_endpoint(), "wss://dstream.binance.com/ws");
+        assert_eq!(coin.depth_url(), "https://dapi.binance.com/dapi/v1/depth");
+
+        assert_eq!("coin-futures".parse::<MarketKind>().unwrap(), MarketKind::CoinFutures);
+        assert!("options".parse::<MarketKind>().is_err());
+    }
+
+    #[test]
+    fn test_spot_stream_names() {
+        let spot = BinanceClient::new(false).with_market(MarketKind::Spot);
+        assert_eq!(spot.stream_name(&Subscription::depth("BTCUSDT")).unwrap(), "btcusdt@depth@100ms");
+        let slow = Subscription { update_speed: Some(DepthUpdateSpeed::Ms500), ..Subscription::partial_depth("BTCUSDT", 5) };
+        assert_eq!(spot.stream_name(&slow).unwrap(), "btcusdt@depth5");
+        assert_eq!(spot.stream_name(&Subscription::book_ticker("BTCUSDT")).unwrap(), "btcusdt@bookTicker");
+        assert_eq!(spot.stream_name(&Subscription::funding_rate("BTCUSDT")), None);
+        assert_eq!(spot.stream_name(&Subscription::liquidation("BTCUSDT")), None);
+    }
+
+    #[test]
+    fn test_subscription_batches() {
+        let mut client = BinanceClient::new(false).with_subscribe_batch_size(50);
+        let subscriptions: Vec<Subscription> = (0..300)
+            .map(|i| Subscription {
+                symbol: format!("SYM{}USDT", i),
+                data_type: DataType::AggTrade,
+                interval: None,
+                contract_type: None,
+                depth_levels: None,
+                update_speed: None,
+                okx_inst_type: None,
+            })
+            .collect();
+
+        let control_msgs = client.build_control_msgs("SUBSCRIBE", &subscriptions);
+
+        assert_eq!(control_msgs.len(), 6);
+        for msg in &control_msgs {
+            assert_eq!(msg["method"], "SUBSCRIBE");
+            assert_eq!(msg["params"].as_array().unwrap().len(), 50);
+        }
+        assert_eq!(control_msgs[5]["id"], 6);
+    }
+
+    #[test]
+    fn test_large_subscribe_is_batched_within_stream_limit() {
+        let mut client = BinanceClient::new(false).with_subscribe_batch_size(10).with_rate_limit(5);
+        let subscriptions: Vec<Subscription> = (0..25)
+            .map(|i| Subscription::agg_trade(format!("SYM{}USDT", i)))
+            .collect();
+
+        let batches: Vec<usize> = client.build_control_msgs("SUBSCRIBE", &subscriptions)
+            .iter()
+            .map(|msg| msg["params"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(batches, vec![10, 10, 5]);
+        assert_eq!(client.rate_limiter.per_second(), 5);
+        assert!(client.check_stream_limit(&subscriptions).is_ok());
+
+        let too_many: Vec<Subscription> = (0..=MAX_STREAMS_PER_CONNECTION)
+            .map(|i| Subscription::agg_trade(format!("SYM{}USDT", i)))
+            .collect();
+        assert!(client.check_stream_limit(&too_many).is_err());
+    }
+
+    fn trade_and_ticker(symbol: &str) -> Vec<Subscription> {
+        [DataType::AggTrade, DataType::BookTicker].into_iter()
+            .map(|data_type| Subscription {
+                symbol: symbol.to_string(),
+                data_type,
+                interval: None,
+                contract_type: None,
+                depth_levels: None,
+                update_speed: None,
+                okx_inst_type: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_subscribe_and_unsubscribe_msgs() {
+        let mut client = BinanceClient::new(false);
+        let subscriptions = trade_and_ticker("BTCUSDT");
+
+        let subscribe = client.build_control_msgs("SUBSCRIBE", &subscriptions);
+        assert_eq!(
+            subscribe,
+            vec![json!({ "method": "SUBSCRIBE", "params": ["btcusdt@aggTrade", "btcusdt@bookTicker"], "id": 1 })]
+        );
+
+        let unsubscribe = client.build_control_msgs("UNSUBSCRIBE", &subscriptions);
+        assert_eq!(
+            unsubscribe,
+            vec![json!({ "method": "UNSUBSCRIBE", "params": ["btcusdt@aggTrade", "btcusdt@bookTicker"], "id": 2 })]
+        );
+    }
+
+    #[test]
+    fn test_control_response_matches_request() {
+        let mut client = BinanceClient::new(false);
+        client.track_subscriptions(trade_and_ticker("BTCUSDT"));
+        client.build_control_msgs("SUBSCRIBE", &trade_and_ticker("BTCUSDT"));
+        assert!(client.pending_requests.contains_key(&1));
+
+        assert!(client.handle_control_response(r#"{"result":null,"id":1}"#));
+        assert!(client.pending_requests.is_empty());
+
+        // Stream payloads are not control responses
+        assert!(!client.handle_control_response(r#"{"e":"aggTrade","s":"BTCUSDT"}"#));
+
+        // Only the streams still tracked are unsubscribed
+        let removed = client.untrack_subscriptions(&trade_and_ticker("BTCUSDT")[..1]);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(client.subscriptions.len(), 1);
+        assert!(client.untrack_subscriptions(&removed).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_silent_connection_goes_stale() {
+        let mut client = BinanceClient::new(false).with_stale_timeout(Duration::from_secs(60));
+        // As if `connect` had just succeeded
+        client.connected = true;
+        client.watchdog.touch();
+        assert!(client.is_connected());
+
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert!(!client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_partial_depth_is_handed_on_as_book_ticker() {
+        let mut client = BinanceClient::new(false).with_derived_book_ticker(true);
+        client.subscriptions.push(Subscription::partial_depth("BTCUSDT", 5));
+        let frame = r#"{"e":"depthUpdate","E":1700000000000,"s":"BTCUSDT","U":1,"u":2,"pu":0,"b":[["49999.5","1.2"],["49999.0","0.4"]],"a":[["50000.0","0.8"],["50000.5","1.1"]]}"#;
+
+        let Some(MarketEvent::BookTicker(ticker)) = client.handle_text(frame).await.unwrap() else {
+            panic!("Expected BookTicker event");
+        };
+        assert_eq!((ticker.bid_price, ticker.bid_qty, ticker.ask_price, ticker.ask_qty), (49999.5, 1.2, 50000.0, 0.8));
+
+        // Full-depth diffs can't say where the top is, so they stay depth updates
+        let eth = frame.replace("BTCUSDT", "ETHUSDT");
+        assert!(matches!(client.handle_text(&eth).await.unwrap(), Some(MarketEvent::DepthUpdate(_))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mock_clock_and_paused_time_are_deterministic() {
+        let clock = MockClock::new(1_700_000_000_000);
+        let mut client = BinanceClient::new(false)
+            .with_stale_timeout(Duration::from_secs(60))
+            .with_clock(Arc::new(clock.clone()));
+        client.connected = true;
+        client.watchdog.touch();
+        let frame = r#"{"e":"aggTrade","E":1699999999990,"s":"BTCUSDT","a":1,"p":"50000.0","q":"0.1","f":1,"l":1,"T":1699999999990,"m":false}"#;
+
+        let Some(MarketEvent::AggTrade(trade)) = client.handle_text(frame).await.unwrap() else {
+            panic!("Expected AggTrade event");
+        };
+        assert_eq!(trade.received_at, 1_700_000_000_000);
+
+        // Just short of the timeout the connection holds; one more second flips it
+        tokio::time::advance(Duration::from_secs(59)).await;
+        clock.advance(Duration::from_secs(59));
+        assert!(client.is_connected());
+        tokio::time::advance(Duration::from_secs(1)).await;
+        clock.advance(Duration::from_secs(1));
+        assert!(!client.is_connected());
+
+        // A frame on a fresh connection is stamped with the advanced clock
+        client.watchdog.touch();
+        let Some(MarketEvent::AggTrade(trade)) = client.handle_text(&frame.replace(r#""a":1"#, r#""a":2"#)).await.unwrap() else {
+            panic!("Expected AggTrade event");
+        };
+        assert_eq!(trade.received_at, 1_700_000_060_000);
+    }
+
+    #[tokio::test]
+    async fn test_with_endpoint_connects_there() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("ws://{}/ws", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio_tungstenite::accept_async(stream).await.unwrap()
+        });
+
+        let mut client = BinanceClient::new(false).with_endpoint(endpoint.clone());
+        assert_eq!(client.ws_endpoint(), endpoint);
+
+        client.connect().await.unwrap();
+        assert!(client.is_connected());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unparseable_frames_trip_the_parse_error_alarm() {
+        let logs = crate::testing::LogCapture::default();
+        let _logger = tracing::subscriber::set_default(
+            crate::logging::subscriber("error", crate::logging::LogFormat::Text, logs.clone()),
+        );
+        let mut client = BinanceClient::new(false)
+            .with_parse_error_monitor(ParseErrorMonitor::new(10, 50).with_reconnect(true));
+        client.connected = true;
+
+        // A format change: frames still arrive, but none carry a recognisable event
+        for trade_id in 0..9 {
+            let frame = format!(r#"{{"event":"trade","data":{{"id":{}}}}}"#, trade_id);
+            assert!(client.handle_text(&frame).await.unwrap().is_none());
+        }
+        assert!(client.connected);
+        assert!(logs.output().is_empty());
+
+        client.handle_text(r#"{"event":"trade","data":{"id":9}}"#).await.unwrap();
+        assert!(!client.connected, "alarm should force a reconnect");
+        assert!(logs.output().contains("failed to parse 100% of the last 10 frames"));
+    }
+
+    #[test]
+    fn test_parse_order_trade_update() {
+        let json = r#"{"e":"ORDER_TRADE_UPDATE","E":1568879465651,"T":1568879465650,"o":{"s":"BTCUSDT","c":"TEST","S":"SELL","o":"LIMIT","f":"GTC","q":"0.002","p":"7103.04","ap":"7103.04","sp":"0","x":"TRADE","X":"PARTIALLY_FILLED","i":8886774,"l":"0.001","z":"0.001","L":"7103.04","N":"USDT","n":"0.00284","T":1568879465650,"t":42,"b":"0","a":"9.91","m":true,"R":false,"wt":"CONTRACT_PRICE","ot":"LIMIT","ps":"BOTH","cp":false,"rp":"1.5"}}"#;
+
+        let Ok(UserDataEvent::OrderUpdate(order)) = parse_user_data(ExchangeType::Binance, json, 1_700_000_000_000) else {
+            panic!("Expected OrderUpdate event");
+        };
+        assert_eq!(order.symbol, "BTCUSDT");
+        assert_eq!(order.order_id, 8886774);
+        assert_eq!(order.client_order_id, "TEST");
+        assert_eq!(order.side, Side::Sell);
+        assert_eq!(order.order_type, "LIMIT");
+        assert_eq!(order.execution_type, "TRADE");
+        assert_eq!(order.status, "PARTIALLY_FILLED");
+        assert_eq!(order.price, 7103.04);
+        assert_eq!(order.quantity, 0.002);
+        assert_eq!(order.last_filled_quantity, 0.001);
+        assert_eq!(order.filled_quantity, 0.001);
+        assert_eq!(order.commission, 0.00284);
+        assert_eq!(order.commission_asset.as_deref(), Some("USDT"));
+        assert_eq!(order.realized_pnl, 1.5);
+        assert_eq!(order.trade_id, 42);
+        assert_eq!(order.timestamp, 1568879465650);
+        assert_eq!(order.received_at, 1_700_000_000_000);
+    }
+
+    #[test]
+    fn test_parse_account_update() {
+        let json = r#"{"e":"ACCOUNT_UPDATE","E":1564745798939,"T":1564745798938,"a":{"m":"ORDER","B":[{"a":"USDT","wb":"122624.12345678","cw":"100.12345678","bc":"50.12345678"}],"P":[{"s":"BTCUSDT","pa":"-0.5","ep":"9000.0","cr":"200","up":"-12.5","mt":"isolated","iw":"0.00000000","ps":"BOTH"}]}}"#;
+
+        let Ok(UserDataEvent::AccountUpdate(account)) = parse_user_data(ExchangeType::Binance, json, 1_700_000_000_000) else {
+            panic!("Expected AccountUpdate event");
+        };
+        assert_eq!(account.reason, "ORDER");
+        assert_eq!(account.balances.len(), 1);
+        assert_eq!(account.balances[0].asset, "USDT");
+        assert_eq!(account.balances[0].wallet_balance, 122624.12345678);
+        assert_eq!(account.positions[0].amount, -0.5);
+        assert_eq!(account.positions[0].unrealized_pnl, -12.5);
+        assert_eq!(account.timestamp, 1564745798938);
+
+        let expired = r#"{"e":"listenKeyExpired","E":1576653824250,"listenKey":"abc"}"#;
+        assert!(matches!(parse_user_data(ExchangeType::Binance, expired, 1_700_000_000_000), Err(GatewayError::Disconnected(_))));
+    }
+
+    #[test]
+    fn test_user_data_stream_needs_futures_credentials() {
+        assert!(matches!(BinanceClient::new(false).user_data_stream(), Err(GatewayError::Config(_))));
+        let spot = BinanceClient::new(false).with_market(MarketKind::Spot).with_api_key("key");
+        assert!(matches!(spot.user_data_stream(), Err(GatewayError::Config(_))));
+
+        let stream = BinanceClient::new(true).with_api_key("key").user_data_stream().unwrap();
+        assert_eq!(stream.listen_key_url, format!("{}/fapi/v1/listenKey", BINANCE_FUTURES_TESTNET_REST));
+    }
 }
@@ -0,0 +1,714 @@
+//! Bitget WebSocket implementation
+//!
+//! This module handles WebSocket connections to Bitget's v2 public API for
+//! USDT-margined futures and parses incoming market data. Contracts are
+//! named like `BTCUSDT`, the same as our canonical symbols.
+
+use crate::exchange::{
+    Exchange, ExchangeType, MarketEvent, AggTrade, Kline, DepthUpdate, BookTicker, Ticker24h, Subscription,
+    DataType, KlineInterval, check_depth_levels, RateLimiter, StaleWatchdog, ParseErrorMonitor, now_ms,
+};
+use crate::metrics;
+use crate::redis_publisher::RedisPublisher;
+use crate::clock::{self, SharedClock};
+use crate::error::{GatewayError, Result};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time;
+use tokio_tungstenite::{connect_async, tungstenite::Message, WebSocketStream};
+use tracing::{debug, error, info, warn};
+
+/// Public v2 endpoint
+pub const BITGET_WS_PUBLIC: &str = "wss://ws.bitget.com/v2/ws/public";
+
+/// Demo trading endpoint, which lists the simulated `SUSDT-FUTURES` contracts
+pub const BITGET_WS_DEMO: &str = "wss://wspap.bitget.com/v2/ws/public";
+
+/// Default number of channels per subscribe frame
+pub const DEFAULT_SUBSCRIBE_BATCH_SIZE: usize = 20;
+
+/// Levels offered by the `books1`, `books5` and `books15` snapshot channels
+pub const SUPPORTED_DEPTH_LEVELS: [u16; 3] = [1, 5, 15];
+
+/// Control frames sent per second by default (Bitget allows 10)
+pub const DEFAULT_MESSAGES_PER_SECOND: u32 = 10;
+
+/// Interval between `ping` text frames; Bitget drops connections silent for two minutes
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Bitget-specific WebSocket client
+pub struct BitgetClient {
+    exchange_type: ExchangeType,
+    ws_url: String,
+    /// `USDT-FUTURES`, or `SUSDT-FUTURES` for demo trading
+    inst_type: &'static str,
+    ws: Option<WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>>,
+    /// Active subscriptions, re-sent after a reconnect
+    subscriptions: Vec<Subscription>,
+    /// Events parsed from a frame but not yet returned; pushes carry lists
+    pending: VecDeque<MarketEvent>,
+    redis_publisher: Option<RedisPublisher>,
+    connected: bool,
+    subscribe_batch_size: usize,
+    /// Paces subscribe and unsubscribe frames
+    rate_limiter: RateLimiter,
+    /// Drops the connection once it goes silent
+    watchdog: StaleWatchdog,
+    /// Flags a connection whose frames mostly fail to parse
+    parse_errors: ParseErrorMonitor,
+    /// Publish partial book snapshots as best bid/ask tickers
+    derive_book_ticker: bool,
+    /// Stamps `received_at` on parsed events
+    clock: SharedClock,
+    /// Sends `ping` while connected
+    keepalive: Option<time::Interval>,
+}
+
+impl BitgetClient {
+    /// Create a new Bitget futures client
+    pub fn new(demo_trading: bool) -> Self {
+        let (ws_url, inst_type) = if demo_trading {
+            (BITGET_WS_DEMO, "SUSDT-FUTURES")
+        } else {
+            (BITGET_WS_PUBLIC, "USDT-FUTURES")
+        };
+
+        Self {
+            exchange_type: ExchangeType::Bitget,
+            ws_url: ws_url.to_string(),
+            inst_type,
+            ws: None,
+            subscriptions: Vec::new(),
+            pending: VecDeque::new(),
+            redis_publisher: None,
+            connected: false,
+            subscribe_batch_size: DEFAULT_SUBSCRIBE_BATCH_SIZE,
+            rate_limiter: RateLimiter::new(DEFAULT_MESSAGES_PER_SECOND, 1),
+            watchdog: StaleWatchdog::default(),
+            parse_errors: ParseErrorMonitor::default(),
+            derive_book_ticker: false,
+            clock: clock::system(),
+            keepalive: None,
+        }
+    }
+
+    /// Set the Redis publisher for forwarding events
+    pub fn with_redis_publisher(mut self, publisher: RedisPublisher) -> Self {
+        self.redis_publisher = Some(publisher);
+        self
+    }
+
+    /// Set how many channels are sent per subscribe frame
+    pub fn with_subscribe_batch_size(mut self, batch_size: usize) -> Self {
+        self.subscribe_batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set how many control frames may be sent per second
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limiter = RateLimiter::new(per_second, 1);
+        self
+    }
+
+    /// Treat the connection as dead after this long without a frame (zero disables)
+    pub fn with_stale_timeout(mut self, timeout: Duration) -> Self {
+        self.watchdog = StaleWatchdog::new(timeout);
+        self
+    }
+
+    /// Alarm on, and optionally reconnect after, a storm of unparseable frames
+    pub fn with_parse_error_monitor(mut self, monitor: ParseErrorMonitor) -> Self {
+        self.parse_errors = monitor;
+        self
+    }
+
+    /// Turn partial book snapshots into best bid/ask tickers before anything sees them
+    pub fn with_derived_book_ticker(mut self, enabled: bool) -> Self {
+        self.derive_book_ticker = enabled;
+        self
+    }
+
+    /// Stamp events with this clock instead of the host's, e.g. a mock in tests
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Get the Bitget channel for a subscription, or `None` if Bitget has no matching feed
+    fn channel(sub: &Subscription) -> Option<String> {
+        match sub.data_type {
+            DataType::AggTrade => Some("trade".to_string()),
+            DataType::Ticker24h => Some("ticker".to_string()),
+            DataType::BookTicker => Some("books1".to_string()),
+            DataType::Depth => match sub.depth_levels {
+                Some(levels) => Some(format!("books{}", levels)),
+                None => Some("books".to_string()),
+            },
+            DataType::Kline => {
+                let interval = sub.interval.unwrap_or(KlineInterval::OneMinute);
+                interval.as_bitget_str().map(|interval| format!("candle{}", interval))
+            }
+            // Funding rides on the ticker and liquidations need a private channel;
+            // neither is split out yet
+            DataType::ContinuousKline
+            | DataType::FundingRate
+            | DataType::Liquidation
+            | DataType::OpenInterest
+            | DataType::Nbbo
+            | DataType::Opportunity => None,
+        }
+    }
+
+    /// Build the channel arguments for a set of subscriptions, without duplicates
+    fn channel_args(&self, subscriptions: &[Subscription]) -> Vec<Value> {
+        let mut args: Vec<Value> = Vec::new();
+        for sub in subscriptions {
+            let Some(channel) = Self::channel(sub) else {
+                continue;
+            };
+            let arg = json!({ "instType": self.inst_type, "channel": channel, "instId": sub.symbol });
+            if !args.contains(&arg) {
+                args.push(arg);
+            }
+        }
+        args
+    }
+
+    /// Build `op` frames for a set of subscriptions, batching their channel arguments
+    fn build_msgs(&self, op: &str, subscriptions: &[Subscription]) -> Vec<Value> {
+        self.channel_args(subscriptions)
+            .chunks(self.subscribe_batch_size)
+            .map(|args| json!({ "op": op, "args": args }))
+            .collect()
+    }
+
+    /// Build the subscribe frames for a set of subscriptions
+    fn build_subscription_msgs(&self, subscriptions: &[Subscription]) -> Vec<Value> {
+        self.build_msgs("subscribe", subscriptions)
+    }
+
+    /// Send frames with a pause between them
+    async fn send_msgs(&mut self, msgs: Vec<Value>) -> Result<()> {
+        for msg in msgs {
+            self.rate_limiter.acquire().await;
+            if let Some(ref mut ws) = self.ws {
+                ws.send(Message::Text(msg.to_string())).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a number Bitget sends as a string
+    fn parse_f64(value: &Value, name: &str) -> Result<f64> {
+        match value {
+            Value::String(s) => Ok(s.parse::<f64>()?),
+            Value::Number(n) => n.as_f64().ok_or_else(|| GatewayError::Parse(format!("Invalid {}", name))),
+            _ => Err(GatewayError::Parse(format!("Missing {}", name))),
+        }
+    }
+
+    /// Read a millisecond timestamp Bitget sends as a string
+    fn parse_ts(value: &Value) -> Option<i64> {
+        value.as_str().and_then(|s| s.parse().ok()).or_else(|| value.as_i64())
+    }
+
+    /// Parse `[price, size]` levels
+    fn parse_levels(levels: Option<&Value>) -> Result<Vec<(f64, f64)>> {
+        levels
+            .and_then(|l| l.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .map(|level| Ok((Self::parse_f64(&level[0], "price")?, Self::parse_f64(&level[1], "size")?)))
+                    .collect()
+            })
+            .unwrap_or_else(|| Ok(Vec::new()))
+    }
+
+    /// Length of a candle in milliseconds
+    fn interval_ms(interval: KlineInterval) -> i64 {
+        let minutes = match interval {
+            KlineInterval::OneMinute => 1,
+            KlineInterval::ThreeMinutes => 3,
+            KlineInterval::FiveMinutes => 5,
+            KlineInterval::FifteenMinutes => 15,
+            KlineInterval::ThirtyMinutes => 30,
+            KlineInterval::OneHour => 60,
+            KlineInterval::TwoHours => 120,
+            KlineInterval::FourHours => 240,
+            KlineInterval::SixHours => 360,
+            KlineInterval::EightHours => 480,
+            KlineInterval::TwelveHours => 720,
+            KlineInterval::OneDay => 1_440,
+            KlineInterval::OneWeek => 10_080,
+            KlineInterval::OneMonth => 43_200,
+        };
+        minutes * 60_000
+    }
+
+    /// Parse a `trade` push; `side` is the taker's side
+    fn parse_trades(&self, data: &[Value], symbol: &str) -> Result<Vec<MarketEvent>> {
+        data.iter()
+            .map(|trade| {
+                let trade_id = trade["tradeId"].as_str()
+                    .ok_or_else(|| GatewayError::Parse("Missing tradeId".to_string()))?;
+
+                Ok(MarketEvent::AggTrade(AggTrade {
+                    exchange: self.exchange_type,
+                    symbol: symbol.to_string(),
+                    price: Self::parse_f64(&trade["price"], "price")?,
+                    quantity: Self::parse_f64(&trade["size"], "size")?,
+                    timestamp: Self::parse_ts(&trade["ts"]).unwrap_or_else(now_ms),
+                    is_buyer_maker: trade["side"].as_str() == Some("sell"),
+                    trade_id: trade_id.parse::<u64>()?,
+                    received_at: self.clock.now_ms(),
+                }))
+            })
+            .collect()
+    }
+
+    /// Parse a `ticker` push into rolling 24-hour statistics
+    fn parse_tickers(&self, data: &[Value], symbol: &str) -> Result<Vec<MarketEvent>> {
+        data.iter()
+            .map(|ticker| {
+                let last_price = Self::parse_f64(&ticker["lastPr"], "lastPr")?;
+                let open_price = Self::parse_f64(&ticker["open24h"], "open24h")?;
+                // `change24h` is a ratio, e.g. 0.0125 for 1.25%
+                let price_change_percent = Self::parse_f64(&ticker["change24h"], "change24h")? * 100.0;
+
+                Ok(MarketEvent::Ticker24h(Ticker24h {
+                    exchange: self.exchange_type,
+                    symbol: symbol.to_string(),
+                    last_price,
+                    open_price,
+                    high_price: Self::parse_f64(&ticker["high24h"], "high24h")?,
+                    low_price: Self::parse_f64(&ticker["low24h"], "low24h")?,
+                    volume: Self::parse_f64(&ticker["baseVolume"], "baseVolume")?,
+                    quote_volume: Self::parse_f64(&ticker["quoteVolume"], "quoteVolume")?,
+                    price_change: last_price - open_price,
+                    price_change_percent,
+                    timestamp: Self::parse_ts(&ticker["ts"]).unwrap_or_else(now_ms),
+                    received_at: self.clock.now_ms(),
+                }))
+            })
+            .collect()
+    }
+
+    /// Parse a `books*` push; `books` sends a snapshot then diffs, the others only snapshots
+    fn parse_books(&self, data: &[Value], symbol: &str, channel: &str, is_snapshot: bool) -> Result<Vec<MarketEvent>> {
+        data.iter()
+            .map(|book| {
+                let bids = Self::parse_levels(book.get("bids"))?;
+                let asks = Self::parse_levels(book.get("asks"))?;
+                let timestamp = Self::parse_ts(&book["ts"]).unwrap_or_else(now_ms);
+
+                if channel == "books1" {
+                    let (&(bid_price, bid_qty), &(ask_price, ask_qty)) = bids.first().zip(asks.first())
+                        .ok_or_else(|| GatewayError::Parse("Empty books1 push".to_string()))?;
+                    return Ok(MarketEvent::BookTicker(BookTicker {
+                        exchange: self.exchange_type,
+                        symbol: symbol.to_string(),
+                        bid_price,
+                        bid_qty,
+                        ask_price,
+                        ask_qty,
+                        timestamp,
+                        received_at: self.clock.now_ms(),
+                    }));
+                }
+
+                Ok(MarketEvent::DepthUpdate(DepthUpdate {
+                    exchange: self.exchange_type,
+                    symbol: symbol.to_string(),
+                    bids,
+                    asks,
+                    timestamp,
+                    is_snapshot: is_snapshot || channel != "books",
+                    first_update_id: None,
+                    final_update_id: book["seq"].as_u64(),
+                    prev_final_update_id: None,
+                    received_at: self.clock.now_ms(),
+                }))
+            })
+            .collect()
+    }
+
+    /// Parse a `candle*` push of `[start, open, high, low, close, base volume, ...]` rows
+    fn parse_candles(&self, data: &[Value], symbol: &str, channel: &str, push_time: i64) -> Result<Vec<MarketEvent>> {
+        let name = channel.trim_start_matches("candle");
+        let interval = KlineInterval::from_bitget_str(name)
+            .ok_or_else(|| GatewayError::Parse(format!("Unknown candle interval: {}", name)))?;
+        let interval_ms = Self::interval_ms(interval);
+
+        data.iter()
+            .map(|candle| {
+                let open_time = Self::parse_ts(&candle[0]).ok_or_else(|| GatewayError::Parse("Missing candle start".to_string()))?;
+
+                Ok(MarketEvent::Kline(Kline {
+                    exchange: self.exchange_type,
+                    symbol: symbol.to_string(),
+                    interval: interval.as_str().to_string(),
+                    open_time,
+                    close_time: open_time + interval_ms - 1,
+                    open: Self::parse_f64(&candle[1], "open")?,
+                    high: Self::parse_f64(&candle[2], "high")?,
+                    low: Self::parse_f64(&candle[3], "low")?,
+                    close: Self::parse_f64(&candle[4], "close")?,
+                    volume: Self::parse_f64(&candle[5], "volume")?,
+                    // Bitget doesn't flag the final update, so a candle counts as closed once its window has passed
+                    is_closed: open_time + interval_ms <= push_time,
+                    contract_type: None,
+                    received_at: self.clock.now_ms(),
+                }))
+            })
+            .collect()
+    }
+
+    /// Parse a frame into market events; acks and pongs yield none
+    fn parse_message(&self, msg: &str) -> Result<Vec<MarketEvent>> {
+        // Reply to our text keepalive
+        if msg == "pong" {
+            return Ok(Vec::new());
+        }
+
+        let data: Value = serde_json::from_str(msg)?;
+
+        if let Some(event) = data["event"].as_str() {
+            if event == "error" {
+                return Err(GatewayError::Exchange {
+                    code: data["code"].to_string().trim_matches('"').to_string(),
+                    message: data["msg"].as_str().unwrap_or_default().to_string(),
+                });
+            }
+            debug!("Bitget {} event: {:?}", event, data);
+            return Ok(Vec::new());
+        }
+
+        let arg = &data["arg"];
+        let channel = arg["channel"].as_str().ok_or_else(|| GatewayError::Parse("Missing channel".to_string()))?;
+        let symbol = arg["instId"].as_str().ok_or_else(|| GatewayError::Parse("Missing instId".to_string()))?;
+        let is_snapshot = data["action"].as_str() == Some("snapshot");
+        let rows = data["data"].as_array().ok_or_else(|| GatewayError::Parse("Missing data".to_string()))?;
+
+        match channel {
+            // The first push replays recent trades that were published before we subscribed
+            "trade" if is_snapshot => Ok(Vec::new()),
+            "trade" => self.parse_trades(rows, symbol),
+            "ticker" => self.parse_tickers(rows, symbol),
+            c if c.starts_with("books") => self.parse_books(rows, symbol, c, is_snapshot),
+            c if c.starts_with("candle") => {
+                let push_time = data["ts"].as_i64().unwrap_or_else(now_ms);
+                let candles = self.parse_candles(rows, symbol, c, push_time)?;
+                // A candle snapshot is history; only its latest candle is current
+                Ok(if is_snapshot { candles.into_iter().last().into_iter().collect() } else { candles })
+            }
+            _ => Err(GatewayError::Parse(format!("Unknown channel: {}", channel))),
+        }
+    }
+
+    /// Parse a text frame, forward the resulting events and return the first
+    async fn handle_text(&mut self, text: &str) -> Result<Option<MarketEvent>> {
+        // Raw frames are a debugging aid, only queued so they never hold up parsing
+        if let Some(ref publisher) = self.redis_publisher {
+            publisher.publish_raw(self.exchange_type, text);
+        }
+
+        match self.parse_message(text) {
+            Ok(mut events) => {
+                self.parse_errors.record_success();
+                if self.derive_book_ticker {
+                    events = events.into_iter().map(MarketEvent::derive_book_ticker).collect();
+                }
+                // Forward to Redis if configured
+                if let Some(ref publisher) = self.redis_publisher {
+                    for event in &events {
+                        if let Err(e) = publisher.publish_event(event).await {
+                            error!("Failed to publish event to Redis: {}", e);
+                        }
+                    }
+                }
+                self.pending.extend(events);
+                Ok(self.pending.pop_front())
+            }
+            Err(e @ GatewayError::Exchange { .. }) => {
+                error!("Bitget error event: {}", e);
+                Ok(None)
+            }
+            Err(e) => {
+                debug!("Failed to parse Bitget message: {}", e);
+                metrics::global().record_parse_error(self.exchange_type);
+                if self.parse_errors.record_failure(self.exchange_type) {
+                    self.connected = false;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for BitgetClient {
+    fn exchange_type(&self) -> ExchangeType {
+        self.exchange_type
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        info!("Connecting to Bitget WebSocket at {}", self.ws_url);
+
+        let (ws_stream, _) = connect_async(&self.ws_url).await
+            .map_err(|e| GatewayError::Connect(format!("Bitget connect failed: {}", e)))?;
+        self.ws = Some(ws_stream);
+        self.connected = true;
+        self.watchdog.touch();
+        self.pending.clear();
+        self.keepalive = Some(time::interval_at(
+            time::Instant::now() + KEEPALIVE_INTERVAL,
+            KEEPALIVE_INTERVAL,
+        ));
+
+        info!("Connected to Bitget WebSocket");
+
+        Ok(())
+    }
+
+    fn active_subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.clone()
+    }
+
+    async fn resubscribe(&mut self) -> Result<()> {
+        // Restore channels that were active before a reconnect
+        if !self.subscriptions.is_empty() {
+            info!("Restoring {} Bitget subscriptions", self.subscriptions.len());
+            let msgs = self.build_subscription_msgs(&self.subscriptions);
+            self.send_msgs(msgs).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(mut ws) = self.ws.take() {
+            ws.close(None).await?;
+        }
+        self.connected = false;
+        self.keepalive = None;
+        info!("Disconnected from Bitget");
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        info!("Subscribing to {} Bitget data streams", subscriptions.len());
+        check_depth_levels(self.exchange_type, &subscriptions, &SUPPORTED_DEPTH_LEVELS)?;
+
+        if !self.connected {
+            self.connect().await?;
+            self.resubscribe().await?;
+        }
+
+        // Channels restored by `resubscribe` are already active
+        let mut added = Vec::new();
+        for sub in subscriptions {
+            if !self.subscriptions.contains(&sub) && !added.contains(&sub) {
+                added.push(sub);
+            }
+        }
+        if added.is_empty() {
+            debug!("All requested Bitget channels are already subscribed");
+            return Ok(());
+        }
+
+        let msgs = self.build_subscription_msgs(&added);
+        self.send_msgs(msgs).await?;
+        self.subscriptions.extend(added);
+
+        info!("Bitget subscription request sent");
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        let removed: Vec<Subscription> = self.subscriptions.iter()
+            .filter(|sub| subscriptions.contains(sub))
+            .cloned()
+            .collect();
+        if removed.is_empty() {
+            debug!("No Bitget channels to unsubscribe");
+            return Ok(());
+        }
+        self.subscriptions.retain(|sub| !removed.contains(sub));
+
+        // Data types sharing a channel keep it open while any of them is subscribed
+        let still_used = self.channel_args(&self.subscriptions);
+        let msgs: Vec<Value> = self.channel_args(&removed)
+            .into_iter()
+            .filter(|arg| !still_used.contains(arg))
+            .collect::<Vec<_>>()
+            .chunks(self.subscribe_batch_size)
+            .map(|args| json!({ "op": "unsubscribe", "args": args }))
+            .collect();
+
+        info!("Unsubscribing from {} Bitget data streams", removed.len());
+        self.send_msgs(msgs).await
+    }
+
+    async fn recv_event(&mut self) -> Result<Option<MarketEvent>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(Some(event));
+        }
+        if !self.connected || self.ws.is_none() {
+            return Ok(None);
+        }
+
+        let ws = self.ws.as_mut().unwrap();
+
+        let keepalive = self.keepalive.as_mut();
+        let keepalive_tick = async move {
+            match keepalive {
+                Some(keepalive) => {
+                    keepalive.tick().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        let msg = tokio::select! {
+            msg = ws.next() => msg,
+            _ = keepalive_tick => {
+                debug!("Sending Bitget keepalive ping");
+                ws.send(Message::Text("ping".to_string())).await?;
+                return Ok(None);
+            }
+            _ = self.watchdog.expired() => {
+                warn!("No Bitget frame for {:?}, dropping the stale connection", self.watchdog.silence());
+                self.connected = false;
+                return Ok(None);
+            }
+        };
+        // Any frame, pongs included, shows the connection is alive
+        self.watchdog.touch();
+
+        match msg {
+            Some(Ok(Message::Text(text))) => self.handle_text(&text).await,
+            Some(Ok(Message::Ping(payload))) => {
+                ws.send(Message::Pong(payload)).await?;
+                self.recv_event().await
+            }
+            Some(Ok(Message::Pong(_))) => {
+                self.recv_event().await
+            }
+            Some(Ok(Message::Close(_))) => {
+                self.connected = false;
+                Ok(None)
+            }
+            Some(Err(e)) => {
+                error!("Bitget WebSocket error: {}", e);
+                self.connected = false;
+                Err(e.into())
+            }
+            None => {
+                self.connected = false;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected && !self.watchdog.is_stale()
+    }
+
+    fn ws_endpoint(&self) -> &str {
+        &self.ws_url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_frames() {
+        let client = BitgetClient::new(false).with_subscribe_batch_size(3);
+        let msgs = client.build_subscription_msgs(&[
+            Subscription::agg_trade("BTCUSDT"),
+            Subscription::ticker_24h("BTCUSDT"),
+            Subscription::depth("ETHUSDT"),
+            Subscription::kline("BTCUSDT", KlineInterval::OneMinute),
+            Subscription::partial_depth("BTCUSDT", 5),
+            Subscription::funding_rate("BTCUSDT"),
+        ]);
+
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(
+            msgs[0],
+            json!({
+                "op": "subscribe",
+                "args": [
+                    { "instType": "USDT-FUTURES", "channel": "trade", "instId": "BTCUSDT" },
+                    { "instType": "USDT-FUTURES", "channel": "ticker", "instId": "BTCUSDT" },
+                    { "instType": "USDT-FUTURES", "channel": "books", "instId": "ETHUSDT" }
+                ]
+            })
+        );
+        let channels: Vec<&str> = msgs[1]["args"].as_array().unwrap().iter()
+            .map(|arg| arg["channel"].as_str().unwrap())
+            .collect();
+        assert_eq!(channels, vec!["candle1m", "books5"]);
+
+        let demo = BitgetClient::new(true).build_subscription_msgs(&[Subscription::agg_trade("BTCUSDT")]);
+        assert_eq!(demo[0]["args"][0]["instType"], "SUSDT-FUTURES");
+    }
+
+    #[test]
+    fn test_parse_trade_push() {
+        let client = BitgetClient::new(false);
+        let msg = r#"{
+            "action": "update",
+            "arg": {"instType": "USDT-FUTURES", "channel": "trade", "instId": "BTCUSDT"},
+            "data": [
+                {"ts": "1695716760565", "price": "27000.5", "size": "0.001", "side": "buy", "tradeId": "1111111111"},
+                {"ts": "1695716760566", "price": "27000.0", "size": "0.25", "side": "sell", "tradeId": "1111111112"}
+            ],
+            "ts": 1695716761589
+        }"#;
+
+        let events = client.parse_message(msg).unwrap();
+        assert_eq!(events.len(), 2);
+
+        let MarketEvent::AggTrade(buy) = &events[0] else {
+            panic!("Expected AggTrade event");
+        };
+        assert_eq!(buy.exchange, ExchangeType::Bitget);
+        assert_eq!(buy.symbol, "BTCUSDT");
+        assert_eq!(buy.price, 27000.5);
+        assert_eq!(buy.quantity, 0.001);
+        assert_eq!(buy.timestamp, 1695716760565);
+        assert_eq!(buy.trade_id, 1111111111);
+        assert!(!buy.is_buyer_maker);
+
+        let MarketEvent::AggTrade(sell) = &events[1] else {
+            panic!("Expected AggTrade event");
+        };
+        assert!(sell.is_buyer_maker);
+
+        // The snapshot replays trades from before the subscription
+        let snapshot = msg.replace("\"update\"", "\"snapshot\"");
+        assert!(client.parse_message(&snapshot).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pong_acks_and_errors() {
+        let client = BitgetClient::new(false);
+        let ack = r#"{"event":"subscribe","arg":{"instType":"USDT-FUTURES","channel":"trade","instId":"BTCUSDT"}}"#;
+        let error = r#"{"event":"error","code":30001,"msg":"instType:USDT-FUTURES,channel:trade,instId:BTCXYZ doesn't exist","arg":{"instType":"USDT-FUTURES","channel":"trade","instId":"BTCXYZ"}}"#;
+
+        assert!(client.parse_message("pong").unwrap().is_empty());
+        assert!(client.parse_message(ack).unwrap().is_empty());
+        match client.parse_message(error) {
+            Err(GatewayError::Exchange { code, .. }) => assert_eq!(code, "30001"),
+            other => panic!("Expected an exchange error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,746 @@
+//! Bybit V5 linear WebSocket implementation
+//!
+//! This module handles WebSocket connections to Bybit USDT perpetuals
+//! and parses incoming market data.
+
+use crate::exchange::{
+    Exchange, ExchangeType, MarketEvent, AggTrade, Kline, DepthUpdate, BookTicker,
+    Subscription, DataType, KlineInterval, check_depth_levels, RateLimiter, StaleWatchdog, ParseErrorMonitor,
+    now_ms,
+};
+use crate::metrics;
+use crate::redis_publisher::RedisPublisher;
+use crate::symbol;
+use crate::clock::{self, SharedClock};
+use crate::error::{GatewayError, Result};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::time;
+use tokio_tungstenite::{connect_async, tungstenite::Message, WebSocketStream};
+use tracing::{debug, error, info, warn};
+use url::Url;
+use uuid::Uuid;
+
+/// Bybit WebSocket endpoints
+pub const BYBIT_WS_LINEAR: &str = "wss://stream.bybit.com/v5/public/linear";
+pub const BYBIT_WS_TESTNET_LINEAR: &str = "wss://stream-testnet.bybit.com/v5/public/linear";
+
+/// Default number of topics per subscribe frame
+pub const DEFAULT_SUBSCRIBE_BATCH_SIZE: usize = 10;
+
+/// Control frames sent per second by default
+pub const DEFAULT_MESSAGES_PER_SECOND: u32 = 10;
+
+/// Order book depth requested for `DataType::Depth` without explicit levels
+const ORDERBOOK_DEPTH: u16 = 50;
+
+/// Bybit recommends an `{"op":"ping"}` every 20s to keep the connection open
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Levels offered by the linear orderbook topics
+pub const SUPPORTED_DEPTH_LEVELS: [u16; 4] = [1, 50, 200, 500];
+
+/// Bybit-specific WebSocket client
+pub struct BybitClient {
+    exchange_type: ExchangeType,
+    ws_url: String,
+    ws: Option<WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>>,
+    /// Active subscriptions, re-sent after a reconnect
+    subscriptions: Vec<Subscription>,
+    /// Events parsed from a frame but not yet returned; trade frames come in batches
+    pending: VecDeque<MarketEvent>,
+    /// Last full ticker per symbol, which deltas carrying only the changed fields are merged into
+    tickers: HashMap<String, Map<String, Value>>,
+    redis_publisher: Option<RedisPublisher>,
+    connected: bool,
+    subscribe_batch_size: usize,
+    /// Paces subscribe and unsubscribe frames
+    rate_limiter: RateLimiter,
+    /// Drops the connection once it goes silent
+    watchdog: StaleWatchdog,
+    /// Application-level ping timer, running while connected
+    keepalive: Option<time::Interval>,
+    /// Flags a connection whose frames mostly fail to parse
+    parse_errors: ParseErrorMonitor,
+    /// Publish partial book snapshots as best bid/ask tickers
+    derive_book_ticker: bool,
+    /// Stamps `received_at` on parsed events
+    clock: SharedClock,
+}
+
+impl BybitClient {
+    /// Create a new Bybit client
+    pub fn new(testnet: bool) -> Self {
+        let ws_url = if testnet {
+            BYBIT_WS_TESTNET_LINEAR.to_string()
+        } else {
+            BYBIT_WS_LINEAR.to_string()
+        };
+
+        Self {
+            exchange_type: ExchangeType::Bybit,
+            ws_url,
+            ws: None,
+            subscriptions: Vec::new(),
+            pending: VecDeque::new(),
+            tickers: HashMap::new(),
+            redis_publisher: None,
+            connected: false,
+            subscribe_batch_size: DEFAULT_SUBSCRIBE_BATCH_SIZE,
+            rate_limiter: RateLimiter::new(DEFAULT_MESSAGES_PER_SECOND, 1),
+            watchdog: StaleWatchdog::default(),
+            keepalive: None,
+            parse_errors: ParseErrorMonitor::default(),
+            derive_book_ticker: false,
+            clock: clock::system(),
+        }
+    }
+
+    /// Set the Redis publisher for forwarding events
+    pub fn with_redis_publisher(mut self, publisher: RedisPublisher) -> Self {
+        self.redis_publisher = Some(publisher);
+        self
+    }
+
+    /// Set how many topics are sent per subscribe frame
+    pub fn with_subscribe_batch_size(mut self, batch_size: usize) -> Self {
+        self.subscribe_batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set how many control frames may be sent per second
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limiter = RateLimiter::new(per_second, 1);
+        self
+    }
+
+    /// Treat the connection as dead after this long without a frame (zero disables)
+    pub fn with_stale_timeout(mut self, timeout: Duration) -> Self {
+        self.watchdog = StaleWatchdog::new(timeout);
+        self
+    }
+
+    /// Alarm on, and optionally reconnect after, a storm of unparseable frames
+    pub fn with_parse_error_monitor(mut self, monitor: ParseErrorMonitor) -> Self {
+        self.parse_errors = monitor;
+        self
+    }
+
+    /// Turn partial book snapshots into best bid/ask tickers before anything sees them
+    pub fn with_derived_book_ticker(mut self, enabled: bool) -> Self {
+        self.derive_book_ticker = enabled;
+        self
+    }
+
+    /// Stamp events with this clock instead of the host's, e.g. a mock in tests
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Convert a kline interval to Bybit notation (minutes, or D/W/M),
+    /// or `None` where Bybit has no such interval
+    fn bybit_interval(interval: KlineInterval) -> Option<&'static str> {
+        let interval = match interval {
+            KlineInterval::OneMinute => "1",
+            KlineInterval::ThreeMinutes => "3",
+            KlineInterval::FiveMinutes => "5",
+            KlineInterval::FifteenMinutes => "15",
+            KlineInterval::ThirtyMinutes => "30",
+            KlineInterval::OneHour => "60",
+            KlineInterval::TwoHours => "120",
+            KlineInterval::FourHours => "240",
+            KlineInterval::SixHours => "360",
+            KlineInterval::EightHours => return None,
+            KlineInterval::TwelveHours => "720",
+            KlineInterval::OneDay => "D",
+            KlineInterval::OneWeek => "W",
+            KlineInterval::OneMonth => "M",
+        };
+        Some(interval)
+    }
+
+    /// Convert a Bybit interval back to standard notation (e.g. 60 -> 1h)
+    fn standard_interval(interval: &str) -> String {
+        match interval {
+            "D" => "1d".to_string(),
+            "W" => "1w".to_string(),
+            "M" => "1M".to_string(),
+            minutes => match minutes.parse::<u32>() {
+                Ok(m) if m >= 60 && m % 60 == 0 => format!("{}h", m / 60),
+                Ok(m) => format!("{}m", m),
+                Err(_) => minutes.to_string(),
+            },
+        }
+    }
+
+    /// Get the Bybit topic for a subscription, or `None` if Bybit has no matching stream
+    fn topic(sub: &Subscription) -> Option<String> {
+        let symbol = symbol::to_exchange_symbol(ExchangeType::Bybit, &sub.symbol);
+        let topic = match sub.data_type {
+            DataType::AggTrade => format!("publicTrade.{}", symbol),
+            DataType::Kline | DataType::ContinuousKline => {
+                let interval = Self::bybit_interval(sub.interval.unwrap_or(KlineInterval::OneMinute))?;
+                format!("kline.{}.{}", interval, symbol)
+            }
+            DataType::BookTicker => format!("tickers.{}", symbol),
+            DataType::Depth => format!("orderbook.{}.{}", sub.depth_levels.unwrap_or(ORDERBOOK_DEPTH), symbol),
+            // Funding is only carried inside the tickers topic
+            DataType::FundingRate => return None,
+            DataType::Liquidation => return None,
+            // The tickers topic's 24h fields aren't parsed yet
+            DataType::Ticker24h => return None,
+            // Polled over REST rather than streamed
+            DataType::OpenInterest => return None,
+            // Derived by the gateway from book tickers and the NBBO
+            DataType::Nbbo | DataType::Opportunity => return None,
+        };
+        Some(topic)
+    }
+
+    /// Build one subscribe frame per batch of subscriptions
+    fn build_subscription_msgs(&self, subscriptions: &[Subscription]) -> Vec<Value> {
+        let topics: Vec<String> = subscriptions.iter().filter_map(Self::topic).collect();
+
+        topics
+            .chunks(self.subscribe_batch_size)
+            .map(|batch| json!({ "op": "subscribe", "args": batch }))
+            .collect()
+    }
+
+    /// Send subscribe frames with a pause between batches
+    async fn send_subscription_msgs(&mut self, msgs: Vec<Value>) -> Result<()> {
+        for msg in msgs {
+            self.rate_limiter.acquire().await;
+
+            if let Some(ref mut ws) = self.ws {
+                ws.send(Message::Text(msg.to_string())).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse a decimal string field
+    fn parse_f64(value: &Value, name: &str) -> Result<f64> {
+        Ok(value.as_str().ok_or_else(|| GatewayError::Parse(format!("Missing {}", name)))?
+            .parse::<f64>()?)
+    }
+
+    /// Parse `[price, size]` levels
+    fn parse_levels(levels: Option<&Value>) -> Vec<(f64, f64)> {
+        levels
+            .and_then(|l| l.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|level| {
+                        let price = level.get(0)?.as_str()?.parse::<f64>().ok()?;
+                        let qty = level.get(1)?.as_str()?.parse::<f64>().ok()?;
+                        Some((price, qty))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Fold a trade id into the `u64` events carry: linear trade ids are
+    /// UUIDs, so their two halves are XORed rather than dropped
+    fn trade_id(id: &str) -> Result<u64> {
+        if let Ok(id) = id.parse::<u64>() {
+            return Ok(id);
+        }
+        let (high, low) = Uuid::parse_str(id)
+            .map_err(|e| GatewayError::Parse(format!("Invalid trade id {}: {}", id, e)))?
+            .as_u64_pair();
+        Ok(high ^ low)
+    }
+
+    /// Parse public trade events from Bybit WebSocket message, one per trade in the batch
+    fn parse_trades(&self, data: &Value) -> Result<Vec<MarketEvent>> {
+        let trades = data.get("data")
+            .and_then(|d| d.as_array())
+            .ok_or_else(|| GatewayError::Parse("Missing trade data".to_string()))?;
+
+        trades
+            .iter()
+            .map(|trade| {
+                let symbol = trade["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
+                    .to_string();
+                let price = Self::parse_f64(&trade["p"], "price")?;
+                let quantity = Self::parse_f64(&trade["v"], "quantity")?;
+                let timestamp = trade["T"].as_i64().ok_or_else(|| GatewayError::Parse("Missing trade time".to_string()))?;
+                let side = trade["S"].as_str().ok_or_else(|| GatewayError::Parse("Missing side".to_string()))?;
+                let id = trade["i"].as_str().ok_or_else(|| GatewayError::Parse("Missing trade id".to_string()))?;
+
+                Ok(MarketEvent::AggTrade(AggTrade {
+                    exchange: self.exchange_type,
+                    symbol,
+                    price,
+                    quantity,
+                    timestamp,
+                    // Bybit reports the taker side, so a taker sell means the buyer was the maker
+                    is_buyer_maker: side == "Sell",
+                    trade_id: Self::trade_id(id)?,
+                    received_at: self.clock.now_ms(),
+                }))
+            })
+            .collect()
+    }
+
+    /// Parse kline event from Bybit WebSocket message
+    fn parse_kline(&self, data: &Value, symbol: &str) -> Result<MarketEvent> {
+        let candle = data.get("data")
+            .and_then(|d| d.as_array())
+            .and_then(|arr| arr.first())
+            .ok_or_else(|| GatewayError::Parse("Empty kline data".to_string()))?;
+
+        let interval = candle["interval"].as_str().ok_or_else(|| GatewayError::Parse("Missing interval".to_string()))?;
+
+        Ok(MarketEvent::Kline(Kline {
+            exchange: self.exchange_type,
+            symbol: symbol.to_string(),
+            interval: Self::standard_interval(interval),
+            open_time: candle["start"].as_i64().ok_or_else(|| GatewayError::Parse("Missing open time".to_string()))?,
+            close_time: candle["end"].as_i64().ok_or_else(|| GatewayError::Parse("Missing close time".to_string()))?,
+            open: Self::parse_f64(&candle["open"], "open")?,
+            high: Self::parse_f64(&candle["high"], "high")?,
+            low: Self::parse_f64(&candle["low"], "low")?,
+            close: Self::parse_f64(&candle["close"], "close")?,
+            volume: Self::parse_f64(&candle["volume"], "volume")?,
+            is_closed: candle["confirm"].as_bool().unwrap_or(false),
+            contract_type: None,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse ticker event from Bybit WebSocket message.
+    ///
+    /// Deltas only carry the fields that changed, so they are merged into the
+    /// symbol's last snapshot. A delta that leaves the top of book alone, or
+    /// arrives before any snapshot, yields no event.
+    fn parse_ticker(&mut self, data: &Value) -> Result<Option<MarketEvent>> {
+        let fields = data.get("data")
+            .and_then(|d| d.as_object())
+            .ok_or_else(|| GatewayError::Parse("Missing ticker data".to_string()))?;
+        let symbol = fields.get("symbol")
+            .and_then(|s| s.as_str())
+            .ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
+            .to_string();
+
+        let ticker = if data["type"].as_str() == Some("delta") {
+            let Some(last) = self.tickers.get_mut(&symbol) else {
+                return Ok(None);
+            };
+            last.extend(fields.clone());
+            if !fields.keys().any(|key| key.starts_with("bid1") || key.starts_with("ask1")) {
+                return Ok(None);
+            }
+            Value::Object(last.clone())
+        } else {
+            self.tickers.insert(symbol.clone(), fields.clone());
+            Value::Object(fields.clone())
+        };
+
+        let bid_price = Self::parse_f64(&ticker["bid1Price"], "bid price")?;
+        let bid_qty = Self::parse_f64(&ticker["bid1Size"], "bid qty")?;
+        let ask_price = Self::parse_f64(&ticker["ask1Price"], "ask price")?;
+        let ask_qty = Self::parse_f64(&ticker["ask1Size"], "ask qty")?;
+        let timestamp = data["ts"].as_i64()
+            .unwrap_or_else(now_ms);
+
+        Ok(Some(MarketEvent::BookTicker(BookTicker {
+            exchange: self.exchange_type,
+            symbol,
+            bid_price,
+            bid_qty,
+            ask_price,
+            ask_qty,
+            timestamp,
+            received_at: self.clock.now_ms(),
+        })))
+    }
+
+    /// Parse order book event from Bybit WebSocket message
+    fn parse_orderbook(&self, data: &Value) -> Result<MarketEvent> {
+        let book = data.get("data").ok_or_else(|| GatewayError::Parse("Missing orderbook data".to_string()))?;
+
+        let symbol = book["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
+            .to_string();
+        let timestamp = data["ts"].as_i64()
+            .unwrap_or_else(now_ms);
+
+        Ok(MarketEvent::DepthUpdate(DepthUpdate {
+            exchange: self.exchange_type,
+            symbol,
+            bids: Self::parse_levels(book.get("b")),
+            asks: Self::parse_levels(book.get("a")),
+            timestamp,
+            is_snapshot: data["type"].as_str() == Some("snapshot"),
+            first_update_id: None,
+            final_update_id: None,
+            prev_final_update_id: None,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse incoming message into market events
+    fn parse_message(&mut self, msg: &str) -> Result<Vec<MarketEvent>> {
+        let data: Value = serde_json::from_str(msg)?;
+
+        // Command responses, keepalive pongs included, carry an "op" field instead of a topic
+        if let Some(op) = data.get("op").and_then(|o| o.as_str()) {
+            if data["success"].as_bool() == Some(false) {
+                warn!("Bybit {} failed: {}", op, data["ret_msg"]);
+            } else {
+                debug!("Bybit {} response: {:?}", op, data);
+            }
+            return Ok(Vec::new());
+        }
+
+        let topic = data.get("topic")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| GatewayError::Parse("Missing topic".to_string()))?;
+
+        if topic.starts_with("publicTrade.") {
+            self.parse_trades(&data)
+        } else if let Some(rest) = topic.strip_prefix("kline.") {
+            let symbol = rest.split('.').nth(1).ok_or_else(|| GatewayError::Parse("Missing kline symbol".to_string()))?;
+            Ok(vec![self.parse_kline(&data, symbol)?])
+        } else if topic.starts_with("tickers.") {
+            Ok(self.parse_ticker(&data)?.into_iter().collect())
+        } else if topic.starts_with("orderbook.") {
+            Ok(vec![self.parse_orderbook(&data)?])
+        } else {
+            Err(GatewayError::Parse(format!("Unknown topic: {}", topic)))
+        }
+    }
+
+    /// Parse a text frame and forward the resulting event
+    async fn handle_text(&mut self, text: &str) -> Result<Option<MarketEvent>> {
+        // Raw frames are a debugging aid, only queued so they never hold up parsing
+        if let Some(ref publisher) = self.redis_publisher {
+            publisher.publish_raw(self.exchange_type, text);
+        }
+
+        match self.parse_message(text) {
+            Ok(mut events) => {
+                self.parse_errors.record_success();
+                if self.derive_book_ticker {
+                    events = events.into_iter().map(MarketEvent::derive_book_ticker).collect();
+                }
+                // Forward to Redis if configured
+                if let Some(ref publisher) = self.redis_publisher {
+                    for event in &events {
+                        if let Err(e) = publisher.publish_event(event).await {
+                            error!("Failed to publish event to Redis: {}", e);
+                        }
+                    }
+                }
+                self.pending.extend(events);
+                Ok(self.pending.pop_front())
+            }
+            Err(e) => {
+                debug!("Failed to parse Bybit message: {}", e);
+                metrics::global().record_parse_error(ExchangeType::Bybit);
+                if self.parse_errors.record_failure(self.exchange_type) {
+                    self.connected = false;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for BybitClient {
+    fn exchange_type(&self) -> ExchangeType {
+        self.exchange_type
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        info!("Connecting to Bybit WebSocket at {}", self.ws_url);
+
+        let url = Url::parse(&self.ws_url)?;
+        let (ws_stream, _) = connect_async(url).await
+            .map_err(|e| GatewayError::Connect(format!("Bybit connect failed: {}", e)))?;
+
+        self.ws = Some(ws_stream);
+        self.connected = true;
+        self.watchdog.touch();
+        // The resubscribe sends fresh snapshots, so nothing from the old connection carries over
+        self.pending.clear();
+        self.tickers.clear();
+        self.keepalive = Some(time::interval_at(
+            time::Instant::now() + KEEPALIVE_INTERVAL,
+            KEEPALIVE_INTERVAL,
+        ));
+
+        info!("Connected to Bybit WebSocket");
+
+        Ok(())
+    }
+
+    fn active_subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.clone()
+    }
+
+    async fn resubscribe(&mut self) -> Result<()> {
+        // Restore topics that were active before a reconnect
+        if !self.subscriptions.is_empty() {
+            info!("Restoring {} Bybit subscriptions", self.subscriptions.len());
+            let msgs = self.build_subscription_msgs(&self.subscriptions);
+            self.send_subscription_msgs(msgs).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(mut ws) = self.ws.take() {
+            ws.close(None).await?;
+        }
+        self.connected = false;
+        self.keepalive = None;
+        info!("Disconnected from Bybit");
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        info!("Subscribing to {} Bybit data streams", subscriptions.len());
+        check_depth_levels(self.exchange_type, &subscriptions, &SUPPORTED_DEPTH_LEVELS)?;
+
+        if !self.connected {
+            self.connect().await?;
+            self.resubscribe().await?;
+        }
+
+        // Topics restored by `resubscribe` are already active
+        let mut added = Vec::new();
+        for sub in subscriptions {
+            if !self.subscriptions.contains(&sub) && !added.contains(&sub) {
+                added.push(sub);
+            }
+        }
+        if added.is_empty() {
+            debug!("All requested Bybit topics are already subscribed");
+            return Ok(());
+        }
+
+        let msgs = self.build_subscription_msgs(&added);
+        self.send_subscription_msgs(msgs).await?;
+        self.subscriptions.extend(added);
+
+        info!("Bybit subscription request sent");
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, _subscriptions: Vec<Subscription>) -> Result<()> {
+        warn!("Unsubscribe not fully implemented for Bybit");
+        Ok(())
+    }
+
+    async fn recv_event(&mut self) -> Result<Option<MarketEvent>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(Some(event));
+        }
+        if !self.connected || self.ws.is_none() {
+            return Ok(None);
+        }
+
+        let ws = self.ws.as_mut().unwrap();
+
+        let keepalive = self.keepalive.as_mut();
+        let keepalive_tick = async move {
+            match keepalive {
+                Some(keepalive) => {
+                    keepalive.tick().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        let msg = tokio::select! {
+            msg = ws.next() => msg,
+            _ = keepalive_tick => {
+                debug!("Sending Bybit keepalive ping");
+                ws.send(Message::Text(json!({ "op": "ping" }).to_string())).await?;
+                return Ok(None);
+            }
+            _ = self.watchdog.expired() => {
+                warn!("No Bybit frame for {:?}, dropping the stale connection", self.watchdog.silence());
+                self.connected = false;
+                return Ok(None);
+            }
+        };
+        // Any frame, pings included, shows the connection is alive
+        self.watchdog.touch();
+
+        match msg {
+            Some(Ok(Message::Text(text))) => self.handle_text(&text).await,
+            Some(Ok(Message::Ping(payload))) => {
+                ws.send(Message::Pong(payload)).await?;
+                self.recv_event().await
+            }
+            Some(Ok(Message::Pong(_))) => {
+                self.recv_event().await
+            }
+            Some(Ok(Message::Close(_))) => {
+                self.connected = false;
+                Ok(None)
+            }
+            Some(Err(e)) => {
+                error!("Bybit WebSocket error: {}", e);
+                self.connected = false;
+                Err(e.into())
+            }
+            None => {
+                self.connected = false;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected && !self.watchdog.is_stale()
+    }
+
+    fn ws_endpoint(&self) -> &str {
+        &self.ws_url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_public_trade() {
+        let mut client = BybitClient::new(false);
+        let json = r#"{"topic":"publicTrade.BTCUSDT","type":"snapshot","ts":1672304486868,"data":[{"T":1672304486865,"s":"BTCUSDT","S":"Sell","v":"0.001","p":"16578.50","L":"PlusTick","i":"20f43950-d8dd-5b31-9112-a178eb6023af","BT":false}]}"#;
+
+        let result = client.parse_message(json).unwrap();
+
+        if let [MarketEvent::AggTrade(trade)] = result.as_slice() {
+            assert_eq!(trade.exchange, ExchangeType::Bybit);
+            assert_eq!(trade.symbol, "BTCUSDT");
+            assert_eq!(trade.price, 16578.5);
+            assert_eq!(trade.quantity, 0.001);
+            assert_eq!(trade.timestamp, 1672304486865);
+            assert!(trade.is_buyer_maker);
+        } else {
+            panic!("Expected one AggTrade event");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batched_trades_each_become_an_event() {
+        let mut client = BybitClient::new(false);
+        // Two fills in the same millisecond, told apart only by their ids
+        let json = r#"{"topic":"publicTrade.BTCUSDT","type":"snapshot","ts":1672304486868,"data":[{"T":1672304486865,"s":"BTCUSDT","S":"Sell","v":"0.001","p":"16578.50","i":"20f43950-d8dd-5b31-9112-a178eb6023af","BT":false},{"T":1672304486865,"s":"BTCUSDT","S":"Buy","v":"0.002","p":"16578.60","i":"a1b2c3d4-0000-5b31-9112-a178eb6023af","BT":false}]}"#;
+
+        let mut trades = Vec::new();
+        trades.push(client.handle_text(json).await.unwrap().unwrap());
+        // The second trade waits for the next receive instead of being dropped
+        trades.push(client.recv_event().await.unwrap().unwrap());
+
+        let [MarketEvent::AggTrade(first), MarketEvent::AggTrade(second)] = trades.as_slice() else {
+            panic!("Expected two AggTrade events");
+        };
+        assert_eq!((first.quantity, second.quantity), (0.001, 0.002));
+        assert!(!second.is_buyer_maker);
+        assert_eq!(first.timestamp, second.timestamp);
+        assert_ne!(first.trade_id, second.trade_id);
+        assert_eq!(first.trade_id, BybitClient::trade_id("20f43950-d8dd-5b31-9112-a178eb6023af").unwrap());
+        assert_eq!(BybitClient::trade_id("2290000000061666327").unwrap(), 2290000000061666327);
+        assert!(BybitClient::trade_id("not-a-uuid").is_err());
+    }
+
+    #[test]
+    fn test_parse_kline() {
+        let mut client = BybitClient::new(false);
+        let json = r#"{"topic":"kline.60.BTCUSDT","data":[{"start":1672322400000,"end":1672325999999,"interval":"60","open":"16649.5","close":"16677","high":"16677","low":"16608","volume":"2.081","turnover":"34666.4005","confirm":true,"timestamp":1672324988882}],"ts":1672324988882,"type":"snapshot"}"#;
+
+        let result = client.parse_message(json).unwrap();
+
+        if let [MarketEvent::Kline(kline)] = result.as_slice() {
+            assert_eq!(kline.symbol, "BTCUSDT");
+            assert_eq!(kline.interval, "1h");
+            assert_eq!(kline.open, 16649.5);
+            assert_eq!(kline.close, 16677.0);
+            assert_eq!(kline.close_time, 1672325999999);
+            assert!(kline.is_closed);
+        } else {
+            panic!("Expected Kline event");
+        }
+    }
+
+    #[test]
+    fn test_parse_ticker_and_topics() {
+        let mut client = BybitClient::new(false);
+        let json = r#"{"topic":"tickers.BTCUSDT","type":"snapshot","data":{"symbol":"BTCUSDT","tickDirection":"PlusTick","lastPrice":"17216.00","bid1Price":"17215.50","bid1Size":"84.489","ask1Price":"17216.00","ask1Size":"83.020"},"cs":24987956059,"ts":1673272861686}"#;
+
+        if let [MarketEvent::BookTicker(ticker)] = client.parse_message(json).unwrap().as_slice() {
+            assert_eq!(ticker.bid_price, 17215.5);
+            assert_eq!(ticker.ask_qty, 83.02);
+            assert_eq!(ticker.timestamp, 1673272861686);
+        } else {
+            panic!("Expected BookTicker event");
+        }
+
+        let sub = |data_type, interval| Subscription {
+            symbol: "BTCUSDT".to_string(),
+            data_type,
+            interval,
+            contract_type: None,
+            depth_levels: None,
+            update_speed: None,
+            okx_inst_type: None,
+        };
+        assert_eq!(BybitClient::topic(&sub(DataType::AggTrade, None)).unwrap(), "publicTrade.BTCUSDT");
+        assert_eq!(BybitClient::topic(&sub(DataType::Kline, Some(KlineInterval::FourHours))).unwrap(), "kline.240.BTCUSDT");
+        assert_eq!(BybitClient::topic(&sub(DataType::BookTicker, None)).unwrap(), "tickers.BTCUSDT");
+        assert_eq!(BybitClient::topic(&sub(DataType::Depth, None)).unwrap(), "orderbook.50.BTCUSDT");
+        assert!(BybitClient::topic(&sub(DataType::FundingRate, None)).is_none());
+        assert_eq!(BybitClient::topic(&sub(DataType::Kline, Some(KlineInterval::OneWeek))).unwrap(), "kline.W.BTCUSDT");
+        assert!(BybitClient::topic(&sub(DataType::Kline, Some(KlineInterval::EightHours))).is_none());
+        assert_eq!(BybitClient::topic(&Subscription::partial_depth("BTCUSDT", 200)).unwrap(), "orderbook.200.BTCUSDT");
+    }
+
+    #[test]
+    fn test_keepalive_pong_is_not_a_parse_error() {
+        let mut client = BybitClient::new(false);
+        let pong = r#"{"success":true,"ret_msg":"pong","conn_id":"0970e817-426e-429a-a679-ff7f55e0b16a","op":"ping"}"#;
+        assert!(client.parse_message(pong).unwrap().is_empty());
+
+        let subscribed = r#"{"success":true,"ret_msg":"","conn_id":"0970e817-426e-429a-a679-ff7f55e0b16a","req_id":"","op":"subscribe"}"#;
+        assert!(client.parse_message(subscribed).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ticker_deltas_merge_into_the_last_snapshot() {
+        let mut client = BybitClient::new(false);
+        let delta = |data: &str, ts: i64| format!(r#"{{"topic":"tickers.BTCUSDT","type":"delta","data":{},"cs":1,"ts":{}}}"#, data, ts);
+
+        // Nothing to merge into before the snapshot
+        assert!(client.parse_message(&delta(r#"{"symbol":"BTCUSDT","bid1Price":"17215.00"}"#, 1)).unwrap().is_empty());
+
+        let snapshot = r#"{"topic":"tickers.BTCUSDT","type":"snapshot","data":{"symbol":"BTCUSDT","lastPrice":"17216.00","bid1Price":"17215.50","bid1Size":"84.489","ask1Price":"17216.00","ask1Size":"83.020"},"cs":0,"ts":1673272861686}"#;
+        assert_eq!(client.parse_message(snapshot).unwrap().len(), 1);
+
+        // Only the bid size moved; the rest of the quote comes from the snapshot
+        let events = client.parse_message(&delta(r#"{"symbol":"BTCUSDT","bid1Size":"10.5"}"#, 1673272861786)).unwrap();
+        let [MarketEvent::BookTicker(ticker)] = events.as_slice() else {
+            panic!("Expected BookTicker event");
+        };
+        assert_eq!((ticker.bid_price, ticker.bid_qty, ticker.ask_price, ticker.ask_qty), (17215.5, 10.5, 17216.0, 83.02));
+        assert_eq!(ticker.timestamp, 1673272861786);
+
+        // A delta away from the top of book changes nothing worth emitting
+        assert!(client.parse_message(&delta(r#"{"symbol":"BTCUSDT","lastPrice":"17217.00"}"#, 2)).unwrap().is_empty());
+    }
+}
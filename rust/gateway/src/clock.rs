@@ -0,0 +1,33 @@
+//! Wall-clock time source
+//!
+//! Events are stamped with the wall-clock time they arrived. Clients read it
+//! through a `Clock` so tests can pin it; timers (staleness, backoff, pings)
+//! run on tokio time instead, which tests drive with `tokio::time::pause`
+//! and `advance`.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Source of wall-clock time
+pub trait Clock: Send + Sync + Debug {
+    /// Milliseconds since the Unix epoch
+    fn now_ms(&self) -> i64;
+}
+
+/// The host's clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+}
+
+/// Clock shared between a client and whoever built it
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Shared handle to the host's clock
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
@@ -0,0 +1,179 @@
+//! Depth update coalescing
+//!
+//! This module merges bursts of depth updates per exchange and symbol and
+//! hands them on at most once per window, so consumers that only need the
+//! freshest book state see fewer, larger updates.
+
+use crate::exchange::{DepthUpdate, ExchangeType};
+use crate::orderbook::Price;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// Updates merged since the first one of the current window
+#[derive(Debug)]
+struct Pending {
+    update: DepthUpdate,
+    since: Instant,
+}
+
+/// Per-symbol buffer that merges depth updates within a window
+#[derive(Debug)]
+pub struct DepthCoalescer {
+    window: Duration,
+    pending: HashMap<(ExchangeType, String), Pending>,
+}
+
+impl DepthCoalescer {
+    /// Create a coalescer emitting each symbol at most once per `window`
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Interval between emits per symbol
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Number of symbols with updates waiting
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Check if no updates are waiting
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Merge an update into its symbol's pending update, starting a window if there was none
+    pub fn push(&mut self, update: DepthUpdate, now: Instant) {
+        let key = (update.exchange, update.symbol.clone());
+        match self.pending.get_mut(&key) {
+            Some(pending) => merge(&mut pending.update, update),
+            None => {
+                self.pending.insert(key, Pending { update, since: now });
+            }
+        }
+    }
+
+    /// Take the updates whose window has closed by `now`
+    pub fn take_due(&mut self, now: Instant) -> Vec<DepthUpdate> {
+        let due: Vec<_> = self.pending
+            .iter()
+            .filter(|(_, pending)| now.duration_since(pending.since) >= self.window)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        due.into_iter()
+            .filter_map(|key| self.pending.remove(&key))
+            .map(|pending| pending.update)
+            .collect()
+    }
+
+    /// Take every pending update regardless of its window, e.g. on shutdown
+    pub fn take_all(&mut self) -> Vec<DepthUpdate> {
+        self.pending.drain().map(|(_, pending)| pending.update).collect()
+    }
+}
+
+/// Fold a later update into an earlier one; the later update's levels win.
+///
+/// A snapshot replaces everything before it. Diffs merged into a snapshot
+/// drop emptied levels; merged diffs keep them so consumers still see the removal.
+fn merge(base: &mut DepthUpdate, update: DepthUpdate) {
+    if update.is_snapshot {
+        *base = update;
+        return;
+    }
+
+    base.bids = merge_levels(&base.bids, &update.bids, base.is_snapshot, true);
+    base.asks = merge_levels(&base.asks, &update.asks, base.is_snapshot, false);
+    base.timestamp = update.timestamp;
+    base.received_at = update.received_at;
+    base.final_update_id = update.final_update_id.or(base.final_update_id);
+}
+
+/// Overlay `newer` levels on `older`, sorted best price first
+fn merge_levels(older: &[(f64, f64)], newer: &[(f64, f64)], drop_empty: bool, descending: bool) -> Vec<(f64, f64)> {
+    let mut levels: BTreeMap<Price, f64> = older.iter().map(|&(price, qty)| (Price(price), qty)).collect();
+    for &(price, qty) in newer {
+        levels.insert(Price(price), qty);
+    }
+    if drop_empty {
+        levels.retain(|_, qty| *qty != 0.0);
+    }
+
+    let levels = levels.into_iter().map(|(price, qty)| (price.0, qty));
+    if descending {
+        levels.rev().collect()
+    } else {
+        levels.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>, first: u64, last: u64) -> DepthUpdate {
+        DepthUpdate {
+            exchange: ExchangeType::Binance,
+            symbol: "BTCUSDT".to_string(),
+            bids,
+            asks,
+            timestamp: last as i64,
+            is_snapshot: false,
+            first_update_id: Some(first),
+            final_update_id: Some(last),
+            prev_final_update_id: Some(first - 1),
+            received_at: last as i64,
+        }
+    }
+
+    #[test]
+    fn test_updates_within_window_coalesce() {
+        let window = Duration::from_millis(250);
+        let mut coalescer = DepthCoalescer::new(window);
+        let start = Instant::now();
+
+        coalescer.push(diff(vec![(100.0, 1.0), (99.0, 2.0)], vec![(101.0, 1.0)], 10, 11), start);
+        coalescer.push(diff(vec![(100.0, 3.0)], vec![(102.0, 4.0)], 12, 13), start + Duration::from_millis(100));
+        coalescer.push(diff(vec![(99.0, 0.0)], vec![(101.0, 5.0)], 14, 15), start + Duration::from_millis(200));
+
+        assert!(coalescer.take_due(start + Duration::from_millis(200)).is_empty());
+        let emitted = coalescer.take_due(start + window);
+        assert_eq!(emitted.len(), 1);
+        assert!(coalescer.is_empty());
+
+        let merged = &emitted[0];
+        assert_eq!(merged.bids, vec![(100.0, 3.0), (99.0, 0.0)]);
+        assert_eq!(merged.asks, vec![(101.0, 5.0), (102.0, 4.0)]);
+        assert_eq!((merged.first_update_id, merged.final_update_id), (Some(10), Some(15)));
+        assert_eq!(merged.prev_final_update_id, Some(9));
+        assert_eq!(merged.timestamp, 15);
+    }
+
+    #[test]
+    fn test_snapshot_resets_and_symbols_are_separate() {
+        let mut coalescer = DepthCoalescer::new(Duration::from_millis(250));
+        let now = Instant::now();
+
+        let mut snapshot = diff(vec![(100.0, 1.0)], vec![(101.0, 1.0)], 20, 20);
+        snapshot.is_snapshot = true;
+        coalescer.push(diff(vec![(98.0, 1.0)], vec![], 10, 11), now);
+        coalescer.push(snapshot, now);
+        coalescer.push(diff(vec![(100.0, 0.0), (99.5, 2.0)], vec![], 21, 22), now);
+        let mut other = diff(vec![(3000.0, 1.0)], vec![], 1, 2);
+        other.symbol = "ETHUSDT".to_string();
+        coalescer.push(other, now);
+        assert_eq!(coalescer.len(), 2);
+
+        let mut emitted = coalescer.take_all();
+        emitted.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        assert_eq!(emitted[0].bids, vec![(99.5, 2.0)]);
+        assert!(emitted[0].is_snapshot);
+        assert_eq!(emitted[1].symbol, "ETHUSDT");
+    }
+}
@@ -0,0 +1,553 @@
+//! Coinbase Exchange WebSocket implementation
+//!
+//! This module handles WebSocket connections to the Coinbase Exchange
+//! spot feed and parses incoming market data.
+
+use crate::exchange::{
+    Exchange, ExchangeType, MarketEvent, AggTrade, DepthUpdate, BookTicker, Subscription, DataType,
+    check_depth_levels, RateLimiter, StaleWatchdog, ParseErrorMonitor, now_ms,
+};
+use crate::metrics;
+use crate::redis_publisher::RedisPublisher;
+use crate::symbol;
+use crate::clock::{self, SharedClock};
+use crate::error::{GatewayError, Result};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message, WebSocketStream};
+use tracing::{debug, error, info, warn};
+use url::Url;
+
+/// Coinbase WebSocket endpoints
+pub const COINBASE_WS: &str = "wss://ws-feed.exchange.coinbase.com";
+pub const COINBASE_WS_SANDBOX: &str = "wss://ws-feed-public.sandbox.exchange.coinbase.com";
+
+/// Default number of product IDs per subscribe frame
+pub const DEFAULT_SUBSCRIBE_BATCH_SIZE: usize = 50;
+
+/// Control frames sent per second by default
+pub const DEFAULT_MESSAGES_PER_SECOND: u32 = 10;
+
+/// Coinbase-specific WebSocket client
+pub struct CoinbaseClient {
+    exchange_type: ExchangeType,
+    ws_url: String,
+    ws: Option<WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>>,
+    /// Active subscriptions, re-sent after a reconnect
+    subscriptions: Vec<Subscription>,
+    redis_publisher: Option<RedisPublisher>,
+    connected: bool,
+    subscribe_batch_size: usize,
+    /// Paces subscribe and unsubscribe frames
+    rate_limiter: RateLimiter,
+    /// Drops the connection once it goes silent
+    watchdog: StaleWatchdog,
+    /// Flags a connection whose frames mostly fail to parse
+    parse_errors: ParseErrorMonitor,
+    /// Stamps `received_at` on parsed events
+    clock: SharedClock,
+}
+
+impl CoinbaseClient {
+    /// Create a new Coinbase client
+    pub fn new(sandbox: bool) -> Self {
+        let ws_url = if sandbox {
+            COINBASE_WS_SANDBOX.to_string()
+        } else {
+            COINBASE_WS.to_string()
+        };
+
+        Self {
+            exchange_type: ExchangeType::Coinbase,
+            ws_url,
+            ws: None,
+            subscriptions: Vec::new(),
+            redis_publisher: None,
+            connected: false,
+            subscribe_batch_size: DEFAULT_SUBSCRIBE_BATCH_SIZE,
+            rate_limiter: RateLimiter::new(DEFAULT_MESSAGES_PER_SECOND, 1),
+            watchdog: StaleWatchdog::default(),
+            parse_errors: ParseErrorMonitor::default(),
+            clock: clock::system(),
+        }
+    }
+
+    /// Set the Redis publisher for forwarding events
+    pub fn with_redis_publisher(mut self, publisher: RedisPublisher) -> Self {
+        self.redis_publisher = Some(publisher);
+        self
+    }
+
+    /// Set how many product IDs are sent per subscribe frame
+    pub fn with_subscribe_batch_size(mut self, batch_size: usize) -> Self {
+        self.subscribe_batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set how many control frames may be sent per second
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limiter = RateLimiter::new(per_second, 1);
+        self
+    }
+
+    /// Treat the connection as dead after this long without a frame (zero disables)
+    pub fn with_stale_timeout(mut self, timeout: Duration) -> Self {
+        self.watchdog = StaleWatchdog::new(timeout);
+        self
+    }
+
+    /// Alarm on, and optionally reconnect after, a storm of unparseable frames
+    pub fn with_parse_error_monitor(mut self, monitor: ParseErrorMonitor) -> Self {
+        self.parse_errors = monitor;
+        self
+    }
+
+    /// Stamp events with this clock instead of the host's, e.g. a mock in tests
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Convert a trading pair to a Coinbase product ID (e.g. BTCUSD -> BTC-USD).
+    /// Symbols with no known quote currency are passed through unchanged.
+    pub fn product_id(symbol: &str) -> String {
+        symbol::to_exchange_symbol(ExchangeType::Coinbase, symbol)
+    }
+
+    /// Convert a Coinbase product ID back to a standard symbol
+    pub fn standard_symbol(product_id: &str) -> String {
+        symbol::from_exchange_symbol(ExchangeType::Coinbase, product_id)
+    }
+
+    /// Get the Coinbase channel for a subscription, or `None` if Coinbase has no matching feed
+    fn channel(sub: &Subscription) -> Option<&'static str> {
+        match sub.data_type {
+            DataType::AggTrade => Some("matches"),
+            DataType::BookTicker => Some("ticker"),
+            // Plain `level2` requires an authenticated feed; the batched one is public
+            DataType::Depth => Some("level2_batch"),
+            // Spot only, so there are no klines, funding, liquidations or open interest
+            DataType::Kline
+            | DataType::ContinuousKline
+            | DataType::FundingRate
+            | DataType::Liquidation
+            | DataType::OpenInterest
+            | DataType::Nbbo
+            | DataType::Opportunity => None,
+            // The ticker channel's 24h fields aren't parsed yet
+            DataType::Ticker24h => None,
+        }
+    }
+
+    /// Build `type` frames for each channel, one per batch of product IDs
+    fn build_msgs(&self, msg_type: &str, subscriptions: &[Subscription]) -> Vec<Value> {
+        let mut channels: Vec<(&str, Vec<String>)> = Vec::new();
+        for sub in subscriptions {
+            let Some(channel) = Self::channel(sub) else {
+                continue;
+            };
+            let product_id = Self::product_id(&sub.symbol);
+
+            match channels.iter_mut().find(|(name, _)| *name == channel) {
+                Some((_, product_ids)) if product_ids.contains(&product_id) => {}
+                Some((_, product_ids)) => product_ids.push(product_id),
+                None => channels.push((channel, vec![product_id])),
+            }
+        }
+
+        channels.iter()
+            .flat_map(|(channel, product_ids)| {
+                product_ids.chunks(self.subscribe_batch_size).map(move |batch| {
+                    json!({ "type": msg_type, "product_ids": batch, "channels": [channel] })
+                })
+            })
+            .collect()
+    }
+
+    /// Build the subscribe frames for a set of subscriptions
+    fn build_subscription_msgs(&self, subscriptions: &[Subscription]) -> Vec<Value> {
+        self.build_msgs("subscribe", subscriptions)
+    }
+
+    /// Send frames with a pause between batches
+    async fn send_msgs(&mut self, msgs: Vec<Value>) -> Result<()> {
+        for msg in msgs {
+            self.rate_limiter.acquire().await;
+
+            if let Some(ref mut ws) = self.ws {
+                ws.send(Message::Text(msg.to_string())).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse a decimal string field
+    fn parse_f64(value: &Value, name: &str) -> Result<f64> {
+        Ok(value.as_str().ok_or_else(|| GatewayError::Parse(format!("Missing {}", name)))?
+            .parse::<f64>()?)
+    }
+
+    /// Parse an RFC 3339 `time` field into milliseconds, falling back to now
+    fn parse_time(data: &Value) -> i64 {
+        data["time"].as_str()
+            .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.timestamp_millis())
+            .unwrap_or_else(now_ms)
+    }
+
+    /// Parse `[price, size]` levels
+    fn parse_levels(levels: Option<&Value>) -> Vec<(f64, f64)> {
+        levels
+            .and_then(|l| l.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|level| {
+                        let price = level.get(0)?.as_str()?.parse::<f64>().ok()?;
+                        let qty = level.get(1)?.as_str()?.parse::<f64>().ok()?;
+                        Some((price, qty))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Parse match event from Coinbase WebSocket message
+    fn parse_match(&self, data: &Value, symbol: String) -> Result<MarketEvent> {
+        let price = Self::parse_f64(&data["price"], "price")?;
+        let quantity = Self::parse_f64(&data["size"], "size")?;
+        let trade_id = data["trade_id"].as_u64().ok_or_else(|| GatewayError::Parse("Missing trade_id".to_string()))?;
+        // Coinbase reports the maker's side
+        let side = data["side"].as_str().ok_or_else(|| GatewayError::Parse("Missing side".to_string()))?;
+
+        Ok(MarketEvent::AggTrade(AggTrade {
+            exchange: self.exchange_type,
+            symbol,
+            price,
+            quantity,
+            timestamp: Self::parse_time(data),
+            is_buyer_maker: side == "buy",
+            trade_id,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse ticker event from Coinbase WebSocket message
+    fn parse_ticker(&self, data: &Value, symbol: String) -> Result<MarketEvent> {
+        Ok(MarketEvent::BookTicker(BookTicker {
+            exchange: self.exchange_type,
+            symbol,
+            bid_price: Self::parse_f64(&data["best_bid"], "best_bid")?,
+            bid_qty: Self::parse_f64(&data["best_bid_size"], "best_bid_size")?,
+            ask_price: Self::parse_f64(&data["best_ask"], "best_ask")?,
+            ask_qty: Self::parse_f64(&data["best_ask_size"], "best_ask_size")?,
+            timestamp: Self::parse_time(data),
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse level2 snapshot or update from Coinbase WebSocket message
+    fn parse_level2(&self, data: &Value, symbol: String, is_snapshot: bool) -> Result<MarketEvent> {
+        let (bids, asks) = if is_snapshot {
+            (Self::parse_levels(data.get("bids")), Self::parse_levels(data.get("asks")))
+        } else {
+            let changes = data["changes"].as_array().ok_or_else(|| GatewayError::Parse("Missing changes".to_string()))?;
+            let mut bids = Vec::new();
+            let mut asks = Vec::new();
+            for change in changes {
+                let side = change[0].as_str().ok_or_else(|| GatewayError::Parse("Missing change side".to_string()))?;
+                let price = Self::parse_f64(&change[1], "price")?;
+                let size = Self::parse_f64(&change[2], "size")?;
+                match side {
+                    "buy" => bids.push((price, size)),
+                    _ => asks.push((price, size)),
+                }
+            }
+            (bids, asks)
+        };
+
+        Ok(MarketEvent::DepthUpdate(DepthUpdate {
+            exchange: self.exchange_type,
+            symbol,
+            bids,
+            asks,
+            timestamp: Self::parse_time(data),
+            is_snapshot,
+            first_update_id: None,
+            final_update_id: None,
+            prev_final_update_id: None,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse incoming message into a MarketEvent
+    fn parse_message(&self, msg: &str) -> Result<MarketEvent> {
+        let data: Value = serde_json::from_str(msg)?;
+
+        let msg_type = data.get("type")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| GatewayError::Parse("Missing type".to_string()))?;
+
+        match msg_type {
+            "subscriptions" => {
+                debug!("Coinbase subscriptions: {:?}", data["channels"]);
+                return Err(GatewayError::Parse("Subscription response".to_string()));
+            }
+            "error" => {
+                warn!("Coinbase error: {} ({})", data["message"], data["reason"]);
+                return Err(GatewayError::Subscription("Error response".to_string()));
+            }
+            _ => {}
+        }
+
+        let product_id = data["product_id"].as_str().ok_or_else(|| GatewayError::Parse("Missing product_id".to_string()))?;
+        let symbol = Self::standard_symbol(product_id);
+
+        match msg_type {
+            // `last_match` is sent once on subscribe with the most recent trade
+            "match" | "last_match" => self.parse_match(&data, symbol),
+            "ticker" => self.parse_ticker(&data, symbol),
+            "snapshot" => self.parse_level2(&data, symbol, true),
+            "l2update" => self.parse_level2(&data, symbol, false),
+            _ => Err(GatewayError::Parse(format!("Unknown message type: {}", msg_type))),
+        }
+    }
+
+    /// Parse a text frame and forward the resulting event
+    async fn handle_text(&mut self, text: &str) -> Result<Option<MarketEvent>> {
+        // Raw frames are a debugging aid, only queued so they never hold up parsing
+        if let Some(ref publisher) = self.redis_publisher {
+            publisher.publish_raw(self.exchange_type, text);
+        }
+
+        match self.parse_message(text) {
+            Ok(event) => {
+                self.parse_errors.record_success();
+                // Forward to Redis if configured
+                if let Some(ref mut publisher) = self.redis_publisher {
+                    if let Err(e) = publisher.publish_event(&event).await {
+                        error!("Failed to publish event to Redis: {}", e);
+                    }
+                }
+                Ok(Some(event))
+            }
+            Err(e) => {
+                debug!("Failed to parse Coinbase message: {}", e);
+                metrics::global().record_parse_error(ExchangeType::Coinbase);
+                if self.parse_errors.record_failure(self.exchange_type) {
+                    self.connected = false;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for CoinbaseClient {
+    fn exchange_type(&self) -> ExchangeType {
+        self.exchange_type
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        info!("Connecting to Coinbase WebSocket at {}", self.ws_url);
+
+        let url = Url::parse(&self.ws_url)?;
+        let (ws_stream, _) = connect_async(url).await
+            .map_err(|e| GatewayError::Connect(format!("Coinbase connect failed: {}", e)))?;
+
+        self.ws = Some(ws_stream);
+        self.connected = true;
+        self.watchdog.touch();
+
+        info!("Connected to Coinbase WebSocket");
+
+        Ok(())
+    }
+
+    fn active_subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.clone()
+    }
+
+    async fn resubscribe(&mut self) -> Result<()> {
+        // Restore channels that were active before a reconnect
+        if !self.subscriptions.is_empty() {
+            info!("Restoring {} Coinbase subscriptions", self.subscriptions.len());
+            let msgs = self.build_subscription_msgs(&self.subscriptions);
+            self.send_msgs(msgs).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(mut ws) = self.ws.take() {
+            ws.close(None).await?;
+        }
+        self.connected = false;
+        info!("Disconnected from Coinbase");
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        info!("Subscribing to {} Coinbase data streams", subscriptions.len());
+        // `level2_batch` always carries the full book
+        check_depth_levels(self.exchange_type, &subscriptions, &[])?;
+
+        if !self.connected {
+            self.connect().await?;
+            self.resubscribe().await?;
+        }
+
+        // Channels restored by `resubscribe` are already active
+        let mut added = Vec::new();
+        for sub in subscriptions {
+            if !self.subscriptions.contains(&sub) && !added.contains(&sub) {
+                added.push(sub);
+            }
+        }
+        if added.is_empty() {
+            debug!("All requested Coinbase channels are already subscribed");
+            return Ok(());
+        }
+
+        let msgs = self.build_subscription_msgs(&added);
+        self.send_msgs(msgs).await?;
+        self.subscriptions.extend(added);
+
+        info!("Coinbase subscription request sent");
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        let removed: Vec<Subscription> = self.subscriptions.iter()
+            .filter(|sub| subscriptions.contains(sub))
+            .cloned()
+            .collect();
+        if removed.is_empty() {
+            debug!("No Coinbase channels to unsubscribe");
+            return Ok(());
+        }
+        self.subscriptions.retain(|sub| !removed.contains(sub));
+
+        info!("Unsubscribing from {} Coinbase data streams", removed.len());
+        let msgs = self.build_msgs("unsubscribe", &removed);
+        self.send_msgs(msgs).await
+    }
+
+    async fn recv_event(&mut self) -> Result<Option<MarketEvent>> {
+        if !self.connected || self.ws.is_none() {
+            return Ok(None);
+        }
+
+        let ws = self.ws.as_mut().unwrap();
+
+        let msg = tokio::select! {
+            msg = ws.next() => msg,
+            _ = self.watchdog.expired() => {
+                warn!("No Coinbase frame for {:?}, dropping the stale connection", self.watchdog.silence());
+                self.connected = false;
+                return Ok(None);
+            }
+        };
+        // Any frame, pings included, shows the connection is alive
+        self.watchdog.touch();
+
+        match msg {
+            Some(Ok(Message::Text(text))) => self.handle_text(&text).await,
+            Some(Ok(Message::Ping(payload))) => {
+                ws.send(Message::Pong(payload)).await?;
+                self.recv_event().await
+            }
+            Some(Ok(Message::Pong(_))) => {
+                self.recv_event().await
+            }
+            Some(Ok(Message::Close(_))) => {
+                self.connected = false;
+                Ok(None)
+            }
+            Some(Err(e)) => {
+                error!("Coinbase WebSocket error: {}", e);
+                self.connected = false;
+                Err(e.into())
+            }
+            None => {
+                self.connected = false;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected && !self.watchdog.is_stale()
+    }
+
+    fn ws_endpoint(&self) -> &str {
+        &self.ws_url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_match() {
+        let client = CoinbaseClient::new(false);
+        let json = r#"{"type":"match","trade_id":10,"sequence":50,"maker_order_id":"ac928c66-ca53-498f-9c13-a110027a60e8","taker_order_id":"132fb6ae-456b-4654-b4e0-d681ac05cea1","time":"2014-11-07T08:19:27.028459Z","product_id":"BTC-USD","size":"5.23512","price":"400.23","side":"sell"}"#;
+
+        if let Ok(MarketEvent::AggTrade(trade)) = client.parse_message(json) {
+            assert_eq!(trade.exchange, ExchangeType::Coinbase);
+            assert_eq!(trade.symbol, "BTCUSD");
+            assert_eq!(trade.price, 400.23);
+            assert_eq!(trade.quantity, 5.23512);
+            assert_eq!(trade.timestamp, 1415348367028);
+            assert_eq!(trade.trade_id, 10);
+            // The maker sold, so the taker bought
+            assert!(!trade.is_buyer_maker);
+        } else {
+            panic!("Expected AggTrade event");
+        }
+    }
+
+    #[test]
+    fn test_parse_ticker() {
+        let client = CoinbaseClient::new(false);
+        let json = r#"{"type":"ticker","sequence":37475248783,"product_id":"ETH-USD","price":"1285.22","open_24h":"1310.79","volume_24h":"245532.79269678","low_24h":"1280.52","high_24h":"1313.8","volume_30d":"9788783.60117027","best_bid":"1285.04","best_bid_size":"0.46688654","best_ask":"1285.27","best_ask_size":"1.56637040","side":"buy","time":"2022-10-19T23:28:22.061769Z","trade_id":370843401,"last_size":"11.4396987"}"#;
+
+        if let Ok(MarketEvent::BookTicker(ticker)) = client.parse_message(json) {
+            assert_eq!(ticker.symbol, "ETHUSD");
+            assert_eq!(ticker.bid_price, 1285.04);
+            assert_eq!(ticker.bid_qty, 0.46688654);
+            assert_eq!(ticker.ask_price, 1285.27);
+            assert_eq!(ticker.ask_qty, 1.5663704);
+            assert_eq!(ticker.timestamp, 1666222102061);
+        } else {
+            panic!("Expected BookTicker event");
+        }
+    }
+
+    #[test]
+    fn test_subscription_msgs() {
+        let client = CoinbaseClient::new(false);
+        let subscriptions = vec![
+            Subscription::agg_trade("BTCUSD"),
+            Subscription::agg_trade("ETHUSDC"),
+            Subscription::book_ticker("BTCUSD"),
+            Subscription::funding_rate("BTCUSD"),
+        ];
+
+        assert_eq!(
+            client.build_subscription_msgs(&subscriptions),
+            vec![
+                json!({ "type": "subscribe", "product_ids": ["BTC-USD", "ETH-USDC"], "channels": ["matches"] }),
+                json!({ "type": "subscribe", "product_ids": ["BTC-USD"], "channels": ["ticker"] }),
+            ]
+        );
+        assert_eq!(CoinbaseClient::standard_symbol("ETH-USDC"), "ETHUSDC");
+    }
+}
@@ -0,0 +1,689 @@
+//! Deribit WebSocket implementation
+//!
+//! This module handles WebSocket connections to Deribit's JSON-RPC API and
+//! parses incoming market data for perpetuals, futures and options. Canonical
+//! symbols map to perpetuals (BTCUSDT -> BTC-PERPETUAL); any name containing
+//! a `-` is taken as a native Deribit instrument (e.g. BTC-27DEC24-100000-C).
+
+use crate::exchange::{
+    Exchange, ExchangeType, MarketEvent, AggTrade, Kline, DepthUpdate, BookTicker, Subscription,
+    DataType, KlineInterval, check_depth_levels, RateLimiter, StaleWatchdog, ParseErrorMonitor, now_ms,
+};
+use crate::metrics;
+use crate::redis_publisher::RedisPublisher;
+use crate::symbol;
+use crate::clock::{self, SharedClock};
+use crate::error::{GatewayError, Result};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message, WebSocketStream};
+use tracing::{debug, error, info, warn};
+
+/// Production JSON-RPC endpoint
+pub const DERIBIT_WS: &str = "wss://www.deribit.com/ws/api/v2";
+
+/// Testnet JSON-RPC endpoint
+pub const DERIBIT_TESTNET_WS: &str = "wss://test.deribit.com/ws/api/v2";
+
+/// Default number of channels per `public/subscribe` request
+pub const DEFAULT_SUBSCRIBE_BATCH_SIZE: usize = 100;
+
+/// Levels offered by the grouped `book.<instrument>.none.<depth>` channels
+pub const SUPPORTED_DEPTH_LEVELS: [u16; 3] = [1, 10, 20];
+
+/// Seconds between server heartbeats; Deribit closes the connection if a test request goes unanswered
+const HEARTBEAT_INTERVAL_SECS: u64 = 30;
+
+/// Requests sent per second by default (Deribit's non-matching engine credits refill at 20/s)
+pub const DEFAULT_MESSAGES_PER_SECOND: u32 = 5;
+
+/// Deribit-specific WebSocket client
+pub struct DeribitClient {
+    exchange_type: ExchangeType,
+    ws_url: String,
+    ws: Option<WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>>,
+    /// Active subscriptions, re-sent after a reconnect
+    subscriptions: Vec<Subscription>,
+    /// Symbol each subscribed instrument was requested as, so events carry the caller's name
+    symbols: HashMap<String, String>,
+    /// Events parsed from a notification but not yet returned; trade notifications come in batches
+    pending: VecDeque<MarketEvent>,
+    redis_publisher: Option<RedisPublisher>,
+    connected: bool,
+    subscribe_batch_size: usize,
+    /// Paces subscribe and unsubscribe requests
+    rate_limiter: RateLimiter,
+    /// Drops the connection once it goes silent
+    watchdog: StaleWatchdog,
+    /// Flags a connection whose frames mostly fail to parse
+    parse_errors: ParseErrorMonitor,
+    /// Publish partial book snapshots as best bid/ask tickers
+    derive_book_ticker: bool,
+    /// Stamps `received_at` on parsed events
+    clock: SharedClock,
+    /// ID of the next request we send
+    next_id: u64,
+}
+
+impl DeribitClient {
+    /// Create a new Deribit client
+    pub fn new(testnet: bool) -> Self {
+        let ws_url = if testnet { DERIBIT_TESTNET_WS } else { DERIBIT_WS };
+
+        Self {
+            exchange_type: ExchangeType::Deribit,
+            ws_url: ws_url.to_string(),
+            ws: None,
+            subscriptions: Vec::new(),
+            symbols: HashMap::new(),
+            pending: VecDeque::new(),
+            redis_publisher: None,
+            connected: false,
+            subscribe_batch_size: DEFAULT_SUBSCRIBE_BATCH_SIZE,
+            rate_limiter: RateLimiter::new(DEFAULT_MESSAGES_PER_SECOND, 1),
+            watchdog: StaleWatchdog::default(),
+            parse_errors: ParseErrorMonitor::default(),
+            derive_book_ticker: false,
+            clock: clock::system(),
+            next_id: 1,
+        }
+    }
+
+    /// Set the Redis publisher for forwarding events
+    pub fn with_redis_publisher(mut self, publisher: RedisPublisher) -> Self {
+        self.redis_publisher = Some(publisher);
+        self
+    }
+
+    /// Set how many channels are sent per subscribe request
+    pub fn with_subscribe_batch_size(mut self, batch_size: usize) -> Self {
+        self.subscribe_batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set how many requests may be sent per second
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limiter = RateLimiter::new(per_second, 1);
+        self
+    }
+
+    /// Treat the connection as dead after this long without a frame (zero disables)
+    pub fn with_stale_timeout(mut self, timeout: Duration) -> Self {
+        self.watchdog = StaleWatchdog::new(timeout);
+        self
+    }
+
+    /// Alarm on, and optionally reconnect after, a storm of unparseable frames
+    pub fn with_parse_error_monitor(mut self, monitor: ParseErrorMonitor) -> Self {
+        self.parse_errors = monitor;
+        self
+    }
+
+    /// Turn partial book snapshots into best bid/ask tickers before anything sees them
+    pub fn with_derived_book_ticker(mut self, enabled: bool) -> Self {
+        self.derive_book_ticker = enabled;
+        self
+    }
+
+    /// Stamp events with this clock instead of the host's, e.g. a mock in tests
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Convert a trading pair to a Deribit instrument (e.g. BTCUSDT -> BTC-PERPETUAL).
+    /// Native instrument names are passed through unchanged.
+    pub fn to_deribit(symbol: &str) -> String {
+        if symbol.contains('-') {
+            symbol.to_string()
+        } else {
+            symbol::to_exchange_symbol(ExchangeType::Deribit, symbol)
+        }
+    }
+
+    /// Get the Deribit channel for a subscription, or `None` if Deribit has no matching feed
+    fn channel(sub: &Subscription) -> Option<String> {
+        let instrument = Self::to_deribit(&sub.symbol);
+        match sub.data_type {
+            DataType::AggTrade => Some(format!("trades.{}.100ms", instrument)),
+            DataType::BookTicker => Some(format!("ticker.{}.100ms", instrument)),
+            DataType::Depth => match sub.depth_levels {
+                Some(levels) => Some(format!("book.{}.none.{}.100ms", instrument, levels)),
+                None => Some(format!("book.{}.100ms", instrument)),
+            },
+            DataType::Kline => {
+                let interval = sub.interval.unwrap_or(KlineInterval::OneMinute);
+                interval.as_deribit_str()
+                    .map(|resolution| format!("chart.trades.{}.{}", instrument, resolution))
+            }
+            // Funding and open interest ride on the ticker, and liquidations on trades;
+            // they aren't split out into separate feeds yet
+            DataType::ContinuousKline
+            | DataType::FundingRate
+            | DataType::Liquidation
+            | DataType::OpenInterest
+            | DataType::Nbbo
+            | DataType::Opportunity => None,
+            // The ticker's 24h stats aren't parsed yet
+            DataType::Ticker24h => None,
+        }
+    }
+
+    /// Build JSON-RPC requests for `method`, one per batch of channels. IDs are added when sent.
+    fn build_msgs(&self, method: &str, subscriptions: &[Subscription]) -> Vec<Value> {
+        let mut channels: Vec<String> = Vec::new();
+        for channel in subscriptions.iter().filter_map(Self::channel) {
+            if !channels.contains(&channel) {
+                channels.push(channel);
+            }
+        }
+
+        channels.chunks(self.subscribe_batch_size)
+            .map(|batch| {
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": method,
+                    "params": { "channels": batch },
+                })
+            })
+            .collect()
+    }
+
+    /// Build the subscribe requests for a set of subscriptions
+    fn build_subscription_msgs(&self, subscriptions: &[Subscription]) -> Vec<Value> {
+        self.build_msgs("public/subscribe", subscriptions)
+    }
+
+    /// Remember which symbol each instrument was requested as
+    fn track_symbols(&mut self, subscriptions: &[Subscription]) {
+        for sub in subscriptions {
+            self.symbols.insert(Self::to_deribit(&sub.symbol), sub.symbol.clone());
+        }
+    }
+
+    /// Symbol reported for an instrument
+    fn standard_symbol(&self, instrument: &str) -> String {
+        self.symbols.get(instrument)
+            .cloned()
+            .unwrap_or_else(|| symbol::from_exchange_symbol(ExchangeType::Deribit, instrument))
+    }
+
+    /// Send a request, stamping it with the next ID
+    async fn send_request(&mut self, mut msg: Value) -> Result<()> {
+        msg["id"] = json!(self.next_id);
+        self.next_id += 1;
+        if let Some(ref mut ws) = self.ws {
+            ws.send(Message::Text(msg.to_string())).await?;
+        }
+        Ok(())
+    }
+
+    /// Send requests with a pause between batches
+    async fn send_msgs(&mut self, msgs: Vec<Value>) -> Result<()> {
+        for msg in msgs {
+            self.rate_limiter.acquire().await;
+            self.send_request(msg).await?;
+        }
+        Ok(())
+    }
+
+    /// Read a numeric field
+    fn parse_f64(value: &Value, name: &str) -> Result<f64> {
+        value.as_f64().ok_or_else(|| GatewayError::Parse(format!("Missing {}", name)))
+    }
+
+    /// Parse `[price, amount]` levels, or `[action, price, amount]` levels from the incremental book.
+    /// Deleted levels come through with a zero amount.
+    fn parse_levels(levels: Option<&Value>) -> Vec<(f64, f64)> {
+        levels
+            .and_then(|l| l.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|level| {
+                        let level = level.as_array()?;
+                        let offset = level.len().checked_sub(2)?;
+                        let price = level[offset].as_f64()?;
+                        let qty = level[offset + 1].as_f64()?;
+                        Some((price, qty))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Parse a `trades` notification, which carries every trade since the last push
+    fn parse_trades(&self, data: &Value) -> Result<Vec<MarketEvent>> {
+        let trades = data.as_array().ok_or_else(|| GatewayError::Parse("Missing trades array".to_string()))?;
+
+        trades.iter()
+            .map(|trade| {
+                let instrument = trade["instrument_name"].as_str()
+                    .ok_or_else(|| GatewayError::Parse("Missing instrument_name".to_string()))?;
+                // Deribit reports the taker's side
+                let direction = trade["direction"].as_str().ok_or_else(|| GatewayError::Parse("Missing direction".to_string()))?;
+
+                Ok(MarketEvent::AggTrade(AggTrade {
+                    exchange: self.exchange_type,
+                    symbol: self.standard_symbol(instrument),
+                    price: Self::parse_f64(&trade["price"], "price")?,
+                    // Contracts for inverse instruments are USD, otherwise the base currency
+                    quantity: Self::parse_f64(&trade["amount"], "amount")?,
+                    timestamp: trade["timestamp"].as_i64().unwrap_or_else(now_ms),
+                    is_buyer_maker: direction == "sell",
+                    trade_id: trade["trade_seq"].as_u64().ok_or_else(|| GatewayError::Parse("Missing trade_seq".to_string()))?,
+                    received_at: self.clock.now_ms(),
+                }))
+            })
+            .collect()
+    }
+
+    /// Parse a `ticker` notification into the best bid and ask
+    fn parse_ticker(&self, data: &Value) -> Result<MarketEvent> {
+        let instrument = data["instrument_name"].as_str().ok_or_else(|| GatewayError::Parse("Missing instrument_name".to_string()))?;
+
+        Ok(MarketEvent::BookTicker(BookTicker {
+            exchange: self.exchange_type,
+            symbol: self.standard_symbol(instrument),
+            bid_price: Self::parse_f64(&data["best_bid_price"], "best_bid_price")?,
+            bid_qty: Self::parse_f64(&data["best_bid_amount"], "best_bid_amount")?,
+            ask_price: Self::parse_f64(&data["best_ask_price"], "best_ask_price")?,
+            ask_qty: Self::parse_f64(&data["best_ask_amount"], "best_ask_amount")?,
+            timestamp: data["timestamp"].as_i64().unwrap_or_else(now_ms),
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse a `book` notification. Grouped books are always snapshots; the
+    /// incremental book starts with a snapshot and chains changes by `change_id`.
+    fn parse_book(&self, data: &Value) -> Result<MarketEvent> {
+        let instrument = data["instrument_name"].as_str().ok_or_else(|| GatewayError::Parse("Missing instrument_name".to_string()))?;
+        let change_id = data["change_id"].as_u64();
+        let is_snapshot = data["type"].as_str().is_none_or(|kind| kind == "snapshot");
+
+        Ok(MarketEvent::DepthUpdate(DepthUpdate {
+            exchange: self.exchange_type,
+            symbol: self.standard_symbol(instrument),
+            bids: Self::parse_levels(data.get("bids")),
+            asks: Self::parse_levels(data.get("asks")),
+            timestamp: data["timestamp"].as_i64().unwrap_or_else(now_ms),
+            is_snapshot,
+            first_update_id: change_id,
+            final_update_id: change_id,
+            prev_final_update_id: data["prev_change_id"].as_u64(),
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse a `chart.trades` notification for the candle in progress
+    fn parse_chart(&self, data: &Value, instrument: &str, resolution: &str) -> Result<MarketEvent> {
+        let interval = KlineInterval::from_deribit_str(resolution)
+            .ok_or_else(|| GatewayError::Parse(format!("Unknown chart resolution: {}", resolution)))?;
+        let open_time = data["tick"].as_i64().ok_or_else(|| GatewayError::Parse("Missing tick".to_string()))?;
+        let minutes = match resolution {
+            "1D" => 1_440,
+            minutes => minutes.parse::<i64>()?,
+        };
+
+        Ok(MarketEvent::Kline(Kline {
+            exchange: self.exchange_type,
+            symbol: self.standard_symbol(instrument),
+            interval: interval.as_str().to_string(),
+            open_time,
+            close_time: open_time + minutes * 60_000 - 1,
+            open: Self::parse_f64(&data["open"], "open")?,
+            high: Self::parse_f64(&data["high"], "high")?,
+            low: Self::parse_f64(&data["low"], "low")?,
+            close: Self::parse_f64(&data["close"], "close")?,
+            volume: Self::parse_f64(&data["volume"], "volume")?,
+            // Deribit only pushes updates to the open candle
+            is_closed: false,
+            contract_type: None,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse a JSON-RPC frame into market events; responses and heartbeats yield none
+    fn parse_value(&self, data: &Value) -> Result<Vec<MarketEvent>> {
+        if let Some(err) = data.get("error") {
+            warn!("Deribit error for request {}: {} ({})", data["id"], err["message"], err["code"]);
+            return Err(GatewayError::Subscription("Error response".to_string()));
+        }
+        if data.get("result").is_some() {
+            return Ok(Vec::new());
+        }
+
+        let method = data["method"].as_str().ok_or_else(|| GatewayError::Parse("Missing method".to_string()))?;
+        match method {
+            "heartbeat" => return Ok(Vec::new()),
+            "subscription" => {}
+            _ => return Err(GatewayError::Parse(format!("Unknown method: {}", method))),
+        }
+
+        let channel = data["params"]["channel"].as_str().ok_or_else(|| GatewayError::Parse("Missing channel".to_string()))?;
+        let payload = &data["params"]["data"];
+        let parts: Vec<&str> = channel.split('.').collect();
+
+        match parts.as_slice() {
+            ["trades", ..] => self.parse_trades(payload),
+            ["ticker", ..] => Ok(vec![self.parse_ticker(payload)?]),
+            ["book", ..] => Ok(vec![self.parse_book(payload)?]),
+            ["chart", "trades", instrument, resolution] => {
+                Ok(vec![self.parse_chart(payload, instrument, resolution)?])
+            }
+            _ => Err(GatewayError::Parse(format!("Unknown channel: {}", channel))),
+        }
+    }
+
+    /// Whether a frame is a heartbeat that must be answered to keep the connection
+    fn is_test_request(data: &Value) -> bool {
+        data["method"] == "heartbeat" && data["params"]["type"] == "test_request"
+    }
+
+    /// Parse a text frame, forward the resulting events and return the first
+    async fn handle_text(&mut self, text: &str) -> Result<Option<MarketEvent>> {
+        // Raw frames are a debugging aid, only queued so they never hold up parsing
+        if let Some(ref publisher) = self.redis_publisher {
+            publisher.publish_raw(self.exchange_type, text);
+        }
+
+        let parsed = serde_json::from_str::<Value>(text)
+            .map_err(GatewayError::from)
+            .and_then(|data| {
+                Ok((Self::is_test_request(&data), self.parse_value(&data)?))
+            });
+
+        match parsed {
+            Ok((test_request, mut events)) => {
+                self.parse_errors.record_success();
+                if self.derive_book_ticker {
+                    events = events.into_iter().map(MarketEvent::derive_book_ticker).collect();
+                }
+                if test_request {
+                    debug!("Answering Deribit heartbeat");
+                    self.send_request(json!({ "jsonrpc": "2.0", "method": "public/test", "params": {} })).await?;
+                }
+
+                // Forward to Redis if configured
+                if let Some(ref mut publisher) = self.redis_publisher {
+                    for event in &events {
+                        if let Err(e) = publisher.publish_event(event).await {
+                            error!("Failed to publish event to Redis: {}", e);
+                        }
+                    }
+                }
+                self.pending.extend(events);
+                Ok(self.pending.pop_front())
+            }
+            Err(e) => {
+                debug!("Failed to parse Deribit message: {}", e);
+                metrics::global().record_parse_error(self.exchange_type);
+                if self.parse_errors.record_failure(self.exchange_type) {
+                    self.connected = false;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for DeribitClient {
+    fn exchange_type(&self) -> ExchangeType {
+        self.exchange_type
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        info!("Connecting to Deribit WebSocket at {}", self.ws_url);
+
+        let (ws_stream, _) = connect_async(&self.ws_url).await
+            .map_err(|e| GatewayError::Connect(format!("Deribit connect failed: {}", e)))?;
+        self.ws = Some(ws_stream);
+        self.connected = true;
+        self.watchdog.touch();
+        self.pending.clear();
+
+        // Ask the server for heartbeats so a dead connection is noticed
+        self.send_request(json!({
+            "jsonrpc": "2.0",
+            "method": "public/set_heartbeat",
+            "params": { "interval": HEARTBEAT_INTERVAL_SECS },
+        })).await?;
+
+        info!("Connected to Deribit WebSocket");
+
+        Ok(())
+    }
+
+    fn active_subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.clone()
+    }
+
+    async fn resubscribe(&mut self) -> Result<()> {
+        // Restore channels that were active before a reconnect
+        if !self.subscriptions.is_empty() {
+            info!("Restoring {} Deribit subscriptions", self.subscriptions.len());
+            let msgs = self.build_subscription_msgs(&self.subscriptions);
+            self.send_msgs(msgs).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(mut ws) = self.ws.take() {
+            ws.close(None).await?;
+        }
+        self.connected = false;
+        info!("Disconnected from Deribit");
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        info!("Subscribing to {} Deribit data streams", subscriptions.len());
+        check_depth_levels(self.exchange_type, &subscriptions, &SUPPORTED_DEPTH_LEVELS)?;
+
+        if !self.connected {
+            self.connect().await?;
+            self.resubscribe().await?;
+        }
+
+        // Channels restored by `resubscribe` are already active
+        let mut added = Vec::new();
+        for sub in subscriptions {
+            if !self.subscriptions.contains(&sub) && !added.contains(&sub) {
+                added.push(sub);
+            }
+        }
+        if added.is_empty() {
+            debug!("All requested Deribit channels are already subscribed");
+            return Ok(());
+        }
+
+        self.track_symbols(&added);
+        let msgs = self.build_subscription_msgs(&added);
+        self.send_msgs(msgs).await?;
+        self.subscriptions.extend(added);
+
+        info!("Deribit subscription request sent");
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        let removed: Vec<Subscription> = self.subscriptions.iter()
+            .filter(|sub| subscriptions.contains(sub))
+            .cloned()
+            .collect();
+        if removed.is_empty() {
+            debug!("No Deribit channels to unsubscribe");
+            return Ok(());
+        }
+        self.subscriptions.retain(|sub| !removed.contains(sub));
+
+        info!("Unsubscribing from {} Deribit data streams", removed.len());
+        let msgs = self.build_msgs("public/unsubscribe", &removed);
+        self.send_msgs(msgs).await
+    }
+
+    async fn recv_event(&mut self) -> Result<Option<MarketEvent>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(Some(event));
+        }
+        if !self.connected || self.ws.is_none() {
+            return Ok(None);
+        }
+
+        let ws = self.ws.as_mut().unwrap();
+
+        let msg = tokio::select! {
+            msg = ws.next() => msg,
+            _ = self.watchdog.expired() => {
+                warn!("No Deribit frame for {:?}, dropping the stale connection", self.watchdog.silence());
+                self.connected = false;
+                return Ok(None);
+            }
+        };
+        // Any frame, pings included, shows the connection is alive
+        self.watchdog.touch();
+
+        match msg {
+            Some(Ok(Message::Text(text))) => self.handle_text(&text).await,
+            Some(Ok(Message::Ping(payload))) => {
+                ws.send(Message::Pong(payload)).await?;
+                self.recv_event().await
+            }
+            Some(Ok(Message::Pong(_))) => {
+                self.recv_event().await
+            }
+            Some(Ok(Message::Close(_))) => {
+                self.connected = false;
+                Ok(None)
+            }
+            Some(Err(e)) => {
+                error!("Deribit WebSocket error: {}", e);
+                self.connected = false;
+                Err(e.into())
+            }
+            None => {
+                self.connected = false;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected && !self.watchdog.is_stale()
+    }
+
+    fn ws_endpoint(&self) -> &str {
+        &self.ws_url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(client: &DeribitClient, msg: &str) -> Result<Vec<MarketEvent>> {
+        client.parse_value(&serde_json::from_str(msg)?)
+    }
+
+    #[test]
+    fn test_channel_mapping() {
+        let channel = |sub: Subscription| DeribitClient::channel(&sub);
+
+        assert_eq!(channel(Subscription::agg_trade("BTCUSDT")), Some("trades.BTC-PERPETUAL.100ms".to_string()));
+        assert_eq!(channel(Subscription::book_ticker("ETHUSDC")), Some("ticker.ETH_USDC-PERPETUAL.100ms".to_string()));
+        assert_eq!(channel(Subscription::depth("BTCUSD")), Some("book.BTC-PERPETUAL.100ms".to_string()));
+        assert_eq!(
+            channel(Subscription::partial_depth("BTC-27DEC24-100000-C", 10)),
+            Some("book.BTC-27DEC24-100000-C.none.10.100ms".to_string())
+        );
+        assert_eq!(
+            channel(Subscription::kline("ETHUSDT", KlineInterval::OneDay)),
+            Some("chart.trades.ETH-PERPETUAL.1D".to_string())
+        );
+        assert_eq!(channel(Subscription::kline("ETHUSDT", KlineInterval::FourHours)), None);
+        assert_eq!(channel(Subscription::funding_rate("BTCUSDT")), None);
+    }
+
+    #[test]
+    fn test_subscription_msgs() {
+        let client = DeribitClient::new(false).with_subscribe_batch_size(2);
+        let subscriptions = vec![
+            Subscription::agg_trade("BTCUSDT"),
+            Subscription::agg_trade("BTCUSD"),
+            Subscription::book_ticker("BTCUSDT"),
+            Subscription::agg_trade("ETHUSDT"),
+            Subscription::liquidation("BTCUSDT"),
+        ];
+
+        // BTCUSDT and BTCUSD share the inverse perpetual, so its trades channel is sent once
+        assert_eq!(
+            client.build_subscription_msgs(&subscriptions),
+            vec![
+                json!({ "jsonrpc": "2.0", "method": "public/subscribe", "params": { "channels": ["trades.BTC-PERPETUAL.100ms", "ticker.BTC-PERPETUAL.100ms"] } }),
+                json!({ "jsonrpc": "2.0", "method": "public/subscribe", "params": { "channels": ["trades.ETH-PERPETUAL.100ms"] } }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_trades() {
+        let mut client = DeribitClient::new(false);
+        client.track_symbols(&[Subscription::agg_trade("BTCUSDT")]);
+        let json = r#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"trades.BTC-PERPETUAL.100ms","data":[{"trade_seq":30289442,"trade_id":"48079269","timestamp":1590484512188,"tick_direction":2,"price":8950.0,"mark_price":8948.9,"instrument_name":"BTC-PERPETUAL","index_price":8955.88,"direction":"sell","amount":10.0},{"trade_seq":30289443,"trade_id":"48079270","timestamp":1590484512189,"tick_direction":0,"price":8950.5,"mark_price":8948.9,"instrument_name":"BTC-PERPETUAL","index_price":8955.88,"direction":"buy","amount":20.0}]}}"#;
+
+        let events = parse(&client, json).unwrap();
+        assert_eq!(events.len(), 2);
+        if let MarketEvent::AggTrade(ref trade) = events[0] {
+            assert_eq!(trade.exchange, ExchangeType::Deribit);
+            // Reported under the symbol it was subscribed as
+            assert_eq!(trade.symbol, "BTCUSDT");
+            assert_eq!(trade.price, 8950.0);
+            assert_eq!(trade.quantity, 10.0);
+            assert_eq!(trade.timestamp, 1590484512188);
+            assert_eq!(trade.trade_id, 30289442);
+            // The taker sold into a resting bid
+            assert!(trade.is_buyer_maker);
+        } else {
+            panic!("Expected AggTrade event");
+        }
+        assert!(matches!(&events[1], MarketEvent::AggTrade(trade) if !trade.is_buyer_maker && trade.trade_id == 30289443));
+    }
+
+    #[test]
+    fn test_parse_incremental_book() {
+        let client = DeribitClient::new(false);
+        let json = r#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"change","timestamp":1554375447971,"prev_change_id":297217,"instrument_name":"BTC-PERPETUAL","change_id":297218,"bids":[["delete",5042.34,0.0],["new",5041.94,20.0]],"asks":[["change",5043.5,10.0]]}}}"#;
+
+        let events = parse(&client, json).unwrap();
+        if let [MarketEvent::DepthUpdate(depth)] = events.as_slice() {
+            assert_eq!(depth.symbol, "BTCUSD");
+            assert!(!depth.is_snapshot);
+            assert_eq!(depth.bids, vec![(5042.34, 0.0), (5041.94, 20.0)]);
+            assert_eq!(depth.asks, vec![(5043.5, 10.0)]);
+            assert_eq!(depth.final_update_id, Some(297218));
+            assert_eq!(depth.prev_final_update_id, Some(297217));
+        } else {
+            panic!("Expected one DepthUpdate event");
+        }
+    }
+
+    #[test]
+    fn test_control_frames_are_not_events() {
+        let client = DeribitClient::new(false);
+        let test_request: Value = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","method":"heartbeat","params":{"type":"test_request"}}"#,
+        ).unwrap();
+
+        assert!(DeribitClient::is_test_request(&test_request));
+        assert!(client.parse_value(&test_request).unwrap().is_empty());
+        assert!(parse(&client, r#"{"jsonrpc":"2.0","id":1,"result":["trades.BTC-PERPETUAL.100ms"]}"#).unwrap().is_empty());
+        assert!(parse(&client, r#"{"jsonrpc":"2.0","id":2,"error":{"code":11050,"message":"bad_request"}}"#).is_err());
+    }
+}
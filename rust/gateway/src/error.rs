@@ -0,0 +1,161 @@
+//! Gateway error type
+//!
+//! This module defines the error the library returns, so callers can match
+//! on what went wrong (a failed connect, a bad frame, a dropped socket)
+//! instead of inspecting message text. The binary wraps it in anyhow.
+
+use thiserror::Error;
+use tokio_tungstenite::tungstenite;
+
+/// Result with a `GatewayError`
+pub type Result<T, E = GatewayError> = std::result::Result<T, E>;
+
+/// Error returned by exchange clients, publishers and the rest of the library
+#[derive(Debug, Error)]
+pub enum GatewayError {
+    /// Opening a connection failed: DNS, TCP, TLS, the WebSocket handshake or login
+    #[error("{0}")]
+    Connect(String),
+    /// A frame or payload could not be decoded or encoded
+    #[error("{0}")]
+    Parse(String),
+    /// The connection is closed or was lost
+    #[error("{0}")]
+    Disconnected(String),
+    /// A request was refused for exceeding a rate limit (HTTP 429, or 418 once Binance bans the IP)
+    #[error("{0}")]
+    RateLimited(String),
+    /// A Redis command or connection failed
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    /// A subscription was invalid or rejected by the exchange
+    #[error("{0}")]
+    Subscription(String),
+    /// The exchange answered with an error frame carrying its own code
+    #[error("Exchange error {code}: {message}")]
+    Exchange { code: String, message: String },
+    /// A setting or config value is invalid
+    #[error("{0}")]
+    Config(String),
+    /// A REST request failed
+    #[error("HTTP error: {0}")]
+    Http(reqwest::Error),
+    /// Producing to Kafka failed
+    #[error("{0}")]
+    Kafka(String),
+    /// Writing or reading a recording failed
+    #[error("{0}")]
+    Recording(String),
+    /// A local socket or file operation failed
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<serde_json::Error> for GatewayError {
+    fn from(error: serde_json::Error) -> Self {
+        GatewayError::Parse(format!("Invalid JSON: {}", error))
+    }
+}
+
+impl From<rmp_serde::encode::Error> for GatewayError {
+    fn from(error: rmp_serde::encode::Error) -> Self {
+        GatewayError::Parse(format!("MessagePack encoding failed: {}", error))
+    }
+}
+
+impl From<std::num::ParseFloatError> for GatewayError {
+    fn from(error: std::num::ParseFloatError) -> Self {
+        GatewayError::Parse(format!("Invalid number: {}", error))
+    }
+}
+
+impl From<std::num::ParseIntError> for GatewayError {
+    fn from(error: std::num::ParseIntError) -> Self {
+        GatewayError::Parse(format!("Invalid integer: {}", error))
+    }
+}
+
+impl From<url::ParseError> for GatewayError {
+    fn from(error: url::ParseError) -> Self {
+        GatewayError::Connect(format!("Invalid URL: {}", error))
+    }
+}
+
+impl From<tungstenite::Error> for GatewayError {
+    fn from(error: tungstenite::Error) -> Self {
+        use tungstenite::Error as WsError;
+        match error {
+            WsError::ConnectionClosed | WsError::AlreadyClosed | WsError::Io(_) | WsError::Protocol(_) => {
+                GatewayError::Disconnected(format!("WebSocket closed: {}", error))
+            }
+            WsError::Utf8 | WsError::Capacity(_) => GatewayError::Parse(format!("Invalid WebSocket frame: {}", error)),
+            _ => GatewayError::Connect(format!("WebSocket error: {}", error)),
+        }
+    }
+}
+
+impl From<reqwest::Error> for GatewayError {
+    fn from(error: reqwest::Error) -> Self {
+        match error.status() {
+            Some(status) if status.as_u16() == 429 || status.as_u16() == 418 => {
+                GatewayError::RateLimited(format!("Rate limited: {}", error))
+            }
+            _ => GatewayError::Http(error),
+        }
+    }
+}
+
+impl From<hyper::Error> for GatewayError {
+    fn from(error: hyper::Error) -> Self {
+        GatewayError::Io(std::io::Error::other(error))
+    }
+}
+
+impl From<rdkafka::error::KafkaError> for GatewayError {
+    fn from(error: rdkafka::error::KafkaError) -> Self {
+        GatewayError::Kafka(format!("Kafka error: {}", error))
+    }
+}
+
+impl From<toml::de::Error> for GatewayError {
+    fn from(error: toml::de::Error) -> Self {
+        GatewayError::Config(error.to_string())
+    }
+}
+
+impl From<parquet::errors::ParquetError> for GatewayError {
+    fn from(error: parquet::errors::ParquetError) -> Self {
+        GatewayError::Recording(format!("Parquet error: {}", error))
+    }
+}
+
+impl From<arrow_schema::ArrowError> for GatewayError {
+    fn from(error: arrow_schema::ArrowError) -> Self {
+        GatewayError::Recording(format!("Arrow error: {}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_errors_map_to_variants() {
+        let json = serde_json::from_str::<serde_json::Value>("{not json").unwrap_err();
+        assert!(matches!(GatewayError::from(json), GatewayError::Parse(_)));
+
+        let closed = GatewayError::from(tungstenite::Error::ConnectionClosed);
+        assert!(matches!(closed, GatewayError::Disconnected(_)));
+
+        let url = GatewayError::from(url::Url::parse("not a url").unwrap_err());
+        assert!(matches!(url, GatewayError::Connect(_)));
+
+        assert!(matches!(GatewayError::from("x".parse::<f64>().unwrap_err()), GatewayError::Parse(_)));
+    }
+
+    #[test]
+    fn test_message_variants_display_the_message() {
+        let error = GatewayError::Subscription("BTCUSDT is not listed".to_string());
+        assert_eq!(error.to_string(), "BTCUSDT is not listed");
+    }
+}
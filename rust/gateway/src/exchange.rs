@@ -3,13 +3,58 @@
 //! This module defines the common interface that all exchange implementations must follow.
 
 use serde::{Deserialize, Serialize};
-use anyhow::Result;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{GatewayError, Result};
+use crate::opportunity::Opportunity;
+use crate::orderbook::{
+    BINANCE_COIN_FUTURES_REST, BINANCE_COIN_FUTURES_TESTNET_REST, BINANCE_FUTURES_REST, BINANCE_FUTURES_TESTNET_REST,
+    BINANCE_SPOT_REST, BINANCE_SPOT_TESTNET_REST,
+};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+/// Current wall-clock time in milliseconds
+pub fn now_ms() -> i64 {
+    SystemClock.now_ms()
+}
 
 /// Supported exchange types
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ExchangeType {
+    #[serde(alias = "binance")]
     Binance,
+    #[serde(alias = "okx")]
     Okx,
+    #[serde(alias = "bybit")]
+    Bybit,
+    #[serde(alias = "coinbase")]
+    Coinbase,
+    #[serde(alias = "kucoin")]
+    Kucoin,
+    #[serde(alias = "deribit")]
+    Deribit,
+    #[serde(alias = "gateio")]
+    Gateio,
+    #[serde(alias = "bitget")]
+    Bitget,
+}
+
+impl ExchangeType {
+    /// Check if this build includes the exchange's client; each one sits behind a Cargo feature of the same name
+    pub fn is_compiled(&self) -> bool {
+        match self {
+            ExchangeType::Binance => cfg!(feature = "binance"),
+            ExchangeType::Okx => cfg!(feature = "okx"),
+            ExchangeType::Bybit => cfg!(feature = "bybit"),
+            ExchangeType::Coinbase => cfg!(feature = "coinbase"),
+            ExchangeType::Kucoin => cfg!(feature = "kucoin"),
+            ExchangeType::Deribit => cfg!(feature = "deribit"),
+            ExchangeType::Gateio => cfg!(feature = "gateio"),
+            ExchangeType::Bitget => cfg!(feature = "bitget"),
+        }
+    }
 }
 
 impl std::fmt::Display for ExchangeType {
@@ -17,6 +62,12 @@ impl std::fmt::Display for ExchangeType {
         match self {
             ExchangeType::Binance => write!(f, "binance"),
             ExchangeType::Okx => write!(f, "okx"),
+            ExchangeType::Bybit => write!(f, "bybit"),
+            ExchangeType::Coinbase => write!(f, "coinbase"),
+            ExchangeType::Kucoin => write!(f, "kucoin"),
+            ExchangeType::Deribit => write!(f, "deribit"),
+            ExchangeType::Gateio => write!(f, "gateio"),
+            ExchangeType::Bitget => write!(f, "bitget"),
         }
     }
 }
@@ -28,6 +79,13 @@ pub enum DataType {
     Kline,         // K-line/candlestick data
     Depth,         // Order book depth
     BookTicker,    // Best bid/ask price
+    ContinuousKline, // Continuous-contract K-line
+    FundingRate,   // Perpetual funding rate
+    Liquidation,   // Forced liquidation orders
+    OpenInterest,  // Open interest (polled over REST)
+    Ticker24h,     // Rolling 24-hour statistics
+    Nbbo,          // Best bid/ask across exchanges (derived, not subscribable)
+    Opportunity,   // Cross-exchange spread over the alert threshold (derived, not subscribable)
 }
 
 impl DataType {
@@ -37,6 +95,13 @@ impl DataType {
             DataType::Kline => "kline",
             DataType::Depth => "depth",
             DataType::BookTicker => "bookTicker",
+            DataType::ContinuousKline => "continuousKline",
+            DataType::FundingRate => "fundingRate",
+            DataType::Liquidation => "liquidation",
+            DataType::OpenInterest => "openInterest",
+            DataType::Ticker24h => "ticker24h",
+            DataType::Nbbo => "nbbo",
+            DataType::Opportunity => "opportunity",
         }
     }
 }
@@ -45,42 +110,412 @@ impl DataType {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum KlineInterval {
     OneMinute,
+    ThreeMinutes,
     FiveMinutes,
     FifteenMinutes,
     ThirtyMinutes,
     OneHour,
+    TwoHours,
     FourHours,
+    SixHours,
+    EightHours,
+    TwelveHours,
     OneDay,
+    OneWeek,
+    OneMonth,
 }
 
 impl KlineInterval {
+    /// Every interval, shortest first
+    pub const ALL: [KlineInterval; 14] = [
+        KlineInterval::OneMinute,
+        KlineInterval::ThreeMinutes,
+        KlineInterval::FiveMinutes,
+        KlineInterval::FifteenMinutes,
+        KlineInterval::ThirtyMinutes,
+        KlineInterval::OneHour,
+        KlineInterval::TwoHours,
+        KlineInterval::FourHours,
+        KlineInterval::SixHours,
+        KlineInterval::EightHours,
+        KlineInterval::TwelveHours,
+        KlineInterval::OneDay,
+        KlineInterval::OneWeek,
+        KlineInterval::OneMonth,
+    ];
+
+    /// Standard (Binance) notation, e.g. `1h`, `1w`, `1M`
     pub fn as_str(&self) -> &'static str {
         match self {
             KlineInterval::OneMinute => "1m",
+            KlineInterval::ThreeMinutes => "3m",
             KlineInterval::FiveMinutes => "5m",
             KlineInterval::FifteenMinutes => "15m",
             KlineInterval::ThirtyMinutes => "30m",
             KlineInterval::OneHour => "1h",
+            KlineInterval::TwoHours => "2h",
             KlineInterval::FourHours => "4h",
+            KlineInterval::SixHours => "6h",
+            KlineInterval::EightHours => "8h",
+            KlineInterval::TwelveHours => "12h",
             KlineInterval::OneDay => "1d",
+            KlineInterval::OneWeek => "1w",
+            KlineInterval::OneMonth => "1M",
+        }
+    }
+
+    /// OKX candle bar (uppercase from hours up), or `None` where OKX has no such bar
+    pub fn as_okx_str(&self) -> Option<&'static str> {
+        let bar = match self {
+            KlineInterval::OneMinute => "1m",
+            KlineInterval::ThreeMinutes => "3m",
+            KlineInterval::FiveMinutes => "5m",
+            KlineInterval::FifteenMinutes => "15m",
+            KlineInterval::ThirtyMinutes => "30m",
+            KlineInterval::OneHour => "1H",
+            KlineInterval::TwoHours => "2H",
+            KlineInterval::FourHours => "4H",
+            KlineInterval::SixHours => "6H",
+            KlineInterval::EightHours => return None,
+            KlineInterval::TwelveHours => "12H",
+            KlineInterval::OneDay => "1D",
+            KlineInterval::OneWeek => "1W",
+            KlineInterval::OneMonth => "1M",
+        };
+        Some(bar)
+    }
+
+    /// Look up an interval from its OKX candle bar
+    pub fn from_okx_str(bar: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|interval| interval.as_okx_str() == Some(bar))
+    }
+
+    /// KuCoin candle type, e.g. `1min`, `1hour`, `1week`
+    pub fn as_kucoin_str(&self) -> &'static str {
+        match self {
+            KlineInterval::OneMinute => "1min",
+            KlineInterval::ThreeMinutes => "3min",
+            KlineInterval::FiveMinutes => "5min",
+            KlineInterval::FifteenMinutes => "15min",
+            KlineInterval::ThirtyMinutes => "30min",
+            KlineInterval::OneHour => "1hour",
+            KlineInterval::TwoHours => "2hour",
+            KlineInterval::FourHours => "4hour",
+            KlineInterval::SixHours => "6hour",
+            KlineInterval::EightHours => "8hour",
+            KlineInterval::TwelveHours => "12hour",
+            KlineInterval::OneDay => "1day",
+            KlineInterval::OneWeek => "1week",
+            KlineInterval::OneMonth => "1month",
+        }
+    }
+
+    /// Look up an interval from its KuCoin candle type
+    pub fn from_kucoin_str(candle_type: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|interval| interval.as_kucoin_str() == candle_type)
+    }
+
+    /// Deribit chart resolution in minutes (`1D` for a day), or `None` where Deribit has no such chart
+    pub fn as_deribit_str(&self) -> Option<&'static str> {
+        let resolution = match self {
+            KlineInterval::OneMinute => "1",
+            KlineInterval::ThreeMinutes => "3",
+            KlineInterval::FiveMinutes => "5",
+            KlineInterval::FifteenMinutes => "15",
+            KlineInterval::ThirtyMinutes => "30",
+            KlineInterval::OneHour => "60",
+            KlineInterval::TwoHours => "120",
+            KlineInterval::SixHours => "360",
+            KlineInterval::TwelveHours => "720",
+            KlineInterval::OneDay => "1D",
+            KlineInterval::FourHours
+            | KlineInterval::EightHours
+            | KlineInterval::OneWeek
+            | KlineInterval::OneMonth => return None,
+        };
+        Some(resolution)
+    }
+
+    /// Look up an interval from its Deribit chart resolution
+    pub fn from_deribit_str(resolution: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|interval| interval.as_deribit_str() == Some(resolution))
+    }
+
+    /// Gate.io futures candlestick interval, or `None` where Gate.io has no such interval
+    pub fn as_gateio_str(&self) -> Option<&'static str> {
+        let interval = match self {
+            KlineInterval::OneMinute => "1m",
+            KlineInterval::FiveMinutes => "5m",
+            KlineInterval::FifteenMinutes => "15m",
+            KlineInterval::ThirtyMinutes => "30m",
+            KlineInterval::OneHour => "1h",
+            KlineInterval::FourHours => "4h",
+            KlineInterval::EightHours => "8h",
+            KlineInterval::OneDay => "1d",
+            KlineInterval::OneWeek => "7d",
+            KlineInterval::ThreeMinutes
+            | KlineInterval::TwoHours
+            | KlineInterval::SixHours
+            | KlineInterval::TwelveHours
+            | KlineInterval::OneMonth => return None,
+        };
+        Some(interval)
+    }
+
+    /// Look up an interval from its Gate.io candlestick interval
+    pub fn from_gateio_str(interval: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|i| i.as_gateio_str() == Some(interval))
+    }
+
+    /// Bitget candle channel suffix, or `None` where Bitget has no such interval
+    pub fn as_bitget_str(&self) -> Option<&'static str> {
+        let interval = match self {
+            KlineInterval::OneMinute => "1m",
+            KlineInterval::FiveMinutes => "5m",
+            KlineInterval::FifteenMinutes => "15m",
+            KlineInterval::ThirtyMinutes => "30m",
+            KlineInterval::OneHour => "1H",
+            KlineInterval::FourHours => "4H",
+            KlineInterval::SixHours => "6H",
+            KlineInterval::TwelveHours => "12H",
+            KlineInterval::OneDay => "1D",
+            KlineInterval::OneWeek => "1W",
+            KlineInterval::OneMonth => "1M",
+            KlineInterval::ThreeMinutes
+            | KlineInterval::TwoHours
+            | KlineInterval::EightHours => return None,
+        };
+        Some(interval)
+    }
+
+    /// Look up an interval from its Bitget candle channel suffix
+    pub fn from_bitget_str(interval: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|i| i.as_bitget_str() == Some(interval))
+    }
+}
+
+/// Futures contract types for continuous-contract streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ContractType {
+    Perpetual,
+    CurrentQuarter,
+    NextQuarter,
+}
+
+impl ContractType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContractType::Perpetual => "PERPETUAL",
+            ContractType::CurrentQuarter => "CURRENT_QUARTER",
+            ContractType::NextQuarter => "NEXT_QUARTER",
+        }
+    }
+}
+
+impl std::str::FromStr for ContractType {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_uppercase().as_str() {
+            "PERPETUAL" => Ok(ContractType::Perpetual),
+            "CURRENT_QUARTER" => Ok(ContractType::CurrentQuarter),
+            "NEXT_QUARTER" => Ok(ContractType::NextQuarter),
+            _ => Err(GatewayError::Config(format!("Unknown contract type: {}", s))),
+        }
+    }
+}
+
+/// How often an exchange pushes depth updates, where it offers a choice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum DepthUpdateSpeed {
+    #[default]
+    #[serde(rename = "100ms")]
+    Ms100,
+    #[serde(rename = "500ms")]
+    Ms500,
+}
+
+impl DepthUpdateSpeed {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DepthUpdateSpeed::Ms100 => "100ms",
+            DepthUpdateSpeed::Ms500 => "500ms",
+        }
+    }
+}
+
+impl std::str::FromStr for DepthUpdateSpeed {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "100ms" | "100" => Ok(DepthUpdateSpeed::Ms100),
+            "500ms" | "500" => Ok(DepthUpdateSpeed::Ms500),
+            _ => Err(GatewayError::Config(format!("Unknown depth update speed: {} (expected 100ms or 500ms)", s))),
+        }
+    }
+}
+
+/// Binance market a client streams from; all of them report as `ExchangeType::Binance`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketKind {
+    Spot,
+    /// USDⓈ-margined futures (`fstream`)
+    #[default]
+    UsdFutures,
+    /// Coin-margined futures (`dstream`)
+    CoinFutures,
+}
+
+impl MarketKind {
+    /// REST base URL
+    pub fn rest_url(self, testnet: bool) -> &'static str {
+        match (self, testnet) {
+            (MarketKind::Spot, false) => BINANCE_SPOT_REST,
+            (MarketKind::Spot, true) => BINANCE_SPOT_TESTNET_REST,
+            (MarketKind::UsdFutures, false) => BINANCE_FUTURES_REST,
+            (MarketKind::UsdFutures, true) => BINANCE_FUTURES_TESTNET_REST,
+            (MarketKind::CoinFutures, false) => BINANCE_COIN_FUTURES_REST,
+            (MarketKind::CoinFutures, true) => BINANCE_COIN_FUTURES_TESTNET_REST,
+        }
+    }
+
+    /// Path of the REST depth snapshot
+    pub fn depth_path(self) -> &'static str {
+        match self {
+            MarketKind::Spot => "/api/v3/depth",
+            MarketKind::UsdFutures => "/fapi/v1/depth",
+            MarketKind::CoinFutures => "/dapi/v1/depth",
+        }
+    }
+
+    /// Path of the REST server time
+    pub fn time_path(self) -> &'static str {
+        match self {
+            MarketKind::Spot => "/api/v3/time",
+            MarketKind::UsdFutures => "/fapi/v1/time",
+            MarketKind::CoinFutures => "/dapi/v1/time",
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarketKind::Spot => "spot",
+            MarketKind::UsdFutures => "usd_futures",
+            MarketKind::CoinFutures => "coin_futures",
+        }
+    }
+}
+
+impl std::fmt::Display for MarketKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for MarketKind {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "spot" => Ok(MarketKind::Spot),
+            "usd_futures" | "usdm" | "futures" => Ok(MarketKind::UsdFutures),
+            "coin_futures" | "coinm" => Ok(MarketKind::CoinFutures),
+            _ => Err(GatewayError::Config(format!("Unknown Binance market: {} (expected spot, usd_futures or coin_futures)", s))),
+        }
+    }
+}
+
+/// OKX instrument type sharing a base/quote pair; all of them report as `ExchangeType::Okx`.
+///
+/// Dated futures need an expiry on top of the pair, so they can't be selected this way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OkxInstType {
+    /// Spot pair (e.g. `BTC-USDT`)
+    #[default]
+    Spot,
+    /// Perpetual swap (e.g. `BTC-USDT-SWAP`)
+    Swap,
+}
+
+impl OkxInstType {
+    /// Type of an instId, from its suffix
+    pub fn from_inst_id(inst_id: &str) -> Self {
+        if inst_id.ends_with("-SWAP") {
+            OkxInstType::Swap
+        } else {
+            OkxInstType::Spot
+        }
+    }
+
+    /// Name OKX uses for the type (`instType`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OkxInstType::Spot => "SPOT",
+            OkxInstType::Swap => "SWAP",
+        }
+    }
+}
+
+impl std::fmt::Display for OkxInstType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for OkxInstType {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "spot" => Ok(OkxInstType::Spot),
+            "swap" | "perp" | "perpetual" => Ok(OkxInstType::Swap),
+            _ => Err(GatewayError::Config(format!("Unknown OKX instrument type: {} (expected spot or swap)", s))),
+        }
+    }
+}
+
+/// Order side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl std::str::FromStr for Side {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "buy" => Ok(Side::Buy),
+            "sell" => Ok(Side::Sell),
+            _ => Err(GatewayError::Config(format!("Unknown side: {}", s))),
         }
     }
 }
 
 /// Aggregated trade data
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AggTrade {
     pub exchange: ExchangeType,
     pub symbol: String,
     pub price: f64,
     pub quantity: f64,
+    /// Exchange event time (ms)
     pub timestamp: i64,
     pub is_buyer_maker: bool,
     pub trade_id: u64,
+    /// When the gateway parsed the event (wall-clock ms), for measuring latency
+    #[serde(default)]
+    pub received_at: i64,
 }
 
 /// K-line/candlestick data
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Kline {
     pub exchange: ExchangeType,
     pub symbol: String,
@@ -93,20 +528,68 @@ pub struct Kline {
     pub close: f64,
     pub volume: f64,
     pub is_closed: bool,
+    /// Set for continuous-contract klines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_type: Option<ContractType>,
+    /// When the gateway parsed the event (wall-clock ms), for measuring latency
+    #[serde(default)]
+    pub received_at: i64,
 }
 
 /// Order book depth update
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DepthUpdate {
     pub exchange: ExchangeType,
     pub symbol: String,
     pub bids: Vec<(f64, f64)>,  // (price, quantity)
     pub asks: Vec<(f64, f64)>,
+    /// Exchange event time (ms)
     pub timestamp: i64,
+    /// True for a full book snapshot, false for an incremental diff
+    #[serde(default)]
+    pub is_snapshot: bool,
+    /// First update ID in the event (Binance `U`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_update_id: Option<u64>,
+    /// Final update ID in the event (Binance `u`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_update_id: Option<u64>,
+    /// Final update ID of the previous event (Binance `pu`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_final_update_id: Option<u64>,
+    /// When the gateway parsed the event (wall-clock ms), for measuring latency
+    #[serde(default)]
+    pub received_at: i64,
+}
+
+impl DepthUpdate {
+    /// Best bid and ask of a partial book snapshot.
+    ///
+    /// Diffs only carry the levels that changed, so they yield nothing, and
+    /// neither does a snapshot with an empty side.
+    pub fn to_book_ticker(&self) -> Option<BookTicker> {
+        if !self.is_snapshot {
+            return None;
+        }
+        let live = |&&(_, qty): &&(f64, f64)| qty > 0.0;
+        let &(bid_price, bid_qty) = self.bids.iter().filter(live).max_by(|a, b| a.0.total_cmp(&b.0))?;
+        let &(ask_price, ask_qty) = self.asks.iter().filter(live).min_by(|a, b| a.0.total_cmp(&b.0))?;
+
+        Some(BookTicker {
+            exchange: self.exchange,
+            symbol: self.symbol.clone(),
+            bid_price,
+            bid_qty,
+            ask_price,
+            ask_qty,
+            timestamp: self.timestamp,
+            received_at: self.received_at,
+        })
+    }
 }
 
 /// Best bid/ask ticker
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BookTicker {
     pub exchange: ExchangeType,
     pub symbol: String,
@@ -114,25 +597,139 @@ pub struct BookTicker {
     pub bid_qty: f64,
     pub ask_price: f64,
     pub ask_qty: f64,
+    /// Exchange event time (ms)
+    pub timestamp: i64,
+    /// When the gateway parsed the event (wall-clock ms), for measuring latency
+    #[serde(default)]
+    pub received_at: i64,
+}
+
+/// Perpetual funding rate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FundingRate {
+    pub exchange: ExchangeType,
+    pub symbol: String,
+    pub funding_rate: f64,
+    /// Time of the next funding settlement (ms)
+    pub next_funding_time: i64,
+    /// Exchange event time (ms)
+    pub timestamp: i64,
+    /// When the gateway parsed the event (wall-clock ms), for measuring latency
+    #[serde(default)]
+    pub received_at: i64,
+}
+
+/// Forced liquidation order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Liquidation {
+    pub exchange: ExchangeType,
+    pub symbol: String,
+    /// Side of the liquidation order (a sell closes a long)
+    pub side: Side,
+    pub price: f64,
+    pub quantity: f64,
+    /// Exchange event time (ms)
     pub timestamp: i64,
+    /// When the gateway parsed the event (wall-clock ms), for measuring latency
+    #[serde(default)]
+    pub received_at: i64,
+}
+
+/// Open interest snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenInterest {
+    pub exchange: ExchangeType,
+    pub symbol: String,
+    /// Open interest in the base asset
+    pub open_interest: f64,
+    /// Exchange event time (ms)
+    pub timestamp: i64,
+    /// When the gateway parsed the event (wall-clock ms), for measuring latency
+    #[serde(default)]
+    pub received_at: i64,
+}
+
+/// Rolling 24-hour ticker statistics
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ticker24h {
+    pub exchange: ExchangeType,
+    pub symbol: String,
+    pub last_price: f64,
+    /// Price 24 hours ago
+    pub open_price: f64,
+    pub high_price: f64,
+    pub low_price: f64,
+    /// Traded volume in the base asset (contracts for OKX swaps)
+    pub volume: f64,
+    /// Traded volume in the quote asset
+    pub quote_volume: f64,
+    /// Last price minus open price
+    pub price_change: f64,
+    pub price_change_percent: f64,
+    /// Exchange event time (ms)
+    pub timestamp: i64,
+    /// When the gateway parsed the event (wall-clock ms), for measuring latency
+    #[serde(default)]
+    pub received_at: i64,
+}
+
+/// Best bid and ask across every connected exchange
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Nbbo {
+    pub symbol: String,
+    pub best_bid: f64,
+    pub best_bid_qty: f64,
+    pub best_bid_exchange: ExchangeType,
+    pub best_ask: f64,
+    pub best_ask_qty: f64,
+    pub best_ask_exchange: ExchangeType,
+    /// Best ask minus best bid; negative when the market is crossed
+    pub spread: f64,
+    /// Exchange event time of the quote that moved the top (ms)
+    pub timestamp: i64,
+    /// When the gateway parsed that quote (wall-clock ms)
+    #[serde(default)]
+    pub received_at: i64,
+}
+
+impl Nbbo {
+    /// Check if one exchange bids above another's ask
+    pub fn is_crossed(&self) -> bool {
+        self.spread < 0.0
+    }
 }
 
 /// Unified market data event
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MarketEvent {
     AggTrade(AggTrade),
     Kline(Kline),
     DepthUpdate(DepthUpdate),
     BookTicker(BookTicker),
+    FundingRate(FundingRate),
+    Liquidation(Liquidation),
+    OpenInterest(OpenInterest),
+    Ticker24h(Ticker24h),
+    /// Derived by the gateway from every exchange's book tickers
+    Nbbo(Nbbo),
+    /// Derived by the gateway from the NBBO
+    Opportunity(Opportunity),
 }
 
 impl MarketEvent {
+    /// Exchange the event came from; for an NBBO or opportunity, the one holding the best bid
     pub fn exchange(&self) -> ExchangeType {
         match self {
             MarketEvent::AggTrade(t) => t.exchange,
             MarketEvent::Kline(k) => k.exchange,
             MarketEvent::DepthUpdate(d) => d.exchange,
             MarketEvent::BookTicker(b) => b.exchange,
+            MarketEvent::FundingRate(f) => f.exchange,
+            MarketEvent::Liquidation(l) => l.exchange,
+            MarketEvent::OpenInterest(o) => o.exchange,
+            MarketEvent::Ticker24h(t) => t.exchange,
+            MarketEvent::Nbbo(n) => n.best_bid_exchange,
+            MarketEvent::Opportunity(o) => o.sell_exchange,
         }
     }
 
@@ -142,25 +739,520 @@ impl MarketEvent {
             MarketEvent::Kline(k) => &k.symbol,
             MarketEvent::DepthUpdate(d) => &d.symbol,
             MarketEvent::BookTicker(b) => &b.symbol,
+            MarketEvent::FundingRate(f) => &f.symbol,
+            MarketEvent::Liquidation(l) => &l.symbol,
+            MarketEvent::OpenInterest(o) => &o.symbol,
+            MarketEvent::Ticker24h(t) => &t.symbol,
+            MarketEvent::Nbbo(n) => &n.symbol,
+            MarketEvent::Opportunity(o) => &o.symbol,
         }
     }
 
     pub fn event_type(&self) -> DataType {
         match self {
             MarketEvent::AggTrade(_) => DataType::AggTrade,
+            MarketEvent::Kline(k) if k.contract_type.is_some() => DataType::ContinuousKline,
             MarketEvent::Kline(_) => DataType::Kline,
             MarketEvent::DepthUpdate(_) => DataType::Depth,
             MarketEvent::BookTicker(_) => DataType::BookTicker,
+            MarketEvent::FundingRate(_) => DataType::FundingRate,
+            MarketEvent::Liquidation(_) => DataType::Liquidation,
+            MarketEvent::OpenInterest(_) => DataType::OpenInterest,
+            MarketEvent::Ticker24h(_) => DataType::Ticker24h,
+            MarketEvent::Nbbo(_) => DataType::Nbbo,
+            MarketEvent::Opportunity(_) => DataType::Opportunity,
+        }
+    }
+
+    /// Replace a partial book snapshot with its best bid/ask; diffs and other events pass through
+    pub fn derive_book_ticker(self) -> Self {
+        match self {
+            MarketEvent::DepthUpdate(depth) => match depth.to_book_ticker() {
+                Some(ticker) => MarketEvent::BookTicker(ticker),
+                None => MarketEvent::DepthUpdate(depth),
+            },
+            event => event,
+        }
+    }
+
+    /// When the gateway parsed the event (wall-clock ms)
+    pub fn received_at(&self) -> i64 {
+        match self {
+            MarketEvent::AggTrade(t) => t.received_at,
+            MarketEvent::Kline(k) => k.received_at,
+            MarketEvent::DepthUpdate(d) => d.received_at,
+            MarketEvent::BookTicker(b) => b.received_at,
+            MarketEvent::FundingRate(f) => f.received_at,
+            MarketEvent::Liquidation(l) => l.received_at,
+            MarketEvent::OpenInterest(o) => o.received_at,
+            MarketEvent::Ticker24h(t) => t.received_at,
+            MarketEvent::Nbbo(n) => n.received_at,
+            MarketEvent::Opportunity(o) => o.received_at,
+        }
+    }
+
+    /// Event time in milliseconds; klines carry none, so their open time is used
+    pub fn timestamp(&self) -> i64 {
+        match self {
+            MarketEvent::AggTrade(t) => t.timestamp,
+            MarketEvent::Kline(k) => k.open_time,
+            MarketEvent::DepthUpdate(d) => d.timestamp,
+            MarketEvent::BookTicker(b) => b.timestamp,
+            MarketEvent::FundingRate(f) => f.timestamp,
+            MarketEvent::Liquidation(l) => l.timestamp,
+            MarketEvent::OpenInterest(o) => o.timestamp,
+            MarketEvent::Ticker24h(t) => t.timestamp,
+            MarketEvent::Nbbo(n) => n.timestamp,
+            MarketEvent::Opportunity(o) => o.timestamp,
         }
     }
 }
 
 /// Subscription request
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Subscription {
     pub symbol: String,
     pub data_type: DataType,
     pub interval: Option<KlineInterval>,
+    /// Contract type for continuous klines
+    pub contract_type: Option<ContractType>,
+    /// Top-of-book levels for a partial depth stream; `None` streams full diffs
+    pub depth_levels: Option<u16>,
+    /// Depth push rate, on exchanges that offer more than one
+    pub update_speed: Option<DepthUpdateSpeed>,
+    /// OKX instrument to stream; `None` uses the client's default
+    pub okx_inst_type: Option<OkxInstType>,
+}
+
+impl Subscription {
+    /// Start building a validated subscription
+    pub fn builder() -> SubscriptionBuilder {
+        SubscriptionBuilder::default()
+    }
+
+    /// Subscription with no interval or contract type
+    fn simple(symbol: impl Into<String>, data_type: DataType) -> Self {
+        Self {
+            symbol: symbol.into(),
+            data_type,
+            interval: None,
+            contract_type: None,
+            depth_levels: None,
+            update_speed: None,
+            okx_inst_type: None,
+        }
+    }
+
+    /// Aggregate trades for a symbol
+    pub fn agg_trade(symbol: impl Into<String>) -> Self {
+        Self::simple(symbol, DataType::AggTrade)
+    }
+
+    /// Klines for a symbol at the given interval
+    pub fn kline(symbol: impl Into<String>, interval: KlineInterval) -> Self {
+        Self {
+            interval: Some(interval),
+            ..Self::simple(symbol, DataType::Kline)
+        }
+    }
+
+    /// Continuous-contract klines for a symbol at the given interval
+    pub fn continuous_kline(symbol: impl Into<String>, interval: KlineInterval, contract_type: ContractType) -> Self {
+        Self {
+            interval: Some(interval),
+            contract_type: Some(contract_type),
+            ..Self::simple(symbol, DataType::ContinuousKline)
+        }
+    }
+
+    /// Order book depth updates for a symbol
+    pub fn depth(symbol: impl Into<String>) -> Self {
+        Self::simple(symbol, DataType::Depth)
+    }
+
+    /// Top `levels` of the order book for a symbol
+    pub fn partial_depth(symbol: impl Into<String>, levels: u16) -> Self {
+        Self {
+            depth_levels: Some(levels),
+            ..Self::simple(symbol, DataType::Depth)
+        }
+    }
+
+    /// Best bid/ask for a symbol
+    pub fn book_ticker(symbol: impl Into<String>) -> Self {
+        Self::simple(symbol, DataType::BookTicker)
+    }
+
+    /// Funding rate for a perpetual symbol
+    pub fn funding_rate(symbol: impl Into<String>) -> Self {
+        Self::simple(symbol, DataType::FundingRate)
+    }
+
+    /// Forced liquidations for a symbol
+    pub fn liquidation(symbol: impl Into<String>) -> Self {
+        Self::simple(symbol, DataType::Liquidation)
+    }
+
+    /// Rolling 24-hour statistics for a symbol
+    pub fn ticker_24h(symbol: impl Into<String>) -> Self {
+        Self::simple(symbol, DataType::Ticker24h)
+    }
+}
+
+/// `BTCUSDT kline 1m`, `BTCUSDT depth 20 levels 100ms`, ...
+impl std::fmt::Display for Subscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.symbol, self.data_type.as_str())?;
+        if let Some(contract_type) = self.contract_type {
+            write!(f, " {}", contract_type.as_str())?;
+        }
+        if let Some(interval) = self.interval {
+            write!(f, " {}", interval.as_str())?;
+        }
+        if let Some(levels) = self.depth_levels {
+            write!(f, " {} levels", levels)?;
+        }
+        if let Some(speed) = self.update_speed {
+            write!(f, " {}", speed.as_str())?;
+        }
+        if let Some(inst_type) = self.okx_inst_type {
+            write!(f, " {}", inst_type)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builder for [`Subscription`] that checks intervals match the data type
+#[derive(Debug, Clone, Default)]
+pub struct SubscriptionBuilder {
+    symbol: Option<String>,
+    data_type: Option<DataType>,
+    interval: Option<KlineInterval>,
+    contract_type: Option<ContractType>,
+    depth_levels: Option<u16>,
+    update_speed: Option<DepthUpdateSpeed>,
+    okx_inst_type: Option<OkxInstType>,
+}
+
+impl SubscriptionBuilder {
+    /// Set the trading pair (e.g. BTCUSDT)
+    pub fn symbol(mut self, symbol: impl Into<String>) -> Self {
+        self.symbol = Some(symbol.into());
+        self
+    }
+
+    /// Set the market data type
+    pub fn data_type(mut self, data_type: DataType) -> Self {
+        self.data_type = Some(data_type);
+        self
+    }
+
+    /// Set the kline interval
+    pub fn interval(mut self, interval: KlineInterval) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Set the contract type for continuous klines
+    pub fn contract_type(mut self, contract_type: ContractType) -> Self {
+        self.contract_type = Some(contract_type);
+        self
+    }
+
+    /// Set the number of order book levels for a partial depth stream
+    pub fn depth_levels(mut self, levels: u16) -> Self {
+        self.depth_levels = Some(levels);
+        self
+    }
+
+    /// Set the depth push rate
+    pub fn update_speed(mut self, speed: DepthUpdateSpeed) -> Self {
+        self.update_speed = Some(speed);
+        self
+    }
+
+    /// Set the OKX instrument type, e.g. the perpetual swap instead of spot
+    pub fn okx_inst_type(mut self, inst_type: OkxInstType) -> Self {
+        self.okx_inst_type = Some(inst_type);
+        self
+    }
+
+    /// Build the subscription, rejecting intervals, contract types or depth options that don't fit the data type
+    pub fn build(self) -> Result<Subscription> {
+        let symbol = self.symbol.ok_or_else(|| GatewayError::Subscription("Subscription is missing a symbol".to_string()))?;
+        let data_type = self.data_type.ok_or_else(|| GatewayError::Subscription("Subscription is missing a data type".to_string()))?;
+
+        let is_kline = matches!(data_type, DataType::Kline | DataType::ContinuousKline);
+        if is_kline && self.interval.is_none() {
+            return Err(GatewayError::Subscription(format!("{} subscription for {} requires an interval", data_type.as_str(), symbol)));
+        }
+        if !is_kline && self.interval.is_some() {
+            return Err(GatewayError::Subscription(format!("{} subscription for {} does not take an interval", data_type.as_str(), symbol)));
+        }
+        if data_type != DataType::ContinuousKline && self.contract_type.is_some() {
+            return Err(GatewayError::Subscription(format!("{} subscription for {} does not take a contract type", data_type.as_str(), symbol)));
+        }
+        if data_type != DataType::Depth && (self.depth_levels.is_some() || self.update_speed.is_some()) {
+            return Err(GatewayError::Subscription(format!("{} subscription for {} does not take depth options", data_type.as_str(), symbol)));
+        }
+        if self.depth_levels == Some(0) {
+            return Err(GatewayError::Subscription(format!("Depth subscription for {} needs at least one level", symbol)));
+        }
+
+        Ok(Subscription {
+            symbol,
+            data_type,
+            interval: self.interval,
+            contract_type: self.contract_type,
+            depth_levels: self.depth_levels,
+            update_speed: self.update_speed,
+            okx_inst_type: self.okx_inst_type,
+        })
+    }
+}
+
+/// Reject depth subscriptions asking for a level count the exchange doesn't stream
+pub fn check_depth_levels(exchange: ExchangeType, subscriptions: &[Subscription], supported: &[u16]) -> Result<()> {
+    for sub in subscriptions {
+        let Some(levels) = sub.depth_levels else {
+            continue;
+        };
+        if !supported.contains(&levels) {
+            let supported: Vec<String> = supported.iter().map(|l| l.to_string()).collect();
+            return Err(GatewayError::Subscription(format!(
+                "{} does not offer {}-level depth for {} (supported: {})",
+                exchange,
+                levels,
+                sub.symbol,
+                if supported.is_empty() { "full book only".to_string() } else { supported.join(", ") }
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Token bucket pacing the control frames sent on one connection.
+///
+/// Holds up to `burst` tokens and regains `per_second` of them every second;
+/// each frame takes one, waiting for a refill when the bucket is empty.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Allow `per_second` frames on average, with bursts of up to `burst`
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            per_second: f64::from(per_second.max(1)),
+            burst,
+            tokens: burst,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Frames allowed per second
+    pub fn per_second(&self) -> u32 {
+        self.per_second as u32
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.burst);
+        self.last_refill = now;
+    }
+
+    /// Take a token if one is available right now
+    pub fn try_acquire(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Wait until a frame may be sent, then take its token
+    pub async fn acquire(&mut self) {
+        while !self.try_acquire() {
+            let wait = (1.0 - self.tokens) / self.per_second;
+            time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
+
+/// Silence after which a connection is treated as dead by default
+pub const DEFAULT_STALE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Tracks when a connection last received a frame of any kind.
+///
+/// A socket can go silent without ever delivering a close frame; once no
+/// frame has arrived for `timeout`, the connection counts as stale and the
+/// client reports itself disconnected so the runner reconnects it.
+#[derive(Debug, Clone)]
+pub struct StaleWatchdog {
+    /// `None` disables the watchdog
+    timeout: Option<Duration>,
+    last_message: Instant,
+}
+
+impl Default for StaleWatchdog {
+    fn default() -> Self {
+        Self::new(DEFAULT_STALE_TIMEOUT)
+    }
+}
+
+impl StaleWatchdog {
+    /// Watch for `timeout` of silence; a zero timeout disables the watchdog
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout: (!timeout.is_zero()).then_some(timeout),
+            last_message: Instant::now(),
+        }
+    }
+
+    /// Time since the last frame
+    pub fn silence(&self) -> Duration {
+        self.last_message.elapsed()
+    }
+
+    /// Record that a frame arrived
+    pub fn touch(&mut self) {
+        self.last_message = Instant::now();
+    }
+
+    /// Whether the timeout has passed since the last frame
+    pub fn is_stale(&self) -> bool {
+        self.timeout.is_some_and(|timeout| self.last_message.elapsed() >= timeout)
+    }
+
+    /// Resolve once the connection turns stale; never resolves when disabled
+    pub async fn expired(&self) {
+        match self.timeout {
+            Some(timeout) => time::sleep_until(self.last_message + timeout).await,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// Share of failed frames over the window that raises the parse error alarm by default
+pub const DEFAULT_PARSE_ERROR_PERCENT: u8 = 50;
+
+/// Frames the parse error rate is measured over by default
+pub const DEFAULT_PARSE_ERROR_WINDOW: usize = 100;
+
+/// Rolling parse error rate of a connection.
+///
+/// When an exchange changes its message format, every frame fails to parse
+/// while the socket itself stays healthy, so nothing reaches the watchdog.
+/// Once more than `percent` of the last `window` frames failed, the monitor
+/// logs an error and, with reconnects enabled, tells the client to drop the
+/// connection so the runner reconnects it.
+#[derive(Debug, Clone)]
+pub struct ParseErrorMonitor {
+    window: usize,
+    /// Zero disables the alarm
+    percent: u8,
+    reconnect: bool,
+    /// Outcome of each recent frame, `true` for a parse failure
+    recent: VecDeque<bool>,
+    failures: usize,
+    /// Set once the alarm fires, until the rate falls back under the threshold
+    alarmed: bool,
+}
+
+impl Default for ParseErrorMonitor {
+    fn default() -> Self {
+        Self::new(DEFAULT_PARSE_ERROR_WINDOW, DEFAULT_PARSE_ERROR_PERCENT)
+    }
+}
+
+impl ParseErrorMonitor {
+    /// Alarm once more than `percent` of the last `window` frames failed to parse; zero percent disables it
+    pub fn new(window: usize, percent: u8) -> Self {
+        let window = window.max(1);
+        Self {
+            window,
+            percent: percent.min(100),
+            reconnect: false,
+            recent: VecDeque::with_capacity(window),
+            failures: 0,
+            alarmed: false,
+        }
+    }
+
+    /// Drop the connection when the alarm fires
+    pub fn with_reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Percentage of the recorded frames that failed to parse
+    pub fn error_rate(&self) -> f64 {
+        if self.recent.is_empty() {
+            return 0.0;
+        }
+        self.failures as f64 * 100.0 / self.recent.len() as f64
+    }
+
+    /// Whether the alarm has fired and the rate is still over the threshold
+    pub fn is_alarmed(&self) -> bool {
+        self.alarmed
+    }
+
+    /// Record a frame that parsed
+    pub fn record_success(&mut self) {
+        self.record(false);
+    }
+
+    /// Record a frame that failed to parse, returning whether the connection should be dropped
+    pub fn record_failure(&mut self, exchange: ExchangeType) -> bool {
+        self.record(true);
+        // Too few frames yet to tell a storm from a few stray control messages
+        if self.alarmed || self.percent == 0 || self.recent.len() < self.window || !self.over_threshold() {
+            return false;
+        }
+
+        self.alarmed = true;
+        tracing::error!(
+            "{} failed to parse {:.0}% of the last {} frames; its message format may have changed",
+            exchange, self.error_rate(), self.window
+        );
+        if !self.reconnect {
+            return false;
+        }
+        // The new connection is judged on its own frames
+        self.reset();
+        true
+    }
+
+    /// Forget the recorded frames
+    pub fn reset(&mut self) {
+        self.recent.clear();
+        self.failures = 0;
+        self.alarmed = false;
+    }
+
+    fn record(&mut self, failed: bool) {
+        self.recent.push_back(failed);
+        self.failures += failed as usize;
+        if self.recent.len() > self.window && self.recent.pop_front() == Some(true) {
+            self.failures -= 1;
+        }
+        if self.alarmed && !self.over_threshold() {
+            self.alarmed = false;
+        }
+    }
+
+    fn over_threshold(&self) -> bool {
+        self.failures * 100 > self.percent as usize * self.recent.len()
+    }
 }
 
 /// Exchange trait that all exchange implementations must follow
@@ -181,6 +1273,12 @@ pub trait Exchange: Send + Sync {
     /// Unsubscribe from market data
     async fn unsubscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()>;
 
+    /// Subscriptions currently active, after any unsubscribes
+    fn active_subscriptions(&self) -> Vec<Subscription>;
+
+    /// Re-send exactly the active subscriptions, e.g. on a fresh connection
+    async fn resubscribe(&mut self) -> Result<()>;
+
     /// Receive the next market event (blocking)
     async fn recv_event(&mut self) -> Result<Option<MarketEvent>>;
 
@@ -189,6 +1287,33 @@ pub trait Exchange: Send + Sync {
 
     /// Get the WebSocket endpoint URL
     fn ws_endpoint(&self) -> &str;
+
+    /// Fetch up to `limit` levels per side of a symbol's book over REST, as a
+    /// snapshot `DepthUpdate` to bootstrap a local order book from.
+    ///
+    /// Exchanges without a REST snapshot return an error.
+    async fn fetch_depth_snapshot(&self, symbol: &str, _limit: u16) -> Result<DepthUpdate> {
+        Err(GatewayError::Subscription(format!("{} has no REST depth snapshot for {}", self.exchange_type(), symbol)))
+    }
+
+    /// Market events as a stream, so consumers can use stream combinators
+    /// instead of polling `recv_event`.
+    ///
+    /// Non-data frames are skipped and receive errors are yielded as items.
+    /// The stream ends once the connection closes; it does not reconnect.
+    fn event_stream(&mut self) -> BoxStream<'_, Result<MarketEvent>> {
+        stream::unfold(self, |exchange| async move {
+            while exchange.is_connected() {
+                match exchange.recv_event().await {
+                    Ok(Some(event)) => return Some((Ok(event), exchange)),
+                    Ok(None) => continue,
+                    Err(e) => return Some((Err(e), exchange)),
+                }
+            }
+            None
+        })
+        .boxed()
+    }
 }
 
 /// Result of handling a market event
@@ -199,5 +1324,246 @@ pub enum EventResult {
     /// Event was skipped (filtering logic)
     Skipped,
     /// Event processing failed
-    Failed(anyhow::Error),
+    Failed(GatewayError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockExchange};
+
+    fn trade(trade_id: u64) -> MarketEvent {
+        testing::agg_trade(ExchangeType::Binance, trade_id)
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_skips_non_events_and_ends_on_close() {
+        // Control frames stand in for pongs and acks
+        let mut exchange = MockExchange::with_events(vec![trade(1)])
+            .with_control_frame()
+            .with_event(trade(2))
+            .with_control_frame()
+            .with_event(trade(3))
+            .with_close_when_drained();
+        exchange.connect().await.unwrap();
+        let mut events = exchange.event_stream();
+
+        for trade_id in 1..=3 {
+            let event = events.next().await.unwrap().unwrap();
+            assert_eq!(event, trade(trade_id));
+        }
+        assert!(events.next().await.is_none());
+    }
+
+    #[test]
+    fn test_kline_interval_mapping() {
+        let binance: Vec<&str> = KlineInterval::ALL.iter().map(|i| i.as_str()).collect();
+        assert_eq!(
+            binance,
+            vec!["1m", "3m", "5m", "15m", "30m", "1h", "2h", "4h", "6h", "8h", "12h", "1d", "1w", "1M"]
+        );
+
+        let okx: Vec<Option<&str>> = KlineInterval::ALL.iter().map(|i| i.as_okx_str()).collect();
+        assert_eq!(
+            okx,
+            vec![
+                Some("1m"), Some("3m"), Some("5m"), Some("15m"), Some("30m"),
+                Some("1H"), Some("2H"), Some("4H"), Some("6H"), None, Some("12H"),
+                Some("1D"), Some("1W"), Some("1M"),
+            ]
+        );
+
+        for interval in KlineInterval::ALL {
+            if let Some(bar) = interval.as_okx_str() {
+                assert_eq!(KlineInterval::from_okx_str(bar), Some(interval));
+            }
+        }
+        // OKX's month and Binance's month share "1M", but OKX minutes stay lowercase
+        assert_eq!(KlineInterval::from_okx_str("1M"), Some(KlineInterval::OneMonth));
+        assert_eq!(KlineInterval::from_okx_str("1m"), Some(KlineInterval::OneMinute));
+
+        for interval in KlineInterval::ALL {
+            assert_eq!(KlineInterval::from_kucoin_str(interval.as_kucoin_str()), Some(interval));
+        }
+        assert_eq!(KlineInterval::from_kucoin_str("1M"), None);
+
+        for interval in KlineInterval::ALL {
+            if let Some(resolution) = interval.as_deribit_str() {
+                assert_eq!(KlineInterval::from_deribit_str(resolution), Some(interval));
+            }
+        }
+        assert_eq!(KlineInterval::from_deribit_str("240"), None);
+    }
+
+    #[test]
+    fn test_builder_matches_constructors() {
+        let built = Subscription::builder()
+            .symbol("BTCUSDT")
+            .data_type(DataType::Kline)
+            .interval(KlineInterval::FiveMinutes)
+            .build()
+            .unwrap();
+        assert_eq!(built, Subscription::kline("BTCUSDT", KlineInterval::FiveMinutes));
+
+        let built = Subscription::builder()
+            .symbol("BTCUSDT")
+            .data_type(DataType::AggTrade)
+            .build()
+            .unwrap();
+        assert_eq!(built, Subscription::agg_trade("BTCUSDT"));
+    }
+
+    #[test]
+    fn test_builder_rejects_mismatched_fields() {
+        // Kline without an interval
+        assert!(Subscription::builder().symbol("BTCUSDT").data_type(DataType::Kline).build().is_err());
+        assert!(Subscription::builder().symbol("BTCUSDT").data_type(DataType::ContinuousKline).build().is_err());
+
+        // Interval on a non-kline type
+        let result = Subscription::builder()
+            .symbol("BTCUSDT")
+            .data_type(DataType::BookTicker)
+            .interval(KlineInterval::OneMinute)
+            .build();
+        assert!(result.is_err());
+
+        // Contract type on a plain kline
+        let result = Subscription::builder()
+            .symbol("BTCUSDT")
+            .data_type(DataType::Kline)
+            .interval(KlineInterval::OneMinute)
+            .contract_type(ContractType::Perpetual)
+            .build();
+        assert!(result.is_err());
+
+        // Missing symbol or data type
+        assert!(Subscription::builder().data_type(DataType::AggTrade).build().is_err());
+        assert!(Subscription::builder().symbol("BTCUSDT").build().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_paces_after_burst() {
+        let mut limiter = RateLimiter::new(20, 2);
+
+        // The burst goes out at once
+        let start = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(20));
+        assert!(!limiter.try_acquire());
+
+        // Then one frame every 50ms
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(190), "paced too fast: {:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(500), "paced too slow: {:?}", elapsed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stale_watchdog() {
+        let mut watchdog = StaleWatchdog::new(Duration::from_secs(60));
+        time::advance(Duration::from_secs(59)).await;
+        assert!(!watchdog.is_stale());
+
+        // A frame pushes the deadline out again
+        watchdog.touch();
+        time::advance(Duration::from_secs(59)).await;
+        assert!(!watchdog.is_stale());
+        time::advance(Duration::from_secs(1)).await;
+        assert!(watchdog.is_stale());
+        watchdog.expired().await;
+
+        let disabled = StaleWatchdog::new(Duration::ZERO);
+        time::advance(Duration::from_secs(3600)).await;
+        assert!(!disabled.is_stale());
+        assert!(time::timeout(Duration::from_secs(1), disabled.expired()).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_error_storm_raises_the_alarm() {
+        let mut monitor = ParseErrorMonitor::new(10, 50);
+        for _ in 0..10 {
+            monitor.record_success();
+        }
+        // Failures only count once they are most of the window
+        for _ in 0..5 {
+            assert!(!monitor.record_failure(ExchangeType::Binance));
+        }
+        assert!(!monitor.is_alarmed());
+        assert!(!monitor.record_failure(ExchangeType::Binance));
+        assert!(monitor.is_alarmed());
+        assert_eq!(monitor.error_rate(), 60.0);
+
+        // Recovers once frames parse again
+        for _ in 0..5 {
+            monitor.record_success();
+        }
+        assert!(!monitor.is_alarmed());
+
+        // With reconnects enabled a storm asks for the connection to be dropped, once
+        let mut monitor = ParseErrorMonitor::new(10, 50).with_reconnect(true);
+        let drops = (0..20).filter(|_| monitor.record_failure(ExchangeType::Okx)).count();
+        assert_eq!(drops, 2);
+
+        let mut disabled = ParseErrorMonitor::new(10, 0);
+        assert!((0..20).all(|_| !disabled.record_failure(ExchangeType::Okx)));
+    }
+
+    #[test]
+    fn test_depth_options() {
+        let built = Subscription::builder()
+            .symbol("BTCUSDT")
+            .data_type(DataType::Depth)
+            .depth_levels(20)
+            .update_speed(DepthUpdateSpeed::Ms500)
+            .build()
+            .unwrap();
+        assert_eq!(built.depth_levels, Some(20));
+        assert_eq!(built.update_speed, Some(DepthUpdateSpeed::Ms500));
+        assert_eq!(Subscription::partial_depth("BTCUSDT", 5).depth_levels, Some(5));
+
+        // Depth options on another type, or zero levels
+        assert!(Subscription::builder().symbol("BTCUSDT").data_type(DataType::AggTrade).depth_levels(5).build().is_err());
+        assert!(Subscription::builder().symbol("BTCUSDT").data_type(DataType::Depth).depth_levels(0).build().is_err());
+
+        let subs = [Subscription::depth("BTCUSDT"), Subscription::partial_depth("BTCUSDT", 20)];
+        assert!(check_depth_levels(ExchangeType::Binance, &subs, &[5, 10, 20]).is_ok());
+        assert!(check_depth_levels(ExchangeType::Okx, &subs, &[5, 400]).is_err());
+
+        assert_eq!("500ms".parse::<DepthUpdateSpeed>().unwrap(), DepthUpdateSpeed::Ms500);
+        assert!("250ms".parse::<DepthUpdateSpeed>().is_err());
+    }
+
+    #[test]
+    fn test_partial_depth_derives_book_ticker() {
+        let mut depth = DepthUpdate {
+            exchange: ExchangeType::Okx,
+            symbol: "BTCUSDT".to_string(),
+            bids: vec![(49999.5, 1.2), (49999.0, 0.4), (49998.0, 3.0), (49997.5, 0.1), (49996.0, 2.5)],
+            asks: vec![(50000.0, 0.8), (50000.5, 1.1), (50001.0, 0.3), (50002.0, 5.0), (50003.5, 0.7)],
+            timestamp: 1_700_000_000_000,
+            is_snapshot: true,
+            first_update_id: None,
+            final_update_id: None,
+            prev_final_update_id: None,
+            received_at: 1_700_000_000_004,
+        };
+
+        assert_eq!(depth.to_book_ticker(), Some(BookTicker {
+            exchange: ExchangeType::Okx,
+            symbol: "BTCUSDT".to_string(),
+            bid_price: 49999.5,
+            bid_qty: 1.2,
+            ask_price: 50000.0,
+            ask_qty: 0.8,
+            timestamp: 1_700_000_000_000,
+            received_at: 1_700_000_000_004,
+        }));
+
+        // A diff only lists changed levels, so it can't say where the top is
+        depth.is_snapshot = false;
+        assert_eq!(depth.to_book_ticker(), None);
+    }
 }
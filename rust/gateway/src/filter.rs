@@ -0,0 +1,184 @@
+//! Event filtering
+//!
+//! This module drops events nobody downstream consumes (open klines, dust
+//! trades, unwanted symbols) before they are published.
+
+use crate::exchange::{EventResult, MarketEvent};
+use std::collections::HashSet;
+
+/// A single condition an event must meet to be published
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterRule {
+    /// Only publish klines whose interval has closed
+    ClosedKlinesOnly,
+    /// Only publish trades of at least this quantity
+    MinTradeQuantity(f64),
+    /// Only publish trades of at least this notional (price × quantity)
+    MinTradeNotional(f64),
+    /// Only publish events for these symbols
+    SymbolWhitelist(HashSet<String>),
+    /// Never publish events for these symbols
+    SymbolBlacklist(HashSet<String>),
+}
+
+impl FilterRule {
+    /// Whether an event passes this rule; rules ignore event types they don't apply to
+    pub fn allows(&self, event: &MarketEvent) -> bool {
+        match (self, event) {
+            (FilterRule::ClosedKlinesOnly, MarketEvent::Kline(k)) => k.is_closed,
+            (FilterRule::MinTradeQuantity(min), MarketEvent::AggTrade(t)) => t.quantity >= *min,
+            (FilterRule::MinTradeNotional(min), MarketEvent::AggTrade(t)) => t.price * t.quantity >= *min,
+            (FilterRule::SymbolWhitelist(symbols), _) => symbols.contains(event.symbol()),
+            (FilterRule::SymbolBlacklist(symbols), _) => !symbols.contains(event.symbol()),
+            _ => true,
+        }
+    }
+}
+
+/// A set of rules that must all pass for an event to be published.
+///
+/// An empty filter lets every event through.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    rules: Vec<FilterRule>,
+}
+
+impl EventFilter {
+    /// Create a filter that passes everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule
+    pub fn with_rule(mut self, rule: FilterRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Drop klines that are still open
+    pub fn with_closed_klines_only(self) -> Self {
+        self.with_rule(FilterRule::ClosedKlinesOnly)
+    }
+
+    /// Drop trades smaller than `quantity`
+    pub fn with_min_trade_quantity(self, quantity: f64) -> Self {
+        self.with_rule(FilterRule::MinTradeQuantity(quantity))
+    }
+
+    /// Drop trades worth less than `notional` in the quote asset
+    pub fn with_min_trade_notional(self, notional: f64) -> Self {
+        self.with_rule(FilterRule::MinTradeNotional(notional))
+    }
+
+    /// Drop events for symbols outside `symbols`
+    pub fn with_symbol_whitelist<I, S>(self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.with_rule(FilterRule::SymbolWhitelist(symbols.into_iter().map(Into::into).collect()))
+    }
+
+    /// Drop events for symbols in `symbols`
+    pub fn with_symbol_blacklist<I, S>(self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.with_rule(FilterRule::SymbolBlacklist(symbols.into_iter().map(Into::into).collect()))
+    }
+
+    /// Combine with another filter; events must pass both
+    pub fn and(mut self, other: EventFilter) -> Self {
+        self.rules.extend(other.rules);
+        self
+    }
+
+    /// Whether the filter has no rules
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether an event passes every rule
+    pub fn allows(&self, event: &MarketEvent) -> bool {
+        self.rules.iter().all(|rule| rule.allows(event))
+    }
+
+    /// `Processed` if the event should be published, `Skipped` if it is filtered out
+    pub fn apply(&self, event: &MarketEvent) -> EventResult {
+        if self.allows(event) {
+            EventResult::Processed
+        } else {
+            EventResult::Skipped
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{AggTrade, ExchangeType, Kline};
+    use crate::testing;
+
+    fn kline(is_closed: bool) -> MarketEvent {
+        MarketEvent::Kline(Kline {
+            exchange: ExchangeType::Binance,
+            symbol: "BTCUSDT".to_string(),
+            interval: "1m".to_string(),
+            open_time: 1_700_000_000_000,
+            close_time: 1_700_000_059_999,
+            open: 50000.0,
+            high: 50100.0,
+            low: 49900.0,
+            close: 50050.0,
+            volume: 12.5,
+            is_closed,
+            contract_type: None,
+            received_at: 0,
+        })
+    }
+
+    fn trade(symbol: &str, price: f64, quantity: f64) -> MarketEvent {
+        MarketEvent::AggTrade(AggTrade {
+            symbol: symbol.to_string(),
+            price,
+            quantity,
+            timestamp: 1_700_000_000_000,
+            ..testing::trade(ExchangeType::Binance, 1)
+        })
+    }
+
+    #[test]
+    fn test_closed_klines_only() {
+        let filter = EventFilter::new().with_closed_klines_only();
+
+        assert!(matches!(filter.apply(&kline(false)), EventResult::Skipped));
+        assert!(matches!(filter.apply(&kline(true)), EventResult::Processed));
+        // Other event types are unaffected
+        assert!(matches!(filter.apply(&trade("BTCUSDT", 50000.0, 0.001)), EventResult::Processed));
+    }
+
+    #[test]
+    fn test_small_trades_are_filtered() {
+        let by_quantity = EventFilter::new().with_min_trade_quantity(0.01);
+        assert!(matches!(by_quantity.apply(&trade("BTCUSDT", 50000.0, 0.001)), EventResult::Skipped));
+        assert!(matches!(by_quantity.apply(&trade("BTCUSDT", 50000.0, 0.01)), EventResult::Processed));
+
+        let by_notional = EventFilter::new().with_min_trade_notional(1000.0);
+        assert!(matches!(by_notional.apply(&trade("BTCUSDT", 50000.0, 0.01)), EventResult::Skipped));
+        assert!(matches!(by_notional.apply(&trade("BTCUSDT", 50000.0, 0.05)), EventResult::Processed));
+    }
+
+    #[test]
+    fn test_filters_compose() {
+        let filter = EventFilter::new()
+            .with_symbol_whitelist(["BTCUSDT", "ETHUSDT"])
+            .and(EventFilter::new().with_symbol_blacklist(["ETHUSDT"]).with_min_trade_quantity(1.0));
+
+        assert!(filter.allows(&trade("BTCUSDT", 50000.0, 2.0)));
+        assert!(!filter.allows(&trade("BTCUSDT", 50000.0, 0.5)));
+        assert!(!filter.allows(&trade("ETHUSDT", 3000.0, 2.0)));
+        assert!(!filter.allows(&trade("SOLUSDT", 100.0, 2.0)));
+        assert!(EventFilter::new().allows(&kline(false)));
+    }
+}
@@ -0,0 +1,783 @@
+//! Gate.io WebSocket implementation
+//!
+//! This module handles WebSocket connections to Gate.io's USDT-margined
+//! futures API and parses incoming market data. Contracts are named like
+//! `BTC_USDT`, and sizes are in contracts rather than the base asset.
+
+use crate::exchange::{
+    Exchange, ExchangeType, MarketEvent, AggTrade, Kline, DepthUpdate, BookTicker, Ticker24h, Subscription,
+    DataType, KlineInterval, check_depth_levels, RateLimiter, StaleWatchdog, ParseErrorMonitor, now_ms,
+};
+use crate::metrics;
+use crate::redis_publisher::RedisPublisher;
+use crate::symbol;
+use crate::clock::{self, SharedClock};
+use crate::error::{GatewayError, Result};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::time;
+use tokio_tungstenite::{connect_async, tungstenite::Message, WebSocketStream};
+use tracing::{debug, error, info, warn};
+
+/// USDT-margined futures endpoint
+pub const GATEIO_FUTURES_WS: &str = "wss://fx-ws.gateio.ws/v4/ws/usdt";
+
+/// USDT-margined futures testnet endpoint
+pub const GATEIO_FUTURES_TESTNET_WS: &str = "wss://fx-ws-testnet.gateio.ws/v4/ws/usdt";
+
+/// Default number of contracts per subscribe frame, for channels that take several
+pub const DEFAULT_SUBSCRIBE_BATCH_SIZE: usize = 50;
+
+/// Levels offered by the `futures.order_book` snapshot channel
+pub const SUPPORTED_DEPTH_LEVELS: [u16; 6] = [1, 5, 10, 20, 50, 100];
+
+/// Control frames sent per second by default
+pub const DEFAULT_MESSAGES_PER_SECOND: u32 = 10;
+
+/// Interval between `futures.ping` frames, keeping the connection from idling out
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Channels whose payload is a list of contracts, so one frame can cover many symbols
+const MULTI_CONTRACT_CHANNELS: [&str; 3] = ["futures.trades", "futures.tickers", "futures.book_ticker"];
+
+/// Gate.io-specific WebSocket client
+pub struct GateioClient {
+    exchange_type: ExchangeType,
+    ws_url: String,
+    ws: Option<WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>>,
+    /// Active subscriptions, re-sent after a reconnect
+    subscriptions: Vec<Subscription>,
+    /// Symbol each subscribed contract was requested as, so events carry the caller's name
+    symbols: HashMap<String, String>,
+    /// Events parsed from a frame but not yet returned; trade frames come in batches
+    pending: VecDeque<MarketEvent>,
+    redis_publisher: Option<RedisPublisher>,
+    connected: bool,
+    subscribe_batch_size: usize,
+    /// Paces subscribe and unsubscribe frames
+    rate_limiter: RateLimiter,
+    /// Drops the connection once it goes silent
+    watchdog: StaleWatchdog,
+    /// Flags a connection whose frames mostly fail to parse
+    parse_errors: ParseErrorMonitor,
+    /// Publish partial book snapshots as best bid/ask tickers
+    derive_book_ticker: bool,
+    /// Stamps `received_at` on parsed events
+    clock: SharedClock,
+    /// Sends `futures.ping` while connected
+    keepalive: Option<time::Interval>,
+}
+
+impl GateioClient {
+    /// Create a new Gate.io futures client
+    pub fn new(testnet: bool) -> Self {
+        let ws_url = if testnet { GATEIO_FUTURES_TESTNET_WS } else { GATEIO_FUTURES_WS };
+
+        Self {
+            exchange_type: ExchangeType::Gateio,
+            ws_url: ws_url.to_string(),
+            ws: None,
+            subscriptions: Vec::new(),
+            symbols: HashMap::new(),
+            pending: VecDeque::new(),
+            redis_publisher: None,
+            connected: false,
+            subscribe_batch_size: DEFAULT_SUBSCRIBE_BATCH_SIZE,
+            rate_limiter: RateLimiter::new(DEFAULT_MESSAGES_PER_SECOND, 1),
+            watchdog: StaleWatchdog::default(),
+            parse_errors: ParseErrorMonitor::default(),
+            derive_book_ticker: false,
+            clock: clock::system(),
+            keepalive: None,
+        }
+    }
+
+    /// Set the Redis publisher for forwarding events
+    pub fn with_redis_publisher(mut self, publisher: RedisPublisher) -> Self {
+        self.redis_publisher = Some(publisher);
+        self
+    }
+
+    /// Set how many contracts are sent per subscribe frame
+    pub fn with_subscribe_batch_size(mut self, batch_size: usize) -> Self {
+        self.subscribe_batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set how many control frames may be sent per second
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limiter = RateLimiter::new(per_second, 1);
+        self
+    }
+
+    /// Treat the connection as dead after this long without a frame (zero disables)
+    pub fn with_stale_timeout(mut self, timeout: Duration) -> Self {
+        self.watchdog = StaleWatchdog::new(timeout);
+        self
+    }
+
+    /// Alarm on, and optionally reconnect after, a storm of unparseable frames
+    pub fn with_parse_error_monitor(mut self, monitor: ParseErrorMonitor) -> Self {
+        self.parse_errors = monitor;
+        self
+    }
+
+    /// Turn partial book snapshots into best bid/ask tickers before anything sees them
+    pub fn with_derived_book_ticker(mut self, enabled: bool) -> Self {
+        self.derive_book_ticker = enabled;
+        self
+    }
+
+    /// Stamp events with this clock instead of the host's, e.g. a mock in tests
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Convert a trading pair to a Gate.io contract (e.g. BTCUSDT -> BTC_USDT)
+    pub fn to_gateio(symbol: &str) -> String {
+        symbol::to_exchange_symbol(ExchangeType::Gateio, symbol)
+    }
+
+    /// Convert a Gate.io contract to a trading pair (e.g. BTC_USDT -> BTCUSDT)
+    pub fn from_gateio(contract: &str) -> String {
+        symbol::from_exchange_symbol(ExchangeType::Gateio, contract)
+    }
+
+    /// Get the Gate.io channel and payload for a subscription, or `None` if Gate.io has no matching feed
+    fn channel(sub: &Subscription) -> Option<(&'static str, Vec<String>)> {
+        let contract = Self::to_gateio(&sub.symbol);
+        match sub.data_type {
+            DataType::AggTrade => Some(("futures.trades", vec![contract])),
+            DataType::BookTicker => Some(("futures.book_ticker", vec![contract])),
+            DataType::Ticker24h => Some(("futures.tickers", vec![contract])),
+            DataType::Depth => match sub.depth_levels {
+                // Snapshots of the top levels, without update ids to chain
+                Some(levels) => Some(("futures.order_book", vec![contract, levels.to_string(), "0".to_string()])),
+                None => Some(("futures.order_book_update", vec![contract, "100ms".to_string(), "100".to_string()])),
+            },
+            DataType::Kline => {
+                let interval = sub.interval.unwrap_or(KlineInterval::OneMinute);
+                interval.as_gateio_str()
+                    .map(|interval| ("futures.candlesticks", vec![interval.to_string(), contract]))
+            }
+            // Funding rides on the ticker and liquidations need an authenticated channel;
+            // neither is split out yet
+            DataType::ContinuousKline
+            | DataType::FundingRate
+            | DataType::Liquidation
+            | DataType::OpenInterest
+            | DataType::Nbbo
+            | DataType::Opportunity => None,
+        }
+    }
+
+    /// Build `event` frames for a set of subscriptions. Contract-list channels are
+    /// batched; the others take one frame per subscription.
+    fn build_msgs(&self, event: &str, subscriptions: &[Subscription]) -> Vec<Value> {
+        let mut batched: Vec<(&str, Vec<String>)> = Vec::new();
+        let mut single: Vec<(&str, Vec<String>)> = Vec::new();
+
+        for (channel, payload) in subscriptions.iter().filter_map(Self::channel) {
+            if MULTI_CONTRACT_CHANNELS.contains(&channel) {
+                match batched.iter_mut().find(|(c, _)| *c == channel) {
+                    Some((_, contracts)) => {
+                        if !contracts.contains(&payload[0]) {
+                            contracts.extend(payload);
+                        }
+                    }
+                    None => batched.push((channel, payload)),
+                }
+            } else if !single.contains(&(channel, payload.clone())) {
+                single.push((channel, payload));
+            }
+        }
+
+        let time = now_ms() / 1000;
+        let frame = |channel: &str, payload: &[String]| {
+            json!({ "time": time, "channel": channel, "event": event, "payload": payload })
+        };
+
+        let mut msgs = Vec::new();
+        for (channel, contracts) in &batched {
+            msgs.extend(contracts.chunks(self.subscribe_batch_size).map(|batch| frame(channel, batch)));
+        }
+        msgs.extend(single.iter().map(|(channel, payload)| frame(channel, payload)));
+        msgs
+    }
+
+    /// Build the subscribe frames for a set of subscriptions
+    fn build_subscription_msgs(&self, subscriptions: &[Subscription]) -> Vec<Value> {
+        self.build_msgs("subscribe", subscriptions)
+    }
+
+    /// Remember which symbol each contract was requested as
+    fn track_symbols(&mut self, subscriptions: &[Subscription]) {
+        for sub in subscriptions {
+            self.symbols.insert(Self::to_gateio(&sub.symbol), sub.symbol.clone());
+        }
+    }
+
+    /// Symbol reported for a contract
+    fn standard_symbol(&self, contract: &str) -> String {
+        self.symbols.get(contract)
+            .cloned()
+            .unwrap_or_else(|| Self::from_gateio(contract))
+    }
+
+    /// Send frames with a pause between them
+    async fn send_msgs(&mut self, msgs: Vec<Value>) -> Result<()> {
+        for msg in msgs {
+            self.rate_limiter.acquire().await;
+            if let Some(ref mut ws) = self.ws {
+                ws.send(Message::Text(msg.to_string())).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a number Gate.io sends either as a string or a JSON number
+    fn parse_f64(value: &Value, name: &str) -> Result<f64> {
+        match value {
+            Value::String(s) => Ok(s.parse::<f64>()?),
+            Value::Number(n) => n.as_f64().ok_or_else(|| GatewayError::Parse(format!("Invalid {}", name))),
+            _ => Err(GatewayError::Parse(format!("Missing {}", name))),
+        }
+    }
+
+    /// Parse `{"p": price, "s": size}` levels
+    fn parse_levels(levels: Option<&Value>) -> Result<Vec<(f64, f64)>> {
+        levels
+            .and_then(|l| l.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .map(|level| Ok((Self::parse_f64(&level["p"], "p")?, Self::parse_f64(&level["s"], "s")?)))
+                    .collect()
+            })
+            .unwrap_or_else(|| Ok(Vec::new()))
+    }
+
+    /// Length of a candle in milliseconds
+    fn interval_ms(interval: KlineInterval) -> i64 {
+        let minutes = match interval {
+            KlineInterval::OneMinute => 1,
+            KlineInterval::ThreeMinutes => 3,
+            KlineInterval::FiveMinutes => 5,
+            KlineInterval::FifteenMinutes => 15,
+            KlineInterval::ThirtyMinutes => 30,
+            KlineInterval::OneHour => 60,
+            KlineInterval::TwoHours => 120,
+            KlineInterval::FourHours => 240,
+            KlineInterval::SixHours => 360,
+            KlineInterval::EightHours => 480,
+            KlineInterval::TwelveHours => 720,
+            KlineInterval::OneDay => 1_440,
+            KlineInterval::OneWeek => 10_080,
+            KlineInterval::OneMonth => 43_200,
+        };
+        minutes * 60_000
+    }
+
+    /// Parse a `futures.trades` update, which carries every trade since the last push
+    fn parse_trades(&self, result: &Value) -> Result<Vec<MarketEvent>> {
+        let trades = result.as_array().ok_or_else(|| GatewayError::Parse("Missing trades array".to_string()))?;
+
+        trades.iter()
+            .map(|trade| {
+                let contract = trade["contract"].as_str().ok_or_else(|| GatewayError::Parse("Missing contract".to_string()))?;
+                // A negative size means the taker sold
+                let size = Self::parse_f64(&trade["size"], "size")?;
+
+                Ok(MarketEvent::AggTrade(AggTrade {
+                    exchange: self.exchange_type,
+                    symbol: self.standard_symbol(contract),
+                    price: Self::parse_f64(&trade["price"], "price")?,
+                    quantity: size.abs(),
+                    timestamp: trade["create_time_ms"].as_i64()
+                        .or_else(|| trade["create_time"].as_i64().map(|secs| secs * 1000))
+                        .unwrap_or_else(now_ms),
+                    is_buyer_maker: size < 0.0,
+                    trade_id: trade["id"].as_u64().ok_or_else(|| GatewayError::Parse("Missing id".to_string()))?,
+                    received_at: self.clock.now_ms(),
+                }))
+            })
+            .collect()
+    }
+
+    /// Parse a `futures.tickers` update into rolling 24-hour statistics
+    fn parse_tickers(&self, result: &Value, timestamp: i64) -> Result<Vec<MarketEvent>> {
+        let tickers = result.as_array().ok_or_else(|| GatewayError::Parse("Missing tickers array".to_string()))?;
+
+        tickers.iter()
+            .map(|ticker| {
+                let contract = ticker["contract"].as_str().ok_or_else(|| GatewayError::Parse("Missing contract".to_string()))?;
+                let last_price = Self::parse_f64(&ticker["last"], "last")?;
+                let price_change_percent = Self::parse_f64(&ticker["change_percentage"], "change_percentage")?;
+                // Gate.io only reports the change, so the open is derived from it
+                let open_price = last_price / (1.0 + price_change_percent / 100.0);
+
+                Ok(MarketEvent::Ticker24h(Ticker24h {
+                    exchange: self.exchange_type,
+                    symbol: self.standard_symbol(contract),
+                    last_price,
+                    open_price,
+                    high_price: Self::parse_f64(&ticker["high_24h"], "high_24h")?,
+                    low_price: Self::parse_f64(&ticker["low_24h"], "low_24h")?,
+                    volume: Self::parse_f64(&ticker["volume_24h_base"], "volume_24h_base")?,
+                    quote_volume: Self::parse_f64(&ticker["volume_24h_quote"], "volume_24h_quote")?,
+                    price_change: last_price - open_price,
+                    price_change_percent,
+                    timestamp,
+                    received_at: self.clock.now_ms(),
+                }))
+            })
+            .collect()
+    }
+
+    /// Parse a `futures.book_ticker` update into the best bid and ask
+    fn parse_book_ticker(&self, result: &Value) -> Result<MarketEvent> {
+        let contract = result["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing s".to_string()))?;
+
+        Ok(MarketEvent::BookTicker(BookTicker {
+            exchange: self.exchange_type,
+            symbol: self.standard_symbol(contract),
+            bid_price: Self::parse_f64(&result["b"], "b")?,
+            bid_qty: Self::parse_f64(&result["B"], "B")?,
+            ask_price: Self::parse_f64(&result["a"], "a")?,
+            ask_qty: Self::parse_f64(&result["A"], "A")?,
+            timestamp: result["t"].as_i64().unwrap_or_else(now_ms),
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse a `futures.order_book_update` diff. Sizes of zero delete a level.
+    fn parse_order_book_update(&self, result: &Value) -> Result<MarketEvent> {
+        let contract = result["s"].as_str().ok_or_else(|| GatewayError::Parse("Missing s".to_string()))?;
+
+        Ok(MarketEvent::DepthUpdate(DepthUpdate {
+            exchange: self.exchange_type,
+            symbol: self.standard_symbol(contract),
+            bids: Self::parse_levels(result.get("b"))?,
+            asks: Self::parse_levels(result.get("a"))?,
+            timestamp: result["t"].as_i64().unwrap_or_else(now_ms),
+            is_snapshot: false,
+            first_update_id: result["U"].as_u64(),
+            final_update_id: result["u"].as_u64(),
+            prev_final_update_id: None,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse a `futures.order_book` snapshot of the top levels
+    fn parse_order_book(&self, result: &Value) -> Result<MarketEvent> {
+        let contract = result["contract"].as_str().ok_or_else(|| GatewayError::Parse("Missing contract".to_string()))?;
+
+        Ok(MarketEvent::DepthUpdate(DepthUpdate {
+            exchange: self.exchange_type,
+            symbol: self.standard_symbol(contract),
+            bids: Self::parse_levels(result.get("bids"))?,
+            asks: Self::parse_levels(result.get("asks"))?,
+            timestamp: result["t"].as_i64().unwrap_or_else(now_ms),
+            is_snapshot: true,
+            first_update_id: None,
+            final_update_id: result["id"].as_u64(),
+            prev_final_update_id: None,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse a `futures.candlesticks` update. Candles are named `<interval>_<contract>`.
+    fn parse_candlesticks(&self, result: &Value) -> Result<Vec<MarketEvent>> {
+        let candles = result.as_array().ok_or_else(|| GatewayError::Parse("Missing candlesticks array".to_string()))?;
+
+        candles.iter()
+            .map(|candle| {
+                let name = candle["n"].as_str().ok_or_else(|| GatewayError::Parse("Missing n".to_string()))?;
+                let (interval, contract) = name.split_once('_')
+                    .ok_or_else(|| GatewayError::Parse(format!("Invalid candlestick name: {}", name)))?;
+                let interval = KlineInterval::from_gateio_str(interval)
+                    .ok_or_else(|| GatewayError::Parse(format!("Unknown candlestick interval: {}", interval)))?;
+                let open_time = candle["t"].as_i64().ok_or_else(|| GatewayError::Parse("Missing t".to_string()))? * 1000;
+
+                Ok(MarketEvent::Kline(Kline {
+                    exchange: self.exchange_type,
+                    symbol: self.standard_symbol(contract),
+                    interval: interval.as_str().to_string(),
+                    open_time,
+                    close_time: open_time + Self::interval_ms(interval) - 1,
+                    open: Self::parse_f64(&candle["o"], "o")?,
+                    high: Self::parse_f64(&candle["h"], "h")?,
+                    low: Self::parse_f64(&candle["l"], "l")?,
+                    close: Self::parse_f64(&candle["c"], "c")?,
+                    volume: Self::parse_f64(&candle["v"], "v")?,
+                    // `w` marks the final update of a window
+                    is_closed: candle["w"].as_bool().unwrap_or(false),
+                    contract_type: None,
+                    received_at: self.clock.now_ms(),
+                }))
+            })
+            .collect()
+    }
+
+    /// Parse a frame into market events; acks and pongs yield none
+    fn parse_value(&self, data: &Value) -> Result<Vec<MarketEvent>> {
+        let channel = data["channel"].as_str().ok_or_else(|| GatewayError::Parse("Missing channel".to_string()))?;
+        let event = data["event"].as_str().unwrap_or_default();
+
+        if let Some(err) = data.get("error").filter(|err| !err.is_null()) {
+            warn!("Gate.io error on {} {}: {} ({})", event, channel, err["message"], err["code"]);
+            return Err(GatewayError::Subscription("Error response".to_string()));
+        }
+
+        match event {
+            "update" | "all" => {}
+            "subscribe" | "unsubscribe" => return Ok(Vec::new()),
+            _ if channel == "futures.pong" => return Ok(Vec::new()),
+            _ => return Err(GatewayError::Parse(format!("Unknown event: {}", event))),
+        }
+
+        let result = &data["result"];
+        let timestamp = data["time_ms"].as_i64()
+            .or_else(|| data["time"].as_i64().map(|secs| secs * 1000))
+            .unwrap_or_else(now_ms);
+
+        match channel {
+            "futures.trades" => self.parse_trades(result),
+            "futures.tickers" => self.parse_tickers(result, timestamp),
+            "futures.book_ticker" => Ok(vec![self.parse_book_ticker(result)?]),
+            "futures.order_book_update" => Ok(vec![self.parse_order_book_update(result)?]),
+            "futures.order_book" => Ok(vec![self.parse_order_book(result)?]),
+            "futures.candlesticks" => self.parse_candlesticks(result),
+            _ => Err(GatewayError::Parse(format!("Unknown channel: {}", channel))),
+        }
+    }
+
+    /// Parse a text frame, forward the resulting events and return the first
+    async fn handle_text(&mut self, text: &str) -> Result<Option<MarketEvent>> {
+        // Raw frames are a debugging aid, only queued so they never hold up parsing
+        if let Some(ref publisher) = self.redis_publisher {
+            publisher.publish_raw(self.exchange_type, text);
+        }
+
+        let parsed = serde_json::from_str::<Value>(text)
+            .map_err(GatewayError::from)
+            .and_then(|data| self.parse_value(&data));
+
+        match parsed {
+            Ok(mut events) => {
+                self.parse_errors.record_success();
+                if self.derive_book_ticker {
+                    events = events.into_iter().map(MarketEvent::derive_book_ticker).collect();
+                }
+                // Forward to Redis if configured
+                if let Some(ref publisher) = self.redis_publisher {
+                    for event in &events {
+                        if let Err(e) = publisher.publish_event(event).await {
+                            error!("Failed to publish event to Redis: {}", e);
+                        }
+                    }
+                }
+                self.pending.extend(events);
+                Ok(self.pending.pop_front())
+            }
+            Err(e) => {
+                debug!("Failed to parse Gate.io message: {}", e);
+                metrics::global().record_parse_error(self.exchange_type);
+                if self.parse_errors.record_failure(self.exchange_type) {
+                    self.connected = false;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for GateioClient {
+    fn exchange_type(&self) -> ExchangeType {
+        self.exchange_type
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        info!("Connecting to Gate.io WebSocket at {}", self.ws_url);
+
+        let (ws_stream, _) = connect_async(&self.ws_url).await
+            .map_err(|e| GatewayError::Connect(format!("Gate.io connect failed: {}", e)))?;
+        self.ws = Some(ws_stream);
+        self.connected = true;
+        self.watchdog.touch();
+        self.pending.clear();
+        self.keepalive = Some(time::interval_at(
+            time::Instant::now() + KEEPALIVE_INTERVAL,
+            KEEPALIVE_INTERVAL,
+        ));
+
+        info!("Connected to Gate.io WebSocket");
+
+        Ok(())
+    }
+
+    fn active_subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.clone()
+    }
+
+    async fn resubscribe(&mut self) -> Result<()> {
+        // Restore channels that were active before a reconnect
+        if !self.subscriptions.is_empty() {
+            info!("Restoring {} Gate.io subscriptions", self.subscriptions.len());
+            let msgs = self.build_subscription_msgs(&self.subscriptions);
+            self.send_msgs(msgs).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(mut ws) = self.ws.take() {
+            ws.close(None).await?;
+        }
+        self.connected = false;
+        self.keepalive = None;
+        info!("Disconnected from Gate.io");
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        info!("Subscribing to {} Gate.io data streams", subscriptions.len());
+        check_depth_levels(self.exchange_type, &subscriptions, &SUPPORTED_DEPTH_LEVELS)?;
+
+        if !self.connected {
+            self.connect().await?;
+            self.resubscribe().await?;
+        }
+
+        // Channels restored by `resubscribe` are already active
+        let mut added = Vec::new();
+        for sub in subscriptions {
+            if !self.subscriptions.contains(&sub) && !added.contains(&sub) {
+                added.push(sub);
+            }
+        }
+        if added.is_empty() {
+            debug!("All requested Gate.io channels are already subscribed");
+            return Ok(());
+        }
+
+        self.track_symbols(&added);
+        let msgs = self.build_subscription_msgs(&added);
+        self.send_msgs(msgs).await?;
+        self.subscriptions.extend(added);
+
+        info!("Gate.io subscription request sent");
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        let removed: Vec<Subscription> = self.subscriptions.iter()
+            .filter(|sub| subscriptions.contains(sub))
+            .cloned()
+            .collect();
+        if removed.is_empty() {
+            debug!("No Gate.io channels to unsubscribe");
+            return Ok(());
+        }
+        self.subscriptions.retain(|sub| !removed.contains(sub));
+
+        info!("Unsubscribing from {} Gate.io data streams", removed.len());
+        let msgs = self.build_msgs("unsubscribe", &removed);
+        self.send_msgs(msgs).await
+    }
+
+    async fn recv_event(&mut self) -> Result<Option<MarketEvent>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(Some(event));
+        }
+        if !self.connected || self.ws.is_none() {
+            return Ok(None);
+        }
+
+        let ws = self.ws.as_mut().unwrap();
+
+        let keepalive = self.keepalive.as_mut();
+        let keepalive_tick = async move {
+            match keepalive {
+                Some(keepalive) => {
+                    keepalive.tick().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        let msg = tokio::select! {
+            msg = ws.next() => msg,
+            _ = keepalive_tick => {
+                debug!("Sending Gate.io keepalive ping");
+                let ping = json!({ "time": now_ms() / 1000, "channel": "futures.ping" });
+                ws.send(Message::Text(ping.to_string())).await?;
+                return Ok(None);
+            }
+            _ = self.watchdog.expired() => {
+                warn!("No Gate.io frame for {:?}, dropping the stale connection", self.watchdog.silence());
+                self.connected = false;
+                return Ok(None);
+            }
+        };
+        // Any frame, pings included, shows the connection is alive
+        self.watchdog.touch();
+
+        match msg {
+            Some(Ok(Message::Text(text))) => self.handle_text(&text).await,
+            Some(Ok(Message::Ping(payload))) => {
+                ws.send(Message::Pong(payload)).await?;
+                self.recv_event().await
+            }
+            Some(Ok(Message::Pong(_))) => {
+                self.recv_event().await
+            }
+            Some(Ok(Message::Close(_))) => {
+                self.connected = false;
+                Ok(None)
+            }
+            Some(Err(e)) => {
+                error!("Gate.io WebSocket error: {}", e);
+                self.connected = false;
+                Err(e.into())
+            }
+            None => {
+                self.connected = false;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected && !self.watchdog.is_stale()
+    }
+
+    fn ws_endpoint(&self) -> &str {
+        &self.ws_url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(client: &GateioClient, msg: &str) -> Result<Vec<MarketEvent>> {
+        client.parse_value(&serde_json::from_str(msg)?)
+    }
+
+    #[test]
+    fn test_gateio_symbol_conversion() {
+        assert_eq!(GateioClient::to_gateio("BTCUSDT"), "BTC_USDT");
+        assert_eq!(GateioClient::to_gateio("ETHUSDC"), "ETH_USDC");
+        assert_eq!(GateioClient::from_gateio("BTC_USDT"), "BTCUSDT");
+        assert_eq!(GateioClient::from_gateio("SOL_USDT"), "SOLUSDT");
+    }
+
+    #[test]
+    fn test_subscription_frames() {
+        let client = GateioClient::new(false).with_subscribe_batch_size(2);
+        let msgs = client.build_subscription_msgs(&[
+            Subscription::agg_trade("BTCUSDT"),
+            Subscription::agg_trade("ETHUSDT"),
+            Subscription::agg_trade("SOLUSDT"),
+            Subscription::kline("BTCUSDT", KlineInterval::OneHour),
+            Subscription::depth("BTCUSDT"),
+            Subscription::partial_depth("ETHUSDT", 20),
+            Subscription::funding_rate("BTCUSDT"),
+        ]);
+
+        let frames: Vec<(&str, Vec<&str>)> = msgs.iter()
+            .map(|msg| {
+                assert_eq!(msg["event"], "subscribe");
+                let payload = msg["payload"].as_array().unwrap().iter().map(|p| p.as_str().unwrap()).collect();
+                (msg["channel"].as_str().unwrap(), payload)
+            })
+            .collect();
+        assert_eq!(frames, vec![
+            ("futures.trades", vec!["BTC_USDT", "ETH_USDT"]),
+            ("futures.trades", vec!["SOL_USDT"]),
+            ("futures.candlesticks", vec!["1h", "BTC_USDT"]),
+            ("futures.order_book_update", vec!["BTC_USDT", "100ms", "100"]),
+            ("futures.order_book", vec!["ETH_USDT", "20", "0"]),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_trades() {
+        let client = GateioClient::new(false);
+        let msg = r#"{
+            "time": 1700000000,
+            "time_ms": 1700000000123,
+            "channel": "futures.trades",
+            "event": "update",
+            "result": [
+                {"size": -108, "id": 27753479, "create_time": 1700000000, "create_time_ms": 1700000000120, "price": "37000.4", "contract": "BTC_USDT"},
+                {"size": 3, "id": 27753480, "create_time": 1700000000, "create_time_ms": 1700000000121, "price": "37000.5", "contract": "BTC_USDT"}
+            ]
+        }"#;
+
+        let events = parse(&client, msg).unwrap();
+        assert_eq!(events.len(), 2);
+
+        let MarketEvent::AggTrade(sell) = &events[0] else {
+            panic!("Expected AggTrade event");
+        };
+        assert_eq!(sell.exchange, ExchangeType::Gateio);
+        assert_eq!(sell.symbol, "BTCUSDT");
+        assert_eq!(sell.price, 37000.4);
+        assert_eq!(sell.quantity, 108.0);
+        assert_eq!(sell.timestamp, 1700000000120);
+        assert_eq!(sell.trade_id, 27753479);
+        assert!(sell.is_buyer_maker);
+
+        let MarketEvent::AggTrade(buy) = &events[1] else {
+            panic!("Expected AggTrade event");
+        };
+        assert_eq!(buy.quantity, 3.0);
+        assert!(!buy.is_buyer_maker);
+    }
+
+    #[test]
+    fn test_parse_candlesticks() {
+        let client = GateioClient::new(false);
+        let msg = r#"{
+            "time": 1700000030,
+            "channel": "futures.candlesticks",
+            "event": "update",
+            "result": [
+                {"t": 1699999980, "v": 2750, "c": "37010.1", "h": "37020.0", "l": "36990.5", "o": "37000.0", "n": "1m_BTC_USDT", "a": "101.25", "w": true}
+            ]
+        }"#;
+
+        let events = parse(&client, msg).unwrap();
+        let MarketEvent::Kline(kline) = &events[0] else {
+            panic!("Expected Kline event");
+        };
+        assert_eq!(kline.symbol, "BTCUSDT");
+        assert_eq!(kline.interval, "1m");
+        assert_eq!(kline.open_time, 1699999980000);
+        assert_eq!(kline.close_time, 1700000039999);
+        assert_eq!(kline.close, 37010.1);
+        assert_eq!(kline.volume, 2750.0);
+        assert!(kline.is_closed);
+    }
+
+    #[test]
+    fn test_acks_and_errors() {
+        let client = GateioClient::new(false);
+        let ack = r#"{"time":1700000000,"channel":"futures.trades","event":"subscribe","error":null,"result":{"status":"success"}}"#;
+        let pong = r#"{"time":1700000000,"channel":"futures.pong","event":"","error":null,"result":null}"#;
+        let error = r#"{"time":1700000000,"channel":"futures.trades","event":"subscribe","error":{"code":2,"message":"unknown contract BTC_XYZ"},"result":null}"#;
+
+        assert!(parse(&client, ack).unwrap().is_empty());
+        assert!(parse(&client, pong).unwrap().is_empty());
+        assert!(parse(&client, error).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+//! Health and readiness probes
+//!
+//! This module tracks each exchange's connection state alongside Redis
+//! health and serves them on `/healthz` and `/readyz` for the orchestrator.
+//! `/subscriptions` lists the streams each exchange is subscribed to.
+
+use crate::clock::{self, SharedClock};
+use crate::exchange::{ExchangeType, Subscription};
+use crate::error::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+/// Connection state of one exchange
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ConnectionStatus {
+    /// Whether the WebSocket is currently connected
+    pub connected: bool,
+    /// Whether an event has arrived since the connection opened, showing data is flowing
+    pub healthy: bool,
+    /// When the last event arrived (Unix ms)
+    pub last_event_ms: Option<i64>,
+    /// When the current connection opened (Unix ms)
+    #[serde(skip)]
+    pub connected_at_ms: Option<i64>,
+}
+
+/// Shared connection and Redis state the probes report on
+#[derive(Debug, Clone)]
+pub struct HealthState {
+    exchanges: Arc<RwLock<HashMap<ExchangeType, ConnectionStatus>>>,
+    redis_ok: Arc<AtomicBool>,
+    /// Active subscriptions per exchange, as of its last (re)subscribe
+    subscriptions: Arc<RwLock<HashMap<ExchangeType, Vec<Subscription>>>>,
+    /// Stamps `last_event_ms` and `connected_at_ms`
+    clock: SharedClock,
+}
+
+impl Default for HealthState {
+    fn default() -> Self {
+        Self {
+            exchanges: Arc::default(),
+            redis_ok: Arc::default(),
+            subscriptions: Arc::default(),
+            clock: clock::system(),
+        }
+    }
+}
+
+impl HealthState {
+    /// Create a state with no exchanges and Redis not yet verified
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stamp event arrivals with this clock instead of the host's
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record whether an exchange is connected. A new connection is unhealthy until its first event.
+    pub fn set_connected(&self, exchange: ExchangeType, connected: bool) {
+        let mut exchanges = self.exchanges.write().unwrap();
+        let status = exchanges.entry(exchange).or_default();
+        status.healthy &= status.connected && connected;
+        if !connected {
+            status.connected_at_ms = None;
+        } else if !status.connected {
+            status.connected_at_ms = Some(self.clock.now_ms());
+        }
+        status.connected = connected;
+    }
+
+    /// Record that an event just arrived from an exchange
+    pub fn record_event(&self, exchange: ExchangeType) {
+        let mut exchanges = self.exchanges.write().unwrap();
+        let status = exchanges.entry(exchange).or_default();
+        let now = self.clock.now_ms();
+        status.connected_at_ms = status.connected_at_ms.or(Some(now));
+        status.connected = true;
+        status.healthy = true;
+        status.last_event_ms = Some(now);
+    }
+
+    /// Record the outcome of the latest Redis ping
+    pub fn set_redis_ok(&self, ok: bool) {
+        self.redis_ok.store(ok, Ordering::Relaxed);
+    }
+
+    /// Record the subscriptions an exchange currently has active
+    pub fn set_subscriptions(&self, exchange: ExchangeType, subscriptions: Vec<Subscription>) {
+        self.subscriptions.write().unwrap().insert(exchange, subscriptions);
+    }
+
+    /// Active subscriptions of one exchange
+    pub fn subscriptions(&self, exchange: ExchangeType) -> Vec<Subscription> {
+        self.subscriptions.read().unwrap().get(&exchange).cloned().unwrap_or_default()
+    }
+
+    /// Status of one exchange, if it has reported yet
+    pub fn status(&self, exchange: ExchangeType) -> Option<ConnectionStatus> {
+        self.exchanges.read().unwrap().get(&exchange).copied()
+    }
+
+    /// How long a connected exchange has gone without an event, counting from
+    /// the connection opening if nothing arrived on it yet; `None` while disconnected
+    pub fn silent_for(&self, exchange: ExchangeType) -> Option<Duration> {
+        let status = self.status(exchange).filter(|status| status.connected)?;
+        let since = status.last_event_ms.max(status.connected_at_ms)?;
+        Some(Duration::from_millis((self.clock.now_ms() - since).max(0) as u64))
+    }
+
+    /// Whether an exchange is connected and has delivered data on this connection,
+    /// as opposed to merely having an open socket
+    pub fn is_healthy(&self, exchange: ExchangeType) -> bool {
+        self.status(exchange).is_some_and(|status| status.connected && status.healthy)
+    }
+
+    /// Ready once Redis answers and at least one exchange is connected
+    pub fn is_ready(&self) -> bool {
+        self.redis_ok.load(Ordering::Relaxed)
+            && self.exchanges.read().unwrap().values().any(|status| status.connected)
+    }
+
+    /// JSON report of Redis and every exchange's connection state
+    pub fn report(&self) -> serde_json::Value {
+        let exchanges: BTreeMap<_, _> = self.exchanges
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(exchange, status)| (exchange.to_string(), *status))
+            .collect();
+
+        json!({
+            "ready": self.is_ready(),
+            "redis": self.redis_ok.load(Ordering::Relaxed),
+            "exchanges": exchanges,
+        })
+    }
+
+    /// JSON list of every exchange's active subscriptions, with their total
+    pub fn subscriptions_report(&self) -> serde_json::Value {
+        let subscriptions = self.subscriptions.read().unwrap();
+        let exchanges: BTreeMap<_, _> = subscriptions
+            .iter()
+            .map(|(exchange, subs)| {
+                let mut names: Vec<String> = subs.iter().map(Subscription::to_string).collect();
+                names.sort();
+                (exchange.to_string(), names)
+            })
+            .collect();
+
+        json!({
+            "total": subscriptions.values().map(Vec::len).sum::<usize>(),
+            "exchanges": exchanges,
+        })
+    }
+}
+
+/// `/healthz` answers 200 while the process is serving; `/readyz` is 503 until ready
+async fn handle(state: HealthState, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let status = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/healthz") => StatusCode::OK,
+        (&Method::GET, "/readyz") if state.is_ready() => StatusCode::OK,
+        (&Method::GET, "/readyz") => StatusCode::SERVICE_UNAVAILABLE,
+        (&Method::GET, "/subscriptions") => {
+            return Ok(Response::builder()
+                .header("Content-Type", "application/json")
+                .body(Body::from(state.subscriptions_report().to_string()))
+                .expect("static response parts are valid"));
+        }
+        _ => {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .expect("static response parts are valid"));
+        }
+    };
+
+    Ok(Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(state.report().to_string()))
+        .expect("static response parts are valid"))
+}
+
+/// Serve the probes on an already-bound listener
+pub fn spawn_server(state: HealthState, listener: TcpListener) -> Result<JoinHandle<()>> {
+    listener.set_nonblocking(true)?;
+    let server = Server::from_tcp(listener)?.serve(make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+    }));
+    info!("Serving health probes on http://{}/readyz", server.local_addr());
+
+    Ok(tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("Health server failed: {}", e);
+        }
+    }))
+}
+
+/// Serve the probes on all interfaces at `port`
+pub fn serve(state: HealthState, port: u16) -> Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port)))?;
+    spawn_server(state, listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockClock;
+    use std::time::Duration;
+
+    #[test]
+    fn test_ready_needs_redis_and_one_exchange() {
+        let state = HealthState::new();
+        assert!(!state.is_ready());
+
+        state.set_connected(ExchangeType::Binance, true);
+        state.set_connected(ExchangeType::Okx, false);
+        assert!(!state.is_ready());
+
+        state.set_redis_ok(true);
+        assert!(state.is_ready());
+
+        state.record_event(ExchangeType::Okx);
+        let okx = state.status(ExchangeType::Okx).unwrap();
+        assert!(okx.connected);
+        assert!(okx.last_event_ms.is_some());
+    }
+
+    #[test]
+    fn test_last_event_is_stamped_by_the_clock() {
+        let clock = MockClock::new(1_700_000_000_000);
+        let state = HealthState::new().with_clock(Arc::new(clock.clone()));
+
+        state.record_event(ExchangeType::Binance);
+        clock.advance(Duration::from_secs(5));
+        state.record_event(ExchangeType::Okx);
+
+        assert_eq!(state.status(ExchangeType::Binance).unwrap().last_event_ms, Some(1_700_000_000_000));
+        assert_eq!(state.status(ExchangeType::Okx).unwrap().last_event_ms, Some(1_700_000_005_000));
+    }
+
+    #[test]
+    fn test_silence_counts_from_the_last_event_or_the_connection() {
+        let clock = MockClock::new(1_700_000_000_000);
+        let state = HealthState::new().with_clock(Arc::new(clock.clone()));
+        assert_eq!(state.silent_for(ExchangeType::Binance), None);
+
+        state.set_connected(ExchangeType::Binance, true);
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(state.silent_for(ExchangeType::Binance), Some(Duration::from_secs(3)));
+
+        state.record_event(ExchangeType::Binance);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(state.silent_for(ExchangeType::Binance), Some(Duration::from_secs(2)));
+
+        // A new connection starts its own count, whatever the last one delivered
+        state.set_connected(ExchangeType::Binance, false);
+        assert_eq!(state.silent_for(ExchangeType::Binance), None);
+        clock.advance(Duration::from_secs(10));
+        state.set_connected(ExchangeType::Binance, true);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(state.silent_for(ExchangeType::Binance), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_healthy_needs_an_event_on_each_connection() {
+        let state = HealthState::new();
+        state.set_connected(ExchangeType::Binance, true);
+        assert!(!state.is_healthy(ExchangeType::Binance));
+
+        state.record_event(ExchangeType::Binance);
+        state.set_connected(ExchangeType::Binance, true);
+        assert!(state.is_healthy(ExchangeType::Binance));
+
+        // A reconnect starts unhealthy again
+        state.set_connected(ExchangeType::Binance, false);
+        state.set_connected(ExchangeType::Binance, true);
+        assert!(!state.is_healthy(ExchangeType::Binance));
+        assert!(!state.is_healthy(ExchangeType::Okx));
+    }
+
+    #[tokio::test]
+    async fn test_readyz_turns_unavailable_when_exchange_disconnects() {
+        let state = HealthState::new();
+        state.set_redis_ok(true);
+        state.record_event(ExchangeType::Binance);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = spawn_server(state.clone(), listener).unwrap();
+
+        let response = reqwest::get(format!("http://{}/readyz", addr)).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        state.set_connected(ExchangeType::Binance, false);
+        let response = reqwest::get(format!("http://{}/readyz", addr)).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["exchanges"]["binance"]["connected"], false);
+        assert!(body["exchanges"]["binance"]["last_event_ms"].is_i64());
+
+        // Liveness doesn't depend on the exchanges
+        let response = reqwest::get(format!("http://{}/healthz", addr)).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_endpoint_lists_every_exchange() {
+        let state = HealthState::new();
+        state.set_subscriptions(
+            ExchangeType::Binance,
+            vec![Subscription::agg_trade("BTCUSDT"), Subscription::partial_depth("BTCUSDT", 20)],
+        );
+        state.set_subscriptions(ExchangeType::Okx, vec![Subscription::book_ticker("ETHUSDT")]);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = spawn_server(state, listener).unwrap();
+
+        let response = reqwest::get(format!("http://{}/subscriptions", addr)).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["total"], 3);
+        assert_eq!(body["exchanges"]["binance"], json!(["BTCUSDT aggTrade", "BTCUSDT depth 20 levels"]));
+        assert_eq!(body["exchanges"]["okx"], json!(["ETHUSDT bookTicker"]));
+
+        handle.abort();
+    }
+}
@@ -0,0 +1,208 @@
+//! Tradable instrument listings
+//!
+//! This module fetches the symbols an exchange lists over REST, so symbol
+//! names can be checked before the gateway subscribes to them.
+
+use crate::exchange::{ExchangeType, Subscription};
+use crate::orderbook::{BINANCE_FUTURES_REST, BINANCE_FUTURES_TESTNET_REST, OKX_REST};
+use crate::symbol;
+use crate::error::{GatewayError, Result};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashSet};
+use tracing::info;
+
+/// Symbols an exchange lists, fetched once and checked against subscriptions
+#[derive(Debug, Clone)]
+pub struct InstrumentList {
+    exchange: ExchangeType,
+    symbols: HashSet<String>,
+}
+
+impl InstrumentList {
+    /// Create a list from already known standard symbols
+    pub fn new(exchange: ExchangeType, symbols: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            exchange,
+            symbols: symbols.into_iter().collect(),
+        }
+    }
+
+    /// Fetch the symbols an exchange currently lists
+    pub async fn fetch(exchange: ExchangeType, testnet: bool) -> Result<Self> {
+        Ok(Self::new(exchange, fetch_symbols(exchange, testnet).await?))
+    }
+
+    /// Exchange the symbols are listed on
+    pub fn exchange(&self) -> ExchangeType {
+        self.exchange
+    }
+
+    /// Number of listed symbols
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Check if the exchange lists no symbols
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Check if the exchange lists a symbol
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.symbols.contains(symbol)
+    }
+
+    /// Fail if any subscription is for a symbol the exchange doesn't list, naming every such symbol
+    pub fn validate_subscriptions(&self, subs: &[Subscription]) -> Result<()> {
+        let unknown: BTreeSet<&str> = subs.iter()
+            .map(|sub| sub.symbol.as_str())
+            .filter(|symbol| !self.contains(symbol))
+            .collect();
+
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        Err(GatewayError::Subscription(format!(
+            "{} does not list {} requested symbol(s): {}",
+            self.exchange,
+            unknown.len(),
+            unknown.into_iter().collect::<Vec<_>>().join(", ")
+        )))
+    }
+}
+
+/// Check if `fetch_symbols` can list an exchange's symbols
+pub fn supports_listing(exchange: ExchangeType) -> bool {
+    matches!(exchange, ExchangeType::Binance | ExchangeType::Okx)
+}
+
+/// Fetch the tradable symbols an exchange lists, as standard symbols, sorted
+pub async fn fetch_symbols(exchange: ExchangeType, testnet: bool) -> Result<Vec<String>> {
+    let http = reqwest::Client::new();
+
+    let mut symbols = match exchange {
+        ExchangeType::Binance => {
+            let rest_url = if testnet { BINANCE_FUTURES_TESTNET_REST } else { BINANCE_FUTURES_REST };
+            let url = format!("{}/fapi/v1/exchangeInfo", rest_url);
+            info!("Fetching Binance instruments from {}", url);
+            let data: Value = http.get(&url).send().await?
+                .error_for_status()?
+                .json()
+                .await?;
+            parse_binance_exchange_info(&data)?
+        }
+        ExchangeType::Okx => {
+            // Spot instIds, the instruments the OKX client streams
+            let url = format!("{}/api/v5/public/instruments?instType=SPOT", OKX_REST);
+            info!("Fetching OKX instruments from {}", url);
+            let mut request = http.get(&url);
+            if testnet {
+                request = request.header("x-simulated-trading", "1");
+            }
+            let data: Value = request.send().await?
+                .error_for_status()?
+                .json()
+                .await?;
+            parse_okx_instruments(&data)?
+        }
+        ExchangeType::Bybit | ExchangeType::Coinbase | ExchangeType::Kucoin | ExchangeType::Deribit
+        | ExchangeType::Gateio | ExchangeType::Bitget => {
+            return Err(GatewayError::Config(format!("Listing symbols is not supported for {}", exchange)));
+        }
+    };
+
+    symbols.sort();
+    symbols.dedup();
+    Ok(symbols)
+}
+
+/// Parse a `/fapi/v1/exchangeInfo` response into the symbols currently trading
+pub fn parse_binance_exchange_info(data: &Value) -> Result<Vec<String>> {
+    let symbols = data["symbols"].as_array().ok_or_else(|| GatewayError::Parse("Missing symbols array".to_string()))?;
+
+    Ok(symbols.iter()
+        .filter(|s| s["status"].as_str() == Some("TRADING"))
+        .filter_map(|s| s["symbol"].as_str().map(String::from))
+        .collect())
+}
+
+/// Parse an `/api/v5/public/instruments` response into the live instruments, as standard symbols
+pub fn parse_okx_instruments(data: &Value) -> Result<Vec<String>> {
+    let code = data["code"].as_str().unwrap_or_default();
+    if code != "0" {
+        return Err(GatewayError::Connect(format!("OKX instruments request failed: {} ({})", data["msg"], code)));
+    }
+    let instruments = data["data"].as_array().ok_or_else(|| GatewayError::Parse("Missing data array".to_string()))?;
+
+    Ok(instruments.iter()
+        .filter(|i| i["state"].as_str() == Some("live"))
+        .filter_map(|i| i["instId"].as_str().map(|inst_id| symbol::from_exchange_symbol(ExchangeType::Okx, inst_id)))
+        .collect())
+}
+
+/// Keep the symbols containing `pattern`, ignoring case
+pub fn filter_symbols(symbols: Vec<String>, pattern: Option<&str>) -> Vec<String> {
+    match pattern {
+        Some(pattern) => {
+            let pattern = pattern.to_uppercase();
+            symbols.into_iter().filter(|s| s.to_uppercase().contains(&pattern)).collect()
+        }
+        None => symbols,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_binance_exchange_info() {
+        let json = r#"{
+            "timezone": "UTC",
+            "serverTime": 1700000000000,
+            "symbols": [
+                {"symbol": "BTCUSDT", "pair": "BTCUSDT", "contractType": "PERPETUAL", "status": "TRADING", "baseAsset": "BTC", "quoteAsset": "USDT"},
+                {"symbol": "ETHUSDT", "pair": "ETHUSDT", "contractType": "PERPETUAL", "status": "TRADING", "baseAsset": "ETH", "quoteAsset": "USDT"},
+                {"symbol": "BTCUSDT_240329", "pair": "BTCUSDT", "contractType": "CURRENT_QUARTER", "status": "SETTLING", "baseAsset": "BTC", "quoteAsset": "USDT"}
+            ]
+        }"#;
+
+        let symbols = parse_binance_exchange_info(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(symbols, vec!["BTCUSDT", "ETHUSDT"]);
+
+        assert_eq!(filter_symbols(symbols.clone(), Some("btc")), vec!["BTCUSDT"]);
+        assert_eq!(filter_symbols(symbols, None).len(), 2);
+    }
+
+    #[test]
+    fn test_parse_okx_instruments() {
+        let json = r#"{"code":"0","msg":"","data":[
+            {"instType":"SPOT","instId":"BTC-USDT","baseCcy":"BTC","quoteCcy":"USDT","state":"live"},
+            {"instType":"SPOT","instId":"ETH-USDC","baseCcy":"ETH","quoteCcy":"USDC","state":"live"},
+            {"instType":"SPOT","instId":"OLD-USDT","baseCcy":"OLD","quoteCcy":"USDT","state":"suspend"}
+        ]}"#;
+
+        let symbols = parse_okx_instruments(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(symbols, vec!["BTCUSDT", "ETHUSDC"]);
+    }
+
+    #[test]
+    fn test_validate_subscriptions() {
+        let listed = InstrumentList::new(ExchangeType::Binance, ["BTCUSDT".to_string(), "ETHUSDT".to_string()]);
+
+        let valid = vec![Subscription::agg_trade("BTCUSDT"), Subscription::depth("ETHUSDT")];
+        assert!(listed.validate_subscriptions(&valid).is_ok());
+
+        let bogus = vec![
+            Subscription::agg_trade("BTCUSDT"),
+            Subscription::agg_trade("NOTACOIN"),
+            Subscription::depth("NOTACOIN"),
+            Subscription::book_ticker("FAKEUSDT"),
+        ];
+        let err = listed.validate_subscriptions(&bogus).unwrap_err().to_string();
+        assert_eq!(err, "binance does not list 2 requested symbol(s): FAKEUSDT, NOTACOIN");
+
+        assert!(supports_listing(ExchangeType::Okx));
+        assert!(!supports_listing(ExchangeType::Kucoin));
+    }
+}
@@ -0,0 +1,167 @@
+//! Kafka publisher for distributing market data
+//!
+//! This module produces market events to Kafka, one topic per event type,
+//! keyed by symbol so each symbol's events land on one partition in order.
+
+use crate::exchange::MarketEvent;
+use crate::redis_publisher::{self, SerializationFormat};
+use crate::error::{GatewayError, Result};
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use std::time::Duration;
+use tracing::info;
+
+/// Prefix of every topic name (e.g. `flash_arb.tick`)
+pub const DEFAULT_TOPIC_PREFIX: &str = "flash_arb";
+
+/// How long the producer keeps retrying a message before giving up on it
+const MESSAGE_TIMEOUT_MS: &str = "5000";
+
+/// Longest wait for queued messages on flush
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct KafkaConfig {
+    /// Comma-separated bootstrap servers
+    pub brokers: String,
+    pub topic_prefix: String,
+    /// Payload encoding
+    pub format: SerializationFormat,
+}
+
+impl Default for KafkaConfig {
+    fn default() -> Self {
+        Self {
+            brokers: "localhost:9092".to_string(),
+            topic_prefix: DEFAULT_TOPIC_PREFIX.to_string(),
+            format: SerializationFormat::Json,
+        }
+    }
+}
+
+/// Topic an event is produced to, named after its Redis channel (`tick`, `depth`, ...)
+pub fn topic_for(prefix: &str, event: &MarketEvent) -> String {
+    let channel = redis_publisher::channel_for(event);
+    let name = channel.strip_prefix("flash_arb:").unwrap_or(channel);
+    format!("{}.{}", prefix, name)
+}
+
+/// Message key, so every event of a symbol goes to the same partition
+pub fn key_for(event: &MarketEvent) -> &str {
+    event.symbol()
+}
+
+/// Kafka publisher for market data
+pub struct KafkaPublisher {
+    producer: FutureProducer,
+    topic_prefix: String,
+    format: SerializationFormat,
+}
+
+impl KafkaPublisher {
+    /// Create a producer for the configured brokers
+    pub fn new(config: KafkaConfig) -> Result<Self> {
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", &config.brokers)
+            .set("message.timeout.ms", MESSAGE_TIMEOUT_MS)
+            .create()
+            .map_err(|e| GatewayError::Kafka(format!("Failed to create Kafka producer: {}", e)))?;
+
+        info!("Producing to Kafka at {} (topics {}.*)", config.brokers, config.topic_prefix);
+
+        Ok(Self {
+            producer,
+            topic_prefix: config.topic_prefix,
+            format: config.format,
+        })
+    }
+
+    /// Queue an event for delivery; delivery itself happens in the background
+    pub fn publish_event(&self, event: &MarketEvent) -> Result<()> {
+        let topic = topic_for(&self.topic_prefix, event);
+        let payload = self.format.encode(event)?;
+        let record = FutureRecord::to(&topic)
+            .key(key_for(event))
+            .payload(&payload);
+
+        self.producer
+            .send_result(record)
+            .map(|_| ())
+            .map_err(|(e, _)| GatewayError::Kafka(format!("Failed to produce to {}: {}", topic, e)))
+    }
+
+    /// Wait for every queued message to be delivered
+    pub async fn flush(&self) -> Result<()> {
+        let producer = self.producer.clone();
+        tokio::task::spawn_blocking(move || producer.flush(FLUSH_TIMEOUT))
+            .await
+            .map_err(|e| GatewayError::Kafka(format!("Kafka flush task failed: {}", e)))?
+            .map_err(|e| GatewayError::Kafka(format!("Failed to flush Kafka producer: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{AggTrade, DepthUpdate, ExchangeType};
+    use crate::testing;
+    use rdkafka::consumer::{Consumer, StreamConsumer};
+    use rdkafka::Message;
+    use tokio::time;
+
+    fn trade() -> MarketEvent {
+        MarketEvent::AggTrade(AggTrade { timestamp: 1_700_000_000_000, ..testing::trade(ExchangeType::Binance, 42) })
+    }
+
+    #[test]
+    fn test_topic_and_key_derivation() {
+        let depth = MarketEvent::DepthUpdate(DepthUpdate {
+            exchange: ExchangeType::Okx,
+            symbol: "ETHUSDT".to_string(),
+            bids: vec![],
+            asks: vec![],
+            timestamp: 1_700_000_000_000,
+            is_snapshot: false,
+            first_update_id: None,
+            final_update_id: None,
+            prev_final_update_id: None,
+            received_at: 0,
+        });
+
+        assert_eq!(topic_for(DEFAULT_TOPIC_PREFIX, &trade()), "flash_arb.tick");
+        assert_eq!(topic_for("md", &depth), "md.depth");
+        assert_eq!(key_for(&trade()), "BTCUSDT");
+        assert_eq!(key_for(&depth), "ETHUSDT");
+    }
+
+    #[tokio::test]
+    #[ignore]  // Requires Kafka running on localhost:9092
+    async fn test_produce_round_trip() {
+        let prefix = format!("flash_arb_test_{}", uuid::Uuid::new_v4().simple());
+        let config = KafkaConfig {
+            topic_prefix: prefix.clone(),
+            ..KafkaConfig::default()
+        };
+        let publisher = KafkaPublisher::new(config.clone()).unwrap();
+
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", &config.brokers)
+            .set("group.id", &prefix)
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .unwrap();
+        let topic = topic_for(&prefix, &trade());
+        consumer.subscribe(&[&topic]).unwrap();
+
+        publisher.publish_event(&trade()).unwrap();
+        publisher.flush().await.unwrap();
+
+        let message = time::timeout(Duration::from_secs(30), consumer.recv())
+            .await
+            .expect("no message within 30s")
+            .unwrap();
+        assert_eq!(message.key(), Some(&b"BTCUSDT"[..]));
+        let event: MarketEvent = serde_json::from_slice(message.payload().unwrap()).unwrap();
+        assert_eq!(event, trade());
+    }
+}
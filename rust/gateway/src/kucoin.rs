@@ -0,0 +1,791 @@
+//! KuCoin WebSocket implementation
+//!
+//! This module handles WebSocket connections to the KuCoin spot feed and
+//! parses incoming market data. KuCoin hands out the WebSocket endpoint and
+//! a connection token over REST, so every connect starts with that request.
+
+use crate::exchange::{
+    Exchange, ExchangeType, MarketEvent, AggTrade, Kline, DepthUpdate, BookTicker, Subscription,
+    DataType, KlineInterval, check_depth_levels, RateLimiter, StaleWatchdog, ParseErrorMonitor, now_ms,
+};
+use crate::metrics;
+use crate::redis_publisher::RedisPublisher;
+use crate::symbol;
+use crate::clock::{self, SharedClock};
+use crate::error::{GatewayError, Result};
+use async_trait::async_trait;
+use chrono::{Months, TimeZone, Utc};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::time;
+use tokio_tungstenite::{connect_async, tungstenite::Message, WebSocketStream};
+use tracing::{debug, error, info, warn};
+use url::Url;
+
+/// REST endpoint that issues public WebSocket tokens
+pub const KUCOIN_BULLET_PUBLIC: &str = "https://api.kucoin.com/api/v1/bullet-public";
+
+/// Endpoint reported before the first connect; the real one comes from the bullet request
+pub const KUCOIN_WS: &str = "wss://ws-api-spot.kucoin.com/";
+
+/// Default number of topic targets per subscribe frame (KuCoin's limit is 100)
+pub const DEFAULT_SUBSCRIBE_BATCH_SIZE: usize = 100;
+
+/// Levels offered by the `level2Depth5` and `level2Depth50` topics
+pub const SUPPORTED_DEPTH_LEVELS: [u16; 2] = [5, 50];
+
+/// Ping interval used when the bullet response doesn't specify one
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(18);
+
+/// How long to wait for the `welcome` frame after connecting
+const WELCOME_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Control frames sent per second by default (KuCoin allows 100 per 10s)
+pub const DEFAULT_MESSAGES_PER_SECOND: u32 = 10;
+
+/// WebSocket endpoint and token issued by the bullet request
+#[derive(Debug, Clone, PartialEq)]
+pub struct KucoinEndpoint {
+    pub endpoint: String,
+    pub token: String,
+    pub ping_interval: Duration,
+}
+
+impl KucoinEndpoint {
+    /// URL to connect to, carrying the token and a fresh connect ID
+    pub fn connect_url(&self) -> Result<Url> {
+        let connect_id = uuid::Uuid::new_v4().to_string();
+        Ok(Url::parse_with_params(
+            &self.endpoint,
+            &[("token", self.token.as_str()), ("connectId", connect_id.as_str())],
+        )?)
+    }
+}
+
+/// KuCoin-specific WebSocket client
+pub struct KucoinClient {
+    exchange_type: ExchangeType,
+    bullet_url: String,
+    ws_url: String,
+    http: reqwest::Client,
+    ws: Option<WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>>,
+    /// Active subscriptions, re-sent after a reconnect
+    subscriptions: Vec<Subscription>,
+    redis_publisher: Option<RedisPublisher>,
+    connected: bool,
+    subscribe_batch_size: usize,
+    /// Paces subscribe and unsubscribe frames
+    rate_limiter: RateLimiter,
+    /// Drops the connection once it goes silent
+    watchdog: StaleWatchdog,
+    /// Flags a connection whose frames mostly fail to parse
+    parse_errors: ParseErrorMonitor,
+    /// Publish partial book snapshots as best bid/ask tickers
+    derive_book_ticker: bool,
+    /// Stamps `received_at` on parsed events
+    clock: SharedClock,
+    /// Application-level ping timer, running while connected
+    keepalive: Option<time::Interval>,
+    /// ID of the next frame we send
+    next_id: u64,
+}
+
+impl Default for KucoinClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KucoinClient {
+    /// Create a new KuCoin client
+    pub fn new() -> Self {
+        Self {
+            exchange_type: ExchangeType::Kucoin,
+            bullet_url: KUCOIN_BULLET_PUBLIC.to_string(),
+            ws_url: KUCOIN_WS.to_string(),
+            http: reqwest::Client::new(),
+            ws: None,
+            subscriptions: Vec::new(),
+            redis_publisher: None,
+            connected: false,
+            subscribe_batch_size: DEFAULT_SUBSCRIBE_BATCH_SIZE,
+            rate_limiter: RateLimiter::new(DEFAULT_MESSAGES_PER_SECOND, 1),
+            watchdog: StaleWatchdog::default(),
+            parse_errors: ParseErrorMonitor::default(),
+            derive_book_ticker: false,
+            clock: clock::system(),
+            keepalive: None,
+            next_id: 1,
+        }
+    }
+
+    /// Set the Redis publisher for forwarding events
+    pub fn with_redis_publisher(mut self, publisher: RedisPublisher) -> Self {
+        self.redis_publisher = Some(publisher);
+        self
+    }
+
+    /// Set how many topic targets are sent per subscribe frame
+    pub fn with_subscribe_batch_size(mut self, batch_size: usize) -> Self {
+        self.subscribe_batch_size = batch_size.clamp(1, DEFAULT_SUBSCRIBE_BATCH_SIZE);
+        self
+    }
+
+    /// Set how many control frames may be sent per second
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limiter = RateLimiter::new(per_second, 1);
+        self
+    }
+
+    /// Treat the connection as dead after this long without a frame (zero disables)
+    pub fn with_stale_timeout(mut self, timeout: Duration) -> Self {
+        self.watchdog = StaleWatchdog::new(timeout);
+        self
+    }
+
+    /// Alarm on, and optionally reconnect after, a storm of unparseable frames
+    pub fn with_parse_error_monitor(mut self, monitor: ParseErrorMonitor) -> Self {
+        self.parse_errors = monitor;
+        self
+    }
+
+    /// Turn partial book snapshots into best bid/ask tickers before anything sees them
+    pub fn with_derived_book_ticker(mut self, enabled: bool) -> Self {
+        self.derive_book_ticker = enabled;
+        self
+    }
+
+    /// Stamp events with this clock instead of the host's, e.g. a mock in tests
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Convert a trading pair to a KuCoin symbol (e.g. BTCUSDT -> BTC-USDT).
+    /// Symbols with no known quote currency are passed through unchanged.
+    pub fn to_kucoin(symbol: &str) -> String {
+        symbol::to_exchange_symbol(ExchangeType::Kucoin, symbol)
+    }
+
+    /// Convert a KuCoin symbol back to a standard symbol
+    pub fn standard_symbol(symbol: &str) -> String {
+        symbol::from_exchange_symbol(ExchangeType::Kucoin, symbol)
+    }
+
+    /// Get the KuCoin topic and target for a subscription, or `None` if KuCoin has no matching feed
+    fn topic(sub: &Subscription) -> Option<(&'static str, String)> {
+        let symbol = Self::to_kucoin(&sub.symbol);
+        match sub.data_type {
+            DataType::AggTrade => Some(("/market/match", symbol)),
+            DataType::BookTicker => Some(("/market/ticker", symbol)),
+            DataType::Depth => match sub.depth_levels {
+                Some(5) => Some(("/spotMarket/level2Depth5", symbol)),
+                _ => Some(("/spotMarket/level2Depth50", symbol)),
+            },
+            DataType::Kline => {
+                let interval = sub.interval.unwrap_or(KlineInterval::OneMinute);
+                Some(("/market/candles", format!("{}_{}", symbol, interval.as_kucoin_str())))
+            }
+            // Spot only, so there are no continuous klines, funding, liquidations or open interest
+            DataType::ContinuousKline
+            | DataType::FundingRate
+            | DataType::Liquidation
+            | DataType::OpenInterest
+            | DataType::Nbbo
+            | DataType::Opportunity => None,
+            // 24h statistics come from the snapshot topic, which isn't parsed yet
+            DataType::Ticker24h => None,
+        }
+    }
+
+    /// Build `type` frames for each topic, one per batch of targets. IDs are added when sent.
+    fn build_msgs(&self, msg_type: &str, subscriptions: &[Subscription]) -> Vec<Value> {
+        let mut topics: Vec<(&str, Vec<String>)> = Vec::new();
+        for sub in subscriptions {
+            let Some((topic, target)) = Self::topic(sub) else {
+                continue;
+            };
+
+            match topics.iter_mut().find(|(name, _)| *name == topic) {
+                Some((_, targets)) if targets.contains(&target) => {}
+                Some((_, targets)) => targets.push(target),
+                None => topics.push((topic, vec![target])),
+            }
+        }
+
+        topics.iter()
+            .flat_map(|(topic, targets)| {
+                targets.chunks(self.subscribe_batch_size).map(move |batch| {
+                    json!({
+                        "type": msg_type,
+                        "topic": format!("{}:{}", topic, batch.join(",")),
+                        "privateChannel": false,
+                        "response": true,
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Build the subscribe frames for a set of subscriptions
+    fn build_subscription_msgs(&self, subscriptions: &[Subscription]) -> Vec<Value> {
+        self.build_msgs("subscribe", subscriptions)
+    }
+
+    /// Take the next frame ID
+    fn take_id(&mut self) -> String {
+        let id = self.next_id;
+        self.next_id += 1;
+        id.to_string()
+    }
+
+    /// Send frames with a pause between batches
+    async fn send_msgs(&mut self, msgs: Vec<Value>) -> Result<()> {
+        for mut msg in msgs {
+            self.rate_limiter.acquire().await;
+
+            msg["id"] = json!(self.take_id());
+            if let Some(ref mut ws) = self.ws {
+                ws.send(Message::Text(msg.to_string())).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Extract the endpoint and token from a bullet-public response
+    fn parse_bullet(response: &Value) -> Result<KucoinEndpoint> {
+        let code = response["code"].as_str().unwrap_or_default();
+        if code != "200000" {
+            return Err(GatewayError::Connect(format!("KuCoin bullet request failed with code {}: {}", code, response["msg"])));
+        }
+
+        let data = &response["data"];
+        let token = data["token"].as_str().ok_or_else(|| GatewayError::Parse("Missing token".to_string()))?;
+        let server = data["instanceServers"].get(0).ok_or_else(|| GatewayError::Parse("Missing instanceServers".to_string()))?;
+        let endpoint = server["endpoint"].as_str().ok_or_else(|| GatewayError::Parse("Missing endpoint".to_string()))?;
+        let ping_interval = server["pingInterval"].as_u64()
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_PING_INTERVAL);
+
+        Ok(KucoinEndpoint {
+            endpoint: endpoint.to_string(),
+            token: token.to_string(),
+            ping_interval,
+        })
+    }
+
+    /// Request a WebSocket endpoint and token
+    async fn fetch_endpoint(&self) -> Result<KucoinEndpoint> {
+        let response: Value = self.http.post(&self.bullet_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Self::parse_bullet(&response)
+    }
+
+    /// Parse a decimal string field
+    fn parse_f64(value: &Value, name: &str) -> Result<f64> {
+        Ok(value.as_str().ok_or_else(|| GatewayError::Parse(format!("Missing {}", name)))?
+            .parse::<f64>()?)
+    }
+
+    /// Parse a nanosecond timestamp, sent as a string or a number, into milliseconds
+    fn parse_nanos(value: &Value) -> Option<i64> {
+        let nanos = match value {
+            Value::String(s) => s.parse::<i64>().ok()?,
+            value => value.as_i64()?,
+        };
+        Some(nanos / 1_000_000)
+    }
+
+    /// Parse `[price, size]` levels
+    fn parse_levels(levels: Option<&Value>) -> Vec<(f64, f64)> {
+        levels
+            .and_then(|l| l.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|level| {
+                        let price = level.get(0)?.as_str()?.parse::<f64>().ok()?;
+                        let qty = level.get(1)?.as_str()?.parse::<f64>().ok()?;
+                        Some((price, qty))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Parse match event from KuCoin WebSocket message
+    fn parse_match(&self, data: &Value, symbol: String) -> Result<MarketEvent> {
+        let trade_id = data["tradeId"].as_str()
+            .ok_or_else(|| GatewayError::Parse("Missing tradeId".to_string()))?
+            .parse::<u64>()?;
+        // KuCoin reports the taker's side
+        let side = data["side"].as_str().ok_or_else(|| GatewayError::Parse("Missing side".to_string()))?;
+
+        Ok(MarketEvent::AggTrade(AggTrade {
+            exchange: self.exchange_type,
+            symbol,
+            price: Self::parse_f64(&data["price"], "price")?,
+            quantity: Self::parse_f64(&data["size"], "size")?,
+            timestamp: Self::parse_nanos(&data["time"]).unwrap_or_else(now_ms),
+            is_buyer_maker: side == "sell",
+            trade_id,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse ticker event from KuCoin WebSocket message
+    fn parse_ticker(&self, data: &Value, symbol: String) -> Result<MarketEvent> {
+        Ok(MarketEvent::BookTicker(BookTicker {
+            exchange: self.exchange_type,
+            symbol,
+            bid_price: Self::parse_f64(&data["bestBid"], "bestBid")?,
+            bid_qty: Self::parse_f64(&data["bestBidSize"], "bestBidSize")?,
+            ask_price: Self::parse_f64(&data["bestAsk"], "bestAsk")?,
+            ask_qty: Self::parse_f64(&data["bestAskSize"], "bestAskSize")?,
+            timestamp: data["time"].as_i64().unwrap_or_else(now_ms),
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse a level2Depth5/50 push, which is always a full top-of-book snapshot
+    fn parse_depth(&self, data: &Value, symbol: String) -> Result<MarketEvent> {
+        Ok(MarketEvent::DepthUpdate(DepthUpdate {
+            exchange: self.exchange_type,
+            symbol,
+            bids: Self::parse_levels(data.get("bids")),
+            asks: Self::parse_levels(data.get("asks")),
+            timestamp: data["timestamp"].as_i64().unwrap_or_else(now_ms),
+            is_snapshot: true,
+            first_update_id: None,
+            final_update_id: None,
+            prev_final_update_id: None,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse candle event from KuCoin WebSocket message
+    fn parse_candle(&self, data: &Value, symbol: String, candle_type: &str) -> Result<MarketEvent> {
+        let candle = data["candles"].as_array().ok_or_else(|| GatewayError::Parse("Missing candles".to_string()))?;
+        let interval = KlineInterval::from_kucoin_str(candle_type)
+            .ok_or_else(|| GatewayError::Parse(format!("Unknown candle type: {}", candle_type)))?;
+
+        // [start (s), open, close, high, low, volume, turnover]
+        let open_time = candle.first()
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| GatewayError::Parse("Missing candle start".to_string()))?
+            .parse::<i64>()? * 1000;
+
+        Ok(MarketEvent::Kline(Kline {
+            exchange: self.exchange_type,
+            symbol,
+            interval: interval.as_str().to_string(),
+            open_time,
+            close_time: Self::close_time(open_time, interval),
+            open: Self::parse_f64(&candle[1], "open")?,
+            high: Self::parse_f64(&candle[3], "high")?,
+            low: Self::parse_f64(&candle[4], "low")?,
+            close: Self::parse_f64(&candle[2], "close")?,
+            volume: Self::parse_f64(&candle[5], "volume")?,
+            // KuCoin only pushes updates to the open candle
+            is_closed: false,
+            contract_type: None,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Last millisecond of a candle opening at `open_time`
+    fn close_time(open_time: i64, interval: KlineInterval) -> i64 {
+        const MINUTE: i64 = 60_000;
+        let length = match interval {
+            KlineInterval::OneMinute => MINUTE,
+            KlineInterval::ThreeMinutes => 3 * MINUTE,
+            KlineInterval::FiveMinutes => 5 * MINUTE,
+            KlineInterval::FifteenMinutes => 15 * MINUTE,
+            KlineInterval::ThirtyMinutes => 30 * MINUTE,
+            KlineInterval::OneHour => 60 * MINUTE,
+            KlineInterval::TwoHours => 120 * MINUTE,
+            KlineInterval::FourHours => 240 * MINUTE,
+            KlineInterval::SixHours => 360 * MINUTE,
+            KlineInterval::EightHours => 480 * MINUTE,
+            KlineInterval::TwelveHours => 720 * MINUTE,
+            KlineInterval::OneDay => 1_440 * MINUTE,
+            KlineInterval::OneWeek => 10_080 * MINUTE,
+            KlineInterval::OneMonth => {
+                // Months vary in length, so step to the next calendar month boundary
+                return Utc.timestamp_millis_opt(open_time)
+                    .single()
+                    .and_then(|open| open.checked_add_months(Months::new(1)))
+                    .map_or(open_time + 30 * 1_440 * MINUTE, |close| close.timestamp_millis())
+                    - 1;
+            }
+        };
+        open_time + length - 1
+    }
+
+    /// Parse incoming message into a MarketEvent, or `None` for non-data frames
+    fn parse_message(&self, msg: &str) -> Result<Option<MarketEvent>> {
+        let data: Value = serde_json::from_str(msg)?;
+
+        let msg_type = data.get("type")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| GatewayError::Parse("Missing type".to_string()))?;
+
+        match msg_type {
+            "welcome" | "ack" | "pong" => return Ok(None),
+            "error" => {
+                warn!("KuCoin error: {} ({})", data["data"], data["code"]);
+                return Err(GatewayError::Subscription("Error response".to_string()));
+            }
+            "message" => {}
+            _ => return Err(GatewayError::Parse(format!("Unknown message type: {}", msg_type))),
+        }
+
+        let topic = data["topic"].as_str().ok_or_else(|| GatewayError::Parse("Missing topic".to_string()))?;
+        let (channel, target) = topic.split_once(':').ok_or_else(|| GatewayError::Parse(format!("Invalid topic: {}", topic)))?;
+        let payload = &data["data"];
+
+        let event = match channel {
+            "/market/match" => self.parse_match(payload, Self::standard_symbol(target))?,
+            "/market/ticker" => self.parse_ticker(payload, Self::standard_symbol(target))?,
+            "/spotMarket/level2Depth5" | "/spotMarket/level2Depth50" => self.parse_depth(payload, Self::standard_symbol(target))?,
+            "/market/candles" => {
+                let (symbol, candle_type) = target.rsplit_once('_')
+                    .ok_or_else(|| GatewayError::Parse(format!("Invalid candles topic: {}", topic)))?;
+                self.parse_candle(payload, Self::standard_symbol(symbol), candle_type)?
+            }
+            _ => return Err(GatewayError::Parse(format!("Unknown topic: {}", topic))),
+        };
+
+        Ok(Some(event))
+    }
+
+    /// Parse a text frame and forward the resulting event
+    async fn handle_text(&mut self, text: &str) -> Result<Option<MarketEvent>> {
+        // Raw frames are a debugging aid, only queued so they never hold up parsing
+        if let Some(ref publisher) = self.redis_publisher {
+            publisher.publish_raw(self.exchange_type, text);
+        }
+
+        let parsed = self.parse_message(text);
+        if parsed.is_ok() {
+            self.parse_errors.record_success();
+        }
+
+        match parsed {
+            Ok(Some(event)) => {
+                let event = if self.derive_book_ticker { event.derive_book_ticker() } else { event };
+                // Forward to Redis if configured
+                if let Some(ref mut publisher) = self.redis_publisher {
+                    if let Err(e) = publisher.publish_event(&event).await {
+                        error!("Failed to publish event to Redis: {}", e);
+                    }
+                }
+                Ok(Some(event))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                debug!("Failed to parse KuCoin message: {}", e);
+                metrics::global().record_parse_error(self.exchange_type);
+                if self.parse_errors.record_failure(self.exchange_type) {
+                    self.connected = false;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for KucoinClient {
+    fn exchange_type(&self) -> ExchangeType {
+        self.exchange_type
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        info!("Requesting KuCoin WebSocket token from {}", self.bullet_url);
+        let endpoint = self.fetch_endpoint().await?;
+
+        info!("Connecting to KuCoin WebSocket at {}", endpoint.endpoint);
+        let (mut ws_stream, _) = connect_async(endpoint.connect_url()?).await
+            .map_err(|e| GatewayError::Connect(format!("KuCoin connect failed: {}", e)))?;
+
+        // KuCoin sends `welcome` once the connection is ready for subscriptions
+        match time::timeout(WELCOME_TIMEOUT, ws_stream.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) if matches!(self.parse_message(&text), Ok(None)) => {}
+            Ok(other) => return Err(GatewayError::Connect(format!("Expected KuCoin welcome, got {:?}", other))),
+            Err(_) => return Err(GatewayError::Connect("Timed out waiting for KuCoin welcome".to_string())),
+        }
+
+        self.ws_url = endpoint.endpoint;
+        self.ws = Some(ws_stream);
+        self.connected = true;
+        self.watchdog.touch();
+        self.keepalive = Some(time::interval_at(
+            time::Instant::now() + endpoint.ping_interval,
+            endpoint.ping_interval,
+        ));
+
+        info!("Connected to KuCoin WebSocket");
+
+        Ok(())
+    }
+
+    fn active_subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.clone()
+    }
+
+    async fn resubscribe(&mut self) -> Result<()> {
+        // Restore topics that were active before a reconnect
+        if !self.subscriptions.is_empty() {
+            info!("Restoring {} KuCoin subscriptions", self.subscriptions.len());
+            let msgs = self.build_subscription_msgs(&self.subscriptions);
+            self.send_msgs(msgs).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(mut ws) = self.ws.take() {
+            ws.close(None).await?;
+        }
+        self.connected = false;
+        self.keepalive = None;
+        info!("Disconnected from KuCoin");
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        info!("Subscribing to {} KuCoin data streams", subscriptions.len());
+        check_depth_levels(self.exchange_type, &subscriptions, &SUPPORTED_DEPTH_LEVELS)?;
+
+        if !self.connected {
+            self.connect().await?;
+            self.resubscribe().await?;
+        }
+
+        // Topics restored by `resubscribe` are already active
+        let mut added = Vec::new();
+        for sub in subscriptions {
+            if !self.subscriptions.contains(&sub) && !added.contains(&sub) {
+                added.push(sub);
+            }
+        }
+        if added.is_empty() {
+            debug!("All requested KuCoin topics are already subscribed");
+            return Ok(());
+        }
+
+        let msgs = self.build_subscription_msgs(&added);
+        self.send_msgs(msgs).await?;
+        self.subscriptions.extend(added);
+
+        info!("KuCoin subscription request sent");
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        let removed: Vec<Subscription> = self.subscriptions.iter()
+            .filter(|sub| subscriptions.contains(sub))
+            .cloned()
+            .collect();
+        if removed.is_empty() {
+            debug!("No KuCoin topics to unsubscribe");
+            return Ok(());
+        }
+        self.subscriptions.retain(|sub| !removed.contains(sub));
+
+        info!("Unsubscribing from {} KuCoin data streams", removed.len());
+        let msgs = self.build_msgs("unsubscribe", &removed);
+        self.send_msgs(msgs).await
+    }
+
+    async fn recv_event(&mut self) -> Result<Option<MarketEvent>> {
+        if !self.connected || self.ws.is_none() {
+            return Ok(None);
+        }
+
+        let ping_id = self.next_id.to_string();
+        let ws = self.ws.as_mut().unwrap();
+
+        let keepalive = self.keepalive.as_mut();
+        let keepalive_tick = async move {
+            match keepalive {
+                Some(keepalive) => {
+                    keepalive.tick().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        let msg = tokio::select! {
+            msg = ws.next() => msg,
+            _ = keepalive_tick => {
+                debug!("Sending KuCoin keepalive ping");
+                let ping = json!({ "type": "ping", "id": ping_id });
+                ws.send(Message::Text(ping.to_string())).await?;
+                self.next_id += 1;
+                return Ok(None);
+            }
+            _ = self.watchdog.expired() => {
+                warn!("No KuCoin frame for {:?}, dropping the stale connection", self.watchdog.silence());
+                self.connected = false;
+                return Ok(None);
+            }
+        };
+        // Any frame, pings included, shows the connection is alive
+        self.watchdog.touch();
+
+        match msg {
+            Some(Ok(Message::Text(text))) => self.handle_text(&text).await,
+            Some(Ok(Message::Ping(payload))) => {
+                ws.send(Message::Pong(payload)).await?;
+                self.recv_event().await
+            }
+            Some(Ok(Message::Pong(_))) => {
+                self.recv_event().await
+            }
+            Some(Ok(Message::Close(_))) => {
+                self.connected = false;
+                Ok(None)
+            }
+            Some(Err(e)) => {
+                error!("KuCoin WebSocket error: {}", e);
+                self.connected = false;
+                Err(e.into())
+            }
+            None => {
+                self.connected = false;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected && !self.watchdog.is_stale()
+    }
+
+    fn ws_endpoint(&self) -> &str {
+        &self.ws_url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_mapping() {
+        let topic = |sub: Subscription| KucoinClient::topic(&sub);
+
+        assert_eq!(topic(Subscription::agg_trade("BTCUSDT")), Some(("/market/match", "BTC-USDT".to_string())));
+        assert_eq!(topic(Subscription::book_ticker("ETHBTC")), Some(("/market/ticker", "ETH-BTC".to_string())));
+        assert_eq!(topic(Subscription::depth("SOLUSDC")), Some(("/spotMarket/level2Depth50", "SOL-USDC".to_string())));
+        assert_eq!(
+            topic(Subscription::kline("BTCUSDT", KlineInterval::OneHour)),
+            Some(("/market/candles", "BTC-USDT_1hour".to_string()))
+        );
+        assert_eq!(topic(Subscription::partial_depth("SOLUSDC", 5)), Some(("/spotMarket/level2Depth5", "SOL-USDC".to_string())));
+        assert_eq!(topic(Subscription::funding_rate("BTCUSDT")), None);
+        assert_eq!(KucoinClient::standard_symbol("BTC-USDT"), "BTCUSDT");
+    }
+
+    #[test]
+    fn test_subscription_msgs() {
+        let client = KucoinClient::new().with_subscribe_batch_size(2);
+        let subscriptions = vec![
+            Subscription::agg_trade("BTCUSDT"),
+            Subscription::agg_trade("ETHUSDT"),
+            Subscription::agg_trade("SOLUSDT"),
+            Subscription::kline("BTCUSDT", KlineInterval::FiveMinutes),
+            Subscription::liquidation("BTCUSDT"),
+        ];
+
+        assert_eq!(
+            client.build_subscription_msgs(&subscriptions),
+            vec![
+                json!({ "type": "subscribe", "topic": "/market/match:BTC-USDT,ETH-USDT", "privateChannel": false, "response": true }),
+                json!({ "type": "subscribe", "topic": "/market/match:SOL-USDT", "privateChannel": false, "response": true }),
+                json!({ "type": "subscribe", "topic": "/market/candles:BTC-USDT_5min", "privateChannel": false, "response": true }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_match() {
+        let client = KucoinClient::new();
+        let json = r#"{"type":"message","topic":"/market/match:BTC-USDT","subject":"trade.l3match","data":{"makerOrderId":"6287c3015c27f000017d0c2f","price":"31716.6","sequence":"1621634226946","side":"sell","size":"0.0001","symbol":"BTC-USDT","takerOrderId":"6287c30292f3a30001a1c8ee","time":"1653064450253896600","tradeId":"1621634226946","type":"match"}}"#;
+
+        if let Ok(Some(MarketEvent::AggTrade(trade))) = client.parse_message(json) {
+            assert_eq!(trade.exchange, ExchangeType::Kucoin);
+            assert_eq!(trade.symbol, "BTCUSDT");
+            assert_eq!(trade.price, 31716.6);
+            assert_eq!(trade.quantity, 0.0001);
+            assert_eq!(trade.timestamp, 1653064450253);
+            assert_eq!(trade.trade_id, 1621634226946);
+            // The taker sold into a resting bid
+            assert!(trade.is_buyer_maker);
+        } else {
+            panic!("Expected AggTrade event");
+        }
+    }
+
+    #[test]
+    fn test_parse_candle() {
+        let client = KucoinClient::new();
+        let json = r#"{"type":"message","topic":"/market/candles:BTC-USDT_1hour","subject":"trade.candles.update","data":{"symbol":"BTC-USDT","candles":["1589968800","9786.9","9740.8","9806.1","9732","27.45649579","268280.09830877"],"time":1589970010253893337}}"#;
+
+        if let Ok(Some(MarketEvent::Kline(kline))) = client.parse_message(json) {
+            assert_eq!(kline.symbol, "BTCUSDT");
+            assert_eq!(kline.interval, "1h");
+            assert_eq!(kline.open_time, 1589968800000);
+            assert_eq!(kline.close_time, 1589972399999);
+            assert_eq!(kline.open, 9786.9);
+            assert_eq!(kline.close, 9740.8);
+            assert_eq!(kline.high, 9806.1);
+            assert_eq!(kline.low, 9732.0);
+        } else {
+            panic!("Expected Kline event");
+        }
+    }
+
+    #[test]
+    fn test_control_frames_are_not_events() {
+        let client = KucoinClient::new();
+
+        assert!(matches!(client.parse_message(r#"{"id":"hQvf8jkno","type":"welcome"}"#), Ok(None)));
+        assert!(matches!(client.parse_message(r#"{"id":"1","type":"ack"}"#), Ok(None)));
+        assert!(matches!(client.parse_message(r#"{"id":"2","type":"pong"}"#), Ok(None)));
+    }
+
+    #[test]
+    fn test_parse_bullet() {
+        let response = json!({
+            "code": "200000",
+            "data": {
+                "token": "2neAiuYvAU61ZDXANAGAsiL4",
+                "instanceServers": [{
+                    "endpoint": "wss://ws-api-spot.kucoin.com/",
+                    "encrypt": true,
+                    "protocol": "websocket",
+                    "pingInterval": 18000,
+                    "pingTimeout": 10000
+                }]
+            }
+        });
+
+        let endpoint = KucoinClient::parse_bullet(&response).unwrap();
+        assert_eq!(endpoint.ping_interval, Duration::from_secs(18));
+        let url = endpoint.connect_url().unwrap();
+        assert!(url.as_str().starts_with("wss://ws-api-spot.kucoin.com/?token=2neAiuYvAU61ZDXANAGAsiL4&connectId="));
+
+        assert!(KucoinClient::parse_bullet(&json!({ "code": "400100", "msg": "bad" })).is_err());
+    }
+}
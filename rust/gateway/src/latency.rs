@@ -0,0 +1,259 @@
+//! Event latency tracking
+//!
+//! This module measures how far each event lags the exchange, as
+//! `received_at - timestamp`, and keeps rolling percentiles per exchange
+//! and data type over a window of recent samples. A known clock offset
+//! can be added to `received_at` to correct for local clock drift.
+
+use crate::exchange::{DataType, ExchangeType, MarketEvent};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Samples kept per (exchange, data type)
+pub const DEFAULT_LATENCY_WINDOW: usize = 1024;
+
+/// How often the gateway logs a latency summary
+pub const LATENCY_REPORT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Latency percentiles over the current window, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub p50: i64,
+    pub p95: i64,
+    pub p99: i64,
+    /// Negative latencies seen (clock skew), recorded as zero
+    pub clamped: u64,
+}
+
+/// Recent latencies of one (exchange, data type)
+#[derive(Debug, Default)]
+struct Window {
+    samples: VecDeque<i64>,
+    clamped: u64,
+}
+
+/// Rolling latency percentiles keyed by (exchange, data type)
+#[derive(Debug)]
+pub struct LatencyTracker {
+    windows: HashMap<(ExchangeType, DataType), Window>,
+    capacity: usize,
+    /// Exchange clock offsets from the local clock, added to receive times
+    clock_offsets: HashMap<ExchangeType, i64>,
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyTracker {
+    /// Create a tracker keeping the default window of samples
+    pub fn new() -> Self {
+        Self {
+            windows: HashMap::new(),
+            capacity: DEFAULT_LATENCY_WINDOW,
+            clock_offsets: HashMap::new(),
+        }
+    }
+
+    /// Keep this many recent samples per (exchange, data type)
+    pub fn with_window(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Correct an exchange's latencies for a clock `offset_ms` ahead of the local clock
+    pub fn set_clock_offset(&mut self, exchange: ExchangeType, offset_ms: i64) {
+        self.clock_offsets.insert(exchange, offset_ms);
+    }
+
+    /// Record an event's latency; events without a receive time are skipped
+    pub fn record(&mut self, event: &MarketEvent) {
+        if event.received_at() == 0 {
+            return;
+        }
+        let offset = self.clock_offsets.get(&event.exchange()).copied().unwrap_or(0);
+        self.record_latency(event.exchange(), event.event_type(), event.received_at() + offset - event.timestamp());
+    }
+
+    /// Record a latency sample, clamping negative values to zero
+    pub fn record_latency(&mut self, exchange: ExchangeType, data_type: DataType, latency_ms: i64) {
+        let window = self.windows.entry((exchange, data_type)).or_default();
+        if latency_ms < 0 {
+            window.clamped += 1;
+        }
+        if window.samples.len() == self.capacity {
+            window.samples.pop_front();
+        }
+        window.samples.push_back(latency_ms.max(0));
+    }
+
+    /// Percentiles for every (exchange, data type) seen so far
+    pub fn latency_stats(&self) -> HashMap<(ExchangeType, DataType), LatencyStats> {
+        self.windows
+            .iter()
+            .map(|(key, window)| {
+                let mut sorted: Vec<i64> = window.samples.iter().copied().collect();
+                sorted.sort_unstable();
+                let stats = LatencyStats {
+                    samples: sorted.len(),
+                    p50: percentile(&sorted, 50.0),
+                    p95: percentile(&sorted, 95.0),
+                    p99: percentile(&sorted, 99.0),
+                    clamped: window.clamped,
+                };
+                (*key, stats)
+            })
+            .collect()
+    }
+
+    /// Render the percentiles as a table sorted by exchange and type
+    pub fn summary(&self) -> String {
+        let mut rows: Vec<_> = self.latency_stats().into_iter().collect();
+        rows.sort_by(|(a, _), (b, _)| (a.0.to_string(), a.1.as_str()).cmp(&(b.0.to_string(), b.1.as_str())));
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{:<10} {:<16} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "EXCHANGE", "TYPE", "SAMPLES", "P50ms", "P95ms", "P99ms", "SKEWED"
+        );
+        for ((exchange, data_type), stats) in rows {
+            let _ = writeln!(
+                out,
+                "{:<10} {:<16} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                exchange.to_string(), data_type.as_str(), stats.samples, stats.p50, stats.p95, stats.p99, stats.clamped
+            );
+        }
+        out
+    }
+}
+
+/// How far an event lags the exchange in ms, with `clock_offset_ms` added to
+/// its receive time and negative values clamped to zero; `None` for events
+/// the gateway didn't stamp
+pub fn event_latency_ms(event: &MarketEvent, clock_offset_ms: i64) -> Option<i64> {
+    (event.received_at() != 0).then(|| (event.received_at() + clock_offset_ms - event.timestamp()).max(0))
+}
+
+/// Nearest-rank percentile of sorted samples
+fn percentile(sorted: &[i64], pct: f64) -> i64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (pct / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::AggTrade;
+
+    #[test]
+    fn test_percentiles_of_known_latencies() {
+        let mut tracker = LatencyTracker::new();
+        for latency in 1..=100 {
+            tracker.record_latency(ExchangeType::Binance, DataType::AggTrade, latency);
+        }
+        tracker.record_latency(ExchangeType::Okx, DataType::AggTrade, 7);
+
+        let stats = tracker.latency_stats();
+        assert_eq!(
+            stats[&(ExchangeType::Binance, DataType::AggTrade)],
+            LatencyStats { samples: 100, p50: 50, p95: 95, p99: 99, clamped: 0 }
+        );
+        assert_eq!(stats[&(ExchangeType::Okx, DataType::AggTrade)].p99, 7);
+    }
+
+    #[test]
+    fn test_negative_latency_is_clamped_and_counted() {
+        let mut tracker = LatencyTracker::new();
+        tracker.record_latency(ExchangeType::Bybit, DataType::Kline, -15);
+        tracker.record_latency(ExchangeType::Bybit, DataType::Kline, 10);
+
+        let stats = tracker.latency_stats()[&(ExchangeType::Bybit, DataType::Kline)];
+        assert_eq!(stats.clamped, 1);
+        assert_eq!(stats.p50, 0);
+        assert_eq!(stats.p99, 10);
+    }
+
+    #[test]
+    fn test_window_keeps_recent_samples() {
+        let mut tracker = LatencyTracker::new().with_window(10);
+        for _ in 0..10 {
+            tracker.record_latency(ExchangeType::Binance, DataType::Depth, 1_000);
+        }
+        for _ in 0..10 {
+            tracker.record_latency(ExchangeType::Binance, DataType::Depth, 5);
+        }
+
+        let stats = tracker.latency_stats()[&(ExchangeType::Binance, DataType::Depth)];
+        assert_eq!(stats.samples, 10);
+        assert_eq!(stats.p99, 5);
+    }
+
+    #[test]
+    fn test_record_uses_event_times() {
+        let mut tracker = LatencyTracker::new();
+        let trade = |timestamp, received_at| MarketEvent::AggTrade(AggTrade {
+            exchange: ExchangeType::Coinbase,
+            symbol: "BTCUSD".to_string(),
+            price: 50000.0,
+            quantity: 0.1,
+            timestamp,
+            is_buyer_maker: false,
+            trade_id: 1,
+            received_at,
+        });
+        tracker.record(&trade(1_000, 1_042));
+        // Not stamped by the gateway, e.g. replayed from an old recording
+        tracker.record(&trade(1_000, 0));
+
+        let stats = tracker.latency_stats()[&(ExchangeType::Coinbase, DataType::AggTrade)];
+        assert_eq!(stats.samples, 1);
+        assert_eq!(stats.p50, 42);
+    }
+
+    #[test]
+    fn test_clock_offset_corrects_latency() {
+        let mut tracker = LatencyTracker::new();
+        // Local clock runs 30ms behind Binance
+        tracker.set_clock_offset(ExchangeType::Binance, 30);
+        tracker.record(&MarketEvent::AggTrade(AggTrade {
+            exchange: ExchangeType::Binance,
+            symbol: "BTCUSDT".to_string(),
+            price: 50000.0,
+            quantity: 0.1,
+            timestamp: 1_000,
+            is_buyer_maker: false,
+            trade_id: 1,
+            received_at: 990,
+        }));
+
+        let stats = tracker.latency_stats()[&(ExchangeType::Binance, DataType::AggTrade)];
+        assert_eq!(stats.clamped, 0);
+        assert_eq!(stats.p50, 20);
+    }
+
+    #[test]
+    fn test_event_latency_is_clamped() {
+        let trade = |timestamp, received_at| MarketEvent::AggTrade(AggTrade {
+            exchange: ExchangeType::Okx,
+            symbol: "BTCUSDT".to_string(),
+            price: 50000.0,
+            quantity: 0.1,
+            timestamp,
+            is_buyer_maker: false,
+            trade_id: 1,
+            received_at,
+        });
+
+        assert_eq!(event_latency_ms(&trade(1_000, 1_042), 0), Some(42));
+        assert_eq!(event_latency_ms(&trade(1_000, 1_042), -50), Some(0));
+        assert_eq!(event_latency_ms(&trade(1_000, 0), 0), None);
+    }
+}
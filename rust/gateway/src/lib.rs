@@ -2,16 +2,94 @@
 //!
 //! High-performance market data gateway for cryptocurrency exchanges.
 
+pub mod aggregate;
+pub mod clock;
+pub mod coalesce;
+pub mod error;
 pub mod exchange;
+pub mod filter;
+pub mod health;
+pub mod instruments;
+pub mod kafka_publisher;
+pub mod latency;
+pub mod logging;
+pub mod manager;
+pub mod metrics;
+pub mod nbbo;
+pub mod open_interest;
+pub mod opportunity;
+pub mod parquet_recorder;
+pub mod proxy;
+pub mod queue;
+pub mod recorder;
+pub mod reconnect;
+pub mod reorder;
+pub mod replay;
 pub mod redis_publisher;
+pub mod runner;
+pub mod settings;
+pub mod sink;
+pub mod stats;
+pub mod symbol;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod time_sync;
+pub mod user_data;
+pub mod ws_server;
 
+#[cfg(feature = "binance")]
 pub mod binance;
+#[cfg(feature = "bitget")]
+pub mod bitget;
+#[cfg(feature = "bybit")]
+pub mod bybit;
+#[cfg(feature = "coinbase")]
+pub mod coinbase;
+#[cfg(feature = "deribit")]
+pub mod deribit;
+#[cfg(feature = "gateio")]
+pub mod gateio;
+#[cfg(feature = "kucoin")]
+pub mod kucoin;
+#[cfg(feature = "okx")]
 pub mod okx;
+pub mod orderbook;
 
 // Re-export commonly used types
 pub use exchange::{
     Exchange, ExchangeType, MarketEvent, DataType, KlineInterval,
-    AggTrade, Kline, DepthUpdate, BookTicker, Subscription,
+    AggTrade, Kline, DepthUpdate, BookTicker, FundingRate, Liquidation, OpenInterest, Ticker24h, Nbbo, Side,
+    Subscription, SubscriptionBuilder, DepthUpdateSpeed, MarketKind, OkxInstType, RateLimiter, StaleWatchdog, ParseErrorMonitor,
 };
 
-pub use redis_publisher::{RedisPublisher, RedisConfig};
+pub use aggregate::TradeAggregator;
+pub use clock::{Clock, SharedClock, SystemClock};
+pub use coalesce::DepthCoalescer;
+pub use error::GatewayError;
+pub use filter::{EventFilter, FilterRule};
+pub use health::{ConnectionStatus, HealthState};
+pub use instruments::InstrumentList;
+pub use kafka_publisher::{KafkaConfig, KafkaPublisher};
+pub use latency::{LatencyStats, LatencyTracker};
+pub use logging::LogFormat;
+pub use manager::GatewayManager;
+pub use metrics::{GatewayMetrics, Metrics};
+pub use nbbo::ConsolidatedBook;
+pub use open_interest::OpenInterestPoller;
+pub use opportunity::{Opportunity, SpreadDetector};
+pub use parquet_recorder::ParquetRecorder;
+pub use proxy::ProxyConfig;
+pub use queue::{BackpressurePolicy, EventQueue};
+pub use orderbook::{OkxOrderBook, OrderBook};
+pub use recorder::{FileRecorder, RotationPolicy};
+pub use reconnect::ReconnectPolicy;
+pub use reorder::ReorderBuffer;
+pub use replay::ReplaySource;
+pub use redis_publisher::{ChannelRouting, ConnectionPool, OutputMode, RedisPublisher, RedisConfig, SerializationFormat};
+pub use settings::GatewayConfig;
+pub use sink::{EventSink, FanoutSink, OutputBackend, StdoutSink};
+pub use stats::EventCounter;
+pub use symbol::Symbol;
+pub use time_sync::TimeSync;
+pub use user_data::{AccountUpdate, OrderUpdate, UserDataEvent};
+pub use ws_server::WsServer;
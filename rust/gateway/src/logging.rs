@@ -0,0 +1,130 @@
+//! Log output
+//!
+//! This module sets up the tracing subscriber, writing either human-readable
+//! lines or one JSON object per line for log aggregators.
+
+use crate::error::{GatewayError, Result};
+use serde::Deserialize;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// How log lines are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with event and span fields as keys
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(GatewayError::Config(format!("Unknown log format: {} (expected text or json)", s))),
+        }
+    }
+}
+
+/// Map a configured level onto a filter directive, falling back to info
+fn level_filter(level: &str) -> &'static str {
+    match level.to_lowercase().as_str() {
+        "trace" => "trace",
+        "debug" => "debug",
+        "info" => "info",
+        "warn" => "warn",
+        "error" => "error",
+        _ => "info",
+    }
+}
+
+/// Build a subscriber writing `format` lines at `level` to `writer`
+pub fn subscriber<W>(level: &str, format: LogFormat, writer: W) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(level_filter(level))
+        .with_writer(writer)
+        .with_target(false);
+
+    match format {
+        LogFormat::Text => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder.json().with_current_span(true).with_span_list(false).finish()),
+    }
+}
+
+/// Install the global subscriber, writing to stdout
+pub fn init(level: &str, format: LogFormat) -> Result<()> {
+    tracing::subscriber::set_global_default(subscriber(level, format, std::io::stdout))
+        .map_err(|e| GatewayError::Config(format!("Failed to install logger: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    /// Writer collecting log output in memory
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'w> MakeWriter<'w> for Captured {
+        type Writer = Captured;
+
+        fn make_writer(&'w self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_json_lines_carry_structured_fields() {
+        let captured = Captured::default();
+        let subscriber = subscriber("info", LogFormat::Json, captured.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("exchange", exchange = "binance");
+            let _entered = span.enter();
+            tracing::info!(symbol = "BTCUSDT", event_type = "agg_trade", "price=50000");
+            tracing::debug!("filtered out below info");
+        });
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 1);
+
+        let line = &lines[0];
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["fields"]["message"], "price=50000");
+        assert_eq!(line["fields"]["symbol"], "BTCUSDT");
+        assert_eq!(line["fields"]["event_type"], "agg_trade");
+        assert_eq!(line["span"]["name"], "exchange");
+        assert_eq!(line["span"]["exchange"], "binance");
+    }
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert_eq!("Text".parse::<LogFormat>().unwrap(), LogFormat::Text);
+        assert!("yaml".parse::<LogFormat>().is_err());
+    }
+}
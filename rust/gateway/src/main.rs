@@ -3,59 +3,86 @@
 //! High-performance market data gateway that connects to multiple exchanges
 //! and publishes market events to Redis for consumption by the strategy engine.
 
-mod exchange;
-mod redis_publisher;
+use flash_arb_gateway::{
+    exchange, filter, health, instruments, kafka_publisher, latency, logging, manager, metrics, open_interest,
+    parquet_recorder, recorder, redis_publisher, replay, runner, settings, sink, stats, time_sync, ws_server,
+};
 
-mod binance;
-mod okx;
+#[cfg(feature = "binance")]
+use flash_arb_gateway::{binance, user_data};
+#[cfg(feature = "bitget")]
+use flash_arb_gateway::bitget;
+#[cfg(feature = "bybit")]
+use flash_arb_gateway::bybit;
+#[cfg(feature = "coinbase")]
+use flash_arb_gateway::coinbase;
+#[cfg(feature = "deribit")]
+use flash_arb_gateway::deribit;
+#[cfg(feature = "gateio")]
+use flash_arb_gateway::gateio;
+#[cfg(feature = "kucoin")]
+use flash_arb_gateway::kucoin;
+#[cfg(feature = "okx")]
+use flash_arb_gateway::okx;
 
 use anyhow::{Context, Result};
-use clap::Parser;
-use exchange::{Exchange, ExchangeType, Subscription, DataType, KlineInterval};
+use clap::{Parser, Subcommand};
+use exchange::{EventResult, Exchange, ExchangeType, MarketEvent, Subscription};
+use filter::EventFilter;
+use health::HealthState;
+use kafka_publisher::{KafkaConfig, KafkaPublisher};
+use instruments::InstrumentList;
+use latency::LatencyTracker;
+use manager::GatewayManager;
+use open_interest::OpenInterestPoller;
+use parquet_recorder::ParquetRecorder;
+use recorder::FileRecorder;
+use replay::ReplaySource;
 use redis_publisher::RedisPublisher;
+use settings::GatewayConfig;
+use sink::{EventSink, FanoutSink, OutputBackend, StdoutSink};
+use stats::{EventCounter, EventSummary};
+use time_sync::TimeSync;
 use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
 use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use ws_server::WsServer;
 use tokio::time;
-use tracing::{error, info, warn};
-use tracing_subscriber;
-
-/// Gateway configuration
-#[derive(Debug, Clone)]
-struct GatewayConfig {
-    /// Redis connection URL
-    redis_url: String,
-    /// Symbols to track
-    symbols: Vec<String>,
-    /// Exchanges to connect
-    exchanges: Vec<ExchangeType>,
-    /// Enable testnet/demo mode
-    testnet: bool,
-}
-
-impl Default for GatewayConfig {
-    fn default() -> Self {
-        Self {
-            redis_url: "redis://127.0.0.1:6379".to_string(),
-            symbols: vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()],
-            exchanges: vec![ExchangeType::Binance],
-            testnet: false,
-        }
-    }
-}
+use tracing::{error, info, trace, warn};
 
 /// Command line arguments
+///
+/// Flags left unset fall back to the config file, then to the built-in defaults.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Redis connection URL
-    #[arg(short, long, default_value = "redis://127.0.0.1:6379")]
-    redis: String,
+    /// Load settings from this TOML file
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Redis connection URL [default: redis://127.0.0.1:6379]
+    #[arg(short, long)]
+    redis: Option<String>,
 
     /// Symbols to track (comma-separated)
     #[arg(short, long, value_delimiter = ',')]
     symbols: Vec<String>,
 
-    /// Exchanges to connect (comma-separated: binance, okx)
+    /// Symbols for one exchange, replacing --symbols there (e.g. okx=BTCUSDT,ETHUSDT; repeatable)
+    #[arg(long)]
+    exchange_symbols: Vec<String>,
+
+    /// Check subscribed symbols against each exchange's instrument list and refuse to start on unknown ones
+    #[arg(long)]
+    strict_symbols: bool,
+
+    /// Subscribe each symbol only on the exchanges that list it, warning about the rest
+    #[arg(long)]
+    skip_unlisted_symbols: bool,
+
+    /// Exchanges to connect (comma-separated: binance, okx, bybit, coinbase, kucoin, deribit, gateio, bitget)
     #[arg(short, long, value_delimiter = ',')]
     exchanges: Vec<String>,
 
@@ -63,69 +90,744 @@ struct Args {
     #[arg(short, long)]
     testnet: bool,
 
-    /// Log level
-    #[arg(short, long, default_value = "info")]
-    log: String,
+    /// Subscription batch size per exchange (comma-separated, e.g. binance=50,okx=20)
+    #[arg(long, value_delimiter = ',')]
+    subscribe_batch_size: Vec<String>,
+
+    /// Subscribe frames sent per second per exchange (comma-separated, e.g. binance=5,okx=10)
+    #[arg(long, value_delimiter = ',')]
+    subscribe_rate_limit: Vec<String>,
+
+    /// Count parsed events by exchange, type and symbol and print a summary on exit
+    #[arg(long)]
+    count: bool,
+
+    /// Log a summary of event counts and latest prices every N seconds, 0 to disable [default: 10]
+    #[arg(long)]
+    summary_interval_secs: Option<u64>,
+
+    /// Log every event at info level (otherwise they are only logged at trace)
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Also subscribe to continuous-contract klines (perpetual, current_quarter, next_quarter)
+    #[arg(long)]
+    continuous_contract: Option<String>,
+
+    /// Rebuild Binance order books from REST snapshots and publish the top N levels
+    #[arg(long)]
+    order_book_depth: Option<usize>,
+
+    /// Stream a partial order book of N levels per exchange instead of full diffs (e.g. binance=20,okx=5)
+    #[arg(long, value_delimiter = ',')]
+    depth_levels: Vec<String>,
+
+    /// Publish partial order books (--depth-levels) as best bid/ask tickers instead of depth updates
+    #[arg(long)]
+    derive_book_ticker: bool,
+
+    /// Depth push rate where the exchange offers a choice (100ms or 500ms) [default: 100ms]
+    #[arg(long)]
+    depth_update_speed: Option<String>,
+
+    /// Binance market to stream: spot, usd_futures or coin_futures [default: usd_futures]
+    #[arg(long)]
+    binance_market: Option<String>,
+
+    /// Binance WebSocket endpoint, replacing the market's default (e.g. a proxy or local mock)
+    #[arg(long)]
+    binance_ws: Option<String>,
+
+    /// OKX instrument to stream per symbol: spot or swap (the perpetual) [default: spot]
+    #[arg(long)]
+    okx_inst_type: Option<String>,
+
+    /// OKX WebSocket endpoint, replacing the public or demo one
+    #[arg(long)]
+    okx_ws: Option<String>,
+
+    /// Binance API key; also streams the account's order and balance updates (futures only)
+    #[arg(long)]
+    binance_api_key: Option<String>,
+
+    /// OKX API key; with the secret and passphrase, depth streams over the tick-by-tick channels
+    #[arg(long)]
+    okx_api_key: Option<String>,
+
+    /// OKX API secret
+    #[arg(long)]
+    okx_api_secret: Option<String>,
+
+    /// OKX API passphrase
+    #[arg(long)]
+    okx_passphrase: Option<String>,
+
+    /// Tunnel Binance and OKX WebSockets through this proxy (http:// or socks5://, with optional user:pass@)
+    /// [default: HTTPS_PROXY or ALL_PROXY]
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Poll open interest every N seconds
+    #[arg(long)]
+    open_interest_interval: Option<u64>,
+
+    /// Reconnect an exchange after N seconds without any frame, 0 to disable [default: 60]
+    #[arg(long)]
+    stale_timeout_secs: Option<u64>,
+
+    /// Log an error once more than N% of an exchange's last frames fail to parse, 0 to disable [default: 50]
+    #[arg(long)]
+    parse_error_percent: Option<u8>,
+
+    /// Number of frames the parse error rate is measured over [default: 100]
+    #[arg(long)]
+    parse_error_window: Option<usize>,
+
+    /// Also reconnect an exchange whose parse error rate trips the alarm
+    #[arg(long)]
+    reconnect_on_parse_errors: bool,
+
+    /// Retry a reconnect that yields no event within N seconds, 0 to disable [default: 30]
+    #[arg(long)]
+    reconnect_verify_timeout_secs: Option<u64>,
+
+    /// Factor each reconnect delay grows by [default: 2.0]
+    #[arg(long)]
+    reconnect_multiplier: Option<f64>,
+
+    /// Fraction of each reconnect delay that is randomized, 0 to disable [default: 0.2]
+    #[arg(long)]
+    reconnect_jitter: Option<f64>,
+
+    /// Seed the reconnect jitter so the delays are reproducible
+    #[arg(long)]
+    reconnect_jitter_seed: Option<u64>,
+
+    /// Tries each exchange connect gets before giving up [default: 5]
+    #[arg(long)]
+    connect_attempts: Option<u32>,
+
+    /// Abandon a connect try after N seconds, 0 to wait forever [default: 10]
+    #[arg(long)]
+    connect_timeout_secs: Option<u64>,
+
+    /// Check Binance and OKX clocks against the local one every N seconds, 0 to disable [default: 300]
+    #[arg(long)]
+    time_sync_interval_secs: Option<u64>,
+
+    /// Warn when an exchange clock is off by more than N milliseconds [default: 500]
+    #[arg(long)]
+    max_clock_drift_ms: Option<u64>,
+
+    /// Correct measured latencies by each exchange's clock offset
+    #[arg(long)]
+    apply_clock_offset: bool,
+
+    /// Only publish klines whose interval has closed
+    #[arg(long)]
+    closed_klines_only: bool,
+
+    /// Only publish trades of at least this quantity
+    #[arg(long)]
+    min_trade_quantity: Option<f64>,
+
+    /// Only publish trades worth at least this much in the quote asset
+    #[arg(long)]
+    min_trade_notional: Option<f64>,
+
+    /// Only publish events for these symbols (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    symbol_whitelist: Vec<String>,
+
+    /// Never publish events for these symbols (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    symbol_blacklist: Vec<String>,
+
+    /// Buffer this many events and publish them to Redis in one pipeline [default: 1]
+    #[arg(long)]
+    redis_batch_size: Option<usize>,
+
+    /// Flush buffered Redis events at least this often (milliseconds) [default: 10]
+    #[arg(long)]
+    redis_flush_interval_ms: Option<u64>,
+
+    /// Redis output mode (pubsub or stream) [default: pubsub]
+    #[arg(long)]
+    redis_output: Option<String>,
+
+    /// Approximate length cap for each Redis stream in stream mode [default: 100000]
+    #[arg(long)]
+    redis_maxlen: Option<usize>,
+
+    /// Redis payload encoding (json or msgpack) [default: json]
+    #[arg(long)]
+    redis_format: Option<String>,
+
+    /// Redis payload compression (none, zstd or zstd:<level>) [default: none]
+    #[arg(long)]
+    redis_compression: Option<String>,
+
+    /// Events to hold for replay while Redis is unreachable [default: 10000]
+    #[arg(long)]
+    redis_backlog_size: Option<usize>,
+
+    /// Publish to Redis over this many connections in parallel [default: 1]
+    #[arg(long)]
+    redis_pool_size: Option<usize>,
+
+    /// Events queued for the Redis publish task, 0 to publish inline [default: 10000]
+    #[arg(long)]
+    redis_queue_size: Option<usize>,
+
+    /// When the Redis queue is full: block, drop_oldest or drop_newest [default: drop_oldest]
+    #[arg(long)]
+    redis_backpressure: Option<String>,
+
+    /// Warn when the Redis queue fills to this percent of its size, 0 to never warn [default: 80]
+    #[arg(long)]
+    redis_queue_warn_percent: Option<u8>,
+
+    /// Merge depth updates per symbol and publish them to Redis at most every N ms, 0 to disable
+    #[arg(long)]
+    redis_depth_coalesce_ms: Option<u64>,
+
+    /// Merge runs of same-price, same-side raw trades within N ms into one trade for Redis, 0 to disable
+    #[arg(long)]
+    redis_trade_aggregation_ms: Option<u64>,
+
+    /// Trade aggregation window per symbol in ms (e.g. BTCUSDT=50,ETHUSDT=0)
+    #[arg(long, value_delimiter = ',')]
+    redis_trade_aggregation_windows: Vec<String>,
+
+    /// Hold events N ms and publish them to Redis sorted by exchange timestamp, 0 to disable
+    #[arg(long)]
+    redis_reorder_grace_ms: Option<u64>,
+
+    /// Also publish every received WebSocket text frame verbatim to {prefix}:raw:{exchange}
+    #[arg(long)]
+    publish_raw: bool,
+
+    /// Also publish the best bid and ask across exchanges to {prefix}:nbbo whenever it changes
+    #[arg(long)]
+    publish_nbbo: bool,
+
+    /// Leave an exchange's quote out of the NBBO once it's N ms older than the newest, 0 to keep it [default: 5000]
+    #[arg(long)]
+    nbbo_max_quote_age_ms: Option<u64>,
+
+    /// Add each event's latency_ms (receive time minus exchange time) to its Redis payload
+    #[arg(long)]
+    publish_latency: bool,
+
+    /// Publish an opportunity when one exchange bids N basis points over another's ask, 0 to disable
+    #[arg(long)]
+    opportunity_threshold_bps: Option<f64>,
+
+    /// Basis points a spread must fall under the threshold before its symbol alerts again [default: 2]
+    #[arg(long)]
+    opportunity_hysteresis_bps: Option<f64>,
+
+    /// Redis channels per event: by_type, by_symbol or by_type_and_symbol [default: by_type]
+    #[arg(long)]
+    redis_routing: Option<String>,
+
+    /// Namespace of the Redis channel names, e.g. inst2 for inst2:tick [default: flash_arb]
+    #[arg(long)]
+    redis_channel_prefix: Option<String>,
+
+    /// Backends to publish events to (comma-separated: redis, kafka, stdout) [default: redis]
+    #[arg(long, value_delimiter = ',')]
+    output: Vec<String>,
+
+    /// Print events to stdout instead of publishing them; no Redis or Kafka is needed
+    #[arg(long, visible_alias = "no-redis")]
+    dry_run: bool,
+
+    /// Kafka bootstrap servers [default: localhost:9092]
+    #[arg(long)]
+    kafka_brokers: Option<String>,
+
+    /// Prefix of the per-type Kafka topics [default: flash_arb]
+    #[arg(long)]
+    kafka_topic_prefix: Option<String>,
+
+    /// Kafka payload encoding (json or msgpack) [default: json]
+    #[arg(long)]
+    kafka_format: Option<String>,
+
+    /// Port for the Prometheus /metrics endpoint, 0 to disable [default: 9100]
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Port for the /healthz and /readyz probes and the /subscriptions list (disabled unless set)
+    #[arg(long)]
+    health_port: Option<u16>,
+
+    /// Serve normalized events to WebSocket clients on this port (disabled unless set)
+    #[arg(long)]
+    ws_serve_port: Option<u16>,
+
+    /// Record every event as NDJSON files in this directory
+    #[arg(long)]
+    record_dir: Option<PathBuf>,
+
+    /// Rotate recordings once a file reaches this many megabytes instead of hourly
+    #[arg(long)]
+    record_rotate_mb: Option<u64>,
+
+    /// Write agg trades and klines as Parquet files partitioned by symbol and date
+    #[arg(long)]
+    parquet_dir: Option<PathBuf>,
+
+    /// Rows buffered per Parquet partition before it is written [default: 10000]
+    #[arg(long)]
+    parquet_batch_rows: Option<usize>,
+
+    /// Stop cleanly after running N seconds, e.g. for a short capture
+    #[arg(long)]
+    max_duration: Option<u64>,
+
+    /// Stop cleanly after receiving N events
+    #[arg(long)]
+    max_events: Option<u64>,
+
+    /// Republish recorded NDJSON events from this directory instead of connecting to exchanges
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Replay pacing as a multiple of real time, 0 for as fast as possible [default: 0]
+    #[arg(long)]
+    replay_speed: Option<f64>,
+
+    /// Log level [default: info]
+    #[arg(short, long)]
+    log: Option<String>,
+
+    /// Log line format: text or json [default: text]
+    #[arg(long)]
+    log_format: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Tasks run instead of the gateway
+#[derive(Subcommand, Debug, PartialEq)]
+enum Command {
+    /// List the symbols each selected exchange trades, then exit
+    Symbols {
+        /// Only list symbols containing this text (case-insensitive)
+        #[arg(long)]
+        contains: Option<String>,
+    },
+}
+
+impl Args {
+    /// Override config values with any flags given on the command line
+    fn apply(self, config: &mut GatewayConfig) -> Result<()> {
+        if let Some(redis) = self.redis {
+            config.redis_url = redis;
+        }
+        if !self.symbols.is_empty() {
+            config.symbols = self.symbols;
+        }
+        for entry in &self.exchange_symbols {
+            let (name, symbols) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid exchange symbols '{}', expected exchange=SYM1,SYM2", entry))?;
+            let symbols = symbols
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+            config.exchange_symbols.insert(parse_exchange_type(name)?, symbols);
+        }
+        if !self.exchanges.is_empty() {
+            config.exchanges = self.exchanges
+                .iter()
+                .map(|e| parse_exchange_type(e))
+                .collect::<Result<Vec<_>>>()?;
+        }
+        config.strict_symbols |= self.strict_symbols;
+        config.skip_unlisted_symbols |= self.skip_unlisted_symbols;
+        config.testnet |= self.testnet;
+        config.count_events |= self.count;
+        if let Some(interval) = self.summary_interval_secs {
+            config.summary_interval_secs = interval;
+        }
+        config.verbose |= self.verbose;
+
+        // Parse per-exchange subscription batch sizes
+        for entry in &self.subscribe_batch_size {
+            let (name, size) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid batch size '{}', expected exchange=size", entry))?;
+            let size = size.parse::<usize>()
+                .context(format!("Invalid batch size for {}", name))?;
+            config.subscribe_batch_sizes.insert(parse_exchange_type(name)?, size);
+        }
+
+        for entry in &self.subscribe_rate_limit {
+            let (name, rate) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid rate limit '{}', expected exchange=frames_per_second", entry))?;
+            let rate = rate.parse::<u32>()
+                .context(format!("Invalid rate limit for {}", name))?;
+            config.subscribe_rate_limits.insert(parse_exchange_type(name)?, rate);
+        }
+
+        if let Some(contract_type) = self.continuous_contract {
+            config.continuous_contract = Some(contract_type.parse()?);
+        }
+        if self.order_book_depth.is_some() {
+            config.order_book_depth = self.order_book_depth;
+        }
+        for entry in &self.depth_levels {
+            let (name, levels) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid depth levels '{}', expected exchange=levels", entry))?;
+            let levels = levels.parse::<u16>()
+                .context(format!("Invalid depth levels for {}", name))?;
+            config.depth_levels.insert(parse_exchange_type(name)?, levels);
+        }
+        config.derive_book_ticker |= self.derive_book_ticker;
+        if let Some(speed) = self.depth_update_speed {
+            config.depth_update_speed = Some(speed.parse()?);
+        }
+        if let Some(market) = self.binance_market {
+            config.binance_market = market.parse()?;
+        }
+        if self.binance_ws.is_some() {
+            config.binance_ws = self.binance_ws;
+        }
+        if let Some(inst_type) = self.okx_inst_type {
+            config.okx_inst_type = inst_type.parse()?;
+        }
+        if self.okx_ws.is_some() {
+            config.okx_ws = self.okx_ws;
+        }
+        if self.binance_api_key.is_some() {
+            config.binance_api_key = self.binance_api_key;
+        }
+        if self.okx_api_key.is_some() {
+            config.okx_api_key = self.okx_api_key;
+        }
+        if self.okx_api_secret.is_some() {
+            config.okx_api_secret = self.okx_api_secret;
+        }
+        if self.okx_passphrase.is_some() {
+            config.okx_passphrase = self.okx_passphrase;
+        }
+        if self.proxy.is_some() {
+            config.proxy = self.proxy;
+        }
+        if self.open_interest_interval.is_some() {
+            config.open_interest_interval_secs = self.open_interest_interval;
+        }
+        if let Some(timeout) = self.stale_timeout_secs {
+            config.stale_timeout_secs = timeout;
+        }
+        if let Some(percent) = self.parse_error_percent {
+            config.parse_error_percent = percent;
+        }
+        if let Some(window) = self.parse_error_window {
+            config.parse_error_window = window;
+        }
+        config.reconnect_on_parse_errors |= self.reconnect_on_parse_errors;
+        if let Some(timeout) = self.reconnect_verify_timeout_secs {
+            config.reconnect_verify_timeout_secs = timeout;
+        }
+        if let Some(multiplier) = self.reconnect_multiplier {
+            config.reconnect_multiplier = multiplier;
+        }
+        if let Some(jitter) = self.reconnect_jitter {
+            config.reconnect_jitter = jitter;
+        }
+        if self.reconnect_jitter_seed.is_some() {
+            config.reconnect_jitter_seed = self.reconnect_jitter_seed;
+        }
+        if let Some(attempts) = self.connect_attempts {
+            config.connect_attempts = attempts;
+        }
+        if let Some(timeout) = self.connect_timeout_secs {
+            config.connect_timeout_secs = timeout;
+        }
+        if let Some(interval) = self.time_sync_interval_secs {
+            config.time_sync_interval_secs = interval;
+        }
+        if let Some(drift) = self.max_clock_drift_ms {
+            config.max_clock_drift_ms = drift;
+        }
+        config.apply_clock_offset |= self.apply_clock_offset;
+        config.closed_klines_only |= self.closed_klines_only;
+        if self.min_trade_quantity.is_some() {
+            config.min_trade_quantity = self.min_trade_quantity;
+        }
+        if self.min_trade_notional.is_some() {
+            config.min_trade_notional = self.min_trade_notional;
+        }
+        if !self.symbol_whitelist.is_empty() {
+            config.symbol_whitelist = self.symbol_whitelist;
+        }
+        if !self.symbol_blacklist.is_empty() {
+            config.symbol_blacklist = self.symbol_blacklist;
+        }
+        if let Some(batch_size) = self.redis_batch_size {
+            config.redis_batch_size = batch_size;
+        }
+        if let Some(interval) = self.redis_flush_interval_ms {
+            config.redis_flush_interval_ms = interval;
+        }
+        if let Some(mode) = self.redis_output {
+            config.redis_output_mode = mode.parse()?;
+        }
+        if let Some(maxlen) = self.redis_maxlen {
+            config.redis_maxlen = maxlen;
+        }
+        if let Some(format) = self.redis_format {
+            config.redis_format = format.parse()?;
+        }
+        if let Some(compression) = self.redis_compression {
+            config.redis_compression = compression.parse()?;
+        }
+        if let Some(backlog_size) = self.redis_backlog_size {
+            config.redis_backlog_size = backlog_size;
+        }
+        if let Some(pool_size) = self.redis_pool_size {
+            config.redis_pool_size = pool_size;
+        }
+        if let Some(queue_size) = self.redis_queue_size {
+            config.redis_queue_size = queue_size;
+        }
+        if let Some(policy) = self.redis_backpressure {
+            config.redis_backpressure = policy.parse()?;
+        }
+        if let Some(percent) = self.redis_queue_warn_percent {
+            config.redis_queue_warn_percent = percent;
+        }
+        if let Some(window) = self.redis_depth_coalesce_ms {
+            config.redis_depth_coalesce_ms = window;
+        }
+        if let Some(window) = self.redis_trade_aggregation_ms {
+            config.redis_trade_aggregation_ms = window;
+        }
+        for entry in &self.redis_trade_aggregation_windows {
+            let (symbol, window) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid trade aggregation window '{}', expected symbol=ms", entry))?;
+            let window = window.parse::<u64>()
+                .context(format!("Invalid trade aggregation window for {}", symbol))?;
+            config.redis_trade_aggregation_windows.insert(symbol.to_uppercase(), window);
+        }
+        if let Some(grace) = self.redis_reorder_grace_ms {
+            config.redis_reorder_grace_ms = grace;
+        }
+        config.publish_raw |= self.publish_raw;
+        config.publish_nbbo |= self.publish_nbbo;
+        if let Some(max_age) = self.nbbo_max_quote_age_ms {
+            config.nbbo_max_quote_age_ms = max_age;
+        }
+        config.publish_latency |= self.publish_latency;
+        if let Some(threshold) = self.opportunity_threshold_bps {
+            config.opportunity_threshold_bps = threshold;
+        }
+        if let Some(hysteresis) = self.opportunity_hysteresis_bps {
+            config.opportunity_hysteresis_bps = hysteresis;
+        }
+        if let Some(routing) = self.redis_routing {
+            config.redis_routing = routing.parse()?;
+        }
+        if let Some(prefix) = self.redis_channel_prefix {
+            config.redis_channel_prefix = prefix;
+        }
+        if !self.output.is_empty() {
+            config.outputs = self.output
+                .iter()
+                .map(|name| name.trim().parse())
+                .collect::<Result<_, _>>()?;
+        }
+        if self.dry_run {
+            config.outputs = vec![OutputBackend::Stdout];
+        }
+        if let Some(brokers) = self.kafka_brokers {
+            config.kafka_brokers = brokers;
+        }
+        if let Some(prefix) = self.kafka_topic_prefix {
+            config.kafka_topic_prefix = prefix;
+        }
+        if let Some(format) = self.kafka_format {
+            config.kafka_format = format.parse()?;
+        }
+        if let Some(port) = self.metrics_port {
+            config.metrics_port = port;
+        }
+        if self.health_port.is_some() {
+            config.health_port = self.health_port;
+        }
+        if self.ws_serve_port.is_some() {
+            config.ws_serve_port = self.ws_serve_port;
+        }
+        if self.record_dir.is_some() {
+            config.record_dir = self.record_dir;
+        }
+        if self.record_rotate_mb.is_some() {
+            config.record_rotate_mb = self.record_rotate_mb;
+        }
+        if self.parquet_dir.is_some() {
+            config.parquet_dir = self.parquet_dir;
+        }
+        if let Some(batch_rows) = self.parquet_batch_rows {
+            config.parquet_batch_rows = batch_rows;
+        }
+        if self.max_duration.is_some() {
+            config.max_duration_secs = self.max_duration;
+        }
+        if self.max_events.is_some() {
+            config.max_events = self.max_events;
+        }
+        if self.replay.is_some() {
+            config.replay_dir = self.replay;
+        }
+        if let Some(speed) = self.replay_speed {
+            config.replay_speed = speed;
+        }
+        if let Some(log) = self.log {
+            config.log_level = log;
+        }
+        if let Some(format) = self.log_format {
+            config.log_format = format.parse()?;
+        }
+
+        Ok(())
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    let command = args.command.take();
 
-    // Initialize logging
-    let log_level = args.log.to_lowercase();
-    let env_filter = match log_level.as_str() {
-        "trace" => "trace",
-        "debug" => "debug",
-        "info" => "info",
-        "warn" => "warn",
-        "error" => "error",
-        _ => "info",
+    let mut config = match &args.config {
+        Some(path) => GatewayConfig::from_file(path)?,
+        None => GatewayConfig::default(),
     };
+    args.apply(&mut config)?;
 
-    tracing_subscriber::fmt()
-        .with_env_filter(env_filter)
-        .with_target(false)
-        .init();
+    // Initialize logging
+    logging::init(&config.log_level, config.log_format)?;
+
+    if let Some(Command::Symbols { contains }) = command {
+        return list_symbols(&config, contains.as_deref()).await;
+    }
 
     info!("Flash Arbitrage Gateway starting...");
 
-    // Parse exchange types
-    let exchanges = if args.exchanges.is_empty() {
-        vec![ExchangeType::Binance]
-    } else {
-        args.exchanges
-            .iter()
-            .map(|e| match e.to_lowercase().as_str() {
-                "binance" => Ok(ExchangeType::Binance),
-                "okx" => Ok(ExchangeType::Okx),
-                _ => anyhow::bail!("Unknown exchange: {}", e),
-            })
-            .collect::<Result<Vec<_>>>()?
+    info!("Configuration: {:?}", config);
+
+    if config.outputs.is_empty() {
+        anyhow::bail!("No output backends configured");
+    }
+    info!("Publishing events to {}", config.outputs.iter().map(|o| o.to_string()).collect::<Vec<_>>().join(", "));
+
+    let (redis_publisher, sinks) = build_outputs(&config).await?;
+
+    let _metrics_server = match config.metrics_port {
+        0 => None,
+        port => Some(metrics::serve(port).context("Failed to start metrics server")?),
     };
 
-    // Default symbols if none provided
-    let symbols = if args.symbols.is_empty() {
-        vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()]
+    if let Some(dir) = config.replay_dir.clone() {
+        return run_replay(&dir, config.replay_speed, redis_publisher, sinks).await;
+    }
+
+    if config.strict_symbols {
+        validate_symbols(&config).await?;
+    } else if config.skip_unlisted_symbols {
+        skip_unlisted_symbols(&mut config).await;
+    }
+
+    let health = HealthState::new();
+    // Without Redis there is nothing to wait for
+    health.set_redis_ok(true);
+    let _health_server = config.health_port
+        .map(|port| health::serve(health.clone(), port))
+        .transpose()
+        .context("Failed to start health server")?;
+
+    // Run the gateway
+    let exchanges = build_exchanges(&config, redis_publisher.as_ref())?;
+    run_gateway(config, exchanges, redis_publisher, sinks, health, shutdown_signal()).await?;
+
+    Ok(())
+}
+
+/// Connect the configured output backends. Redis is only contacted when it is one of them.
+async fn build_outputs(config: &GatewayConfig) -> Result<(Option<RedisPublisher>, FanoutSink)> {
+    let redis_publisher = if config.outputs.contains(&OutputBackend::Redis) {
+        Some(connect_redis(config).await?)
     } else {
-        args.symbols
+        None
     };
 
-    let config = GatewayConfig {
-        redis_url: args.redis,
-        symbols,
-        exchanges,
-        testnet: args.testnet,
-    };
+    // Redis is published to by the exchange tasks themselves; other backends receive events from the gateway loop
+    let mut sinks = FanoutSink::new();
+    if config.outputs.contains(&OutputBackend::Kafka) {
+        sinks = sinks.with_sink(KafkaPublisher::new(KafkaConfig {
+            brokers: config.kafka_brokers.clone(),
+            topic_prefix: config.kafka_topic_prefix.clone(),
+            format: config.kafka_format,
+        })?);
+    }
+    if config.outputs.contains(&OutputBackend::Stdout) {
+        sinks = sinks.with_sink(StdoutSink::new());
+    }
 
-    info!("Configuration: {:?}", config);
+    Ok((redis_publisher, sinks))
+}
 
-    // Create Redis publisher
+/// Connect to Redis and check it answers
+async fn connect_redis(config: &GatewayConfig) -> Result<RedisPublisher> {
     let redis_publisher = RedisPublisher::new(redis_publisher::RedisConfig {
-        url: config.redis_url,
+        url: config.redis_url.clone(),
+        batch_size: config.redis_batch_size,
+        flush_interval_ms: config.redis_flush_interval_ms,
+        output_mode: config.redis_output_mode,
+        maxlen: config.redis_maxlen,
+        format: config.redis_format,
+        compression: config.redis_compression,
+        backlog_size: config.redis_backlog_size,
+        channel_prefix: config.redis_channel_prefix.clone(),
+        channel_overrides: config.redis_channels.clone(),
+        pool_size: config.redis_pool_size,
+        // A replay has no socket to keep drained and should publish every event
+        queue_size: if config.replay_dir.is_some() { 0 } else { config.redis_queue_size },
+        backpressure: config.redis_backpressure,
+        queue_warn_percent: config.redis_queue_warn_percent,
+        // Nothing runs the coalesce task during a replay
+        depth_coalesce_ms: if config.replay_dir.is_some() { 0 } else { config.redis_depth_coalesce_ms },
+        // Recordings already hold whatever the live gateway published
+        trade_aggregation_ms: if config.replay_dir.is_some() { 0 } else { config.redis_trade_aggregation_ms },
+        trade_aggregation_windows: if config.replay_dir.is_some() {
+            HashMap::new()
+        } else {
+            config.redis_trade_aggregation_windows.clone()
+        },
+        // Nothing runs the reorder task during a replay either
+        reorder_grace_ms: if config.replay_dir.is_some() { 0 } else { config.redis_reorder_grace_ms },
+        publish_raw: config.publish_raw,
+        publish_nbbo: config.publish_nbbo,
+        nbbo_max_quote_age_ms: config.nbbo_max_quote_age_ms,
+        opportunity_threshold_bps: config.opportunity_threshold_bps,
+        opportunity_hysteresis_bps: config.opportunity_hysteresis_bps,
+        routing: config.redis_routing,
+        include_latency: config.publish_latency,
+        apply_clock_offset: config.apply_clock_offset,
+        // Exchanges publish as they parse, ahead of the loop counting towards --max-events
+        max_events: config.max_events,
     })
     .await
-    .context("Failed to connect to Redis")?;
+    .context("Failed to connect to Redis")?
+    .with_filter(config.event_filter());
 
     info!("Connected to Redis at {}", config.redis_url);
 
@@ -134,162 +836,1026 @@ async fn main() -> Result<()> {
         Ok(_) => info!("Redis connection verified"),
         Err(e) => {
             error!("Redis ping failed: {}", e);
-            return Err(e.context("Redis connection check failed"));
+            return Err(anyhow::Error::new(e).context("Redis connection check failed"));
         }
     }
 
-    // Run the gateway
-    run_gateway(config, redis_publisher).await?;
+    Ok(redis_publisher)
+}
+
+/// How often the gateway pings Redis and reports the backlog
+const REDIS_HEALTH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long exchange tasks get to close their connections on shutdown
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Wait for Ctrl-C or SIGTERM, returning the signal's name
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = sigterm.recv() => "SIGTERM",
+            },
+            Err(e) => {
+                warn!("Failed to install SIGTERM handler: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
+
+/// Print the symbols each configured exchange trades, warning about configured symbols it doesn't
+async fn list_symbols(config: &GatewayConfig, contains: Option<&str>) -> Result<()> {
+    for exchange in &config.exchanges {
+        let listed = match instruments::fetch_symbols(*exchange, config.testnet).await {
+            Ok(listed) => listed,
+            Err(e) => {
+                error!("Failed to list {} symbols: {}", exchange, e);
+                continue;
+            }
+        };
+
+        for symbol in config.symbols_for(*exchange) {
+            if !listed.contains(symbol) {
+                warn!("{} does not list configured symbol {}", exchange, symbol);
+            }
+        }
+
+        let shown = instruments::filter_symbols(listed, contains);
+        println!("# {} ({} symbols)", exchange, shown.len());
+        for symbol in shown {
+            println!("{}", symbol);
+        }
+    }
 
     Ok(())
 }
 
-/// Main gateway loop
-async fn run_gateway(config: GatewayConfig, redis_publisher: RedisPublisher) -> Result<()> {
+/// Check each exchange's subscriptions against the symbols it lists, failing on any it doesn't
+async fn validate_symbols(config: &GatewayConfig) -> Result<()> {
+    for exchange in &config.exchanges {
+        if !instruments::supports_listing(*exchange) {
+            warn!("Cannot check {} symbols: listing its instruments is not supported", exchange);
+            continue;
+        }
+
+        let listed = InstrumentList::fetch(*exchange, config.testnet).await
+            .context(format!("Failed to fetch {} instruments", exchange))?;
+        listed.validate_subscriptions(&config.subscriptions_for(*exchange))?;
+        info!("{} lists all {} subscribed symbols ({} listed)", exchange, config.symbols_for(*exchange).len(), listed.len());
+    }
+
+    Ok(())
+}
+
+/// Drop symbols from the exchanges that don't list them, keeping every symbol where no listing is available
+async fn skip_unlisted_symbols(config: &mut GatewayConfig) {
+    let mut listings = Vec::new();
+    for exchange in &config.exchanges {
+        if !instruments::supports_listing(*exchange) {
+            warn!("Cannot check {} symbols: listing its instruments is not supported", exchange);
+            continue;
+        }
+        match InstrumentList::fetch(*exchange, config.testnet).await {
+            Ok(listed) => listings.push(listed),
+            Err(e) => warn!("Failed to fetch {} instruments, subscribing to every symbol: {}", exchange, e),
+        }
+    }
+    config.retain_listed_symbols(&listings);
+}
+
+/// Republish a recorded session to every output until it ends or a signal arrives
+async fn run_replay(dir: &std::path::Path, speed: f64, redis_publisher: Option<RedisPublisher>, mut sinks: FanoutSink) -> Result<()> {
+    let mut source = ReplaySource::from_dir(dir)?.with_speed(speed);
+    info!("Replaying {} at {}x", dir.display(), if speed > 0.0 { speed.to_string() } else { "max".to_string() });
+
+    let flush_handle = redis_publisher
+        .as_ref()
+        .and_then(|publisher| publisher.is_batching().then(|| publisher.spawn_flush_task()));
+    if let Some(publisher) = redis_publisher {
+        sinks = sinks.with_sink(publisher);
+    }
+
+    let result = tokio::select! {
+        signal = shutdown_signal() => {
+            info!("{} received, stopping replay", signal);
+            Ok(())
+        }
+        published = source.replay_into(&mut sinks) => {
+            published.map(|count| info!("Replay finished: {} events published", count))
+        }
+    };
+
+    if let Some(flush_handle) = flush_handle {
+        flush_handle.abort();
+    }
+    if let Err(e) = sinks.shutdown().await {
+        error!("Failed to flush outputs after replay: {}", e);
+    }
+
+    Ok(result?)
+}
+
+/// Create a client for every configured exchange, forwarding to Redis when it is enabled
+fn build_exchanges(
+    config: &GatewayConfig,
+    redis_publisher: Option<&RedisPublisher>,
+) -> Result<HashMap<ExchangeType, Box<dyn Exchange>>> {
     let mut exchange_map: HashMap<ExchangeType, Box<dyn Exchange>> = HashMap::new();
+    let proxy = config.proxy().context("Invalid proxy")?;
+    if let Some(ref proxy) = proxy {
+        info!("Tunneling Binance and OKX connections through {:?} proxy {}:{}", proxy.kind, proxy.host, proxy.port);
+    }
+
+    if let Some(missing) = config.exchanges.iter().find(|exchange| !exchange.is_compiled()) {
+        anyhow::bail!(
+            "{} support is not compiled into this build; rebuild with `--features {}` to use it",
+            missing, missing
+        );
+    }
 
     // Initialize exchanges
     for exchange_type in &config.exchanges {
+        let batch_size = config.subscribe_batch_sizes.get(exchange_type).copied();
+        let rate_limit = config.subscribe_rate_limits.get(exchange_type).copied();
+        // Only partial books are snapshots of the top; full-depth streams stay depth updates
+        let derive_book_ticker = config.derive_book_ticker && config.depth_levels.contains_key(exchange_type);
         let exchange: Box<dyn Exchange> = match exchange_type {
+            #[cfg(feature = "binance")]
             ExchangeType::Binance => {
-                info!("Initializing Binance client (testnet={})", config.testnet);
-                Box::new(binance::BinanceClient::new(config.testnet)
-                    .with_redis_publisher(redis_publisher.clone()))
+                info!("Initializing Binance {} client (testnet={})", config.binance_market, config.testnet);
+                let mut client = binance::BinanceClient::new(config.testnet)
+                    .with_market(config.binance_market)
+                    .with_subscribe_batch_size(batch_size.unwrap_or(binance::DEFAULT_SUBSCRIBE_BATCH_SIZE))
+                    .with_rate_limit(rate_limit.unwrap_or(binance::DEFAULT_MESSAGES_PER_SECOND))
+                    .with_stale_timeout(config.stale_timeout())
+                    .with_parse_error_monitor(config.parse_error_monitor())
+                    .with_derived_book_ticker(derive_book_ticker)
+                    .with_connect_policy(config.reconnect_policy());
+                if let Some(publisher) = redis_publisher {
+                    client = client.with_redis_publisher(publisher.clone());
+                }
+                if let Some(depth) = config.order_book_depth {
+                    client = client.with_order_book(depth);
+                }
+                if let Some(ref url) = config.binance_ws {
+                    client = client.with_endpoint(url.clone());
+                }
+                if let Some(ref proxy) = proxy {
+                    client = client.with_proxy(proxy.clone());
+                }
+                Box::new(client)
             }
+            #[cfg(feature = "okx")]
             ExchangeType::Okx => {
-                info!("Initializing OKX client (demo={})", config.testnet);
-                Box::new(okx::OkxClient::new(config.testnet)
-                    .with_redis_publisher(redis_publisher.clone()))
+                info!("Initializing OKX {} client (demo={})", config.okx_inst_type, config.testnet);
+                let mut client = okx::OkxClient::new(config.testnet)
+                    .with_inst_type(config.okx_inst_type)
+                    .with_subscribe_batch_size(batch_size.unwrap_or(okx::DEFAULT_SUBSCRIBE_BATCH_SIZE))
+                    .with_rate_limit(rate_limit.unwrap_or(okx::DEFAULT_MESSAGES_PER_SECOND))
+                    .with_stale_timeout(config.stale_timeout())
+                    .with_parse_error_monitor(config.parse_error_monitor())
+                    .with_derived_book_ticker(derive_book_ticker)
+                    .with_connect_policy(config.reconnect_policy());
+                if let Some(publisher) = redis_publisher {
+                    client = client.with_redis_publisher(publisher.clone());
+                }
+                if let Some(ref url) = config.okx_ws {
+                    client = client.with_endpoint(url.clone());
+                }
+                match (&config.okx_api_key, &config.okx_api_secret, &config.okx_passphrase) {
+                    (Some(key), Some(secret), Some(passphrase)) => {
+                        client = client.with_credentials(key.clone(), secret.clone(), passphrase.clone());
+                    }
+                    (None, None, None) => {}
+                    _ => anyhow::bail!("okx_api_key, okx_api_secret and okx_passphrase must be set together"),
+                }
+                if let Some(ref proxy) = proxy {
+                    client = client.with_proxy(proxy.clone());
+                }
+                Box::new(client)
             }
+            #[cfg(feature = "bybit")]
+            ExchangeType::Bybit => {
+                info!("Initializing Bybit client (testnet={})", config.testnet);
+                let mut client = bybit::BybitClient::new(config.testnet)
+                    .with_subscribe_batch_size(batch_size.unwrap_or(bybit::DEFAULT_SUBSCRIBE_BATCH_SIZE))
+                    .with_rate_limit(rate_limit.unwrap_or(bybit::DEFAULT_MESSAGES_PER_SECOND))
+                    .with_stale_timeout(config.stale_timeout())
+                    .with_parse_error_monitor(config.parse_error_monitor())
+                    .with_derived_book_ticker(derive_book_ticker);
+                if let Some(publisher) = redis_publisher {
+                    client = client.with_redis_publisher(publisher.clone());
+                }
+                Box::new(client)
+            }
+            #[cfg(feature = "coinbase")]
+            ExchangeType::Coinbase => {
+                info!("Initializing Coinbase client (sandbox={})", config.testnet);
+                // Coinbase's level2 channel is always the full book, so there is no top to derive from
+                let _ = derive_book_ticker;
+                let mut client = coinbase::CoinbaseClient::new(config.testnet)
+                    .with_subscribe_batch_size(batch_size.unwrap_or(coinbase::DEFAULT_SUBSCRIBE_BATCH_SIZE))
+                    .with_rate_limit(rate_limit.unwrap_or(coinbase::DEFAULT_MESSAGES_PER_SECOND))
+                    .with_stale_timeout(config.stale_timeout())
+                    .with_parse_error_monitor(config.parse_error_monitor());
+                if let Some(publisher) = redis_publisher {
+                    client = client.with_redis_publisher(publisher.clone());
+                }
+                Box::new(client)
+            }
+            #[cfg(feature = "kucoin")]
+            ExchangeType::Kucoin => {
+                if config.testnet {
+                    warn!("KuCoin has no public testnet; connecting to production");
+                }
+                info!("Initializing KuCoin client");
+                let mut client = kucoin::KucoinClient::new()
+                    .with_subscribe_batch_size(batch_size.unwrap_or(kucoin::DEFAULT_SUBSCRIBE_BATCH_SIZE))
+                    .with_rate_limit(rate_limit.unwrap_or(kucoin::DEFAULT_MESSAGES_PER_SECOND))
+                    .with_stale_timeout(config.stale_timeout())
+                    .with_parse_error_monitor(config.parse_error_monitor())
+                    .with_derived_book_ticker(derive_book_ticker);
+                if let Some(publisher) = redis_publisher {
+                    client = client.with_redis_publisher(publisher.clone());
+                }
+                Box::new(client)
+            }
+            #[cfg(feature = "deribit")]
+            ExchangeType::Deribit => {
+                info!("Initializing Deribit client (testnet={})", config.testnet);
+                let mut client = deribit::DeribitClient::new(config.testnet)
+                    .with_subscribe_batch_size(batch_size.unwrap_or(deribit::DEFAULT_SUBSCRIBE_BATCH_SIZE))
+                    .with_rate_limit(rate_limit.unwrap_or(deribit::DEFAULT_MESSAGES_PER_SECOND))
+                    .with_stale_timeout(config.stale_timeout())
+                    .with_parse_error_monitor(config.parse_error_monitor())
+                    .with_derived_book_ticker(derive_book_ticker);
+                if let Some(publisher) = redis_publisher {
+                    client = client.with_redis_publisher(publisher.clone());
+                }
+                Box::new(client)
+            }
+            #[cfg(feature = "gateio")]
+            ExchangeType::Gateio => {
+                info!("Initializing Gate.io client (testnet={})", config.testnet);
+                let mut client = gateio::GateioClient::new(config.testnet)
+                    .with_subscribe_batch_size(batch_size.unwrap_or(gateio::DEFAULT_SUBSCRIBE_BATCH_SIZE))
+                    .with_rate_limit(rate_limit.unwrap_or(gateio::DEFAULT_MESSAGES_PER_SECOND))
+                    .with_stale_timeout(config.stale_timeout())
+                    .with_parse_error_monitor(config.parse_error_monitor())
+                    .with_derived_book_ticker(derive_book_ticker);
+                if let Some(publisher) = redis_publisher {
+                    client = client.with_redis_publisher(publisher.clone());
+                }
+                Box::new(client)
+            }
+            #[cfg(feature = "bitget")]
+            ExchangeType::Bitget => {
+                info!("Initializing Bitget client (demo={})", config.testnet);
+                let mut client = bitget::BitgetClient::new(config.testnet)
+                    .with_subscribe_batch_size(batch_size.unwrap_or(bitget::DEFAULT_SUBSCRIBE_BATCH_SIZE))
+                    .with_rate_limit(rate_limit.unwrap_or(bitget::DEFAULT_MESSAGES_PER_SECOND))
+                    .with_stale_timeout(config.stale_timeout())
+                    .with_parse_error_monitor(config.parse_error_monitor())
+                    .with_derived_book_ticker(derive_book_ticker);
+                if let Some(publisher) = redis_publisher {
+                    client = client.with_redis_publisher(publisher.clone());
+                }
+                Box::new(client)
+            }
+            // Turned away above; only reachable in builds leaving some exchange out
+            #[allow(unreachable_patterns)]
+            other => anyhow::bail!("{} support is not compiled into this build", other),
         };
 
         exchange_map.insert(*exchange_type, exchange);
     }
 
-    // Connect to all exchanges
-    for (exchange_type, exchange) in exchange_map.iter_mut() {
-        info!("Connecting to {}...", exchange_type);
-        let mut ex = Box::new(exchange.as_ref());
+    Ok(exchange_map)
+}
+
+/// Binance user data stream, when an API key is configured
+#[cfg(feature = "binance")]
+fn build_user_data_stream(
+    config: &GatewayConfig,
+    redis_publisher: Option<&RedisPublisher>,
+) -> Result<Option<binance::UserDataStream>> {
+    let Some(ref api_key) = config.binance_api_key else {
+        return Ok(None);
+    };
+    if !config.exchanges.contains(&ExchangeType::Binance) {
+        warn!("Binance API key is set but binance is not streamed; skipping the user data stream");
+        return Ok(None);
+    }
+
+    let mut client = binance::BinanceClient::new(config.testnet)
+        .with_market(config.binance_market)
+        .with_connect_policy(config.reconnect_policy())
+        .with_api_key(api_key.clone());
+    if let Some(ref url) = config.binance_ws {
+        client = client.with_endpoint(url.clone());
+    }
+    if let Some(proxy) = config.proxy().context("Invalid proxy")? {
+        client = client.with_proxy(proxy);
+    }
 
-        // We need to use the actual mutable reference
-        let actual_exchange = exchange_map.get_mut(exchange_type).unwrap();
-        actual_exchange.connect().await
-            .context(format!("Failed to connect to {}", exchange_type))?;
+    let mut stream = client.user_data_stream()?.with_reconnect_policy(config.reconnect_policy());
+    if let Some(publisher) = redis_publisher {
+        stream = stream.with_redis_publisher(publisher.clone());
     }
+    Ok(Some(stream))
+}
+
+/// Everything the gateway loop does with an event once it arrives
+struct EventOutputs {
+    filter: EventFilter,
+    latency: LatencyTracker,
+    summary: EventSummary,
+    counter: Option<EventCounter>,
+    recorder: Option<FileRecorder>,
+    parquet: Option<ParquetRecorder>,
+    sinks: FanoutSink,
+    ws_server: Option<WsServer>,
+    verbose: bool,
+}
 
-    // Subscribe to market data
-    let subscriptions = create_subscriptions(&config.symbols);
-    info!("Subscribing to {} data streams per exchange", subscriptions.len());
+impl EventOutputs {
+    /// Measure, record and publish one event
+    async fn handle(&mut self, event: &MarketEvent) {
+        self.latency.record(event);
+        self.summary.record(event);
+        if let Some(ref mut counter) = self.counter {
+            counter.record(event);
+        }
+        if let Some(ref mut recorder) = self.recorder {
+            if let Err(e) = recorder.record(event) {
+                warn!("Failed to record event: {}", e);
+            }
+        }
+        if let Some(ref mut parquet) = self.parquet {
+            if let Err(e) = parquet.record(event) {
+                warn!("Failed to write Parquet batch: {}", e);
+            }
+        }
+        // Redis filters the events it is handed too, so skips are only counted here
+        if let EventResult::Skipped = self.filter.apply(event) {
+            metrics::global().record_skipped(event.exchange());
+            return;
+        }
+        if !self.sinks.is_empty() {
+            if let Err(e) = self.sinks.publish_event(event).await {
+                warn!("Failed to publish event: {}", e);
+            }
+        }
+        if let Some(ref ws_server) = self.ws_server {
+            if let Err(e) = ws_server.publish(event) {
+                warn!("Failed to forward event to WebSocket clients: {}", e);
+            }
+        }
+        if self.verbose {
+            info!(
+                exchange = %event.exchange(),
+                symbol = event.symbol(),
+                event_type = event.event_type().as_str(),
+                "{}",
+                describe_event(event)
+            );
+        } else {
+            trace!(
+                exchange = %event.exchange(),
+                symbol = event.symbol(),
+                event_type = event.event_type().as_str(),
+                "{}",
+                describe_event(event)
+            );
+        }
+    }
 
-    for (exchange_type, exchange) in exchange_map.iter_mut() {
-        if let Err(e) = exchange.subscribe(subscriptions.clone()).await {
-            warn!("Failed to subscribe to {}: {}", exchange_type, e);
+    /// Flush the sinks, then close out the recordings
+    async fn shutdown(&mut self) {
+        if let Err(e) = self.sinks.shutdown().await {
+            error!("Failed to flush outputs on shutdown: {}", e);
+        }
+        if let Some(ref mut recorder) = self.recorder {
+            if let Err(e) = recorder.flush() {
+                error!("Failed to flush recording on shutdown: {}", e);
+            }
+        }
+        if let Some(ref mut parquet) = self.parquet {
+            match parquet.flush() {
+                Ok(rows) => info!("Wrote {} buffered Parquet rows", rows),
+                Err(e) => error!("Failed to write Parquet batches on shutdown: {}", e),
+            }
         }
     }
+}
+
+/// Main gateway loop, running until `shutdown` resolves or every exchange task stops
+async fn run_gateway(
+    config: GatewayConfig,
+    exchange_map: HashMap<ExchangeType, Box<dyn Exchange>>,
+    redis_publisher: Option<RedisPublisher>,
+    sinks: FanoutSink,
+    health: HealthState,
+    shutdown: impl Future<Output = &'static str>,
+) -> Result<()> {
+    #[cfg(feature = "binance")]
+    let user_data_stream = build_user_data_stream(&config, redis_publisher.as_ref())?;
+
+    let mut manager = GatewayManager::new(exchange_map)
+        .with_reconnect_policy(config.reconnect_policy())
+        .with_health(health.clone());
+    manager.connect_all().await?;
+
+    let subscriptions: HashMap<ExchangeType, Vec<Subscription>> = config.exchanges
+        .iter()
+        .map(|exchange_type| (*exchange_type, config.subscriptions_for(*exchange_type)))
+        .collect();
+    manager.subscribe_all(&subscriptions).await;
 
     info!("Gateway running, streaming market data...");
 
-    // Main event loop - receive events from all exchanges
-    let mut ping_interval = time::interval(Duration::from_secs(30));
-    let mut reconnect_interval = time::interval(Duration::from_secs(5));
+    // Batched events are also flushed on a timer so quiet periods don't hold them back
+    let flush_handle = redis_publisher
+        .as_ref()
+        .and_then(|publisher| publisher.is_batching().then(|| publisher.spawn_flush_task()));
+    // Exchanges only queue events, so a slow Redis doesn't stop them reading their sockets
+    let publish_handle = redis_publisher.as_ref().and_then(|publisher| publisher.spawn_publish_task());
+    let coalesce_handle = redis_publisher.as_ref().and_then(|publisher| publisher.spawn_coalesce_task());
+    let aggregate_handle = redis_publisher.as_ref().and_then(|publisher| publisher.spawn_aggregate_task());
+    let reorder_handle = redis_publisher.as_ref().and_then(|publisher| publisher.spawn_reorder_task());
+    let raw_handle = redis_publisher.as_ref().and_then(|publisher| publisher.spawn_raw_task());
+
+    // Each exchange runs on its own task and forwards events here
+    let (tx, mut rx) = mpsc::channel(runner::EVENT_CHANNEL_CAPACITY);
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let exchange_handles = manager.spawn(tx.clone(), shutdown_rx.clone());
+    let mut poller_handles = Vec::new();
+
+    // Account updates stay off the market event path, so they are only logged and published to Redis
+    #[cfg(feature = "binance")]
+    if let Some(stream) = user_data_stream {
+        let (user_tx, mut user_rx) = mpsc::channel(runner::EVENT_CHANNEL_CAPACITY);
+        poller_handles.push(stream.spawn(user_tx, shutdown_rx.clone()));
+        poller_handles.push(tokio::spawn(async move {
+            while let Some(event) = user_rx.recv().await {
+                info!(exchange = %event.exchange(), "{}", describe_user_data(&event));
+            }
+        }));
+    }
+
+    // Open interest is REST-only, so it is polled alongside the streams
+    if let Some(interval) = config.open_interest_interval() {
+        for exchange_type in &config.exchanges {
+            let symbols = config.symbols_for(*exchange_type).to_vec();
+            match OpenInterestPoller::new(*exchange_type, config.testnet, symbols) {
+                Some(poller) => {
+                    let mut poller = poller.with_interval(interval);
+                    if let Some(ref publisher) = redis_publisher {
+                        poller = poller.with_redis_publisher(publisher.clone());
+                    }
+                    poller_handles.push(poller.spawn(tx.clone()));
+                }
+                None => warn!("Open interest polling is not supported for {}", exchange_type),
+            }
+        }
+    }
+    drop(tx);
+
+    // A drifting host clock skews every latency figure, so check it up front and then periodically
+    if let Some(interval) = config.time_sync_interval() {
+        let time_sync = TimeSync::new(config.testnet)
+            .with_binance_market(config.binance_market)
+            .with_max_drift(Duration::from_millis(config.max_clock_drift_ms))
+            .with_interval(interval);
+        time_sync.check_all(&config.exchanges).await;
+        poller_handles.push(time_sync.spawn(config.exchanges.clone()));
+    }
+
+    let mut outputs = EventOutputs {
+        filter: config.event_filter(),
+        latency: LatencyTracker::new(),
+        summary: EventSummary::new(),
+        counter: config.count_events.then(EventCounter::new),
+        recorder: config.record_dir
+            .as_ref()
+            .map(|dir| FileRecorder::new(dir, config.record_rotation()))
+            .transpose()?,
+        parquet: config.parquet_dir
+            .as_ref()
+            .map(|dir| ParquetRecorder::new(dir).map(|r| r.with_batch_rows(config.parquet_batch_rows)))
+            .transpose()?,
+        sinks,
+        ws_server: config.ws_serve_port
+            .map(|port| {
+                let server = WsServer::new();
+                ws_server::serve(server.clone(), port).map(|_| server)
+            })
+            .transpose()
+            .context("Failed to start WebSocket server")?,
+        verbose: config.verbose,
+    };
+    let mut received: u64 = 0;
+    tokio::pin!(shutdown);
+    let mut redis_health = time::interval(REDIS_HEALTH_INTERVAL);
+    if config.apply_clock_offset {
+        for (exchange, offset) in metrics::global().clock_offsets() {
+            outputs.latency.set_clock_offset(exchange, offset);
+        }
+    }
+    let mut latency_report = time::interval(latency::LATENCY_REPORT_INTERVAL);
+    latency_report.reset();
+    let mut metrics_report = time::interval(metrics::SNAPSHOT_INTERVAL);
+    metrics_report.reset();
+    let mut summary_report = config.summary_interval().map(|period| time::interval_at(time::Instant::now() + period, period));
+    let deadline = config.max_duration().map(|duration| time::Instant::now() + duration);
 
     loop {
+        // Checked before waiting so filtered-out events count towards the limit too
+        if let Some(max_events) = config.max_events.filter(|&max_events| received >= max_events) {
+            info!("Received {} events, stopping gateway", max_events);
+            break;
+        }
+
         tokio::select! {
-            // Periodic ping to keep connections alive
-            _ = ping_interval.tick() => {
-                debug!("Sending keepalive ping to exchanges");
-                for (exchange_type, exchange) in exchange_map.iter_mut() {
-                    if !exchange.is_connected() {
-                        warn!("{} is not connected, will attempt reconnect", exchange_type);
-                    }
+            signal = &mut shutdown => {
+                info!("{} received, stopping gateway", signal);
+                break;
+            }
+
+            _ = async {
+                match deadline {
+                    Some(deadline) => time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
                 }
+            } => {
+                info!("Ran for {}s, stopping gateway", config.max_duration_secs.unwrap_or_default());
+                break;
             }
 
-            // Check for reconnections
-            _ = reconnect_interval.tick() => {
-                for (exchange_type, exchange) in exchange_map.iter_mut() {
-                    if !exchange.is_connected() {
-                        info!("Attempting to reconnect to {}...", exchange_type);
-                        if let Err(e) = exchange.connect().await {
-                            error!("Failed to reconnect to {}: {}", exchange_type, e);
-                        } else {
-                            info!("Successfully reconnected to {}", exchange_type);
-                            let _ = exchange.subscribe(subscriptions.clone()).await;
+            _ = redis_health.tick() => {
+                if let Some(ref redis_publisher) = redis_publisher {
+                    // A successful ping also replays anything backlogged during an outage
+                    let redis_ok = match redis_publisher.ping().await {
+                        Ok(_) => true,
+                        Err(e) => {
+                            warn!("Redis health check failed: {}", e);
+                            false
                         }
+                    };
+                    health.set_redis_ok(redis_ok);
+                    if redis_publisher.backlog_len() > 0 {
+                        warn!(
+                            "Redis backlog: {} events pending, {} dropped",
+                            redis_publisher.backlog_len(), redis_publisher.dropped_events()
+                        );
+                    }
+                }
+                if let Some(ref mut recorder) = outputs.recorder {
+                    if let Err(e) = recorder.flush() {
+                        warn!("Failed to flush recording: {}", e);
                     }
                 }
             }
 
-            // Process events (with timeout)
-            result = async {
-                for (exchange_type, exchange) in exchange_map.iter_mut() {
-                    if let Ok(Some(event)) = exchange.recv_event().await {
-                        let symbol = event.symbol();
-                        let event_type = event.event_type();
-                        info!("[{}] {}: {} - {}", exchange_type, symbol, event_type.as_str(),
-                            match event {
-                                exchange::MarketEvent::AggTrade(t) => format!("price={}", t.price),
-                                exchange::MarketEvent::Kline(k) => format!("close={}", k.close),
-                                exchange::MarketEvent::BookTicker(b) => format!("bid={}/ask={}", b.bid_price, b.ask_price),
-                                exchange::MarketEvent::DepthUpdate(_) => "update".to_string(),
-                            }
-                        );
+            _ = latency_report.tick() => {
+                if config.apply_clock_offset {
+                    for (exchange, offset) in metrics::global().clock_offsets() {
+                        outputs.latency.set_clock_offset(exchange, offset);
                     }
                 }
-                Ok::<(), anyhow::Error>(())
-            } => {
-                if let Err(e) = result {
-                    error!("Error processing events: {}", e);
+                let stats = outputs.latency.latency_stats();
+                if !stats.is_empty() {
+                    info!("Event latency over the last {} events per stream:\n{}", latency::DEFAULT_LATENCY_WINDOW, outputs.latency.summary());
+                    metrics::global().set_latency_stats(stats);
                 }
             }
+
+            _ = metrics_report.tick() => {
+                if let Some(ref redis_publisher) = redis_publisher {
+                    if let Err(e) = redis_publisher.publish_metrics(&metrics::global().snapshot()).await {
+                        warn!("Failed to publish metrics snapshot: {}", e);
+                    }
+                }
+            }
+
+            _ = async {
+                match summary_report.as_mut() {
+                    Some(report) => report.tick().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                info!("{}", outputs.summary.take());
+            }
+
+            event = rx.recv() => {
+                let Some(event) = event else {
+                    warn!("All exchange tasks have stopped");
+                    break;
+                };
+
+                received += 1;
+                outputs.handle(&event).await;
+            }
         }
     }
+
+    // Stop ingesting: exchange tasks close their own connections; give them a moment to do it
+    let _ = shutdown_tx.send(true);
+    let exchange_count = exchange_handles.len();
+    for handle in exchange_handles {
+        if time::timeout(SHUTDOWN_TIMEOUT, handle).await.is_err() {
+            warn!("Exchange task did not stop within {:?}", SHUTDOWN_TIMEOUT);
+        }
+    }
+    for handle in poller_handles {
+        handle.abort();
+    }
+
+    // Then handle whatever the exchanges sent before they stopped, still within the event limit
+    rx.close();
+    let limit = config.max_events.unwrap_or(u64::MAX);
+    while received < limit {
+        let Some(event) = rx.recv().await else {
+            break;
+        };
+        received += 1;
+        outputs.handle(&event).await;
+    }
+
+    // Flush Redis only after the exchanges stop publishing, with the background tasks out of the way
+    let background = [flush_handle, coalesce_handle, aggregate_handle, reorder_handle, publish_handle, raw_handle];
+    for handle in background.into_iter().flatten() {
+        handle.abort();
+    }
+    let mut flushed = 0;
+    if let Some(ref redis_publisher) = redis_publisher {
+        if redis_publisher.queue_dropped() > 0 {
+            warn!("{} events were dropped from the full Redis publish queue", redis_publisher.queue_dropped());
+        }
+        let (queued, peak) = redis_publisher.queue_depth();
+        if peak > 0 {
+            info!("Redis publish queue peaked at {} events, {} left to publish", peak, queued);
+        }
+        match redis_publisher.shutdown().await {
+            Ok(count) => flushed = count,
+            Err(e) => error!("Failed to flush Redis on shutdown: {}", e),
+        }
+    }
+    // Then the other outputs and the recordings
+    outputs.shutdown().await;
+
+    // A last metrics snapshot, so consumers see the final counts
+    metrics::global().set_latency_stats(outputs.latency.latency_stats());
+    if let Some(ref redis_publisher) = redis_publisher {
+        if let Err(e) = redis_publisher.publish_metrics(&metrics::global().snapshot()).await {
+            warn!("Failed to publish final metrics snapshot: {}", e);
+        }
+    }
+
+    info!(
+        "Gateway stopped: {} events received, {} exchanges closed, {} held events flushed, {} left in Redis backlog",
+        received, exchange_count, flushed, redis_publisher.as_ref().map_or(0, |p| p.backlog_len())
+    );
+
+    if let Some(counter) = outputs.counter {
+        println!("{}", counter.summary());
+    }
+
+    Ok(())
 }
 
-/// Create subscriptions for all symbols
-fn create_subscriptions(symbols: &[String]) -> Vec<Subscription> {
-    let mut subscriptions = Vec::new();
-
-    let intervals = [
-        KlineInterval::OneMinute,
-        KlineInterval::FiveMinutes,
-        KlineInterval::FifteenMinutes,
-        KlineInterval::ThirtyMinutes,
-        KlineInterval::OneHour,
-        KlineInterval::FourHours,
-    ];
-
-    for symbol in symbols {
-        // Aggregate trades
-        subscriptions.push(Subscription {
-            symbol: symbol.clone(),
-            data_type: DataType::AggTrade,
-            interval: None,
-        });
+/// Short description of an event's key value for the per-event log line
+fn describe_event(event: &exchange::MarketEvent) -> String {
+    match event {
+        exchange::MarketEvent::AggTrade(t) => format!("price={}", t.price),
+        exchange::MarketEvent::Kline(k) => format!("close={}", k.close),
+        exchange::MarketEvent::BookTicker(b) => format!("bid={}/ask={}", b.bid_price, b.ask_price),
+        exchange::MarketEvent::DepthUpdate(_) => "update".to_string(),
+        exchange::MarketEvent::FundingRate(f) => format!("rate={}", f.funding_rate),
+        exchange::MarketEvent::Liquidation(l) => format!("{:?} {}@{}", l.side, l.quantity, l.price),
+        exchange::MarketEvent::OpenInterest(o) => format!("oi={}", o.open_interest),
+        exchange::MarketEvent::Ticker24h(t) => format!("last={} change={}%", t.last_price, t.price_change_percent),
+        exchange::MarketEvent::Nbbo(n) => format!(
+            "bid={}@{}/ask={}@{} spread={}",
+            n.best_bid, n.best_bid_exchange, n.best_ask, n.best_ask_exchange, n.spread
+        ),
+        exchange::MarketEvent::Opportunity(o) => format!(
+            "buy {}@{}/sell {}@{} {:.1}bps",
+            o.ask_price, o.buy_exchange, o.bid_price, o.sell_exchange, o.spread_bps
+        ),
+    }
+}
 
-        // Klines for each interval
-        for interval in &intervals {
-            subscriptions.push(Subscription {
-                symbol: symbol.clone(),
-                data_type: DataType::Kline,
-                interval: Some(*interval),
-            });
+/// One-line summary of an order or account update
+#[cfg(feature = "binance")]
+fn describe_user_data(event: &user_data::UserDataEvent) -> String {
+    match event {
+        user_data::UserDataEvent::OrderUpdate(o) => format!(
+            "{} order {} {:?} {} {}/{} filled @ {}",
+            o.symbol, o.order_id, o.side, o.status, o.filled_quantity, o.quantity, o.average_price
+        ),
+        user_data::UserDataEvent::AccountUpdate(a) => format!(
+            "account update ({}): {} balances, {} positions",
+            a.reason, a.balances.len(), a.positions.len()
+        ),
+    }
+}
+
+/// Parse an exchange name from the command line
+fn parse_exchange_type(name: &str) -> Result<ExchangeType> {
+    match name.trim().to_lowercase().as_str() {
+        "binance" => Ok(ExchangeType::Binance),
+        "okx" => Ok(ExchangeType::Okx),
+        "bybit" => Ok(ExchangeType::Bybit),
+        "coinbase" => Ok(ExchangeType::Coinbase),
+        "kucoin" => Ok(ExchangeType::Kucoin),
+        "deribit" => Ok(ExchangeType::Deribit),
+        "gateio" => Ok(ExchangeType::Gateio),
+        "bitget" => Ok(ExchangeType::Bitget),
+        _ => anyhow::bail!("Unknown exchange: {}", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exchange::{AggTrade, MarketEvent};
+    use flash_arb_gateway::testing::{self, MockExchange, MockRedisConnection};
+    use redis_publisher::RedisConfig;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    /// Buffer shared between the sink and the test
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap().lines().map(String::from).collect()
         }
+    }
 
-        // Book ticker
-        subscriptions.push(Subscription {
-            symbol: symbol.clone(),
-            data_type: DataType::BookTicker,
-            interval: None,
-        });
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trade(id: u64) -> MarketEvent {
+        MarketEvent::AggTrade(AggTrade {
+            price: 50000.0 + id as f64,
+            timestamp: 1_700_000_000_000,
+            ..testing::trade(ExchangeType::Binance, id)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_needs_no_redis() {
+        let mut config = GatewayConfig {
+            // Nothing listens here, so any attempt to reach Redis would fail
+            redis_url: "redis://127.0.0.1:1".to_string(),
+            metrics_port: 0,
+            ..GatewayConfig::default()
+        };
+        Args::parse_from(["gateway", "--no-redis"]).apply(&mut config).unwrap();
+        assert_eq!(config.outputs, vec![OutputBackend::Stdout]);
+
+        let (redis_publisher, sinks) = build_outputs(&config).await.unwrap();
+        assert!(redis_publisher.is_none());
+        assert_eq!(sinks.len(), 1);
+
+        // Swap the stdout sink for one the test can read back
+        let buffer = SharedBuffer::default();
+        let sinks = FanoutSink::new().with_sink(StdoutSink::with_writer(buffer.clone()));
+        let exchange = MockExchange::with_events((1..=3).map(trade).collect());
+        let exchanges: HashMap<ExchangeType, Box<dyn Exchange>> =
+            HashMap::from([(ExchangeType::Binance, Box::new(exchange) as Box<dyn Exchange>)]);
+
+        let printed = buffer.clone();
+        let shutdown = async move {
+            while printed.lines().len() < 3 {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+            "test"
+        };
+        time::timeout(
+            Duration::from_secs(10),
+            run_gateway(config, exchanges, None, sinks, HealthState::new(), shutdown),
+        )
+        .await
+        .expect("gateway did not stop")
+        .unwrap();
+
+        let events: Vec<MarketEvent> = buffer.lines().iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(events, vec![trade(1), trade(2), trade(3)]);
+    }
+
+    #[tokio::test]
+    async fn test_max_events_stops_the_gateway() {
+        let mut config = GatewayConfig { metrics_port: 0, ..GatewayConfig::default() };
+        Args::parse_from(["gateway", "--no-redis", "--max-events", "5"]).apply(&mut config).unwrap();
+        assert_eq!(config.max_events, Some(5));
+
+        let buffer = SharedBuffer::default();
+        let sinks = FanoutSink::new().with_sink(StdoutSink::with_writer(buffer.clone()));
+        let exchange = MockExchange::with_events((1..=8).map(trade).collect());
+        let exchanges: HashMap<ExchangeType, Box<dyn Exchange>> =
+            HashMap::from([(ExchangeType::Binance, Box::new(exchange) as Box<dyn Exchange>)]);
+
+        // No shutdown signal ever arrives; the event limit alone ends the run
+        time::timeout(
+            Duration::from_secs(10),
+            run_gateway(config, exchanges, None, sinks, HealthState::new(), std::future::pending()),
+        )
+        .await
+        .expect("gateway did not stop")
+        .unwrap();
 
-        // Depth
-        subscriptions.push(Subscription {
-            symbol: symbol.clone(),
-            data_type: DataType::Depth,
-            interval: None,
+        assert_eq!(buffer.lines().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_max_events_caps_what_exchanges_publish_to_redis() {
+        let mut config = GatewayConfig { metrics_port: 0, ..GatewayConfig::default() };
+        Args::parse_from(["gateway", "--no-redis", "--max-events", "5"]).apply(&mut config).unwrap();
+
+        // The exchange publishes each event itself, before the gateway loop sees it
+        let redis = MockRedisConnection::default();
+        let publisher = redis.publisher(RedisConfig { max_events: config.max_events, ..RedisConfig::default() }).await.unwrap();
+        let exchange = MockExchange::with_events((1..=8).map(trade).collect()).with_redis_publisher(publisher);
+        let exchanges: HashMap<ExchangeType, Box<dyn Exchange>> =
+            HashMap::from([(ExchangeType::Binance, Box::new(exchange) as Box<dyn Exchange>)]);
+
+        time::timeout(
+            Duration::from_secs(10),
+            run_gateway(config, exchanges, None, FanoutSink::new(), HealthState::new(), std::future::pending()),
+        )
+        .await
+        .expect("gateway did not stop")
+        .unwrap();
+
+        assert_eq!(redis.commands(), 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_exchanges_publish_to_redis_and_the_loop_feeds_the_sinks() {
+        let mut config = GatewayConfig { metrics_port: 0, ..GatewayConfig::default() };
+        Args::parse_from(["gateway", "--no-redis", "--max-events", "5"]).apply(&mut config).unwrap();
+
+        let okx_trade = |id| {
+            let mut event = trade(id);
+            if let MarketEvent::AggTrade(ref mut trade) = event {
+                trade.exchange = ExchangeType::Okx;
+            }
+            event
+        };
+        let expected = HashMap::from([
+            (ExchangeType::Binance, vec![trade(1), trade(2), trade(3)]),
+            (ExchangeType::Okx, vec![okx_trade(1), okx_trade(2)]),
+        ]);
+
+        // Wired like build_exchanges: every exchange publishes to Redis itself, the loop feeds the sinks
+        let redis = MockRedisConnection::default();
+        let publisher = redis.publisher(RedisConfig::default()).await.unwrap();
+        // Binance drops its connection mid-script; the event after the drop arrives over the reconnect
+        let binance = MockExchange::new(ExchangeType::Binance)
+            .with_event(trade(1))
+            .with_event(trade(2))
+            .with_disconnect()
+            .with_event(trade(3))
+            .with_redis_publisher(publisher.clone());
+        let okx = MockExchange::new(ExchangeType::Okx)
+            .with_event(okx_trade(1))
+            .with_control_frame()
+            .with_event(okx_trade(2))
+            .with_redis_publisher(publisher);
+        let binance_calls = binance.calls();
+        let exchanges: HashMap<ExchangeType, Box<dyn Exchange>> = HashMap::from([
+            (ExchangeType::Binance, Box::new(binance) as Box<dyn Exchange>),
+            (ExchangeType::Okx, Box::new(okx) as Box<dyn Exchange>),
+        ]);
+        let buffer = SharedBuffer::default();
+        let sinks = FanoutSink::new().with_sink(StdoutSink::with_writer(buffer.clone()));
+
+        time::timeout(
+            Duration::from_secs(60),
+            run_gateway(config, exchanges, None, sinks, HealthState::new(), std::future::pending()),
+        )
+        .await
+        .expect("gateway did not stop")
+        .unwrap();
+        assert_eq!((binance_calls.counts().connect, binance_calls.counts().resubscribe), (2, 1));
+
+        // The exchanges interleave, but each one's events keep their order on stdout and in Redis
+        let printed: Vec<MarketEvent> = buffer.lines().iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(printed.len(), 5);
+        let pipelines = redis.pipelines();
+        assert_eq!(pipelines.len(), 5);
+        let contains = |haystack: &[u8], needle: &[u8]| haystack.windows(needle.len()).any(|window| window == needle);
+        for (exchange, expected) in &expected {
+            let received: Vec<_> = printed.iter().filter(|event| event.exchange() == *exchange).cloned().collect();
+            assert_eq!(&received, expected, "{} events out of order on stdout", exchange);
+
+            let published: Vec<usize> = expected
+                .iter()
+                .map(|event| {
+                    let json = serde_json::to_vec(event).unwrap();
+                    pipelines.iter().position(|pipeline| contains(pipeline, &json)).expect("event not published")
+                })
+                .collect();
+            assert!(published.windows(2).all(|pair| pair[0] < pair[1]), "{} events out of order in Redis", exchange);
+            assert!(published.iter().all(|&i| contains(&pipelines[i], b"flash_arb:tick")));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_flushes_held_events_and_recording() {
+        let record_dir = std::env::temp_dir().join(format!("flash-arb-shutdown-{}", uuid::Uuid::new_v4()));
+        let mut config = GatewayConfig { metrics_port: 0, record_dir: Some(record_dir.clone()), ..GatewayConfig::default() };
+        Args::parse_from(["gateway", "--no-redis", "--max-events", "4"]).apply(&mut config).unwrap();
+
+        // Nothing runs the publisher's background tasks, so only the shutdown sends these
+        let redis = MockRedisConnection::default();
+        let publisher = redis.publisher(RedisConfig {
+            batch_size: 100,
+            queue_size: 10,
+            depth_coalesce_ms: 60_000,
+            trade_aggregation_ms: 60_000,
+            ..RedisConfig::default()
+        })
+        .await
+        .unwrap();
+        let depth = MarketEvent::DepthUpdate(exchange::DepthUpdate {
+            exchange: ExchangeType::Binance,
+            symbol: "BTCUSDT".to_string(),
+            bids: vec![(50000.0, 1.0)],
+            asks: vec![],
+            timestamp: 1_700_000_000_000,
+            is_snapshot: false,
+            first_update_id: Some(1),
+            final_update_id: Some(1),
+            prev_final_update_id: None,
+            received_at: 0,
         });
+        let mut okx_trade = trade(3);
+        if let MarketEvent::AggTrade(ref mut trade) = okx_trade {
+            trade.exchange = ExchangeType::Okx;
+        }
+        let exchange = MockExchange::with_events(vec![trade(1), trade(2), depth, okx_trade]);
+
+        let sinks = FanoutSink::new().with_sink(publisher);
+        let exchanges: HashMap<ExchangeType, Box<dyn Exchange>> =
+            HashMap::from([(ExchangeType::Binance, Box::new(exchange) as Box<dyn Exchange>)]);
+        time::timeout(
+            Duration::from_secs(10),
+            run_gateway(config, exchanges, None, sinks, HealthState::new(), std::future::pending()),
+        )
+        .await
+        .expect("gateway did not stop")
+        .unwrap();
+
+        // Queued trades, the coalesced depth update and the aggregated trade all went out
+        assert_eq!(redis.commands(), 4);
+        let recorded: usize = std::fs::read_dir(&record_dir)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap().lines().count())
+            .sum();
+        assert_eq!(recorded, 4);
+        std::fs::remove_dir_all(&record_dir).unwrap();
+    }
+
+    #[test]
+    fn test_symbols_subcommand() {
+        let args = Args::parse_from(["gateway", "--exchanges", "okx", "symbols", "--contains", "btc"]);
+        assert_eq!(args.command, Some(Command::Symbols { contains: Some("btc".to_string()) }));
+        assert_eq!(args.exchanges, vec!["okx"]);
+
+        assert_eq!(Args::parse_from(["gateway"]).command, None);
     }
 
-    subscriptions
+    #[cfg(not(feature = "bybit"))]
+    #[test]
+    fn test_exchange_left_out_of_the_build_is_rejected() {
+        let config = GatewayConfig { exchanges: vec![ExchangeType::Bybit], ..GatewayConfig::default() };
+        let Err(e) = build_exchanges(&config, None) else {
+            panic!("Expected bybit to be rejected");
+        };
+        assert!(e.to_string().contains("--features bybit"));
+    }
 }
@@ -0,0 +1,379 @@
+//! Exchange orchestration
+//!
+//! This module owns the configured exchange clients and brings them up as a
+//! group, connecting and subscribing every one, before handing each to its
+//! own task that forwards events and reconnects with backoff. Once started,
+//! the manager receives from every task through `next_event` and can force
+//! a fresh connection on exchanges that went quiet. Each connection logs
+//! inside its own session span, handed on to the task along with the exchange.
+
+use crate::error::{GatewayError, Result};
+use crate::exchange::{Exchange, ExchangeType, MarketEvent, Subscription};
+use crate::health::HealthState;
+use crate::metrics;
+use crate::reconnect::ReconnectPolicy;
+use crate::runner;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch, Notify};
+use tokio::task::JoinHandle;
+use tracing::{info, warn, Instrument, Span};
+
+/// Default silence after which `reconnect_stale` drops a connection
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// The set of exchange clients a gateway streams from
+pub struct GatewayManager {
+    /// Exchanges not yet handed to their tasks
+    exchanges: HashMap<ExchangeType, Box<dyn Exchange>>,
+    policy: ReconnectPolicy,
+    health: HealthState,
+    /// Span of each exchange's current connection
+    sessions: HashMap<ExchangeType, Span>,
+    /// Silence after which a connection counts as stale
+    stale_after: Duration,
+    /// Wakes a spawned exchange's task to drop its connection and reconnect
+    reconnects: HashMap<ExchangeType, Arc<Notify>>,
+    /// Events from the tasks, once started
+    events: Option<mpsc::Receiver<MarketEvent>>,
+}
+
+impl GatewayManager {
+    /// Manage these exchanges, none of them connected yet
+    pub fn new(exchanges: HashMap<ExchangeType, Box<dyn Exchange>>) -> Self {
+        Self {
+            exchanges,
+            policy: ReconnectPolicy::default(),
+            health: HealthState::new(),
+            sessions: HashMap::new(),
+            stale_after: DEFAULT_STALE_AFTER,
+            reconnects: HashMap::new(),
+            events: None,
+        }
+    }
+
+    /// Back off reconnects with this policy
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Report connection changes and event arrivals to `health`
+    pub fn with_health(mut self, health: HealthState) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// Treat a connection as stale after this long without an event
+    pub fn with_stale_after(mut self, stale_after: Duration) -> Self {
+        self.stale_after = stale_after;
+        self
+    }
+
+    /// Connect every exchange once.
+    ///
+    /// Every exchange is tried even after one fails, so the error lists all
+    /// the failures in a stable order rather than whichever came up first.
+    pub async fn connect_all(&mut self) -> Result<()> {
+        let mut failures = Vec::new();
+        for (exchange_type, exchange) in self.exchanges.iter_mut() {
+            let session = runner::session_span(*exchange_type);
+            self.sessions.insert(*exchange_type, session.clone());
+            session.in_scope(|| info!("Connecting to {}...", exchange_type));
+            match exchange.connect().instrument(session).await {
+                Ok(()) => {
+                    metrics::global().set_connected(*exchange_type, true);
+                    self.health.set_connected(*exchange_type, true);
+                }
+                Err(e) => failures.push(format!("{}: {}", exchange_type, e)),
+            }
+        }
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+        failures.sort();
+        Err(GatewayError::Connect(format!("Failed to connect to {}", failures.join("; "))))
+    }
+
+    /// Subscribe each exchange to its entry in `subscriptions`.
+    ///
+    /// A failed subscribe is logged rather than returned so one exchange
+    /// refusing a stream doesn't stop the others.
+    pub async fn subscribe_all(&mut self, subscriptions: &HashMap<ExchangeType, Vec<Subscription>>) {
+        for (exchange_type, exchange) in self.exchanges.iter_mut() {
+            let Some(subs) = subscriptions.get(exchange_type) else {
+                continue;
+            };
+            let session = session(&mut self.sessions, *exchange_type);
+            async {
+                info!("Subscribing to {} data streams on {}", subs.len(), exchange_type);
+                if let Err(e) = exchange.subscribe(subs.clone()).await {
+                    warn!("Failed to subscribe to {}: {}", exchange_type, e);
+                }
+            }
+            .instrument(session)
+            .await;
+            self.health.set_subscriptions(*exchange_type, exchange.active_subscriptions());
+        }
+    }
+
+    /// Subscriptions each exchange currently has active
+    pub fn active_subscriptions(&self) -> HashMap<ExchangeType, Vec<Subscription>> {
+        self.exchanges
+            .iter()
+            .map(|(exchange_type, exchange)| (*exchange_type, exchange.active_subscriptions()))
+            .collect()
+    }
+
+    /// Move each exchange onto its own task forwarding events to `tx`.
+    ///
+    /// The tasks reconnect on their own and stop once `shutdown` is set.
+    pub fn spawn(&mut self, tx: mpsc::Sender<MarketEvent>, shutdown: watch::Receiver<bool>) -> Vec<JoinHandle<()>> {
+        let exchanges: Vec<_> = self.exchanges.drain().collect();
+        exchanges
+            .into_iter()
+            .map(|(exchange_type, exchange)| {
+                let session = session(&mut self.sessions, exchange_type);
+                let reconnect = self.reconnects.entry(exchange_type).or_default().clone();
+                runner::spawn_exchange_session(
+                    exchange, session, tx.clone(), self.policy.clone(), shutdown.clone(), reconnect, self.health.clone()
+                )
+            })
+            .collect()
+    }
+
+    /// Move each exchange onto its own task, their events read back through `next_event`
+    pub fn start(&mut self, shutdown: watch::Receiver<bool>) -> Vec<JoinHandle<()>> {
+        let (tx, rx) = mpsc::channel(runner::EVENT_CHANNEL_CAPACITY);
+        self.events = Some(rx);
+        self.spawn(tx, shutdown)
+    }
+
+    /// Receive the next event from whichever started exchange sends one first.
+    ///
+    /// Cancel-safe: an event is only taken off the channel when this returns
+    /// it, so it can sit in a `select!` next to timers. Returns `None` once
+    /// every task has stopped, or if the manager was never started.
+    pub async fn next_event(&mut self) -> Option<MarketEvent> {
+        self.events.as_mut()?.recv().await
+    }
+
+    /// Force a fresh connection on every spawned exchange silent for longer than the stale timeout.
+    ///
+    /// Returns the exchanges told to reconnect; their tasks drop the quiet
+    /// connection and reconnect with the usual backoff and resubscribe.
+    pub fn reconnect_stale(&self) -> Vec<ExchangeType> {
+        self.reconnects
+            .iter()
+            .filter(|(exchange_type, _)| {
+                self.health.silent_for(**exchange_type).is_some_and(|silence| silence > self.stale_after)
+            })
+            .map(|(exchange_type, reconnect)| {
+                reconnect.notify_one();
+                *exchange_type
+            })
+            .collect()
+    }
+}
+
+/// Span of an exchange's current connection, starting a session if it has none yet
+fn session(sessions: &mut HashMap<ExchangeType, Span>, exchange_type: ExchangeType) -> Span {
+    sessions.entry(exchange_type).or_insert_with(|| runner::session_span(exchange_type)).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::KlineInterval;
+    use crate::testing::{agg_trade, MockClock, MockExchange};
+    use tokio::time;
+
+    fn managed(exchanges: Vec<MockExchange>) -> GatewayManager {
+        let exchanges = exchanges
+            .into_iter()
+            .map(|exchange| (exchange.exchange_type(), Box::new(exchange) as Box<dyn Exchange>))
+            .collect();
+        GatewayManager::new(exchanges).with_reconnect_policy(
+            ReconnectPolicy::new(Duration::from_millis(100), Duration::from_secs(1)).with_jitter(0.0)
+        )
+    }
+
+    #[tokio::test]
+    async fn test_connect_subscribe_and_receive_from_two_exchanges() {
+        let binance = MockExchange::with_events(vec![agg_trade(ExchangeType::Binance, 1), agg_trade(ExchangeType::Binance, 2)]);
+        let okx = MockExchange::with_events(vec![agg_trade(ExchangeType::Okx, 1)]).with_control_frame();
+        let (binance_calls, okx_calls) = (binance.calls(), okx.calls());
+        let mut manager = managed(vec![binance, okx]);
+
+        manager.connect_all().await.unwrap();
+        manager.subscribe_all(&HashMap::from([
+            (ExchangeType::Binance, vec![Subscription::agg_trade("BTCUSDT")]),
+            (ExchangeType::Okx, vec![Subscription::agg_trade("BTCUSDT")]),
+        ])).await;
+        for calls in [&binance_calls, &okx_calls] {
+            assert_eq!((calls.counts().connect, calls.counts().subscribe), (1, 1));
+        }
+
+        let (tx, mut rx) = mpsc::channel(runner::EVENT_CHANNEL_CAPACITY);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handles = manager.spawn(tx, shutdown_rx);
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(time::timeout(Duration::from_secs(1), rx.recv()).await.unwrap().unwrap());
+        }
+        assert_eq!(received.iter().filter(|event| event.exchange() == ExchangeType::Binance).count(), 2);
+        assert!(received.contains(&agg_trade(ExchangeType::Okx, 1)));
+
+        // Both scripts are drained, so the exchanges go quiet
+        assert!(time::timeout(Duration::from_millis(50), rx.recv()).await.is_err());
+
+        shutdown_tx.send(true).unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_active_subscriptions_after_subscribing_three_streams() {
+        let health = HealthState::new();
+        let mut manager = managed(vec![MockExchange::new(ExchangeType::Binance)]).with_health(health.clone());
+        let subs = vec![
+            Subscription::agg_trade("BTCUSDT"),
+            Subscription::book_ticker("BTCUSDT"),
+            Subscription::kline("ETHUSDT", KlineInterval::OneMinute),
+        ];
+
+        manager.connect_all().await.unwrap();
+        manager.subscribe_all(&HashMap::from([(ExchangeType::Binance, subs.clone())])).await;
+
+        assert_eq!(manager.active_subscriptions(), HashMap::from([(ExchangeType::Binance, subs.clone())]));
+        assert_eq!(health.subscriptions(ExchangeType::Binance), subs);
+        assert_eq!(health.subscriptions_report()["total"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_connect_failure_names_the_exchange() {
+        let mut manager = managed(vec![MockExchange::new(ExchangeType::Bybit).with_failed_connects(1)]);
+        let err = manager.connect_all().await.unwrap_err();
+        assert!(err.to_string().contains("bybit"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_connect_all_connects_each_exchange_exactly_once() {
+        let exchanges: Vec<_> = [ExchangeType::Binance, ExchangeType::Okx, ExchangeType::Bybit, ExchangeType::Gateio]
+            .into_iter()
+            .map(MockExchange::new)
+            .collect();
+        let calls: Vec<_> = exchanges.iter().map(MockExchange::calls).collect();
+        let mut manager = managed(exchanges);
+
+        manager.connect_all().await.unwrap();
+        assert!(calls.iter().all(|calls| calls.counts().connect == 1));
+
+        // A failure doesn't stop the exchanges after it from being tried, and each is reported
+        let exchanges = vec![
+            MockExchange::new(ExchangeType::Okx).with_failed_connects(1),
+            MockExchange::new(ExchangeType::Binance),
+            MockExchange::new(ExchangeType::Deribit).with_failed_connects(1),
+        ];
+        let calls: Vec<_> = exchanges.iter().map(MockExchange::calls).collect();
+        let err = managed(exchanges).connect_all().await.unwrap_err().to_string();
+        assert!(calls.iter().all(|calls| calls.counts().connect == 1));
+        assert!(err.contains("deribit: ") && err.contains("okx: ") && !err.contains("binance"), "{}", err);
+        assert!(err.find("deribit").unwrap() < err.find("okx").unwrap(), "{}", err);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spawned_exchange_reconnects_and_resubscribes_after_a_drop() {
+        let dropping = MockExchange::new(ExchangeType::Okx)
+            .with_disconnect()
+            .with_event(agg_trade(ExchangeType::Okx, 7));
+        let calls = dropping.calls();
+        let mut manager = managed(vec![dropping]);
+        manager.connect_all().await.unwrap();
+
+        let (tx, mut rx) = mpsc::channel(runner::EVENT_CHANNEL_CAPACITY);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handles = manager.spawn(tx, shutdown_rx);
+
+        // The task reconnects on its own and the event after the drop comes through
+        assert_eq!(rx.recv().await, Some(agg_trade(ExchangeType::Okx, 7)));
+        assert_eq!((calls.counts().connect, calls.counts().resubscribe), (2, 1));
+
+        shutdown_tx.send(true).unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_next_event_reads_every_started_exchange() {
+        let binance = MockExchange::with_events(vec![agg_trade(ExchangeType::Binance, 1), agg_trade(ExchangeType::Binance, 2)]);
+        let okx = MockExchange::with_events(vec![agg_trade(ExchangeType::Okx, 1)]);
+        let mut manager = managed(vec![binance, okx]);
+        assert_eq!(manager.next_event().await, None);
+
+        manager.connect_all().await.unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handles = manager.start(shutdown_rx);
+
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            // Timing out between events drops nothing, as receiving is cancel-safe
+            if let Ok(event) = time::timeout(Duration::from_millis(1), manager.next_event()).await {
+                received.push(event.unwrap());
+            }
+        }
+        assert_eq!(received.iter().filter(|event| event.exchange() == ExchangeType::Binance).count(), 2);
+        assert!(received.contains(&agg_trade(ExchangeType::Okx, 1)));
+
+        shutdown_tx.send(true).unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(manager.next_event().await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reconnect_stale_reconnects_only_quiet_exchanges() {
+        let clock = MockClock::new(1_700_000_000_000);
+        let health = HealthState::new().with_clock(Arc::new(clock.clone()));
+        let quiet = MockExchange::with_events(vec![agg_trade(ExchangeType::Okx, 1)]);
+        let busy = MockExchange::with_events(vec![agg_trade(ExchangeType::Binance, 1)]);
+        let (quiet_calls, busy_calls) = (quiet.calls(), busy.calls());
+        let mut manager = managed(vec![quiet, busy]).with_health(health.clone()).with_stale_after(Duration::from_secs(30));
+        assert!(manager.reconnect_stale().is_empty());
+
+        manager.connect_all().await.unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handles = manager.start(shutdown_rx);
+        for _ in 0..2 {
+            manager.next_event().await.unwrap();
+        }
+
+        clock.advance(Duration::from_secs(20));
+        assert!(manager.reconnect_stale().is_empty());
+        health.record_event(ExchangeType::Binance);
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(manager.reconnect_stale(), vec![ExchangeType::Okx]);
+
+        // OKX's task drops the quiet connection and opens a new one, resubscribed
+        time::timeout(Duration::from_secs(5), async {
+            while quiet_calls.counts().resubscribe == 0 {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("stale exchange was not reconnected");
+        assert!(quiet_calls.counts().disconnect >= 1);
+        assert_eq!((busy_calls.counts().connect, busy_calls.counts().disconnect), (1, 0));
+
+        shutdown_tx.send(true).unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    }
+}
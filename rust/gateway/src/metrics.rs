@@ -0,0 +1,508 @@
+//! Prometheus metrics
+//!
+//! This module keeps per-exchange counters for throughput and errors and
+//! serves them over HTTP in the Prometheus text exposition format. The same
+//! counters can be taken as a serializable snapshot for publishing elsewhere.
+
+use crate::exchange::{DataType, ExchangeType};
+use crate::latency::LatencyStats;
+use crate::error::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+/// Default port for the `/metrics` endpoint
+pub const DEFAULT_METRICS_PORT: u16 = 9100;
+
+/// How often the gateway publishes a metrics snapshot to Redis
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Prometheus text format content type
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Counters for a single exchange
+#[derive(Debug, Default)]
+struct ExchangeMetrics {
+    events_received: AtomicU64,
+    parse_errors: AtomicU64,
+    reconnects: AtomicU64,
+    redis_publish_failures: AtomicU64,
+    trade_gaps: AtomicU64,
+    checksum_mismatches: AtomicU64,
+    events_skipped: AtomicU64,
+    events_dropped: AtomicU64,
+    events_late: AtomicU64,
+    connected: AtomicBool,
+}
+
+/// Name, type, help text and value of an exported metric family
+type MetricFamily = (&'static str, &'static str, &'static str, fn(&ExchangeMetrics) -> u64);
+
+const FAMILIES: [MetricFamily; 10] = [
+    ("flash_arb_events_received_total", "counter", "Market events received from the exchange",
+        |m| m.events_received.load(Ordering::Relaxed)),
+    ("flash_arb_parse_errors_total", "counter", "Exchange messages that failed to parse",
+        |m| m.parse_errors.load(Ordering::Relaxed)),
+    ("flash_arb_reconnects_total", "counter", "Successful reconnects to the exchange",
+        |m| m.reconnects.load(Ordering::Relaxed)),
+    ("flash_arb_redis_publish_failures_total", "counter", "Events that failed to publish to Redis",
+        |m| m.redis_publish_failures.load(Ordering::Relaxed)),
+    ("flash_arb_trade_gaps_total", "counter", "Trade id sequence gaps, each meaning missed trades",
+        |m| m.trade_gaps.load(Ordering::Relaxed)),
+    ("flash_arb_checksum_mismatches_total", "counter", "Local order books that failed the exchange's checksum and were resynced",
+        |m| m.checksum_mismatches.load(Ordering::Relaxed)),
+    ("flash_arb_events_skipped_total", "counter", "Events dropped by the event filter instead of published",
+        |m| m.events_skipped.load(Ordering::Relaxed)),
+    ("flash_arb_events_dropped_total", "counter", "Events dropped because the publish queue was full",
+        |m| m.events_dropped.load(Ordering::Relaxed)),
+    ("flash_arb_events_late_total", "counter", "Events that arrived behind the reorder horizon and were published out of order",
+        |m| m.events_late.load(Ordering::Relaxed)),
+    ("flash_arb_exchange_connected", "gauge", "Whether the exchange connection is up (1) or down (0)",
+        |m| m.connected.load(Ordering::Relaxed) as u64),
+];
+
+/// Counters for one exchange in a `GatewayMetrics` snapshot
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExchangeSnapshot {
+    pub events_received: u64,
+    pub parse_errors: u64,
+    pub reconnects: u64,
+    pub redis_publish_failures: u64,
+    pub trade_gaps: u64,
+    pub checksum_mismatches: u64,
+    pub events_skipped: u64,
+    pub events_dropped: u64,
+    pub events_late: u64,
+    pub connected: bool,
+}
+
+impl From<&ExchangeMetrics> for ExchangeSnapshot {
+    fn from(m: &ExchangeMetrics) -> Self {
+        Self {
+            events_received: m.events_received.load(Ordering::Relaxed),
+            parse_errors: m.parse_errors.load(Ordering::Relaxed),
+            reconnects: m.reconnects.load(Ordering::Relaxed),
+            redis_publish_failures: m.redis_publish_failures.load(Ordering::Relaxed),
+            trade_gaps: m.trade_gaps.load(Ordering::Relaxed),
+            checksum_mismatches: m.checksum_mismatches.load(Ordering::Relaxed),
+            events_skipped: m.events_skipped.load(Ordering::Relaxed),
+            events_dropped: m.events_dropped.load(Ordering::Relaxed),
+            events_late: m.events_late.load(Ordering::Relaxed),
+            connected: m.connected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time copy of the gateway's counters, keyed by name so it serializes stably
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GatewayMetrics {
+    pub uptime_secs: u64,
+    pub events_received: u64,
+    pub parse_errors: u64,
+    pub reconnects: u64,
+    pub redis_publish_failures: u64,
+    /// Events received per data type (`aggTrade`, `kline`, ...)
+    pub events_by_type: BTreeMap<String, u64>,
+    /// Counters per exchange
+    pub exchanges: BTreeMap<String, ExchangeSnapshot>,
+    /// Last measured exchange clock offset from the local clock, per exchange
+    pub clock_offsets_ms: BTreeMap<String, i64>,
+    /// Events waiting in the publish queue
+    pub queue_depth: u64,
+    /// Most events the publish queue has held at once
+    pub queue_high_watermark: u64,
+}
+
+/// Per-exchange metrics registry
+#[derive(Debug)]
+pub struct Metrics {
+    exchanges: RwLock<HashMap<ExchangeType, Arc<ExchangeMetrics>>>,
+    /// Events received per data type, across exchanges
+    event_types: Mutex<HashMap<DataType, u64>>,
+    /// Latest latency percentiles, refreshed by the gateway's periodic report
+    latency: RwLock<HashMap<(ExchangeType, DataType), LatencyStats>>,
+    /// Latest exchange clock offsets in ms, refreshed by the time sync check
+    clock_offsets: RwLock<HashMap<ExchangeType, i64>>,
+    /// Events currently waiting in the publish queue
+    queue_depth: AtomicU64,
+    /// Most events the publish queue has held at once
+    queue_high_watermark: AtomicU64,
+    started: Instant,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            exchanges: RwLock::default(),
+            event_types: Mutex::default(),
+            latency: RwLock::default(),
+            clock_offsets: RwLock::default(),
+            queue_depth: AtomicU64::new(0),
+            queue_high_watermark: AtomicU64::new(0),
+            started: Instant::now(),
+        }
+    }
+}
+
+/// The process-wide registry the exchanges and publisher report to
+pub fn global() -> &'static Metrics {
+    static GLOBAL: OnceLock<Metrics> = OnceLock::new();
+    GLOBAL.get_or_init(Metrics::new)
+}
+
+impl Metrics {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counters for an exchange, created on first use
+    fn exchange(&self, exchange: ExchangeType) -> Arc<ExchangeMetrics> {
+        if let Some(metrics) = self.exchanges.read().unwrap().get(&exchange) {
+            return metrics.clone();
+        }
+        self.exchanges.write().unwrap().entry(exchange).or_default().clone()
+    }
+
+    /// Count an event received from an exchange
+    pub fn record_event(&self, exchange: ExchangeType) {
+        self.exchange(exchange).events_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count an event of a data type, for the snapshot's per-type totals
+    pub fn record_event_type(&self, data_type: DataType) {
+        *self.event_types.lock().unwrap().entry(data_type).or_insert(0) += 1;
+    }
+
+    /// Count a message that could not be parsed
+    pub fn record_parse_error(&self, exchange: ExchangeType) {
+        self.exchange(exchange).parse_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a successful reconnect
+    pub fn record_reconnect(&self, exchange: ExchangeType) {
+        self.exchange(exchange).reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count an event that could not be published to Redis
+    pub fn record_publish_failure(&self, exchange: ExchangeType) {
+        self.exchange(exchange).redis_publish_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a gap in an exchange's trade id sequence
+    pub fn record_trade_gap(&self, exchange: ExchangeType) {
+        self.exchange(exchange).trade_gaps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a local order book that failed its checksum
+    pub fn record_checksum_mismatch(&self, exchange: ExchangeType) {
+        self.exchange(exchange).checksum_mismatches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count an event the event filter kept from being published
+    pub fn record_skipped(&self, exchange: ExchangeType) {
+        self.exchange(exchange).events_skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count an event dropped because the publish queue was full
+    pub fn record_dropped(&self, exchange: ExchangeType) {
+        self.exchange(exchange).events_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count an event published out of order because it reached the reorder buffer late
+    pub fn record_late(&self, exchange: ExchangeType) {
+        self.exchange(exchange).events_late.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record whether an exchange is currently connected
+    pub fn set_connected(&self, exchange: ExchangeType, connected: bool) {
+        self.exchange(exchange).connected.store(connected, Ordering::Relaxed);
+    }
+
+    /// Replace the latency percentiles served on `/metrics`
+    pub fn set_latency_stats(&self, stats: HashMap<(ExchangeType, DataType), LatencyStats>) {
+        *self.latency.write().unwrap() = stats;
+    }
+
+    /// Record how far an exchange's clock is ahead of the local one
+    pub fn set_clock_offset(&self, exchange: ExchangeType, offset_ms: i64) {
+        self.clock_offsets.write().unwrap().insert(exchange, offset_ms);
+    }
+
+    /// Last measured clock offset of one exchange, if it was checked
+    pub fn clock_offset(&self, exchange: ExchangeType) -> Option<i64> {
+        self.clock_offsets.read().unwrap().get(&exchange).copied()
+    }
+
+    /// Last measured clock offset of every checked exchange
+    pub fn clock_offsets(&self) -> HashMap<ExchangeType, i64> {
+        self.clock_offsets.read().unwrap().clone()
+    }
+
+    /// Record the publish queue's depth, raising its high-watermark if exceeded
+    pub fn set_queue_depth(&self, depth: usize) {
+        self.queue_depth.store(depth as u64, Ordering::Relaxed);
+        self.queue_high_watermark.fetch_max(depth as u64, Ordering::Relaxed);
+    }
+
+    /// Copy the current counters into a serializable snapshot
+    pub fn snapshot(&self) -> GatewayMetrics {
+        let exchanges: BTreeMap<String, ExchangeSnapshot> = self.exchanges
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(exchange, metrics)| (exchange.to_string(), ExchangeSnapshot::from(metrics.as_ref())))
+            .collect();
+        let events_by_type = self.event_types
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(data_type, count)| (data_type.as_str().to_string(), *count))
+            .collect();
+        let total = |field: fn(&ExchangeSnapshot) -> u64| exchanges.values().map(field).sum();
+
+        GatewayMetrics {
+            uptime_secs: self.started.elapsed().as_secs(),
+            events_received: total(|e| e.events_received),
+            parse_errors: total(|e| e.parse_errors),
+            reconnects: total(|e| e.reconnects),
+            redis_publish_failures: total(|e| e.redis_publish_failures),
+            events_by_type,
+            exchanges,
+            clock_offsets_ms: self.clock_offsets()
+                .into_iter()
+                .map(|(exchange, offset)| (exchange.to_string(), offset))
+                .collect(),
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            queue_high_watermark: self.queue_high_watermark.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut exchanges: Vec<_> = self.exchanges
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(exchange, metrics)| (exchange.to_string(), metrics.clone()))
+            .collect();
+        exchanges.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut out = String::new();
+        for (name, kind, help, value) in FAMILIES {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (exchange, metrics) in &exchanges {
+                let _ = writeln!(out, "{}{{exchange=\"{}\"}} {}", name, exchange, value(metrics));
+            }
+        }
+        self.render_latency(&mut out);
+        self.render_clock_offsets(&mut out);
+        self.render_queue(&mut out);
+        out
+    }
+
+    fn render_queue(&self, out: &mut String) {
+        for (name, help, value) in [
+            ("flash_arb_publish_queue_depth", "Events waiting in the publish queue", &self.queue_depth),
+            ("flash_arb_publish_queue_high_watermark", "Most events the publish queue has held at once", &self.queue_high_watermark),
+        ] {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+    }
+
+    fn render_clock_offsets(&self, out: &mut String) {
+        let mut offsets: Vec<_> = self.clock_offsets()
+            .into_iter()
+            .map(|(exchange, offset)| (exchange.to_string(), offset))
+            .collect();
+        offsets.sort();
+
+        let name = "flash_arb_clock_offset_ms";
+        let _ = writeln!(out, "# HELP {} How far the exchange clock is ahead of the local clock", name);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (exchange, offset) in offsets {
+            let _ = writeln!(out, "{}{{exchange=\"{}\"}} {}", name, exchange, offset);
+        }
+    }
+
+    fn render_latency(&self, out: &mut String) {
+        let mut latency: Vec<_> = self.latency
+            .read()
+            .unwrap()
+            .iter()
+            .map(|((exchange, data_type), stats)| (exchange.to_string(), data_type.as_str(), *stats))
+            .collect();
+        latency.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+        let name = "flash_arb_event_latency_ms";
+        let _ = writeln!(out, "# HELP {} Exchange-to-gateway latency percentiles over recent events", name);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (exchange, data_type, stats) in &latency {
+            for (quantile, value) in [("0.5", stats.p50), ("0.95", stats.p95), ("0.99", stats.p99)] {
+                let _ = writeln!(
+                    out,
+                    "{}{{exchange=\"{}\",type=\"{}\",quantile=\"{}\"}} {}",
+                    name, exchange, data_type, quantile, value
+                );
+            }
+        }
+
+        let name = "flash_arb_event_latency_skewed_total";
+        let _ = writeln!(out, "# HELP {} Events with negative latency from clock skew, counted as zero", name);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        for (exchange, data_type, stats) in &latency {
+            let _ = writeln!(out, "{}{{exchange=\"{}\",type=\"{}\"}} {}", name, exchange, data_type, stats.clamped);
+        }
+    }
+}
+
+/// Answer `/metrics` scrapes; everything else is a 404
+async fn handle(metrics: &'static Metrics, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header("Content-Type", CONTENT_TYPE)
+            .body(Body::from(metrics.render())),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+    Ok(response.expect("static response parts are valid"))
+}
+
+/// Serve `metrics` on an already-bound listener
+pub fn spawn_server(metrics: &'static Metrics, listener: TcpListener) -> Result<JoinHandle<()>> {
+    listener.set_nonblocking(true)?;
+    let server = Server::from_tcp(listener)?.serve(make_service_fn(move |_| async move {
+        Ok::<_, Infallible>(service_fn(move |req| handle(metrics, req)))
+    }));
+    info!("Serving metrics on http://{}/metrics", server.local_addr());
+
+    Ok(tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("Metrics server failed: {}", e);
+        }
+    }))
+}
+
+/// Serve the global registry on all interfaces at `port`
+pub fn serve(port: u16) -> Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port)))?;
+    spawn_server(global(), listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_format() {
+        let metrics = Metrics::new();
+        metrics.record_event(ExchangeType::Okx);
+        metrics.record_event(ExchangeType::Binance);
+        metrics.record_event(ExchangeType::Binance);
+        metrics.set_connected(ExchangeType::Binance, true);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE flash_arb_events_received_total counter\n"));
+        assert!(text.contains("# TYPE flash_arb_exchange_connected gauge\n"));
+        assert!(text.contains("flash_arb_events_received_total{exchange=\"binance\"} 2\n"));
+        assert!(text.contains("flash_arb_exchange_connected{exchange=\"binance\"} 1\n"));
+        assert!(text.contains("flash_arb_exchange_connected{exchange=\"okx\"} 0\n"));
+
+        // Exchanges are listed in a stable order
+        let binance = text.find("flash_arb_parse_errors_total{exchange=\"binance\"}").unwrap();
+        let okx = text.find("flash_arb_parse_errors_total{exchange=\"okx\"}").unwrap();
+        assert!(binance < okx);
+    }
+
+    #[test]
+    fn test_snapshot_counts() {
+        let metrics = Metrics::new();
+        for (exchange, data_type) in [
+            (ExchangeType::Binance, DataType::AggTrade),
+            (ExchangeType::Binance, DataType::AggTrade),
+            (ExchangeType::Binance, DataType::Depth),
+            (ExchangeType::Okx, DataType::AggTrade),
+        ] {
+            metrics.record_event(exchange);
+            metrics.record_event_type(data_type);
+        }
+        metrics.record_parse_error(ExchangeType::Okx);
+        metrics.record_reconnect(ExchangeType::Binance);
+        metrics.record_publish_failure(ExchangeType::Binance);
+        metrics.set_connected(ExchangeType::Binance, true);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.events_received, 4);
+        assert_eq!((snapshot.parse_errors, snapshot.reconnects, snapshot.redis_publish_failures), (1, 1, 1));
+        assert_eq!(snapshot.events_by_type, BTreeMap::from([("aggTrade".to_string(), 3), ("depth".to_string(), 1)]));
+        assert_eq!(snapshot.exchanges["binance"], ExchangeSnapshot {
+            events_received: 3,
+            reconnects: 1,
+            redis_publish_failures: 1,
+            connected: true,
+            ..ExchangeSnapshot::default()
+        });
+        assert_eq!(snapshot.exchanges["okx"].parse_errors, 1);
+
+        let json: serde_json::Value = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["exchanges"]["okx"]["events_received"], 1);
+        assert_eq!(json["events_by_type"]["aggTrade"], 3);
+    }
+
+    #[test]
+    fn test_render_latency() {
+        let metrics = Metrics::new();
+        metrics.set_latency_stats(HashMap::from([(
+            (ExchangeType::Okx, DataType::Kline),
+            LatencyStats { samples: 10, p50: 12, p95: 40, p99: 85, clamped: 2 },
+        )]));
+
+        let text = metrics.render();
+        assert!(text.contains("flash_arb_event_latency_ms{exchange=\"okx\",type=\"kline\",quantile=\"0.95\"} 40\n"));
+        assert!(text.contains("flash_arb_event_latency_skewed_total{exchange=\"okx\",type=\"kline\"} 2\n"));
+    }
+
+    #[tokio::test]
+    async fn test_scrape_endpoint() {
+        let metrics: &'static Metrics = Box::leak(Box::new(Metrics::new()));
+        metrics.record_event(ExchangeType::Bybit);
+        metrics.record_parse_error(ExchangeType::Bybit);
+        metrics.record_reconnect(ExchangeType::Bybit);
+        metrics.record_publish_failure(ExchangeType::Bybit);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = spawn_server(metrics, listener).unwrap();
+
+        let response = reqwest::get(format!("http://{}/metrics", addr)).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let body = response.text().await.unwrap();
+        for name in [
+            "flash_arb_events_received_total{exchange=\"bybit\"} 1",
+            "flash_arb_parse_errors_total{exchange=\"bybit\"} 1",
+            "flash_arb_reconnects_total{exchange=\"bybit\"} 1",
+            "flash_arb_redis_publish_failures_total{exchange=\"bybit\"} 1",
+            "flash_arb_exchange_connected{exchange=\"bybit\"} 0",
+        ] {
+            assert!(body.contains(name), "missing {} in:\n{}", name, body);
+        }
+
+        let missing = reqwest::get(format!("http://{}/other", addr)).await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+        handle.abort();
+    }
+}
@@ -0,0 +1,188 @@
+//! Consolidated best bid and offer
+//!
+//! This module folds book tickers from every connected exchange into one
+//! top of book per symbol: the highest bid, the lowest ask and the
+//! exchanges quoting them. A negative spread means the market is crossed,
+//! i.e. one exchange bids above another's ask, which is the spread an
+//! arbitrage can take.
+//!
+//! Quotes older than a maximum age are left out, so an exchange that
+//! disconnects or goes quiet can't hold the top with a frozen price.
+
+use crate::exchange::{BookTicker, ExchangeType, Nbbo};
+use std::collections::HashMap;
+
+/// Default age in ms past which an exchange's quote no longer counts towards the top
+pub const DEFAULT_MAX_QUOTE_AGE_MS: u64 = 5_000;
+
+/// Latest book ticker per exchange and the consolidated top per symbol
+#[derive(Debug)]
+pub struct ConsolidatedBook {
+    quotes: HashMap<String, HashMap<ExchangeType, BookTicker>>,
+    tops: HashMap<String, Nbbo>,
+    /// Quotes received this long before the newest one are dropped; 0 keeps them forever
+    max_quote_age_ms: i64,
+}
+
+impl Default for ConsolidatedBook {
+    fn default() -> Self {
+        Self {
+            quotes: HashMap::new(),
+            tops: HashMap::new(),
+            max_quote_age_ms: DEFAULT_MAX_QUOTE_AGE_MS as i64,
+        }
+    }
+}
+
+impl ConsolidatedBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop quotes received more than `max_age_ms` before the ticker being folded in (0 never drops them)
+    pub fn with_max_quote_age_ms(mut self, max_age_ms: u64) -> Self {
+        self.max_quote_age_ms = max_age_ms as i64;
+        self
+    }
+
+    /// Number of symbols with a consolidated top
+    pub fn len(&self) -> usize {
+        self.tops.len()
+    }
+
+    /// Check if no symbol has been quoted yet
+    pub fn is_empty(&self) -> bool {
+        self.tops.is_empty()
+    }
+
+    /// Current consolidated top of a symbol
+    pub fn nbbo(&self, symbol: &str) -> Option<&Nbbo> {
+        self.tops.get(symbol)
+    }
+
+    /// Fold in an exchange's book ticker, returning the new top if it changed.
+    ///
+    /// The top changes when the best bid or ask moves to another price or
+    /// exchange; size changes at an unchanged price don't emit. Ages are
+    /// measured on the gateway's clock, from each quote's `received_at` to
+    /// this ticker's.
+    pub fn update(&mut self, ticker: &BookTicker) -> Option<Nbbo> {
+        let quotes = self.quotes.entry(ticker.symbol.clone()).or_default();
+        quotes.insert(ticker.exchange, ticker.clone());
+        if self.max_quote_age_ms > 0 {
+            quotes.retain(|_, quote| ticker.received_at - quote.received_at <= self.max_quote_age_ms);
+        }
+
+        let bid = quotes
+            .values()
+            .filter(|quote| quote.bid_price > 0.0)
+            .max_by(|a, b| a.bid_price.total_cmp(&b.bid_price).then(a.bid_qty.total_cmp(&b.bid_qty)))?;
+        let ask = quotes
+            .values()
+            .filter(|quote| quote.ask_price > 0.0)
+            .min_by(|a, b| a.ask_price.total_cmp(&b.ask_price).then(b.ask_qty.total_cmp(&a.ask_qty)))?;
+
+        let nbbo = Nbbo {
+            symbol: ticker.symbol.clone(),
+            best_bid: bid.bid_price,
+            best_bid_qty: bid.bid_qty,
+            best_bid_exchange: bid.exchange,
+            best_ask: ask.ask_price,
+            best_ask_qty: ask.ask_qty,
+            best_ask_exchange: ask.exchange,
+            spread: ask.ask_price - bid.bid_price,
+            timestamp: ticker.timestamp,
+            received_at: ticker.received_at,
+        };
+
+        let unchanged = self.tops.get(&ticker.symbol).is_some_and(|top| {
+            top.best_bid == nbbo.best_bid
+                && top.best_bid_exchange == nbbo.best_bid_exchange
+                && top.best_ask == nbbo.best_ask
+                && top.best_ask_exchange == nbbo.best_ask_exchange
+        });
+        self.tops.insert(ticker.symbol.clone(), nbbo.clone());
+        (!unchanged).then_some(nbbo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticker(exchange: ExchangeType, bid_price: f64, ask_price: f64, timestamp: i64) -> BookTicker {
+        BookTicker {
+            exchange,
+            symbol: "BTCUSDT".to_string(),
+            bid_price,
+            bid_qty: 1.0,
+            ask_price,
+            ask_qty: 2.0,
+            timestamp,
+            received_at: timestamp + 3,
+        }
+    }
+
+    #[test]
+    fn test_nbbo_across_two_exchanges() {
+        let mut book = ConsolidatedBook::new();
+
+        let nbbo = book.update(&ticker(ExchangeType::Binance, 50000.0, 50001.0, 1_000)).unwrap();
+        assert_eq!((nbbo.best_bid_exchange, nbbo.best_ask_exchange), (ExchangeType::Binance, ExchangeType::Binance));
+        assert_eq!(nbbo.spread, 1.0);
+
+        // OKX bids higher but asks wider: it takes the bid, Binance keeps the ask
+        let nbbo = book.update(&ticker(ExchangeType::Okx, 50000.5, 50002.0, 1_010)).unwrap();
+        assert_eq!((nbbo.best_bid, nbbo.best_bid_exchange), (50000.5, ExchangeType::Okx));
+        assert_eq!((nbbo.best_ask, nbbo.best_ask_exchange), (50001.0, ExchangeType::Binance));
+        assert_eq!(nbbo.spread, 0.5);
+        assert!(!nbbo.is_crossed());
+        assert_eq!((nbbo.timestamp, nbbo.received_at), (1_010, 1_013));
+
+        // Binance moving its bid below the top leaves the NBBO where it was
+        assert!(book.update(&ticker(ExchangeType::Binance, 49999.0, 50001.0, 1_020)).is_none());
+        assert_eq!(book.nbbo("BTCUSDT").unwrap().best_bid_exchange, ExchangeType::Okx);
+        assert_eq!(book.len(), 1);
+    }
+
+    #[test]
+    fn test_crossed_market_is_detected() {
+        let mut book = ConsolidatedBook::new();
+        book.update(&ticker(ExchangeType::Binance, 50000.0, 50001.0, 1_000));
+
+        // OKX bids above Binance's ask
+        let nbbo = book.update(&ticker(ExchangeType::Okx, 50003.0, 50004.0, 1_010)).unwrap();
+        assert_eq!((nbbo.best_bid, nbbo.best_bid_exchange), (50003.0, ExchangeType::Okx));
+        assert_eq!((nbbo.best_ask, nbbo.best_ask_exchange), (50001.0, ExchangeType::Binance));
+        assert_eq!(nbbo.spread, -2.0);
+        assert!(nbbo.is_crossed());
+
+        // Binance catching up uncrosses it
+        let nbbo = book.update(&ticker(ExchangeType::Binance, 50003.0, 50005.0, 1_020)).unwrap();
+        assert_eq!(nbbo.best_ask_exchange, ExchangeType::Okx);
+        assert!(!nbbo.is_crossed());
+    }
+
+    #[test]
+    fn test_stale_exchange_stops_holding_the_top() {
+        let mut book = ConsolidatedBook::new().with_max_quote_age_ms(1_000);
+        book.update(&ticker(ExchangeType::Binance, 50000.0, 50001.0, 1_000));
+
+        // OKX's bid above Binance's ask crosses the market while both are fresh
+        let nbbo = book.update(&ticker(ExchangeType::Okx, 50003.0, 50004.0, 1_500)).unwrap();
+        assert!(nbbo.is_crossed());
+
+        // OKX then goes quiet; once its quote is too old Binance alone makes the top
+        assert!(book.update(&ticker(ExchangeType::Binance, 50000.0, 50001.0, 2_400)).is_none());
+        let nbbo = book.update(&ticker(ExchangeType::Binance, 50000.0, 50001.0, 2_600)).unwrap();
+        assert_eq!((nbbo.best_bid, nbbo.best_bid_exchange), (50000.0, ExchangeType::Binance));
+        assert_eq!((nbbo.best_ask, nbbo.best_ask_exchange), (50001.0, ExchangeType::Binance));
+        assert!(!nbbo.is_crossed());
+
+        // With no age limit the frozen quote would still hold the bid
+        let mut book = ConsolidatedBook::new().with_max_quote_age_ms(0);
+        book.update(&ticker(ExchangeType::Okx, 50003.0, 50004.0, 1_500));
+        let nbbo = book.update(&ticker(ExchangeType::Binance, 50000.0, 50001.0, 100_000)).unwrap();
+        assert_eq!(nbbo.best_bid_exchange, ExchangeType::Okx);
+    }
+}
@@ -1,33 +1,140 @@
 //! OKX Futures WebSocket implementation
 //!
 //! This module handles WebSocket connections to OKX Futures
-//! and parses incoming market data.
+//! and parses incoming market data. With an API key it logs in and streams
+//! the tick-by-tick books channels instead of the public ones.
 
 use crate::exchange::{
     Exchange, ExchangeType, MarketEvent, AggTrade, Kline, DepthUpdate, BookTicker,
-    Subscription, DataType, KlineInterval,
+    FundingRate, Liquidation, Side, Ticker24h, Subscription, DataType, KlineInterval, RateLimiter,
+    StaleWatchdog, ParseErrorMonitor, check_depth_levels, now_ms,
 };
+use crate::metrics;
+use crate::orderbook::OkxOrderBook;
+use crate::proxy::{self, ProxyConfig};
+use crate::reconnect::ReconnectPolicy;
 use crate::redis_publisher::RedisPublisher;
-use anyhow::{Result, anyhow};
+use crate::symbol;
+use crate::clock::{self, SharedClock};
+use crate::error::{GatewayError, Result};
+pub use crate::exchange::OkxInstType;
 use async_trait::async_trait;
+use base64::Engine;
+use chrono::{FixedOffset, Months, TimeZone};
 use futures_util::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
 use serde_json::{json, Value};
-use tokio_tungstenite::{connect_async, tungstenite::Message, WebSocketStream};
+use sha2::Sha256;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::time;
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
 use tracing::{debug, error, info, warn};
 use url::Url;
 
+pub use crate::orderbook::OKX_REST;
+
 /// OKX WebSocket endpoints
 pub const OKX_WS_PUBLIC: &str = "wss://ws.okx.com:8443/ws/v5/public";
 pub const OKX_WS_DEMO: &str = "wss://wspap.okx.com:8443/ws/v5/public"; // Demo trading
 
+/// Most levels per side the REST books endpoint returns
+pub const MAX_REST_DEPTH: u16 = 400;
+
+/// Default number of channels per subscribe frame
+pub const DEFAULT_SUBSCRIBE_BATCH_SIZE: usize = 20;
+
+/// Most channels OKX accepts in one subscribe frame
+pub const MAX_CHANNELS_PER_FRAME: usize = 64;
+
+/// Most bytes of channel args OKX accepts in one frame
+const MAX_ARGS_BYTES_PER_FRAME: usize = 64 * 1024;
+
+/// Control frames sent per second by default
+pub const DEFAULT_MESSAGES_PER_SECOND: u32 = 10;
+
+/// How long to wait for a batch of subscription acks
+const SUBSCRIBE_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Error codes OKX returns for a malformed subscribe arg (60012) or an unknown channel or instrument (60018)
+const OKX_BAD_CHANNEL_CODES: [&str; 2] = ["60012", "60018"];
+
+/// OKX aligns day-and-longer candles to UTC+8
+const CANDLE_UTC_OFFSET_SECS: i32 = 8 * 3600;
+
+/// Levels offered by the public books channels: `books5` and the full 400-level `books`
+pub const SUPPORTED_DEPTH_LEVELS: [u16; 2] = [5, 400];
+
+/// Levels offered once logged in: `books5`, `books50-l2-tbt` and the full `books-l2-tbt`
+pub const TICK_BY_TICK_DEPTH_LEVELS: [u16; 3] = [5, 50, 400];
+
+/// Request path signed by the WebSocket `login` op
+const LOGIN_PATH: &str = "/users/self/verify";
+
+/// How long to wait for the answer to a `login` op
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// API key an `OkxClient` logs in with
+#[derive(Clone)]
+struct Credentials {
+    api_key: String,
+    secret: String,
+    passphrase: String,
+}
+
+/// Sign a `login` op: base64 of the HMAC-SHA256 of `timestamp + "GET" + "/users/self/verify"`
+fn login_signature(secret: &str, timestamp: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}GET{}", timestamp, LOGIN_PATH).as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+/// OKX drops connections idle for 30s, so send a text ping well before that
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(20);
+
 /// OKX-specific WebSocket client
 pub struct OkxClient {
     exchange_type: ExchangeType,
     ws_url: String,
     ws: Option<WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>>,
-    symbols: Vec<String>,
+    /// REST requests go to the live host, flagged as simulated for demo trading
+    demo_trading: bool,
+    /// Instrument streamed for subscriptions that don't pick one
+    inst_type: OkxInstType,
+    http: reqwest::Client,
+    /// Active subscriptions, re-sent after a reconnect
+    subscriptions: Vec<Subscription>,
     redis_publisher: Option<RedisPublisher>,
     connected: bool,
+    subscribe_batch_size: usize,
+    /// Paces subscribe and unsubscribe frames
+    rate_limiter: RateLimiter,
+    /// Drops the connection once it goes silent
+    watchdog: StaleWatchdog,
+    /// Flags a connection whose frames mostly fail to parse
+    parse_errors: ParseErrorMonitor,
+    /// Publish partial book snapshots as best bid/ask tickers
+    derive_book_ticker: bool,
+    /// Stamps `received_at` on parsed events
+    clock: SharedClock,
+    /// Frames read while waiting for subscription acks, replayed by `recv_event`
+    pending: VecDeque<String>,
+    /// Extra events parsed from a frame that yields more than one, returned by `recv_event`
+    ready: VecDeque<MarketEvent>,
+    /// Local books per instId, checked against each update's checksum
+    order_books: HashMap<String, OkxOrderBook>,
+    /// Channel args whose book failed its checksum and must be resubscribed
+    resync: Vec<Value>,
+    /// Application-level "ping" timer, running while connected
+    keepalive: Option<time::Interval>,
+    /// Retries and timeout for each connect call
+    connect_policy: ReconnectPolicy,
+    /// Proxy the WebSocket is tunneled through, if any
+    proxy: Option<ProxyConfig>,
+    /// API key for the tick-by-tick books channels; public channels are used without one
+    credentials: Option<Credentials>,
+    /// Whether this connection has logged in
+    logged_in: bool,
 }
 
 impl OkxClient {
@@ -44,9 +151,27 @@ impl OkxClient {
             exchange_type,
             ws_url,
             ws: None,
-            symbols: Vec::new(),
+            demo_trading,
+            inst_type: OkxInstType::default(),
+            http: reqwest::Client::new(),
+            subscriptions: Vec::new(),
             redis_publisher: None,
             connected: false,
+            subscribe_batch_size: DEFAULT_SUBSCRIBE_BATCH_SIZE,
+            rate_limiter: RateLimiter::new(DEFAULT_MESSAGES_PER_SECOND, 1),
+            watchdog: StaleWatchdog::default(),
+            parse_errors: ParseErrorMonitor::default(),
+            derive_book_ticker: false,
+            clock: clock::system(),
+            pending: VecDeque::new(),
+            ready: VecDeque::new(),
+            order_books: HashMap::new(),
+            resync: Vec::new(),
+            keepalive: None,
+            connect_policy: ReconnectPolicy::default(),
+            proxy: None,
+            credentials: None,
+            logged_in: false,
         }
     }
 
@@ -56,167 +181,575 @@ impl OkxClient {
         self
     }
 
-    /// Build subscription message for OKX
-    fn build_subscription_msg(&self, subscriptions: &[Subscription]) -> Value {
-        let mut ops = Vec::new();
+    /// Connect to `url` instead of the public or demo endpoint, e.g. a regional host or a proxy
+    pub fn with_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.ws_url = url.into();
+        self
+    }
+
+    /// Tunnel the WebSocket through an HTTP CONNECT or SOCKS5 proxy
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Stream trades, candles, books and tickers of this instrument type, e.g. the perpetual swap instead of spot,
+    /// unless a subscription sets its own
+    pub fn with_inst_type(mut self, inst_type: OkxInstType) -> Self {
+        self.inst_type = inst_type;
+        self
+    }
+
+    /// Instrument type streamed by default
+    pub fn inst_type(&self) -> OkxInstType {
+        self.inst_type
+    }
+
+    /// Set the retries and timeout applied to each connect call
+    pub fn with_connect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.connect_policy = policy;
+        self
+    }
 
+    /// Log in with an API key so depth streams over the tick-by-tick `books-l2-tbt` channels
+    pub fn with_credentials(mut self, api_key: impl Into<String>, secret: impl Into<String>, passphrase: impl Into<String>) -> Self {
+        self.credentials = Some(Credentials {
+            api_key: api_key.into(),
+            secret: secret.into(),
+            passphrase: passphrase.into(),
+        });
+        self
+    }
+
+    /// Book depths this client can stream
+    fn supported_depth_levels(&self) -> &'static [u16] {
+        if self.credentials.is_some() {
+            &TICK_BY_TICK_DEPTH_LEVELS
+        } else {
+            &SUPPORTED_DEPTH_LEVELS
+        }
+    }
+
+    /// instId a subscription streams: its own instrument type, or the client's default
+    fn inst_id(&self, sub: &Subscription) -> String {
+        let inst_type = match sub.data_type {
+            // Funding and liquidations only exist on perpetual swaps
+            DataType::FundingRate | DataType::Liquidation => OkxInstType::Swap,
+            _ => sub.okx_inst_type.unwrap_or(self.inst_type),
+        };
+        Self::to_okx(&sub.symbol, inst_type)
+    }
+
+    /// Build the channel argument for a subscription (OKX tracks state per channel and instId),
+    /// or `None` if it has no WebSocket channel
+    fn channel_arg(&self, sub: &Subscription) -> Option<Value> {
+        let channel = match sub.data_type {
+            DataType::AggTrade => "trades".to_string(),
+            // OKX has no continuous-contract candles, so use the instrument's own
+            DataType::Kline | DataType::ContinuousKline => {
+                let bar = sub.interval.unwrap_or(KlineInterval::OneMinute).as_okx_str()?;
+                format!("candle{}", bar)
+            }
+            // The tick-by-tick channels need a logged-in connection
+            DataType::Depth => match (sub.depth_levels, self.credentials.is_some()) {
+                (Some(5), _) => "books5".to_string(),
+                (Some(50), true) => "books50-l2-tbt".to_string(),
+                (_, true) => "books-l2-tbt".to_string(),
+                (_, false) => "books".to_string(),
+            },
+            DataType::BookTicker | DataType::Ticker24h => "tickers".to_string(),
+            DataType::FundingRate => "funding-rate".to_string(),
+            // Liquidations are pushed for every swap; `parse_liquidation` keeps the tracked symbols
+            DataType::Liquidation => {
+                return Some(json!({ "channel": "liquidation-orders", "instType": "SWAP" }));
+            }
+            // Polled over REST
+            DataType::OpenInterest => return None,
+            // Derived by the gateway from book tickers and the NBBO
+            DataType::Nbbo | DataType::Opportunity => return None,
+        };
+
+        Some(json!({
+            "channel": channel,
+            "instId": self.inst_id(sub)
+        }))
+    }
+
+    /// Record subscriptions as active, returning the ones not already tracked
+    fn track_subscriptions(&mut self, subscriptions: Vec<Subscription>) -> Vec<Subscription> {
+        let mut added = Vec::new();
         for sub in subscriptions {
-            let channel = match sub.data_type {
-                DataType::AggTrade => {
-                    format!("public-trade:{}", Self::okx_symbol(&sub.symbol))
-                }
-                DataType::Kline => {
-                    let interval = sub.interval.unwrap_or(KlineInterval::OneMinute).as_str();
-                    format!("public-candle{}:{}", interval, Self::okx_symbol(&sub.symbol))
-                }
-                DataType::Depth => {
-                    format!("public-books:{}", Self::okx_symbol(&sub.symbol))
+            if !self.subscriptions.contains(&sub) && !added.contains(&sub) {
+                added.push(sub);
+            }
+        }
+        self.subscriptions.extend(added.iter().cloned());
+        added
+    }
+
+    /// Build the frames that restore all active subscriptions on a fresh connection
+    fn resubscribe_msgs(&self) -> Vec<Value> {
+        self.build_subscription_msgs(&self.subscriptions)
+    }
+
+    /// Set how many channels are sent per subscribe frame, at most 64
+    pub fn with_subscribe_batch_size(mut self, batch_size: usize) -> Self {
+        self.subscribe_batch_size = batch_size.clamp(1, MAX_CHANNELS_PER_FRAME);
+        self
+    }
+
+    /// Set how many control frames may be sent per second
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limiter = RateLimiter::new(per_second, 1);
+        self
+    }
+
+    /// Treat the connection as dead after this long without a frame (zero disables)
+    pub fn with_stale_timeout(mut self, timeout: Duration) -> Self {
+        self.watchdog = StaleWatchdog::new(timeout);
+        self
+    }
+
+    /// Alarm on, and optionally reconnect after, a storm of unparseable frames
+    pub fn with_parse_error_monitor(mut self, monitor: ParseErrorMonitor) -> Self {
+        self.parse_errors = monitor;
+        self
+    }
+
+    /// Turn partial book snapshots into best bid/ask tickers before anything sees them
+    pub fn with_derived_book_ticker(mut self, enabled: bool) -> Self {
+        self.derive_book_ticker = enabled;
+        self
+    }
+
+    /// Stamp events with this clock instead of the host's, e.g. a mock in tests
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Distinct channel args for a set of subscriptions
+    fn channel_args(&self, subscriptions: &[Subscription]) -> Vec<Value> {
+        let mut args: Vec<Value> = Vec::new();
+        for arg in subscriptions.iter().filter_map(|sub| self.channel_arg(sub)) {
+            if !args.contains(&arg) {
+                args.push(arg);
+            }
+        }
+        args
+    }
+
+    /// Build one `op` frame per batch of channel args, keeping each within OKX's frame limits
+    fn build_op_msgs(&self, op: &str, args: &[Value]) -> Vec<Value> {
+        let mut msgs = Vec::new();
+        let mut batch: Vec<Value> = Vec::new();
+        let mut batch_bytes = 0;
+
+        for arg in args {
+            let arg_bytes = arg.to_string().len();
+            if !batch.is_empty()
+                && (batch.len() >= self.subscribe_batch_size || batch_bytes + arg_bytes > MAX_ARGS_BYTES_PER_FRAME)
+            {
+                msgs.push(json!({ "op": op, "args": std::mem::take(&mut batch) }));
+                batch_bytes = 0;
+            }
+            batch_bytes += arg_bytes;
+            batch.push(arg.clone());
+        }
+        if !batch.is_empty() {
+            msgs.push(json!({ "op": op, "args": batch }));
+        }
+
+        msgs
+    }
+
+    /// Build one subscribe frame per batch of distinct channel args
+    fn build_subscription_msgs(&self, subscriptions: &[Subscription]) -> Vec<Value> {
+        self.build_op_msgs("subscribe", &self.channel_args(subscriptions))
+    }
+
+    /// Build one unsubscribe frame per batch of distinct channel args
+    fn build_unsubscribe_msgs(&self, subscriptions: &[Subscription]) -> Vec<Value> {
+        self.build_op_msgs("unsubscribe", &self.channel_args(subscriptions))
+    }
+
+    /// Stop tracking subscriptions, returning the channel args no remaining subscription uses
+    fn untrack_subscriptions(&mut self, subscriptions: &[Subscription]) -> Vec<Subscription> {
+        self.subscriptions.retain(|sub| !subscriptions.contains(sub));
+
+        // Liquidations share one instType-wide channel across symbols
+        let remaining = self.channel_args(&self.subscriptions);
+        subscriptions.iter()
+            .filter(|sub| self.channel_arg(sub).is_some_and(|arg| !remaining.contains(&arg)))
+            .cloned()
+            .collect()
+    }
+
+    /// Wait until every channel in `args` is acknowledged, buffering any data frames
+    async fn await_subscribe_acks(&mut self, args: &[Value]) -> Result<()> {
+        let ws = self.ws.as_mut().ok_or_else(|| GatewayError::Disconnected("Not connected".to_string()))?;
+        let mut waiting: Vec<&Value> = args.iter().collect();
+
+        let wait = async {
+            while !waiting.is_empty() {
+                match ws.next().await {
+                    Some(Ok(Message::Text(text))) => {
+                        let data: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+                        match data.get("event").and_then(|e| e.as_str()) {
+                            Some("subscribe") => match data.get("arg") {
+                                Some(arg) => waiting.retain(|sent| *sent != arg),
+                                None => debug!("OKX subscribe ack without an arg: {}", text),
+                            },
+                            Some("error") => {
+                                let error = error_event(&data);
+                                log_error_event(&error);
+                                return Err(error);
+                            }
+                            _ => self.pending.push_back(text),
+                        }
+                    }
+                    Some(Ok(Message::Ping(payload))) => {
+                        ws.send(Message::Pong(payload)).await?;
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.into()),
+                    None => return Err(GatewayError::Disconnected("OKX connection closed during subscribe".to_string())),
                 }
-                DataType::BookTicker => {
-                    format!("public-tickers:{}", Self::okx_symbol(&sub.symbol))
+            }
+            Ok(())
+        };
+
+        match time::timeout(SUBSCRIBE_ACK_TIMEOUT, wait).await {
+            Ok(result) => result,
+            Err(_) => Err(GatewayError::Subscription(format!(
+                "Timed out waiting for OKX subscription acks ({}/{} acknowledged)",
+                args.len() - waiting.len(),
+                args.len()
+            ))),
+        }
+    }
+
+    /// Build the `login` op for `timestamp` (Unix seconds), or `None` without credentials
+    fn build_login_msg(&self, timestamp: &str) -> Option<Value> {
+        let credentials = self.credentials.as_ref()?;
+        Some(json!({
+            "op": "login",
+            "args": [{
+                "apiKey": credentials.api_key,
+                "passphrase": credentials.passphrase,
+                "timestamp": timestamp,
+                "sign": login_signature(&credentials.secret, timestamp),
+            }]
+        }))
+    }
+
+    /// Log in once per connection, which the tick-by-tick channels require
+    async fn login(&mut self) -> Result<()> {
+        let timestamp = (now_ms() / 1000).to_string();
+        let Some(login) = self.build_login_msg(&timestamp) else {
+            return Err(GatewayError::Config("OKX tick-by-tick channels need API credentials".to_string()));
+        };
+        let ws = self.ws.as_mut().ok_or_else(|| GatewayError::Disconnected("Not connected".to_string()))?;
+        self.rate_limiter.acquire().await;
+        ws.send(Message::Text(login.to_string())).await?;
+
+        let wait = async {
+            loop {
+                match ws.next().await {
+                    Some(Ok(Message::Text(text))) => {
+                        let data: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+                        match data.get("event").and_then(|e| e.as_str()) {
+                            Some("login") => return Ok(()),
+                            Some("error") => {
+                                return Err(GatewayError::Connect(format!("OKX login failed: {}", error_event(&data))));
+                            }
+                            _ => self.pending.push_back(text),
+                        }
+                    }
+                    Some(Ok(Message::Ping(payload))) => {
+                        ws.send(Message::Pong(payload)).await?;
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.into()),
+                    None => return Err(GatewayError::Disconnected("OKX connection closed during login".to_string())),
                 }
-            };
+            }
+        };
+        time::timeout(LOGIN_TIMEOUT, wait)
+            .await
+            .map_err(|_| GatewayError::Connect("Timed out waiting for the OKX login response".to_string()))??;
 
-            ops.push(json!({
-                "channel": channel,
-                "op": "subscribe"
-            }));
+        info!("Logged in to OKX");
+        self.logged_in = true;
+        Ok(())
+    }
+
+    /// Send subscribe frames one batch at a time, each acknowledged before the next is sent
+    async fn send_subscription_msgs(&mut self, msgs: Vec<Value>) -> Result<()> {
+        let tick_by_tick = msgs.iter()
+            .flat_map(|msg| msg["args"].as_array().cloned().unwrap_or_default())
+            .any(|arg| arg["channel"].as_str().is_some_and(|channel| channel.ends_with("-l2-tbt")));
+        if tick_by_tick && !self.logged_in {
+            self.login().await?;
         }
 
-        json!({ "op": "subscribe", "args": ops })
+        for sub_msg in msgs {
+            // The rate limiter spaces the frames out
+            self.rate_limiter.acquire().await;
+
+            let msg_str = serde_json::to_string(&sub_msg)?;
+            if let Some(ref mut ws) = self.ws {
+                ws.send(Message::Text(msg_str)).await?;
+            }
+
+            let args = sub_msg["args"].as_array().cloned().unwrap_or_default();
+            self.await_subscribe_acks(&args).await?;
+        }
+
+        Ok(())
     }
 
-    /// Convert trading pair to OKX format (e.g., BTCUSDT -> BTC-USDT)
-    fn okx_symbol(symbol: &str) -> String {
-        // Insert hyphen before USDT
-        symbol.replace("USDT", "-USDT")
-            .replace("USD", "-USD") // For other USD pairs
+    /// Convert a trading pair to an OKX instId (e.g. BTCUSDT -> BTC-USDT or BTC-USDT-SWAP).
+    /// Symbols with no known quote currency are passed through unchanged.
+    pub fn to_okx(symbol: &str, instrument_type: OkxInstType) -> String {
+        let pair = symbol::to_exchange_symbol(ExchangeType::Okx, symbol);
+
+        match instrument_type {
+            OkxInstType::Spot => pair,
+            OkxInstType::Swap => format!("{}-SWAP", pair),
+        }
     }
 
-    /// Convert OKX symbol back to standard format
-    fn standard_symbol(okx_symbol: &str) -> String {
-        okx_symbol.replace("-", "")
+    /// Convert an OKX instId back to a standard symbol, dropping any `-SWAP` or expiry suffix
+    pub fn from_okx(inst_id: &str) -> String {
+        symbol::from_exchange_symbol(ExchangeType::Okx, inst_id)
     }
 
-    /// Parse aggregated trade event from OKX WebSocket message
-    fn parse_trade(&self, data: &Value, symbol: &str) -> Result<MarketEvent> {
-        let arr = data.get("data").and_then(|d| d.as_array())
-            .ok_or_else(|| anyhow!("Missing data array"))?;
+    /// Symbol events carry for an instId: `BTCUSDT` for the `BTC-USDT` spot pair and
+    /// `BTCUSDT-SWAP` for the perpetual, so downstream can tell the two apart
+    pub fn standard_symbol(inst_id: &str) -> String {
+        match OkxInstType::from_inst_id(inst_id) {
+            OkxInstType::Spot => Self::from_okx(inst_id),
+            OkxInstType::Swap => format!("{}-SWAP", Self::from_okx(inst_id)),
+        }
+    }
 
-        if arr.is_empty() {
-            return Err(anyhow!("Empty trade data"));
+    /// instId for a symbol as events carry it; one without a `-SWAP` suffix uses the default instrument type
+    fn symbol_inst_id(&self, symbol: &str) -> String {
+        match symbol.strip_suffix("-SWAP") {
+            Some(pair) => Self::to_okx(pair, OkxInstType::Swap),
+            None => Self::to_okx(symbol, self.inst_type),
         }
+    }
 
-        let trade = &arr[0];
-        let price = trade["px"].as_str().ok_or_else(|| anyhow!("Missing price"))?
-            .parse::<f64>()?;
-        let quantity = trade["sz"].as_str().ok_or_else(|| anyhow!("Missing quantity"))?
-            .parse::<f64>()?;
-        let timestamp = trade["ts"].as_i64().ok_or_else(|| anyhow!("Missing timestamp"))?;
-        let side = trade["side"].as_str().ok_or_else(|| anyhow!("Missing side"))?;
-        // OKX: buy=true means taker was buyer (not buyer maker)
-        let is_buyer_maker = side == "sell";
+    /// Parse trade events from OKX WebSocket message, one per trade in the batch
+    fn parse_trades(&self, data: &Value, symbol: &str) -> Result<Vec<MarketEvent>> {
+        let trades = data.get("data").and_then(|d| d.as_array())
+            .ok_or_else(|| GatewayError::Parse("Missing data array".to_string()))?;
 
-        Ok(MarketEvent::AggTrade(AggTrade {
-            exchange: self.exchange_type,
-            symbol: Self::standard_symbol(symbol),
-            price,
-            quantity,
-            timestamp,
-            is_buyer_maker,
-            trade_id: timestamp as u64, // OKX uses timestamp as trade ID
-        }))
+        trades
+            .iter()
+            .map(|trade| {
+                let price = trade["px"].as_str().ok_or_else(|| GatewayError::Parse("Missing price".to_string()))?
+                    .parse::<f64>()?;
+                let quantity = trade["sz"].as_str().ok_or_else(|| GatewayError::Parse("Missing quantity".to_string()))?
+                    .parse::<f64>()?;
+                let timestamp = trade["ts"].as_str().ok_or_else(|| GatewayError::Parse("Missing timestamp".to_string()))?
+                    .parse::<i64>()?;
+                let side = trade["side"].as_str().ok_or_else(|| GatewayError::Parse("Missing side".to_string()))?;
+                let trade_id = trade["tradeId"].as_str().ok_or_else(|| GatewayError::Parse("Missing tradeId".to_string()))?
+                    .parse::<u64>()?;
+
+                Ok(MarketEvent::AggTrade(AggTrade {
+                    exchange: self.exchange_type,
+                    symbol: Self::standard_symbol(symbol),
+                    price,
+                    quantity,
+                    timestamp,
+                    // OKX reports the taker side, so a taker sell means the buyer was the maker
+                    is_buyer_maker: side == "sell",
+                    trade_id,
+                    received_at: self.clock.now_ms(),
+                }))
+            })
+            .collect()
     }
 
     /// Parse kline event from OKX WebSocket message
     fn parse_kline(&self, data: &Value, symbol: &str, channel: &str) -> Result<MarketEvent> {
         let arr = data.get("data").and_then(|d| d.as_array())
-            .ok_or_else(|| anyhow!("Missing data array"))?;
+            .ok_or_else(|| GatewayError::Parse("Missing data array".to_string()))?;
 
         if arr.is_empty() {
-            return Err(anyhow!("Empty kline data"));
+            return Err(GatewayError::Parse("Empty kline data".to_string()));
         }
 
+        // `[ts, o, h, l, c, vol, volCcy, volCcyQuote, confirm]`, all strings
         let candle = &arr[0];
 
-        // Extract interval from channel (e.g., "public-candle1m:BTC-USDT")
-        let interval = channel
-            .split(':')
-            .next()
-            .and_then(|c| c.strip_prefix("public-candle"))
-            .unwrap_or("1m");
+        // Extract interval from channel (e.g., "candle1H") in standard notation
+        let bar = channel.strip_prefix("candle").unwrap_or("1m");
+        let interval = KlineInterval::from_okx_str(bar).map_or(bar, |interval| interval.as_str());
 
-        let open = candle[0].as_str().ok_or_else(|| anyhow!("Missing open"))?
+        let open = candle[1].as_str().ok_or_else(|| GatewayError::Parse("Missing open".to_string()))?
             .parse::<f64>()?;
-        let high = candle[1].as_str().ok_or_else(|| anyhow!("Missing high"))?
+        let high = candle[2].as_str().ok_or_else(|| GatewayError::Parse("Missing high".to_string()))?
             .parse::<f64>()?;
-        let low = candle[2].as_str().ok_or_else(|| anyhow!("Missing low"))?
+        let low = candle[3].as_str().ok_or_else(|| GatewayError::Parse("Missing low".to_string()))?
             .parse::<f64>()?;
-        let close = candle[3].as_str().ok_or_else(|| anyhow!("Missing close"))?
+        let close = candle[4].as_str().ok_or_else(|| GatewayError::Parse("Missing close".to_string()))?
             .parse::<f64>()?;
-        let volume = candle[5].as_str().ok_or_else(|| anyhow!("Missing volume"))?
+        let volume = candle[5].as_str().ok_or_else(|| GatewayError::Parse("Missing volume".to_string()))?
             .parse::<f64>()?;
-        let timestamp = candle[0].as_str().ok_or_else(|| anyhow!("Missing timestamp"))?
+        let timestamp = candle[0].as_str().ok_or_else(|| GatewayError::Parse("Missing timestamp".to_string()))?
             .parse::<i64>()?;
 
-        // OKX confirms candle closing
-        let confirm = candle.get("confirm").and_then(|c| c.as_bool()).unwrap_or(false);
+        // "1" once the candle has closed, "0" while it is still forming
+        let confirm = candle[8].as_str() == Some("1");
 
         Ok(MarketEvent::Kline(Kline {
             exchange: self.exchange_type,
             symbol: Self::standard_symbol(symbol),
             interval: interval.to_string(),
             open_time: timestamp,
-            close_time: timestamp + self.interval_ms(interval) - 1,
+            close_time: self.close_time(timestamp, interval),
             open,
             high,
             low,
             close,
             volume,
             is_closed: confirm,
+            contract_type: None,
+            received_at: self.clock.now_ms(),
         }))
     }
 
     /// Get interval duration in milliseconds
     fn interval_ms(&self, interval: &str) -> i64 {
-        let num = interval.trim_end_matches('m')
-            .trim_end_matches('h')
-            .trim_end_matches('d')
-            .parse::<u64>()
-            .unwrap_or(1);
-
-        let base = if interval.ends_with('m') {
-            60_000
-        } else if interval.ends_with('h') {
-            3_600_000
-        } else if interval.ends_with('d') {
-            86_400_000
-        } else {
-            60_000
+        let count = interval.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let num = count.parse::<u64>().unwrap_or(1);
+
+        // `m` is minutes and `M` is months, as in standard notation
+        let base = match &interval[count.len()..] {
+            "m" => 60_000,
+            "h" => 3_600_000,
+            "d" => 86_400_000,
+            "w" => 604_800_000,
+            // Approximate; `close_time` uses real month lengths
+            "M" => 30 * 86_400_000,
+            _ => 60_000,
         };
 
         num as i64 * base
     }
 
+    /// Last millisecond of a candle opening at `open_time`
+    fn close_time(&self, open_time: i64, interval: &str) -> i64 {
+        // Months vary in length, so step to the next calendar month boundary
+        if let Some(months) = interval.strip_suffix('M').and_then(|n| n.parse::<u32>().ok()) {
+            let offset = FixedOffset::east_opt(CANDLE_UTC_OFFSET_SECS).unwrap();
+            let close = offset.timestamp_millis_opt(open_time)
+                .single()
+                .and_then(|open| open.checked_add_months(Months::new(months)));
+            if let Some(close) = close {
+                return close.timestamp_millis() - 1;
+            }
+        }
+
+        open_time + self.interval_ms(interval) - 1
+    }
+
+    /// Parse funding rate event from OKX WebSocket message
+    fn parse_funding_rate(&self, data: &Value, symbol: &str) -> Result<MarketEvent> {
+        let arr = data.get("data").and_then(|d| d.as_array())
+            .ok_or_else(|| GatewayError::Parse("Missing data array".to_string()))?;
+
+        if arr.is_empty() {
+            return Err(GatewayError::Parse("Empty funding rate data".to_string()));
+        }
+
+        let funding = &arr[0];
+
+        let funding_rate = funding["fundingRate"].as_str().ok_or_else(|| GatewayError::Parse("Missing fundingRate".to_string()))?
+            .parse::<f64>()?;
+        // fundingTime is the upcoming settlement; nextFundingTime is the one after it
+        let next_funding_time = funding["fundingTime"].as_str().ok_or_else(|| GatewayError::Parse("Missing fundingTime".to_string()))?
+            .parse::<i64>()?;
+        let timestamp = funding["ts"].as_str().ok_or_else(|| GatewayError::Parse("Missing timestamp".to_string()))?
+            .parse::<i64>()?;
+
+        Ok(MarketEvent::FundingRate(FundingRate {
+            exchange: self.exchange_type,
+            symbol: Self::standard_symbol(symbol),
+            funding_rate,
+            next_funding_time,
+            timestamp,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse liquidation event from OKX WebSocket message, or `None` for untracked instruments
+    fn parse_liquidation(&self, data: &Value) -> Result<Option<MarketEvent>> {
+        let arr = data.get("data").and_then(|d| d.as_array())
+            .ok_or_else(|| GatewayError::Parse("Missing data array".to_string()))?;
+
+        if arr.is_empty() {
+            return Err(GatewayError::Parse("Empty liquidation data".to_string()));
+        }
+
+        let order = &arr[0];
+        let inst_id = order["instId"].as_str().ok_or_else(|| GatewayError::Parse("Missing instId".to_string()))?;
+        let symbol = Self::standard_symbol(inst_id);
+
+        if !self.is_subscribed(DataType::Liquidation, inst_id) {
+            return Ok(None);
+        }
+
+        // OKX pushes at most one liquidation per instrument per second
+        let detail = order["details"].get(0).ok_or_else(|| GatewayError::Parse("Missing liquidation details".to_string()))?;
+
+        let side = detail["side"].as_str().ok_or_else(|| GatewayError::Parse("Missing side".to_string()))?
+            .parse::<Side>()?;
+        let price = detail["bkPx"].as_str().ok_or_else(|| GatewayError::Parse("Missing bankruptcy price".to_string()))?
+            .parse::<f64>()?;
+        // Size is in contracts
+        let quantity = detail["sz"].as_str().ok_or_else(|| GatewayError::Parse("Missing size".to_string()))?
+            .parse::<f64>()?;
+        let timestamp = detail["ts"].as_str().ok_or_else(|| GatewayError::Parse("Missing timestamp".to_string()))?
+            .parse::<i64>()?;
+
+        Ok(Some(MarketEvent::Liquidation(Liquidation {
+            exchange: self.exchange_type,
+            symbol,
+            side,
+            price,
+            quantity,
+            timestamp,
+            received_at: self.clock.now_ms(),
+        })))
+    }
+
     /// Parse book ticker event from OKX WebSocket message
     fn parse_ticker(&self, data: &Value, symbol: &str) -> Result<MarketEvent> {
         let arr = data.get("data").and_then(|d| d.as_array())
-            .ok_or_else(|| anyhow!("Missing data array"))?;
+            .ok_or_else(|| GatewayError::Parse("Missing data array".to_string()))?;
 
         if arr.is_empty() {
-            return Err(anyhow!("Empty ticker data"));
+            return Err(GatewayError::Parse("Empty ticker data".to_string()));
         }
 
         let ticker = &arr[0];
-        let bid_price = ticker["bidPx"].as_str().ok_or_else(|| anyhow!("Missing bid price"))?
+        let bid_price = ticker["bidPx"].as_str().ok_or_else(|| GatewayError::Parse("Missing bid price".to_string()))?
             .parse::<f64>().unwrap_or(0.0);
-        let bid_qty = ticker["bidSz"].as_str().ok_or_else(|| anyhow!("Missing bid qty"))?
+        let bid_qty = ticker["bidSz"].as_str().ok_or_else(|| GatewayError::Parse("Missing bid qty".to_string()))?
             .parse::<f64>().unwrap_or(0.0);
-        let ask_price = ticker["askPx"].as_str().ok_or_else(|| anyhow!("Missing ask price"))?
+        let ask_price = ticker["askPx"].as_str().ok_or_else(|| GatewayError::Parse("Missing ask price".to_string()))?
             .parse::<f64>().unwrap_or(0.0);
-        let ask_qty = ticker["askSz"].as_str().ok_or_else(|| anyhow!("Missing ask qty"))?
+        let ask_qty = ticker["askSz"].as_str().ok_or_else(|| GatewayError::Parse("Missing ask qty".to_string()))?
             .parse::<f64>().unwrap_or(0.0);
-        let timestamp = ticker["ts"].as_i64().ok_or_else(|| anyhow!("Missing timestamp"))?;
+        let timestamp = ticker["ts"].as_str().ok_or_else(|| GatewayError::Parse("Missing timestamp".to_string()))?
+            .parse::<i64>()?;
 
         Ok(MarketEvent::BookTicker(BookTicker {
             exchange: self.exchange_type,
@@ -226,56 +759,331 @@ impl OkxClient {
             ask_price,
             ask_qty,
             timestamp,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Parse rolling 24h statistics from a tickers message
+    fn parse_ticker_24h(&self, data: &Value, symbol: &str) -> Result<MarketEvent> {
+        let ticker = data.get("data").and_then(|d| d.get(0))
+            .ok_or_else(|| GatewayError::Parse("Empty ticker data".to_string()))?;
+        let field = |key: &str| -> Result<f64> {
+            Ok(ticker[key].as_str().ok_or_else(|| GatewayError::Parse(format!("Missing {}", key)))?
+                .parse::<f64>()?)
+        };
+
+        let last_price = field("last")?;
+        let open_price = field("open24h")?;
+        // OKX doesn't send the change, so derive it from the open 24h ago
+        let price_change = last_price - open_price;
+        let price_change_percent = if open_price != 0.0 { price_change / open_price * 100.0 } else { 0.0 };
+        let timestamp = ticker["ts"].as_str().ok_or_else(|| GatewayError::Parse("Missing timestamp".to_string()))?
+            .parse::<i64>()?;
+
+        Ok(MarketEvent::Ticker24h(Ticker24h {
+            exchange: self.exchange_type,
+            symbol: Self::standard_symbol(symbol),
+            last_price,
+            open_price,
+            high_price: field("high24h")?,
+            low_price: field("low24h")?,
+            volume: field("vol24h")?,
+            quote_volume: field("volCcy24h")?,
+            price_change,
+            price_change_percent,
+            timestamp,
+            received_at: self.clock.now_ms(),
+        }))
+    }
+
+    /// Whether a data type is subscribed for an instId
+    fn is_subscribed(&self, data_type: DataType, inst_id: &str) -> bool {
+        self.subscriptions.iter().any(|sub| sub.data_type == data_type && self.inst_id(sub) == inst_id)
+    }
+
+    /// Parse order book event from OKX WebSocket message
+    fn parse_books(&self, data: &Value, symbol: &str) -> Result<MarketEvent> {
+        let arr = data.get("data").and_then(|d| d.as_array())
+            .ok_or_else(|| GatewayError::Parse("Missing data array".to_string()))?;
+
+        if arr.is_empty() {
+            return Err(GatewayError::Parse("Empty books data".to_string()));
+        }
+
+        let book = &arr[0];
+
+        let timestamp = book["ts"].as_str().ok_or_else(|| GatewayError::Parse("Missing timestamp".to_string()))?
+            .parse::<i64>()?;
+
+        // Each level is [price, size, liquidatedOrders, numOrders]
+        let mut bids = Vec::new();
+        if let Some(bid_array) = book.get("bids").and_then(|b| b.as_array()) {
+            for bid in bid_array {
+                if let Some(arr) = bid.as_array() {
+                    if arr.len() >= 2 {
+                        let price = arr[0].as_str().and_then(|s| s.parse::<f64>().ok());
+                        let qty = arr[1].as_str().and_then(|s| s.parse::<f64>().ok());
+                        if let (Some(p), Some(q)) = (price, qty) {
+                            bids.push((p, q));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut asks = Vec::new();
+        if let Some(ask_array) = book.get("asks").and_then(|a| a.as_array()) {
+            for ask in ask_array {
+                if let Some(arr) = ask.as_array() {
+                    if arr.len() >= 2 {
+                        let price = arr[0].as_str().and_then(|s| s.parse::<f64>().ok());
+                        let qty = arr[1].as_str().and_then(|s| s.parse::<f64>().ok());
+                        if let (Some(p), Some(q)) = (price, qty) {
+                            asks.push((p, q));
+                        }
+                    }
+                }
+            }
+        }
+
+        // "snapshot" carries the full book, "update" carries diffs; books5 has no action
+        // and always pushes the full top of book
+        let is_snapshot = data.get("action")
+            .and_then(|a| a.as_str())
+            .is_none_or(|action| action == "snapshot");
+
+        Ok(MarketEvent::DepthUpdate(DepthUpdate {
+            exchange: self.exchange_type,
+            symbol: Self::standard_symbol(symbol),
+            bids,
+            asks,
+            timestamp,
+            is_snapshot,
+            first_update_id: None,
+            final_update_id: None,
+            prev_final_update_id: None,
+            received_at: self.clock.now_ms(),
         }))
     }
 
-    /// Parse incoming message into a MarketEvent
-    fn parse_message(&mut self, msg: &str) -> Result<(MarketEvent, String)> {
+    /// Parse a `/api/v5/market/books` response, whose `data` matches a books push
+    fn parse_books_snapshot(&self, data: &Value, inst_id: &str) -> Result<DepthUpdate> {
+        let code = data["code"].as_str().unwrap_or_default();
+        if code != "0" {
+            return Err(GatewayError::Connect(format!("OKX books request failed: {} ({})", data["msg"], code)));
+        }
+
+        match self.parse_books(data, inst_id)? {
+            MarketEvent::DepthUpdate(snapshot) => Ok(snapshot),
+            _ => unreachable!("parse_books only builds depth updates"),
+        }
+    }
+
+    /// Apply a books message to the local book and verify its checksum, returning whether
+    /// the update should be published. Checksum failures are book-integrity problems rather
+    /// than parse errors, so they are counted on their own and never reach `parse_errors`.
+    fn update_order_book(&mut self, data: &Value, is_snapshot: bool) -> Result<bool> {
+        let book = &data["data"][0];
+        // Channels without a checksum (e.g. books5) always push the full top of book
+        let Some(checksum) = book.get("checksum").and_then(|c| c.as_i64()) else {
+            return Ok(true);
+        };
+        let inst_id = data["arg"]["instId"].as_str().unwrap_or_default();
+
+        if !is_snapshot && !self.order_books.contains_key(inst_id) {
+            debug!("Dropping {} books update while awaiting its snapshot", inst_id);
+            return Ok(false);
+        }
+
+        let order_book = self.order_books
+            .entry(inst_id.to_string())
+            .or_default();
+        order_book.apply(book, is_snapshot)?;
+
+        if !order_book.verify_checksum(checksum as i32) {
+            error!(
+                "OKX {} book checksum mismatch (expected {}, computed {}), resubscribing",
+                inst_id, checksum, order_book.checksum()
+            );
+            metrics::global().record_checksum_mismatch(self.exchange_type);
+            self.order_books.remove(inst_id);
+            self.resync.push(data["arg"].clone());
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Resubscribe books channels that failed their checksum to get a fresh snapshot,
+    /// paced like any other control frame so repeated mismatches can't flood OKX
+    async fn resubscribe_books(&mut self) -> Result<()> {
+        if self.resync.is_empty() {
+            return Ok(());
+        }
+
+        let args = std::mem::take(&mut self.resync);
+        let ws = self.ws.as_mut().ok_or_else(|| GatewayError::Disconnected("Not connected".to_string()))?;
+
+        for op in ["unsubscribe", "subscribe"] {
+            self.rate_limiter.acquire().await;
+            ws.send(Message::Text(json!({ "op": op, "args": args }).to_string())).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Parse incoming message into a MarketEvent, or `None` for non-data frames
+    fn parse_message(&mut self, msg: &str) -> Result<Option<(MarketEvent, String)>> {
+        // Reply to our text keepalive
+        if msg == "pong" {
+            return Ok(None);
+        }
+
         let data: Value = serde_json::from_str(msg)?;
 
-        // Check if this is a subscription confirmation
+        // Subscribe/unsubscribe acks and errors carry an event instead of data
         if let Some(event) = data.get("event").and_then(|e| e.as_str()) {
-            if event == "subscribe" {
-                debug!("Subscription confirmed: {:?}", data);
-                return Err(anyhow!("Subscription confirmation"));
+            if event == "error" {
+                return Err(error_event(&data));
             }
+            debug!("OKX {} event: {:?}", event, data);
+            return Ok(None);
         }
 
-        let arg = data.get("arg").ok_or_else(|| anyhow!("Missing arg"))?;
+        let arg = data.get("arg").ok_or_else(|| GatewayError::Parse("Missing arg".to_string()))?;
         let channel = arg.get("channel")
             .and_then(|c| c.as_str())
-            .ok_or_else(|| anyhow!("Missing channel"))?;
+            .ok_or_else(|| GatewayError::Parse("Missing channel".to_string()))?;
+
+        // Subscribed by instType, so the instrument is only in the data
+        if channel == "liquidation-orders" {
+            let event = self.parse_liquidation(&data)?;
+            return Ok(event.map(|event| {
+                let symbol = event.symbol().to_string();
+                (event, symbol)
+            }));
+        }
+
         let symbol = arg.get("instId")
             .and_then(|s| s.as_str())
-            .ok_or_else(|| anyhow!("Missing instId"))?;
+            .ok_or_else(|| GatewayError::Parse("Missing instId".to_string()))?;
 
         // Parse based on channel type
-        if channel.contains("trade") {
-            let event = self.parse_trade(&data, symbol)?;
-            Ok((event, symbol.to_string()))
+        if channel == "funding-rate" {
+            let event = self.parse_funding_rate(&data, symbol)?;
+            Ok(Some((event, symbol.to_string())))
+        } else if channel.contains("trade") {
+            // Trades filled together share a frame; the rest are returned by later `recv_event` calls
+            let mut trades = self.parse_trades(&data, symbol)?.into_iter();
+            let event = trades.next().ok_or_else(|| GatewayError::Parse("Empty trade data".to_string()))?;
+            self.ready.extend(trades);
+            Ok(Some((event, symbol.to_string())))
         } else if channel.contains("candle") {
             let event = self.parse_kline(&data, symbol, channel)?;
-            Ok((event, symbol.to_string()))
+            Ok(Some((event, symbol.to_string())))
         } else if channel.contains("tickers") {
+            // One tickers channel feeds both best bid/ask and 24h statistics
+            let wants_24h = self.is_subscribed(DataType::Ticker24h, symbol);
+            let wants_book = !wants_24h || self.is_subscribed(DataType::BookTicker, symbol);
+
+            if !wants_book {
+                let event = self.parse_ticker_24h(&data, symbol)?;
+                return Ok(Some((event, symbol.to_string())));
+            }
             let event = self.parse_ticker(&data, symbol)?;
-            Ok((event, symbol.to_string()))
+            if wants_24h {
+                let stats = self.parse_ticker_24h(&data, symbol)?;
+                self.ready.push_back(stats);
+            }
+            Ok(Some((event, symbol.to_string())))
         } else if channel.contains("books") {
-            // For depth updates, return a simplified version
-            let timestamp = data.get("data")
-                .and_then(|d| d.get("ts"))
-                .and_then(|ts| ts.as_i64())
-                .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
-
-            Ok((MarketEvent::DepthUpdate(DepthUpdate {
-                exchange: self.exchange_type,
-                symbol: Self::standard_symbol(symbol),
-                bids: Vec::new(),
-                asks: Vec::new(),
-                timestamp,
-            }), symbol.to_string()))
+            let event = self.parse_books(&data, symbol)?;
+            if let MarketEvent::DepthUpdate(ref depth) = event {
+                // The book is out of sync until its fresh snapshot arrives
+                if !self.update_order_book(&data, depth.is_snapshot)? {
+                    return Ok(None);
+                }
+            }
+            Ok(Some((event, symbol.to_string())))
         } else {
-            Err(anyhow!("Unknown channel: {}", channel))
+            Err(GatewayError::Parse(format!("Unknown channel: {}", channel)))
+        }
+    }
+
+    /// Forward an event to Redis if configured
+    async fn forward(&mut self, event: MarketEvent) -> MarketEvent {
+        let event = if self.derive_book_ticker { event.derive_book_ticker() } else { event };
+        if let Some(ref mut publisher) = self.redis_publisher {
+            if let Err(e) = publisher.publish_event(&event).await {
+                error!("Failed to publish event to Redis: {}", e);
+            }
         }
+        event
+    }
+
+    /// Parse a text frame and forward the resulting event
+    async fn handle_text(&mut self, text: &str) -> Result<Option<MarketEvent>> {
+        // Raw frames are a debugging aid, only queued so they never hold up parsing
+        if let Some(ref publisher) = self.redis_publisher {
+            publisher.publish_raw(self.exchange_type, text);
+        }
+
+        let result = self.parse_message(text);
+        if result.is_ok() {
+            self.parse_errors.record_success();
+        }
+
+        if let Err(e) = self.resubscribe_books().await {
+            error!("Failed to resubscribe OKX books: {}", e);
+        }
+
+        match result {
+            Ok(Some((event, _symbol))) => Ok(Some(self.forward(event).await)),
+            Ok(None) => Ok(None),
+            Err(e @ GatewayError::Exchange { .. }) => {
+                log_error_event(&e);
+                Ok(None)
+            }
+            Err(e) => {
+                debug!("Failed to parse OKX message: {}", e);
+                metrics::global().record_parse_error(self.exchange_type);
+                if self.parse_errors.record_failure(self.exchange_type) {
+                    self.connected = false;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Reject kline subscriptions at an interval OKX has no candle channel for (e.g. 8h),
+/// which would otherwise be tracked without ever being subscribed
+fn check_kline_intervals(subscriptions: &[Subscription]) -> Result<()> {
+    for sub in subscriptions {
+        if let Some(interval) = sub.interval.filter(|interval| interval.as_okx_str().is_none()) {
+            return Err(GatewayError::Config(format!(
+                "OKX has no {} candles for {}", interval.as_str(), sub.symbol
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Error from an OKX `{"event":"error","code":"...","msg":"..."}` frame
+fn error_event(data: &Value) -> GatewayError {
+    GatewayError::Exchange {
+        code: data["code"].as_str().unwrap_or_default().to_string(),
+        message: data["msg"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// Log an OKX error frame, pointing at the subscription when it names a bad channel
+fn log_error_event(error: &GatewayError) {
+    match error {
+        GatewayError::Exchange { code, .. } if OKX_BAD_CHANNEL_CODES.contains(&code.as_str()) => {
+            error!("OKX rejected a subscription, check the channel and instrument: {}", error);
+        }
+        _ => error!("OKX error event: {}", error),
     }
 }
 
@@ -289,12 +1097,37 @@ impl Exchange for OkxClient {
         info!("Connecting to OKX WebSocket at {}", self.ws_url);
 
         let url = Url::parse(&self.ws_url)?;
-        let (ws_stream, _) = connect_async(url).await?;
+        let proxy = self.proxy.clone();
+        let (ws_stream, _) = self.connect_policy
+            .retry_connect("OKX", || proxy::connect_async(url.clone(), proxy.clone()))
+            .await?;
 
         self.ws = Some(ws_stream);
         self.connected = true;
+        self.logged_in = false;
+        self.watchdog.touch();
+        self.keepalive = Some(time::interval_at(
+            time::Instant::now() + KEEPALIVE_INTERVAL,
+            KEEPALIVE_INTERVAL,
+        ));
 
         info!("Connected to OKX WebSocket");
+
+        Ok(())
+    }
+
+    fn active_subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.clone()
+    }
+
+    async fn resubscribe(&mut self) -> Result<()> {
+        // Restore channels that were active before a reconnect
+        if !self.subscriptions.is_empty() {
+            info!("Restoring {} OKX subscriptions", self.subscriptions.len());
+            let msgs = self.resubscribe_msgs();
+            self.send_subscription_msgs(msgs).await?;
+        }
+
         Ok(())
     }
 
@@ -303,37 +1136,59 @@ impl Exchange for OkxClient {
             ws.close(None).await?;
         }
         self.connected = false;
+        self.logged_in = false;
+        self.keepalive = None;
         info!("Disconnected from OKX");
         Ok(())
     }
 
     async fn subscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
         info!("Subscribing to {} OKX data streams", subscriptions.len());
+        check_depth_levels(self.exchange_type, &subscriptions, self.supported_depth_levels())?;
+        check_kline_intervals(&subscriptions)?;
 
         if !self.connected {
             self.connect().await?;
+            self.resubscribe().await?;
         }
 
-        let sub_msg = self.build_subscription_msg(&subscriptions);
-        let msg_str = serde_json::to_string(&sub_msg)?;
-
-        if let Some(ref mut ws) = self.ws {
-            ws.send(Message::Text(msg_str)).await?;
+        // Channels restored by `resubscribe` are already active
+        let added = self.track_subscriptions(subscriptions);
+        if added.is_empty() {
+            debug!("All requested OKX channels are already subscribed");
+            return Ok(());
         }
 
-        // Track symbols
-        for sub in &subscriptions {
-            if !self.symbols.contains(&sub.symbol) {
-                self.symbols.push(sub.symbol.clone());
-            }
-        }
+        let msgs = self.build_subscription_msgs(&added);
+        self.send_subscription_msgs(msgs).await?;
 
         info!("OKX subscription request sent");
         Ok(())
     }
 
-    async fn unsubscribe(&mut self, _subscriptions: Vec<Subscription>) -> Result<()> {
-        warn!("Unsubscribe not fully implemented for OKX");
+    async fn unsubscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        let removed = self.untrack_subscriptions(&subscriptions);
+        if removed.is_empty() {
+            debug!("No OKX channels to unsubscribe");
+            return Ok(());
+        }
+
+        info!("Unsubscribing from {} OKX data streams", removed.len());
+
+        // A later resubscribe must start again from a snapshot
+        for sub in removed.iter().filter(|sub| sub.data_type == DataType::Depth) {
+            let inst_id = self.inst_id(sub);
+            self.order_books.remove(&inst_id);
+        }
+
+        let msgs = self.build_unsubscribe_msgs(&removed);
+        if let Some(ref mut ws) = self.ws {
+            for msg in msgs {
+                self.rate_limiter.acquire().await;
+                ws.send(Message::Text(msg.to_string())).await?;
+            }
+        }
+
         Ok(())
     }
 
@@ -342,26 +1197,44 @@ impl Exchange for OkxClient {
             return Ok(None);
         }
 
+        if let Some(event) = self.ready.pop_front() {
+            return Ok(Some(self.forward(event).await));
+        }
+
+        if let Some(text) = self.pending.pop_front() {
+            return self.handle_text(&text).await;
+        }
+
         let ws = self.ws.as_mut().unwrap();
 
-        match ws.next().await {
-            Some(Ok(Message::Text(text))) => {
-                match self.parse_message(&text) {
-                    Ok((event, _symbol)) => {
-                        // Forward to Redis if configured
-                        if let Some(ref mut publisher) = self.redis_publisher {
-                            if let Err(e) = publisher.publish_event(&event).await {
-                                error!("Failed to publish event to Redis: {}", e);
-                            }
-                        }
-                        Ok(Some(event))
-                    }
-                    Err(e) => {
-                        debug!("Failed to parse OKX message: {}", e);
-                        Ok(None)
-                    }
+        let keepalive = self.keepalive.as_mut();
+        let keepalive_tick = async move {
+            match keepalive {
+                Some(keepalive) => {
+                    keepalive.tick().await;
                 }
+                None => std::future::pending().await,
+            }
+        };
+
+        let msg = tokio::select! {
+            msg = ws.next() => msg,
+            _ = keepalive_tick => {
+                debug!("Sending OKX keepalive ping");
+                ws.send(Message::Text("ping".to_string())).await?;
+                return Ok(None);
+            }
+            _ = self.watchdog.expired() => {
+                warn!("No OKX frame for {:?}, dropping the stale connection", self.watchdog.silence());
+                self.connected = false;
+                return Ok(None);
             }
+        };
+        // Any frame, pings included, shows the connection is alive
+        self.watchdog.touch();
+
+        match msg {
+            Some(Ok(Message::Text(text))) => self.handle_text(&text).await,
             Some(Ok(Message::Ping(payload))) => {
                 ws.send(Message::Pong(payload)).await?;
                 self.recv_event().await
@@ -387,22 +1260,622 @@ impl Exchange for OkxClient {
     }
 
     fn is_connected(&self) -> bool {
-        self.connected
+        self.connected && !self.watchdog.is_stale()
     }
 
     fn ws_endpoint(&self) -> &str {
         &self.ws_url
     }
+
+    async fn fetch_depth_snapshot(&self, symbol: &str, limit: u16) -> Result<DepthUpdate> {
+        // Same instrument as the books channels
+        let inst_id = self.symbol_inst_id(symbol);
+        let url = format!("{}/api/v5/market/books?instId={}&sz={}", OKX_REST, inst_id, limit.min(MAX_REST_DEPTH));
+        info!("Fetching {} order book snapshot from {}", inst_id, url);
+
+        let mut request = self.http.get(&url);
+        if self.demo_trading {
+            request = request.header("x-simulated-trading", "1");
+        }
+        let data: Value = request.send().await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        self.parse_books_snapshot(&data, &inst_id)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_books_snapshot() {
+        let client = OkxClient::new(false);
+        let json = r#"{"code":"0","msg":"","data":[{"asks":[["41006.8","0.60038921","0","1"],["41007.1","1.2","0","3"]],"bids":[["41006.3","0.30178218","0","2"],["41005.9","0.5","0","1"]],"ts":"1629966436396"}]}"#;
+
+        let snapshot = client.parse_books_snapshot(&serde_json::from_str(json).unwrap(), "BTC-USDT").unwrap();
+        assert_eq!(snapshot.exchange, ExchangeType::Okx);
+        assert_eq!(snapshot.symbol, "BTCUSDT");
+        assert!(snapshot.is_snapshot);
+        assert_eq!(snapshot.timestamp, 1629966436396);
+        assert_eq!(snapshot.bids[0], (41006.3, 0.30178218));
+        assert_eq!(snapshot.asks[0], (41006.8, 0.60038921));
+        assert_eq!(snapshot.bids.len(), 2);
+
+        let error = r#"{"code":"51001","msg":"Instrument ID does not exist","data":[]}"#;
+        assert!(client.parse_books_snapshot(&serde_json::from_str(error).unwrap(), "XYZ-USDT").is_err());
+    }
+
     #[test]
     fn test_okx_symbol_conversion() {
-        assert_eq!(OkxClient::okx_symbol("BTCUSDT"), "BTC-USDT");
-        assert_eq!(OkxClient::okx_symbol("ETHUSDT"), "ETH-USDT");
+        assert_eq!(OkxClient::to_okx("BTCUSDT", OkxInstType::Spot), "BTC-USDT");
+        assert_eq!(OkxClient::to_okx("ETHUSDT", OkxInstType::Spot), "ETH-USDT");
+        assert_eq!(OkxClient::from_okx("BTC-USDT"), "BTCUSDT");
+
+        assert_eq!(OkxClient::to_okx("BTCUSDC", OkxInstType::Spot), "BTC-USDC");
+        assert_eq!(OkxClient::to_okx("ETHUSD", OkxInstType::Swap), "ETH-USD-SWAP");
+        assert_eq!(OkxClient::to_okx("ETHBTC", OkxInstType::Spot), "ETH-BTC");
+        assert_eq!(OkxClient::to_okx("BTCUSDT", OkxInstType::Swap), "BTC-USDT-SWAP");
+
+        assert_eq!(OkxClient::from_okx("BTC-USDT-SWAP"), "BTCUSDT");
+        assert_eq!(OkxClient::from_okx("ETH-USD-SWAP"), "ETHUSD");
+        assert_eq!(OkxClient::from_okx("BTC-USDC"), "BTCUSDC");
+    }
+
+    #[test]
+    fn test_swap_inst_type_round_trips() {
+        let client = OkxClient::new(false).with_inst_type("swap".parse().unwrap());
+        assert_eq!(client.inst_type(), OkxInstType::Swap);
+
+        let arg = client.channel_arg(&Subscription::agg_trade("BTCUSDT")).unwrap();
+        assert_eq!(arg, json!({ "channel": "trades", "instId": "BTC-USDT-SWAP" }));
+        assert_eq!(OkxClient::standard_symbol(arg["instId"].as_str().unwrap()), "BTCUSDT-SWAP");
         assert_eq!(OkxClient::standard_symbol("BTC-USDT"), "BTCUSDT");
+        assert_eq!(client.symbol_inst_id("BTCUSDT-SWAP"), "BTC-USDT-SWAP");
+
+        // Spot stays the default
+        let arg = OkxClient::new(false).channel_arg(&Subscription::agg_trade("BTCUSDT")).unwrap();
+        assert_eq!(arg["instId"], "BTC-USDT");
+        assert!("futures".parse::<OkxInstType>().is_err());
+    }
+
+    #[test]
+    fn test_spot_and_swap_subscribed_together() {
+        let mut client = OkxClient::new(false);
+        let spot = Subscription::book_ticker("BTCUSDT");
+        let swap = Subscription::builder()
+            .symbol("BTCUSDT")
+            .data_type(DataType::Ticker24h)
+            .okx_inst_type(OkxInstType::Swap)
+            .build()
+            .unwrap();
+        assert_eq!(swap.to_string(), "BTCUSDT ticker24h SWAP");
+        let added = client.track_subscriptions(vec![spot.clone(), swap.clone()]);
+        assert_eq!(added.len(), 2);
+
+        let msgs = client.build_subscription_msgs(&added);
+        assert_eq!(
+            msgs,
+            vec![json!({
+                "op": "subscribe",
+                "args": [
+                    { "channel": "tickers", "instId": "BTC-USDT" },
+                    { "channel": "tickers", "instId": "BTC-USDT-SWAP" }
+                ]
+            })]
+        );
+
+        // Each instrument yields what was subscribed for it, under its own symbol
+        let spot_tickers = r#"{"arg":{"channel":"tickers","instId":"BTC-USDT"},"data":[{"instType":"SPOT","instId":"BTC-USDT","last":"9999.99","lastSz":"0.1","askPx":"9999.99","askSz":"11","bidPx":"8888.88","bidSz":"5","open24h":"9000","high24h":"10000","low24h":"8888.88","volCcy24h":"2222","vol24h":"2222","sodUtc0":"2222","sodUtc8":"2222","ts":"1597026383085"}]}"#;
+        let swap_tickers = r#"{"arg":{"channel":"tickers","instId":"BTC-USDT-SWAP"},"data":[{"instType":"SWAP","instId":"BTC-USDT-SWAP","last":"10001.5","lastSz":"2","askPx":"10002","askSz":"40","bidPx":"10001","bidSz":"25","open24h":"9010","high24h":"10010","low24h":"8890","volCcy24h":"3333","vol24h":"333300","sodUtc0":"9500","sodUtc8":"9600","ts":"1597026383086"}]}"#;
+        match client.parse_message(spot_tickers) {
+            Ok(Some((MarketEvent::BookTicker(ticker), _))) => assert_eq!(ticker.symbol, "BTCUSDT"),
+            other => panic!("Expected a spot book ticker, got {:?}", other),
+        }
+        match client.parse_message(swap_tickers) {
+            Ok(Some((MarketEvent::Ticker24h(ticker), _))) => assert_eq!(ticker.symbol, "BTCUSDT-SWAP"),
+            other => panic!("Expected swap 24h statistics, got {:?}", other),
+        }
+        assert!(client.ready.is_empty());
+
+        // Dropping the swap leaves the spot channel alone
+        let removed = client.untrack_subscriptions(&[swap]);
+        assert_eq!(
+            client.build_unsubscribe_msgs(&removed)[0]["args"],
+            json!([{ "channel": "tickers", "instId": "BTC-USDT-SWAP" }])
+        );
+        assert_eq!(client.subscriptions, vec![spot]);
+    }
+
+    #[test]
+    fn test_pong_and_acks_are_not_events() {
+        let mut client = OkxClient::new(false);
+
+        assert!(matches!(client.parse_message("pong"), Ok(None)));
+        let ack = r#"{"event":"subscribe","arg":{"channel":"trades","instId":"BTC-USDT"},"connId":"a4d3ae55"}"#;
+        assert!(matches!(client.parse_message(ack), Ok(None)));
+    }
+
+    #[test]
+    fn test_error_event_carries_code_and_message() {
+        let mut client = OkxClient::new(false);
+
+        let frame = r#"{"event":"error","code":"60018","msg":"Wrong URL or channel:tradez,instId:BTC-USDT doesn't exist.","connId":"a4d3ae55"}"#;
+        match client.parse_message(frame) {
+            Err(GatewayError::Exchange { code, message }) => {
+                assert_eq!(code, "60018");
+                assert_eq!(message, "Wrong URL or channel:tradez,instId:BTC-USDT doesn't exist.");
+            }
+            other => panic!("Expected an exchange error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unsubscribe_message() {
+        let mut client = OkxClient::new(false);
+        let trades = Subscription {
+            symbol: "BTCUSDT".to_string(),
+            data_type: DataType::AggTrade,
+            interval: None,
+            contract_type: None,
+            depth_levels: None,
+            update_speed: None,
+            okx_inst_type: None,
+        };
+        let tickers = Subscription {
+            symbol: "ETHUSDT".to_string(),
+            data_type: DataType::BookTicker,
+            interval: None,
+            contract_type: None,
+            depth_levels: None,
+            update_speed: None,
+            okx_inst_type: None,
+        };
+        client.track_subscriptions(vec![trades.clone(), tickers.clone()]);
+
+        let removed = client.untrack_subscriptions(&[trades, tickers]);
+        assert!(client.subscriptions.is_empty());
+
+        let msgs = client.build_unsubscribe_msgs(&removed);
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(
+            msgs[0],
+            json!({
+                "op": "unsubscribe",
+                "args": [
+                    { "channel": "trades", "instId": OkxClient::to_okx("BTCUSDT", OkxInstType::Spot) },
+                    { "channel": "tickers", "instId": OkxClient::to_okx("ETHUSDT", OkxInstType::Spot) }
+                ]
+            })
+        );
+
+        let ack = r#"{"event":"unsubscribe","arg":{"channel":"trades","instId":"BTC-USDT"},"connId":"a4d3ae55"}"#;
+        assert!(matches!(client.parse_message(ack), Ok(None)));
+    }
+
+    #[test]
+    fn test_shared_liquidation_channel_stays_subscribed() {
+        let mut client = OkxClient::new(false);
+        let liquidations: Vec<Subscription> = ["BTCUSDT", "ETHUSDT"].iter()
+            .map(|symbol| Subscription {
+                symbol: symbol.to_string(),
+                data_type: DataType::Liquidation,
+                interval: None,
+                contract_type: None,
+                depth_levels: None,
+                update_speed: None,
+                okx_inst_type: None,
+            })
+            .collect();
+        client.track_subscriptions(liquidations.clone());
+
+        // ETHUSDT still needs the instType-wide channel
+        assert!(client.untrack_subscriptions(&liquidations[..1]).is_empty());
+        assert_eq!(client.untrack_subscriptions(&liquidations[1..]).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_kline_channels_use_okx_bars() {
+        let kline = |interval| Subscription::kline("BTCUSDT", interval);
+
+        let mut client = OkxClient::new(false);
+
+        assert_eq!(client.channel_arg(&kline(KlineInterval::OneHour)).unwrap()["channel"], "candle1H");
+        assert_eq!(client.channel_arg(&kline(KlineInterval::OneWeek)).unwrap()["channel"], "candle1W");
+        assert_eq!(client.channel_arg(&kline(KlineInterval::OneMonth)).unwrap()["channel"], "candle1M");
+        assert!(client.channel_arg(&kline(KlineInterval::EightHours)).is_none());
+
+        // An interval without a channel is refused up front rather than tracked but never subscribed
+        assert!(check_kline_intervals(&[kline(KlineInterval::OneHour), kline(KlineInterval::OneWeek)]).is_ok());
+        let err = check_kline_intervals(&[kline(KlineInterval::OneHour), kline(KlineInterval::EightHours)]).unwrap_err();
+        assert!(matches!(err, GatewayError::Config(_)));
+        assert!(err.to_string().contains("8h"), "{}", err);
+        let err = client.subscribe(vec![kline(KlineInterval::EightHours)]).await.unwrap_err();
+        assert!(matches!(err, GatewayError::Config(_)));
+        assert!(client.active_subscriptions().is_empty());
+
+        // Every standard interval yields a duration without panicking
+        for interval in KlineInterval::ALL {
+            assert!(client.interval_ms(interval.as_str()) > 0);
+        }
+    }
+
+    #[test]
+    fn test_depth_levels_pick_books_channel() {
+        let client = OkxClient::new(false);
+        let channel = |sub: Subscription| client.channel_arg(&sub).unwrap()["channel"].clone();
+
+        assert_eq!(channel(Subscription::partial_depth("BTCUSDT", 5)), "books5");
+        assert_eq!(channel(Subscription::partial_depth("BTCUSDT", 400)), "books");
+        assert_eq!(channel(Subscription::depth("BTCUSDT")), "books");
+
+        let unsupported = [Subscription::partial_depth("BTCUSDT", 20)];
+        assert!(check_depth_levels(ExchangeType::Okx, &unsupported, &SUPPORTED_DEPTH_LEVELS).is_err());
+
+        // A logged-in client streams tick by tick
+        let client = OkxClient::new(false).with_credentials("key", "secret", "passphrase");
+        let channel = |sub: Subscription| client.channel_arg(&sub).unwrap()["channel"].clone();
+        assert_eq!(channel(Subscription::partial_depth("BTCUSDT", 5)), "books5");
+        assert_eq!(channel(Subscription::partial_depth("BTCUSDT", 50)), "books50-l2-tbt");
+        assert_eq!(channel(Subscription::depth("BTCUSDT")), "books-l2-tbt");
+        assert!(check_depth_levels(ExchangeType::Okx, &[Subscription::partial_depth("BTCUSDT", 50)], client.supported_depth_levels()).is_ok());
+    }
+
+    #[test]
+    fn test_login_signature() {
+        let client = OkxClient::new(false).with_credentials("my-key", "22582BD0CFF14C41EDBF1AB98506286D", "my-passphrase");
+        let login = client.build_login_msg("1538054050").unwrap();
+
+        assert_eq!(login["op"], "login");
+        let args = &login["args"][0];
+        assert_eq!(args["apiKey"], "my-key");
+        assert_eq!(args["passphrase"], "my-passphrase");
+        assert_eq!(args["timestamp"], "1538054050");
+        // HMAC-SHA256 of "1538054050GET/users/self/verify", base64 encoded
+        assert_eq!(args["sign"], "+LdIr8lkkvhr5hoA3g9TMC0+uQJ849ftAcocA/ouu4M=");
+
+        assert!(OkxClient::new(false).build_login_msg("1538054050").is_none());
+    }
+
+    #[test]
+    fn test_parse_candle() {
+        let client = OkxClient::new(false);
+        let msg = |confirm: &str| serde_json::json!({
+            "arg": {"channel": "candle1m", "instId": "BTC-USDT"},
+            "data": [["1700000040000", "37000.1", "37010.5", "36990.2", "37005.3", "12.5", "462500.0", "462500.0", confirm]]
+        });
+
+        let MarketEvent::Kline(kline) = client.parse_kline(&msg("1"), "BTC-USDT", "candle1m").unwrap() else {
+            panic!("Expected Kline event");
+        };
+        assert_eq!(kline.symbol, "BTCUSDT");
+        assert_eq!((kline.open_time, kline.close_time), (1700000040000, 1700000099999));
+        assert_eq!((kline.open, kline.high, kline.low, kline.close), (37000.1, 37010.5, 36990.2, 37005.3));
+        assert_eq!(kline.volume, 12.5);
+        assert!(kline.is_closed);
+
+        let MarketEvent::Kline(kline) = client.parse_kline(&msg("0"), "BTC-USDT", "candle1m").unwrap() else {
+            panic!("Expected Kline event");
+        };
+        assert!(!kline.is_closed);
+    }
+
+    #[test]
+    fn test_parse_documented_candle() {
+        // Push example from the OKX v5 docs for the candle channel
+        let client = OkxClient::new(false);
+        let msg = serde_json::json!({
+            "arg": {"channel": "candle1D", "instId": "BTC-USDT"},
+            "data": [["1597026383085", "8533.02", "8553.74", "8527.17", "8548.26", "45247", "529.5858061", "529.5858061", "0"]]
+        });
+
+        let MarketEvent::Kline(kline) = client.parse_kline(&msg, "BTC-USDT", "candle1D").unwrap() else {
+            panic!("Expected Kline event");
+        };
+        assert_eq!(kline.interval, "1d");
+        assert_eq!(kline.open_time, 1597026383085);
+        assert_eq!(kline.open, 8533.02);
+        assert_eq!(kline.high, 8553.74);
+        assert_eq!(kline.low, 8527.17);
+        assert_eq!(kline.close, 8548.26);
+        assert_eq!(kline.volume, 45247.0);
+        assert!(!kline.is_closed);
+    }
+
+    #[test]
+    fn test_week_and_month_close_time() {
+        let client = OkxClient::new(false);
+
+        // Week starting 2024-01-01 00:00 UTC+8
+        let week_open = 1704038400000;
+        assert_eq!(client.close_time(week_open, "1w"), week_open + 7 * 86_400_000 - 1);
+
+        // January 2024 (31 days) starting 2024-01-01 00:00 UTC+8
+        let month_open = 1704038400000;
+        assert_eq!(client.close_time(month_open, "1M"), 1706716799999);
+        assert_eq!(client.close_time(month_open, "1M") - month_open + 1, 31 * 86_400_000);
+
+        // February 2024 is a 29-day month
+        let feb_open = 1706716800000;
+        assert_eq!(client.close_time(feb_open, "1M") - feb_open + 1, 29 * 86_400_000);
+
+        // Minutes are unaffected by the month suffix
+        assert_eq!(client.close_time(0, "1m"), 59_999);
+    }
+
+    #[test]
+    fn test_parse_funding_rate() {
+        let mut client = OkxClient::new(false);
+        let json = r#"{"arg":{"channel":"funding-rate","instId":"BTC-USDT-SWAP"},"data":[{"fundingRate":"0.0001875391284828","fundingTime":"1700726400000","instId":"BTC-USDT-SWAP","instType":"SWAP","method":"current_period","nextFundingRate":"","nextFundingTime":"1700755200000","ts":"1700724675402"}]}"#;
+
+        if let Ok(Some((MarketEvent::FundingRate(funding), _))) = client.parse_message(json) {
+            assert_eq!(funding.symbol, "BTCUSDT-SWAP");
+            assert_eq!(funding.funding_rate, 0.0001875391284828);
+            assert_eq!(funding.next_funding_time, 1700726400000);
+            assert_eq!(funding.timestamp, 1700724675402);
+        } else {
+            panic!("Expected FundingRate event");
+        }
+    }
+
+    #[test]
+    fn test_parse_tickers() {
+        let mut client = OkxClient::new(false);
+        let json = r#"{"arg":{"channel":"tickers","instId":"BTC-USDT"},"data":[{"instType":"SPOT","instId":"BTC-USDT","last":"9999.99","lastSz":"0.1","askPx":"9999.99","askSz":"11","bidPx":"8888.88","bidSz":"5","open24h":"9000","high24h":"10000","low24h":"8888.88","volCcy24h":"2222","vol24h":"2222","sodUtc0":"2222","sodUtc8":"2222","ts":"1597026383085"}]}"#;
+
+        // Without a 24h subscription the channel only yields best bid/ask
+        assert!(matches!(client.parse_message(json), Ok(Some((MarketEvent::BookTicker(_), _)))));
+        assert!(client.ready.is_empty());
+
+        client.track_subscriptions(vec![Subscription::book_ticker("BTCUSDT"), Subscription::ticker_24h("BTCUSDT")]);
+        assert!(matches!(client.parse_message(json), Ok(Some((MarketEvent::BookTicker(_), _)))));
+        if let Some(MarketEvent::Ticker24h(ticker)) = client.ready.pop_front() {
+            assert_eq!(ticker.symbol, "BTCUSDT");
+            assert_eq!(ticker.last_price, 9999.99);
+            assert_eq!(ticker.open_price, 9000.0);
+            assert_eq!(ticker.high_price, 10000.0);
+            assert_eq!(ticker.low_price, 8888.88);
+            assert_eq!(ticker.volume, 2222.0);
+            assert!((ticker.price_change - 999.99).abs() < 1e-9);
+            assert!((ticker.price_change_percent - 11.111).abs() < 1e-9);
+            assert_eq!(ticker.timestamp, 1597026383085);
+        } else {
+            panic!("Expected Ticker24h event");
+        }
+    }
+
+    #[test]
+    fn test_parse_liquidation() {
+        let mut client = OkxClient::new(false);
+        client.track_subscriptions(vec![Subscription {
+            symbol: "BTCUSDT".to_string(),
+            data_type: DataType::Liquidation,
+            interval: None,
+            contract_type: None,
+            depth_levels: None,
+            update_speed: None,
+            okx_inst_type: None,
+        }]);
+        let json = r#"{"arg":{"channel":"liquidation-orders","instType":"SWAP"},"data":[{"details":[{"bkLoss":"0","bkPx":"35366.7","ccy":"","posSide":"long","side":"sell","sz":"12","ts":"1700725200000"}],"instFamily":"BTC-USDT","instId":"BTC-USDT-SWAP","instType":"SWAP","uly":"BTC-USDT"}]}"#;
+
+        if let Ok(Some((MarketEvent::Liquidation(liquidation), _))) = client.parse_message(json) {
+            assert_eq!(liquidation.symbol, "BTCUSDT-SWAP");
+            assert_eq!(liquidation.side, Side::Sell);
+            assert_eq!(liquidation.price, 35366.7);
+            assert_eq!(liquidation.quantity, 12.0);
+        } else {
+            panic!("Expected Liquidation event");
+        }
+
+        // Liquidations for instruments we don't track are dropped
+        let other = json.replace("BTC-USDT-SWAP", "ETH-USDT-SWAP");
+        assert!(matches!(client.parse_message(&other), Ok(None)));
+    }
+
+    #[test]
+    fn test_parse_books() {
+        let mut client = OkxClient::new(false);
+        let json = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[["8476.98","415","0","13"],["8477","7","0","2"]],"bids":[["8476.97","256","0","12"],["8475.55","101","0","1"]],"ts":"1597026383085","checksum":2123921068,"prevSeqId":-1,"seqId":123456}]}"#;
+
+        let result = client.parse_message(json);
+
+        if let Ok(Some((MarketEvent::DepthUpdate(depth), _))) = result {
+            assert_eq!(depth.symbol, "BTCUSDT");
+            assert_eq!(depth.bids[0], (8476.97, 256.0));
+            assert_eq!(depth.asks[0], (8476.98, 415.0));
+            assert_eq!(depth.bids.len(), 2);
+            assert_eq!(depth.timestamp, 1597026383085);
+            assert!(depth.is_snapshot);
+        } else {
+            panic!("Expected DepthUpdate event");
+        }
+
+        let update = json.replace(r#""action":"snapshot""#, r#""action":"update""#);
+        if let Ok(Some((MarketEvent::DepthUpdate(depth), _))) = client.parse_message(&update) {
+            assert!(!depth.is_snapshot);
+        } else {
+            panic!("Expected DepthUpdate event");
+        }
+
+        // No exchange time means a malformed frame, not one to date locally
+        let untimed = json.replace(r#""ts":"1597026383085","#, "");
+        assert!(matches!(client.parse_message(&untimed), Err(GatewayError::Parse(_))));
+    }
+
+    #[test]
+    fn test_subscription_batches() {
+        let client = OkxClient::new(false).with_subscribe_batch_size(40);
+        let subscriptions: Vec<Subscription> = (0..300)
+            .map(|i| Subscription {
+                symbol: format!("SYM{}USDT", i),
+                data_type: DataType::AggTrade,
+                interval: None,
+                contract_type: None,
+                depth_levels: None,
+                update_speed: None,
+                okx_inst_type: None,
+            })
+            .collect();
+
+        let msgs = client.build_subscription_msgs(&subscriptions);
+
+        assert_eq!(msgs.len(), 8);
+        assert_eq!(msgs[0]["args"].as_array().unwrap().len(), 40);
+        assert_eq!(msgs[7]["args"].as_array().unwrap().len(), 20);
+    }
+
+    #[test]
+    fn test_subscribe_frames_hold_at_most_64_channels() {
+        let client = OkxClient::new(false).with_subscribe_batch_size(100);
+        let subscriptions: Vec<Subscription> = (0..150)
+            .map(|i| Subscription {
+                symbol: format!("SYM{}USDT", i),
+                data_type: DataType::AggTrade,
+                interval: None,
+                contract_type: None,
+                depth_levels: None,
+                update_speed: None,
+                okx_inst_type: None,
+            })
+            .collect();
+
+        let msgs = client.build_subscription_msgs(&subscriptions);
+
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(msgs[0]["args"].as_array().unwrap().len(), 64);
+        assert_eq!(msgs[1]["args"].as_array().unwrap().len(), 64);
+        assert_eq!(msgs[2]["args"].as_array().unwrap().len(), 22);
+    }
+
+    #[test]
+    fn test_resubscribe_restores_tracked_channels() {
+        let mut client = OkxClient::new(false);
+        let trades = Subscription {
+            symbol: "BTCUSDT".to_string(),
+            data_type: DataType::AggTrade,
+            interval: None,
+            contract_type: None,
+            depth_levels: None,
+            update_speed: None,
+            okx_inst_type: None,
+        };
+        let klines = Subscription {
+            symbol: "BTCUSDT".to_string(),
+            data_type: DataType::Kline,
+            interval: Some(KlineInterval::FiveMinutes),
+            contract_type: None,
+            depth_levels: None,
+            update_speed: None,
+            okx_inst_type: None,
+        };
+
+        let added = client.track_subscriptions(vec![trades.clone(), klines.clone()]);
+        assert_eq!(added.len(), 2);
+
+        // Subscribing again after a reconnect adds nothing new
+        assert!(client.track_subscriptions(vec![trades, klines]).is_empty());
+
+        let msgs = client.resubscribe_msgs();
+        assert_eq!(msgs.len(), 1);
+
+        let args = msgs[0]["args"].as_array().unwrap();
+        let channels: Vec<&str> = args.iter().map(|a| a["channel"].as_str().unwrap()).collect();
+        assert_eq!(channels, vec!["trades", "candle5m"]);
+        for arg in args {
+            assert_eq!(arg["instId"], OkxClient::to_okx("BTCUSDT", OkxInstType::Spot));
+        }
+    }
+
+    #[test]
+    fn test_checksum_mismatch_queues_resubscribe() {
+        let mut client = OkxClient::new(false);
+        let json = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[["3366.8","9","10","3"],["3368","8","3","4"]],"bids":[["3366.1","7","0","3"],["3366","6","3","4"]],"ts":"1597026383085","checksum":-1881014294}]}"#;
+
+        assert!(matches!(client.parse_message(json), Ok(Some(_))));
+        assert!(client.resync.is_empty());
+
+        // An update whose checksum no longer matches the local book is dropped, without counting as a parse error
+        let mismatches = || metrics::global().snapshot().exchanges.get("okx").map_or(0, |okx| okx.checksum_mismatches);
+        let before = mismatches();
+        let update = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"update","data":[{"asks":[],"bids":[["3366","0","0","0"]],"ts":"1597026383185","checksum":-1881014294}]}"#;
+        assert!(matches!(client.parse_message(update), Ok(None)));
+        assert_eq!(client.resync, vec![json!({"channel": "books", "instId": "BTC-USDT"})]);
+        assert!(!client.order_books.contains_key("BTC-USDT"));
+        assert!(mismatches() > before);
+
+        // Further updates wait for the fresh snapshot
+        assert!(matches!(client.parse_message(update), Ok(None)));
+        assert_eq!(client.resync.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_book_resubscribes_are_rate_limited() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("ws://{}/ws/v5/public", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut ops = Vec::new();
+            while ops.len() < 2 {
+                if let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let frame: Value = serde_json::from_str(&text).unwrap();
+                    ops.push(frame["op"].as_str().unwrap().to_string());
+                }
+            }
+            ops
+        });
+
+        let mut client = OkxClient::new(false).with_endpoint(endpoint).with_rate_limit(1);
+        client.connect().await.unwrap();
+        client.resync.push(json!({"channel": "books", "instId": "BTC-USDT"}));
+
+        // The unsubscribe takes the only token, so the subscribe waits a second for the next
+        time::pause();
+        let start = time::Instant::now();
+        client.resubscribe_books().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert_eq!(server.await.unwrap(), ["unsubscribe", "subscribe"]);
+    }
+
+    #[test]
+    fn test_parse_trades_emits_every_trade() {
+        let mut client = OkxClient::new(false);
+        let json = r#"{"arg":{"channel":"trades","instId":"BTC-USDT-SWAP"},"data":[{"instId":"BTC-USDT-SWAP","tradeId":"130639474","px":"42219.9","sz":"0.12060306","side":"buy","ts":"1630048897897","count":"3"},{"instId":"BTC-USDT-SWAP","tradeId":"130639475","px":"42220.1","sz":"2","side":"sell","ts":"1630048897897","count":"1"}]}"#;
+
+        let first = match client.parse_message(json) {
+            Ok(Some((MarketEvent::AggTrade(trade), _))) => trade,
+            other => panic!("Expected a trade, got {:?}", other),
+        };
+        assert_eq!((first.trade_id, first.price, first.is_buyer_maker), (130639474, 42219.9, false));
+        assert_eq!(first.symbol, "BTCUSDT-SWAP");
+
+        // The second fill of the frame waits for the next `recv_event`, with its own id
+        match client.ready.pop_front() {
+            Some(MarketEvent::AggTrade(second)) => {
+                assert_eq!((second.trade_id, second.quantity, second.is_buyer_maker), (130639475, 2.0, true));
+                assert_eq!(second.timestamp, first.timestamp);
+            }
+            other => panic!("Expected a second trade, got {:?}", other),
+        }
+        assert!(client.ready.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_with_endpoint_connects_there() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("ws://{}/ws/v5/public", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio_tungstenite::accept_async(stream).await.unwrap()
+        });
+
+        let mut client = OkxClient::new(false).with_endpoint(endpoint.clone());
+        assert_eq!(client.ws_endpoint(), endpoint);
+
+        client.connect().await.unwrap();
+        assert!(client.is_connected());
+        server.await.unwrap();
     }
 }
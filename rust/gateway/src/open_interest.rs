@@ -0,0 +1,224 @@
+//! Open interest REST poller
+//!
+//! This module polls open interest over REST, since it is not available
+//! on the exchange WebSocket streams, and forwards it as market events.
+
+use crate::clock::{self, SharedClock};
+use crate::exchange::{ExchangeType, MarketEvent, OpenInterest};
+use crate::orderbook::{BINANCE_FUTURES_REST, BINANCE_FUTURES_TESTNET_REST, OKX_REST};
+use crate::redis_publisher::RedisPublisher;
+use crate::symbol::{from_exchange_symbol, to_exchange_symbol};
+use crate::error::{GatewayError, Result};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::{debug, error, info, warn};
+
+/// Default time between polls
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Polls open interest for a set of symbols on one exchange
+pub struct OpenInterestPoller {
+    exchange_type: ExchangeType,
+    rest_url: String,
+    testnet: bool,
+    symbols: Vec<String>,
+    interval: Duration,
+    http: reqwest::Client,
+    redis_publisher: Option<RedisPublisher>,
+    /// Stamps `received_at` on each result
+    clock: SharedClock,
+}
+
+impl OpenInterestPoller {
+    /// Create a poller, or `None` if the exchange has no open interest endpoint wired up
+    pub fn new(exchange_type: ExchangeType, testnet: bool, symbols: Vec<String>) -> Option<Self> {
+        let rest_url = match (exchange_type, testnet) {
+            (ExchangeType::Binance, false) => BINANCE_FUTURES_REST,
+            (ExchangeType::Binance, true) => BINANCE_FUTURES_TESTNET_REST,
+            (ExchangeType::Okx, _) => OKX_REST,
+            (ExchangeType::Bybit | ExchangeType::Coinbase | ExchangeType::Kucoin | ExchangeType::Deribit
+            | ExchangeType::Gateio | ExchangeType::Bitget, _) => {
+                return None
+            }
+        };
+
+        Some(Self {
+            exchange_type,
+            rest_url: rest_url.to_string(),
+            testnet,
+            symbols,
+            interval: DEFAULT_POLL_INTERVAL,
+            http: reqwest::Client::new(),
+            redis_publisher: None,
+            clock: clock::system(),
+        })
+    }
+
+    /// Set the time between polls
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the Redis publisher for forwarding events
+    pub fn with_redis_publisher(mut self, publisher: RedisPublisher) -> Self {
+        self.redis_publisher = Some(publisher);
+        self
+    }
+
+    /// Stamp results with this clock instead of the host's, e.g. a mock in tests
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Parse a Binance `/fapi/v1/openInterest` response received at `received_at`
+    pub fn parse_binance(data: &Value, received_at: i64) -> Result<OpenInterest> {
+        let symbol = data["symbol"].as_str().ok_or_else(|| GatewayError::Parse("Missing symbol".to_string()))?
+            .to_string();
+        let open_interest = data["openInterest"].as_str().ok_or_else(|| GatewayError::Parse("Missing openInterest".to_string()))?
+            .parse::<f64>()?;
+        let timestamp = data["time"].as_i64().ok_or_else(|| GatewayError::Parse("Missing time".to_string()))?;
+
+        Ok(OpenInterest {
+            exchange: ExchangeType::Binance,
+            symbol,
+            open_interest,
+            timestamp,
+            received_at,
+        })
+    }
+
+    /// Parse an OKX `/api/v5/public/open-interest` response received at `received_at`
+    pub fn parse_okx(data: &Value, received_at: i64) -> Result<OpenInterest> {
+        if data["code"].as_str() != Some("0") {
+            return Err(GatewayError::Parse(format!("OKX open interest error: {}", data["msg"])));
+        }
+
+        let entry = data["data"].get(0).ok_or_else(|| GatewayError::Parse("Empty open interest data".to_string()))?;
+        let inst_id = entry["instId"].as_str().ok_or_else(|| GatewayError::Parse("Missing instId".to_string()))?;
+        // oiCcy is in the base currency, matching Binance; oi is in contracts
+        let open_interest = entry["oiCcy"].as_str().ok_or_else(|| GatewayError::Parse("Missing oiCcy".to_string()))?
+            .parse::<f64>()?;
+        let timestamp = entry["ts"].as_str().ok_or_else(|| GatewayError::Parse("Missing timestamp".to_string()))?
+            .parse::<i64>()?;
+
+        Ok(OpenInterest {
+            exchange: ExchangeType::Okx,
+            symbol: from_exchange_symbol(ExchangeType::Okx, inst_id),
+            open_interest,
+            timestamp,
+            received_at,
+        })
+    }
+
+    /// Fetch open interest for one symbol
+    async fn fetch(&self, symbol: &str) -> Result<OpenInterest> {
+        match self.exchange_type {
+            ExchangeType::Binance => {
+                let url = format!("{}/fapi/v1/openInterest?symbol={}", self.rest_url, symbol);
+                let data: Value = self.http.get(&url).send().await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Self::parse_binance(&data, self.clock.now_ms())
+            }
+            ExchangeType::Okx => {
+                let url = format!(
+                    "{}/api/v5/public/open-interest?instType=SWAP&instId={}-SWAP",
+                    self.rest_url, to_exchange_symbol(ExchangeType::Okx, symbol)
+                );
+                let mut request = self.http.get(&url);
+                if self.testnet {
+                    request = request.header("x-simulated-trading", "1");
+                }
+                let data: Value = request.send().await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Self::parse_okx(&data, self.clock.now_ms())
+            }
+            ExchangeType::Bybit | ExchangeType::Coinbase | ExchangeType::Kucoin | ExchangeType::Deribit
+            | ExchangeType::Gateio | ExchangeType::Bitget => {
+                Err(GatewayError::Config(format!("Open interest polling is not supported for {}", self.exchange_type)))
+            }
+        }
+    }
+
+    /// Poll every symbol once, publishing and forwarding each result
+    async fn poll(&mut self, tx: &mpsc::Sender<MarketEvent>) -> Result<()> {
+        for symbol in self.symbols.clone() {
+            let event = match self.fetch(&symbol).await {
+                Ok(open_interest) => MarketEvent::OpenInterest(open_interest),
+                Err(e) => {
+                    warn!("Failed to fetch {} open interest for {}: {}", self.exchange_type, symbol, e);
+                    continue;
+                }
+            };
+
+            if let Some(ref mut publisher) = self.redis_publisher {
+                if let Err(e) = publisher.publish_event(&event).await {
+                    error!("Failed to publish event to Redis: {}", e);
+                }
+            }
+
+            tx.send(event).await.map_err(|_| GatewayError::Disconnected("Event receiver dropped".to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Poll on a fixed interval until the receiving side is dropped
+    pub fn spawn(mut self, tx: mpsc::Sender<MarketEvent>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            info!(
+                "Polling {} open interest for {} symbols every {:?}",
+                self.exchange_type, self.symbols.len(), self.interval
+            );
+            let mut ticker = time::interval(self.interval);
+
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.poll(&tx).await {
+                    debug!("Stopping {} open interest poller: {}", self.exchange_type, e);
+                    return;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_binance_open_interest() {
+        let json = r#"{"openInterest":"10659.509","symbol":"BTCUSDT","time":1589437530011}"#;
+        let oi = OpenInterestPoller::parse_binance(&serde_json::from_str(json).unwrap(), 1589437530042).unwrap();
+
+        assert_eq!(oi.exchange, ExchangeType::Binance);
+        assert_eq!(oi.symbol, "BTCUSDT");
+        assert_eq!(oi.open_interest, 10659.509);
+        assert_eq!(oi.timestamp, 1589437530011);
+        assert_eq!(oi.received_at, 1589437530042);
+    }
+
+    #[test]
+    fn test_parse_okx_open_interest() {
+        let json = r#"{"code":"0","data":[{"instId":"BTC-USDT-SWAP","instType":"SWAP","oi":"5000","oiCcy":"555.55","oiUsd":"50000","ts":"1597026383085"}],"msg":""}"#;
+        let oi = OpenInterestPoller::parse_okx(&serde_json::from_str(json).unwrap(), 1597026383100).unwrap();
+
+        assert_eq!(oi.exchange, ExchangeType::Okx);
+        assert_eq!(oi.symbol, "BTCUSDT");
+        assert_eq!(oi.open_interest, 555.55);
+        assert_eq!(oi.timestamp, 1597026383085);
+
+        let error = r#"{"code":"51001","data":[],"msg":"Instrument ID does not exist"}"#;
+        assert!(OpenInterestPoller::parse_okx(&serde_json::from_str(error).unwrap(), 1597026383100).is_err());
+    }
+}
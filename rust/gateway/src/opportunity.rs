@@ -0,0 +1,153 @@
+//! Cross-exchange spread alerts
+//!
+//! This module watches the consolidated top of book and raises an
+//! `Opportunity` when one exchange bids above another's ask by more than a
+//! threshold in basis points. Once raised, a symbol stays quiet until the
+//! spread falls back below the threshold minus a hysteresis band, so a
+//! spread hovering at the threshold doesn't alert on every tick.
+
+use crate::exchange::{ExchangeType, Nbbo};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Hysteresis band under the threshold, in basis points
+pub const DEFAULT_HYSTERESIS_BPS: f64 = 2.0;
+
+/// Crossed market wide enough to buy on one exchange and sell on another
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Opportunity {
+    pub symbol: String,
+    /// Exchange with the low ask, to buy on
+    pub buy_exchange: ExchangeType,
+    pub ask_price: f64,
+    /// Exchange with the high bid, to sell on
+    pub sell_exchange: ExchangeType,
+    pub bid_price: f64,
+    /// Bid minus ask over the ask, in basis points
+    pub spread_bps: f64,
+    /// Size both tops can fill
+    pub quantity: f64,
+    /// Bid minus ask times `quantity`, in the quote asset, before fees
+    pub edge: f64,
+    /// Exchange event time of the quote that opened the spread (ms)
+    pub timestamp: i64,
+    /// When the gateway received that quote (wall-clock ms)
+    pub received_at: i64,
+}
+
+/// Raises an opportunity when the consolidated spread crosses a threshold
+#[derive(Debug)]
+pub struct SpreadDetector {
+    threshold_bps: f64,
+    hysteresis_bps: f64,
+    /// Symbols with an opportunity raised and not yet closed
+    open: HashSet<String>,
+}
+
+impl SpreadDetector {
+    /// Create a detector alerting on spreads of at least `threshold_bps`
+    pub fn new(threshold_bps: f64) -> Self {
+        Self {
+            threshold_bps,
+            hysteresis_bps: DEFAULT_HYSTERESIS_BPS,
+            open: HashSet::new(),
+        }
+    }
+
+    /// Keep an opportunity open until the spread falls this many basis points under the threshold
+    pub fn with_hysteresis_bps(mut self, hysteresis_bps: f64) -> Self {
+        self.hysteresis_bps = hysteresis_bps.max(0.0);
+        self
+    }
+
+    /// Check if an opportunity is open for a symbol
+    pub fn is_open(&self, symbol: &str) -> bool {
+        self.open.contains(symbol)
+    }
+
+    /// Check a new consolidated top, returning an opportunity when one opens
+    pub fn check(&mut self, nbbo: &Nbbo) -> Option<Opportunity> {
+        if nbbo.best_ask <= 0.0 || nbbo.best_bid_exchange == nbbo.best_ask_exchange {
+            self.open.remove(&nbbo.symbol);
+            return None;
+        }
+        let spread_bps = (nbbo.best_bid - nbbo.best_ask) / nbbo.best_ask * 10_000.0;
+
+        if self.open.contains(&nbbo.symbol) {
+            if spread_bps < self.threshold_bps - self.hysteresis_bps {
+                self.open.remove(&nbbo.symbol);
+            }
+            return None;
+        }
+        if spread_bps < self.threshold_bps {
+            return None;
+        }
+
+        self.open.insert(nbbo.symbol.clone());
+        let quantity = nbbo.best_bid_qty.min(nbbo.best_ask_qty);
+        Some(Opportunity {
+            symbol: nbbo.symbol.clone(),
+            buy_exchange: nbbo.best_ask_exchange,
+            ask_price: nbbo.best_ask,
+            sell_exchange: nbbo.best_bid_exchange,
+            bid_price: nbbo.best_bid,
+            spread_bps,
+            quantity,
+            edge: (nbbo.best_bid - nbbo.best_ask) * quantity,
+            timestamp: nbbo.timestamp,
+            received_at: nbbo.received_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nbbo(best_bid: f64, best_ask: f64) -> Nbbo {
+        Nbbo {
+            symbol: "BTCUSDT".to_string(),
+            best_bid,
+            best_bid_qty: 2.0,
+            best_bid_exchange: ExchangeType::Binance,
+            best_ask,
+            best_ask_qty: 0.5,
+            best_ask_exchange: ExchangeType::Okx,
+            spread: best_ask - best_bid,
+            timestamp: 1_000,
+            received_at: 1_005,
+        }
+    }
+
+    #[test]
+    fn test_wide_crossed_spread_is_an_opportunity() {
+        let mut detector = SpreadDetector::new(10.0);
+
+        // A 0.01 gap is 1 bp, under the threshold
+        assert!(detector.check(&nbbo(100.0, 99.99)).is_none());
+
+        let opportunity = detector.check(&nbbo(100.0, 99.5)).unwrap();
+        assert_eq!((opportunity.buy_exchange, opportunity.ask_price), (ExchangeType::Okx, 99.5));
+        assert_eq!((opportunity.sell_exchange, opportunity.bid_price), (ExchangeType::Binance, 100.0));
+        assert!((opportunity.spread_bps - 50.25).abs() < 0.01);
+        assert_eq!(opportunity.quantity, 0.5);
+        assert!((opportunity.edge - 0.25).abs() < 1e-9);
+        assert_eq!(opportunity.timestamp, 1_000);
+    }
+
+    #[test]
+    fn test_hysteresis_keeps_an_open_spread_quiet() {
+        let mut detector = SpreadDetector::new(10.0).with_hysteresis_bps(5.0);
+
+        assert!(detector.check(&nbbo(100.0, 99.8)).is_some());
+        // Dipping under the threshold but not the band, then back over it
+        assert!(detector.check(&nbbo(100.0, 99.92)).is_none());
+        assert!(detector.check(&nbbo(100.0, 99.8)).is_none());
+        assert!(detector.is_open("BTCUSDT"));
+
+        // Closing below threshold minus hysteresis re-arms the symbol
+        assert!(detector.check(&nbbo(100.0, 99.99)).is_none());
+        assert!(!detector.is_open("BTCUSDT"));
+        assert!(detector.check(&nbbo(100.0, 99.8)).is_some());
+    }
+}
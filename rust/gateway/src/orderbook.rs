@@ -0,0 +1,387 @@
+//! Local order book reconstruction
+//!
+//! This module rebuilds a full order book from a REST snapshot plus
+//! Binance `@depth` diff events, following the Binance Futures
+//! "how to manage a local order book" procedure, and maintains OKX
+//! `books` channel books verified against their CRC32 checksum.
+
+use crate::exchange::{DepthUpdate, ExchangeType};
+use crate::error::{GatewayError, Result};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use tracing::{debug, info};
+
+/// Number of levels per side covered by the OKX checksum
+pub const OKX_CHECKSUM_DEPTH: usize = 25;
+
+/// Binance Futures REST endpoints
+pub const BINANCE_FUTURES_REST: &str = "https://fapi.binance.com";
+pub const BINANCE_FUTURES_TESTNET_REST: &str = "https://testnet.binancefuture.com";
+pub const BINANCE_COIN_FUTURES_REST: &str = "https://dapi.binance.com";
+pub const BINANCE_COIN_FUTURES_TESTNET_REST: &str = "https://testnet.binancefuture.com";
+
+/// Binance spot REST endpoints
+pub const BINANCE_SPOT_REST: &str = "https://api.binance.com";
+pub const BINANCE_SPOT_TESTNET_REST: &str = "https://testnet.binance.vision";
+
+/// OKX REST endpoint (demo trading uses the same host with a header)
+pub const OKX_REST: &str = "https://www.okx.com";
+
+/// Number of levels requested for the REST snapshot
+pub const SNAPSHOT_LIMIT: u16 = 1000;
+
+/// Price levels as (price, quantity) pairs
+pub type Levels = Vec<(f64, f64)>;
+
+/// Price key with a total ordering so it can index a BTreeMap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Price(pub(crate) f64);
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Local order book for a single symbol
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    symbol: String,
+    bids: BTreeMap<Price, f64>,
+    asks: BTreeMap<Price, f64>,
+    /// `lastUpdateId` of the snapshot, then `u` of the last applied diff
+    last_update_id: u64,
+    /// Set once the first diff bridging the snapshot has been applied
+    synced: bool,
+}
+
+impl OrderBook {
+    /// Create a book from snapshot levels
+    pub fn from_snapshot(
+        symbol: &str,
+        last_update_id: u64,
+        bids: &[(f64, f64)],
+        asks: &[(f64, f64)],
+    ) -> Self {
+        let mut book = Self {
+            symbol: symbol.to_string(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            last_update_id,
+            synced: false,
+        };
+        Self::apply_levels(&mut book.bids, bids);
+        Self::apply_levels(&mut book.asks, asks);
+        book
+    }
+
+    /// Parse a REST depth response (`/fapi/v1/depth`, `/api/v3/depth`, ...) into a book
+    pub fn from_snapshot_json(symbol: &str, data: &Value) -> Result<Self> {
+        let last_update_id = data["lastUpdateId"].as_u64()
+            .ok_or_else(|| GatewayError::Parse("Missing lastUpdateId".to_string()))?;
+
+        let parse_levels = |key: &str| -> Vec<(f64, f64)> {
+            data[key].as_array()
+                .map(|levels| {
+                    levels.iter()
+                        .filter_map(|level| {
+                            let price = level.get(0)?.as_str()?.parse::<f64>().ok()?;
+                            let qty = level.get(1)?.as_str()?.parse::<f64>().ok()?;
+                            Some((price, qty))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Ok(Self::from_snapshot(symbol, last_update_id, &parse_levels("bids"), &parse_levels("asks")))
+    }
+
+    /// Fetch a REST depth snapshot from a depth endpoint (e.g. `https://fapi.binance.com/fapi/v1/depth`)
+    pub async fn fetch_snapshot(depth_url: &str, symbol: &str, limit: u16) -> Result<Self> {
+        let data = Self::fetch_snapshot_json(depth_url, symbol, limit).await?;
+        Self::from_snapshot_json(symbol, &data)
+    }
+
+    /// Fetch the raw depth response from a depth endpoint
+    pub async fn fetch_snapshot_json(depth_url: &str, symbol: &str, limit: u16) -> Result<Value> {
+        let url = format!("{}?symbol={}&limit={}", depth_url, symbol, limit);
+        info!("Fetching {} order book snapshot from {}", symbol, url);
+
+        Ok(reqwest::get(&url).await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// Apply a depth diff to the book.
+    ///
+    /// Returns `Ok(false)` for stale diffs already covered by the snapshot and
+    /// `Ok(true)` once applied. Errors on a sequence gap, after which the book
+    /// must be rebuilt from a fresh snapshot.
+    pub fn apply_update(&mut self, update: &DepthUpdate) -> Result<bool> {
+        let first = update.first_update_id.ok_or_else(|| GatewayError::Parse("Missing first update id (U)".to_string()))?;
+        let last = update.final_update_id.ok_or_else(|| GatewayError::Parse("Missing final update id (u)".to_string()))?;
+
+        // Drop any event where u < lastUpdateId of the snapshot
+        if last < self.last_update_id {
+            debug!("Dropping stale {} depth update (u={} < {})", self.symbol, last, self.last_update_id);
+            return Ok(false);
+        }
+
+        // Futures diffs carry the previous event's u as pu; spot diffs have no pu and
+        // instead start right after the previous event (U = previous u + 1)
+        let spot = update.prev_final_update_id.is_none();
+        if !self.synced {
+            // The first applied event must straddle the snapshot: U <= lastUpdateId <= u
+            // (U <= lastUpdateId + 1 on spot)
+            let max_first = if spot { self.last_update_id + 1 } else { self.last_update_id };
+            if first > max_first {
+                return Err(GatewayError::Parse(format!(
+                    "Gap after snapshot for {}: U={} > lastUpdateId={}",
+                    self.symbol, first, self.last_update_id
+                )));
+            }
+        } else if spot && first != self.last_update_id + 1 {
+            return Err(GatewayError::Parse(format!(
+                "Sequence gap for {}: U={}, expected {}",
+                self.symbol, first, self.last_update_id + 1
+            )));
+        } else if !spot && update.prev_final_update_id != Some(self.last_update_id) {
+            // Each subsequent event's pu must equal the previous event's u
+            return Err(GatewayError::Parse(format!(
+                "Sequence gap for {}: pu={:?}, expected {}",
+                self.symbol, update.prev_final_update_id, self.last_update_id
+            )));
+        }
+
+        Self::apply_levels(&mut self.bids, &update.bids);
+        Self::apply_levels(&mut self.asks, &update.asks);
+        self.last_update_id = last;
+        self.synced = true;
+
+        Ok(true)
+    }
+
+    /// Set level quantities, removing levels whose quantity is zero
+    fn apply_levels(side: &mut BTreeMap<Price, f64>, levels: &[(f64, f64)]) {
+        for &(price, qty) in levels {
+            if qty == 0.0 {
+                side.remove(&Price(price));
+            } else {
+                side.insert(Price(price), qty);
+            }
+        }
+    }
+
+    /// Highest bid (price, quantity)
+    pub fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bids.iter().next_back().map(|(p, q)| (p.0, *q))
+    }
+
+    /// Lowest ask (price, quantity)
+    pub fn best_ask(&self) -> Option<(f64, f64)> {
+        self.asks.iter().next().map(|(p, q)| (p.0, *q))
+    }
+
+    /// Top `depth` bids (descending) and asks (ascending)
+    pub fn top_levels(&self, depth: usize) -> (Levels, Levels) {
+        let bids = self.bids.iter().rev().take(depth).map(|(p, q)| (p.0, *q)).collect();
+        let asks = self.asks.iter().take(depth).map(|(p, q)| (p.0, *q)).collect();
+        (bids, asks)
+    }
+
+    /// Update ID of the last applied snapshot or diff
+    pub fn last_update_id(&self) -> u64 {
+        self.last_update_id
+    }
+
+    /// Build a top-`depth` snapshot event from the reconstructed book, received at `received_at`
+    pub fn to_depth_update(&self, exchange: ExchangeType, depth: usize, timestamp: i64, received_at: i64) -> DepthUpdate {
+        let (bids, asks) = self.top_levels(depth);
+
+        DepthUpdate {
+            exchange,
+            symbol: self.symbol.clone(),
+            bids,
+            asks,
+            timestamp,
+            is_snapshot: true,
+            first_update_id: None,
+            final_update_id: Some(self.last_update_id),
+            prev_final_update_id: None,
+            received_at,
+        }
+    }
+}
+
+/// Local OKX order book keeping levels as sent, since the checksum covers the raw strings
+#[derive(Debug, Clone, Default)]
+pub struct OkxOrderBook {
+    /// Price -> (price, size) strings as received
+    bids: BTreeMap<Price, (String, String)>,
+    asks: BTreeMap<Price, (String, String)>,
+}
+
+impl OkxOrderBook {
+    /// Apply one `books` data entry; a snapshot replaces the whole book
+    pub fn apply(&mut self, book: &Value, is_snapshot: bool) -> Result<()> {
+        if is_snapshot {
+            self.bids.clear();
+            self.asks.clear();
+        }
+        Self::apply_levels(&mut self.bids, &book["bids"])?;
+        Self::apply_levels(&mut self.asks, &book["asks"])?;
+        Ok(())
+    }
+
+    /// Set levels from `[price, size, ...]` arrays, removing zero-size levels
+    fn apply_levels(side: &mut BTreeMap<Price, (String, String)>, levels: &Value) -> Result<()> {
+        for level in levels.as_array().into_iter().flatten() {
+            let price_str = level.get(0).and_then(|p| p.as_str())
+                .ok_or_else(|| GatewayError::Parse("Missing level price".to_string()))?;
+            let size_str = level.get(1).and_then(|q| q.as_str())
+                .ok_or_else(|| GatewayError::Parse("Missing level size".to_string()))?;
+            let price = Price(price_str.parse::<f64>()?);
+
+            if size_str.parse::<f64>()? == 0.0 {
+                side.remove(&price);
+            } else {
+                side.insert(price, (price_str.to_string(), size_str.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the checksum input: alternating bid and ask `price:size` for the top 25 levels
+    pub fn checksum_string(&self) -> String {
+        let mut bids = self.bids.values().rev();
+        let mut asks = self.asks.values();
+        let mut parts = Vec::with_capacity(OKX_CHECKSUM_DEPTH * 4);
+
+        for _ in 0..OKX_CHECKSUM_DEPTH {
+            if let Some((price, size)) = bids.next() {
+                parts.push(price.as_str());
+                parts.push(size.as_str());
+            }
+            if let Some((price, size)) = asks.next() {
+                parts.push(price.as_str());
+                parts.push(size.as_str());
+            }
+        }
+
+        parts.join(":")
+    }
+
+    /// CRC32 of the checksum string as a signed integer, matching OKX's `checksum` field
+    pub fn checksum(&self) -> i32 {
+        crc32fast::hash(self.checksum_string().as_bytes()) as i32
+    }
+
+    /// Check the book against the `checksum` sent with the last update
+    pub fn verify_checksum(&self, expected: i32) -> bool {
+        self.checksum() == expected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(first: u64, last: u64, prev: u64, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>) -> DepthUpdate {
+        DepthUpdate {
+            exchange: ExchangeType::Binance,
+            symbol: "BTCUSDT".to_string(),
+            bids,
+            asks,
+            timestamp: 0,
+            is_snapshot: false,
+            first_update_id: Some(first),
+            final_update_id: Some(last),
+            prev_final_update_id: Some(prev),
+            received_at: 0,
+        }
+    }
+
+    fn snapshot() -> OrderBook {
+        let json = r#"{"lastUpdateId":100,"E":1589436922972,"T":1589436922959,"bids":[["50000.0","1.0"],["49999.0","2.0"],["49998.0","3.0"]],"asks":[["50001.0","1.5"],["50002.0","2.5"]]}"#;
+        OrderBook::from_snapshot_json("BTCUSDT", &serde_json::from_str(json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_replay_snapshot_and_diffs() {
+        let mut book = snapshot();
+        assert_eq!(book.best_bid(), Some((50000.0, 1.0)));
+        assert_eq!(book.best_ask(), Some((50001.0, 1.5)));
+
+        // Stale diff entirely before the snapshot is dropped
+        assert!(!book.apply_update(&diff(90, 95, 89, vec![(60000.0, 1.0)], vec![])).unwrap());
+
+        // First diff straddles lastUpdateId; removes the best bid
+        assert!(book.apply_update(&diff(98, 105, 97, vec![(50000.0, 0.0)], vec![(50000.5, 0.7)])).unwrap());
+        // Second diff chains on pu; adds a higher bid
+        assert!(book.apply_update(&diff(106, 110, 105, vec![(50000.2, 4.0)], vec![])).unwrap());
+        // Third diff removes the new best ask
+        assert!(book.apply_update(&diff(111, 120, 110, vec![], vec![(50000.5, 0.0)])).unwrap());
+
+        assert_eq!(book.best_bid(), Some((50000.2, 4.0)));
+        assert_eq!(book.best_ask(), Some((50001.0, 1.5)));
+        assert_eq!(book.last_update_id(), 120);
+
+        let (bids, asks) = book.top_levels(2);
+        assert_eq!(bids, vec![(50000.2, 4.0), (49999.0, 2.0)]);
+        assert_eq!(asks, vec![(50001.0, 1.5), (50002.0, 2.5)]);
+    }
+
+    #[test]
+    fn test_sequence_gap_is_an_error() {
+        let mut book = snapshot();
+        assert!(book.apply_update(&diff(98, 105, 97, vec![], vec![])).unwrap());
+
+        // pu should be 105
+        assert!(book.apply_update(&diff(110, 115, 108, vec![], vec![])).is_err());
+
+        // A first diff that starts after the snapshot also means events were missed
+        let mut book = snapshot();
+        assert!(book.apply_update(&diff(101, 105, 100, vec![], vec![])).is_err());
+    }
+
+    #[test]
+    fn test_spot_diffs_chain_on_first_update_id() {
+        let spot_diff = |first, last, bids| DepthUpdate { prev_final_update_id: None, ..diff(first, last, 0, bids, vec![]) };
+        let mut book = snapshot();
+
+        // Spot's first diff may start right after the snapshot
+        assert!(book.apply_update(&spot_diff(101, 104, vec![(50000.0, 5.0)])).unwrap());
+        assert!(book.apply_update(&spot_diff(105, 107, vec![])).unwrap());
+        assert_eq!(book.best_bid(), Some((50000.0, 5.0)));
+
+        assert!(book.apply_update(&spot_diff(109, 110, vec![])).is_err());
+    }
+
+    #[test]
+    fn test_okx_checksum_matches_documented_example() {
+        // Example from the OKX v5 order book checksum documentation
+        let snapshot: Value = serde_json::from_str(r#"{"bids":[["3366.1","7","0","3"],["3366","6","3","4"]],"asks":[["3366.8","9","10","3"],["3368","8","3","4"]]}"#).unwrap();
+        let mut book = OkxOrderBook::default();
+        book.apply(&snapshot, true).unwrap();
+
+        assert_eq!(book.checksum_string(), "3366.1:7:3366.8:9:3366:6:3368:8");
+        assert!(book.verify_checksum(-1881014294));
+
+        // Removing a level changes the checksum
+        let update: Value = serde_json::from_str(r#"{"bids":[["3366","0","0","0"]],"asks":[]}"#).unwrap();
+        book.apply(&update, false).unwrap();
+        assert_eq!(book.checksum_string(), "3366.1:7:3366.8:9:3368:8");
+        assert!(!book.verify_checksum(-1881014294));
+    }
+}
@@ -0,0 +1,322 @@
+//! Columnar event recording
+//!
+//! This module buffers agg trades and klines into row batches and writes
+//! them as Parquet files partitioned by symbol and date, laid out as
+//! `<dir>/<type>/symbol=<SYMBOL>/date=<YYYY-MM-DD>/part-*.parquet` so
+//! pandas and polars can read a directory as one dataset.
+
+use crate::exchange::{AggTrade, Kline, MarketEvent};
+use crate::error::{GatewayError, Result};
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, TimestampMillisecondArray,
+    UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, NaiveDate, Utc};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Default rows buffered per partition before it is written
+pub const DEFAULT_PARQUET_BATCH_ROWS: usize = 10_000;
+
+/// Symbol and UTC date a row is filed under
+type Partition = (String, NaiveDate);
+
+/// Writes agg trades and klines to partitioned Parquet files
+pub struct ParquetRecorder {
+    dir: PathBuf,
+    batch_rows: usize,
+    trades: HashMap<Partition, Vec<AggTrade>>,
+    klines: HashMap<Partition, Vec<Kline>>,
+    trade_schema: SchemaRef,
+    kline_schema: SchemaRef,
+    /// Files written so far, keeps part names unique
+    sequence: u64,
+}
+
+impl ParquetRecorder {
+    /// Record into `dir`, creating it if needed
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .map_err(|e| GatewayError::Recording(format!("Failed to create Parquet directory {}: {}", dir.display(), e)))?;
+
+        Ok(Self {
+            dir,
+            batch_rows: DEFAULT_PARQUET_BATCH_ROWS,
+            trades: HashMap::new(),
+            klines: HashMap::new(),
+            trade_schema: Arc::new(trade_schema()),
+            kline_schema: Arc::new(kline_schema()),
+            sequence: 0,
+        })
+    }
+
+    /// Write a partition once it holds this many rows
+    pub fn with_batch_rows(mut self, batch_rows: usize) -> Self {
+        self.batch_rows = batch_rows.max(1);
+        self
+    }
+
+    /// Rows buffered and not yet written
+    pub fn pending_rows(&self) -> usize {
+        self.trades.values().map(Vec::len).sum::<usize>() + self.klines.values().map(Vec::len).sum::<usize>()
+    }
+
+    /// Buffer an event, writing its partition when full; other event types are ignored
+    pub fn record(&mut self, event: &MarketEvent) -> Result<()> {
+        match event {
+            MarketEvent::AggTrade(trade) => {
+                let partition = partition(&trade.symbol, trade.timestamp);
+                let rows = self.trades.entry(partition.clone()).or_default();
+                rows.push(trade.clone());
+                if rows.len() >= self.batch_rows {
+                    let rows = self.trades.remove(&partition).unwrap_or_default();
+                    self.write_trades(&partition, &rows)?;
+                }
+            }
+            MarketEvent::Kline(kline) => {
+                let partition = partition(&kline.symbol, kline.open_time);
+                let rows = self.klines.entry(partition.clone()).or_default();
+                rows.push(kline.clone());
+                if rows.len() >= self.batch_rows {
+                    let rows = self.klines.remove(&partition).unwrap_or_default();
+                    self.write_klines(&partition, &rows)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Write every buffered partition, returning how many rows were written
+    pub fn flush(&mut self) -> Result<usize> {
+        let mut written = 0;
+
+        for (partition, rows) in std::mem::take(&mut self.trades) {
+            self.write_trades(&partition, &rows)?;
+            written += rows.len();
+        }
+        for (partition, rows) in std::mem::take(&mut self.klines) {
+            self.write_klines(&partition, &rows)?;
+            written += rows.len();
+        }
+
+        Ok(written)
+    }
+
+    fn write_trades(&mut self, partition: &Partition, rows: &[AggTrade]) -> Result<()> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|t| t.exchange.to_string()))),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|t| t.symbol.as_str()))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|t| t.price))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|t| t.quantity))),
+            Arc::new(timestamps(rows.iter().map(|t| t.timestamp))),
+            Arc::new(BooleanArray::from(rows.iter().map(|t| t.is_buyer_maker).collect::<Vec<_>>())),
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|t| t.trade_id))),
+            Arc::new(timestamps(rows.iter().map(|t| t.received_at))),
+        ];
+        let batch = RecordBatch::try_new(self.trade_schema.clone(), columns)?;
+        self.write_batch("agg_trade", partition, batch)
+    }
+
+    fn write_klines(&mut self, partition: &Partition, rows: &[Kline]) -> Result<()> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|k| k.exchange.to_string()))),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|k| k.symbol.as_str()))),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|k| k.interval.as_str()))),
+            Arc::new(timestamps(rows.iter().map(|k| k.open_time))),
+            Arc::new(timestamps(rows.iter().map(|k| k.close_time))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|k| k.open))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|k| k.high))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|k| k.low))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|k| k.close))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|k| k.volume))),
+            Arc::new(BooleanArray::from(rows.iter().map(|k| k.is_closed).collect::<Vec<_>>())),
+            Arc::new(StringArray::from(
+                rows.iter()
+                    .map(|k| k.contract_type.map(|c| c.as_str()))
+                    .collect::<Vec<_>>(),
+            )),
+            Arc::new(timestamps(rows.iter().map(|k| k.received_at))),
+        ];
+        let batch = RecordBatch::try_new(self.kline_schema.clone(), columns)?;
+        self.write_batch("kline", partition, batch)
+    }
+
+    /// Write one batch to a new part file in its partition directory
+    fn write_batch(&mut self, kind: &str, (symbol, date): &Partition, batch: RecordBatch) -> Result<()> {
+        let dir = self.dir
+            .join(kind)
+            .join(format!("symbol={}", symbol))
+            .join(format!("date={}", date));
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(format!(
+            "part-{}-{:06}.parquet",
+            Utc::now().format("%Y%m%d%H%M%S"), self.sequence
+        ));
+        self.sequence += 1;
+
+        let file = File::create(&path)
+            .map_err(|e| GatewayError::Recording(format!("Failed to create Parquet file {}: {}", path.display(), e)))?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(props))?;
+        writer.write(&batch)?;
+        writer.close()?;
+
+        debug!("Wrote {} {} rows to {}", batch.num_rows(), kind, path.display());
+        Ok(())
+    }
+}
+
+impl Drop for ParquetRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("Failed to write buffered Parquet rows: {}", e);
+        }
+    }
+}
+
+fn partition(symbol: &str, timestamp_ms: i64) -> Partition {
+    let date = DateTime::<Utc>::from_timestamp_millis(timestamp_ms)
+        .unwrap_or_default()
+        .date_naive();
+    (symbol.to_string(), date)
+}
+
+fn timestamps(values: impl Iterator<Item = i64>) -> TimestampMillisecondArray {
+    TimestampMillisecondArray::from_iter_values(values).with_timezone("UTC")
+}
+
+fn timestamp_field(name: &str) -> Field {
+    Field::new(name, DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())), false)
+}
+
+fn trade_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("exchange", DataType::Utf8, false),
+        Field::new("symbol", DataType::Utf8, false),
+        Field::new("price", DataType::Float64, false),
+        Field::new("quantity", DataType::Float64, false),
+        timestamp_field("timestamp"),
+        Field::new("is_buyer_maker", DataType::Boolean, false),
+        Field::new("trade_id", DataType::UInt64, false),
+        timestamp_field("received_at"),
+    ])
+}
+
+fn kline_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("exchange", DataType::Utf8, false),
+        Field::new("symbol", DataType::Utf8, false),
+        Field::new("interval", DataType::Utf8, false),
+        timestamp_field("open_time"),
+        timestamp_field("close_time"),
+        Field::new("open", DataType::Float64, false),
+        Field::new("high", DataType::Float64, false),
+        Field::new("low", DataType::Float64, false),
+        Field::new("close", DataType::Float64, false),
+        Field::new("volume", DataType::Float64, false),
+        Field::new("is_closed", DataType::Boolean, false),
+        Field::new("contract_type", DataType::Utf8, true),
+        timestamp_field("received_at"),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{AggTrade, ExchangeType};
+    use crate::testing;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::path::Path;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("flash-arb-parquet-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn trade(trade_id: u64) -> MarketEvent {
+        MarketEvent::AggTrade(AggTrade {
+            price: 50000.0 + trade_id as f64,
+            quantity: 0.5,
+            // 2024-01-01T00:00:00Z
+            timestamp: 1_704_067_200_000 + trade_id as i64,
+            received_at: 1_704_067_200_000 + trade_id as i64,
+            ..testing::trade(ExchangeType::Binance, trade_id)
+        })
+    }
+
+    fn parquet_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(parquet_files(&path));
+            } else if path.extension().is_some_and(|ext| ext == "parquet") {
+                files.push(path);
+            }
+        }
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_agg_trades_round_trip() {
+        let dir = temp_dir();
+        let mut recorder = ParquetRecorder::new(&dir).unwrap().with_batch_rows(100);
+        for trade_id in 0..5 {
+            recorder.record(&trade(trade_id)).unwrap();
+        }
+        assert_eq!(recorder.pending_rows(), 5);
+        assert_eq!(recorder.flush().unwrap(), 5);
+
+        let files = parquet_files(&dir);
+        assert_eq!(files.len(), 1);
+        assert!(files[0].starts_with(dir.join("agg_trade/symbol=BTCUSDT/date=2024-01-01")));
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&files[0]).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+        let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+        assert_eq!(rows, 5);
+
+        let prices = batches[0]
+            .column_by_name("price")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(prices.value(3), 50003.0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_full_partition_is_written_immediately() {
+        let dir = temp_dir();
+        let mut recorder = ParquetRecorder::new(&dir).unwrap().with_batch_rows(2);
+        for trade_id in 0..3 {
+            recorder.record(&trade(trade_id)).unwrap();
+        }
+
+        assert_eq!(parquet_files(&dir).len(), 1);
+        assert_eq!(recorder.pending_rows(), 1);
+
+        // The remainder is written on drop
+        drop(recorder);
+        assert_eq!(parquet_files(&dir).len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
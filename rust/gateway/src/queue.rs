@@ -0,0 +1,304 @@
+//! Bounded event queue
+//!
+//! This module decouples reading from an exchange socket from publishing:
+//! readers push parsed events and a publisher task pops them. When the
+//! publisher falls behind, the configured policy decides whether readers
+//! wait or events are dropped, so a slow backend doesn't stall the sockets.
+
+use crate::exchange::MarketEvent;
+use crate::metrics;
+use crate::error::{GatewayError, Result};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+
+/// Default number of events the queue holds before the policy applies
+pub const DEFAULT_QUEUE_SIZE: usize = 10_000;
+
+/// Default fill level, in percent of capacity, that logs a warning
+pub const DEFAULT_WARN_PERCENT: u8 = 80;
+
+/// What a push does when the queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackpressurePolicy {
+    /// Wait for room; nothing is lost, but the reader stops draining its socket
+    Block,
+    /// Drop the oldest queued event to make room, keeping the freshest data
+    #[default]
+    DropOldest,
+    /// Drop the event being pushed
+    DropNewest,
+}
+
+impl std::str::FromStr for BackpressurePolicy {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "block" => Ok(BackpressurePolicy::Block),
+            "drop_oldest" => Ok(BackpressurePolicy::DropOldest),
+            "drop_newest" => Ok(BackpressurePolicy::DropNewest),
+            _ => Err(GatewayError::Config(format!(
+                "Unknown backpressure policy: {} (expected block, drop_oldest or drop_newest)", s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    events: Mutex<VecDeque<MarketEvent>>,
+    capacity: usize,
+    policy: BackpressurePolicy,
+    dropped: AtomicU64,
+    /// Most events held at once
+    high_watermark: AtomicUsize,
+    /// Depth that logs a warning when reached; 0 never warns
+    warn_depth: AtomicUsize,
+    /// Whether the depth is at or above `warn_depth`, so each crossing warns once
+    above_warn_depth: AtomicBool,
+    /// Woken when an event is pushed
+    pushed: Notify,
+    /// Woken when an event is popped
+    popped: Notify,
+}
+
+/// Bounded multi-producer queue of events waiting to be published.
+///
+/// Clones are handles to the same queue.
+#[derive(Debug, Clone)]
+pub struct EventQueue {
+    inner: Arc<Inner>,
+}
+
+impl EventQueue {
+    /// Create a queue holding up to `capacity` events (at least one)
+    pub fn new(capacity: usize, policy: BackpressurePolicy) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                events: Mutex::new(VecDeque::new()),
+                capacity: capacity.max(1),
+                policy,
+                dropped: AtomicU64::new(0),
+                high_watermark: AtomicUsize::new(0),
+                warn_depth: AtomicUsize::new(0),
+                above_warn_depth: AtomicBool::new(false),
+                pushed: Notify::new(),
+                popped: Notify::new(),
+            }),
+        }
+    }
+
+    /// Log a warning when the queue fills to `percent` of its capacity; 0 never warns
+    pub fn with_warn_threshold(self, percent: u8) -> Self {
+        let depth = match percent.min(100) {
+            0 => 0,
+            percent => (self.inner.capacity * percent as usize).div_ceil(100).max(1),
+        };
+        self.inner.warn_depth.store(depth, Ordering::Relaxed);
+        self
+    }
+
+    /// Number of queued events
+    pub fn len(&self) -> usize {
+        self.inner.events.lock().unwrap().len()
+    }
+
+    /// Check if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Most events the queue has held at once
+    pub fn high_watermark(&self) -> usize {
+        self.inner.high_watermark.load(Ordering::Relaxed)
+    }
+
+    /// Number of events dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Queue an event, applying the policy if the queue is full.
+    ///
+    /// Returns false if an event (this one or an older one) was dropped.
+    /// Only `Block` ever waits.
+    pub async fn push(&self, event: MarketEvent) -> bool {
+        loop {
+            {
+                let mut events = self.inner.events.lock().unwrap();
+                if events.len() < self.inner.capacity {
+                    events.push_back(event);
+                    let depth = events.len();
+                    drop(events);
+                    self.record_depth(depth);
+                    self.inner.pushed.notify_one();
+                    return true;
+                }
+
+                match self.inner.policy {
+                    BackpressurePolicy::Block => {}
+                    BackpressurePolicy::DropOldest => {
+                        let oldest = events.pop_front().expect("a full queue has events");
+                        events.push_back(event);
+                        let depth = events.len();
+                        drop(events);
+                        self.record_depth(depth);
+                        self.record_drop(&oldest);
+                        self.inner.pushed.notify_one();
+                        return false;
+                    }
+                    BackpressurePolicy::DropNewest => {
+                        drop(events);
+                        self.record_drop(&event);
+                        return false;
+                    }
+                }
+            }
+            self.inner.popped.notified().await;
+        }
+    }
+
+    /// Take the next event, waiting until one is pushed
+    pub async fn pop(&self) -> MarketEvent {
+        loop {
+            if let Some(event) = self.try_pop() {
+                return event;
+            }
+            self.inner.pushed.notified().await;
+        }
+    }
+
+    /// Take the next event if one is queued
+    pub fn try_pop(&self) -> Option<MarketEvent> {
+        let mut events = self.inner.events.lock().unwrap();
+        let event = events.pop_front();
+        let depth = events.len();
+        drop(events);
+        if event.is_some() {
+            self.record_depth(depth);
+            self.inner.popped.notify_one();
+        }
+        event
+    }
+
+    /// Update the watermark and gauges, warning once each time the threshold is crossed
+    fn record_depth(&self, depth: usize) {
+        self.inner.high_watermark.fetch_max(depth, Ordering::Relaxed);
+        metrics::global().set_queue_depth(depth);
+
+        let warn_depth = self.inner.warn_depth.load(Ordering::Relaxed);
+        if warn_depth == 0 {
+            return;
+        }
+        let above = depth >= warn_depth;
+        if above && !self.inner.above_warn_depth.swap(true, Ordering::Relaxed) {
+            warn!(
+                "Publish queue is {}% full ({} of {} events); the publisher is falling behind",
+                depth * 100 / self.inner.capacity, depth, self.inner.capacity
+            );
+        } else if !above {
+            self.inner.above_warn_depth.store(false, Ordering::Relaxed);
+        }
+    }
+
+    fn record_drop(&self, event: &MarketEvent) {
+        let dropped = self.inner.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        metrics::global().record_dropped(event.exchange());
+        debug!("Publish queue full, dropped a {} event ({} dropped so far)", event.exchange(), dropped);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::ExchangeType;
+    use crate::logging::{self, LogFormat};
+    use crate::testing::{self, LogCapture};
+    use std::time::Duration;
+    use tokio::time;
+
+    fn trade(trade_id: u64) -> MarketEvent {
+        testing::agg_trade(ExchangeType::Binance, trade_id)
+    }
+
+    fn drain(queue: &EventQueue) -> Vec<MarketEvent> {
+        std::iter::from_fn(|| queue.try_pop()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_by_policy() {
+        let oldest = EventQueue::new(2, BackpressurePolicy::DropOldest);
+        assert!(oldest.push(trade(1)).await);
+        assert!(oldest.push(trade(2)).await);
+        assert!(!oldest.push(trade(3)).await);
+        assert_eq!(oldest.dropped(), 1);
+        assert_eq!(drain(&oldest), vec![trade(2), trade(3)]);
+
+        let newest = EventQueue::new(2, BackpressurePolicy::DropNewest);
+        for trade_id in 1..=3 {
+            newest.push(trade(trade_id)).await;
+        }
+        assert_eq!(newest.dropped(), 1);
+        assert_eq!(drain(&newest), vec![trade(1), trade(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room() {
+        let queue = EventQueue::new(2, BackpressurePolicy::Block);
+        queue.push(trade(1)).await;
+        queue.push(trade(2)).await;
+
+        let producer = queue.clone();
+        let blocked = tokio::spawn(async move { producer.push(trade(3)).await });
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+        assert_eq!(queue.len(), 2);
+
+        // Popping makes room and lets the push through, without dropping anything
+        assert_eq!(queue.pop().await, trade(1));
+        assert!(time::timeout(Duration::from_secs(5), blocked).await.unwrap().unwrap());
+        assert_eq!(queue.dropped(), 0);
+        assert_eq!(drain(&queue), vec![trade(2), trade(3)]);
+    }
+
+    #[tokio::test]
+    async fn test_crossing_threshold_warns_and_raises_watermark() {
+        let logs = LogCapture::default();
+        let _logger = tracing::subscriber::set_default(logging::subscriber("warn", LogFormat::Text, logs.clone()));
+        let warnings = || logs.output().matches("Publish queue is").count();
+
+        let queue = EventQueue::new(10, BackpressurePolicy::DropOldest).with_warn_threshold(50);
+        for trade_id in 1..=4 {
+            queue.push(trade(trade_id)).await;
+        }
+        assert_eq!(warnings(), 0);
+
+        // Reaching 5 of 10 crosses the threshold; staying above it doesn't warn again
+        queue.push(trade(5)).await;
+        queue.push(trade(6)).await;
+        assert_eq!(warnings(), 1);
+        assert_eq!(queue.high_watermark(), 6);
+        assert!(metrics::global().snapshot().queue_high_watermark >= 6);
+
+        // Draining below the threshold re-arms the warning
+        drain(&queue);
+        assert_eq!(queue.high_watermark(), 6);
+        for trade_id in 7..=11 {
+            queue.push(trade(trade_id)).await;
+        }
+        assert_eq!(warnings(), 2);
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!("block".parse::<BackpressurePolicy>().unwrap(), BackpressurePolicy::Block);
+        assert_eq!("drop-oldest".parse::<BackpressurePolicy>().unwrap(), BackpressurePolicy::DropOldest);
+        assert_eq!("Drop_Newest".parse::<BackpressurePolicy>().unwrap(), BackpressurePolicy::DropNewest);
+        assert!("drop".parse::<BackpressurePolicy>().is_err());
+    }
+}
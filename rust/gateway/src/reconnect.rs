@@ -0,0 +1,264 @@
+//! Reconnect backoff policy
+//!
+//! This module computes exponential backoff delays with jitter for
+//! exchange reconnect attempts, how long a reconnect may go without
+//! data before it counts as failed, and retries a single connect call.
+
+use crate::error::{GatewayError, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time;
+use tracing::warn;
+
+/// Default delay before the first reconnect attempt
+pub const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// Default upper bound on the reconnect delay
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Default factor each reconnect delay grows by
+pub const DEFAULT_MULTIPLIER: f64 = 2.0;
+
+/// Default fraction of the delay that is randomized
+pub const DEFAULT_JITTER: f64 = 0.2;
+
+/// Default wait for the first event after a reconnect
+pub const DEFAULT_VERIFY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default number of tries a connect call gets
+pub const DEFAULT_CONNECT_ATTEMPTS: u32 = 5;
+
+/// Default limit on one connect try, TLS handshake included
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Exponential backoff with jitter for reconnect attempts
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    initial_delay: Duration,
+    max_delay: Duration,
+    multiplier: f64,
+    /// Delays are scaled by a random factor in `[1 - jitter, 1 + jitter]`
+    jitter: f64,
+    rng: StdRng,
+    attempt: u32,
+    /// A reconnect with no event within this long is retried; `None` trusts the open socket
+    verify_timeout: Option<Duration>,
+    /// Tries a connect call gets before giving up
+    connect_attempts: u32,
+    /// A connect try still pending after this long is abandoned; `None` waits forever
+    connect_timeout: Option<Duration>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_INITIAL_DELAY, DEFAULT_MAX_DELAY)
+    }
+}
+
+impl ReconnectPolicy {
+    /// Create a policy doubling from `initial_delay` up to `max_delay`, jittered from OS entropy
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            initial_delay,
+            max_delay,
+            multiplier: DEFAULT_MULTIPLIER,
+            jitter: DEFAULT_JITTER,
+            rng: StdRng::from_entropy(),
+            attempt: 0,
+            verify_timeout: Some(DEFAULT_VERIFY_TIMEOUT),
+            connect_attempts: DEFAULT_CONNECT_ATTEMPTS,
+            connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+        }
+    }
+
+    /// Set the backoff multiplier
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Set the jitter fraction (clamped to `0.0..=1.0`); 0 follows the exact exponential schedule
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Use a seeded RNG so the jittered delay sequence is reproducible
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Retry a reconnect that yields no event within `timeout` (zero disables the check)
+    pub fn with_verify_timeout(mut self, timeout: Duration) -> Self {
+        self.verify_timeout = (!timeout.is_zero()).then_some(timeout);
+        self
+    }
+
+    /// How long a reconnect may go without an event before it is retried
+    pub fn verify_timeout(&self) -> Option<Duration> {
+        self.verify_timeout
+    }
+
+    /// Give a connect call up to `attempts` tries (at least one)
+    pub fn with_connect_attempts(mut self, attempts: u32) -> Self {
+        self.connect_attempts = attempts.max(1);
+        self
+    }
+
+    /// Abandon a connect try after `timeout` (zero waits forever)
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = (!timeout.is_zero()).then_some(timeout);
+        self
+    }
+
+    /// Number of delays handed out since the last reset
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Get the delay before the next attempt and advance the schedule
+    pub fn next_delay(&mut self) -> Duration {
+        let base = self.initial_delay.as_secs_f64() * self.multiplier.powi(self.attempt as i32);
+        let base = base.min(self.max_delay.as_secs_f64());
+        self.attempt = self.attempt.saturating_add(1);
+
+        let factor = if self.jitter > 0.0 {
+            self.rng.gen_range(1.0 - self.jitter..=1.0 + self.jitter)
+        } else {
+            1.0
+        };
+
+        Duration::from_secs_f64((base * factor).min(self.max_delay.as_secs_f64()))
+    }
+
+    /// Restart the schedule after a successful connection
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Run `connect` until it succeeds, backing off between failed or timed-out tries.
+    ///
+    /// Gives up with the last error once the connect attempts are used up.
+    pub async fn retry_connect<T, E, F, Fut>(&mut self, name: &str, mut connect: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = std::result::Result<T, E>>,
+        E: Into<GatewayError>,
+    {
+        self.reset();
+        let mut attempt = 1;
+        loop {
+            let result = match self.connect_timeout {
+                Some(timeout) => match time::timeout(timeout, connect()).await {
+                    Ok(result) => result.map_err(Into::into),
+                    Err(_) => Err(GatewayError::Connect(format!("timed out after {:?}", timeout))),
+                },
+                None => connect().await.map_err(Into::into),
+            };
+
+            let error = match result {
+                Ok(connection) => {
+                    self.reset();
+                    return Ok(connection);
+                }
+                Err(e) => e,
+            };
+            if attempt >= self.connect_attempts {
+                return Err(GatewayError::Connect(format!("{} connect failed after {} attempts: {}", name, attempt, error)));
+            }
+
+            let delay = self.next_delay();
+            warn!("{} connect attempt {}/{} failed: {}; retrying in {:?}", name, attempt, self.connect_attempts, error, delay);
+            time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_without_jitter() {
+        let mut policy = ReconnectPolicy::new(Duration::from_secs(1), Duration::from_secs(30))
+            .with_jitter(0.0);
+
+        let delays: Vec<u64> = (0..7).map(|_| policy.next_delay().as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30, 30]);
+
+        policy.reset();
+        assert_eq!(policy.next_delay(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_connect_succeeds_on_third_attempt() {
+        let mut policy = ReconnectPolicy::default().with_jitter(0.0);
+        let mut attempts = 0;
+
+        let connected = policy
+            .retry_connect("mock", || {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    if attempt <= 2 {
+                        Err(GatewayError::Connect("dns lookup failed".to_string()))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(connected, 3);
+        assert_eq!(attempts, 3);
+        // A success restarts the backoff schedule
+        assert_eq!(policy.attempt(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hanging_connect_times_out_and_gives_up() {
+        let mut policy = ReconnectPolicy::default()
+            .with_connect_attempts(2)
+            .with_connect_timeout(Duration::from_secs(5));
+        let mut attempts = 0;
+
+        let result: Result<()> = policy
+            .retry_connect("mock", || {
+                attempts += 1;
+                std::future::pending::<Result<()>>()
+            })
+            .await;
+
+        let error = result.unwrap_err();
+        assert!(matches!(error, GatewayError::Connect(_)));
+        let error = error.to_string();
+        assert!(error.contains("mock connect failed after 2 attempts"), "{}", error);
+        assert!(error.contains("timed out"), "{}", error);
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn test_seeded_jitter_is_reproducible() {
+        let sequence = |seed: u64| -> Vec<Duration> {
+            let mut policy = ReconnectPolicy::new(Duration::from_millis(500), Duration::from_secs(60))
+                .with_jitter(0.5)
+                .with_seed(seed);
+            (0..8).map(|_| policy.next_delay()).collect()
+        };
+
+        let first = sequence(42);
+        assert_eq!(first, sequence(42));
+        assert_ne!(first, sequence(7));
+
+        for (attempt, delay) in first.iter().enumerate() {
+            let base = (0.5 * 2f64.powi(attempt as i32)).min(60.0);
+            let secs = delay.as_secs_f64();
+            assert!(secs >= base * 0.5 && secs <= (base * 1.5).min(60.0), "attempt {}: {:?}", attempt, delay);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Market event recording
+//!
+//! This module writes every market event as a line of JSON to rotating
+//! files so live sessions can be replayed for backtesting.
+
+use crate::exchange::MarketEvent;
+use crate::error::{GatewayError, Result};
+use chrono::{DateTime, Timelike, Utc};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Extension of recorded files
+pub const RECORDING_EXTENSION: &str = "ndjson";
+
+/// When the recorder starts a new file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationPolicy {
+    /// At the top of every UTC hour
+    #[default]
+    Hourly,
+    /// Once the current file reaches this many bytes
+    Size(u64),
+}
+
+/// Writes market events as NDJSON to timestamped, rotating files
+pub struct FileRecorder {
+    dir: PathBuf,
+    rotation: RotationPolicy,
+    writer: Option<BufWriter<File>>,
+    current_path: Option<PathBuf>,
+    /// Bytes written to the current file
+    bytes_written: u64,
+    /// Hour the current file was opened in
+    opened_at: DateTime<Utc>,
+    /// Files opened so far, keeps names unique within the same second
+    sequence: u64,
+}
+
+impl FileRecorder {
+    /// Record into `dir`, creating it if needed
+    pub fn new(dir: impl Into<PathBuf>, rotation: RotationPolicy) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .map_err(|e| GatewayError::Recording(format!("Failed to create recording directory {}: {}", dir.display(), e)))?;
+
+        Ok(Self {
+            dir,
+            rotation,
+            writer: None,
+            current_path: None,
+            bytes_written: 0,
+            opened_at: Utc::now(),
+            sequence: 0,
+        })
+    }
+
+    /// File currently being written, if any event has been recorded
+    pub fn current_path(&self) -> Option<&Path> {
+        self.current_path.as_deref()
+    }
+
+    /// Append an event, rotating to a new file first if the policy says so
+    pub fn record(&mut self, event: &MarketEvent) -> Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+
+        let now = Utc::now();
+        if self.should_rotate(now) {
+            self.rotate(now)?;
+        }
+
+        let writer = self.writer.as_mut().expect("rotate opens a file");
+        writer.write_all(&line)?;
+        self.bytes_written += line.len() as u64;
+        Ok(())
+    }
+
+    /// Write buffered lines through to disk
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn should_rotate(&self, now: DateTime<Utc>) -> bool {
+        if self.writer.is_none() {
+            return true;
+        }
+
+        match self.rotation {
+            RotationPolicy::Hourly => {
+                now.date_naive() != self.opened_at.date_naive() || now.hour() != self.opened_at.hour()
+            }
+            RotationPolicy::Size(max_bytes) => self.bytes_written >= max_bytes,
+        }
+    }
+
+    /// Close the current file and open a new timestamped one
+    fn rotate(&mut self, now: DateTime<Utc>) -> Result<()> {
+        self.flush()?;
+
+        let name = format!(
+            "events-{}-{:04}.{}",
+            now.format("%Y%m%d-%H%M%S"), self.sequence, RECORDING_EXTENSION
+        );
+        let path = self.dir.join(name);
+        let file = File::create(&path)
+            .map_err(|e| GatewayError::Recording(format!("Failed to create recording file {}: {}", path.display(), e)))?;
+        info!("Recording market events to {}", path.display());
+
+        self.writer = Some(BufWriter::new(file));
+        self.current_path = Some(path);
+        self.bytes_written = 0;
+        self.opened_at = now;
+        self.sequence += 1;
+        Ok(())
+    }
+}
+
+impl Drop for FileRecorder {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{AggTrade, ExchangeType};
+    use crate::testing;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("flash-arb-recorder-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn trade(trade_id: u64) -> MarketEvent {
+        MarketEvent::AggTrade(AggTrade {
+            price: 50000.0 + trade_id as f64,
+            timestamp: 1_700_000_000_000 + trade_id as i64,
+            is_buyer_maker: trade_id.is_multiple_of(2),
+            received_at: 1_700_000_000_000 + trade_id as i64,
+            ..testing::trade(ExchangeType::Binance, trade_id)
+        })
+    }
+
+    #[test]
+    fn test_recorded_lines_round_trip() {
+        let dir = temp_dir();
+        let events: Vec<_> = (1..=3).map(trade).collect();
+
+        let mut recorder = FileRecorder::new(&dir, RotationPolicy::Hourly).unwrap();
+        for event in &events {
+            recorder.record(event).unwrap();
+        }
+        recorder.flush().unwrap();
+        let path = recorder.current_path().unwrap().to_path_buf();
+
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("events-") && name.ends_with(".ndjson"));
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let read: Vec<MarketEvent> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(read, events);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_size_rotation_starts_new_files() {
+        let dir = temp_dir();
+        let mut recorder = FileRecorder::new(&dir, RotationPolicy::Size(1)).unwrap();
+        for trade_id in 1..=3 {
+            recorder.record(&trade(trade_id)).unwrap();
+        }
+        drop(recorder);
+
+        let mut files: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        files.sort();
+        assert_eq!(files.len(), 3);
+        for (file, trade_id) in files.iter().zip(1..) {
+            let event: MarketEvent = serde_json::from_str(fs::read_to_string(file).unwrap().trim()).unwrap();
+            assert_eq!(event, trade(trade_id));
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,38 +1,770 @@
 //! Redis publisher for distributing market data
 //!
-//! This module handles publishing market events to Redis channels
-//! for consumption by the Python strategy engine.
+//! This module handles publishing market events to Redis channels, or
+//! appending them to Redis streams, for consumption by the Python
+//! strategy engine.
+//!
+//! Payloads are the event encoded in the configured format, optionally with
+//! a `latency_ms` field next to it (see [`Envelope`]). With zstd
+//! compression enabled, each payload is instead the single byte
+//! [`ZSTD_MAGIC`] followed by one zstd frame of the encoded event. The magic
+//! byte is never the first byte of JSON or MessagePack, so consumers can
+//! tell compressed and plain payloads apart on a shared channel.
 
-use crate::exchange::{MarketEvent, DataType, ExchangeType};
-use anyhow::Result;
-use redis::{AsyncCommands, Client, ConnectionManager};
-use serde_json::to_string;
-use tracing::{debug, error, info};
+use crate::aggregate::{self, TradeAggregator};
+use crate::coalesce::DepthCoalescer;
+use crate::exchange::{EventResult, ExchangeType, MarketEvent};
+use crate::filter::EventFilter;
+use crate::latency;
+use crate::metrics::{self, GatewayMetrics, Metrics};
+use crate::nbbo::{self, ConsolidatedBook};
+use crate::opportunity::{self, Opportunity, SpreadDetector};
+use crate::queue::{self, BackpressurePolicy, EventQueue};
+use crate::reorder::ReorderBuffer;
+use crate::user_data::UserDataEvent;
+use crate::error::{GatewayError, Result};
+use redis::{aio::{ConnectionLike, ConnectionManager}, AsyncCommands, Client, Cmd, Pipeline};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::{debug, error, info, warn};
 
 /// Redis channel names
 pub const CHANNEL_TICK: &str = "flash_arb:tick";
 pub const CHANNEL_KLINE: &str = "flash_arb:kline";
 pub const CHANNEL_DEPTH: &str = "flash_arb:depth";
 pub const CHANNEL_TICKER: &str = "flash_arb:ticker";
+pub const CHANNEL_FUNDING: &str = "flash_arb:funding";
+pub const CHANNEL_LIQUIDATION: &str = "flash_arb:liquidation";
+pub const CHANNEL_OPEN_INTEREST: &str = "flash_arb:open_interest";
+pub const CHANNEL_TICKER_24H: &str = "flash_arb:ticker_24h";
+pub const CHANNEL_NBBO: &str = "flash_arb:nbbo";
+pub const CHANNEL_METRICS: &str = "flash_arb:metrics";
+pub const CHANNEL_USER_DATA: &str = "flash_arb:user_data";
+pub const CHANNEL_OPPORTUNITY: &str = "flash_arb:opportunity";
+
+/// Channel an event is published to
+pub fn channel_for(event: &MarketEvent) -> &'static str {
+    match event {
+        MarketEvent::AggTrade(_) => CHANNEL_TICK,
+        MarketEvent::Kline(_) => CHANNEL_KLINE,
+        MarketEvent::DepthUpdate(_) => CHANNEL_DEPTH,
+        MarketEvent::BookTicker(_) => CHANNEL_TICKER,
+        MarketEvent::FundingRate(_) => CHANNEL_FUNDING,
+        MarketEvent::Liquidation(_) => CHANNEL_LIQUIDATION,
+        MarketEvent::OpenInterest(_) => CHANNEL_OPEN_INTEREST,
+        MarketEvent::Ticker24h(_) => CHANNEL_TICKER_24H,
+        MarketEvent::Nbbo(_) => CHANNEL_NBBO,
+        MarketEvent::Opportunity(_) => CHANNEL_OPPORTUNITY,
+    }
+}
+
+/// Namespace of the default channel names
+pub const DEFAULT_CHANNEL_PREFIX: &str = "flash_arb";
+
+/// Channel names per event type, derived from a prefix (`{prefix}:tick`, ...)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMap {
+    pub tick: String,
+    pub kline: String,
+    pub depth: String,
+    pub ticker: String,
+    pub funding: String,
+    pub liquidation: String,
+    pub open_interest: String,
+    pub ticker_24h: String,
+    /// Best bid and ask across exchanges
+    pub nbbo: String,
+    /// Periodic gateway metrics snapshots, not market events
+    pub metrics: String,
+    /// Order and account updates from authenticated user data streams
+    pub user_data: String,
+    /// Cross-exchange spreads over the alert threshold
+    pub opportunity: String,
+}
+
+impl Default for ChannelMap {
+    fn default() -> Self {
+        Self::with_prefix(DEFAULT_CHANNEL_PREFIX)
+    }
+}
+
+impl ChannelMap {
+    /// Name every channel `{prefix}:{type}`
+    pub fn with_prefix(prefix: &str) -> Self {
+        let name = |kind: &str| format!("{}:{}", prefix, kind);
+        Self {
+            tick: name("tick"),
+            kline: name("kline"),
+            depth: name("depth"),
+            ticker: name("ticker"),
+            funding: name("funding"),
+            liquidation: name("liquidation"),
+            open_interest: name("open_interest"),
+            ticker_24h: name("ticker_24h"),
+            nbbo: name("nbbo"),
+            metrics: name("metrics"),
+            user_data: name("user_data"),
+            opportunity: name("opportunity"),
+        }
+    }
+
+    /// Replace one channel's name, keyed by its type (`tick`, `kline`, ...)
+    pub fn with_override(mut self, kind: &str, channel: impl Into<String>) -> Result<Self> {
+        let slot = match kind {
+            "tick" => &mut self.tick,
+            "kline" => &mut self.kline,
+            "depth" => &mut self.depth,
+            "ticker" => &mut self.ticker,
+            "funding" => &mut self.funding,
+            "liquidation" => &mut self.liquidation,
+            "open_interest" => &mut self.open_interest,
+            "ticker_24h" => &mut self.ticker_24h,
+            "nbbo" => &mut self.nbbo,
+            "metrics" => &mut self.metrics,
+            "user_data" => &mut self.user_data,
+            "opportunity" => &mut self.opportunity,
+            _ => return Err(GatewayError::Config(format!("Unknown Redis channel type: {}", kind))),
+        };
+        *slot = channel.into();
+        Ok(self)
+    }
+
+    /// Channel an event is published to
+    pub fn channel(&self, event: &MarketEvent) -> &str {
+        match event {
+            MarketEvent::AggTrade(_) => &self.tick,
+            MarketEvent::Kline(_) => &self.kline,
+            MarketEvent::DepthUpdate(_) => &self.depth,
+            MarketEvent::BookTicker(_) => &self.ticker,
+            MarketEvent::FundingRate(_) => &self.funding,
+            MarketEvent::Liquidation(_) => &self.liquidation,
+            MarketEvent::OpenInterest(_) => &self.open_interest,
+            MarketEvent::Ticker24h(_) => &self.ticker_24h,
+            MarketEvent::Nbbo(_) => &self.nbbo,
+            MarketEvent::Opportunity(_) => &self.opportunity,
+        }
+    }
+}
+
+/// Which channels an event is published to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)]
+pub enum ChannelRouting {
+    /// One channel per event type (`flash_arb:tick`)
+    #[default]
+    ByType,
+    /// One channel per event type and symbol (`flash_arb:tick:BTCUSDT`) instead
+    BySymbol,
+    /// Both the type channel and the per-symbol channel
+    ByTypeAndSymbol,
+}
+
+impl ChannelRouting {
+    /// Channels for an event of `symbol` whose type channel is `type_channel`
+    pub fn channels(&self, type_channel: &str, symbol: &str) -> Vec<String> {
+        let symbol_channel = || format!("{}:{}", type_channel, channel_symbol(symbol));
+        match self {
+            ChannelRouting::ByType => vec![type_channel.to_string()],
+            ChannelRouting::BySymbol => vec![symbol_channel()],
+            ChannelRouting::ByTypeAndSymbol => vec![type_channel.to_string(), symbol_channel()],
+        }
+    }
+}
+
+impl std::str::FromStr for ChannelRouting {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "by_type" | "type" => Ok(ChannelRouting::ByType),
+            "by_symbol" | "symbol" => Ok(ChannelRouting::BySymbol),
+            "by_type_and_symbol" | "both" => Ok(ChannelRouting::ByTypeAndSymbol),
+            _ => Err(GatewayError::Config(format!(
+                "Unknown Redis routing: {} (expected by_type, by_symbol or by_type_and_symbol)", s
+            ))),
+        }
+    }
+}
+
+/// Symbol as a channel name segment: uppercased, with anything but letters,
+/// digits, `-`, `_` and `.` replaced by `_` so it can't add a `:` level or a
+/// PSUBSCRIBE wildcard
+pub fn channel_symbol(symbol: &str) -> String {
+    symbol.trim()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' => c.to_ascii_uppercase(),
+            'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Prefix stream keys add to the channel's name (e.g. `flash_arb:stream:tick`)
+pub const STREAM_PREFIX: &str = "flash_arb:stream:";
+
+/// Default approximate length cap for each stream
+pub const DEFAULT_STREAM_MAXLEN: usize = 100_000;
+
+/// How events are written to Redis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// PUBLISH to channels; consumers that aren't connected miss events
+    #[default]
+    PubSub,
+    /// XADD to capped per-type streams that consumers can replay
+    Stream,
+}
+
+impl std::str::FromStr for OutputMode {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "pubsub" => Ok(OutputMode::PubSub),
+            "stream" => Ok(OutputMode::Stream),
+            _ => Err(GatewayError::Config(format!("Unknown Redis output mode: {}", s))),
+        }
+    }
+}
+
+/// Default number of events held while Redis is unreachable
+pub const DEFAULT_BACKLOG_SIZE: usize = 10_000;
+
+/// How event payloads are encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SerializationFormat {
+    #[default]
+    Json,
+    /// Compact binary encoding with named fields, faster for the consumer to decode
+    #[serde(rename = "msgpack", alias = "messagepack")]
+    MessagePack,
+}
+
+impl SerializationFormat {
+    /// Encode an event, or an [`Envelope`] around one, in this format
+    pub fn encode<T: Serialize>(&self, event: &T) -> Result<Vec<u8>> {
+        match self {
+            SerializationFormat::Json => Ok(serde_json::to_vec(event)?),
+            SerializationFormat::MessagePack => Ok(rmp_serde::to_vec_named(event)?),
+        }
+    }
+}
+
+impl std::str::FromStr for SerializationFormat {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(SerializationFormat::Json),
+            "msgpack" | "messagepack" => Ok(SerializationFormat::MessagePack),
+            _ => Err(GatewayError::Config(format!("Unknown serialization format: {}", s))),
+        }
+    }
+}
+
+/// Published form of an event: the event itself, plus the gateway's latency
+/// estimate when enabled (`{"AggTrade": {...}, "latency_ms": 12}`)
+#[derive(Debug, Serialize)]
+pub struct Envelope<'a> {
+    #[serde(flatten)]
+    pub event: &'a MarketEvent,
+    /// `received_at - timestamp`, corrected by the exchange's clock offset and clamped non-negative
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<i64>,
+}
+
+/// First byte of a zstd-compressed payload (0xC1 is never used by MessagePack and isn't valid JSON)
+pub const ZSTD_MAGIC: u8 = 0xC1;
+
+/// zstd level used when none is given
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Compression applied to encoded payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Compression {
+    #[default]
+    None,
+    /// zstd frame behind [`ZSTD_MAGIC`]
+    Zstd { level: i32 },
+}
+
+impl Compression {
+    /// Compress an encoded payload, adding the magic byte
+    pub fn compress(&self, payload: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(payload),
+            Compression::Zstd { level } => {
+                let mut out = Vec::with_capacity(payload.len() / 2 + 1);
+                out.push(ZSTD_MAGIC);
+                zstd::stream::copy_encode(payload.as_slice(), &mut out, *level)?;
+                Ok(out)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Compression {
+    type Err = GatewayError;
+
+    /// `none`, `zstd` or `zstd:<level>`
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, level) = match s.split_once(':') {
+            Some((name, level)) => (name, Some(level)),
+            None => (s, None),
+        };
+        match (name.to_lowercase().as_str(), level) {
+            ("none", None) => Ok(Compression::None),
+            ("zstd", None) => Ok(Compression::Zstd { level: DEFAULT_ZSTD_LEVEL }),
+            ("zstd", Some(level)) => {
+                let level: i32 = level.parse()
+                    .map_err(|_| GatewayError::Config(format!("Invalid zstd level: {}", level)))?;
+                if !zstd::compression_level_range().contains(&level) {
+                    return Err(GatewayError::Config(format!("zstd level {} is out of range", level)));
+                }
+                Ok(Compression::Zstd { level })
+            }
+            _ => Err(GatewayError::Config(format!("Unknown compression: {}", s))),
+        }
+    }
+}
+
+impl TryFrom<String> for Compression {
+    type Error = GatewayError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
 
 /// Configuration for Redis connection
 #[derive(Debug, Clone)]
 pub struct RedisConfig {
     pub url: String,
+    /// Events buffered before a pipelined flush; 1 publishes each event immediately
+    pub batch_size: usize,
+    /// Longest time a buffered event waits before the background flush sends it
+    pub flush_interval_ms: u64,
+    /// Pub/sub channels or streams
+    pub output_mode: OutputMode,
+    /// Approximate per-stream length cap (`MAXLEN ~`) in stream mode
+    pub maxlen: usize,
+    /// Payload encoding
+    pub format: SerializationFormat,
+    /// Compression applied after encoding
+    pub compression: Compression,
+    /// Events held for replay while Redis is unreachable; the oldest are dropped beyond this
+    pub backlog_size: usize,
+    /// Namespace of the channel names, so several gateways can share one Redis
+    pub channel_prefix: String,
+    /// Channel names replacing the prefixed ones, keyed by type (`tick`, `kline`, ...)
+    pub channel_overrides: HashMap<String, String>,
+    /// Connections to publish over; 1 sends everything through one multiplexed connection
+    pub pool_size: usize,
+    /// Events queued for the publish task; 0 publishes inline in the caller
+    pub queue_size: usize,
+    /// What happens to events when the queue is full
+    pub backpressure: BackpressurePolicy,
+    /// Warn when the queue fills to this percent of its size; 0 never warns
+    pub queue_warn_percent: u8,
+    /// Merge depth updates per symbol and publish them at most this often; 0 publishes every update
+    pub depth_coalesce_ms: u64,
+    /// Merge runs of same-price, same-side trades arriving within this many ms; 0 publishes every trade
+    pub trade_aggregation_ms: u64,
+    /// Aggregation windows in ms replacing `trade_aggregation_ms` for individual symbols
+    pub trade_aggregation_windows: HashMap<String, u64>,
+    /// Hold events this many ms and publish them in timestamp order; 0 publishes in arrival order
+    pub reorder_grace_ms: u64,
+    /// Also publish every received text frame verbatim to `{prefix}:raw:{exchange}`
+    pub publish_raw: bool,
+    /// Also publish the best bid and ask across exchanges whenever it changes
+    pub publish_nbbo: bool,
+    /// Leave an exchange's quote out of the NBBO once it's this many ms older than the newest; 0 keeps it
+    pub nbbo_max_quote_age_ms: u64,
+    /// Publish an opportunity when one exchange bids this many basis points over another's ask; 0 disables
+    pub opportunity_threshold_bps: f64,
+    /// Basis points the spread must fall under the threshold before its symbol alerts again
+    pub opportunity_hysteresis_bps: f64,
+    /// Publish to the type channels, per-symbol channels or both
+    pub routing: ChannelRouting,
+    /// Add each event's `latency_ms` to its payload
+    pub include_latency: bool,
+    /// Correct `latency_ms` by the last measured clock offset of the event's exchange
+    pub apply_clock_offset: bool,
+    /// Stop publishing market events after this many across every handle, matching the gateway's event limit
+    pub max_events: Option<u64>,
 }
 
 impl Default for RedisConfig {
     fn default() -> Self {
         Self {
             url: "redis://127.0.0.1:6379".to_string(),
+            batch_size: 1,
+            flush_interval_ms: 10,
+            output_mode: OutputMode::PubSub,
+            maxlen: DEFAULT_STREAM_MAXLEN,
+            format: SerializationFormat::Json,
+            compression: Compression::None,
+            backlog_size: DEFAULT_BACKLOG_SIZE,
+            channel_prefix: DEFAULT_CHANNEL_PREFIX.to_string(),
+            channel_overrides: HashMap::new(),
+            pool_size: 1,
+            queue_size: 0,
+            backpressure: BackpressurePolicy::default(),
+            queue_warn_percent: queue::DEFAULT_WARN_PERCENT,
+            depth_coalesce_ms: 0,
+            trade_aggregation_ms: 0,
+            trade_aggregation_windows: HashMap::new(),
+            reorder_grace_ms: 0,
+            publish_raw: false,
+            publish_nbbo: false,
+            nbbo_max_quote_age_ms: nbbo::DEFAULT_MAX_QUOTE_AGE_MS,
+            opportunity_threshold_bps: 0.0,
+            opportunity_hysteresis_bps: opportunity::DEFAULT_HYSTERESIS_BPS,
+            routing: ChannelRouting::ByType,
+            include_latency: false,
+            apply_clock_offset: false,
+            max_events: None,
+        }
+    }
+}
+
+impl RedisConfig {
+    /// Trade aggregator for the configured windows, if any window is set
+    pub fn trade_aggregator(&self) -> Option<TradeAggregator> {
+        let enabled = self.trade_aggregation_ms > 0 || self.trade_aggregation_windows.values().any(|&ms| ms > 0);
+        enabled.then(|| {
+            self.trade_aggregation_windows.iter().fold(
+                TradeAggregator::new(Duration::from_millis(self.trade_aggregation_ms)),
+                |aggregator, (symbol, &ms)| aggregator.with_symbol_window(symbol, Duration::from_millis(ms)),
+            )
+        })
+    }
+
+    /// Channel names from the prefix and overrides
+    pub fn channel_map(&self) -> Result<ChannelMap> {
+        self.channel_overrides
+            .iter()
+            .try_fold(ChannelMap::with_prefix(&self.channel_prefix), |map, (kind, channel)| {
+                map.with_override(kind, channel)
+            })
+    }
+}
+
+/// An event ready to be written to Redis
+#[derive(Debug, Clone)]
+pub struct OutgoingMessage {
+    pub channel: String,
+    pub symbol: String,
+    pub exchange: String,
+    /// Event encoded in the publisher's serialization format
+    pub payload: Vec<u8>,
+}
+
+impl OutgoingMessage {
+    /// Stream key for this message's channel, `stream:` inserted before its last segment
+    pub fn stream_key(&self) -> String {
+        match self.channel.rsplit_once(':') {
+            Some((namespace, name)) => format!("{}:stream:{}", namespace, name),
+            None => format!("stream:{}", self.channel),
+        }
+    }
+
+    /// Build the PUBLISH or XADD command that writes this message
+    pub fn command(&self, mode: OutputMode, maxlen: usize) -> Cmd {
+        match mode {
+            OutputMode::PubSub => {
+                let mut cmd = redis::cmd("PUBLISH");
+                cmd.arg(&self.channel).arg(&self.payload);
+                cmd
+            }
+            OutputMode::Stream => {
+                let mut cmd = redis::cmd("XADD");
+                cmd.arg(self.stream_key())
+                    .arg("MAXLEN").arg("~").arg(maxlen)
+                    .arg("*")
+                    .arg("symbol").arg(&self.symbol)
+                    .arg("exchange").arg(&self.exchange)
+                    .arg("payload").arg(&self.payload);
+                cmd
+            }
+        }
+    }
+}
+
+/// Buffered messages waiting for a pipelined flush
+#[derive(Debug, Default)]
+pub struct PublishBuffer {
+    messages: Vec<OutgoingMessage>,
+}
+
+impl PublishBuffer {
+    /// Queue a message, returning true once `batch_size` messages are buffered
+    pub fn push(&mut self, message: OutgoingMessage, batch_size: usize) -> bool {
+        self.messages.push(message);
+        self.messages.len() >= batch_size
+    }
+
+    /// Take all buffered messages in arrival order
+    pub fn drain(&mut self) -> Vec<OutgoingMessage> {
+        std::mem::take(&mut self.messages)
+    }
+
+    /// Check if nothing is buffered
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// Raw frames held for the raw task; further frames are dropped until it catches up
+pub const RAW_QUEUE_SIZE: usize = 1_000;
+
+/// Raw frames, as (channel, frame), on their way from the readers to the raw task
+#[derive(Clone)]
+struct RawFrames {
+    tx: mpsc::Sender<(String, String)>,
+    rx: Arc<Mutex<mpsc::Receiver<(String, String)>>>,
+}
+
+impl RawFrames {
+    fn new() -> Self {
+        let (tx, rx) = mpsc::channel(RAW_QUEUE_SIZE);
+        Self { tx, rx: Arc::new(Mutex::new(rx)) }
+    }
+}
+
+/// Bounded queue of events that failed to reach Redis, replayed in order
+#[derive(Debug)]
+pub struct EventBacklog {
+    messages: VecDeque<OutgoingMessage>,
+    capacity: usize,
+    dropped: u64,
+}
+
+impl EventBacklog {
+    /// Create a backlog holding at most `capacity` messages
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            messages: VecDeque::new(),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    /// Append messages behind everything already queued
+    pub fn extend(&mut self, messages: impl IntoIterator<Item = OutgoingMessage>) {
+        self.messages.extend(messages);
+        self.trim();
+    }
+
+    /// Put a failed batch back in front of anything queued since it was taken
+    pub fn requeue(&mut self, messages: Vec<OutgoingMessage>) {
+        for message in messages.into_iter().rev() {
+            self.messages.push_front(message);
+        }
+        self.trim();
+    }
+
+    /// Take all queued messages in order
+    pub fn drain(&mut self) -> Vec<OutgoingMessage> {
+        self.messages.drain(..).collect()
+    }
+
+    /// Number of queued messages
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Check if nothing is queued
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Messages dropped because the backlog was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Drop the oldest messages beyond capacity
+    fn trim(&mut self) {
+        while self.messages.len() > self.capacity {
+            self.messages.pop_front();
+            self.dropped += 1;
+        }
+    }
+}
+
+/// Log an opportunity as it is raised, ahead of its trip through the queue
+fn log_opportunity(opportunity: &Opportunity) {
+    info!(
+        "{} opportunity: buy {} @ {} / sell {} @ {} ({:.1} bps, edge {:.4})",
+        opportunity.symbol,
+        opportunity.buy_exchange,
+        opportunity.ask_price,
+        opportunity.sell_exchange,
+        opportunity.bid_price,
+        opportunity.spread_bps,
+        opportunity.edge
+    );
+}
+
+/// Write `messages` after any backlogged ones, keeping them all in the backlog if the write fails
+async fn send_with_backlog<C: ConnectionLike>(
+    conn: &mut C,
+    backlog: &std::sync::Mutex<EventBacklog>,
+    messages: Vec<OutgoingMessage>,
+    mode: OutputMode,
+    maxlen: usize,
+) -> Result<usize> {
+    let messages = {
+        let mut backlog = backlog.lock().unwrap();
+        if backlog.is_empty() {
+            messages
+        } else {
+            backlog.extend(messages);
+            backlog.drain()
+        }
+    };
+    if messages.is_empty() {
+        return Ok(0);
+    }
+
+    match build_pipeline(&messages, mode, maxlen).query_async::<_, ()>(conn).await {
+        Ok(()) => Ok(messages.len()),
+        Err(e) => {
+            backlog.lock().unwrap().requeue(messages);
+            Err(e.into())
+        }
+    }
+}
+
+/// Build one pipeline writing `messages` in order
+pub fn build_pipeline(messages: &[OutgoingMessage], mode: OutputMode, maxlen: usize) -> Pipeline {
+    let mut pipe = redis::pipe();
+    for message in messages {
+        pipe.add_command(message.command(mode, maxlen)).ignore();
+    }
+    pipe
+}
+
+/// Redis connections shared by every handle to a publisher.
+///
+/// Writes are spread across the connections by channel, so each channel's
+/// events stay in order on one connection while different channels are
+/// written in parallel.
+#[derive(Clone)]
+pub struct ConnectionPool<C = ConnectionManager> {
+    conns: Arc<Vec<C>>,
+}
+
+impl<C: ConnectionLike + Clone> ConnectionPool<C> {
+    /// Open `size` connections (at least one) with `connect`
+    pub async fn connect<F, Fut>(size: usize, mut connect: F) -> Result<Self>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<C>>,
+    {
+        let mut conns = Vec::with_capacity(size.max(1));
+        for _ in 0..size.max(1) {
+            conns.push(connect().await?);
         }
+        Ok(Self { conns: Arc::new(conns) })
+    }
+
+    /// Number of connections in the pool
+    pub fn len(&self) -> usize {
+        self.conns.len()
+    }
+
+    /// Check if the pool has no connections
+    pub fn is_empty(&self) -> bool {
+        self.conns.is_empty()
+    }
+
+    /// Handle to the connection that writes `channel`
+    pub fn get(&self, channel: &str) -> C {
+        let index = if self.conns.len() == 1 {
+            0
+        } else {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            channel.hash(&mut hasher);
+            (hasher.finish() % self.conns.len() as u64) as usize
+        };
+        self.conns[index].clone()
+    }
+
+    /// Write `messages` behind the backlog on the connection for their first channel.
+    ///
+    /// A batch goes out as one pipeline on a single connection.
+    pub async fn send(
+        &self,
+        backlog: &std::sync::Mutex<EventBacklog>,
+        messages: Vec<OutgoingMessage>,
+        mode: OutputMode,
+        maxlen: usize,
+    ) -> Result<usize> {
+        let channel = messages.first().map_or("", |m| m.channel.as_str());
+        let mut conn = self.get(channel);
+        send_with_backlog(&mut conn, backlog, messages, mode, maxlen).await
     }
 }
 
-/// Redis publisher for market data
-pub struct RedisPublisher {
-    client: Client,
-    conn: ConnectionManager,
+/// Redis publisher for market data.
+///
+/// Clones are handles to one logical publisher: they share the connections,
+/// the batch buffer and the backlog, and publish through `&self`, so each
+/// exchange task can hold its own clone. With a pool of more than one
+/// connection the writes also go out in parallel.
+#[derive(Clone)]
+pub struct RedisPublisher<C = ConnectionManager> {
+    pool: ConnectionPool<C>,
+    batch_size: usize,
+    flush_interval: Duration,
+    output_mode: OutputMode,
+    maxlen: usize,
+    format: SerializationFormat,
+    compression: Compression,
+    channels: ChannelMap,
+    routing: ChannelRouting,
+    /// Events failing the filter are dropped instead of published
+    filter: EventFilter,
+    /// Shared so every handle to the publisher feeds the same flush
+    buffer: Arc<Mutex<PublishBuffer>>,
+    /// Events waiting for Redis to come back, shared like the buffer
+    backlog: Arc<std::sync::Mutex<EventBacklog>>,
+    /// Events waiting for the publish task, if publishing is decoupled from the callers
+    queue: Option<EventQueue>,
+    /// Depth updates merged until their window closes, if coalescing is enabled
+    coalescer: Option<Arc<std::sync::Mutex<DepthCoalescer>>>,
+    /// Trades merged into runs until the run ends, if trade aggregation is enabled
+    aggregator: Option<Arc<std::sync::Mutex<TradeAggregator>>>,
+    /// Events held to be published in timestamp order, if reordering is enabled
+    reorder: Option<Arc<std::sync::Mutex<ReorderBuffer>>>,
+    /// Book tickers consolidated across exchanges, if the NBBO or opportunities are published
+    consolidated: Option<Arc<std::sync::Mutex<ConsolidatedBook>>>,
+    publish_nbbo: bool,
+    /// Alerts on wide cross-exchange spreads, if a threshold is set
+    spread_detector: Option<Arc<std::sync::Mutex<SpreadDetector>>>,
+    /// Prefix of the raw frame channels (`{prefix}:raw`), if raw frames are published
+    raw_prefix: Option<String>,
+    /// Raw frames waiting for the raw task, if raw frames are published
+    raw_frames: Option<RawFrames>,
+    include_latency: bool,
+    /// Where measured exchange clock offsets are read from, if latency is corrected for them
+    clock_offsets: Option<&'static Metrics>,
+    /// Market events still allowed through, shared by every handle, if publishing is capped
+    remaining_events: Option<Arc<AtomicU64>>,
 }
 
 impl RedisPublisher {
@@ -40,64 +772,1300 @@ impl RedisPublisher {
     pub async fn new(config: RedisConfig) -> Result<Self> {
         info!("Connecting to Redis at {}", config.url);
 
-        let client = Client::open(config.url)?;
-        let conn = ConnectionManager::new(client.clone()).await?;
+        let client = Client::open(config.url.as_str())?;
+        let pool = ConnectionPool::connect(config.pool_size, || {
+            let client = client.clone();
+            async move { Ok(ConnectionManager::new(client).await?) }
+        })
+        .await?;
 
-        info!("Connected to Redis successfully");
+        info!("Connected to Redis successfully ({} connections)", pool.len());
 
-        Ok(Self { client, conn })
+        Self::with_pool(config, pool)
     }
+}
 
-    /// Publish a market event to the appropriate channel
-    pub async fn publish_event(&mut self, event: &MarketEvent) -> Result<()> {
-        let (channel, payload) = self.prepare_event(event)?;
+impl<C: ConnectionLike + Clone + Send + Sync + 'static> RedisPublisher<C> {
+    /// Create a publisher writing through already-open connections
+    pub fn with_pool(config: RedisConfig, pool: ConnectionPool<C>) -> Result<Self> {
+        Ok(Self {
+            pool,
+            batch_size: config.batch_size.max(1),
+            flush_interval: Duration::from_millis(config.flush_interval_ms.max(1)),
+            output_mode: config.output_mode,
+            maxlen: config.maxlen,
+            format: config.format,
+            compression: config.compression,
+            channels: config.channel_map()?,
+            routing: config.routing,
+            filter: EventFilter::new(),
+            buffer: Arc::new(Mutex::new(PublishBuffer::default())),
+            backlog: Arc::new(std::sync::Mutex::new(EventBacklog::new(config.backlog_size))),
+            queue: (config.queue_size > 0).then(|| {
+                EventQueue::new(config.queue_size, config.backpressure).with_warn_threshold(config.queue_warn_percent)
+            }),
+            coalescer: (config.depth_coalesce_ms > 0).then(|| {
+                Arc::new(std::sync::Mutex::new(DepthCoalescer::new(Duration::from_millis(config.depth_coalesce_ms))))
+            }),
+            aggregator: config.trade_aggregator().map(|aggregator| Arc::new(std::sync::Mutex::new(aggregator))),
+            reorder: (config.reorder_grace_ms > 0).then(|| {
+                Arc::new(std::sync::Mutex::new(ReorderBuffer::new(Duration::from_millis(config.reorder_grace_ms))))
+            }),
+            consolidated: (config.publish_nbbo || config.opportunity_threshold_bps > 0.0)
+                .then(|| {
+                    let book = ConsolidatedBook::new().with_max_quote_age_ms(config.nbbo_max_quote_age_ms);
+                    Arc::new(std::sync::Mutex::new(book))
+                }),
+            publish_nbbo: config.publish_nbbo,
+            spread_detector: (config.opportunity_threshold_bps > 0.0).then(|| {
+                let detector = SpreadDetector::new(config.opportunity_threshold_bps)
+                    .with_hysteresis_bps(config.opportunity_hysteresis_bps);
+                Arc::new(std::sync::Mutex::new(detector))
+            }),
+            raw_prefix: config.publish_raw.then(|| format!("{}:raw", config.channel_prefix)),
+            raw_frames: config.publish_raw.then(RawFrames::new),
+            include_latency: config.include_latency,
+            clock_offsets: config.apply_clock_offset.then(metrics::global),
+            remaining_events: config.max_events.map(|max_events| Arc::new(AtomicU64::new(max_events))),
+        })
+    }
 
-        debug!("Publishing to {}: {}", channel, payload);
+    /// Read exchange clock offsets from this registry instead of the global one, e.g. a private one in tests
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_clock_offsets(mut self, metrics: &'static Metrics) -> Self {
+        self.clock_offsets = Some(metrics);
+        self
+    }
 
-        self.conn
-            .publish(channel, payload)
-            .await?;
+    /// Check if events are buffered and pipelined rather than published one by one
+    pub fn is_batching(&self) -> bool {
+        self.batch_size > 1
+    }
+
+    /// Number of events waiting in the backlog for Redis to come back
+    pub fn backlog_len(&self) -> usize {
+        self.backlog.lock().unwrap().len()
+    }
+
+    /// Number of events dropped because the backlog was full
+    pub fn dropped_events(&self) -> u64 {
+        self.backlog.lock().unwrap().dropped()
+    }
+
+    /// Events waiting for the publish task and the most it has had waiting at once
+    pub fn queue_depth(&self) -> (usize, usize) {
+        self.queue.as_ref().map_or((0, 0), |queue| (queue.len(), queue.high_watermark()))
+    }
+
+    /// Number of events dropped because the publish queue was full
+    pub fn queue_dropped(&self) -> u64 {
+        self.queue.as_ref().map_or(0, EventQueue::dropped)
+    }
+
+    /// Drop events the filter rejects instead of publishing them
+    pub fn with_filter(mut self, filter: EventFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Publish a market event to the appropriate channel or stream.
+    ///
+    /// Events rejected by the filter are skipped. With depth coalescing,
+    /// depth updates are held for the coalesce task instead, and with trade
+    /// aggregation, raw trades are held until their run ends. With reordering,
+    /// events wait out the grace period so they go out by timestamp. With the
+    /// NBBO enabled, a book ticker that moves the best bid or ask across
+    /// exchanges is followed by an opportunity when that opens a spread over
+    /// the alert threshold, and by the new NBBO. With a publish
+    /// queue the event is only queued for the publish task, so a slow Redis
+    /// doesn't hold up the caller. If Redis can't be reached the event is kept
+    /// in the backlog and sent, in order, ahead of later events once Redis is back.
+    /// Once the event limit is reached, every further event is dropped.
+    pub async fn publish_event(&self, event: &MarketEvent) -> Result<()> {
+        if !self.take_event_slot() {
+            return Ok(());
+        }
+        if let EventResult::Skipped = self.filter.apply(event) {
+            return Ok(());
+        }
+
+        if let (MarketEvent::DepthUpdate(update), Some(coalescer)) = (event, &self.coalescer) {
+            coalescer.lock().unwrap().push(update.clone(), Instant::now());
+            return Ok(());
+        }
+        if let (MarketEvent::AggTrade(trade), Some(aggregator)) = (event, &self.aggregator) {
+            if !aggregate::aggregates_trades(trade.exchange) {
+                let ready = aggregator.lock().unwrap().push(trade.clone(), Instant::now());
+                return match ready {
+                    Some(trade) => self.dispatch(&MarketEvent::AggTrade(trade)).await,
+                    None => Ok(()),
+                };
+            }
+        }
+        if let (MarketEvent::BookTicker(ticker), Some(consolidated)) = (event, &self.consolidated) {
+            let nbbo = consolidated.lock().unwrap().update(ticker);
+            // Each derived event is dispatched even if an earlier one failed; the first error is returned
+            let mut result = self.dispatch(event).await;
+            let Some(nbbo) = nbbo else {
+                return result;
+            };
+            if let Some(ref detector) = self.spread_detector {
+                let opportunity = detector.lock().unwrap().check(&nbbo);
+                if let Some(opportunity) = opportunity {
+                    log_opportunity(&opportunity);
+                    result = result.and(self.dispatch(&MarketEvent::Opportunity(opportunity)).await);
+                }
+            }
+            if self.publish_nbbo {
+                result = result.and(self.dispatch(&MarketEvent::Nbbo(nbbo)).await);
+            }
+            return result;
+        }
+        self.dispatch(event).await
+    }
+
+    /// Use up one of the remaining events, false once the event limit is reached.
+    ///
+    /// The slot is taken before anything else, so filtered-out events count
+    /// towards the limit just as they do in the gateway loop.
+    fn take_event_slot(&self) -> bool {
+        self.remaining_events.as_ref().is_none_or(|remaining| {
+            remaining.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1)).is_ok()
+        })
+    }
+
+    /// Hold an event for the reorder task, or pass it on when it is late or reordering is off
+    async fn dispatch(&self, event: &MarketEvent) -> Result<()> {
+        let Some(ref reorder) = self.reorder else {
+            return self.forward(event).await;
+        };
+
+        let late = reorder.lock().unwrap().push(event.clone(), Instant::now());
+        match late {
+            Some(event) => {
+                debug!("{} {} event at {} arrived behind the reorder horizon", event.exchange(), event.symbol(), event.timestamp());
+                metrics::global().record_late(event.exchange());
+                self.forward(&event).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Queue an event for the publish task, or publish it now without a queue
+    async fn forward(&self, event: &MarketEvent) -> Result<()> {
+        match self.queue {
+            Some(ref queue) => {
+                queue.push(event.clone()).await;
+                Ok(())
+            }
+            None => self.publish_now(event).await,
+        }
+    }
+
+    /// Publish an event without going through the queue
+    async fn publish_now(&self, event: &MarketEvent) -> Result<()> {
+        let result = self.publish(event).await;
+        if result.is_err() {
+            metrics::global().record_publish_failure(event.exchange());
+        }
+        result
+    }
+
+    /// Encode an event and send it now or buffer it for the next flush
+    async fn publish(&self, event: &MarketEvent) -> Result<()> {
+        let messages = self.prepare_event(event)?;
+
+        for message in &messages {
+            debug!("Publishing to {}: {} bytes", message.channel, message.payload.len());
+        }
+
+        if !self.is_batching() {
+            self.send(messages).await?;
+            return Ok(());
+        }
+
+        let mut full = false;
+        {
+            let mut buffer = self.buffer.lock().await;
+            for message in messages {
+                full |= buffer.push(message, self.batch_size);
+            }
+        }
+        if full {
+            self.flush().await?;
+        }
 
         Ok(())
     }
 
-    /// Prepare an event for publishing (returns channel and JSON payload)
-    fn prepare_event(&self, event: &MarketEvent) -> Result<(String, String)> {
-        let json = to_string(event)?;
+    /// Send all buffered events in a single pipeline, returning how many were sent
+    pub async fn flush(&self) -> Result<usize> {
+        let messages = self.buffer.lock().await.drain();
+        if messages.is_empty() {
+            return Ok(0);
+        }
 
-        let channel = match event {
-            MarketEvent::AggTrade(_) => CHANNEL_TICK,
-            MarketEvent::Kline(_) => CHANNEL_KLINE,
-            MarketEvent::DepthUpdate(_) => CHANNEL_DEPTH,
-            MarketEvent::BookTicker(_) => CHANNEL_TICKER,
+        let count = self.send(messages).await?;
+        debug!("Flushed {} buffered events", count);
+        Ok(count)
+    }
+
+    /// Send any backlogged events, returning how many were sent
+    pub async fn replay_backlog(&self) -> Result<usize> {
+        let count = self.send(Vec::new()).await?;
+        if count > 0 {
+            info!("Replayed {} backlogged events to Redis", count);
+        }
+        Ok(count)
+    }
+
+    /// Write messages behind the backlog, backlogging them all on failure
+    async fn send(&self, messages: Vec<OutgoingMessage>) -> Result<usize> {
+        let result = self.pool.send(&self.backlog, messages, self.output_mode, self.maxlen).await;
+        if result.is_err() {
+            warn!("Redis unavailable, {} events backlogged ({} dropped)", self.backlog_len(), self.dropped_events());
+        }
+        result
+    }
+
+    /// Flush the shared buffer every flush interval so quiet periods don't strand events
+    pub fn spawn_flush_task(&self) -> JoinHandle<()> {
+        let flusher = self.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = time::interval(flusher.flush_interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = flusher.flush().await {
+                    error!("Failed to flush Redis batch: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Publish queued events on their own task, if publishing is queued
+    pub fn spawn_publish_task(&self) -> Option<JoinHandle<()>> {
+        let queue = self.queue.clone()?;
+        let publisher = self.clone();
+
+        Some(tokio::spawn(async move {
+            loop {
+                let event = queue.pop().await;
+                if let Err(e) = publisher.publish_now(&event).await {
+                    error!("Failed to publish queued event to Redis: {}", e);
+                }
+            }
+        }))
+    }
+
+    /// Publish coalesced depth updates as their windows close, if coalescing is enabled
+    pub fn spawn_coalesce_task(&self) -> Option<JoinHandle<()>> {
+        let coalescer = self.coalescer.clone()?;
+        let publisher = self.clone();
+        let window = coalescer.lock().unwrap().window();
+
+        Some(tokio::spawn(async move {
+            // Check several times a window so an update waits little past its own
+            let mut ticker = time::interval((window / 5).max(Duration::from_millis(1)));
+            loop {
+                ticker.tick().await;
+                let due = coalescer.lock().unwrap().take_due(Instant::now());
+                for update in due {
+                    if let Err(e) = publisher.dispatch(&MarketEvent::DepthUpdate(update)).await {
+                        error!("Failed to publish coalesced depth update: {}", e);
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Publish every held depth update now, e.g. on shutdown, returning how many were sent
+    pub async fn flush_coalesced(&self) -> usize {
+        let Some(ref coalescer) = self.coalescer else {
+            return 0;
+        };
+
+        let pending = coalescer.lock().unwrap().take_all();
+        let mut count = 0;
+        for update in pending {
+            if self.publish_now(&MarketEvent::DepthUpdate(update)).await.is_ok() {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Publish aggregated trades as their windows close, if trade aggregation is enabled
+    pub fn spawn_aggregate_task(&self) -> Option<JoinHandle<()>> {
+        let aggregator = self.aggregator.clone()?;
+        let publisher = self.clone();
+        let window = aggregator.lock().unwrap().min_window();
+
+        Some(tokio::spawn(async move {
+            let mut ticker = time::interval((window / 5).max(Duration::from_millis(1)));
+            loop {
+                ticker.tick().await;
+                let due = aggregator.lock().unwrap().take_due(Instant::now());
+                for trade in due {
+                    if let Err(e) = publisher.dispatch(&MarketEvent::AggTrade(trade)).await {
+                        error!("Failed to publish aggregated trade: {}", e);
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Publish every held trade run now, e.g. on shutdown, returning how many were sent
+    pub async fn flush_aggregated(&self) -> usize {
+        let Some(ref aggregator) = self.aggregator else {
+            return 0;
         };
 
-        Ok((channel.to_string(), json))
+        let pending = aggregator.lock().unwrap().take_all();
+        let mut count = 0;
+        for trade in pending {
+            if self.publish_now(&MarketEvent::AggTrade(trade)).await.is_ok() {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Publish held events in timestamp order as their grace periods pass, if reordering is enabled
+    pub fn spawn_reorder_task(&self) -> Option<JoinHandle<()>> {
+        let reorder = self.reorder.clone()?;
+        let publisher = self.clone();
+        let grace = reorder.lock().unwrap().grace();
+
+        Some(tokio::spawn(async move {
+            let mut ticker = time::interval((grace / 5).max(Duration::from_millis(1)));
+            loop {
+                ticker.tick().await;
+                let due = reorder.lock().unwrap().take_due(Instant::now());
+                for event in due {
+                    if let Err(e) = publisher.forward(&event).await {
+                        error!("Failed to publish reordered event: {}", e);
+                    }
+                }
+            }
+        }))
     }
 
-    /// Publish to a custom channel
-    pub async fn publish_to_channel(&mut self, channel: &str, data: &str) -> Result<()> {
-        self.conn
-            .publish(channel, data)
+    /// Publish every held event in timestamp order now, e.g. on shutdown, returning how many were sent
+    pub async fn flush_reordered(&self) -> usize {
+        let Some(ref reorder) = self.reorder else {
+            return 0;
+        };
+
+        let pending = reorder.lock().unwrap().take_all();
+        let mut count = 0;
+        for event in pending {
+            if self.publish_now(&event).await.is_ok() {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Publish whatever is still queued, e.g. on shutdown, returning how many were sent.
+    ///
+    /// Events that fail to send stay in the backlog like any other.
+    pub async fn drain_queue(&self) -> usize {
+        let Some(ref queue) = self.queue else {
+            return 0;
+        };
+
+        let mut count = 0;
+        while let Some(event) = queue.try_pop() {
+            if self.publish_now(&event).await.is_ok() {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Publish everything still held, e.g. on shutdown, returning how many events were sent.
+    ///
+    /// The batch buffer goes out first, as it holds the oldest events, then
+    /// the queued ones, those held for reordering, and the coalesced depth
+    /// updates and aggregated trades, which are newer than anything queued.
+    /// Events that fail to send stay in the backlog like any other.
+    pub async fn shutdown(&self) -> Result<usize> {
+        let buffered = self.flush().await?;
+        let drained = self.drain_queue().await;
+        if drained > 0 {
+            info!("Published {} queued events", drained);
+        }
+        let reordered = self.flush_reordered().await;
+        if reordered > 0 {
+            info!("Published {} held events in timestamp order", reordered);
+        }
+        let coalesced = self.flush_coalesced().await;
+        if coalesced > 0 {
+            info!("Published {} coalesced depth updates", coalesced);
+        }
+        let aggregated = self.flush_aggregated().await;
+        if aggregated > 0 {
+            info!("Published {} aggregated trades", aggregated);
+        }
+        // Batching holds on to what was just published, so send that too
+        self.flush().await?;
+        let raw = self.flush_raw().await;
+        if raw > 0 {
+            debug!("Published {} queued raw frames", raw);
+        }
+        Ok(buffered + drained + reordered + coalesced + aggregated)
+    }
+
+    /// Prepare an event for publishing, one message (channel, identifying
+    /// fields and encoded payload) per channel the routing sends it to
+    fn prepare_event(&self, event: &MarketEvent) -> Result<Vec<OutgoingMessage>> {
+        let latency_ms = self.include_latency.then(|| {
+            let offset = self.clock_offsets
+                .and_then(|metrics| metrics.clock_offset(event.exchange()))
+                .unwrap_or(0);
+            latency::event_latency_ms(event, offset)
+        }).flatten();
+        let payload = self.compression.compress(self.format.encode(&Envelope { event, latency_ms })?)?;
+
+        Ok(self.routing
+            .channels(self.channels.channel(event), event.symbol())
+            .into_iter()
+            .map(|channel| OutgoingMessage {
+                channel,
+                symbol: event.symbol().to_string(),
+                exchange: event.exchange().to_string(),
+                payload: payload.clone(),
+            })
+            .collect())
+    }
+
+    /// Channel raw frames from an exchange are published to, if raw publishing is enabled
+    pub fn raw_channel(&self, exchange: ExchangeType) -> Option<String> {
+        self.raw_prefix.as_ref().map(|prefix| format!("{}:{}", prefix, exchange))
+    }
+
+    /// Queue a received frame for the raw task to publish verbatim to the exchange's raw channel; does nothing unless enabled.
+    ///
+    /// Raw frames bypass the filter, event queue, batching and backlog, and
+    /// this never waits: a frame that finds the raw queue full is dropped, so
+    /// a slow Redis can't hold up the socket reads or the parsed events.
+    pub fn publish_raw(&self, exchange: ExchangeType, frame: &str) {
+        let (Some(channel), Some(raw)) = (self.raw_channel(exchange), &self.raw_frames) else {
+            return;
+        };
+        if raw.tx.try_send((channel, frame.to_string())).is_err() {
+            debug!("Raw frame queue full, dropping a {} frame", exchange);
+        }
+    }
+
+    /// Publish queued raw frames on their own task, if raw frames are published
+    pub fn spawn_raw_task(&self) -> Option<JoinHandle<()>> {
+        let raw = self.raw_frames.clone()?;
+        let publisher = self.clone();
+
+        Some(tokio::spawn(async move {
+            let mut frames = raw.rx.lock().await;
+            while let Some((channel, frame)) = frames.recv().await {
+                if let Err(e) = publisher.publish_to_channel(&channel, frame).await {
+                    debug!("Failed to publish raw frame to Redis: {}", e);
+                }
+            }
+        }))
+    }
+
+    /// Publish the raw frames still queued, e.g. on shutdown, returning how many were sent
+    pub async fn flush_raw(&self) -> usize {
+        let Some(ref raw) = self.raw_frames else {
+            return 0;
+        };
+
+        let mut frames = raw.rx.lock().await;
+        let mut count = 0;
+        while let Ok((channel, frame)) = frames.try_recv() {
+            if self.publish_to_channel(&channel, frame).await.is_ok() {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Publish a metrics snapshot as JSON to the metrics channel
+    pub async fn publish_metrics(&self, snapshot: &GatewayMetrics) -> Result<()> {
+        let payload = serde_json::to_vec(snapshot)?;
+        self.publish_to_channel(&self.channels.metrics, payload).await
+    }
+
+    /// Publish an order or account update as JSON to the user data channel
+    pub async fn publish_user_data(&self, event: &UserDataEvent) -> Result<()> {
+        let payload = serde_json::to_vec(event)?;
+        self.publish_to_channel(&self.channels.user_data, payload).await
+    }
+
+    /// Publish to a custom channel (text or already-encoded bytes)
+    pub async fn publish_to_channel(&self, channel: &str, data: impl AsRef<[u8]>) -> Result<()> {
+        self.pool
+            .get(channel)
+            .publish::<_, _, ()>(channel, data.as_ref())
             .await?;
         Ok(())
     }
 
-    /// Ping Redis to check connection
-    pub async fn ping(&mut self) -> Result<String> {
-        let response: String = self.conn.ping().await?;
+    /// Ping Redis to check connection, replaying the backlog once it responds
+    pub async fn ping(&self) -> Result<String> {
+        let response: String = redis::cmd("PING").query_async(&mut self.pool.get("")).await?;
+        self.replay_backlog().await?;
         Ok(response)
     }
-
-    /// Get the Redis client
-    pub fn client(&self) -> &Client {
-        &self.client
-    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{self, MockRedisConnection};
+    use std::borrow::Cow;
+
+    /// Undo [`Compression::compress`], passing payloads without the magic byte through
+    fn decompress(payload: &[u8]) -> Result<Cow<'_, [u8]>> {
+        match payload.split_first() {
+            Some((&ZSTD_MAGIC, frame)) => Ok(Cow::Owned(zstd::stream::decode_all(frame)?)),
+            _ => Ok(Cow::Borrowed(payload)),
+        }
+    }
+
+    #[test]
+    fn test_buffered_events_flush_as_one_pipeline() {
+        let batch_size = 5;
+        let mut buffer = PublishBuffer::default();
+
+        for i in 0..batch_size - 1 {
+            assert!(!buffer.push(message(CHANNEL_TICK, &format!("tick-{}", i)), batch_size));
+        }
+        assert!(buffer.push(message(CHANNEL_DEPTH, "depth-0"), batch_size));
+
+        let messages = buffer.drain();
+        assert!(buffer.is_empty());
+        assert_eq!(messages.len(), batch_size);
+
+        let pipe = build_pipeline(&messages, OutputMode::PubSub, DEFAULT_STREAM_MAXLEN);
+        let commands: Vec<Vec<u8>> = pipe.cmd_iter().map(|cmd| cmd.get_packed_command()).collect();
+        assert_eq!(commands.len(), batch_size);
+
+        // Every PUBLISH goes out in one packed request, in arrival order
+        let packed = pipe.get_packed_pipeline();
+        assert_eq!(packed, commands.concat());
+        let payload_order: Vec<usize> = (0..batch_size - 1)
+            .map(|i| find(&packed, format!("tick-{}", i).as_bytes()))
+            .collect();
+        assert!(payload_order.windows(2).all(|w| w[0] < w[1]));
+        assert!(find(&packed, b"depth-0") > payload_order[batch_size - 2]);
+    }
+
+    #[test]
+    fn test_channel_prefix_routes_events() {
+        let config = RedisConfig {
+            channel_prefix: "inst2".to_string(),
+            ..RedisConfig::default()
+        };
+        let channels = config.channel_map().unwrap();
+        let tick = MarketEvent::AggTrade(crate::exchange::AggTrade {
+            exchange: crate::exchange::ExchangeType::Binance,
+            symbol: "BTCUSDT".to_string(),
+            price: 50000.0,
+            quantity: 0.1,
+            timestamp: 0,
+            is_buyer_maker: false,
+            trade_id: 1,
+            received_at: 0,
+        });
+        assert_eq!(channels.channel(&tick), "inst2:tick");
+        assert_eq!(channels.open_interest, "inst2:open_interest");
+        assert_eq!(message("inst2:tick", "{}").stream_key(), "inst2:stream:tick");
+
+        // The default prefix keeps the existing names
+        assert_eq!(RedisConfig::default().channel_map().unwrap().channel(&tick), CHANNEL_TICK);
+    }
+
+    #[tokio::test]
+    async fn test_routing_modes_pick_channels() {
+        let tick = MarketEvent::AggTrade(crate::exchange::AggTrade {
+            exchange: crate::exchange::ExchangeType::Binance,
+            symbol: "btcusdt".to_string(),
+            price: 50000.0,
+            quantity: 0.1,
+            timestamp: 0,
+            is_buyer_maker: false,
+            trade_id: 1,
+            received_at: 0,
+        });
+        let tick = &tick;
+        let channels = |routing: ChannelRouting| async move {
+            let config = RedisConfig { routing, ..RedisConfig::default() };
+            let publisher = MockRedisConnection::default().publisher(config).await.unwrap();
+            publisher.prepare_event(tick).unwrap().into_iter().map(|m| m.channel).collect::<Vec<_>>()
+        };
+
+        assert_eq!(channels(ChannelRouting::ByType).await, vec!["flash_arb:tick"]);
+        assert_eq!(channels(ChannelRouting::BySymbol).await, vec!["flash_arb:tick:BTCUSDT"]);
+        assert_eq!(
+            channels(ChannelRouting::ByTypeAndSymbol).await,
+            vec!["flash_arb:tick", "flash_arb:tick:BTCUSDT"]
+        );
+
+        assert_eq!(channel_symbol("ETH-USDT"), "ETH-USDT");
+        assert_eq!(channel_symbol("btc:usdt*"), "BTC_USDT_");
+        assert_eq!("by-type-and-symbol".parse::<ChannelRouting>().unwrap(), ChannelRouting::ByTypeAndSymbol);
+        assert!("by_exchange".parse::<ChannelRouting>().is_err());
+    }
+
+    #[test]
+    fn test_channel_overrides() {
+        let config = RedisConfig {
+            channel_prefix: "inst2".to_string(),
+            channel_overrides: HashMap::from([("depth".to_string(), "books".to_string())]),
+            ..RedisConfig::default()
+        };
+        let channels = config.channel_map().unwrap();
+        assert_eq!(channels.depth, "books");
+        assert_eq!(channels.kline, "inst2:kline");
+        assert_eq!(message("books", "{}").stream_key(), "stream:books");
+
+        assert!(ChannelMap::default().with_override("trades", "x").is_err());
+    }
+
+    #[test]
+    fn test_stream_mode_builds_capped_xadd() {
+        let cmd = message(CHANNEL_TICK, "{}").command(OutputMode::Stream, 5000);
+        let args: Vec<String> = cmd.args_iter()
+            .map(|arg| match arg {
+                redis::Arg::Simple(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+                redis::Arg::Cursor => String::new(),
+            })
+            .collect();
+
+        assert_eq!(
+            args,
+            vec![
+                "XADD", "flash_arb:stream:tick", "MAXLEN", "~", "5000", "*",
+                "symbol", "BTCUSDT", "exchange", "binance", "payload", "{}",
+            ]
+        );
+    }
+
+    fn message(channel: &str, payload: &str) -> OutgoingMessage {
+        OutgoingMessage {
+            channel: channel.to_string(),
+            symbol: "BTCUSDT".to_string(),
+            exchange: "binance".to_string(),
+            payload: payload.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let event = MarketEvent::AggTrade(crate::exchange::AggTrade {
+            exchange: crate::exchange::ExchangeType::Binance,
+            symbol: "BTCUSDT".to_string(),
+            price: 50000.5,
+            quantity: 0.25,
+            timestamp: 1700000000000,
+            is_buyer_maker: true,
+            trade_id: 42,
+            received_at: 1700000000000,
+        });
+
+        let bytes = SerializationFormat::MessagePack.encode(&event).unwrap();
+        assert!(bytes.len() < SerializationFormat::Json.encode(&event).unwrap().len());
+
+        let decoded: MarketEvent = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, event);
+    }
+
+    #[test]
+    fn test_zstd_round_trip() {
+        let levels: Vec<(f64, f64)> = (0..500)
+            .map(|i| (50000.0 - i as f64 * 0.5, 1.0 + i as f64 * 0.25))
+            .collect();
+        let event = MarketEvent::DepthUpdate(crate::exchange::DepthUpdate {
+            exchange: crate::exchange::ExchangeType::Binance,
+            symbol: "BTCUSDT".to_string(),
+            bids: levels.clone(),
+            asks: levels,
+            timestamp: 1700000000000,
+            is_snapshot: true,
+            first_update_id: Some(1),
+            final_update_id: Some(2),
+            prev_final_update_id: None,
+            received_at: 1700000000000,
+        });
+
+        let encoded = SerializationFormat::Json.encode(&event).unwrap();
+        let compressed = Compression::Zstd { level: DEFAULT_ZSTD_LEVEL }.compress(encoded.clone()).unwrap();
+        assert_eq!(compressed[0], ZSTD_MAGIC);
+        assert!(compressed.len() < encoded.len() / 2);
+
+        let decoded: MarketEvent = serde_json::from_slice(&decompress(&compressed).unwrap()).unwrap();
+        assert_eq!(decoded, event);
+
+        // Uncompressed payloads pass straight through
+        assert_eq!(&*decompress(&encoded).unwrap(), encoded.as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_latency_ms_is_added_to_the_envelope() {
+        let redis = MockRedisConnection::default();
+        // A private registry keeps the offset set below from leaking into other tests
+        let offsets: &'static Metrics = Box::leak(Box::new(Metrics::new()));
+        let config = RedisConfig { include_latency: true, ..RedisConfig::default() };
+        let publisher = redis.publisher(config).await.unwrap().with_clock_offsets(offsets);
+        let trade = |exchange, received_at| MarketEvent::AggTrade(crate::exchange::AggTrade {
+            exchange,
+            symbol: "BTCUSDT".to_string(),
+            price: 50000.0,
+            quantity: 0.1,
+            timestamp: 1_000,
+            is_buyer_maker: false,
+            trade_id: 1,
+            received_at,
+        });
+        let latency_of = |event: &MarketEvent| {
+            let payload = &publisher.prepare_event(event).unwrap()[0].payload;
+            let value: serde_json::Value = serde_json::from_slice(payload).unwrap();
+            assert!(value.get("AggTrade").is_some());
+            value.get("latency_ms").map(|latency| latency.as_i64().unwrap())
+        };
+
+        assert_eq!(latency_of(&trade(ExchangeType::Binance, 1_042)), Some(42));
+        // A local clock 60ms behind KuCoin's makes the event look older than it arrived
+        offsets.set_clock_offset(ExchangeType::Kucoin, -60);
+        assert_eq!(latency_of(&trade(ExchangeType::Kucoin, 1_042)), Some(0));
+        // Unstamped events carry no estimate
+        assert_eq!(latency_of(&trade(ExchangeType::Binance, 0)), None);
+
+        // Off by default, leaving the bare event
+        let plain = redis.publisher(RedisConfig::default()).await.unwrap();
+        let payload = &plain.prepare_event(&trade(ExchangeType::Binance, 1_042)).unwrap()[0].payload;
+        let decoded: MarketEvent = serde_json::from_slice(payload).unwrap();
+        assert_eq!(decoded, trade(ExchangeType::Binance, 1_042));
+
+        // MessagePack payloads carry it the same way
+        let config = RedisConfig { include_latency: true, format: SerializationFormat::MessagePack, ..RedisConfig::default() };
+        let msgpack = redis.publisher(config).await.unwrap();
+        let payload = &msgpack.prepare_event(&trade(ExchangeType::Binance, 1_042)).unwrap()[0].payload;
+        let value: serde_json::Value = rmp_serde::from_slice(payload).unwrap();
+        assert_eq!(value["latency_ms"], 42);
+        assert_eq!(value["AggTrade"]["trade_id"], 1);
+    }
+
+    #[test]
+    fn test_parse_compression() {
+        assert_eq!("none".parse::<Compression>().unwrap(), Compression::None);
+        assert_eq!("zstd".parse::<Compression>().unwrap(), Compression::Zstd { level: DEFAULT_ZSTD_LEVEL });
+        assert_eq!("zstd:19".parse::<Compression>().unwrap(), Compression::Zstd { level: 19 });
+        assert!("zstd:99".parse::<Compression>().is_err());
+        assert!("gzip".parse::<Compression>().is_err());
+    }
+
+    /// Connection that fails every write while `down` is set
+    #[derive(Clone, Default)]
+    struct FlakyConnection {
+        down: bool,
+        pipelines: Vec<Vec<u8>>,
+    }
+
+    impl ConnectionLike for FlakyConnection {
+        fn req_packed_command<'a>(&'a mut self, _cmd: &'a Cmd) -> redis::RedisFuture<'a, redis::Value> {
+            unimplemented!("publishing always goes through a pipeline")
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            pipe: &'a Pipeline,
+            _offset: usize,
+            count: usize,
+        ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+            let result = if self.down {
+                Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into())
+            } else {
+                self.pipelines.push(pipe.get_packed_pipeline());
+                Ok(vec![redis::Value::Okay; count])
+            };
+            Box::pin(std::future::ready(result))
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backlog_grows_while_down_and_drains_in_order() {
+        let mut conn = FlakyConnection { down: true, ..Default::default() };
+        let backlog = std::sync::Mutex::new(EventBacklog::new(DEFAULT_BACKLOG_SIZE));
+
+        for i in 0..3 {
+            assert!(send_tick(&mut conn, &backlog, &format!("event-{}", i)).await.is_err());
+            assert_eq!(backlog.lock().unwrap().len(), i + 1);
+        }
+        assert!(conn.pipelines.is_empty());
+
+        // The backlog goes out ahead of the first event after recovery
+        conn.down = false;
+        assert_eq!(send_tick(&mut conn, &backlog, "event-3").await.unwrap(), 4);
+        assert!(backlog.lock().unwrap().is_empty());
+        assert_eq!(conn.pipelines.len(), 1);
+
+        let packed = &conn.pipelines[0];
+        let order: Vec<usize> = (0..4).map(|i| find(packed, format!("event-{}", i).as_bytes())).collect();
+        assert!(order.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    async fn send_tick(
+        conn: &mut FlakyConnection,
+        backlog: &std::sync::Mutex<EventBacklog>,
+        payload: &str,
+    ) -> Result<usize> {
+        let messages = vec![message(CHANNEL_TICK, payload)];
+        send_with_backlog(conn, backlog, messages, OutputMode::PubSub, DEFAULT_STREAM_MAXLEN).await
+    }
+
+    #[test]
+    fn test_full_backlog_drops_oldest() {
+        let mut backlog = EventBacklog::new(3);
+        backlog.extend((0..2).map(|i| message(CHANNEL_TICK, &format!("event-{}", i))));
+
+        // A failed batch returns to the front, ahead of events queued meanwhile
+        backlog.requeue((2..4).map(|i| message(CHANNEL_TICK, &format!("event-{}", i))).collect());
+        assert_eq!(backlog.len(), 3);
+        assert_eq!(backlog.dropped(), 1);
+
+        let payloads: Vec<Vec<u8>> = backlog.drain().into_iter().map(|m| m.payload).collect();
+        assert_eq!(payloads, vec![b"event-3".to_vec(), b"event-0".to_vec(), b"event-1".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_pool_opens_one_connection_per_slot() {
+        let mut opened = 0;
+        let pool = ConnectionPool::connect(4, || {
+            opened += 1;
+            std::future::ready(Ok(FlakyConnection::default()))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(opened, 4);
+        assert_eq!(pool.len(), 4);
+    }
+
+    /// Connection whose writes only complete once `parties` writes are in flight at the same time
+    #[derive(Clone)]
+    struct RendezvousConnection {
+        barrier: Arc<tokio::sync::Barrier>,
+    }
+
+    impl ConnectionLike for RendezvousConnection {
+        fn req_packed_command<'a>(&'a mut self, _cmd: &'a Cmd) -> redis::RedisFuture<'a, redis::Value> {
+            unimplemented!("publishing always goes through a pipeline")
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _pipe: &'a Pipeline,
+            _offset: usize,
+            count: usize,
+        ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+            Box::pin(async move {
+                self.barrier.wait().await;
+                Ok(vec![redis::Value::Okay; count])
+            })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pooled_sends_run_concurrently() {
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let pool = ConnectionPool::connect(2, || {
+            std::future::ready(Ok(RendezvousConnection { barrier: barrier.clone() }))
+        })
+        .await
+        .unwrap();
+        let backlog = std::sync::Mutex::new(EventBacklog::new(DEFAULT_BACKLOG_SIZE));
+
+        // Neither write can finish until both are in flight, so serialized sends would hang
+        let send = |channel: &str| {
+            pool.send(&backlog, vec![message(channel, "event")], OutputMode::PubSub, DEFAULT_STREAM_MAXLEN)
+        };
+        let (tick, kline) = time::timeout(Duration::from_secs(5), async {
+            tokio::join!(send(CHANNEL_TICK), send(CHANNEL_KLINE))
+        })
+        .await
+        .expect("pooled sends were serialized");
+
+        assert_eq!(tick.unwrap(), 1);
+        assert_eq!(kline.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cloned_publisher_publishes_from_two_tasks() {
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let pool = ConnectionPool::connect(1, || {
+            std::future::ready(Ok(RendezvousConnection { barrier: barrier.clone() }))
+        })
+        .await
+        .unwrap();
+        let publisher = RedisPublisher::with_pool(RedisConfig::default(), pool).unwrap();
+
+        // Both writes share one connection and each only completes once the other is in flight
+        let tasks: Vec<_> = ["BTCUSDT", "ETHUSDT"]
+            .into_iter()
+            .map(|symbol| {
+                let publisher = publisher.clone();
+                tokio::spawn(async move { publisher.publish_event(&trade(symbol)).await })
+            })
+            .collect();
+
+        for task in tasks {
+            time::timeout(Duration::from_secs(5), task)
+                .await
+                .expect("clones were serialized")
+                .unwrap()
+                .unwrap();
+        }
+        assert_eq!(publisher.backlog_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_event_limit_is_shared_by_clones() {
+        let redis = MockRedisConnection::default();
+        let publisher = redis.publisher(RedisConfig { max_events: Some(3), ..RedisConfig::default() }).await.unwrap();
+        let clone = publisher.clone();
+
+        for symbol in ["BTCUSDT", "ETHUSDT"] {
+            publisher.publish_event(&trade(symbol)).await.unwrap();
+            clone.publish_event(&trade(symbol)).await.unwrap();
+        }
+        assert_eq!(redis.commands(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_queued_publish_returns_without_writing() {
+        let redis = MockRedisConnection::default();
+        let config = RedisConfig { queue_size: 2, backpressure: BackpressurePolicy::DropOldest, ..RedisConfig::default() };
+        let publisher = redis.publisher(config).await.unwrap();
+
+        // Nothing drains the queue, so the oldest event makes room for the newest
+        for symbol in ["BTCUSDT", "ETHUSDT", "SOLUSDT"] {
+            publisher.publish_event(&trade(symbol)).await.unwrap();
+        }
+        assert_eq!(redis.commands(), 0);
+        assert_eq!(publisher.queue_depth().0, 2);
+        assert_eq!(publisher.queue_dropped(), 1);
+
+        assert_eq!(publisher.drain_queue().await, 2);
+        let sent = redis.pipelines().concat();
+        assert!(find_opt(&sent, b"BTCUSDT").is_none());
+        assert!(find(&sent, b"ETHUSDT") < find(&sent, b"SOLUSDT"));
+    }
+
+    #[tokio::test]
+    async fn test_depth_updates_are_coalesced() {
+        let redis = MockRedisConnection::default();
+        let config = RedisConfig { depth_coalesce_ms: 60_000, ..RedisConfig::default() };
+        let publisher = redis.publisher(config).await.unwrap();
+
+        for (bid_qty, update_id) in [(1.0, 1), (2.0, 2), (3.0, 3)] {
+            publisher.publish_event(&depth(bid_qty, update_id)).await.unwrap();
+        }
+        publisher.publish_event(&trade("BTCUSDT")).await.unwrap();
+        // Only the trade goes out; the depth updates wait for their window
+        assert_eq!(redis.commands(), 1);
+
+        assert_eq!(publisher.flush_coalesced().await, 1);
+        assert_eq!(redis.commands(), 2);
+        assert_eq!(publisher.flush_coalesced().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_raw_trades_are_aggregated() {
+        let redis = MockRedisConnection::default();
+        let config = RedisConfig { trade_aggregation_ms: 60_000, ..RedisConfig::default() };
+        let publisher = redis.publisher(config).await.unwrap();
+
+        for _ in 0..3 {
+            let mut event = trade("BTCUSDT");
+            if let MarketEvent::AggTrade(ref mut trade) = event {
+                trade.exchange = ExchangeType::Okx;
+            }
+            publisher.publish_event(&event).await.unwrap();
+        }
+        // Binance trades are aggregated already and go straight out
+        publisher.publish_event(&trade("BTCUSDT")).await.unwrap();
+        assert_eq!(redis.commands(), 1);
+
+        assert_eq!(publisher.flush_aggregated().await, 1);
+        assert_eq!(redis.commands(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_publishes_every_held_event() {
+        let redis = MockRedisConnection::default();
+        let config = RedisConfig {
+            batch_size: 100,
+            queue_size: 10,
+            depth_coalesce_ms: 60_000,
+            trade_aggregation_ms: 60_000,
+            ..RedisConfig::default()
+        };
+        let publisher = redis.publisher(config).await.unwrap();
+
+        // Nothing runs the publish, coalesce or aggregate tasks, so every event is held somewhere
+        publisher.publish_now(&trade("SOLUSDT")).await.unwrap();
+        publisher.publish_event(&trade("ETHUSDT")).await.unwrap();
+        publisher.publish_event(&depth(1.0, 1)).await.unwrap();
+        let mut okx_trade = trade("XRPUSDT");
+        if let MarketEvent::AggTrade(ref mut trade) = okx_trade {
+            trade.exchange = ExchangeType::Okx;
+        }
+        publisher.publish_event(&okx_trade).await.unwrap();
+        assert_eq!(redis.commands(), 0);
+
+        assert_eq!(publisher.shutdown().await.unwrap(), 4);
+        assert_eq!(redis.commands(), 4);
+        assert_eq!(publisher.queue_depth().0, 0);
+
+        // Buffered first, then queued, then coalesced and aggregated
+        let sent = redis.pipelines().concat();
+        assert!(find(&sent, b"SOLUSDT") < find(&sent, b"ETHUSDT"));
+        assert!(find(&sent, b"ETHUSDT") < find(&sent, b"DepthUpdate"));
+        assert!(find(&sent, b"DepthUpdate") < find(&sent, b"XRPUSDT"));
+        assert_eq!(publisher.shutdown().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_nbbo_follows_book_tickers_that_move_it() {
+        let redis = MockRedisConnection::default();
+        let config = RedisConfig { publish_nbbo: true, ..RedisConfig::default() };
+        let publisher = redis.publisher(config).await.unwrap();
+        let ticker = |exchange, bid_price, ask_price| {
+            MarketEvent::BookTicker(crate::exchange::BookTicker {
+                exchange,
+                symbol: "BTCUSDT".to_string(),
+                bid_price,
+                bid_qty: 1.0,
+                ask_price,
+                ask_qty: 1.0,
+                timestamp: 0,
+                received_at: 0,
+            })
+        };
+
+        publisher.publish_event(&ticker(ExchangeType::Binance, 50000.0, 50001.0)).await.unwrap();
+        publisher.publish_event(&ticker(ExchangeType::Okx, 50002.0, 50003.0)).await.unwrap();
+        // OKX's ask isn't the best, so moving it leaves the NBBO alone
+        publisher.publish_event(&ticker(ExchangeType::Okx, 50002.0, 50004.0)).await.unwrap();
+        assert_eq!(redis.commands(), 5);
+
+        let pipelines = redis.pipelines();
+        let nbbo = &pipelines[3];
+        assert!(find_opt(nbbo, CHANNEL_NBBO.as_bytes()).is_some());
+        assert!(find_opt(nbbo, br#""best_bid_exchange":"Okx""#).is_some());
+        assert!(find_opt(nbbo, br#""spread":-1.0"#).is_some());
+        assert!(pipelines[4..].iter().all(|p| find_opt(p, CHANNEL_NBBO.as_bytes()).is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_wide_spread_is_published_as_an_opportunity() {
+        let redis = MockRedisConnection::default();
+        let config = RedisConfig { opportunity_threshold_bps: 10.0, ..RedisConfig::default() };
+        let publisher = redis.publisher(config).await.unwrap();
+        let ticker = |exchange, bid_price, ask_price| {
+            MarketEvent::BookTicker(crate::exchange::BookTicker {
+                exchange,
+                symbol: "BTCUSDT".to_string(),
+                bid_price,
+                bid_qty: 1.0,
+                ask_price,
+                ask_qty: 1.0,
+                timestamp: 0,
+                received_at: 0,
+            })
+        };
+
+        publisher.publish_event(&ticker(ExchangeType::Okx, 99.0, 99.5)).await.unwrap();
+        publisher.publish_event(&ticker(ExchangeType::Binance, 100.0, 100.5)).await.unwrap();
+        // The NBBO itself isn't published without publish_nbbo
+        assert_eq!(redis.commands(), 3);
+
+        let pipelines = redis.pipelines();
+        let opportunity = &pipelines[2];
+        assert!(find_opt(opportunity, CHANNEL_OPPORTUNITY.as_bytes()).is_some());
+        assert!(find_opt(opportunity, br#""buy_exchange":"Okx""#).is_some());
+        assert!(find_opt(opportunity, br#""sell_exchange":"Binance""#).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_opportunity_and_nbbo_are_backlogged_while_redis_is_down() {
+        let conn = FlakyConnection { down: true, ..Default::default() };
+        let pool = ConnectionPool::connect(1, || std::future::ready(Ok(conn.clone()))).await.unwrap();
+        let config = RedisConfig { publish_nbbo: true, opportunity_threshold_bps: 10.0, ..RedisConfig::default() };
+        let publisher = RedisPublisher::with_pool(config, pool).unwrap();
+        let ticker = |exchange, bid_price, ask_price| {
+            MarketEvent::BookTicker(crate::exchange::BookTicker {
+                exchange,
+                symbol: "BTCUSDT".to_string(),
+                bid_price,
+                bid_qty: 1.0,
+                ask_price,
+                ask_qty: 1.0,
+                timestamp: 0,
+                received_at: 0,
+            })
+        };
+
+        assert!(publisher.publish_event(&ticker(ExchangeType::Okx, 99.0, 99.5)).await.is_err());
+        // The failed book ticker doesn't stop the opportunity or the NBBO behind it
+        assert!(publisher.publish_event(&ticker(ExchangeType::Binance, 100.0, 100.5)).await.is_err());
+
+        let channels: Vec<String> = publisher.backlog.lock().unwrap().drain().into_iter().map(|m| m.channel).collect();
+        assert_eq!(channels, [CHANNEL_TICKER, CHANNEL_NBBO, CHANNEL_TICKER, CHANNEL_OPPORTUNITY, CHANNEL_NBBO]);
+    }
+
+    #[tokio::test]
+    async fn test_reordered_events_publish_by_timestamp() {
+        let redis = MockRedisConnection::default();
+        let config = RedisConfig { reorder_grace_ms: 60_000, ..RedisConfig::default() };
+        let publisher = redis.publisher(config).await.unwrap();
+
+        let at = |symbol: &str, timestamp: i64| {
+            let mut event = trade(symbol);
+            if let MarketEvent::AggTrade(ref mut trade) = event {
+                trade.timestamp = timestamp;
+            }
+            event
+        };
+        publisher.publish_event(&at("ETHUSDT", 2_000)).await.unwrap();
+        publisher.publish_event(&at("BTCUSDT", 1_000)).await.unwrap();
+        assert_eq!(redis.commands(), 0);
+
+        assert_eq!(publisher.flush_reordered().await, 2);
+        let packed = redis.pipelines().concat();
+        let find = |needle: &[u8]| packed.windows(needle.len()).position(|w| w == needle).unwrap();
+        assert!(find(b"BTCUSDT") < find(b"ETHUSDT"));
+
+        // Older than what already went out, so it is published straight away
+        publisher.publish_event(&at("SOLUSDT", 1_500)).await.unwrap();
+        assert_eq!(redis.commands(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_raw_frames_are_published_unchanged() {
+        let frame = r#"{"e":"someNewEvent","E":1700000000000,"s":"BTCUSDT","x":[1, 2.50]}"#;
+
+        let redis = MockRedisConnection::default();
+        let publisher = redis.publisher(RedisConfig::default()).await.unwrap();
+        publisher.publish_raw(ExchangeType::Binance, frame);
+        assert_eq!(publisher.flush_raw().await, 0);
+        assert_eq!(redis.commands(), 0);
+
+        let redis = MockRedisConnection::default();
+        let config = RedisConfig { publish_raw: true, channel_prefix: "inst2".to_string(), ..RedisConfig::default() };
+        let publisher = redis.publisher(config).await.unwrap();
+        // Only queued for the raw task, so the reader never waits on Redis
+        publisher.publish_raw(ExchangeType::Binance, frame);
+        assert_eq!(redis.commands(), 0);
+
+        let task = publisher.spawn_raw_task().unwrap();
+        time::timeout(Duration::from_secs(5), async {
+            while redis.commands() == 0 {
+                time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("raw frame was not published");
+        task.abort();
+
+        let expected = redis::cmd("PUBLISH").arg("inst2:raw:binance").arg(frame.as_bytes()).get_packed_command();
+        assert_eq!(redis.pipelines(), vec![expected]);
+    }
+
+    #[tokio::test]
+    async fn test_raw_frames_past_the_queue_size_are_dropped() {
+        let redis = MockRedisConnection::default();
+        let publisher = redis.publisher(RedisConfig { publish_raw: true, ..RedisConfig::default() }).await.unwrap();
+
+        for i in 0..RAW_QUEUE_SIZE + 5 {
+            publisher.publish_raw(ExchangeType::Okx, &format!("frame-{}", i));
+        }
+        assert_eq!(publisher.flush_raw().await, RAW_QUEUE_SIZE);
+        assert!(find_opt(&redis.pipelines()[0], b"frame-0").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_snapshot_is_published_as_json() {
+        let redis = MockRedisConnection::default();
+        let publisher = redis.publisher(RedisConfig::default()).await.unwrap();
+        let snapshot = GatewayMetrics { events_received: 3, ..GatewayMetrics::default() };
+
+        publisher.publish_metrics(&snapshot).await.unwrap();
+
+        let payload = serde_json::to_vec(&snapshot).unwrap();
+        let expected = redis::cmd("PUBLISH").arg(CHANNEL_METRICS).arg(payload).get_packed_command();
+        assert_eq!(redis.pipelines(), vec![expected]);
+    }
+
+    #[tokio::test]
+    async fn test_user_data_is_published_to_its_own_channel() {
+        let redis = MockRedisConnection::default();
+        let publisher = redis.publisher(RedisConfig::default()).await.unwrap();
+        let event = UserDataEvent::AccountUpdate(crate::user_data::AccountUpdate {
+            exchange: ExchangeType::Binance,
+            reason: "FUNDING_FEE".to_string(),
+            balances: Vec::new(),
+            positions: Vec::new(),
+            timestamp: 1700000000000,
+            received_at: 0,
+        });
+
+        publisher.publish_user_data(&event).await.unwrap();
+
+        let payload = serde_json::to_vec(&event).unwrap();
+        let expected = redis::cmd("PUBLISH").arg(CHANNEL_USER_DATA).arg(payload).get_packed_command();
+        assert_eq!(redis.pipelines(), vec![expected]);
+    }
+
+    fn depth(bid_qty: f64, update_id: u64) -> MarketEvent {
+        MarketEvent::DepthUpdate(crate::exchange::DepthUpdate {
+            exchange: crate::exchange::ExchangeType::Binance,
+            symbol: "BTCUSDT".to_string(),
+            bids: vec![(50000.0, bid_qty)],
+            asks: vec![],
+            timestamp: 0,
+            is_snapshot: false,
+            first_update_id: Some(update_id),
+            final_update_id: Some(update_id),
+            prev_final_update_id: None,
+            received_at: 0,
+        })
+    }
+
+    fn trade(symbol: &str) -> MarketEvent {
+        MarketEvent::AggTrade(crate::exchange::AggTrade {
+            symbol: symbol.to_string(),
+            ..testing::trade(crate::exchange::ExchangeType::Binance, 1)
+        })
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> usize {
+        find_opt(haystack, needle).unwrap()
+    }
+
+    fn find_opt(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|w| w == needle)
+    }
 
     #[tokio::test]
     #[ignore]  // Requires Redis to be running
@@ -106,8 +2074,43 @@ mod tests {
         let publisher = RedisPublisher::new(config).await;
         assert!(publisher.is_ok());
 
-        let mut publisher = publisher.unwrap();
+        let publisher = publisher.unwrap();
         let pong = publisher.ping().await.unwrap();
         assert_eq!(pong, "PONG");
     }
+
+    #[tokio::test]
+    #[ignore]  // Requires Redis to be running
+    async fn test_stream_xadd_round_trip() {
+        let config = RedisConfig {
+            output_mode: OutputMode::Stream,
+            ..RedisConfig::default()
+        };
+        let publisher = RedisPublisher::new(config).await.unwrap();
+
+        let event = MarketEvent::AggTrade(crate::exchange::AggTrade {
+            exchange: crate::exchange::ExchangeType::Binance,
+            symbol: "XADDTEST".to_string(),
+            price: 50000.0,
+            quantity: 0.1,
+            timestamp: 0,
+            is_buyer_maker: false,
+            trade_id: 1,
+            received_at: 0,
+        });
+        publisher.publish_event(&event).await.unwrap();
+
+        let key = format!("{}tick", STREAM_PREFIX);
+        let entries: Vec<(String, std::collections::HashMap<String, String>)> = redis::cmd("XRANGE")
+            .arg(&key).arg("-").arg("+")
+            .query_async(&mut publisher.pool.get(&key))
+            .await
+            .unwrap();
+
+        let (_, fields) = entries.iter().rev()
+            .find(|(_, fields)| fields.get("symbol").map(String::as_str) == Some("XADDTEST"))
+            .expect("entry not found");
+        assert_eq!(fields["exchange"], "binance");
+        assert!(fields["payload"].contains("\"XADDTEST\""));
+    }
 }
@@ -0,0 +1,173 @@
+//! Event reordering
+//!
+//! This module holds events from every exchange for a short grace period and
+//! releases them sorted by exchange timestamp, so a merged output stream is
+//! monotonic. Each event waits at most about the grace period; one that
+//! arrives older than what was already released can't be put in order and
+//! is passed straight through as late.
+
+use crate::exchange::MarketEvent;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// An event waiting for its grace period to pass
+#[derive(Debug)]
+struct Held {
+    event: MarketEvent,
+    since: Instant,
+}
+
+/// Buffer releasing events in timestamp order after a grace period
+#[derive(Debug)]
+pub struct ReorderBuffer {
+    grace: Duration,
+    /// Held events keyed by timestamp, then arrival order to keep ties stable
+    held: BTreeMap<(i64, u64), Held>,
+    arrivals: u64,
+    /// Timestamp of the last released event; anything older arrives late
+    horizon: Option<i64>,
+}
+
+impl ReorderBuffer {
+    /// Create a buffer holding each event for `grace` before it may be released
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            held: BTreeMap::new(),
+            arrivals: 0,
+            horizon: None,
+        }
+    }
+
+    /// Time each event is held for
+    pub fn grace(&self) -> Duration {
+        self.grace
+    }
+
+    /// Number of events held
+    pub fn len(&self) -> usize {
+        self.held.len()
+    }
+
+    /// Check if no events are held
+    pub fn is_empty(&self) -> bool {
+        self.held.is_empty()
+    }
+
+    /// Hold an event, or hand it back if it is older than the last released one.
+    ///
+    /// A returned event is late: publishing it now is the best that can be
+    /// done, but it breaks the ordering of the stream.
+    pub fn push(&mut self, event: MarketEvent, now: Instant) -> Option<MarketEvent> {
+        if self.horizon.is_some_and(|horizon| event.timestamp() < horizon) {
+            return Some(event);
+        }
+
+        self.arrivals += 1;
+        self.held.insert((event.timestamp(), self.arrivals), Held { event, since: now });
+        None
+    }
+
+    /// Take the events due by `now`, in timestamp order.
+    ///
+    /// An event is due once it has been held for the grace period; older
+    /// events still inside theirs are released with it so the order holds.
+    pub fn take_due(&mut self, now: Instant) -> Vec<MarketEvent> {
+        let newest_due = self.held
+            .iter()
+            .filter(|(_, held)| now.duration_since(held.since) >= self.grace)
+            .map(|(key, _)| *key)
+            .max();
+        let Some(newest_due) = newest_due else {
+            return Vec::new();
+        };
+
+        let rest = self.held.split_off(&(newest_due.0, newest_due.1 + 1));
+        let due = std::mem::replace(&mut self.held, rest);
+        self.release(due)
+    }
+
+    /// Take every held event in timestamp order regardless of its grace period, e.g. on shutdown
+    pub fn take_all(&mut self) -> Vec<MarketEvent> {
+        let held = std::mem::take(&mut self.held);
+        self.release(held)
+    }
+
+    /// Move the horizon past released events and unwrap them
+    fn release(&mut self, events: BTreeMap<(i64, u64), Held>) -> Vec<MarketEvent> {
+        if let Some(&(timestamp, _)) = events.keys().next_back() {
+            self.horizon = Some(self.horizon.map_or(timestamp, |horizon| horizon.max(timestamp)));
+        }
+        events.into_values().map(|held| held.event).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{AggTrade, ExchangeType};
+    use crate::testing;
+
+    fn trade(exchange: ExchangeType, timestamp: i64) -> MarketEvent {
+        MarketEvent::AggTrade(AggTrade {
+            timestamp,
+            received_at: timestamp + 5,
+            ..testing::trade(exchange, timestamp as u64)
+        })
+    }
+
+    #[test]
+    fn test_out_of_order_events_emit_in_timestamp_order() {
+        let grace = Duration::from_millis(50);
+        let mut buffer = ReorderBuffer::new(grace);
+        let start = Instant::now();
+
+        for (exchange, timestamp) in [
+            (ExchangeType::Binance, 1_030),
+            (ExchangeType::Okx, 1_010),
+            (ExchangeType::Bybit, 1_020),
+            (ExchangeType::Okx, 1_000),
+        ] {
+            assert!(buffer.push(trade(exchange, timestamp), start).is_none());
+        }
+        assert!(buffer.take_due(start + Duration::from_millis(20)).is_empty());
+
+        // Arrived later, so it is still inside its own grace period
+        assert!(buffer.push(trade(ExchangeType::Binance, 1_040), start + Duration::from_millis(30)).is_none());
+
+        let emitted: Vec<i64> = buffer.take_due(start + grace).iter().map(MarketEvent::timestamp).collect();
+        assert_eq!(emitted, vec![1_000, 1_010, 1_020, 1_030]);
+        assert_eq!(buffer.len(), 1);
+
+        let emitted: Vec<i64> = buffer.take_all().iter().map(MarketEvent::timestamp).collect();
+        assert_eq!(emitted, vec![1_040]);
+    }
+
+    #[test]
+    fn test_events_behind_the_horizon_are_late() {
+        let mut buffer = ReorderBuffer::new(Duration::from_millis(50));
+        let start = Instant::now();
+
+        buffer.push(trade(ExchangeType::Binance, 1_000), start);
+        assert_eq!(buffer.take_all().len(), 1);
+
+        let late = buffer.push(trade(ExchangeType::Okx, 990), start).unwrap();
+        assert_eq!(late.timestamp(), 990);
+        assert!(buffer.is_empty());
+        // Equal timestamps still fit after the released event
+        assert!(buffer.push(trade(ExchangeType::Okx, 1_000), start).is_none());
+    }
+
+    #[test]
+    fn test_newer_event_due_releases_older_ones_still_held() {
+        let grace = Duration::from_millis(50);
+        let mut buffer = ReorderBuffer::new(grace);
+        let start = Instant::now();
+
+        buffer.push(trade(ExchangeType::Binance, 2_000), start);
+        buffer.push(trade(ExchangeType::Okx, 1_000), start + Duration::from_millis(40));
+
+        let emitted: Vec<i64> = buffer.take_due(start + grace).iter().map(MarketEvent::timestamp).collect();
+        assert_eq!(emitted, vec![1_000, 2_000]);
+    }
+}
@@ -0,0 +1,210 @@
+//! Recorded session replay
+//!
+//! This module reads NDJSON files written by the recorder back in order and
+//! republishes their events, optionally paced like the original session.
+
+use crate::exchange::MarketEvent;
+use crate::recorder::RECORDING_EXTENSION;
+use crate::sink::EventSink;
+use crate::error::{GatewayError, Result};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Lines};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time;
+use tracing::info;
+
+/// Yields recorded events from a directory of NDJSON files.
+///
+/// Files are read in name order, which the recorder's timestamped names make
+/// chronological. A speed of 0 replays as fast as possible; otherwise gaps
+/// between event timestamps are divided by the speed (1.0 is real time).
+pub struct ReplaySource {
+    files: VecDeque<PathBuf>,
+    current: Option<(PathBuf, Lines<BufReader<File>>, usize)>,
+    speed: f64,
+    /// Latest event time seen, so out-of-order events never wait
+    clock_ms: Option<i64>,
+}
+
+impl ReplaySource {
+    /// Replay every recording in `dir`
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .map_err(|e| GatewayError::Recording(format!("Failed to read replay directory {}: {}", dir.display(), e)))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == RECORDING_EXTENSION))
+            .collect();
+        files.sort();
+
+        if files.is_empty() {
+            return Err(GatewayError::Recording(format!(
+                "No .{} recordings found in {}", RECORDING_EXTENSION, dir.display()
+            )));
+        }
+        info!("Replaying {} recording files from {}", files.len(), dir.display());
+
+        Ok(Self {
+            files: files.into(),
+            current: None,
+            speed: 0.0,
+            clock_ms: None,
+        })
+    }
+
+    /// Pace events at this multiple of real time (0 for no pacing)
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed.max(0.0);
+        self
+    }
+
+    /// Next recorded event, after waiting out its gap from the previous one
+    pub async fn next_event(&mut self) -> Result<Option<MarketEvent>> {
+        let Some(event) = self.read_next()? else {
+            return Ok(None);
+        };
+
+        let timestamp = event.timestamp();
+        if let Some(clock_ms) = self.clock_ms {
+            if self.speed > 0.0 && timestamp > clock_ms {
+                let gap_ms = (timestamp - clock_ms) as f64 / self.speed;
+                time::sleep(Duration::from_secs_f64(gap_ms / 1000.0)).await;
+            }
+        }
+        self.clock_ms = Some(self.clock_ms.map_or(timestamp, |clock_ms| clock_ms.max(timestamp)));
+
+        Ok(Some(event))
+    }
+
+    /// Publish every remaining event to `sink`, returning how many were published
+    pub async fn replay_into(&mut self, sink: &mut impl EventSink) -> Result<u64> {
+        let mut published = 0;
+        while let Some(event) = self.next_event().await? {
+            sink.publish_event(&event).await?;
+            published += 1;
+        }
+        Ok(published)
+    }
+
+    /// Parse the next non-empty line, moving on to the next file as each runs out
+    fn read_next(&mut self) -> Result<Option<MarketEvent>> {
+        loop {
+            if self.current.is_none() {
+                let Some(path) = self.files.pop_front() else {
+                    return Ok(None);
+                };
+                let file = File::open(&path)
+                    .map_err(|e| GatewayError::Recording(format!("Failed to open recording {}: {}", path.display(), e)))?;
+                self.current = Some((path, BufReader::new(file).lines(), 0));
+            }
+
+            let (path, lines, line_no) = self.current.as_mut().unwrap();
+            match lines.next() {
+                Some(line) => {
+                    *line_no += 1;
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let event = serde_json::from_str(&line)
+                        .map_err(|e| GatewayError::Parse(format!("Invalid event at {}:{}: {}", path.display(), line_no, e)))?;
+                    return Ok(Some(event));
+                }
+                None => self.current = None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{AggTrade, ExchangeType};
+    use async_trait::async_trait;
+    use crate::recorder::{FileRecorder, RotationPolicy};
+    use crate::testing;
+
+    /// Sink that keeps what it was given
+    #[derive(Default)]
+    struct CollectingSink {
+        events: Vec<MarketEvent>,
+    }
+
+    #[async_trait]
+    impl EventSink for CollectingSink {
+        async fn publish_event(&mut self, event: &MarketEvent) -> Result<()> {
+            self.events.push(event.clone());
+            Ok(())
+        }
+    }
+
+    fn trade(trade_id: u64, timestamp: i64) -> MarketEvent {
+        MarketEvent::AggTrade(AggTrade {
+            symbol: "ETHUSDT".to_string(),
+            price: 3000.0,
+            quantity: 1.0,
+            timestamp,
+            received_at: timestamp,
+            ..testing::trade(ExchangeType::Okx, trade_id)
+        })
+    }
+
+    fn record(events: &[MarketEvent], rotation: RotationPolicy) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("flash-arb-replay-{}", uuid::Uuid::new_v4()));
+        let mut recorder = FileRecorder::new(&dir, rotation).unwrap();
+        for event in events {
+            recorder.record(event).unwrap();
+        }
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_replay_at_max_speed_publishes_everything() {
+        // An hour of trades replays instantly at speed 0
+        let events: Vec<_> = (0..5).map(|i| trade(i, i as i64 * 900_000)).collect();
+        let dir = record(&events, RotationPolicy::Size(200));
+
+        let mut sink = CollectingSink::default();
+        let mut source = ReplaySource::from_dir(&dir).unwrap();
+        let published = time::timeout(Duration::from_secs(5), source.replay_into(&mut sink))
+            .await
+            .expect("max-speed replay should not wait")
+            .unwrap();
+
+        assert_eq!(published, 5);
+        assert_eq!(sink.events, events);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_speed_scales_gaps() {
+        let events = vec![trade(1, 0), trade(2, 200), trade(3, 100), trade(4, 400)];
+        let dir = record(&events, RotationPolicy::Hourly);
+
+        let start = time::Instant::now();
+        let mut sink = CollectingSink::default();
+        let mut source = ReplaySource::from_dir(&dir).unwrap().with_speed(2.0);
+        source.replay_into(&mut sink).await.unwrap();
+
+        // 400ms of event time at 2x; the late event doesn't rewind the clock
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(200), "replayed too fast: {:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "replayed too slow: {:?}", elapsed);
+        assert_eq!(sink.events.len(), 4);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_empty_directory_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("flash-arb-replay-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        assert!(ReplaySource::from_dir(&dir).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
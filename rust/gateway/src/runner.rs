@@ -0,0 +1,484 @@
+//! Per-exchange event tasks
+//!
+//! This module runs each exchange client on its own task, reconnecting
+//! with backoff when the connection drops, and forwards parsed events
+//! over a shared channel so no exchange can block another. Each connection
+//! logs inside its own span, so interleaved lines can be told apart.
+
+use crate::exchange::{Exchange, ExchangeType, MarketEvent};
+use crate::health::HealthState;
+use crate::metrics;
+use crate::reconnect::ReconnectPolicy;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, Notify};
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+use uuid::Uuid;
+
+/// Capacity of the channel shared by all exchange tasks
+pub const EVENT_CHANNEL_CAPACITY: usize = 10_000;
+
+/// Span for one connection to an exchange, carrying a fresh `session_id` to correlate its log lines
+pub fn session_span(exchange: ExchangeType) -> Span {
+    info_span!("exchange", exchange = %exchange, session_id = %Uuid::new_v4())
+}
+
+/// Move an exchange, already connected within `session`, onto its own task that forwards events to `tx`.
+///
+/// The task reconnects with `policy`'s backoff when the exchange disconnects and
+/// resubscribes to whatever the exchange still has active. A reconnect only
+/// counts once an event arrives within the policy's verify timeout; otherwise
+/// it is dropped and retried. The task stops, disconnecting the exchange, once
+/// `shutdown` is set or the receiving side is dropped. Connection changes and
+/// event arrivals are reported to `health`. Notifying `reconnect` drops the
+/// current connection so the task opens a fresh one, e.g. when it has gone quiet.
+///
+/// The task keeps logging in `session` until the connection drops; every
+/// reconnect starts a new session.
+pub fn spawn_exchange_session(
+    exchange: Box<dyn Exchange>,
+    session: Span,
+    tx: mpsc::Sender<MarketEvent>,
+    policy: ReconnectPolicy,
+    shutdown: watch::Receiver<bool>,
+    reconnect: Arc<Notify>,
+    health: HealthState,
+) -> JoinHandle<()> {
+    tokio::spawn(run_exchange(exchange, session, tx, policy, shutdown, reconnect, health))
+}
+
+/// Receive events from one exchange until shutdown or the receiver goes away
+async fn run_exchange(
+    mut exchange: Box<dyn Exchange>,
+    mut session: Span,
+    tx: mpsc::Sender<MarketEvent>,
+    mut policy: ReconnectPolicy,
+    mut shutdown: watch::Receiver<bool>,
+    reconnect: Arc<Notify>,
+    health: HealthState,
+) {
+    let exchange_type = exchange.exchange_type();
+    metrics::global().set_connected(exchange_type, exchange.is_connected());
+    health.set_connected(exchange_type, exchange.is_connected());
+
+    while !*shutdown.borrow() {
+        // A reconnect is a new connection, so its lines get a new session id
+        if !exchange.is_connected() {
+            session = session_span(exchange_type);
+        }
+        tokio::select! {
+            // A dropped sender also means shut down
+            _ = shutdown.changed() => {}
+            _ = reconnect.notified() => {
+                if exchange.is_connected() {
+                    async {
+                        warn!("Dropping the quiet {} connection to reconnect", exchange_type);
+                        if let Err(e) = exchange.disconnect().await {
+                            warn!("Failed to disconnect from {}: {}", exchange_type, e);
+                        }
+                    }
+                    .instrument(session.clone())
+                    .await;
+                    metrics::global().set_connected(exchange_type, false);
+                    health.set_connected(exchange_type, false);
+                }
+            }
+            running = step(exchange.as_mut(), &tx, &mut policy, &health).instrument(session.clone()) => {
+                if !running {
+                    session.in_scope(|| debug!("Event receiver dropped, stopping {} task", exchange_type));
+                    break;
+                }
+            }
+        }
+        if shutdown.has_changed().is_err() {
+            break;
+        }
+    }
+
+    if exchange.is_connected() {
+        async {
+            match exchange.disconnect().await {
+                Ok(()) => info!("Closed {} connection", exchange_type),
+                Err(e) => warn!("Failed to disconnect from {}: {}", exchange_type, e),
+            }
+        }
+        .instrument(session)
+        .await;
+    }
+    metrics::global().set_connected(exchange_type, false);
+    health.set_connected(exchange_type, false);
+}
+
+/// Reconnect if needed and forward the next event, returning false once the receiver is gone
+async fn step(
+    exchange: &mut dyn Exchange,
+    tx: &mpsc::Sender<MarketEvent>,
+    policy: &mut ReconnectPolicy,
+    health: &HealthState,
+) -> bool {
+    let exchange_type = exchange.exchange_type();
+
+    if !exchange.is_connected() {
+        let delay = policy.next_delay();
+        info!("{} disconnected, reconnecting in {:?} (attempt {})", exchange_type, delay, policy.attempt());
+        time::sleep(delay).await;
+
+        if let Err(e) = exchange.connect().await {
+            error!("Failed to reconnect to {}: {}", exchange_type, e);
+            return true;
+        }
+        // Only what is still active, so earlier unsubscribes stay in effect
+        if let Err(e) = exchange.resubscribe().await {
+            error!("Failed to resubscribe to {}: {}", exchange_type, e);
+            return true;
+        }
+        metrics::global().set_connected(exchange_type, true);
+        health.set_connected(exchange_type, true);
+        health.set_subscriptions(exchange_type, exchange.active_subscriptions());
+
+        // An open socket isn't proof the subscriptions took, so wait for data
+        let first = match policy.verify_timeout() {
+            Some(timeout) => {
+                let first = time::timeout(timeout, next_event(exchange)).await;
+                let Ok(Some(event)) = first else {
+                    if first.is_err() {
+                        warn!("No {} data within {:?} of reconnecting, retrying", exchange_type, timeout);
+                    }
+                    if exchange.is_connected() {
+                        if let Err(e) = exchange.disconnect().await {
+                            warn!("Failed to drop unverified {} connection: {}", exchange_type, e);
+                        }
+                    }
+                    metrics::global().set_connected(exchange_type, false);
+                    health.set_connected(exchange_type, false);
+                    return true;
+                };
+                Some(event)
+            }
+            None => None,
+        };
+
+        info!("Successfully reconnected to {}", exchange_type);
+        policy.reset();
+        metrics::global().record_reconnect(exchange_type);
+        if let Some(event) = first {
+            return forward(event, exchange_type, tx, health).await;
+        }
+    }
+
+    let running = match exchange.recv_event().await {
+        Ok(Some(event)) => forward(event, exchange_type, tx, health).await,
+        Ok(None) => true,
+        Err(e) => {
+            error!("Error receiving {} event: {}", exchange_type, e);
+            true
+        }
+    };
+    if !exchange.is_connected() {
+        metrics::global().set_connected(exchange_type, false);
+        health.set_connected(exchange_type, false);
+    }
+    running
+}
+
+/// Receive until the first event, or `None` if the connection closes or fails first
+async fn next_event(exchange: &mut dyn Exchange) -> Option<MarketEvent> {
+    while exchange.is_connected() {
+        match exchange.recv_event().await {
+            Ok(Some(event)) => return Some(event),
+            Ok(None) => continue,
+            Err(e) => {
+                error!("Error receiving {} event: {}", exchange.exchange_type(), e);
+                return None;
+            }
+        }
+    }
+    None
+}
+
+/// Count an event and send it on, returning false once the receiver is gone
+async fn forward(
+    event: MarketEvent,
+    exchange_type: ExchangeType,
+    tx: &mpsc::Sender<MarketEvent>,
+    health: &HealthState,
+) -> bool {
+    metrics::global().record_event(exchange_type);
+    metrics::global().record_event_type(event.event_type());
+    health.record_event(exchange_type);
+    tx.send(event).await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::Subscription;
+    use crate::logging::{self, LogFormat};
+    use crate::testing::{agg_trade, LogCapture, MockExchange};
+    use crate::error::Result;
+    use serde_json::Value;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Exchange that emits each scripted event after its delay, then goes quiet
+    struct ScriptedExchange {
+        exchange_type: ExchangeType,
+        script: VecDeque<(Duration, MarketEvent)>,
+        connected: bool,
+        /// Set once `disconnect` is called
+        disconnected: Arc<AtomicBool>,
+        active: Vec<Subscription>,
+        /// Subscriptions sent by each `resubscribe`
+        resubscribed: Vec<Vec<Subscription>>,
+    }
+
+    impl ScriptedExchange {
+        fn new(exchange_type: ExchangeType, first_delay: Duration, gap: Duration, count: u64) -> Self {
+            let script = (0..count)
+                .map(|i| {
+                    let delay = if i == 0 { first_delay } else { gap };
+                    (delay, agg_trade(exchange_type, i))
+                })
+                .collect();
+
+            Self {
+                exchange_type,
+                script,
+                connected: true,
+                disconnected: Arc::default(),
+                active: Vec::new(),
+                resubscribed: Vec::new(),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Exchange for ScriptedExchange {
+        fn exchange_type(&self) -> ExchangeType {
+            self.exchange_type
+        }
+
+        async fn connect(&mut self) -> Result<()> {
+            self.connected = true;
+            Ok(())
+        }
+
+        async fn disconnect(&mut self) -> Result<()> {
+            self.connected = false;
+            self.disconnected.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn subscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+            self.active.extend(subscriptions);
+            Ok(())
+        }
+
+        async fn unsubscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+            self.active.retain(|sub| !subscriptions.contains(sub));
+            Ok(())
+        }
+
+        fn active_subscriptions(&self) -> Vec<Subscription> {
+            self.active.clone()
+        }
+
+        async fn resubscribe(&mut self) -> Result<()> {
+            self.resubscribed.push(self.active.clone());
+            Ok(())
+        }
+
+        async fn recv_event(&mut self) -> Result<Option<MarketEvent>> {
+            match self.script.pop_front() {
+                Some((delay, event)) => {
+                    time::sleep(delay).await;
+                    Ok(Some(event))
+                }
+                // A quiet exchange never returns, like an idle socket
+                None => std::future::pending().await,
+            }
+        }
+
+        fn is_connected(&self) -> bool {
+            self.connected
+        }
+
+        fn ws_endpoint(&self) -> &str {
+            "mock://"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_from_two_exchanges_interleave() {
+        let gap = Duration::from_millis(60);
+        let binance = ScriptedExchange::new(ExchangeType::Binance, Duration::ZERO, gap, 3);
+        let okx = ScriptedExchange::new(ExchangeType::Okx, gap / 2, gap, 3);
+
+        let (tx, mut rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handles = vec![
+            spawn_exchange_session(Box::new(binance), session_span(ExchangeType::Binance), tx.clone(), ReconnectPolicy::default(), shutdown_rx.clone(), Arc::default(), HealthState::new()),
+            spawn_exchange_session(Box::new(okx), session_span(ExchangeType::Okx), tx, ReconnectPolicy::default(), shutdown_rx, Arc::default(), HealthState::new()),
+        ];
+
+        let mut received = Vec::new();
+        while received.len() < 6 {
+            let event = time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("timed out waiting for events")
+                .expect("channel closed");
+            received.push(event.exchange());
+        }
+
+        // Each exchange goes quiet after its script without holding up the other
+        assert_eq!(
+            received,
+            vec![
+                ExchangeType::Binance, ExchangeType::Okx,
+                ExchangeType::Binance, ExchangeType::Okx,
+                ExchangeType::Binance, ExchangeType::Okx,
+            ]
+        );
+
+        for handle in handles {
+            handle.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_disconnects_every_exchange() {
+        let exchanges = [ExchangeType::Binance, ExchangeType::Okx, ExchangeType::Coinbase]
+            .map(|exchange_type| ScriptedExchange::new(exchange_type, Duration::ZERO, Duration::ZERO, 1));
+        let disconnected: Vec<_> = exchanges.iter().map(|e| e.disconnected.clone()).collect();
+
+        let (tx, mut rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let health = HealthState::new();
+        let handles: Vec<_> = exchanges.into_iter()
+            .map(|exchange| {
+                let session = session_span(exchange.exchange_type);
+                spawn_exchange_session(Box::new(exchange), session, tx.clone(), ReconnectPolicy::default(), shutdown_rx.clone(), Arc::default(), health.clone())
+            })
+            .collect();
+
+        // Let every exchange go idle on its socket before shutting down
+        for _ in 0..3 {
+            rx.recv().await.unwrap();
+        }
+        assert!(disconnected.iter().all(|flag| !flag.load(Ordering::SeqCst)));
+        assert!(health.status(ExchangeType::Okx).unwrap().connected);
+
+        shutdown_tx.send(true).unwrap();
+        for handle in handles {
+            time::timeout(Duration::from_secs(5), handle)
+                .await
+                .expect("task did not stop on shutdown")
+                .unwrap();
+        }
+        assert!(disconnected.iter().all(|flag| flag.load(Ordering::SeqCst)));
+        for exchange_type in [ExchangeType::Binance, ExchangeType::Okx, ExchangeType::Coinbase] {
+            assert!(!health.status(exchange_type).unwrap().connected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_resubscribes_only_active_subscriptions() {
+        let mut exchange = ScriptedExchange::new(ExchangeType::Okx, Duration::ZERO, Duration::ZERO, 1);
+        let btc = Subscription::agg_trade("BTCUSDT");
+        let eth = Subscription::agg_trade("ETHUSDT");
+        exchange.subscribe(vec![btc.clone(), eth.clone()]).await.unwrap();
+        exchange.unsubscribe(vec![eth]).await.unwrap();
+        exchange.connected = false;
+
+        let (tx, mut rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let mut policy = ReconnectPolicy::new(Duration::from_millis(1), Duration::from_millis(1));
+        assert!(step(&mut exchange, &tx, &mut policy, &HealthState::new()).await);
+
+        assert!(exchange.is_connected());
+        assert_eq!(exchange.resubscribed, vec![vec![btc]]);
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_silent_reconnect_is_unhealthy_and_retried() {
+        // Opens fine but never sends anything
+        let exchange = MockExchange::new(ExchangeType::Kucoin);
+        let calls = exchange.calls();
+        let policy = ReconnectPolicy::new(Duration::from_millis(1), Duration::from_millis(1))
+            .with_verify_timeout(Duration::from_millis(300));
+
+        let (tx, _rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        let health = HealthState::new();
+        let handle = spawn_exchange_session(Box::new(exchange), session_span(ExchangeType::Kucoin), tx, policy, shutdown_rx, Arc::default(), health.clone());
+
+        time::sleep(Duration::from_millis(100)).await;
+        let status = health.status(ExchangeType::Kucoin).unwrap();
+        assert!(status.connected);
+        assert!(!health.is_healthy(ExchangeType::Kucoin));
+
+        // The unverified connection is dropped and opened again
+        time::timeout(Duration::from_secs(5), async {
+            while calls.counts().connect < 2 {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("silent reconnect was not retried");
+        assert!(calls.counts().disconnect >= 1);
+        assert!(!health.is_healthy(ExchangeType::Kucoin));
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_log_lines_carry_exchange_and_session_id() {
+        let logs = LogCapture::default();
+        let _logger = tracing::subscriber::set_default(logging::subscriber("info", LogFormat::Json, logs.clone()));
+
+        // Two connections, each logging a receive error
+        let exchange = MockExchange::new(ExchangeType::Okx)
+            .with_event(agg_trade(ExchangeType::Okx, 1))
+            .with_error("first")
+            .with_disconnect()
+            .with_event(agg_trade(ExchangeType::Okx, 2))
+            .with_error("second");
+        let policy = ReconnectPolicy::new(Duration::from_millis(1), Duration::from_millis(1));
+        let (tx, mut rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handle = spawn_exchange_session(Box::new(exchange), session_span(ExchangeType::Okx), tx, policy, shutdown_rx, Arc::default(), HealthState::new());
+
+        let errors = || -> Vec<Value> {
+            logs.output()
+                .lines()
+                .map(|line| serde_json::from_str::<Value>(line).unwrap())
+                .filter(|line| line["fields"]["message"].as_str().is_some_and(|m| m.starts_with("Error receiving")))
+                .collect()
+        };
+        time::timeout(Duration::from_secs(5), async {
+            while errors().len() < 2 {
+                let _ = rx.try_recv();
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("both connections should log their error");
+        shutdown_tx.send(true).unwrap();
+        handle.await.unwrap();
+
+        let sessions: Vec<String> = errors()
+            .iter()
+            .map(|line| {
+                assert_eq!(line["span"]["name"], "exchange");
+                assert_eq!(line["span"]["exchange"], "okx");
+                line["span"]["session_id"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert!(sessions.iter().all(|id| Uuid::parse_str(id).is_ok()));
+        // The reconnect minted a new session id
+        assert_ne!(sessions[0], sessions[1]);
+    }
+}
@@ -0,0 +1,657 @@
+//! Gateway configuration
+//!
+//! This module defines the gateway's settings and loads them from a TOML file.
+//! Command line flags are applied on top by the binary.
+
+use crate::exchange::{
+    self, ContractType, DataType, DepthUpdateSpeed, ExchangeType, KlineInterval, MarketKind, OkxInstType, ParseErrorMonitor,
+    Subscription,
+};
+use crate::filter::EventFilter;
+use crate::instruments::InstrumentList;
+use crate::logging::LogFormat;
+use crate::metrics;
+use crate::parquet_recorder;
+use crate::proxy::ProxyConfig;
+use crate::queue::{self, BackpressurePolicy};
+use crate::reconnect::{self, ReconnectPolicy};
+use crate::recorder::RotationPolicy;
+use crate::kafka_publisher;
+use crate::redis_publisher::{self, ChannelRouting, Compression, OutputMode, SerializationFormat};
+use crate::sink::OutputBackend;
+use crate::stats;
+use crate::time_sync;
+use crate::error::{GatewayError, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::warn;
+
+/// Gateway configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GatewayConfig {
+    /// Redis connection URL
+    pub redis_url: String,
+    /// Symbols to track
+    pub symbols: Vec<String>,
+    /// Symbols to track on a specific exchange, replacing `symbols` there
+    pub exchange_symbols: HashMap<ExchangeType, Vec<String>>,
+    /// Refuse to start if an exchange doesn't list a subscribed symbol
+    pub strict_symbols: bool,
+    /// Subscribe each exchange only to the symbols it lists, warning about the rest
+    pub skip_unlisted_symbols: bool,
+    /// Exchanges to connect
+    pub exchanges: Vec<ExchangeType>,
+    /// Enable testnet/demo mode
+    pub testnet: bool,
+    /// Log level (trace, debug, info, warn or error)
+    pub log_level: String,
+    /// Log line format (text or json)
+    pub log_format: LogFormat,
+    /// Subscriptions sent per batch on the initial subscribe, per exchange
+    pub subscribe_batch_sizes: HashMap<ExchangeType, usize>,
+    /// Subscribe/unsubscribe frames sent per second, per exchange
+    pub subscribe_rate_limits: HashMap<ExchangeType, u32>,
+    /// Count parsed events and print a summary on exit
+    pub count_events: bool,
+    /// Log a summary of event counts and latest prices every this many seconds (0 disables)
+    pub summary_interval_secs: u64,
+    /// Log every event at info level instead of trace
+    pub verbose: bool,
+    /// Also subscribe to continuous-contract klines of this contract type
+    pub continuous_contract: Option<ContractType>,
+    /// Maintain local Binance order books and publish this many levels
+    pub order_book_depth: Option<usize>,
+    /// Stream a partial book of this many levels instead of full diffs, per exchange
+    pub depth_levels: HashMap<ExchangeType, u16>,
+    /// Turn partial book snapshots into best bid/ask tickers instead of depth updates
+    pub derive_book_ticker: bool,
+    /// Depth push rate where the exchange offers a choice (100ms or 500ms)
+    pub depth_update_speed: Option<DepthUpdateSpeed>,
+    /// Binance market streamed (spot, USDⓈ-M or COIN-M futures)
+    pub binance_market: MarketKind,
+    /// Binance WebSocket endpoint replacing the market's default one
+    pub binance_ws: Option<String>,
+    /// OKX instrument streamed per symbol: spot (`BTC-USDT`) or the perpetual swap (`BTC-USDT-SWAP`)
+    pub okx_inst_type: OkxInstType,
+    /// OKX WebSocket endpoint replacing the public or demo one
+    pub okx_ws: Option<String>,
+    /// Binance API key; with it, the account's futures order and balance updates are streamed too
+    pub binance_api_key: Option<String>,
+    /// OKX API key, secret and passphrase; with all three, depth streams over the tick-by-tick channels
+    pub okx_api_key: Option<String>,
+    pub okx_api_secret: Option<String>,
+    pub okx_passphrase: Option<String>,
+    /// HTTP or SOCKS5 proxy for the Binance and OKX WebSockets (`HTTPS_PROXY`/`ALL_PROXY` if unset)
+    pub proxy: Option<String>,
+    /// Poll open interest over REST every this many seconds
+    pub open_interest_interval_secs: Option<u64>,
+    /// Reconnect an exchange that sends no frame for this many seconds (0 disables)
+    pub stale_timeout_secs: u64,
+    /// Log an error once more than this percentage of an exchange's recent frames fail to parse (0 disables)
+    pub parse_error_percent: u8,
+    /// Frames the parse error rate is measured over
+    pub parse_error_window: usize,
+    /// Also reconnect an exchange when its parse error rate trips the alarm
+    pub reconnect_on_parse_errors: bool,
+    /// Retry a reconnect that yields no event within this many seconds (0 trusts the open socket)
+    pub reconnect_verify_timeout_secs: u64,
+    /// Factor each reconnect delay grows by
+    pub reconnect_multiplier: f64,
+    /// Fraction of each reconnect delay that is randomized (0 disables jitter)
+    pub reconnect_jitter: f64,
+    /// Seed for the jitter, making the delay sequence reproducible; entropy if unset
+    pub reconnect_jitter_seed: Option<u64>,
+    /// Tries each exchange connect gets before giving up
+    pub connect_attempts: u32,
+    /// Abandon a connect try after this many seconds (0 waits forever)
+    pub connect_timeout_secs: u64,
+    /// Check exchange clocks against the local one every this many seconds (0 disables)
+    pub time_sync_interval_secs: u64,
+    /// Warn when an exchange clock is off by more than this many milliseconds
+    pub max_clock_drift_ms: u64,
+    /// Correct measured latencies by the last clock offset of each exchange
+    pub apply_clock_offset: bool,
+    /// Only publish klines whose interval has closed
+    pub closed_klines_only: bool,
+    /// Only publish trades of at least this quantity
+    pub min_trade_quantity: Option<f64>,
+    /// Only publish trades worth at least this much in the quote asset
+    pub min_trade_notional: Option<f64>,
+    /// Only publish events for these symbols, if set
+    pub symbol_whitelist: Vec<String>,
+    /// Never publish events for these symbols
+    pub symbol_blacklist: Vec<String>,
+    /// Redis events pipelined per flush (1 disables batching)
+    pub redis_batch_size: usize,
+    /// Background Redis flush interval in milliseconds
+    pub redis_flush_interval_ms: u64,
+    /// Publish to pub/sub channels or append to streams
+    pub redis_output_mode: OutputMode,
+    /// Approximate stream length cap in stream mode
+    pub redis_maxlen: usize,
+    /// Redis payload encoding
+    pub redis_format: SerializationFormat,
+    /// Redis payload compression (`none`, `zstd` or `zstd:<level>`)
+    pub redis_compression: Compression,
+    /// Events held while Redis is unreachable
+    pub redis_backlog_size: usize,
+    /// Redis connections to publish over in parallel
+    pub redis_pool_size: usize,
+    /// Events queued between the exchange readers and the Redis publish task (0 publishes inline)
+    pub redis_queue_size: usize,
+    /// What happens to events when the Redis publish queue is full
+    pub redis_backpressure: BackpressurePolicy,
+    /// Warn when the Redis publish queue fills to this percent of its size (0 never warns)
+    pub redis_queue_warn_percent: u8,
+    /// Merge depth updates per symbol and publish them to Redis at most every this many ms (0 disables)
+    pub redis_depth_coalesce_ms: u64,
+    /// Merge runs of same-price, same-side raw trades within this many ms into one trade for Redis (0 disables)
+    pub redis_trade_aggregation_ms: u64,
+    /// Trade aggregation windows in ms for individual symbols, replacing the one above
+    pub redis_trade_aggregation_windows: HashMap<String, u64>,
+    /// Hold events this many ms and publish them to Redis in exchange-timestamp order (0 disables)
+    pub redis_reorder_grace_ms: u64,
+    /// Also publish every received text frame verbatim to `{prefix}:raw:{exchange}` for debugging
+    pub publish_raw: bool,
+    /// Also publish the best bid and ask across exchanges to `{prefix}:nbbo` whenever it changes
+    pub publish_nbbo: bool,
+    /// Leave an exchange's quote out of the NBBO once it's this many ms older than the newest (0 keeps it)
+    pub nbbo_max_quote_age_ms: u64,
+    /// Add each event's `latency_ms` (receive time minus exchange time) to its Redis payload
+    pub publish_latency: bool,
+    /// Publish an opportunity to `{prefix}:opportunity` when one exchange bids this many
+    /// basis points over another's ask (0 disables)
+    pub opportunity_threshold_bps: f64,
+    /// Basis points the spread must fall under the threshold before its symbol alerts again
+    pub opportunity_hysteresis_bps: f64,
+    /// Publish to the type channels, per-symbol channels (`{prefix}:tick:BTCUSDT`) or both
+    pub redis_routing: ChannelRouting,
+    /// Namespace of the Redis channel names (`{prefix}:tick`, ...)
+    pub redis_channel_prefix: String,
+    /// Redis channel names replacing the prefixed ones, keyed by type (`tick`, `kline`, ...)
+    pub redis_channels: HashMap<String, String>,
+    /// Backends every event is published to
+    pub outputs: Vec<OutputBackend>,
+    /// Kafka bootstrap servers, comma-separated
+    pub kafka_brokers: String,
+    /// Prefix of the per-type Kafka topics
+    pub kafka_topic_prefix: String,
+    /// Kafka payload encoding, independent of Redis's
+    pub kafka_format: SerializationFormat,
+    /// Port for the Prometheus `/metrics` endpoint (0 disables it)
+    pub metrics_port: u16,
+    /// Port for the `/healthz` and `/readyz` probes and `/subscriptions`, if enabled
+    pub health_port: Option<u16>,
+    /// Port for the WebSocket feed of normalized events, if enabled
+    pub ws_serve_port: Option<u16>,
+    /// Record every event as NDJSON into this directory
+    pub record_dir: Option<PathBuf>,
+    /// Rotate recordings at this size in megabytes instead of hourly
+    pub record_rotate_mb: Option<u64>,
+    /// Write agg trades and klines as partitioned Parquet into this directory
+    pub parquet_dir: Option<PathBuf>,
+    /// Rows buffered per Parquet partition before it is written
+    pub parquet_batch_rows: usize,
+    /// Stop the gateway after running this many seconds
+    pub max_duration_secs: Option<u64>,
+    /// Stop the gateway after receiving this many events
+    pub max_events: Option<u64>,
+    /// Republish recordings from this directory instead of connecting to exchanges
+    pub replay_dir: Option<PathBuf>,
+    /// Replay pacing as a multiple of real time (0 replays as fast as possible)
+    pub replay_speed: f64,
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            symbols: vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()],
+            exchange_symbols: HashMap::new(),
+            strict_symbols: false,
+            skip_unlisted_symbols: false,
+            exchanges: vec![ExchangeType::Binance],
+            testnet: false,
+            log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            subscribe_batch_sizes: HashMap::new(),
+            subscribe_rate_limits: HashMap::new(),
+            count_events: false,
+            summary_interval_secs: stats::DEFAULT_SUMMARY_INTERVAL.as_secs(),
+            verbose: false,
+            continuous_contract: None,
+            order_book_depth: None,
+            depth_levels: HashMap::new(),
+            derive_book_ticker: false,
+            depth_update_speed: None,
+            binance_market: MarketKind::default(),
+            binance_ws: None,
+            okx_inst_type: OkxInstType::default(),
+            okx_ws: None,
+            binance_api_key: None,
+            okx_api_key: None,
+            okx_api_secret: None,
+            okx_passphrase: None,
+            proxy: None,
+            open_interest_interval_secs: None,
+            stale_timeout_secs: exchange::DEFAULT_STALE_TIMEOUT.as_secs(),
+            parse_error_percent: exchange::DEFAULT_PARSE_ERROR_PERCENT,
+            parse_error_window: exchange::DEFAULT_PARSE_ERROR_WINDOW,
+            reconnect_on_parse_errors: false,
+            reconnect_verify_timeout_secs: reconnect::DEFAULT_VERIFY_TIMEOUT.as_secs(),
+            reconnect_multiplier: reconnect::DEFAULT_MULTIPLIER,
+            reconnect_jitter: reconnect::DEFAULT_JITTER,
+            reconnect_jitter_seed: None,
+            connect_attempts: reconnect::DEFAULT_CONNECT_ATTEMPTS,
+            connect_timeout_secs: reconnect::DEFAULT_CONNECT_TIMEOUT.as_secs(),
+            time_sync_interval_secs: time_sync::DEFAULT_TIME_SYNC_INTERVAL.as_secs(),
+            max_clock_drift_ms: time_sync::DEFAULT_MAX_CLOCK_DRIFT.as_millis() as u64,
+            apply_clock_offset: false,
+            closed_klines_only: false,
+            min_trade_quantity: None,
+            min_trade_notional: None,
+            symbol_whitelist: Vec::new(),
+            symbol_blacklist: Vec::new(),
+            redis_batch_size: 1,
+            redis_flush_interval_ms: 10,
+            redis_output_mode: OutputMode::PubSub,
+            redis_maxlen: redis_publisher::DEFAULT_STREAM_MAXLEN,
+            redis_format: SerializationFormat::Json,
+            redis_compression: Compression::None,
+            redis_backlog_size: redis_publisher::DEFAULT_BACKLOG_SIZE,
+            redis_pool_size: 1,
+            redis_queue_size: queue::DEFAULT_QUEUE_SIZE,
+            redis_backpressure: BackpressurePolicy::default(),
+            redis_queue_warn_percent: queue::DEFAULT_WARN_PERCENT,
+            redis_depth_coalesce_ms: 0,
+            redis_trade_aggregation_ms: 0,
+            redis_trade_aggregation_windows: HashMap::new(),
+            redis_reorder_grace_ms: 0,
+            publish_raw: false,
+            publish_nbbo: false,
+            nbbo_max_quote_age_ms: crate::nbbo::DEFAULT_MAX_QUOTE_AGE_MS,
+            publish_latency: false,
+            opportunity_threshold_bps: 0.0,
+            opportunity_hysteresis_bps: crate::opportunity::DEFAULT_HYSTERESIS_BPS,
+            redis_routing: ChannelRouting::ByType,
+            redis_channel_prefix: redis_publisher::DEFAULT_CHANNEL_PREFIX.to_string(),
+            redis_channels: HashMap::new(),
+            outputs: vec![OutputBackend::Redis],
+            kafka_brokers: "localhost:9092".to_string(),
+            kafka_topic_prefix: kafka_publisher::DEFAULT_TOPIC_PREFIX.to_string(),
+            kafka_format: SerializationFormat::Json,
+            metrics_port: metrics::DEFAULT_METRICS_PORT,
+            health_port: None,
+            ws_serve_port: None,
+            record_dir: None,
+            record_rotate_mb: None,
+            parquet_dir: None,
+            parquet_batch_rows: parquet_recorder::DEFAULT_PARQUET_BATCH_ROWS,
+            max_duration_secs: None,
+            max_events: None,
+            replay_dir: None,
+            replay_speed: 0.0,
+        }
+    }
+}
+
+impl GatewayConfig {
+    /// Load a configuration from a TOML file; missing keys keep their defaults
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| GatewayError::Config(format!("Failed to read config file {}: {}", path.display(), e)))?;
+        Self::from_toml(&contents)
+            .map_err(|e| GatewayError::Config(format!("Invalid config file {}: {}", path.display(), e)))
+    }
+
+    /// Parse a configuration from TOML text
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Symbols to subscribe to on an exchange
+    pub fn symbols_for(&self, exchange: ExchangeType) -> &[String] {
+        self.exchange_symbols
+            .get(&exchange)
+            .unwrap_or(&self.symbols)
+    }
+
+    /// Drop the symbols each listed exchange doesn't list from its symbols,
+    /// warning about them, and return what was dropped per exchange.
+    ///
+    /// Exchanges without a listing keep all their symbols.
+    pub fn retain_listed_symbols(&mut self, listings: &[InstrumentList]) -> HashMap<ExchangeType, Vec<String>> {
+        let mut skipped = HashMap::new();
+        for listed in listings {
+            let exchange = listed.exchange();
+            let (kept, unlisted): (Vec<String>, Vec<String>) = self.symbols_for(exchange)
+                .iter()
+                .cloned()
+                .partition(|symbol| listed.contains(symbol));
+            if unlisted.is_empty() {
+                continue;
+            }
+
+            warn!("Skipping {} on {}: not listed there", unlisted.join(", "), exchange);
+            if kept.is_empty() {
+                warn!("{} lists none of the configured symbols", exchange);
+            }
+            self.exchange_symbols.insert(exchange, kept);
+            skipped.insert(exchange, unlisted);
+        }
+        skipped
+    }
+
+    /// Subscriptions to request from an exchange for its symbols
+    pub fn subscriptions_for(&self, exchange: ExchangeType) -> Vec<Subscription> {
+        let mut subscriptions = create_subscriptions(self.symbols_for(exchange), self.continuous_contract);
+        for sub in subscriptions.iter_mut().filter(|sub| sub.data_type == DataType::Depth) {
+            sub.depth_levels = self.depth_levels.get(&exchange).copied();
+            sub.update_speed = self.depth_update_speed;
+        }
+        subscriptions
+    }
+
+    /// How recordings are split into files
+    pub fn record_rotation(&self) -> RotationPolicy {
+        match self.record_rotate_mb {
+            Some(mb) => RotationPolicy::Size(mb * 1024 * 1024),
+            None => RotationPolicy::Hourly,
+        }
+    }
+
+    /// Open interest polling interval, if enabled
+    pub fn open_interest_interval(&self) -> Option<Duration> {
+        self.open_interest_interval_secs.map(Duration::from_secs)
+    }
+
+    /// Interval between event summaries, if enabled
+    pub fn summary_interval(&self) -> Option<Duration> {
+        (self.summary_interval_secs > 0).then(|| Duration::from_secs(self.summary_interval_secs))
+    }
+
+    /// Proxy for exchange WebSockets, from the `proxy` setting or else the environment
+    pub fn proxy(&self) -> Result<Option<ProxyConfig>> {
+        match self.proxy {
+            Some(ref url) => ProxyConfig::parse(url).map(Some),
+            None => ProxyConfig::from_env(),
+        }
+    }
+
+    /// Runtime after which the gateway stops, if limited
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration_secs.map(Duration::from_secs)
+    }
+
+    /// Interval between exchange clock checks, if enabled
+    pub fn time_sync_interval(&self) -> Option<Duration> {
+        (self.time_sync_interval_secs > 0).then(|| Duration::from_secs(self.time_sync_interval_secs))
+    }
+
+    /// Silence after which an exchange connection is dropped; zero disables the check
+    pub fn stale_timeout(&self) -> Duration {
+        Duration::from_secs(self.stale_timeout_secs)
+    }
+
+    /// Parse error alarm handed to each exchange client
+    pub fn parse_error_monitor(&self) -> ParseErrorMonitor {
+        ParseErrorMonitor::new(self.parse_error_window, self.parse_error_percent)
+            .with_reconnect(self.reconnect_on_parse_errors)
+    }
+
+    /// Backoff, connect retries and post-reconnect verification for the exchanges
+    pub fn reconnect_policy(&self) -> ReconnectPolicy {
+        let policy = ReconnectPolicy::default()
+            .with_multiplier(self.reconnect_multiplier)
+            .with_jitter(self.reconnect_jitter)
+            .with_verify_timeout(Duration::from_secs(self.reconnect_verify_timeout_secs))
+            .with_connect_attempts(self.connect_attempts)
+            .with_connect_timeout(Duration::from_secs(self.connect_timeout_secs));
+        match self.reconnect_jitter_seed {
+            Some(seed) => policy.with_seed(seed),
+            None => policy,
+        }
+    }
+
+    /// Filter applied to events before they are published
+    pub fn event_filter(&self) -> EventFilter {
+        let mut filter = EventFilter::new();
+        if self.closed_klines_only {
+            filter = filter.with_closed_klines_only();
+        }
+        if let Some(quantity) = self.min_trade_quantity {
+            filter = filter.with_min_trade_quantity(quantity);
+        }
+        if let Some(notional) = self.min_trade_notional {
+            filter = filter.with_min_trade_notional(notional);
+        }
+        if !self.symbol_whitelist.is_empty() {
+            filter = filter.with_symbol_whitelist(self.symbol_whitelist.iter().cloned());
+        }
+        if !self.symbol_blacklist.is_empty() {
+            filter = filter.with_symbol_blacklist(self.symbol_blacklist.iter().cloned());
+        }
+        filter
+    }
+}
+
+/// Kline intervals subscribed for every symbol
+const SUBSCRIBED_INTERVALS: [KlineInterval; 6] = [
+    KlineInterval::OneMinute,
+    KlineInterval::FiveMinutes,
+    KlineInterval::FifteenMinutes,
+    KlineInterval::ThirtyMinutes,
+    KlineInterval::OneHour,
+    KlineInterval::FourHours,
+];
+
+/// Create subscriptions for all symbols
+pub fn create_subscriptions(symbols: &[String], continuous_contract: Option<ContractType>) -> Vec<Subscription> {
+    let mut subscriptions = Vec::new();
+
+    for symbol in symbols {
+        subscriptions.push(Subscription::agg_trade(symbol));
+
+        for interval in &SUBSCRIBED_INTERVALS {
+            subscriptions.push(Subscription::kline(symbol, *interval));
+        }
+
+        if let Some(contract_type) = continuous_contract {
+            for interval in &SUBSCRIBED_INTERVALS {
+                subscriptions.push(Subscription::continuous_kline(symbol, *interval, contract_type));
+            }
+        }
+
+        subscriptions.push(Subscription::book_ticker(symbol));
+        subscriptions.push(Subscription::ticker_24h(symbol));
+        subscriptions.push(Subscription::depth(symbol));
+        subscriptions.push(Subscription::funding_rate(symbol));
+        subscriptions.push(Subscription::liquidation(symbol));
+    }
+
+    subscriptions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+redis_url = "redis://redis.internal:6380"
+exchanges = ["binance", "okx"]
+symbols = ["BTCUSDT", "ETHUSDT"]
+testnet = true
+log_level = "debug"
+log_format = "json"
+redis_output_mode = "stream"
+redis_format = "msgpack"
+redis_compression = "zstd:5"
+redis_backpressure = "drop_newest"
+open_interest_interval_secs = 30
+stale_timeout_secs = 90
+closed_klines_only = true
+min_trade_notional = 1000.0
+
+[exchange_symbols]
+okx = ["BTCUSDT", "SOLUSDT"]
+
+[subscribe_batch_sizes]
+binance = 50
+"#;
+
+    #[test]
+    fn test_from_toml() {
+        let config = GatewayConfig::from_toml(SAMPLE).unwrap();
+
+        let expected = GatewayConfig {
+            redis_url: "redis://redis.internal:6380".to_string(),
+            exchanges: vec![ExchangeType::Binance, ExchangeType::Okx],
+            symbols: vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()],
+            exchange_symbols: HashMap::from([
+                (ExchangeType::Okx, vec!["BTCUSDT".to_string(), "SOLUSDT".to_string()]),
+            ]),
+            testnet: true,
+            log_level: "debug".to_string(),
+            log_format: LogFormat::Json,
+            subscribe_batch_sizes: HashMap::from([(ExchangeType::Binance, 50)]),
+            redis_output_mode: OutputMode::Stream,
+            redis_format: SerializationFormat::MessagePack,
+            redis_compression: Compression::Zstd { level: 5 },
+            redis_backpressure: BackpressurePolicy::DropNewest,
+            open_interest_interval_secs: Some(30),
+            stale_timeout_secs: 90,
+            closed_klines_only: true,
+            min_trade_notional: Some(1000.0),
+            ..GatewayConfig::default()
+        };
+        assert_eq!(config, expected);
+        assert_eq!(config.open_interest_interval(), Some(Duration::from_secs(30)));
+        assert_eq!(config.stale_timeout(), Duration::from_secs(90));
+        assert_eq!(config.event_filter(), EventFilter::new().with_closed_klines_only().with_min_trade_notional(1000.0));
+    }
+
+    #[test]
+    fn test_reconnect_policy_from_config() {
+        let config = GatewayConfig::from_toml("reconnect_multiplier = 3.0\nreconnect_jitter = 0.0").unwrap();
+        let mut policy = config.reconnect_policy();
+        let delays: Vec<u64> = (0..4).map(|_| policy.next_delay().as_secs()).collect();
+        assert_eq!(delays, vec![1, 3, 9, 27]);
+
+        // A seed makes the jittered schedule repeat
+        let seeded = GatewayConfig::from_toml("reconnect_jitter_seed = 42").unwrap();
+        let sequence = || -> Vec<Duration> {
+            let mut policy = seeded.reconnect_policy();
+            (0..5).map(|_| policy.next_delay()).collect()
+        };
+        assert_eq!(sequence(), sequence());
+    }
+
+    #[test]
+    fn test_symbols_for_falls_back_to_global_list() {
+        let config = GatewayConfig::from_toml(SAMPLE).unwrap();
+
+        assert_eq!(config.symbols_for(ExchangeType::Okx), ["BTCUSDT", "SOLUSDT"]);
+        assert_eq!(config.symbols_for(ExchangeType::Binance), ["BTCUSDT", "ETHUSDT"]);
+    }
+
+    #[test]
+    fn test_empty_file_uses_defaults() {
+        assert_eq!(GatewayConfig::from_toml("").unwrap(), GatewayConfig::default());
+    }
+
+    #[test]
+    fn test_unknown_key_is_rejected() {
+        assert!(GatewayConfig::from_toml("redis = \"redis://localhost\"").is_err());
+    }
+
+    #[test]
+    fn test_subscriptions_per_exchange() {
+        let config = GatewayConfig {
+            exchanges: vec![ExchangeType::Binance, ExchangeType::Okx, ExchangeType::Bybit],
+            symbols: vec!["SOLUSDT".to_string()],
+            exchange_symbols: HashMap::from([
+                (ExchangeType::Binance, vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()]),
+                (ExchangeType::Okx, vec!["BTCUSDT".to_string()]),
+            ]),
+            ..GatewayConfig::default()
+        };
+
+        let symbols = |exchange| {
+            let mut symbols: Vec<_> = config.subscriptions_for(exchange)
+                .into_iter()
+                .map(|sub| sub.symbol)
+                .collect();
+            symbols.dedup();
+            symbols
+        };
+
+        let per_symbol = create_subscriptions(&["BTCUSDT".to_string()], None).len();
+        assert_eq!(config.subscriptions_for(ExchangeType::Binance).len(), 2 * per_symbol);
+        assert_eq!(config.subscriptions_for(ExchangeType::Okx).len(), per_symbol);
+        assert_eq!(symbols(ExchangeType::Binance), ["BTCUSDT", "ETHUSDT"]);
+        assert_eq!(symbols(ExchangeType::Okx), ["BTCUSDT"]);
+        // Exchanges without their own list fall back to the global one
+        assert_eq!(symbols(ExchangeType::Bybit), ["SOLUSDT"]);
+    }
+
+    #[test]
+    fn test_depth_levels_per_exchange() {
+        let config = GatewayConfig::from_toml(r#"
+depth_update_speed = "500ms"
+
+[depth_levels]
+binance = 20
+"#).unwrap();
+
+        let depth = |exchange| config.subscriptions_for(exchange)
+            .into_iter()
+            .find(|sub| sub.data_type == DataType::Depth)
+            .unwrap();
+        assert_eq!(depth(ExchangeType::Binance).depth_levels, Some(20));
+        assert_eq!(depth(ExchangeType::Binance).update_speed, Some(DepthUpdateSpeed::Ms500));
+        assert_eq!(depth(ExchangeType::Okx).depth_levels, None);
+    }
+
+    #[test]
+    fn test_output_backends() {
+        assert_eq!(GatewayConfig::default().outputs, vec![OutputBackend::Redis]);
+
+        let config = GatewayConfig::from_toml(r#"
+outputs = ["redis", "kafka"]
+kafka_brokers = "kafka-1:9092,kafka-2:9092"
+redis_format = "msgpack"
+"#).unwrap();
+        assert_eq!(config.outputs, vec![OutputBackend::Redis, OutputBackend::Kafka]);
+        assert_eq!(config.kafka_brokers, "kafka-1:9092,kafka-2:9092");
+        assert_eq!(config.kafka_topic_prefix, "flash_arb");
+        // Redis's encoding doesn't carry over to Kafka
+        assert_eq!(config.kafka_format, SerializationFormat::Json);
+    }
+
+    #[test]
+    fn test_unlisted_symbols_are_skipped_per_exchange() {
+        let logs = crate::testing::LogCapture::default();
+        let _logger = tracing::subscriber::set_default(crate::logging::subscriber("warn", LogFormat::Text, logs.clone()));
+
+        let mut config = GatewayConfig {
+            exchanges: vec![ExchangeType::Binance, ExchangeType::Okx],
+            symbols: vec!["BTCUSDT".to_string(), "1000PEPEUSDT".to_string()],
+            ..GatewayConfig::default()
+        };
+        let listings = [
+            InstrumentList::new(ExchangeType::Binance, ["BTCUSDT".to_string(), "1000PEPEUSDT".to_string()]),
+            InstrumentList::new(ExchangeType::Okx, ["BTCUSDT".to_string(), "ETHUSDT".to_string()]),
+        ];
+
+        let skipped = config.retain_listed_symbols(&listings);
+        assert_eq!(skipped, HashMap::from([(ExchangeType::Okx, vec!["1000PEPEUSDT".to_string()])]));
+        assert_eq!(config.symbols_for(ExchangeType::Binance), ["BTCUSDT", "1000PEPEUSDT"]);
+        assert_eq!(config.symbols_for(ExchangeType::Okx), ["BTCUSDT"]);
+        assert!(config.subscriptions_for(ExchangeType::Okx).iter().all(|sub| sub.symbol == "BTCUSDT"));
+        assert!(logs.output().contains("Skipping 1000PEPEUSDT on okx"), "{}", logs.output());
+    }
+}
@@ -0,0 +1,245 @@
+//! Event output sinks
+//!
+//! This module abstracts where normalized events are published, so the
+//! gateway can write to Redis, Kafka, stdout or several backends at once.
+
+use crate::exchange::MarketEvent;
+use crate::kafka_publisher::KafkaPublisher;
+use crate::redis_publisher::RedisPublisher;
+use crate::error::{GatewayError, Result};
+use async_trait::async_trait;
+use redis::aio::ConnectionLike;
+use serde::Deserialize;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Backend that published events are written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputBackend {
+    Redis,
+    Kafka,
+    /// JSON lines on stdout, for running without any broker
+    Stdout,
+}
+
+impl std::str::FromStr for OutputBackend {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "redis" => Ok(OutputBackend::Redis),
+            "kafka" => Ok(OutputBackend::Kafka),
+            "stdout" => Ok(OutputBackend::Stdout),
+            _ => Err(GatewayError::Config(format!("Unknown output backend: {}", s))),
+        }
+    }
+}
+
+impl fmt::Display for OutputBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputBackend::Redis => write!(f, "redis"),
+            OutputBackend::Kafka => write!(f, "kafka"),
+            OutputBackend::Stdout => write!(f, "stdout"),
+        }
+    }
+}
+
+/// Destination for published events
+#[async_trait]
+pub trait EventSink: Send {
+    async fn publish_event(&mut self, event: &MarketEvent) -> Result<()>;
+
+    /// Write out anything the sink is still buffering
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Write out everything the sink still holds before the gateway exits
+    async fn shutdown(&mut self) -> Result<()> {
+        self.flush().await
+    }
+}
+
+#[async_trait]
+impl<C: ConnectionLike + Clone + Send + Sync + 'static> EventSink for RedisPublisher<C> {
+    async fn publish_event(&mut self, event: &MarketEvent) -> Result<()> {
+        RedisPublisher::publish_event(self, event).await
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        RedisPublisher::flush(self).await.map(|_| ())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        RedisPublisher::shutdown(self).await.map(|_| ())
+    }
+}
+
+#[async_trait]
+impl EventSink for KafkaPublisher {
+    async fn publish_event(&mut self, event: &MarketEvent) -> Result<()> {
+        KafkaPublisher::publish_event(self, event)
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        KafkaPublisher::flush(self).await
+    }
+}
+
+/// Writes each event as one line of JSON
+pub struct StdoutSink {
+    out: Box<dyn Write + Send>,
+}
+
+impl Default for StdoutSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StdoutSink {
+    pub fn new() -> Self {
+        Self::with_writer(io::stdout())
+    }
+
+    /// Write to `out` instead of stdout
+    pub fn with_writer(out: impl Write + Send + 'static) -> Self {
+        Self { out: Box::new(out) }
+    }
+}
+
+#[async_trait]
+impl EventSink for StdoutSink {
+    async fn publish_event(&mut self, event: &MarketEvent) -> Result<()> {
+        serde_json::to_writer(&mut self.out, event)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        Ok(self.out.flush()?)
+    }
+}
+
+/// Publishes every event to each of its sinks.
+///
+/// A failing sink doesn't stop the others from receiving the event; the
+/// first error is returned once all of them have been tried.
+#[derive(Default)]
+pub struct FanoutSink {
+    sinks: Vec<Box<dyn EventSink>>,
+}
+
+impl FanoutSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also publish to `sink`
+    pub fn with_sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+}
+
+#[async_trait]
+impl EventSink for FanoutSink {
+    async fn publish_event(&mut self, event: &MarketEvent) -> Result<()> {
+        let mut result = Ok(());
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.publish_event(event).await {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.flush().await {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.shutdown().await {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{AggTrade, ExchangeType};
+    use std::sync::{Arc, Mutex};
+
+    /// Records events into a shared list, optionally failing every publish
+    struct SharedSink {
+        events: Arc<Mutex<Vec<MarketEvent>>>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl EventSink for SharedSink {
+        async fn publish_event(&mut self, event: &MarketEvent) -> Result<()> {
+            self.events.lock().unwrap().push(event.clone());
+            if self.fail {
+                return Err(GatewayError::Disconnected("sink unavailable".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fanout_reaches_every_sink() {
+        let first = Arc::new(Mutex::new(Vec::new()));
+        let second = Arc::new(Mutex::new(Vec::new()));
+        let mut sink = FanoutSink::new()
+            .with_sink(SharedSink { events: first.clone(), fail: true })
+            .with_sink(SharedSink { events: second.clone(), fail: false });
+        assert_eq!(sink.len(), 2);
+
+        let event = MarketEvent::AggTrade(AggTrade {
+            exchange: ExchangeType::Bybit,
+            symbol: "BTCUSDT".to_string(),
+            price: 50000.0,
+            quantity: 0.1,
+            timestamp: 1_700_000_000_000,
+            is_buyer_maker: true,
+            trade_id: 7,
+            received_at: 0,
+        });
+
+        // The first sink's failure is reported, but the second still gets the event
+        assert!(sink.publish_event(&event).await.is_err());
+        assert_eq!(*first.lock().unwrap(), vec![event.clone()]);
+        assert_eq!(*second.lock().unwrap(), vec![event]);
+        assert!(sink.flush().await.is_ok());
+    }
+
+    #[test]
+    fn test_parse_output_backend() {
+        assert_eq!("redis".parse::<OutputBackend>().unwrap(), OutputBackend::Redis);
+        assert_eq!("Kafka".parse::<OutputBackend>().unwrap(), OutputBackend::Kafka);
+        assert_eq!("stdout".parse::<OutputBackend>().unwrap(), OutputBackend::Stdout);
+        assert!("nats".parse::<OutputBackend>().is_err());
+    }
+}
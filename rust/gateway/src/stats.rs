@@ -0,0 +1,250 @@
+//! Parsed-event statistics
+//!
+//! This module counts parsed market events by exchange, type and symbol
+//! so a run can be sanity-checked when it ends, and aggregates them into
+//! periodic summaries instead of a log line per event.
+
+use crate::exchange::{DataType, ExchangeType, MarketEvent};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// Default interval between periodic event summaries
+pub const DEFAULT_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Counts of parsed events keyed by (exchange, data type, symbol)
+#[derive(Debug)]
+pub struct EventCounter {
+    counts: HashMap<(ExchangeType, DataType, String), u64>,
+    started: Instant,
+}
+
+impl Default for EventCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventCounter {
+    /// Create an empty counter
+    pub fn new() -> Self {
+        Self {
+            counts: HashMap::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Count a parsed event
+    pub fn record(&mut self, event: &MarketEvent) {
+        let key = (event.exchange(), event.event_type(), event.symbol().to_string());
+        *self.counts.entry(key).or_insert(0) += 1;
+    }
+
+    /// Number of events seen for an (exchange, type, symbol) combination
+    pub fn count(&self, exchange: ExchangeType, data_type: DataType, symbol: &str) -> u64 {
+        self.counts
+            .get(&(exchange, data_type, symbol.to_string()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Total number of events seen
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Render the counts as a table sorted by exchange, type and symbol
+    pub fn summary(&self) -> String {
+        let mut rows: Vec<_> = self.counts.iter().collect();
+        rows.sort_by(|(a, _), (b, _)| {
+            (a.0.to_string(), a.1.as_str(), &a.2).cmp(&(b.0.to_string(), b.1.as_str(), &b.2))
+        });
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{:<10} {:<12} {:<14} {:>10}", "EXCHANGE", "TYPE", "SYMBOL", "COUNT");
+        for ((exchange, data_type, symbol), count) in rows {
+            let _ = writeln!(
+                out,
+                "{:<10} {:<12} {:<14} {:>10}",
+                exchange.to_string(), data_type.as_str(), symbol, count
+            );
+        }
+        let _ = write!(
+            out,
+            "{} events in {:.1}s",
+            self.total(),
+            self.started.elapsed().as_secs_f64()
+        );
+
+        out
+    }
+}
+
+/// Event counts by exchange and type over a window, plus the latest price per symbol
+#[derive(Debug)]
+pub struct EventSummary {
+    counts: HashMap<(ExchangeType, DataType), u64>,
+    prices: HashMap<String, f64>,
+    window_start: Instant,
+}
+
+impl Default for EventSummary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventSummary {
+    /// Start an empty window
+    pub fn new() -> Self {
+        Self {
+            counts: HashMap::new(),
+            prices: HashMap::new(),
+            window_start: Instant::now(),
+        }
+    }
+
+    /// Count an event and remember its price
+    pub fn record(&mut self, event: &MarketEvent) {
+        *self.counts.entry((event.exchange(), event.event_type())).or_insert(0) += 1;
+        if let Some(price) = last_price(event) {
+            match self.prices.get_mut(event.symbol()) {
+                Some(last) => *last = price,
+                None => {
+                    self.prices.insert(event.symbol().to_string(), price);
+                }
+            }
+        }
+    }
+
+    /// Events of a type from an exchange in the current window
+    pub fn count(&self, exchange: ExchangeType, data_type: DataType) -> u64 {
+        self.counts.get(&(exchange, data_type)).copied().unwrap_or(0)
+    }
+
+    /// Events in the current window
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Latest price seen for a symbol
+    pub fn last_price(&self, symbol: &str) -> Option<f64> {
+        self.prices.get(symbol).copied()
+    }
+
+    /// Render the window as one line and start a new one; prices carry over
+    pub fn take(&mut self) -> String {
+        let mut counts: Vec<_> = self.counts.drain().collect();
+        counts.sort_by(|(a, _), (b, _)| (a.0.to_string(), a.1.as_str()).cmp(&(b.0.to_string(), b.1.as_str())));
+        let mut prices: Vec<_> = self.prices.iter().collect();
+        prices.sort_by(|a, b| a.0.cmp(b.0));
+
+        let total: u64 = counts.iter().map(|(_, count)| count).sum();
+        let mut out = format!("{} events in {:.1}s", total, self.window_start.elapsed().as_secs_f64());
+        for ((exchange, data_type), count) in counts {
+            let _ = write!(out, " {}/{}={}", exchange, data_type.as_str(), count);
+        }
+        if !prices.is_empty() {
+            out.push_str(" | last");
+            for (symbol, price) in prices {
+                let _ = write!(out, " {}={}", symbol, price);
+            }
+        }
+
+        self.window_start = Instant::now();
+        out
+    }
+}
+
+/// Price an event reports for its symbol: trade price, kline close, book mid or last price
+fn last_price(event: &MarketEvent) -> Option<f64> {
+    match event {
+        MarketEvent::AggTrade(t) => Some(t.price),
+        MarketEvent::Kline(k) => Some(k.close),
+        MarketEvent::BookTicker(b) => Some((b.bid_price + b.ask_price) / 2.0),
+        MarketEvent::Ticker24h(t) => Some(t.last_price),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{AggTrade, BookTicker};
+    use crate::testing;
+
+    fn trade(exchange: ExchangeType, symbol: &str) -> MarketEvent {
+        MarketEvent::AggTrade(AggTrade { symbol: symbol.to_string(), ..testing::trade(exchange, 1) })
+    }
+
+    fn ticker(exchange: ExchangeType, symbol: &str) -> MarketEvent {
+        MarketEvent::BookTicker(BookTicker {
+            exchange,
+            symbol: symbol.to_string(),
+            bid_price: 49999.0,
+            bid_qty: 1.0,
+            ask_price: 50001.0,
+            ask_qty: 1.0,
+            timestamp: 0,
+            received_at: 0,
+        })
+    }
+
+    #[test]
+    fn test_counts_known_mix() {
+        let mut counter = EventCounter::new();
+        let events = vec![
+            trade(ExchangeType::Binance, "BTCUSDT"),
+            trade(ExchangeType::Binance, "BTCUSDT"),
+            trade(ExchangeType::Okx, "BTCUSDT"),
+            ticker(ExchangeType::Binance, "ETHUSDT"),
+            ticker(ExchangeType::Binance, "ETHUSDT"),
+            ticker(ExchangeType::Binance, "ETHUSDT"),
+        ];
+        for event in &events {
+            counter.record(event);
+        }
+
+        assert_eq!(counter.total(), 6);
+        assert_eq!(counter.count(ExchangeType::Binance, DataType::AggTrade, "BTCUSDT"), 2);
+        assert_eq!(counter.count(ExchangeType::Okx, DataType::AggTrade, "BTCUSDT"), 1);
+        assert_eq!(counter.count(ExchangeType::Binance, DataType::BookTicker, "ETHUSDT"), 3);
+        assert_eq!(counter.count(ExchangeType::Okx, DataType::BookTicker, "ETHUSDT"), 0);
+
+        let summary = counter.summary();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].starts_with("binance") && lines[1].contains("aggTrade") && lines[1].ends_with("2"));
+        assert!(lines[2].contains("bookTicker") && lines[2].ends_with("3"));
+        assert!(lines[3].starts_with("okx") && lines[3].ends_with("1"));
+        assert!(lines[4].starts_with("6 events"));
+    }
+
+    #[test]
+    fn test_summary_counts_by_type_per_window() {
+        let mut summary = EventSummary::new();
+        summary.record(&trade(ExchangeType::Binance, "BTCUSDT"));
+        summary.record(&trade(ExchangeType::Binance, "ETHUSDT"));
+        summary.record(&ticker(ExchangeType::Binance, "BTCUSDT"));
+        summary.record(&trade(ExchangeType::Okx, "BTCUSDT"));
+
+        assert_eq!(summary.total(), 4);
+        assert_eq!(summary.count(ExchangeType::Binance, DataType::AggTrade), 2);
+        assert_eq!(summary.count(ExchangeType::Binance, DataType::BookTicker), 1);
+        assert_eq!(summary.count(ExchangeType::Okx, DataType::AggTrade), 1);
+        // The trade after the ticker is the latest BTCUSDT price
+        assert_eq!(summary.last_price("BTCUSDT"), Some(50000.0));
+
+        let line = summary.take();
+        assert!(line.starts_with("4 events"));
+        assert!(line.contains("binance/aggTrade=2 binance/bookTicker=1 okx/aggTrade=1"));
+        assert!(line.ends_with("| last BTCUSDT=50000 ETHUSDT=50000"));
+
+        // The next window starts from zero but remembers prices
+        summary.record(&ticker(ExchangeType::Binance, "BTCUSDT"));
+        assert_eq!(summary.total(), 1);
+        assert_eq!(summary.count(ExchangeType::Binance, DataType::AggTrade), 0);
+        assert_eq!(summary.last_price("BTCUSDT"), Some(50000.0));
+        assert_eq!(summary.last_price("ETHUSDT"), Some(50000.0));
+    }
+}
@@ -0,0 +1,187 @@
+//! Symbol normalization
+//!
+//! This module converts between the gateway's canonical symbols (`BTCUSDT`)
+//! and each exchange's own notation (`BTC-USDT`, `BTC-USD`, ...), so clients
+//! never split or join pairs by hand.
+
+use crate::exchange::ExchangeType;
+use crate::error::{GatewayError, Result};
+use std::fmt;
+
+/// Quote currencies recognised when splitting a canonical symbol. Longer
+/// codes come before their suffixes so `USDT`/`TUSD` win over `USD`.
+const QUOTE_CURRENCIES: [&str; 14] = [
+    "FDUSD", "USDT", "USDC", "BUSD", "TUSD", "USD", "EUR", "GBP", "TRY", "BTC", "ETH", "BNB", "KCS", "DAI",
+];
+
+/// Separators accepted between base and quote
+const SEPARATORS: [char; 3] = ['-', '/', '_'];
+
+/// A trading pair, e.g. base `BTC` and quote `USDT`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    pub base: String,
+    pub quote: String,
+}
+
+impl Symbol {
+    pub fn new(base: impl Into<String>, quote: impl Into<String>) -> Self {
+        Self {
+            base: base.into().to_uppercase(),
+            quote: quote.into().to_uppercase(),
+        }
+    }
+
+    /// Parse a canonical symbol (`BTCUSDT`), or a pair with a separator (`BTC-USDT`, `BTC/USDT`)
+    pub fn parse_canonical(symbol: &str) -> Result<Self> {
+        let symbol = symbol.trim().to_uppercase();
+
+        if let Some((base, quote)) = symbol.split_once(SEPARATORS) {
+            if base.is_empty() || quote.is_empty() {
+                return Err(GatewayError::Parse(format!("Invalid symbol: {}", symbol)));
+            }
+            return Ok(Self::new(base, quote));
+        }
+
+        QUOTE_CURRENCIES.iter()
+            .find_map(|quote| {
+                let base = symbol.strip_suffix(quote)?;
+                (!base.is_empty()).then(|| Self::new(base, *quote))
+            })
+            .ok_or_else(|| GatewayError::Parse(format!("Unknown quote currency in symbol: {}", symbol)))
+    }
+
+    /// Parse a symbol in an exchange's notation, ignoring OKX `-SWAP` and expiry suffixes
+    pub fn from_exchange(exchange: ExchangeType, symbol: &str) -> Result<Self> {
+        match exchange {
+            ExchangeType::Binance | ExchangeType::Bybit | ExchangeType::Bitget => Self::parse_canonical(symbol),
+            ExchangeType::Okx | ExchangeType::Coinbase | ExchangeType::Kucoin => {
+                let mut parts = symbol.split('-');
+                match (parts.next(), parts.next()) {
+                    (Some(base), Some(quote)) if !base.is_empty() && !quote.is_empty() => {
+                        Ok(Self::new(base, quote))
+                    }
+                    _ => Err(GatewayError::Parse(format!("Invalid {} symbol: {}", exchange, symbol))),
+                }
+            }
+            ExchangeType::Deribit => {
+                let name = symbol.strip_suffix("-PERPETUAL")
+                    .ok_or_else(|| GatewayError::Parse(format!("Not a Deribit perpetual: {}", symbol)))?;
+                match name.split_once('_') {
+                    Some((base, quote)) if !base.is_empty() && !quote.is_empty() => Ok(Self::new(base, quote)),
+                    Some(_) => Err(GatewayError::Parse(format!("Invalid {} symbol: {}", exchange, symbol))),
+                    // Inverse perpetuals are margined and quoted in USD
+                    None if !name.is_empty() => Ok(Self::new(name, "USD")),
+                    None => Err(GatewayError::Parse(format!("Invalid {} symbol: {}", exchange, symbol))),
+                }
+            }
+            ExchangeType::Gateio => match symbol.split_once('_') {
+                Some((base, quote)) if !base.is_empty() && !quote.is_empty() => Ok(Self::new(base, quote)),
+                _ => Err(GatewayError::Parse(format!("Invalid {} symbol: {}", exchange, symbol))),
+            },
+        }
+    }
+
+    /// This pair in an exchange's notation
+    pub fn to_exchange(&self, exchange: ExchangeType) -> String {
+        match exchange {
+            ExchangeType::Binance | ExchangeType::Bybit | ExchangeType::Bitget => self.to_string(),
+            ExchangeType::Okx | ExchangeType::Coinbase | ExchangeType::Kucoin => {
+                format!("{}-{}", self.base, self.quote)
+            }
+            // Linear perpetuals exist only for USDC; USD and USDT pairs map to the inverse perpetual
+            ExchangeType::Deribit if self.quote == "USDC" => format!("{}_USDC-PERPETUAL", self.base),
+            ExchangeType::Deribit => format!("{}-PERPETUAL", self.base),
+            ExchangeType::Gateio => format!("{}_{}", self.base, self.quote),
+        }
+    }
+}
+
+impl fmt::Display for Symbol {
+    /// The canonical form, e.g. `BTCUSDT`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.base, self.quote)
+    }
+}
+
+/// Convert a canonical symbol to an exchange's notation, passing unknown pairs through unchanged
+pub fn to_exchange_symbol(exchange: ExchangeType, symbol: &str) -> String {
+    Symbol::parse_canonical(symbol)
+        .map_or_else(|_| symbol.to_string(), |symbol| symbol.to_exchange(exchange))
+}
+
+/// Convert an exchange's symbol to canonical form, passing unknown pairs through unchanged
+pub fn from_exchange_symbol(exchange: ExchangeType, symbol: &str) -> String {
+    Symbol::from_exchange(exchange, symbol)
+        .map_or_else(|_| symbol.to_string(), |symbol| symbol.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binance_round_trip() {
+        let symbol = Symbol::from_exchange(ExchangeType::Binance, "BTCUSDT").unwrap();
+        assert_eq!(symbol, Symbol::new("BTC", "USDT"));
+        assert_eq!(symbol.to_exchange(ExchangeType::Binance), "BTCUSDT");
+
+        // Multiplier prefixes stay part of the base
+        let shib = Symbol::parse_canonical("1000SHIBUSDT").unwrap();
+        assert_eq!(shib, Symbol::new("1000SHIB", "USDT"));
+        assert_eq!(shib.to_exchange(ExchangeType::Okx), "1000SHIB-USDT");
+    }
+
+    #[test]
+    fn test_okx_round_trip() {
+        let symbol = Symbol::from_exchange(ExchangeType::Okx, "BTC-USDT").unwrap();
+        assert_eq!(symbol.to_string(), "BTCUSDT");
+        assert_eq!(symbol.to_exchange(ExchangeType::Okx), "BTC-USDT");
+
+        assert_eq!(Symbol::from_exchange(ExchangeType::Okx, "ETH-USD-SWAP").unwrap(), Symbol::new("ETH", "USD"));
+        assert_eq!(Symbol::from_exchange(ExchangeType::Okx, "BTC-USD-250328").unwrap(), Symbol::new("BTC", "USD"));
+    }
+
+    #[test]
+    fn test_coinbase_round_trip() {
+        let symbol = Symbol::from_exchange(ExchangeType::Coinbase, "BTC-USD").unwrap();
+        assert_eq!(symbol, Symbol::new("BTC", "USD"));
+        assert_eq!(symbol.to_exchange(ExchangeType::Coinbase), "BTC-USD");
+
+        // Longer quote codes win over `USD`
+        assert_eq!(to_exchange_symbol(ExchangeType::Coinbase, "ETHUSDT"), "ETH-USDT");
+        assert_eq!(to_exchange_symbol(ExchangeType::Coinbase, "BTCFDUSD"), "BTC-FDUSD");
+    }
+
+    #[test]
+    fn test_deribit_perpetuals() {
+        assert_eq!(to_exchange_symbol(ExchangeType::Deribit, "BTCUSDT"), "BTC-PERPETUAL");
+        assert_eq!(to_exchange_symbol(ExchangeType::Deribit, "ETHUSDC"), "ETH_USDC-PERPETUAL");
+
+        assert_eq!(Symbol::from_exchange(ExchangeType::Deribit, "BTC-PERPETUAL").unwrap(), Symbol::new("BTC", "USD"));
+        assert_eq!(from_exchange_symbol(ExchangeType::Deribit, "SOL_USDC-PERPETUAL"), "SOLUSDC");
+        // Options and futures have no canonical form
+        assert_eq!(from_exchange_symbol(ExchangeType::Deribit, "BTC-27DEC24-100000-C"), "BTC-27DEC24-100000-C");
+    }
+
+    #[test]
+    fn test_gateio_symbols() {
+        assert_eq!(to_exchange_symbol(ExchangeType::Gateio, "BTCUSDT"), "BTC_USDT");
+        assert_eq!(Symbol::from_exchange(ExchangeType::Gateio, "ETH_USDC").unwrap(), Symbol::new("ETH", "USDC"));
+        assert_eq!(from_exchange_symbol(ExchangeType::Gateio, "SOL_USDT"), "SOLUSDT");
+        assert_eq!(to_exchange_symbol(ExchangeType::Bitget, "BTCUSDT"), "BTCUSDT");
+    }
+
+    #[test]
+    fn test_unparseable_symbols() {
+        assert!(Symbol::parse_canonical("USDT").is_err());
+        assert!(Symbol::parse_canonical("XYZ").is_err());
+        assert!(Symbol::parse_canonical("BTC-").is_err());
+        assert!(Symbol::from_exchange(ExchangeType::Kucoin, "BTCUSDT").is_err());
+
+        // Lenient helpers pass unknown symbols through
+        assert_eq!(to_exchange_symbol(ExchangeType::Okx, "XYZ"), "XYZ");
+        assert_eq!(from_exchange_symbol(ExchangeType::Kucoin, "XYZ"), "XYZ");
+        assert_eq!(Symbol::parse_canonical("btc/usdt").unwrap().to_string(), "BTCUSDT");
+    }
+}
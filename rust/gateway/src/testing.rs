@@ -0,0 +1,420 @@
+//! Test doubles
+//!
+//! This module provides an in-memory `Exchange`, event sink and Redis
+//! connection so the event path (reconnects, filtering, publishing) can be
+//! exercised without network access, a log writer for asserting on what was logged, and a
+//! clock that only moves when told to, along with the trade fixture the
+//! tests share. It is compiled for tests and behind the `testing` feature.
+
+use crate::clock::Clock;
+use crate::exchange::{AggTrade, Exchange, ExchangeType, MarketEvent, Subscription};
+use crate::redis_publisher::{ConnectionPool, RedisConfig, RedisPublisher};
+use crate::sink::EventSink;
+use crate::error::{GatewayError, Result};
+use redis::aio::ConnectionLike;
+use redis::{Cmd, Pipeline};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// One scripted step of a `MockExchange`
+#[derive(Debug, Clone)]
+enum Step {
+    /// `recv_event` returns the event
+    Event(MarketEvent),
+    /// `recv_event` returns no event, like a pong or subscription ack
+    ControlFrame,
+    /// The connection drops
+    Disconnect,
+    /// `recv_event` fails with this message
+    Error(String),
+}
+
+/// How often each `Exchange` method was called on a `MockExchange`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallCounts {
+    pub connect: usize,
+    pub disconnect: usize,
+    pub subscribe: usize,
+    pub unsubscribe: usize,
+    pub resubscribe: usize,
+}
+
+/// Shared view of a `MockExchange`'s calls, readable after the exchange is boxed or moved onto a task
+#[derive(Debug, Clone, Default)]
+pub struct MockCalls(Arc<Mutex<CallCounts>>);
+
+impl MockCalls {
+    /// Calls made so far
+    pub fn counts(&self) -> CallCounts {
+        *self.0.lock().unwrap()
+    }
+
+    fn record(&self, f: impl FnOnce(&mut CallCounts)) {
+        f(&mut self.0.lock().unwrap());
+    }
+}
+
+/// Exchange that plays back a script of events and connection drops.
+///
+/// Once the script runs out it stays quiet like an idle socket, or closes
+/// if built `with_close_when_drained`. Built `with_redis_publisher`, it
+/// publishes each event as it hands it out, like the real clients do.
+pub struct MockExchange {
+    exchange_type: ExchangeType,
+    script: VecDeque<Step>,
+    connected: bool,
+    active: Vec<Subscription>,
+    close_when_drained: bool,
+    /// Connect attempts left to fail
+    failed_connects: usize,
+    calls: MockCalls,
+    redis_publisher: Option<RedisPublisher<MockRedisConnection>>,
+}
+
+impl MockExchange {
+    /// Create a disconnected exchange with an empty script
+    pub fn new(exchange_type: ExchangeType) -> Self {
+        Self {
+            exchange_type,
+            script: VecDeque::new(),
+            connected: false,
+            active: Vec::new(),
+            close_when_drained: false,
+            failed_connects: 0,
+            calls: MockCalls::default(),
+            redis_publisher: None,
+        }
+    }
+
+    /// Create an exchange that emits `events` in order, reporting the first event's exchange
+    pub fn with_events(events: Vec<MarketEvent>) -> Self {
+        let exchange_type = events.first().map_or(ExchangeType::Binance, |e| e.exchange());
+        events.into_iter().fold(Self::new(exchange_type), Self::with_event)
+    }
+
+    /// Append an event to the script
+    pub fn with_event(mut self, event: MarketEvent) -> Self {
+        self.script.push_back(Step::Event(event));
+        self
+    }
+
+    /// Append a frame that carries no event
+    pub fn with_control_frame(mut self) -> Self {
+        self.script.push_back(Step::ControlFrame);
+        self
+    }
+
+    /// Append a dropped connection; the exchange reports itself disconnected until reconnected
+    pub fn with_disconnect(mut self) -> Self {
+        self.script.push_back(Step::Disconnect);
+        self
+    }
+
+    /// Append a receive error
+    pub fn with_error(mut self, message: impl Into<String>) -> Self {
+        self.script.push_back(Step::Error(message.into()));
+        self
+    }
+
+    /// Fail the next `count` connect attempts
+    pub fn with_failed_connects(mut self, count: usize) -> Self {
+        self.failed_connects = count;
+        self
+    }
+
+    /// Close the connection once the script runs out instead of going quiet
+    pub fn with_close_when_drained(mut self) -> Self {
+        self.close_when_drained = true;
+        self
+    }
+
+    /// Publish each event to Redis before returning it, as the exchange clients do
+    pub fn with_redis_publisher(mut self, publisher: RedisPublisher<MockRedisConnection>) -> Self {
+        self.redis_publisher = Some(publisher);
+        self
+    }
+
+    /// Handle for reading call counts after the exchange is moved
+    pub fn calls(&self) -> MockCalls {
+        self.calls.clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl Exchange for MockExchange {
+    fn exchange_type(&self) -> ExchangeType {
+        self.exchange_type
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        self.calls.record(|c| c.connect += 1);
+        if self.failed_connects > 0 {
+            self.failed_connects -= 1;
+            return Err(GatewayError::Connect("Mock connect failure".to_string()));
+        }
+        self.connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.calls.record(|c| c.disconnect += 1);
+        self.connected = false;
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        self.calls.record(|c| c.subscribe += 1);
+        for sub in subscriptions {
+            if !self.active.contains(&sub) {
+                self.active.push(sub);
+            }
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, subscriptions: Vec<Subscription>) -> Result<()> {
+        self.calls.record(|c| c.unsubscribe += 1);
+        self.active.retain(|sub| !subscriptions.contains(sub));
+        Ok(())
+    }
+
+    fn active_subscriptions(&self) -> Vec<Subscription> {
+        self.active.clone()
+    }
+
+    async fn resubscribe(&mut self) -> Result<()> {
+        self.calls.record(|c| c.resubscribe += 1);
+        Ok(())
+    }
+
+    async fn recv_event(&mut self) -> Result<Option<MarketEvent>> {
+        match self.script.pop_front() {
+            Some(Step::Event(event)) => {
+                if let Some(ref publisher) = self.redis_publisher {
+                    publisher.publish_event(&event).await?;
+                }
+                Ok(Some(event))
+            }
+            Some(Step::ControlFrame) => Ok(None),
+            Some(Step::Disconnect) => {
+                self.connected = false;
+                Ok(None)
+            }
+            Some(Step::Error(message)) => Err(GatewayError::Disconnected(message)),
+            None if self.close_when_drained => {
+                self.connected = false;
+                Ok(None)
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    fn ws_endpoint(&self) -> &str {
+        "mock://"
+    }
+}
+
+/// A 0.1 BTCUSDT trade at 50000 on `exchange`; override fields with struct update syntax
+pub fn trade(exchange: ExchangeType, trade_id: u64) -> AggTrade {
+    AggTrade {
+        exchange,
+        symbol: "BTCUSDT".to_string(),
+        price: 50000.0,
+        quantity: 0.1,
+        timestamp: 0,
+        is_buyer_maker: false,
+        trade_id,
+        received_at: 0,
+    }
+}
+
+/// [`trade`] as a market event, for scripting a `MockExchange` or feeding a sink
+pub fn agg_trade(exchange: ExchangeType, trade_id: u64) -> MarketEvent {
+    MarketEvent::AggTrade(trade(exchange, trade_id))
+}
+
+/// Sink keeping every event it is given, in order.
+///
+/// Clones share the events, so the test can keep one while the gateway owns another.
+#[derive(Debug, Clone, Default)]
+pub struct VecSink(Arc<Mutex<Vec<MarketEvent>>>);
+
+impl VecSink {
+    /// Events published so far
+    pub fn events(&self) -> Vec<MarketEvent> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl EventSink for VecSink {
+    async fn publish_event(&mut self, event: &MarketEvent) -> Result<()> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+/// Log writer keeping everything written in memory.
+///
+/// Clones share the buffer, so the test can keep one while a subscriber writes to another.
+#[derive(Debug, Clone, Default)]
+pub struct LogCapture(Arc<Mutex<Vec<u8>>>);
+
+impl LogCapture {
+    /// Everything logged so far
+    pub fn output(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl Write for LogCapture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'w> MakeWriter<'w> for LogCapture {
+    type Writer = LogCapture;
+
+    fn make_writer(&'w self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Clock standing still at a set time until advanced.
+///
+/// Clones share the time, so the test can keep one while a client reads another.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock(Arc<AtomicI64>);
+
+impl MockClock {
+    /// Start at `now_ms` milliseconds since the Unix epoch
+    pub fn new(now_ms: i64) -> Self {
+        Self(Arc::new(AtomicI64::new(now_ms)))
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, by: Duration) {
+        self.0.fetch_add(by.as_millis() as i64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Redis connection that accepts every pipeline and keeps what was sent.
+///
+/// Clones share the record, so the test can keep one while the publisher
+/// owns the others.
+#[derive(Debug, Clone, Default)]
+pub struct MockRedisConnection {
+    pipelines: Arc<Mutex<Vec<SentPipeline>>>,
+}
+
+/// Packed commands of one pipeline and how many commands it held
+type SentPipeline = (Vec<u8>, usize);
+
+impl MockRedisConnection {
+    /// Build a publisher whose every connection records into this one
+    pub async fn publisher(&self, config: RedisConfig) -> Result<RedisPublisher<Self>> {
+        let pool = ConnectionPool::connect(config.pool_size, || std::future::ready(Ok(self.clone()))).await?;
+        RedisPublisher::with_pool(config, pool)
+    }
+
+    /// Packed pipelines sent so far, in order
+    pub fn pipelines(&self) -> Vec<Vec<u8>> {
+        self.pipelines.lock().unwrap().iter().map(|(packed, _)| packed.clone()).collect()
+    }
+
+    /// Number of commands sent across every pipeline
+    pub fn commands(&self) -> usize {
+        self.pipelines.lock().unwrap().iter().map(|(_, count)| count).sum()
+    }
+}
+
+impl ConnectionLike for MockRedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> redis::RedisFuture<'a, redis::Value> {
+        self.pipelines.lock().unwrap().push((cmd.get_packed_command(), 1));
+        Box::pin(std::future::ready(Ok(redis::Value::Okay)))
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        pipe: &'a Pipeline,
+        _offset: usize,
+        count: usize,
+    ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+        self.pipelines.lock().unwrap().push((pipe.get_packed_pipeline(), count));
+        Box::pin(std::future::ready(Ok(vec![redis::Value::Okay; count])))
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn okx_trade(symbol: &str, trade_id: u64) -> MarketEvent {
+        MarketEvent::AggTrade(AggTrade { symbol: symbol.to_string(), ..trade(ExchangeType::Okx, trade_id) })
+    }
+
+    #[tokio::test]
+    async fn test_mock_events_reach_the_publisher() {
+        let mut exchange = MockExchange::with_events(vec![okx_trade("BTCUSDT", 1), okx_trade("ETHUSDT", 2)])
+            .with_close_when_drained();
+        let calls = exchange.calls();
+        let redis = MockRedisConnection::default();
+        let publisher = redis.publisher(RedisConfig::default()).await.unwrap();
+
+        exchange.connect().await.unwrap();
+        exchange.subscribe(vec![Subscription::agg_trade("BTCUSDT"), Subscription::agg_trade("ETHUSDT")]).await.unwrap();
+        while exchange.is_connected() {
+            if let Some(event) = exchange.recv_event().await.unwrap() {
+                publisher.publish_event(&event).await.unwrap();
+            }
+        }
+
+        assert_eq!(exchange.exchange_type(), ExchangeType::Okx);
+        assert_eq!(calls.counts(), CallCounts { connect: 1, subscribe: 1, ..CallCounts::default() });
+        assert_eq!(redis.commands(), 2);
+        let sent = redis.pipelines().concat();
+        for symbol in ["BTCUSDT", "ETHUSDT"] {
+            assert!(sent.windows(symbol.len()).any(|w| w == symbol.as_bytes()), "{} not published", symbol);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_disconnects_and_fails() {
+        let mut exchange = MockExchange::with_events(vec![okx_trade("BTCUSDT", 1)])
+            .with_disconnect()
+            .with_error("boom")
+            .with_failed_connects(1);
+
+        assert!(exchange.connect().await.is_err());
+        exchange.connect().await.unwrap();
+        assert_eq!(exchange.recv_event().await.unwrap(), Some(okx_trade("BTCUSDT", 1)));
+        assert_eq!(exchange.recv_event().await.unwrap(), None);
+        assert!(!exchange.is_connected());
+        assert!(exchange.recv_event().await.is_err());
+        assert_eq!(exchange.calls().counts().connect, 2);
+    }
+}
@@ -0,0 +1,196 @@
+//! Exchange clock sync check
+//!
+//! This module compares the local clock with an exchange's server time over
+//! REST. Latency is measured against exchange timestamps, so a drifting host
+//! clock skews every latency figure; the check records the measured offset
+//! and warns once it passes the allowed drift.
+
+use crate::error::{GatewayError, Result};
+use crate::exchange::{now_ms, ExchangeType, MarketKind};
+use crate::metrics;
+use crate::orderbook::OKX_REST;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::{info, warn};
+
+/// Default time between clock checks
+pub const DEFAULT_TIME_SYNC_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Default clock offset tolerated before warning
+pub const DEFAULT_MAX_CLOCK_DRIFT: Duration = Duration::from_millis(500);
+
+/// Check if `TimeSync` can read an exchange's server time
+pub fn supports_time_sync(exchange: ExchangeType) -> bool {
+    matches!(exchange, ExchangeType::Binance | ExchangeType::Okx)
+}
+
+/// Parse a Binance `/time` response (`{"serverTime":...}`) into milliseconds
+pub fn parse_binance_time(data: &Value) -> Result<i64> {
+    data["serverTime"].as_i64().ok_or_else(|| GatewayError::Parse("Missing serverTime".to_string()))
+}
+
+/// Parse an OKX `/api/v5/public/time` response into milliseconds
+pub fn parse_okx_time(data: &Value) -> Result<i64> {
+    let code = data["code"].as_str().unwrap_or_default();
+    if code != "0" {
+        return Err(GatewayError::Parse(format!("OKX time request failed: {} ({})", data["msg"], code)));
+    }
+    let ts = data["data"][0]["ts"].as_str().ok_or_else(|| GatewayError::Parse("Missing ts".to_string()))?;
+    Ok(ts.parse::<i64>()?)
+}
+
+/// How far the exchange clock is ahead of the local one, in milliseconds.
+///
+/// The server stamped its time somewhere between `sent_ms` and
+/// `received_ms`, so it is compared with the local midpoint of the request.
+/// Adding the offset to a local time gives the exchange's time.
+pub fn clock_offset_ms(server_ms: i64, sent_ms: i64, received_ms: i64) -> i64 {
+    server_ms - (sent_ms + (received_ms - sent_ms) / 2)
+}
+
+/// Checks exchange clocks against the local clock, at startup and periodically
+#[derive(Debug, Clone)]
+pub struct TimeSync {
+    testnet: bool,
+    binance_market: MarketKind,
+    max_drift: Duration,
+    interval: Duration,
+    http: reqwest::Client,
+}
+
+impl TimeSync {
+    /// Create a checker for mainnet or testnet endpoints
+    pub fn new(testnet: bool) -> Self {
+        Self {
+            testnet,
+            binance_market: MarketKind::default(),
+            max_drift: DEFAULT_MAX_CLOCK_DRIFT,
+            interval: DEFAULT_TIME_SYNC_INTERVAL,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Ask the server time of this Binance market's REST API
+    pub fn with_binance_market(mut self, market: MarketKind) -> Self {
+        self.binance_market = market;
+        self
+    }
+
+    /// Warn when a clock is off by more than this
+    pub fn with_max_drift(mut self, max_drift: Duration) -> Self {
+        self.max_drift = max_drift;
+        self
+    }
+
+    /// Set the time between periodic checks
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Fetch an exchange's current server time in milliseconds
+    async fn fetch_server_time(&self, exchange: ExchangeType) -> Result<i64> {
+        match exchange {
+            ExchangeType::Binance => {
+                let market = self.binance_market;
+                let url = format!("{}{}", market.rest_url(self.testnet), market.time_path());
+                let data: Value = self.http.get(&url).send().await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                parse_binance_time(&data)
+            }
+            ExchangeType::Okx => {
+                let mut request = self.http.get(format!("{}/api/v5/public/time", OKX_REST));
+                if self.testnet {
+                    request = request.header("x-simulated-trading", "1");
+                }
+                let data: Value = request.send().await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                parse_okx_time(&data)
+            }
+            ExchangeType::Bybit | ExchangeType::Coinbase | ExchangeType::Kucoin | ExchangeType::Deribit
+            | ExchangeType::Gateio | ExchangeType::Bitget => {
+                Err(GatewayError::Config(format!("Clock sync is not supported for {}", exchange)))
+            }
+        }
+    }
+
+    /// Measure an exchange's clock offset, record it in the metrics and warn if it exceeds the allowed drift
+    pub async fn check(&self, exchange: ExchangeType) -> Result<i64> {
+        let sent = now_ms();
+        let server = self.fetch_server_time(exchange).await?;
+        let offset = clock_offset_ms(server, sent, now_ms());
+
+        metrics::global().set_clock_offset(exchange, offset);
+        if offset.unsigned_abs() > self.max_drift.as_millis() as u64 {
+            warn!(
+                "Local clock is {}ms {} {} (more than {:?}); latency figures are skewed, check NTP",
+                offset.abs(), if offset > 0 { "behind" } else { "ahead of" }, exchange, self.max_drift
+            );
+        } else {
+            info!("{} clock offset: {}ms", exchange, offset);
+        }
+        Ok(offset)
+    }
+
+    /// Check every exchange that supports it, logging the ones that fail
+    pub async fn check_all(&self, exchanges: &[ExchangeType]) -> HashMap<ExchangeType, i64> {
+        let mut offsets = HashMap::new();
+        for &exchange in exchanges.iter().filter(|e| supports_time_sync(**e)) {
+            match self.check(exchange).await {
+                Ok(offset) => {
+                    offsets.insert(exchange, offset);
+                }
+                Err(e) => warn!("Failed to check {} server time: {}", exchange, e),
+            }
+        }
+        offsets
+    }
+
+    /// Re-check the clocks every interval, starting one interval from now
+    pub fn spawn(self, exchanges: Vec<ExchangeType>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = time::interval_at(time::Instant::now() + self.interval, self.interval);
+            loop {
+                ticker.tick().await;
+                self.check_all(&exchanges).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets_from_server_time_responses() {
+        // Request sent at 1_700_000_000_000 and answered 40ms later
+        let (sent, received) = (1_700_000_000_000, 1_700_000_000_040);
+
+        let binance: Value = serde_json::from_str(r#"{"serverTime":1700000000270}"#).unwrap();
+        let server = parse_binance_time(&binance).unwrap();
+        assert_eq!(clock_offset_ms(server, sent, received), 250);
+
+        let okx: Value = serde_json::from_str(r#"{"code":"0","msg":"","data":[{"ts":"1699999999520"}]}"#).unwrap();
+        let server = parse_okx_time(&okx).unwrap();
+        assert_eq!(clock_offset_ms(server, sent, received), -500);
+
+        let failed: Value = serde_json::from_str(r#"{"code":"50001","msg":"Service unavailable","data":[]}"#).unwrap();
+        assert!(matches!(parse_okx_time(&failed), Err(GatewayError::Parse(_))));
+        assert!(parse_binance_time(&Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_supported_exchanges() {
+        assert!(supports_time_sync(ExchangeType::Binance));
+        assert!(supports_time_sync(ExchangeType::Okx));
+        assert!(!supports_time_sync(ExchangeType::Kucoin));
+    }
+}
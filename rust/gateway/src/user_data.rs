@@ -0,0 +1,102 @@
+//! Account events from authenticated user data streams
+//!
+//! These are order and balance updates for the gateway's own account, kept
+//! apart from `MarketEvent` so market consumers never see private data.
+
+use crate::exchange::{ExchangeType, Side};
+use serde::{Deserialize, Serialize};
+
+/// Order state change: placement, fill, cancel or expiry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderUpdate {
+    pub exchange: ExchangeType,
+    pub symbol: String,
+    pub order_id: u64,
+    pub client_order_id: String,
+    pub side: Side,
+    /// Order type as sent by the exchange (`LIMIT`, `MARKET`, ...)
+    pub order_type: String,
+    pub time_in_force: String,
+    pub price: f64,
+    pub quantity: f64,
+    /// What happened to the order (`NEW`, `TRADE`, `CANCELED`, ...)
+    pub execution_type: String,
+    /// Order status after this update (`NEW`, `PARTIALLY_FILLED`, `FILLED`, ...)
+    pub status: String,
+    /// Price and quantity of the fill in this update, zero if none
+    pub last_filled_price: f64,
+    pub last_filled_quantity: f64,
+    /// Cumulative filled quantity and its average price
+    pub filled_quantity: f64,
+    pub average_price: f64,
+    pub commission: f64,
+    pub commission_asset: Option<String>,
+    pub realized_pnl: f64,
+    pub trade_id: u64,
+    pub reduce_only: bool,
+    /// Exchange transaction time (ms)
+    pub timestamp: i64,
+    /// When the gateway parsed the event (wall-clock ms)
+    #[serde(default)]
+    pub received_at: i64,
+}
+
+/// Wallet balance of one asset after an account change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BalanceUpdate {
+    pub asset: String,
+    pub wallet_balance: f64,
+    pub cross_wallet_balance: f64,
+    /// Change excluding PnL and commission
+    pub balance_change: f64,
+}
+
+/// Position of one symbol after an account change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionUpdate {
+    pub symbol: String,
+    /// Signed size: negative for a short
+    pub amount: f64,
+    pub entry_price: f64,
+    pub unrealized_pnl: f64,
+    /// `BOTH` in one-way mode, `LONG` or `SHORT` in hedge mode
+    pub position_side: String,
+}
+
+/// Balances and positions changed by a fill, funding fee, transfer, ...
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountUpdate {
+    pub exchange: ExchangeType,
+    /// Why the account changed (`ORDER`, `FUNDING_FEE`, `DEPOSIT`, ...)
+    pub reason: String,
+    pub balances: Vec<BalanceUpdate>,
+    pub positions: Vec<PositionUpdate>,
+    /// Exchange transaction time (ms)
+    pub timestamp: i64,
+    /// When the gateway parsed the event (wall-clock ms)
+    #[serde(default)]
+    pub received_at: i64,
+}
+
+/// Event from a user data stream
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum UserDataEvent {
+    OrderUpdate(OrderUpdate),
+    AccountUpdate(AccountUpdate),
+}
+
+impl UserDataEvent {
+    pub fn exchange(&self) -> ExchangeType {
+        match self {
+            UserDataEvent::OrderUpdate(o) => o.exchange,
+            UserDataEvent::AccountUpdate(a) => a.exchange,
+        }
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        match self {
+            UserDataEvent::OrderUpdate(o) => o.timestamp,
+            UserDataEvent::AccountUpdate(a) => a.timestamp,
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! WebSocket event feed
+//!
+//! This module re-broadcasts normalized market events to downstream
+//! WebSocket clients that don't read from Redis. Each client can narrow its
+//! feed by exchange, symbol and data type with a subscribe message.
+
+use crate::exchange::MarketEvent;
+use crate::error::Result;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, error, info, warn};
+
+/// Events buffered per client; a client further behind than this misses events
+pub const DEFAULT_CLIENT_BUFFER: usize = 4096;
+
+/// An event serialized once and shared by every client
+#[derive(Debug)]
+struct Frame {
+    exchange: String,
+    symbol: String,
+    data_type: &'static str,
+    json: String,
+}
+
+/// Events a client wants; an empty set matches everything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct ClientFilter {
+    exchanges: BTreeSet<String>,
+    symbols: BTreeSet<String>,
+    types: BTreeSet<String>,
+}
+
+impl ClientFilter {
+    /// Lowercase exchanges and uppercase symbols to match the event fields
+    fn normalized(self) -> Self {
+        Self {
+            exchanges: self.exchanges.into_iter().map(|e| e.to_lowercase()).collect(),
+            symbols: self.symbols.into_iter().map(|s| s.to_uppercase()).collect(),
+            types: self.types,
+        }
+    }
+
+    fn matches(&self, frame: &Frame) -> bool {
+        (self.exchanges.is_empty() || self.exchanges.contains(&frame.exchange))
+            && (self.symbols.is_empty() || self.symbols.contains(&frame.symbol))
+            && (self.types.is_empty() || self.types.contains(frame.data_type))
+    }
+}
+
+/// Message a client sends, e.g. `{"op":"subscribe","symbols":["BTCUSDT"],"types":["aggTrade"]}`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum ClientRequest {
+    /// Replace the client's filter
+    Subscribe(ClientFilter),
+}
+
+/// Fans events out to every connected WebSocket client
+#[derive(Debug, Clone)]
+pub struct WsServer {
+    tx: broadcast::Sender<Arc<Frame>>,
+}
+
+impl Default for WsServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WsServer {
+    /// Create a server buffering the default number of events per client
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(DEFAULT_CLIENT_BUFFER);
+        Self { tx }
+    }
+
+    /// Buffer this many events per client before a slow client starts missing them
+    pub fn with_buffer(mut self, events: usize) -> Self {
+        self.tx = broadcast::channel(events.max(1)).0;
+        self
+    }
+
+    /// Clients currently connected
+    pub fn client_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    /// Send an event to every client whose filter matches it
+    pub fn publish(&self, event: &MarketEvent) -> Result<()> {
+        if self.tx.receiver_count() == 0 {
+            return Ok(());
+        }
+
+        let frame = Frame {
+            exchange: event.exchange().to_string(),
+            symbol: event.symbol().to_string(),
+            data_type: event.event_type().as_str(),
+            json: serde_json::to_string(event)?,
+        };
+        // Fails only if every client disconnected in the meantime
+        let _ = self.tx.send(Arc::new(frame));
+        Ok(())
+    }
+}
+
+/// Forward matching events to one client until it disconnects
+async fn handle_client(stream: TcpStream, addr: SocketAddr, mut rx: broadcast::Receiver<Arc<Frame>>) -> Result<()> {
+    let ws = tokio_tungstenite::accept_async(stream).await?;
+    info!("WebSocket client {} connected", addr);
+
+    let (mut sink, mut source) = ws.split();
+    let mut filter = ClientFilter::default();
+
+    loop {
+        tokio::select! {
+            frame = rx.recv() => match frame {
+                Ok(frame) => {
+                    if filter.matches(&frame) {
+                        sink.send(Message::Text(frame.json.clone())).await?;
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("WebSocket client {} fell behind and missed {} events", addr, missed);
+                }
+                Err(RecvError::Closed) => break,
+            },
+
+            msg = source.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let reply = match serde_json::from_str::<ClientRequest>(&text) {
+                        Ok(ClientRequest::Subscribe(requested)) => {
+                            filter = requested.normalized();
+                            debug!("WebSocket client {} subscribed with {:?}", addr, filter);
+                            json!({ "event": "subscribed", "filter": filter })
+                        }
+                        Err(e) => json!({ "event": "error", "message": e.to_string() }),
+                    };
+                    sink.send(Message::Text(reply.to_string())).await?;
+                }
+                Some(Ok(Message::Ping(payload))) => sink.send(Message::Pong(payload)).await?,
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+            },
+        }
+    }
+
+    info!("WebSocket client {} disconnected", addr);
+    Ok(())
+}
+
+/// Accept clients on an already-bound listener
+pub fn spawn_server(server: WsServer, listener: TcpListener) -> Result<JoinHandle<()>> {
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    info!("Serving market events on ws://{}", listener.local_addr()?);
+
+    Ok(tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    let rx = server.tx.subscribe();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_client(stream, addr, rx).await {
+                            debug!("WebSocket client {} dropped: {}", addr, e);
+                        }
+                    });
+                }
+                Err(e) => error!("Failed to accept WebSocket client: {}", e),
+            }
+        }
+    }))
+}
+
+/// Accept clients on all interfaces at `port`
+pub fn serve(server: WsServer, port: u16) -> Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port)))?;
+    spawn_server(server, listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{AggTrade, BookTicker, ExchangeType};
+    use crate::testing;
+    use std::time::Duration;
+    use tokio::time;
+
+    fn trade(symbol: &str, trade_id: u64) -> MarketEvent {
+        MarketEvent::AggTrade(AggTrade {
+            symbol: symbol.to_string(),
+            timestamp: 1_700_000_000_000,
+            ..testing::trade(ExchangeType::Binance, trade_id)
+        })
+    }
+
+    async fn next_json<S>(client: &mut S) -> serde_json::Value
+    where
+        S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        let msg = time::timeout(Duration::from_secs(5), client.next()).await.unwrap().unwrap().unwrap();
+        serde_json::from_str(msg.to_text().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_filter_matches_requested_streams() {
+        let filter: ClientFilter = serde_json::from_str(r#"{"symbols":["btcusdt"],"types":["aggTrade"]}"#).unwrap();
+        let filter = filter.normalized();
+        let frame = |symbol: &str, data_type| Frame {
+            exchange: "okx".to_string(),
+            symbol: symbol.to_string(),
+            data_type,
+            json: String::new(),
+        };
+
+        assert!(filter.matches(&frame("BTCUSDT", "aggTrade")));
+        assert!(!filter.matches(&frame("BTCUSDT", "bookTicker")));
+        assert!(!filter.matches(&frame("ETHUSDT", "aggTrade")));
+        assert!(ClientFilter::default().matches(&frame("ETHUSDT", "depth")));
+    }
+
+    #[tokio::test]
+    async fn test_client_receives_subscribed_events() {
+        let server = WsServer::new();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = spawn_server(server.clone(), listener).unwrap();
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap();
+        let subscribe = json!({ "op": "subscribe", "symbols": ["BTCUSDT"], "types": ["aggTrade"] });
+        client.send(Message::Text(subscribe.to_string())).await.unwrap();
+
+        assert_eq!(next_json(&mut client).await["event"], "subscribed");
+
+        server.publish(&trade("ETHUSDT", 1)).unwrap();
+        server.publish(&MarketEvent::BookTicker(BookTicker {
+            exchange: ExchangeType::Binance,
+            symbol: "BTCUSDT".to_string(),
+            bid_price: 49999.0,
+            bid_qty: 1.0,
+            ask_price: 50001.0,
+            ask_qty: 1.0,
+            timestamp: 1_700_000_000_000,
+            received_at: 0,
+        })).unwrap();
+        server.publish(&trade("BTCUSDT", 2)).unwrap();
+
+        // Only the BTCUSDT trade passes the filter
+        let event: MarketEvent = serde_json::from_value(next_json(&mut client).await).unwrap();
+        assert_eq!(event, trade("BTCUSDT", 2));
+        assert_eq!(server.client_count(), 1);
+
+        handle.abort();
+    }
+}
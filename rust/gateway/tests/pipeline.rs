@@ -0,0 +1,154 @@
+//! End-to-end run of the embedded event path: mock exchanges publishing to
+//! Redis as the clients do, through the manager's tasks and `next_event`
+//! into a sink, without network access or Redis.
+
+use flash_arb_gateway::exchange::{AggTrade, BookTicker, Kline, KlineInterval};
+use flash_arb_gateway::testing::{self, MockExchange, MockRedisConnection, VecSink};
+use flash_arb_gateway::{
+    EventSink, Exchange, ExchangeType, GatewayManager, MarketEvent, RedisConfig, ReconnectPolicy, Subscription,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time;
+
+fn trade(exchange: ExchangeType, trade_id: u64) -> MarketEvent {
+    MarketEvent::AggTrade(AggTrade {
+        price: 50000.0 + trade_id as f64,
+        timestamp: 1_700_000_000_000 + trade_id as i64,
+        ..testing::trade(exchange, trade_id)
+    })
+}
+
+fn kline(exchange: ExchangeType) -> MarketEvent {
+    MarketEvent::Kline(Kline {
+        exchange,
+        symbol: "BTCUSDT".to_string(),
+        interval: "1m".to_string(),
+        open_time: 1_700_000_000_000,
+        close_time: 1_700_000_059_999,
+        open: 50000.0,
+        high: 50010.0,
+        low: 49990.0,
+        close: 50005.0,
+        volume: 12.5,
+        is_closed: true,
+        contract_type: None,
+        received_at: 0,
+    })
+}
+
+fn book_ticker(exchange: ExchangeType) -> MarketEvent {
+    MarketEvent::BookTicker(BookTicker {
+        exchange,
+        symbol: "BTCUSDT".to_string(),
+        bid_price: 50000.0,
+        bid_qty: 1.0,
+        ask_price: 50000.5,
+        ask_qty: 2.0,
+        timestamp: 1_700_000_000_000,
+        received_at: 0,
+    })
+}
+
+fn contains(haystack: &[u8], needle: &str) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle.as_bytes())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_mock_exchanges_reach_redis_and_the_sink_in_order() {
+    // Each exchange publishes to Redis itself, as the clients do; the sink gets what next_event hands out
+    let redis = MockRedisConnection::default();
+    let publisher = redis.publisher(RedisConfig::default()).await.unwrap();
+    // Binance drops its connection mid-script; the events after the drop arrive over the reconnect
+    let binance = MockExchange::new(ExchangeType::Binance)
+        .with_event(trade(ExchangeType::Binance, 1))
+        .with_event(kline(ExchangeType::Binance))
+        .with_disconnect()
+        .with_event(trade(ExchangeType::Binance, 2))
+        .with_redis_publisher(publisher.clone());
+    let okx = MockExchange::new(ExchangeType::Okx)
+        .with_event(book_ticker(ExchangeType::Okx))
+        .with_control_frame()
+        .with_event(trade(ExchangeType::Okx, 1))
+        .with_redis_publisher(publisher);
+    let (binance_calls, okx_calls) = (binance.calls(), okx.calls());
+    let expected = HashMap::from([
+        (ExchangeType::Binance, vec![trade(ExchangeType::Binance, 1), kline(ExchangeType::Binance), trade(ExchangeType::Binance, 2)]),
+        (ExchangeType::Okx, vec![book_ticker(ExchangeType::Okx), trade(ExchangeType::Okx, 1)]),
+    ]);
+
+    let exchanges: HashMap<ExchangeType, Box<dyn Exchange>> = HashMap::from([
+        (ExchangeType::Binance, Box::new(binance) as Box<dyn Exchange>),
+        (ExchangeType::Okx, Box::new(okx) as Box<dyn Exchange>),
+    ]);
+    let mut manager = GatewayManager::new(exchanges).with_reconnect_policy(
+        ReconnectPolicy::new(Duration::from_millis(100), Duration::from_secs(1)).with_jitter(0.0)
+    );
+    manager.connect_all().await.unwrap();
+    let subscriptions = vec![
+        Subscription::agg_trade("BTCUSDT"),
+        Subscription::kline("BTCUSDT", KlineInterval::OneMinute),
+        Subscription::book_ticker("BTCUSDT"),
+    ];
+    manager.subscribe_all(&HashMap::from([
+        (ExchangeType::Binance, subscriptions.clone()),
+        (ExchangeType::Okx, subscriptions),
+    ])).await;
+
+    let mut collected = VecSink::default();
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let handles = manager.start(shutdown_rx);
+
+    for _ in 0..5 {
+        let event = time::timeout(Duration::from_secs(10), manager.next_event())
+            .await
+            .expect("pipeline stalled")
+            .expect("exchange tasks stopped");
+        collected.publish_event(&event).await.unwrap();
+    }
+    // Both scripts are drained, so nothing else comes through
+    assert!(time::timeout(Duration::from_secs(1), manager.next_event()).await.is_err());
+
+    shutdown_tx.send(true).unwrap();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    // The two exchanges interleave, but each one's events keep their order
+    let events = collected.events();
+    assert_eq!(events.len(), 5);
+    for (exchange, expected) in &expected {
+        let received: Vec<_> = events.iter().filter(|event| event.exchange() == *exchange).cloned().collect();
+        assert_eq!(&received, expected, "{} events out of order", exchange);
+    }
+
+    // Binance reconnected once and resubscribed; OKX stayed up
+    assert_eq!((binance_calls.counts().connect, binance_calls.counts().resubscribe), (2, 1));
+    assert_eq!((okx_calls.counts().connect, okx_calls.counts().resubscribe), (1, 0));
+    assert_eq!((binance_calls.counts().disconnect, okx_calls.counts().disconnect), (1, 1));
+
+    // Redis got one publish per event, each exchange's in order, each on its type's channel
+    let pipelines = redis.pipelines();
+    assert_eq!(pipelines.len(), events.len());
+    for expected in expected.values() {
+        let published: Vec<usize> = expected
+            .iter()
+            .map(|event| {
+                let json = serde_json::to_string(event).unwrap();
+                pipelines.iter().position(|pipeline| contains(pipeline, &json)).expect("event not published")
+            })
+            .collect();
+        assert!(published.windows(2).all(|pair| pair[0] < pair[1]));
+
+        for (&i, event) in published.iter().zip(expected) {
+            let channel = match event {
+                MarketEvent::AggTrade(_) => "flash_arb:tick",
+                MarketEvent::Kline(_) => "flash_arb:kline",
+                MarketEvent::BookTicker(_) => "flash_arb:ticker",
+                other => panic!("Unexpected event {:?}", other),
+            };
+            assert!(contains(&pipelines[i], channel), "{:?} not published to {}", event, channel);
+        }
+    }
+}